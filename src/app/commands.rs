@@ -0,0 +1,876 @@
+//! The subcommands: `ask`, `history`, `report`, `models`, `config`, `cache`,
+//! `cleanup`, `drive`, and `manpage`.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use clap::CommandFactory;
+use crossterm::style::Stylize;
+use serde_json::{json, Map, Value};
+
+use super::{cost_estimator, expand_tilde, generation_flags, recording, run_keys, run_primary};
+use crate::cli::{self, ProgressMode};
+use crate::conversion::LatexConverter;
+use crate::providers::replay::Recording;
+use crate::quota::{QuotaConfig, QuotaMonitor};
+use crate::{
+    cache, config, cost, history, ingest, models, providers, quota, telemetry, templates,
+    transcripts, utils,
+};
+
+/// `recapit ask`: reuse (or produce) a transcript of `source`, then answer `question` from it.
+pub(super) async fn run_ask(
+    cli: cli::Cli,
+    source: &str,
+    question: &str,
+    refresh: bool,
+    cache_context: bool,
+) -> anyhow::Result<()> {
+    let cached = if refresh {
+        None
+    } else {
+        transcripts::TranscriptIndex::load().lookup(source).cloned()
+    };
+    let entry = match cached {
+        Some(entry) => {
+            if !cli.quiet {
+                eprintln!("using transcript {}", entry.path.display());
+            }
+            entry
+        }
+        None => {
+            let mut transcribe = cli.clone();
+            transcribe.cmd = None;
+            transcribe.source = vec![source.to_string()];
+            transcribe.quiet = true;
+            transcribe.progress = Some(ProgressMode::None);
+            Box::pin(run_primary(transcribe)).await?;
+            transcripts::TranscriptIndex::load()
+                .lookup(source)
+                .cloned()
+                .ok_or_else(|| anyhow!("transcription of {source} produced no output"))?
+        }
+    };
+    let transcript = fs::read_to_string(&entry.path)
+        .with_context(|| format!("reading {}", entry.path.display()))?;
+
+    let mut cfg =
+        config::AppConfig::load_with_profile(cli.config.as_deref(), cli.profile.as_deref())?;
+    if let Some(path) = &cli.api_key_file {
+        cfg.key_source.file = Some(path.clone());
+    }
+    let model = cli
+        .model
+        .clone()
+        .unwrap_or_else(|| cfg.default_model.clone());
+    let request_limits = crate::constants::rate_limits_per_minute()
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    let token_limits = crate::constants::token_limits_per_minute()
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    let quota = QuotaMonitor::new(QuotaConfig::new(request_limits, token_limits))
+        .with_state_file(quota::default_state_path());
+    let generation = cfg.generation.merged(&generation_flags(&cli));
+    generation.validate()?;
+    let recording = recording(&cli)?;
+    let converter = LatexConverter::new(
+        run_keys(&cfg, recording.as_ref().is_some_and(Recording::is_replay))?,
+        telemetry::RunMonitor::new(),
+        Some(quota),
+    )?
+    .with_generation(generation)
+    .with_safety(cfg.safety.clone())
+    .with_context_cache(crate::providers::context_cache::ContextCacheConfig {
+        enabled: cfg.context_cache.enabled || cache_context,
+        ..cfg.context_cache.clone()
+    })
+    .with_recording(recording);
+    let prompt = templates::TemplateLoader::new(cfg.templates_dir.clone()).ask_prompt();
+    let mut metadata = Map::new();
+    metadata.insert("source".into(), Value::String(source.to_string()));
+    metadata.insert(
+        "transcript".into(),
+        Value::String(entry.path.to_string_lossy().to_string()),
+    );
+    let answer = converter
+        .answer(&model, &prompt, &transcript, question, metadata)
+        .await?;
+    println!("{}", answer.trim_end());
+    Ok(())
+}
+
+/// The point `--since AGE` reaches back to.
+pub(super) fn since_cutoff(
+    since: Option<std::time::Duration>,
+) -> anyhow::Result<Option<time::OffsetDateTime>> {
+    since
+        .map(|age| Ok(time::OffsetDateTime::now_utc() - time::Duration::try_from(age)?))
+        .transpose()
+}
+
+/// The configured display currency, for reports that do not otherwise need the
+/// config; a config that does not load leaves them in dollars.
+pub(super) fn display_currency(cli: &cli::Cli) -> cost::Currency {
+    config::AppConfig::load_with_profile(cli.config.as_deref(), cli.profile.as_deref())
+        .map(|cfg| cfg.currency)
+        .unwrap_or_default()
+}
+
+/// `recapit history`: past jobs, oldest first.
+pub(super) fn run_history(
+    since: Option<std::time::Duration>,
+    json_output: bool,
+    currency: &cost::Currency,
+) -> anyhow::Result<()> {
+    let entries = history::load(since_cutoff(since)?)?;
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+    if entries.is_empty() {
+        println!(
+            "No jobs recorded yet in {}",
+            history::history_path().display()
+        );
+        return Ok(());
+    }
+    for entry in &entries {
+        // Pad before styling; the escape codes would throw off the width.
+        let status = match entry.status.as_str() {
+            "succeeded" => format!("{:<9}", "ok").green(),
+            "cancelled" => format!("{:<9}", "cancelled").yellow(),
+            other => format!("{other:<9}").red(),
+        };
+        println!(
+            "{}  {} {:<10} {:>9} tok {:>7.1}s  {}  {}",
+            entry
+                .finished_at
+                .get(..16)
+                .unwrap_or(&entry.finished_at)
+                .replace('T', " "),
+            status,
+            currency.format(entry.cost_usd, 4),
+            entry.input_tokens + entry.output_tokens,
+            entry.duration_seconds,
+            entry.model.as_str().magenta(),
+            entry.source,
+        );
+        if let Some(output) = &entry.output {
+            println!("{:>18}-> {}", "", output.display());
+        }
+        if let Some(error) = &entry.error {
+            println!("{:>18}{}", "", error.as_str().dim());
+        }
+    }
+    let totals = history::aggregate(&entries);
+    println!(
+        "\n{} jobs ({} not succeeded) · est cost {}",
+        totals.jobs,
+        totals.failed,
+        currency.format(totals.cost_usd, 4)
+    );
+    Ok(())
+}
+
+/// `recapit report cost --aggregate`: totals over the run history.
+pub(super) fn run_report_aggregate(
+    since: Option<std::time::Duration>,
+    json_output: bool,
+    currency: &cost::Currency,
+) -> anyhow::Result<()> {
+    let entries = history::load(since_cutoff(since)?)?;
+    let totals = history::aggregate(&entries);
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&totals)?);
+        return Ok(());
+    }
+    println!("{}", "Recapit Cost Report (history)".bold());
+    if let (Some(first), Some(last)) = (entries.first(), entries.last()) {
+        println!(
+            "Jobs:   {} ({} not succeeded), {} to {}",
+            totals.jobs,
+            totals.failed,
+            first.finished_at.get(..10).unwrap_or_default(),
+            last.finished_at.get(..10).unwrap_or_default()
+        );
+    } else {
+        println!("Jobs:   0");
+    }
+    println!(
+        "Total cost: {}",
+        currency.format(totals.cost_usd, 4).green().bold()
+    );
+    println!("Requests: {}", totals.requests);
+    println!(
+        "Tokens: input {} | output {}",
+        totals.input_tokens, totals.output_tokens
+    );
+    if !totals.by_model.is_empty() {
+        println!("\n{}", "Per-model cost:".bold());
+        for (name, usage) in &totals.by_model {
+            println!(
+                "  {} -> {}, tokens in {}, out {}",
+                name.as_str().magenta(),
+                currency.format(usage.total_cost, 4),
+                usage.input_tokens,
+                usage.output_tokens
+            );
+        }
+    }
+    Ok(())
+}
+
+pub(super) fn run_report_cost(
+    path: &Path,
+    json_output: bool,
+    per_chunk: bool,
+    currency: &cost::Currency,
+) -> anyhow::Result<()> {
+    let text = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    if json_output && !per_chunk {
+        println!("{}", text);
+        return Ok(());
+    }
+    let summary: Value =
+        serde_json::from_str(&text).with_context(|| format!("parsing {}", path.display()))?;
+    let chunks = summary
+        .get("chunks")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&chunks)?);
+        return Ok(());
+    }
+
+    let job = summary
+        .get("job")
+        .and_then(|v| v.as_object())
+        .cloned()
+        .unwrap_or_default();
+    let source = job
+        .get("source")
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown");
+    let model = job
+        .get("model")
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown");
+    let kind = job
+        .get("kind")
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown");
+
+    println!("{}", "Recapit Cost Report".bold());
+    println!("Source: {}", source.cyan());
+    println!("Kind:   {}", kind.cyan());
+    println!("Model:  {}", model.cyan());
+
+    let totals = summary
+        .get("totals")
+        .and_then(|v| v.as_object())
+        .cloned()
+        .unwrap_or_default();
+    let total_cost = totals
+        .get("est_cost_usd")
+        .and_then(|v| v.as_f64())
+        .unwrap_or(0.0);
+    let total_requests = totals.get("requests").and_then(|v| v.as_u64()).unwrap_or(0);
+    let total_input_tokens = totals
+        .get("input_tokens")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+    let total_output_tokens = totals
+        .get("output_tokens")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+
+    println!(
+        "Total cost: {}",
+        currency.format(total_cost, 4).green().bold()
+    );
+    println!("Requests: {}", total_requests);
+    println!(
+        "Tokens: input {} | output {}",
+        total_input_tokens, total_output_tokens
+    );
+    let cached_tokens = totals
+        .get("cached_input_tokens")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+    if cached_tokens > 0 {
+        let cache_cost = totals
+            .get("cache_read_cost_usd")
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0);
+        println!(
+            "Cached input: {} tokens, {}",
+            cached_tokens,
+            currency.format(cache_cost, 4)
+        );
+    }
+
+    if let Some(by_model) = summary.get("by_model").and_then(|v| v.as_object()) {
+        if !by_model.is_empty() {
+            println!("\n{}", "Per-model usage:".bold());
+            for (name, data) in by_model {
+                let requests = data.get("requests").and_then(|v| v.as_u64()).unwrap_or(0);
+                let tokens_in = data
+                    .get("input_tokens")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0);
+                let tokens_out = data
+                    .get("output_tokens")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0);
+                println!(
+                    "  {} -> requests {}, tokens in {}, out {}",
+                    name.as_str().magenta(),
+                    requests,
+                    tokens_in,
+                    tokens_out
+                );
+            }
+        }
+    }
+
+    if per_chunk {
+        print_chunk_costs(&chunks, currency);
+    }
+
+    if let Some(notes) = summary.get("notes").and_then(|v| v.as_array()) {
+        println!("\n{}", "Notes:".bold());
+        println!("  total: {}", notes.len());
+        for note in notes.iter().take(5) {
+            if let Some(name) = note.get("name").and_then(|v| v.as_str()) {
+                println!("  - {}", name);
+            }
+        }
+        if notes.len() > 5 {
+            println!("  ... {} more", notes.len() - 5);
+        }
+    }
+
+    Ok(())
+}
+
+pub(super) fn print_chunk_costs(chunks: &[Value], currency: &cost::Currency) {
+    println!("\n{}", "Per-chunk cost:".bold());
+    if chunks.is_empty() {
+        println!("  (no chunked requests in this summary)");
+        return;
+    }
+    println!(
+        "  {:>5}  {:>4}  {:>9}  {:>9}  {:>10}  {:>9}  {:>7}  {:>9}",
+        "chunk", "reqs", "tokens in", "out", "cost", "upload", "retries", "latency"
+    );
+    let number = |chunk: &Value, key: &str| chunk.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+    for chunk in chunks {
+        let cost = chunk
+            .get("cost_usd")
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0);
+        let upload = match number(chunk, "upload_bytes") {
+            0 => "-".to_string(),
+            bytes => crate::cache::format_size(bytes),
+        };
+        println!(
+            "  {:>5}  {:>4}  {:>9}  {:>9}  {:>10}  {:>9}  {:>7}  {:>8.1}s",
+            number(chunk, "chunk_index"),
+            number(chunk, "requests"),
+            number(chunk, "input_tokens"),
+            number(chunk, "output_tokens"),
+            currency.format(cost, 4),
+            upload,
+            number(chunk, "retries"),
+            number(chunk, "latency_ms") as f64 / 1000.0,
+        );
+    }
+}
+
+pub(super) fn run_manpage(dir: Option<&Path>) -> anyhow::Result<()> {
+    let command = cli::Cli::command();
+    let Some(dir) = dir else {
+        clap_mangen::Man::new(command).render(&mut std::io::stdout())?;
+        return Ok(());
+    };
+    utils::ensure_dir(dir)?;
+    clap_mangen::generate_to(command, dir)
+        .with_context(|| format!("writing man pages to {}", dir.display()))?;
+    println!("Wrote man pages to {}", dir.display());
+    Ok(())
+}
+
+pub(super) fn run_config_init(path: &Path, force: bool) -> anyhow::Result<()> {
+    if path.exists() && !force {
+        anyhow::bail!(
+            "{} already exists; pass --force to replace it",
+            path.display()
+        );
+    }
+    fs::write(path, config::STARTER_CONFIG)
+        .with_context(|| format!("writing {}", path.display()))?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+pub(super) fn run_config_show(cli: &cli::Cli, json_output: bool) -> anyhow::Result<()> {
+    let mut cfg =
+        config::AppConfig::load_with_profile(cli.config.as_deref(), cli.profile.as_deref())?;
+    if let Some(path) = &cli.api_key_file {
+        cfg.key_source.file = Some(path.clone());
+    }
+    let mut effective = cfg.effective();
+    let overrides = config::env_overrides(&cfg);
+    if json_output {
+        effective["environment"] = overrides
+            .into_iter()
+            .map(|(name, value)| (name, Value::String(value)))
+            .collect::<Map<_, _>>()
+            .into();
+        println!("{}", serde_json::to_string_pretty(&effective)?);
+        return Ok(());
+    }
+    match &cfg.config_path {
+        Some(path) => println!("# Config file: {}", path.display()),
+        None => println!("# No recapit.yaml found; showing built-in defaults"),
+    }
+    if let Some(object) = effective.as_object_mut() {
+        object.remove("config_file");
+    }
+    print!("{}", serde_yaml::to_string(&effective)?);
+    if !overrides.is_empty() {
+        println!("# Environment overrides:");
+        for (name, value) in overrides {
+            println!("#   {name}={value}");
+        }
+    }
+    Ok(())
+}
+
+pub(super) fn run_config_validate(cli: &cli::Cli) -> anyhow::Result<()> {
+    let path = config::resolve_config_path(cli.config.as_deref())?.ok_or_else(|| {
+        anyhow!("No recapit.yaml found; pass --config, set RECAPIT_CONFIG, or run `recapit config init`")
+    })?;
+    let problems = config::validate_config(&path);
+    if problems.is_empty() {
+        println!("{} is valid", path.display());
+        return Ok(());
+    }
+    for problem in &problems {
+        eprintln!("  - {problem}");
+    }
+    anyhow::bail!("{} problem(s) in {}", problems.len(), path.display())
+}
+
+pub(super) async fn run_models(
+    cli: &cli::Cli,
+    json_output: bool,
+    all: bool,
+    local: bool,
+) -> anyhow::Result<()> {
+    let mut cfg =
+        config::AppConfig::load_with_profile(cli.config.as_deref(), cli.profile.as_deref())?;
+    if let Some(path) = &cli.api_key_file {
+        cfg.key_source.file = Some(path.clone());
+    }
+    let listing = if local {
+        None
+    } else {
+        match cfg.api_keys() {
+            Ok(keys) => models::ModelCache::refresh(keys.primary(), cli.refresh_models).await,
+            Err(err) => {
+                eprintln!(
+                    "{} could not list models ({err:#}); showing the cached listing or built-in tables",
+                    "warning:".yellow()
+                );
+                models::ModelCache::load()
+            }
+        }
+    };
+    let listed = listing.as_ref().map(|listing| listing.models.clone());
+    let pricing = cost_estimator(&cfg).await?;
+    let models = crate::models::catalog(listed.as_deref(), &pricing, all);
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&models)?);
+        return Ok(());
+    }
+
+    let dash = || "-".to_string();
+    let header = [
+        "MODEL",
+        "CONTEXT",
+        "OUTPUT",
+        "MODALITIES",
+        "RPM",
+        "TPM",
+        "$/1M IN",
+        "$/1M OUT",
+    ];
+    let rows: Vec<[String; 8]> = models
+        .iter()
+        .map(|model| {
+            let marker = if model.is_default { " *" } else { "" };
+            let rates = model.pricing.as_ref().map(|pricing| &pricing.text);
+            [
+                format!("{}{marker}", model.name),
+                model.input_token_limit.map_or_else(dash, |n| n.to_string()),
+                model
+                    .output_token_limit
+                    .map_or_else(dash, |n| n.to_string()),
+                if model.modalities.is_empty() {
+                    dash()
+                } else {
+                    model.modalities.join(",")
+                },
+                model
+                    .requests_per_minute
+                    .map_or_else(dash, |n| n.to_string()),
+                model.tokens_per_minute.map_or_else(dash, |n| n.to_string()),
+                rates.map_or_else(dash, |pair| format!("{:.2}", pair.input)),
+                rates.map_or_else(dash, |pair| format!("{:.2}", pair.output)),
+            ]
+        })
+        .collect();
+    let widths: Vec<usize> = (0..header.len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].len())
+                .chain([header[column].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    println!("{}", line(header.to_vec()).bold());
+    for row in &rows {
+        println!("{}", line(row.iter().map(String::as_str).collect()));
+    }
+    println!();
+    println!("* default model. RPM/TPM are the per-minute limits recapit paces requests to; prices are text rates in USD.");
+    match &listing {
+        Some(listing) => {
+            let fetched = time::OffsetDateTime::from_unix_timestamp(listing.fetched_at)
+                .map(|at| at.date().to_string())
+                .unwrap_or_default();
+            println!("Listing fetched {fetched}; --refresh-models fetches it again.");
+        }
+        None => println!("Context sizes come from the models endpoint, which was not queried."),
+    }
+    Ok(())
+}
+
+pub(super) fn run_cleanup_cache(
+    dry_run: bool,
+    yes: bool,
+    transcripts_only: bool,
+) -> anyhow::Result<()> {
+    let Some(mut base) = dirs::cache_dir() else {
+        println!("No cache directory available on this platform.");
+        return Ok(());
+    };
+    base = if transcripts_only {
+        cache::cache_root()
+    } else {
+        base.join("recapit")
+    };
+    if !base.exists() {
+        println!("Cache directory not found: {}", base.display());
+        return Ok(());
+    }
+    if !yes && !dry_run {
+        anyhow::bail!(
+            "Refusing to remove {}; pass --yes to confirm",
+            base.display()
+        );
+    }
+    if dry_run {
+        println!("Would remove {}", base.display());
+    } else {
+        fs::remove_dir_all(&base)?;
+        println!("Removed {}", base.display());
+    }
+    Ok(())
+}
+
+/// `recapit cleanup cache --older-than/--max-size`: evict entries across every cache
+/// instead of removing the whole folder.
+pub(super) fn run_trim_cache(
+    older_than: Option<std::time::Duration>,
+    max_size: Option<u64>,
+    dry_run: bool,
+    yes: bool,
+    transcripts_only: bool,
+) -> anyhow::Result<()> {
+    let manager = if transcripts_only {
+        cache::CacheManager::responses()
+    } else {
+        cache::CacheManager::default()
+    };
+    let plan = manager.plan(older_than, max_size);
+    let bytes: u64 = plan.iter().map(|entry| entry.bytes).sum();
+    if plan.is_empty() {
+        println!("Nothing to remove.");
+        return Ok(());
+    }
+    if dry_run {
+        for entry in &plan {
+            println!(
+                "Would remove {} ({})",
+                entry.path.display(),
+                cache::format_size(entry.bytes)
+            );
+        }
+        println!(
+            "{} entries, {} in total",
+            plan.len(),
+            cache::format_size(bytes)
+        );
+        return Ok(());
+    }
+    if !yes {
+        anyhow::bail!(
+            "Refusing to remove {} cache entries ({}); pass --yes to confirm",
+            plan.len(),
+            cache::format_size(bytes)
+        );
+    }
+    let freed = manager.remove(&plan)?;
+    println!(
+        "Removed {} cache entries, freeing {}",
+        plan.len(),
+        cache::format_size(freed)
+    );
+    Ok(())
+}
+
+/// After a run, evict least recently used cache entries beyond `cache.max_size`.
+pub(super) fn trim_cache(limit: u64, quiet: bool) {
+    let manager = cache::CacheManager::default();
+    let plan = manager.plan(None, Some(limit));
+    if plan.is_empty() {
+        return;
+    }
+    match manager.remove(&plan) {
+        Ok(freed) if !quiet => eprintln!(
+            "cache over {}: removed {} least recently used entries ({})",
+            cache::format_size(limit),
+            plan.len(),
+            cache::format_size(freed)
+        ),
+        Ok(_) => {}
+        Err(err) => tracing::warn!("could not trim the cache: {err:#}"),
+    }
+}
+
+/// `recapit cache stats`: what each cache holds.
+pub(super) fn run_cache_stats(json: bool) -> anyhow::Result<()> {
+    let stats = cache::CacheManager::default().stats();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+    println!(
+        "{:<10} {:>8} {:>10} {:>7}  folder",
+        "cache", "entries", "size", "oldest"
+    );
+    for area in &stats {
+        println!(
+            "{:<10} {:>8} {:>10} {:>7}  {}",
+            area.name,
+            area.entries,
+            cache::format_size(area.bytes),
+            area.oldest_days
+                .map(|days| format!("{days}d"))
+                .unwrap_or_else(|| "-".into()),
+            area.root.display()
+        );
+    }
+    let total: u64 = stats.iter().map(|area| area.bytes).sum();
+    let entries: usize = stats.iter().map(|area| area.entries).sum();
+    println!(
+        "{:<10} {:>8} {:>10}",
+        "total",
+        entries,
+        cache::format_size(total)
+    );
+    Ok(())
+}
+
+/// `recapit cleanup remote`: list the primary key's Files API uploads and delete
+/// the ones older than `older_than` and starting with `prefix`, such as uploads
+/// leaked by a crashed run.
+pub(super) async fn run_cleanup_remote(
+    cfg: &config::AppConfig,
+    older_than: Option<std::time::Duration>,
+    prefix: Option<&str>,
+    dry_run: bool,
+    yes: bool,
+    json: bool,
+) -> anyhow::Result<()> {
+    let keys = cfg.api_keys()?;
+    let now = time::OffsetDateTime::now_utc();
+    let files: Vec<providers::uploads::RemoteFile> = providers::gemini::list_files(keys.primary())
+        .await?
+        .iter()
+        .filter_map(providers::uploads::RemoteFile::from_listing)
+        .collect();
+    let (stale, kept): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .partition(|file| file.matches(now, older_than, prefix));
+    // Printed once the deletions are done, with the files that really went.
+    let report = |deleted: &[&str]| -> anyhow::Result<()> {
+        if json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&json!({
+                    "matched": stale,
+                    "kept": kept,
+                    "deleted": deleted,
+                }))?
+            );
+        }
+        Ok(())
+    };
+    if !json {
+        if stale.is_empty() && kept.is_empty() {
+            println!("No files in the Files API for this key.");
+            return Ok(());
+        }
+        for (file, verdict) in stale
+            .iter()
+            .map(|file| (file, "delete"))
+            .chain(kept.iter().map(|file| (file, "keep")))
+        {
+            let age = file
+                .age(now)
+                .map(|age| format!("{}h", age.whole_hours()))
+                .unwrap_or_else(|| "?".into());
+            println!(
+                "{verdict:<6} {:<24} {:>10} {:>6}  {}",
+                file.name,
+                cache::format_size(file.size_bytes),
+                age,
+                file.display_name.as_deref().unwrap_or("")
+            );
+        }
+    }
+    let total: u64 = stale.iter().map(|file| file.size_bytes).sum();
+    if stale.is_empty() || dry_run {
+        report(&[])?;
+        if !json {
+            println!(
+                "{} of {} files match ({})",
+                stale.len(),
+                stale.len() + kept.len(),
+                cache::format_size(total)
+            );
+        }
+        return Ok(());
+    }
+    if !yes {
+        report(&[])?;
+        anyhow::bail!(
+            "Refusing to delete {} remote files ({}); pass --yes to confirm",
+            stale.len(),
+            cache::format_size(total)
+        );
+    }
+    let mut deleted = Vec::new();
+    for file in &stale {
+        match providers::gemini::delete_remote_file(keys.primary(), &file.name).await {
+            Ok(()) => deleted.push(file.name.as_str()),
+            Err(err) => eprintln!("{err:#}"),
+        }
+    }
+    providers::uploads::UploadIndex::forget_files(&deleted)?;
+    report(&deleted)?;
+    if !json {
+        println!("Deleted {} of {} remote files", deleted.len(), stale.len());
+    }
+    if deleted.len() < stale.len() {
+        anyhow::bail!(
+            "{} file(s) could not be deleted",
+            stale.len() - deleted.len()
+        );
+    }
+    Ok(())
+}
+
+pub(super) async fn run_drive_login(
+    cli: &cli::Cli,
+    client_secrets: Option<&Path>,
+    open_browser: bool,
+) -> anyhow::Result<()> {
+    let cfg = config::AppConfig::load_with_profile(cli.config.as_deref(), cli.profile.as_deref())?;
+    let client = match client_secrets
+        .map(expand_tilde)
+        .or(cfg.drive_client_secrets_file.clone())
+    {
+        Some(path) => ingest::OAuthClient::from_secrets_file(&path)?,
+        None => match (cfg.drive_client_id, cfg.drive_client_secret) {
+            (Some(client_id), Some(client_secret)) => ingest::OAuthClient {
+                client_id,
+                client_secret,
+            },
+            _ => anyhow::bail!(
+                "No OAuth client configured: pass --client-secrets with the JSON of a Desktop app client, or set drive.client_secrets_file (or client_id and client_secret) in recapit.yaml"
+            ),
+        },
+    };
+    // The sign-in waits on a blocking socket and uses reqwest's blocking client,
+    // neither of which may run on the async runtime.
+    let path =
+        tokio::task::spawn_blocking(move || ingest::login_drive(&client, open_browser)).await??;
+    println!(
+        "Signed in to Google Drive; token saved to {}",
+        path.display()
+    );
+    Ok(())
+}
+
+pub(super) fn run_drive_logout() -> anyhow::Result<()> {
+    if ingest::logout_drive()? {
+        println!("Removed {}", ingest::drive_token_path().display());
+    } else {
+        println!("Not signed in to Google Drive");
+    }
+    Ok(())
+}
+
+pub(super) fn run_cleanup_downloads(path: &Path, dry_run: bool, yes: bool) -> anyhow::Result<()> {
+    if !yes && !dry_run {
+        anyhow::bail!("Refusing to remove downloads without --yes confirmation");
+    }
+    let expanded = expand_tilde(path);
+    let targets = [expanded.join("downloads"), expanded.join("pickles")];
+    let mut removed_any = false;
+    for target in targets {
+        if target.exists() {
+            if dry_run {
+                println!("Would remove {}", target.display());
+            } else {
+                fs::remove_dir_all(&target)?;
+                println!("Removed {}", target.display());
+            }
+            removed_any = true;
+        }
+    }
+    if !removed_any {
+        println!("No cleanup targets found under {}", expanded.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,158 @@
+//! `--to markdown|json`: converting existing LaTeX or Markdown files instead of
+//! transcribing sources.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde_json::{Map, Value};
+
+use crate::conversion::{collect_tex_files, LatexConverter};
+use crate::providers::GenerationConfig;
+use crate::quota::{QuotaConfig, QuotaMonitor};
+use crate::{config, constants, quota, telemetry, templates};
+
+pub(super) enum ConversionKind {
+    Markdown,
+    Json,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn run_conversion(
+    source: PathBuf,
+    output_dir: Option<PathBuf>,
+    file_pattern: String,
+    skip_existing: bool,
+    model_override: Option<String>,
+    recursive: bool,
+    kind: ConversionKind,
+    generation: GenerationConfig,
+    api_key_file: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    use std::fs;
+
+    let mut cfg = config::AppConfig::load(None)?;
+    if api_key_file.is_some() {
+        cfg.key_source.file = api_key_file;
+    }
+    let generation = cfg.generation.merged(&generation);
+    generation.validate()?;
+    let loader = templates::TemplateLoader::new(cfg.templates_dir.clone());
+    let default_model = model_override
+        .or_else(|| cfg.convert_model.clone())
+        .unwrap_or_else(|| cfg.default_model.clone());
+
+    let request_limits = constants::rate_limits_per_minute()
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    let token_limits = constants::token_limits_per_minute()
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    let quota = QuotaMonitor::new(QuotaConfig::new(request_limits, token_limits))
+        .with_state_file(quota::default_state_path());
+    let monitor = telemetry::RunMonitor::new();
+    let converter = LatexConverter::new(cfg.api_keys()?, monitor, Some(quota))?
+        .with_generation(generation)
+        .with_safety(cfg.safety.clone());
+
+    let mut files = collect_tex_files(&source, &file_pattern, recursive)?;
+    if files.is_empty() && matches!(kind, ConversionKind::Json) && file_pattern == "*.tex" {
+        files = collect_tex_files(&source, "*.md", recursive)?;
+    }
+    if files.is_empty() {
+        println!("No files matched pattern {}", file_pattern);
+        return Ok(());
+    }
+
+    let prompt_markdown = loader.latex_to_md_prompt();
+    let prompt_json = loader.latex_to_json_prompt();
+    let prompt_markdown_json = loader.markdown_to_json_prompt();
+
+    for tex_file in files {
+        let content = fs::read_to_string(&tex_file)
+            .with_context(|| format!("reading {}", tex_file.display()))?;
+        let extension = tex_file
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        let mut metadata = Map::new();
+        metadata.insert(
+            "source".into(),
+            Value::String(tex_file.to_string_lossy().to_string()),
+        );
+        metadata.insert("input_extension".into(), Value::String(extension.clone()));
+
+        let output_root = output_dir
+            .clone()
+            .or_else(|| cfg.output_dir.clone())
+            .unwrap_or_else(|| tex_file.parent().unwrap_or(Path::new(".")).to_path_buf());
+        fs::create_dir_all(&output_root)?;
+
+        match kind {
+            ConversionKind::Markdown => {
+                let metadata = metadata.clone();
+                let out_path = output_root.join(format!(
+                    "{}.md",
+                    tex_file.file_stem().unwrap_or_default().to_string_lossy()
+                ));
+                if skip_existing && out_path.exists() {
+                    continue;
+                }
+                let text = converter
+                    .latex_to_markdown(&default_model, &prompt_markdown, &content, metadata)
+                    .await?;
+                let mut value = text;
+                if !value.ends_with('\n') {
+                    value.push('\n');
+                }
+                fs::write(out_path, value)?;
+            }
+            ConversionKind::Json => {
+                let metadata = metadata.clone();
+                let out_path = output_root.join(format!(
+                    "{}.json",
+                    tex_file.file_stem().unwrap_or_default().to_string_lossy()
+                ));
+                if skip_existing && out_path.exists() {
+                    continue;
+                }
+                let operation = extension.as_str();
+                let text = match operation {
+                    "tex" | "ltx" => {
+                        converter
+                            .latex_to_json(&default_model, &prompt_json, &content, metadata)
+                            .await?
+                    }
+                    "md" | "markdown" | "mdown" => {
+                        converter
+                            .markdown_to_json(
+                                &default_model,
+                                &prompt_markdown_json,
+                                &content,
+                                metadata,
+                            )
+                            .await?
+                    }
+                    _ => {
+                        println!(
+                            "Skipping {} (unsupported extension {})",
+                            tex_file.display(),
+                            extension
+                        );
+                        continue;
+                    }
+                };
+                let mut value = text;
+                if !value.ends_with('\n') {
+                    value.push('\n');
+                }
+                fs::write(out_path, value)?;
+            }
+        }
+    }
+
+    Ok(())
+}
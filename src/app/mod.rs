@@ -0,0 +1,1647 @@
+//! The `recapit` command line: argument handling, the transcribe workflow that
+//! assembles the provider, quota, and budget for each job, and the subcommands.
+
+mod commands;
+mod convert;
+mod plan;
+mod sources;
+
+use commands::{
+    display_currency, run_ask, run_cache_stats, run_cleanup_cache, run_cleanup_downloads,
+    run_cleanup_remote, run_config_init, run_config_show, run_config_validate, run_drive_login,
+    run_drive_logout, run_history, run_manpage, run_models, run_report_aggregate, run_report_cost,
+    run_trim_cache, trim_cache,
+};
+use convert::{run_conversion, ConversionKind};
+use plan::{planned_tokens_per_second, run_plan};
+use sources::{
+    expand_sources, write_batch_summary, write_mirror_index, write_playlist_summary, FolderWalk,
+};
+
+use crate::{
+    batch, cli, config, conversion, core, cost, doctor, engine, history, hooks, ingest, metrics,
+    models, progress, providers, quota, render, selection, server, telemetry, transcripts, tui,
+    utils, video, watch,
+};
+use anyhow::{anyhow, Context};
+use batch::SourceInput;
+use clap::CommandFactory;
+use cli::{
+    ChunkBoundaryArg, ConflictArg, ConversionTarget, DetailArg, GroupArg, OutputFormatArg,
+    ProgressMode, VideoModeArg,
+};
+use conversion::LatexConverter;
+use core::{Asset, Cancelled, ConflictPolicy, Detail, Grouping, Job, Kind, OutputFormat, PdfMode};
+use engine::Engine;
+use ingest::{CompositeIngestor, CompositeNormalizer, YtDlpCookies};
+use progress::{ChunkControl, Progress, ProgressScope, ProgressStage};
+use providers::gemini::GeminiProvider;
+use providers::keys::ApiKeys;
+use providers::replay::Recording;
+use providers::GenerationConfig;
+use quota::{QuotaConfig, QuotaMonitor};
+use render::writer::CompositeWriter;
+use selection::IndexSelection;
+use serde_json::{json, Value};
+use serde_yaml::Value as YamlValue;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+use tracing_subscriber::filter::{FilterExt, LevelFilter};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+use utils::slugify;
+use video::{ChunkBoundaryMode, VideoMode};
+
+/// Send log lines to stderr, or to the dashboard's log pane while it is up, and to
+/// the `--log-file` as JSON.
+pub fn init_logging(cli: &cli::Cli) {
+    // While the dashboard is up, log lines go to its log pane instead of stderr,
+    // and so do warnings that RUST_LOG would otherwise hide.
+    let log_file = open_log_file(cli);
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(|| tui::LOG.stderr_writer())
+                .with_filter(EnvFilter::from_default_env()),
+        )
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(|| tui::LOG.pane_writer())
+                .with_ansi(false)
+                .without_time()
+                .with_target(false)
+                .with_filter(EnvFilter::from_default_env().or(LevelFilter::WARN)),
+        )
+        .with(log_file.map(|file| {
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_current_span(true)
+                .with_span_list(true)
+                .with_writer(std::sync::Mutex::new(file))
+                .with_filter(
+                    EnvFilter::try_from_default_env()
+                        .unwrap_or_else(|_| EnvFilter::new("info,recapit=debug")),
+                )
+        }))
+        .init();
+}
+
+/// Run what the command line asks for: a subcommand, or transcribing its sources.
+pub async fn run(cli: cli::Cli) -> anyhow::Result<()> {
+    match &cli.cmd {
+        Some(cli::Command::Ask {
+            source,
+            question,
+            refresh,
+            cache_context,
+        }) => run_ask(cli.clone(), source, question, *refresh, *cache_context).await?,
+        Some(cli::Command::Watch {
+            dir,
+            recursive,
+            existing,
+            settle_seconds,
+            log,
+        }) => {
+            let options = watch::WatchOptions {
+                recursive: *recursive,
+                existing: *existing,
+                settle: std::time::Duration::from_secs(*settle_seconds),
+                log: log.clone(),
+            };
+            watch::run_watch(cli.clone(), dir, options).await?
+        }
+        Some(cli::Command::Serve {
+            listen,
+            max_concurrent,
+            token,
+        }) => {
+            let options = server::ServeOptions {
+                addr: *listen,
+                max_concurrent: *max_concurrent,
+                token: token.clone(),
+            };
+            server::run_server(cli.clone(), options).await?
+        }
+        Some(cli::Command::History { since, json }) => {
+            run_history(*since, *json, &display_currency(&cli))?
+        }
+        Some(cli::Command::Report { command }) => match command {
+            cli::ReportCommand::Cost {
+                aggregate: true,
+                since,
+                json,
+                ..
+            } => run_report_aggregate(*since, *json, &display_currency(&cli))?,
+            cli::ReportCommand::Cost {
+                input,
+                json,
+                per_chunk,
+                ..
+            } => run_report_cost(input, *json, *per_chunk, &display_currency(&cli))?,
+        },
+        Some(cli::Command::Cleanup { command }) => match command {
+            cli::CleanupCommand::Cache {
+                dry_run,
+                yes,
+                transcripts,
+                older_than,
+                max_size,
+            } => {
+                if older_than.is_some() || max_size.is_some() {
+                    run_trim_cache(*older_than, *max_size, *dry_run, *yes, *transcripts)?
+                } else {
+                    run_cleanup_cache(*dry_run, *yes, *transcripts)?
+                }
+            }
+            cli::CleanupCommand::Remote {
+                older_than,
+                prefix,
+                dry_run,
+                yes,
+                json,
+            } => {
+                let mut cfg = config::AppConfig::load_with_profile(
+                    cli.config.as_deref(),
+                    cli.profile.as_deref(),
+                )?;
+                if let Some(path) = &cli.api_key_file {
+                    cfg.key_source.file = Some(path.clone());
+                }
+                run_cleanup_remote(&cfg, *older_than, prefix.as_deref(), *dry_run, *yes, *json)
+                    .await?
+            }
+            cli::CleanupCommand::Downloads { path, dry_run, yes } => {
+                run_cleanup_downloads(path, *dry_run, *yes)?
+            }
+        },
+        Some(cli::Command::Cache { command }) => match command {
+            cli::CacheCommand::Stats { json } => run_cache_stats(*json)?,
+        },
+        Some(cli::Command::Drive { command }) => match command {
+            cli::DriveCommand::Login {
+                client_secrets,
+                no_browser,
+            } => run_drive_login(&cli, client_secrets.as_deref(), !*no_browser).await?,
+            cli::DriveCommand::Logout => run_drive_logout()?,
+        },
+        Some(cli::Command::Config { command }) => match command {
+            cli::ConfigCommand::Init { path, force } => run_config_init(path, *force)?,
+            cli::ConfigCommand::Show { json } => run_config_show(&cli, *json)?,
+            cli::ConfigCommand::Validate => run_config_validate(&cli)?,
+        },
+        Some(cli::Command::Models { json, all, local }) => {
+            run_models(&cli, *json, *all, *local).await?
+        }
+        Some(cli::Command::Doctor) => {
+            let mut cfg = config::AppConfig::load_with_profile(
+                cli.config.as_deref(),
+                cli.profile.as_deref(),
+            )?;
+            if let Some(path) = &cli.api_key_file {
+                cfg.key_source.file = Some(path.clone());
+            }
+            doctor::run_doctor(&cfg).await?
+        }
+        Some(cli::Command::Completions { shell }) => {
+            clap_complete::generate(
+                *shell,
+                &mut cli::Cli::command(),
+                "recapit",
+                &mut std::io::stdout(),
+            );
+        }
+        Some(cli::Command::Manpage { dir }) => run_manpage(dir.as_deref())?,
+        None => run_primary(cli).await?,
+    }
+
+    Ok(())
+}
+
+/// The `--log-file` (or `log_file` from recapit.yaml) opened for appending. A file
+/// that cannot be opened is reported and the run goes on without it.
+fn open_log_file(cli: &cli::Cli) -> Option<fs::File> {
+    let path = cli.log_file.clone().or_else(|| {
+        config::AppConfig::load_with_profile(cli.config.as_deref(), cli.profile.as_deref())
+            .ok()
+            .and_then(|cfg| cfg.log_file)
+    })?;
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        let _ = utils::ensure_dir(parent);
+    }
+    match fs::OpenOptions::new().create(true).append(true).open(&path) {
+        Ok(file) => Some(file),
+        Err(err) => {
+            eprintln!("could not open log file {}: {err}", path.display());
+            None
+        }
+    }
+}
+
+fn merged_presets(cfg: &config::AppConfig) -> HashMap<String, HashMap<String, YamlValue>> {
+    let mut presets: HashMap<String, HashMap<String, YamlValue>> = HashMap::new();
+    presets.insert("basic".into(), HashMap::new());
+    presets.insert(
+        "speed".into(),
+        HashMap::from([("pdf_mode".into(), YamlValue::String("images".into()))]),
+    );
+    presets.insert(
+        "quality".into(),
+        HashMap::from([("pdf_mode".into(), YamlValue::String("pdf".into()))]),
+    );
+
+    for (key, value) in cfg.presets.clone() {
+        presets.insert(key.to_lowercase(), value);
+    }
+
+    presets
+}
+
+fn preset_str<'a>(settings: &'a HashMap<String, YamlValue>, key: &str) -> Option<&'a str> {
+    settings.get(key).and_then(|value| value.as_str())
+}
+
+/// The recording `--record` writes to or `--replay` reads from, if either was given.
+fn recording(cli: &cli::Cli) -> anyhow::Result<Option<Recording>> {
+    match (&cli.record, &cli.replay) {
+        (Some(dir), _) => Recording::record(dir).map(Some),
+        (None, Some(dir)) => Recording::replay(dir).map(Some),
+        (None, None) => Ok(None),
+    }
+}
+
+/// The run's API keys; an offline run (`--replay`, `--no-llm`) calls no API and
+/// needs none.
+fn run_keys(cfg: &config::AppConfig, offline: bool) -> anyhow::Result<ApiKeys> {
+    if offline {
+        return Ok(cfg
+            .api_keys()
+            .unwrap_or_else(|_| ApiKeys::single(String::new())));
+    }
+    cfg.api_keys()
+}
+
+/// Sampling settings given on the command line; they override `generation` in the config.
+fn generation_flags(cli: &cli::Cli) -> GenerationConfig {
+    GenerationConfig {
+        temperature: cli.temperature,
+        top_p: cli.top_p,
+        max_output_tokens: cli.max_output_tokens,
+        stop_sequences: cli.stop_sequences.clone(),
+    }
+}
+
+fn read_prompt_file(path: &Path) -> anyhow::Result<String> {
+    fs::read_to_string(path).with_context(|| format!("reading prompt file {}", path.display()))
+}
+
+fn preset_bool(settings: &HashMap<String, YamlValue>, key: &str) -> bool {
+    settings
+        .get(key)
+        .and_then(|value| value.as_bool())
+        .unwrap_or(false)
+}
+
+fn preset_strings(settings: &HashMap<String, YamlValue>, key: &str) -> Vec<String> {
+    match settings.get(key) {
+        Some(YamlValue::String(value)) => vec![value.clone()],
+        Some(YamlValue::Sequence(values)) => values
+            .iter()
+            .filter_map(|value| value.as_str().map(str::to_string))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Expand a profile's `output_dir` pattern for one source: `{stem}` is the source's
+/// file stem (or slug for URLs) and `{date}` today's date.
+fn render_output_dir(pattern: &str, source: &str) -> PathBuf {
+    let stem = if source.contains("://") {
+        slugify(
+            source
+                .rsplit('/')
+                .find(|part| !part.is_empty())
+                .unwrap_or(source),
+        )
+    } else {
+        Path::new(source)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| slugify(source))
+    };
+    let date = time::OffsetDateTime::now_utc().date().to_string();
+    expand_tilde(Path::new(
+        &pattern.replace("{stem}", &stem).replace("{date}", &date),
+    ))
+}
+
+fn resolve_media_resolution(value: Option<&str>) -> anyhow::Result<(String, Option<String>)> {
+    let default_value = "default".to_string();
+    let Some(raw) = value else {
+        return Ok((default_value.clone(), Some(default_value)));
+    };
+
+    let normalized = raw.to_lowercase();
+    let allowed = ["default", "low", "medium", "high", "unspecified", ""];
+    if !allowed.contains(&normalized.as_str()) {
+        return Err(anyhow!(format!("Invalid media resolution '{}'.", raw)));
+    }
+
+    if normalized.is_empty() {
+        return Ok((default_value.clone(), Some(default_value)));
+    }
+
+    Ok((normalized.clone(), Some(normalized)))
+}
+
+/// Label, primary output, telemetry and cost for one finished job.
+pub type JobSummary = (
+    String,
+    Option<PathBuf>,
+    telemetry::RunSummary,
+    cost::CostSummary,
+);
+
+pub(crate) async fn run_primary(cli: cli::Cli) -> anyhow::Result<()> {
+    run_jobs(cli, None).await.map(|_| ())
+}
+
+/// The transcribe workflow. With an `observer`, progress is forwarded there instead of
+/// driving the TUI (used by `recapit serve`).
+pub async fn run_jobs(
+    mut cli: cli::Cli,
+    observer: Option<mpsc::UnboundedSender<Progress>>,
+) -> anyhow::Result<Vec<JobSummary>> {
+    let resume = cli.resume.as_deref().map(batch::load_resume).transpose()?;
+    if let Some(point) = &resume {
+        tracing::info!(
+            "resuming {} in {}: {} unfinished chunk(s)",
+            point.source,
+            point.folder.display(),
+            point.unfinished
+        );
+        cli.source = vec![point.source.clone()];
+        cli.output_dir = point.folder.parent().map(Path::to_path_buf);
+    }
+    let sources = if cli.source.is_empty() && cli.jobs.is_none() {
+        return Err(anyhow!(
+            "A source path or URL is required unless using a subcommand"
+        ));
+    } else {
+        cli.source.clone()
+    };
+
+    // Handle conversion-first flow (single source only)
+    if let Some(target) = &cli.to {
+        let source = sources
+            .first()
+            .ok_or_else(|| anyhow!("A source path is required for conversion"))?;
+        let default_pattern = match cli.from {
+            cli::ConversionSource::Latex => "*.tex".to_string(),
+            cli::ConversionSource::Markdown => "*.md".to_string(),
+            cli::ConversionSource::Auto => cli.file_pattern.clone(),
+        };
+        let pattern = if cli.file_pattern != "*.tex" {
+            cli.file_pattern.clone()
+        } else {
+            default_pattern
+        };
+        let kind = match target {
+            ConversionTarget::Markdown => ConversionKind::Markdown,
+            ConversionTarget::Json => ConversionKind::Json,
+        };
+        return run_conversion(
+            PathBuf::from(source),
+            cli.output_dir.clone(),
+            pattern,
+            cli.skip_existing,
+            cli.convert_model.clone().or_else(|| cli.model.clone()),
+            if cli.no_recursive {
+                false
+            } else {
+                cli.recursive
+            },
+            kind,
+            generation_flags(&cli),
+            cli.api_key_file.clone(),
+        )
+        .await
+        .map(|_| Vec::new());
+    }
+
+    let mut cfg =
+        config::AppConfig::load_with_profile(cli.config.as_deref(), cli.profile.as_deref())?;
+    if let Some(path) = &cli.api_key_file {
+        cfg.key_source.file = Some(path.clone());
+    }
+    if cli.count_tokens {
+        cfg.count_tokens = true;
+    }
+    if cli.youtube_captions {
+        cfg.ytdlp_options.captions = true;
+    }
+    if cli.remove_silence {
+        cfg.video_silence.enabled = true;
+    }
+    if let Some(model) = &cli.convert_model {
+        cfg.convert_model = Some(model.clone());
+    }
+    if let Some(model) = &cli.synthesize_model {
+        cfg.synthesize_model = Some(model.clone());
+    }
+    if !cli.fallback_model.is_empty() {
+        cfg.model_fallbacks = cli.fallback_model.clone();
+    }
+    if let Some(boundaries) = cli.chunk_boundaries {
+        cfg.video_chunk_boundaries.mode = match boundaries {
+            ChunkBoundaryArg::Fixed => ChunkBoundaryMode::Fixed,
+            ChunkBoundaryArg::Silence => ChunkBoundaryMode::Silence,
+            ChunkBoundaryArg::Scene => ChunkBoundaryMode::Scene,
+        };
+    }
+    if let Some(mode) = cli.video_mode {
+        cfg.video_mode = match mode {
+            VideoModeArg::Full => VideoMode::Full,
+            VideoModeArg::Slides => VideoMode::Slides,
+            VideoModeArg::Hybrid => VideoMode::Hybrid,
+            VideoModeArg::Audio => VideoMode::Audio,
+        };
+    }
+    cfg.generation = cfg.generation.merged(&generation_flags(&cli));
+    cfg.generation.validate()?;
+    let presets = merged_presets(&cfg);
+    let preset_key = cli.preset.to_lowercase();
+    let mut job_settings = presets
+        .get(&preset_key)
+        .ok_or_else(|| {
+            anyhow!(
+                "Unknown preset '{}'. Available presets: {}",
+                cli.preset,
+                presets.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+        })?
+        .clone();
+    // Profile keys win over the preset; CLI flags still win over both.
+    job_settings.extend(cfg.profile_settings.clone());
+    let preset_config = &job_settings;
+
+    let grouping = match cli.group {
+        Some(GroupArg::ImagesAsDocument) => Grouping::ImagesAsDocument,
+        Some(GroupArg::Separate) => Grouping::Separate,
+        None => match preset_str(preset_config, "group") {
+            Some("images-as-document") => Grouping::ImagesAsDocument,
+            _ => Grouping::Separate,
+        },
+    };
+    let on_conflict = match cli.on_conflict {
+        Some(ConflictArg::Prompt) => ConflictPolicy::Prompt,
+        Some(ConflictArg::Overwrite) => ConflictPolicy::Overwrite,
+        Some(ConflictArg::Rename) => ConflictPolicy::Rename,
+        Some(ConflictArg::Skip) => ConflictPolicy::Skip,
+        Some(ConflictArg::Fail) => ConflictPolicy::Fail,
+        None => match preset_str(preset_config, "on_conflict") {
+            Some("overwrite") => ConflictPolicy::Overwrite,
+            Some("rename") => ConflictPolicy::Rename,
+            Some("skip") => ConflictPolicy::Skip,
+            Some("fail") => ConflictPolicy::Fail,
+            _ => ConflictPolicy::Prompt,
+        },
+    };
+    let include = if cli.include.is_empty() {
+        preset_strings(preset_config, "include")
+    } else {
+        cli.include.clone()
+    };
+    let exclude = if cli.exclude.is_empty() {
+        preset_strings(preset_config, "exclude")
+    } else {
+        cli.exclude.clone()
+    };
+    let max_depth = cli.max_depth.or_else(|| {
+        preset_config
+            .get("max_depth")
+            .and_then(|value| value.as_u64())
+            .map(|depth| depth as usize)
+    });
+    let chunk_concurrency = cli
+        .chunk_concurrency
+        .map(|value| value as usize)
+        .or_else(|| {
+            preset_config
+                .get("chunk_concurrency")
+                .and_then(|value| value.as_u64())
+                .map(|value| value as usize)
+        })
+        .unwrap_or(1)
+        .max(1);
+    let diarize = cli.diarize || preset_bool(preset_config, "diarize");
+    let synthesize = cli.synthesize || preset_bool(preset_config, "synthesize");
+    let embed_chapters = cli.embed_chapters || preset_bool(preset_config, "embed_chapters");
+    let save_metadata = cli.save_metadata || preset_bool(preset_config, "save_metadata");
+    let pdf_split_outline = cli.pdf_split_outline
+        || preset_bool(preset_config, "pdf_split_outline")
+        || cfg.pdf_split_outline;
+    let prompt_override = match (&cli.prompt, &cli.prompt_file) {
+        (Some(text), _) => Some(text.clone()),
+        (None, Some(path)) => Some(read_prompt_file(path)?),
+        (None, None) => match preset_str(preset_config, "prompt") {
+            Some(text) => Some(text.to_string()),
+            None => preset_str(preset_config, "prompt_file")
+                .map(|path| read_prompt_file(Path::new(path)))
+                .transpose()?,
+        },
+    };
+    let mut vars = cfg.template_vars.clone();
+    for entry in &cli.vars {
+        let (name, value) = entry
+            .split_once('=')
+            .ok_or_else(|| anyhow!("--var expects NAME=VALUE, got '{entry}'"))?;
+        vars.insert(name.trim().to_ascii_uppercase(), value.to_string());
+    }
+    let language = cli
+        .language
+        .clone()
+        .or_else(|| preset_str(preset_config, "language").map(str::to_string))
+        .map(|language| language.trim().to_string())
+        .filter(|language| !language.is_empty());
+    let detail = match cli.detail {
+        Some(DetailArg::Brief) => Detail::Brief,
+        Some(DetailArg::Standard) => Detail::Standard,
+        Some(DetailArg::Verbatim) => Detail::Verbatim,
+        None => match preset_str(preset_config, "detail") {
+            Some("brief") => Detail::Brief,
+            Some("verbatim") => Detail::Verbatim,
+            _ => Detail::Standard,
+        },
+    };
+    let glossary = match cli.glossary.as_ref().or(cfg.glossary_file.as_ref()) {
+        Some(path) => {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("reading glossary {}", path.display()))?;
+            Some(text.trim().to_string()).filter(|text| !text.is_empty())
+        }
+        None => None,
+    };
+    let output_dir_pattern = preset_str(preset_config, "output_dir").map(str::to_string);
+    let default_output_dir = |source: &str| {
+        cli.output_dir
+            .clone()
+            .or_else(|| {
+                output_dir_pattern
+                    .as_deref()
+                    .map(|pattern| render_output_dir(pattern, source))
+            })
+            .or_else(|| cfg.output_dir.clone())
+    };
+
+    let path_filter = ingest::PathFilter::new(&include, &exclude)?;
+
+    let cli_recursive = if cli.no_recursive {
+        Some(false)
+    } else if cli.recursive {
+        Some(true)
+    } else {
+        None
+    };
+
+    let mut exports = if cli.export.is_empty() {
+        cfg.exports.clone()
+    } else {
+        cli.export.clone()
+    };
+    if let Some(preset_exports) = preset_config
+        .get("exports")
+        .and_then(|value| value.as_sequence())
+    {
+        for value in preset_exports {
+            if let Some(item) = value.as_str() {
+                exports.push(item.to_string());
+            }
+        }
+    }
+    exports.retain(|value| !value.trim().is_empty());
+    exports.sort();
+    exports.dedup();
+
+    let mut save_full_response = cfg.save_full_response;
+    if let Some(value) = preset_config
+        .get("save_full_response")
+        .and_then(|v| v.as_bool())
+    {
+        save_full_response = value;
+    }
+    let mut save_intermediates = cfg.save_intermediates;
+    if let Some(value) = preset_config
+        .get("save_intermediates")
+        .and_then(|v| v.as_bool())
+    {
+        save_intermediates = value;
+    }
+    // The finished chunks are read back from their saved responses.
+    save_intermediates |= resume.is_some();
+
+    let cookies = YtDlpCookies {
+        from_browser: cli
+            .cookies_from_browser
+            .clone()
+            .or(cfg.cookies_from_browser.clone()),
+        file: cli.cookies.clone().or(cfg.cookies_file.clone()),
+    };
+    let mut inputs = match &cli.jobs {
+        Some(path) => batch::load_jobs(path)?,
+        None => Vec::new(),
+    };
+    inputs.extend(sources.iter().map(|source| SourceInput::plain(source)));
+    let folder_walk = cli.mirror_structure.then(|| FolderWalk {
+        recursive: cli_recursive
+            .or_else(|| {
+                preset_config
+                    .get("recursive")
+                    .and_then(|value| value.as_bool())
+            })
+            .unwrap_or(false),
+        max_depth,
+        filter: path_filter,
+    });
+    let (specs, playlists, mirrors) = expand_sources(
+        &inputs,
+        cli.output_dir.as_deref(),
+        &cookies,
+        &cfg.ytdlp_options,
+        cli.quiet,
+        folder_walk.as_ref(),
+    )?;
+    if specs.is_empty() {
+        return Err(anyhow!("No videos found in the given playlist"));
+    }
+
+    if cli.dry_run {
+        let source = &specs[0].source;
+        let page_selection = resolve_page_selection(
+            &cli.pages,
+            preset_config.get("pages").and_then(|value| value.as_str()),
+            0,
+            specs.len(),
+        )?;
+        let pdf_mode = parse_pdf_mode(&cli.pdf_mode);
+        let job = Job {
+            source: source.clone(),
+            job_label: source.clone(),
+            job_id: slugify(source),
+            job_index: 0,
+            job_total: 1,
+            recursive: cli_recursive
+                .or_else(|| {
+                    preset_config
+                        .get("recursive")
+                        .and_then(|value| value.as_bool())
+                })
+                .unwrap_or(false),
+            include: include.clone(),
+            exclude: exclude.clone(),
+            max_depth,
+            kind: parse_kind(&cli.kind).or_else(|| {
+                preset_config
+                    .get("kind")
+                    .and_then(|value| value.as_str())
+                    .and_then(parse_kind)
+            }),
+            pdf_mode,
+            output_dir: specs[0]
+                .output_dir
+                .clone()
+                .or_else(|| default_output_dir(source)),
+            model: cli
+                .model
+                .clone()
+                .unwrap_or_else(|| cfg.default_model.clone()),
+            convert_model: cfg.convert_model.clone(),
+            synthesize_model: cfg.synthesize_model.clone(),
+            preset: Some(preset_key.clone()),
+            export: exports.clone(),
+            format: cli
+                .format
+                .map(|v| match v {
+                    OutputFormatArg::Markdown => OutputFormat::Markdown,
+                    OutputFormatArg::Latex => OutputFormat::Latex,
+                    OutputFormatArg::Org => OutputFormat::Org,
+                    OutputFormatArg::Rst => OutputFormat::Rst,
+                    OutputFormatArg::Json => OutputFormat::Json,
+                })
+                .unwrap_or(cfg.default_format),
+            skip_existing: cli.skip_existing,
+            on_conflict,
+            page_selection,
+            media_resolution: resolve_media_resolution(Some(cfg.media_resolution.as_str()))?.1,
+            save_full_response,
+            save_intermediates,
+            save_metadata,
+            save_sidecar: cfg.save_sidecar,
+            max_workers: cfg.max_workers,
+            max_video_workers: cfg.max_video_workers,
+            pdf_dpi: cfg.pdf_dpi,
+            pdf_chunk_pages: resolve_pdf_chunk_pages(&cfg, preset_config, cli.pdf_chunk_pages),
+            pdf_split_outline,
+            chunk_concurrency,
+            chunk_context_lines: cfg.chunk_context_lines,
+            video_mode: cfg.video_mode,
+            grouping,
+            prompt_override: prompt_override.clone(),
+            vars: vars.clone(),
+            language: language.clone(),
+            detail,
+            glossary: glossary.clone(),
+            diarize,
+            embed_chapters,
+            synthesize,
+            use_cache: !cli.no_cache,
+            continue_on_error: cli.continue_on_error,
+            resume_folder: None,
+        };
+        let estimator = cost_estimator(&cfg).await?;
+        return run_plan(&cfg, job, &cookies, &estimator, cli.json).map(|_| Vec::new());
+    }
+
+    let cost = cost_estimator(&cfg).await?;
+    let summary_cost = cost.clone();
+    let control = ChunkControl::default();
+    let usage = tui::UsageFeed::new(summary_cost.clone());
+
+    // `recapit serve` passes an observer and runs its own exporter for the life of
+    // the server.
+    let otlp_endpoint = cfg.otlp_endpoint.clone().filter(|_| observer.is_none());
+    let (tx, rx) = mpsc::unbounded_channel::<Progress>();
+    let (cancel_tx, mut cancel_rx) = mpsc::unbounded_channel::<()>();
+    // The dashboard reads keys in raw mode, JSON progress is read by a program, and
+    // `recapit serve` has no terminal: none of them can answer a budget prompt.
+    let can_prompt = observer.is_none()
+        && matches!(
+            cli.progress_mode(),
+            ProgressMode::Plain | ProgressMode::None
+        );
+    let tui_handle = if let Some(observer) = observer {
+        let mut rx = rx;
+        tokio::spawn(async move {
+            while let Some(progress) = rx.recv().await {
+                let _ = observer.send(progress);
+            }
+        });
+        None
+    } else {
+        match cli.progress_mode() {
+            ProgressMode::Tui => Some(tokio::spawn(tui::run_tui(
+                rx,
+                cancel_tx.clone(),
+                control.clone(),
+                usage.clone(),
+            ))),
+            ProgressMode::Plain => Some(tokio::spawn(progress_plain(rx))),
+            ProgressMode::Json => Some(tokio::spawn(progress_json(rx))),
+            ProgressMode::None => None,
+        }
+    };
+
+    let request_limits = crate::constants::rate_limits_per_minute()
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    let token_limits = crate::constants::token_limits_per_minute()
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    let quota = QuotaMonitor::new(QuotaConfig::new(request_limits, token_limits))
+        .with_state_file(quota::default_state_path());
+
+    let budget = cli.max_cost.or(cfg.max_cost).map(|limit| {
+        cost::CostBudget::new(limit, cfg.budget_action, summary_cost.clone())
+            .with_prompt(can_prompt)
+    });
+
+    let media_candidate = cli
+        .media_resolution
+        .clone()
+        .or_else(|| {
+            preset_config
+                .get("media_resolution")
+                .and_then(|value| value.as_str())
+                .map(|s| s.to_string())
+        })
+        .unwrap_or_else(|| cfg.media_resolution.clone());
+    let (media_label, media_enum) = resolve_media_resolution(Some(media_candidate.as_str()))?;
+    let cli_format_arg = cli.format.clone();
+
+    let tokens_per_second = planned_tokens_per_second(&cfg, &media_label);
+
+    let total_jobs = specs.len();
+    tx.send(Progress {
+        scope: ProgressScope::Run,
+        stage: ProgressStage::Discover,
+        current: 0,
+        total: total_jobs as u64,
+        status: "start".into(),
+        finished: false,
+    })
+    .ok();
+
+    let recording = recording(&cli)?;
+    let offline = cli.no_llm || recording.as_ref().is_some_and(Recording::is_replay);
+    let model_listing = match cfg.api_keys() {
+        Ok(keys) if !offline => {
+            models::ModelCache::refresh(keys.primary(), cli.refresh_models).await
+        }
+        _ => None,
+    };
+    let mut unlisted_warned = HashSet::new();
+
+    let export_stop = CancellationToken::new();
+    let _stop_export = export_stop.clone().drop_guard();
+    if let Some(endpoint) = &otlp_endpoint {
+        tokio::spawn(metrics::export_periodically(
+            endpoint.clone(),
+            std::time::Duration::from_secs(cfg.metrics_interval_seconds),
+            export_stop.clone(),
+        ));
+    }
+
+    let mut summaries = Vec::new();
+    // Parallel to `summaries`: "ok", "failed", or "cancelled", with the error.
+    let mut outcomes: Vec<(&str, Option<String>)> = Vec::new();
+    let mut failed_chunks = Vec::new();
+
+    // The first Ctrl+C (or `q` in the TUI) lets requests in flight finish and ends
+    // the run after the current job writes its manifest; a second one quits at once.
+    let cancel = CancellationToken::new();
+    let listening = CancellationToken::new();
+    let _stop_listening = listening.clone().drop_guard();
+    tokio::spawn({
+        let cancel = cancel.clone();
+        async move {
+            tokio::select! {
+                Some(()) = cancel_rx.recv() => {}
+                _ = tokio::signal::ctrl_c() => {}
+                _ = listening.cancelled() => return,
+            }
+            eprintln!("cancelling: waiting for requests in flight (Ctrl+C again to quit now)");
+            cancel.cancel();
+            tokio::select! {
+                Ok(()) = tokio::signal::ctrl_c() => std::process::exit(130),
+                _ = listening.cancelled() => {}
+            }
+        }
+    });
+
+    for (idx, spec) in specs.iter().enumerate() {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let source = &spec.source;
+        let job_label = spec.label.clone();
+        let job_id = slugify(source);
+
+        let overrides = &spec.overrides;
+        let cli_kind = overrides
+            .kind
+            .as_deref()
+            .and_then(parse_kind)
+            .or_else(|| parse_kind(&cli.kind));
+        let effective_kind = if cli_kind.is_some() {
+            cli_kind
+        } else {
+            preset_config
+                .get("kind")
+                .and_then(|value| value.as_str())
+                .and_then(parse_kind)
+        };
+
+        let mut effective_pdf_mode =
+            parse_pdf_mode(overrides.pdf_mode.as_deref().unwrap_or(&cli.pdf_mode));
+        if matches!(effective_pdf_mode, PdfMode::Auto) {
+            if let Some(preset_pdf) = preset_config
+                .get("pdf_mode")
+                .and_then(|value| value.as_str())
+            {
+                effective_pdf_mode = parse_pdf_mode(preset_pdf);
+            }
+        }
+
+        let mut effective_pdf_dpi = cfg.pdf_dpi;
+        if let Some(value) = preset_config
+            .get("pdf_dpi")
+            .and_then(|value| value.as_u64())
+            .and_then(|value| u32::try_from(value).ok())
+        {
+            if value > 0 {
+                effective_pdf_dpi = value;
+            }
+        }
+        if let Some(value) = cli.pdf_dpi {
+            if value > 0 {
+                effective_pdf_dpi = value;
+            }
+        }
+
+        let effective_model = overrides
+            .model
+            .clone()
+            .or(cli.model.clone())
+            .or_else(|| {
+                preset_config
+                    .get("model")
+                    .and_then(|value| value.as_str())
+                    .map(|s| s.to_string())
+            })
+            .unwrap_or_else(|| cfg.default_model.clone());
+
+        let cli_format = cli_format_arg.clone().map(|value| match value {
+            OutputFormatArg::Markdown => OutputFormat::Markdown,
+            OutputFormatArg::Latex => OutputFormat::Latex,
+            OutputFormatArg::Org => OutputFormat::Org,
+            OutputFormatArg::Rst => OutputFormat::Rst,
+            OutputFormatArg::Json => OutputFormat::Json,
+        });
+        let preset_format = preset_config
+            .get("format")
+            .and_then(|value| value.as_str())
+            .and_then(OutputFormat::parse);
+        let override_format = match overrides.format.as_deref() {
+            Some(value) => Some(OutputFormat::parse(value).ok_or_else(|| {
+                anyhow!("unknown format '{value}' for {source} in the jobs file")
+            })?),
+            None => None,
+        };
+        let effective_format = override_format
+            .or(cli_format)
+            .or(preset_format)
+            .unwrap_or(cfg.default_format);
+
+        let effective_recursive = cli_recursive
+            .or_else(|| {
+                preset_config
+                    .get("recursive")
+                    .and_then(|value| value.as_bool())
+            })
+            .unwrap_or(false);
+
+        let mut max_workers = cfg.max_workers;
+        if let Some(value) = preset_config.get("max_workers").and_then(|v| v.as_u64()) {
+            if value > 0 {
+                max_workers = value as usize;
+            }
+        }
+        let mut max_video_workers = cfg.max_video_workers;
+        if let Some(value) = preset_config
+            .get("max_video_workers")
+            .and_then(|v| v.as_u64())
+        {
+            if value > 0 {
+                max_video_workers = value as usize;
+            }
+        }
+
+        let page_selection = match &overrides.pages {
+            Some(pages) => Some(IndexSelection::parse(pages)?),
+            None => resolve_page_selection(
+                &cli.pages,
+                preset_config.get("pages").and_then(|value| value.as_str()),
+                idx,
+                specs.len(),
+            )?,
+        };
+        let job_exports = overrides.exports.clone().unwrap_or_else(|| exports.clone());
+
+        let job = Job {
+            source: source.clone(),
+            job_label: job_label.clone(),
+            job_id: job_id.clone(),
+            job_index: idx,
+            job_total: total_jobs,
+            recursive: effective_recursive,
+            include: include.clone(),
+            exclude: exclude.clone(),
+            max_depth,
+            kind: effective_kind,
+            pdf_mode: effective_pdf_mode,
+            output_dir: spec
+                .output_dir
+                .clone()
+                .or_else(|| default_output_dir(source)),
+            model: effective_model.clone(),
+            convert_model: cfg.convert_model.clone(),
+            synthesize_model: cfg.synthesize_model.clone(),
+            preset: Some(preset_key.clone()),
+            export: job_exports,
+            format: effective_format,
+            // Exports of the transcript with placeholders are rebuilt on a resume.
+            skip_existing: cli.skip_existing && resume.is_none(),
+            on_conflict,
+            page_selection,
+            media_resolution: media_enum.clone(),
+            save_full_response,
+            save_intermediates,
+            save_metadata,
+            save_sidecar: cfg.save_sidecar,
+            max_workers,
+            max_video_workers,
+            pdf_dpi: effective_pdf_dpi,
+            pdf_chunk_pages: resolve_pdf_chunk_pages(&cfg, preset_config, cli.pdf_chunk_pages),
+            pdf_split_outline,
+            chunk_concurrency,
+            chunk_context_lines: cfg.chunk_context_lines,
+            video_mode: cfg.video_mode,
+            grouping,
+            prompt_override: prompt_override.clone(),
+            vars: vars.clone(),
+            language: language.clone(),
+            detail,
+            glossary: glossary.clone(),
+            diarize,
+            embed_chapters,
+            synthesize,
+            // A cached transcript would skip the requests being recorded or replayed.
+            use_cache: !cli.no_cache && recording.is_none(),
+            continue_on_error: cli.continue_on_error,
+            resume_folder: resume.as_ref().map(|point| point.folder.clone()),
+        };
+
+        if model_listing
+            .as_ref()
+            .is_some_and(|listing| !listing.lists(&job.model))
+            && unlisted_warned.insert(job.model.clone())
+        {
+            tracing::warn!(
+                "model {} is not in the models endpoint's listing for this key; see `recapit models --refresh-models`",
+                job.model
+            );
+        }
+        let model_key = job.model.clone();
+        let capability_checker = move |capability: &str| models::supports(&model_key, capability);
+
+        let monitor = telemetry::RunMonitor::new();
+        usage.add(monitor.clone());
+        let provider = GeminiProvider::new(
+            run_keys(&cfg, offline)?,
+            job.model.clone(),
+            monitor.clone(),
+            Some(quota.clone()),
+        )
+        .with_progress(tx.clone())
+        .with_budget(budget.clone())
+        .with_keep_uploads(!cli.delete_uploads)
+        .with_generation(cfg.generation.clone())
+        .with_safety(cfg.safety.clone())
+        .with_token_count(cfg.count_tokens)
+        .with_fallbacks(cfg.model_fallbacks.clone())
+        .with_cancel(cancel.clone())
+        .with_control(control.clone())
+        .with_recording(recording.clone());
+        let normalizer = CompositeNormalizer::new(
+            None,
+            cfg.video_encoder_preference,
+            Some(cfg.video_max_chunk_seconds),
+            Some(cfg.video_max_chunk_bytes),
+            cfg.video_token_limit,
+            Some(tokens_per_second),
+            Some(job.pdf_dpi),
+            Some(Box::new(capability_checker)),
+        )?
+        .with_cookies(cookies.clone())
+        .with_ytdlp_options(cfg.ytdlp_options.clone())
+        .with_silence_removal(cfg.video_silence)
+        .with_encoding(cfg.video_encode.clone())
+        .with_chunk_boundaries(cfg.video_chunk_boundaries)
+        .with_ocr(cfg.pdf_ocr)
+        .with_image_prep(cfg.image_prep)
+        .with_progress(tx.clone());
+        let ingestor = CompositeIngestor::new()?
+            .with_media_hosts(&cfg.media_url_hosts)
+            .with_max_download(cfg.download_max_bytes)
+            .with_progress(tx.clone());
+        let converter = LatexConverter::new(
+            run_keys(&cfg, offline)?,
+            monitor.clone(),
+            Some(quota.clone()),
+        )?
+        .with_generation(cfg.generation.clone())
+        .with_safety(cfg.safety.clone())
+        .with_recording(recording.clone());
+        let mut engine = Engine::new(
+            Box::new(ingestor),
+            Box::new(normalizer),
+            Box::new(provider),
+            Box::new(CompositeWriter::new()),
+            tx.clone(),
+            monitor.clone(),
+            cost.clone(),
+            Some(converter),
+            &cfg,
+        )?
+        .with_cancel(cancel.clone())
+        .with_extract_only(cli.no_llm)
+        .with_prompt(can_prompt);
+
+        tx.send(Progress {
+            scope: ProgressScope::Run,
+            stage: ProgressStage::Discover,
+            current: idx as u64,
+            total: total_jobs as u64,
+            status: job_label.clone(),
+            finished: false,
+        })
+        .ok();
+
+        let result = engine
+            .run(&job)
+            .instrument(tracing::info_span!("job", label = %job_label, source = %source))
+            .await;
+        if !cli.keep_extracted {
+            if let Err(err) = ingest::remove_extracted(Path::new(source)) {
+                tracing::warn!("{err:#}");
+            }
+        }
+        let result = match result {
+            Ok(r) => r,
+            // Keep the partial usage so the totals below include it.
+            Err(e) if e.is::<Cancelled>() => {
+                drop(engine);
+                let summary = monitor.summarize();
+                let costs = summary_cost.estimate(&monitor.events());
+                if let Some(budget) = &budget {
+                    budget.commit(costs.total_cost);
+                }
+                let payload = hooks::job_payload(
+                    source,
+                    &job_label,
+                    None,
+                    Some("cancelled"),
+                    &summary,
+                    &costs,
+                );
+                hooks::fire(&cfg.on_complete, &payload).await;
+                let mut entry =
+                    history::HistoryEntry::new(source, &job_label, &job.model, &summary, &costs);
+                entry.status = "cancelled".into();
+                record_history(&entry);
+                summaries.push((job_label, None, summary, costs));
+                outcomes.push(("cancelled", None));
+                break;
+            }
+            // The rest of the batch still runs; the run fails at the end.
+            Err(e) => {
+                drop(engine);
+                let error = format!("{e:#}");
+                tracing::error!("{job_label}: {error}");
+                let summary = monitor.summarize();
+                let costs = summary_cost.estimate(&monitor.events());
+                if let Some(budget) = &budget {
+                    budget.commit(costs.total_cost);
+                }
+                let payload =
+                    hooks::job_payload(source, &job_label, None, Some(&error), &summary, &costs);
+                hooks::fire(&cfg.on_complete, &payload).await;
+                let mut entry =
+                    history::HistoryEntry::new(source, &job_label, &job.model, &summary, &costs);
+                entry.status = "failed".into();
+                entry.error = Some(error.clone());
+                record_history(&entry);
+                tx.send(Progress {
+                    scope: ProgressScope::Run,
+                    stage: ProgressStage::Write,
+                    current: (idx + 1) as u64,
+                    total: total_jobs as u64,
+                    status: job_label.clone(),
+                    finished: idx + 1 == total_jobs,
+                })
+                .ok();
+                summaries.push((job_label, None, summary, costs));
+                outcomes.push(("failed", Some(error)));
+                continue;
+            }
+        };
+        if let Some(path) = result.as_ref().filter(|_| !cli.no_llm) {
+            if let Err(err) = transcripts::TranscriptIndex::record(source, path, &job.model) {
+                tracing::warn!("recording transcript location: {err:#}");
+            }
+        }
+
+        tx.send(Progress {
+            scope: ProgressScope::Run,
+            stage: ProgressStage::Write,
+            current: (idx + 1) as u64,
+            total: total_jobs as u64,
+            status: job_label.clone(),
+            finished: idx + 1 == total_jobs,
+        })
+        .ok();
+
+        drop(engine);
+
+        failed_chunks.extend(monitor.notes("chunk.failed"));
+        let summary = monitor.summarize();
+        let costs = summary_cost.estimate(&monitor.events());
+        if let Some(budget) = &budget {
+            budget.commit(costs.total_cost);
+        }
+        let payload = hooks::job_payload(
+            source,
+            &job_label,
+            result.as_deref(),
+            None,
+            &summary,
+            &costs,
+        );
+        hooks::fire(&cfg.on_complete, &payload).await;
+        let mut entry =
+            history::HistoryEntry::new(source, &job_label, &job.model, &summary, &costs);
+        entry.output = result.clone();
+        record_history(&entry);
+        summaries.push((job_label, result.clone(), summary, costs));
+        outcomes.push(("ok", None));
+    }
+
+    drop(tx);
+
+    if let Some(handle) = tui_handle {
+        handle.await??;
+    }
+
+    for (group, playlist) in playlists.iter().enumerate() {
+        write_playlist_summary(playlist, group, &specs, &summaries)?;
+    }
+    for (group, mirror) in mirrors.iter().enumerate() {
+        write_mirror_index(mirror, group, &specs, &summaries)?;
+    }
+    if let Some(jobs_path) = &cli.jobs {
+        write_batch_summary(
+            jobs_path,
+            cli.output_dir.as_deref(),
+            &specs,
+            &summaries,
+            &outcomes,
+        )?;
+    }
+
+    if !cli.quiet {
+        let mut total_in = 0;
+        let mut total_out = 0;
+        let mut total_tokens = 0;
+        let mut total_cost = 0.0;
+        let mut total_time = 0.0;
+
+        for (label, output, summary, costs) in &summaries {
+            total_in += summary.total_input_tokens;
+            total_out += summary.total_output_tokens;
+            total_tokens += summary.total_tokens;
+            total_cost += costs.total_cost;
+            total_time += summary.total_duration_seconds;
+            println!(
+                "job {}: tokens in {} out {} total {} · est cost {} · elapsed {:.2}s{}",
+                label,
+                summary.total_input_tokens,
+                summary.total_output_tokens,
+                summary.total_tokens,
+                cost.currency().format(costs.total_cost, 6),
+                summary.total_duration_seconds,
+                output
+                    .as_ref()
+                    .map(|p| format!(" · output {}", p.display()))
+                    .unwrap_or_default()
+            );
+        }
+        if summaries.len() > 1 {
+            println!(
+                "total: tokens in {} out {} total {} · est cost {} · elapsed {:.2}s",
+                total_in,
+                total_out,
+                total_tokens,
+                cost.currency().format(total_cost, 6),
+                total_time
+            );
+        }
+    }
+
+    if let Some(endpoint) = &otlp_endpoint {
+        export_stop.cancel();
+        if let Err(err) = metrics::METRICS.push_otlp(endpoint).await {
+            tracing::warn!("{err:#}");
+        }
+    }
+    if let Some(limit) = cfg.cache_max_bytes {
+        trim_cache(limit, cli.quiet);
+    }
+
+    if cancel.is_cancelled() {
+        return Err(Cancelled.into());
+    }
+    let failed_jobs = outcomes
+        .iter()
+        .filter(|(status, _)| *status == "failed")
+        .count();
+    let mut resumable = Vec::new();
+    if !failed_chunks.is_empty() {
+        eprintln!("failed chunks:");
+        for failure in &failed_chunks {
+            if let Some(manifest) = failure
+                .get("manifest_path")
+                .and_then(Value::as_str)
+                .filter(|path| !path.is_empty())
+                .filter(|_| failure.get("resumable") == Some(&Value::Bool(true)))
+            {
+                if !resumable.contains(&manifest) {
+                    resumable.push(manifest);
+                }
+            }
+            let field = |key: &str| failure.get(key).cloned().unwrap_or(Value::Null);
+            eprintln!(
+                "  {}: chunk {} of {}: {}",
+                field("job_label").as_str().unwrap_or_default(),
+                field("chunk_index").as_u64().unwrap_or(0) + 1,
+                field("chunk_total"),
+                field("error").as_str().unwrap_or_default(),
+            );
+        }
+    }
+    if !resumable.is_empty() {
+        eprintln!("retry them with:");
+        for manifest in &resumable {
+            eprintln!("  recapit --resume {manifest}");
+        }
+    }
+    if failed_jobs > 0 {
+        anyhow::bail!("{failed_jobs} of {} job(s) failed", summaries.len());
+    }
+    match (failed_chunks.len(), resumable.as_slice()) {
+        (0, _) => {}
+        (failed, [manifest]) => anyhow::bail!(
+            "{failed} chunk(s) failed; `recapit --resume {manifest}` retries only those"
+        ),
+        (failed, []) => anyhow::bail!(
+            "{failed} chunk(s) failed; turn on save_intermediates to keep the finished chunks so --resume can retry only the failed ones"
+        ),
+        (failed, _) => {
+            anyhow::bail!("{failed} chunk(s) failed; retry them with the commands above")
+        }
+    }
+
+    Ok(summaries)
+}
+
+/// Append a finished job to the run history and the job counters; a failure to
+/// write it never fails the run.
+fn record_history(entry: &history::HistoryEntry) {
+    metrics::METRICS.job_finished(&entry.status);
+    if let Err(err) = history::record(entry) {
+        tracing::warn!("recording run history: {err:#}");
+    }
+}
+
+/// One JSON object per line on stderr, so wrappers can follow a run without the TUI.
+async fn progress_json(mut rx: mpsc::UnboundedReceiver<Progress>) -> anyhow::Result<()> {
+    use std::io::Write;
+    let mut throughput = progress::Throughput::default();
+    while let Some(progress) = rx.recv().await {
+        let mut event = serde_json::to_value(&progress)?;
+        let now = time::OffsetDateTime::now_utc();
+        let rfc3339 = |at: time::OffsetDateTime| {
+            at.format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_default()
+        };
+        event["timestamp"] = json!(rfc3339(now));
+        if let Some(rate) = throughput.record(&progress) {
+            event["rate"] = json!(rate);
+            event["eta"] = json!(rfc3339(
+                now + time::Duration::seconds(rate.eta_seconds as i64)
+            ));
+        }
+        let mut stderr = std::io::stderr().lock();
+        writeln!(stderr, "{event}")?;
+    }
+    Ok(())
+}
+
+/// `--progress plain`: a status line on stderr every few seconds while something
+/// changed, and one per finished job, with no cursor control.
+async fn progress_plain(mut rx: mpsc::UnboundedReceiver<Progress>) -> anyhow::Result<()> {
+    let started = std::time::Instant::now();
+    let mut throughput = progress::Throughput::default();
+    let mut latest: Vec<(Progress, Option<progress::Rate>)> = Vec::new();
+    let mut changed = false;
+    let mut ticker = tokio::time::interval(std::time::Duration::from_secs(10));
+    let stamp = || format!("[{}]", progress::format_eta(started.elapsed().as_secs()));
+    loop {
+        tokio::select! {
+            progress = rx.recv() => {
+                let Some(progress) = progress else { break };
+                if let (ProgressScope::Job { label, .. }, true) = (&progress.scope, progress.finished) {
+                    eprintln!("{} {label}: {}", stamp(), progress.status);
+                }
+                let rate = throughput.record(&progress);
+                latest.retain(|(seen, _)| seen.scope != progress.scope);
+                if !progress.finished {
+                    latest.push((progress, rate));
+                }
+                changed = true;
+            }
+            _ = ticker.tick() => {
+                if !changed || latest.is_empty() {
+                    continue;
+                }
+                changed = false;
+                let parts: Vec<String> = latest
+                    .iter()
+                    .filter(|(progress, _)| !matches!(progress.scope, ProgressScope::ChunkDetail { .. }))
+                    .map(|(progress, rate)| {
+                        if matches!(progress.scope, ProgressScope::Download { .. } | ProgressScope::Encode { .. }) {
+                            return format!("{} {}", progress.scope, progress.status);
+                        }
+                        let mut part = format!(
+                            "{} {} {}/{}",
+                            progress.scope,
+                            progress.stage.label(),
+                            progress.current,
+                            progress.total
+                        );
+                        if let Some(rate) = rate {
+                            part.push_str(&format!(
+                                " ({:.1} {}/min, eta {})",
+                                rate.per_minute,
+                                rate.unit,
+                                progress::format_eta(rate.eta_seconds)
+                            ));
+                        }
+                        part
+                    })
+                    .collect();
+                eprintln!("{} {}", stamp(), parts.join(" · "));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_kind(input: &str) -> Option<Kind> {
+    match input.to_lowercase().as_str() {
+        "slides" => Some(Kind::Slides),
+        "lecture" => Some(Kind::Lecture),
+        "document" => Some(Kind::Document),
+        "image" => Some(Kind::Image),
+        "video" => Some(Kind::Video),
+        "audio" => Some(Kind::Audio),
+        _ => None,
+    }
+}
+
+fn parse_pdf_mode(input: &str) -> PdfMode {
+    match input.to_lowercase().as_str() {
+        "images" => PdfMode::Images,
+        "pdf" => PdfMode::Pdf,
+        "text" => PdfMode::Text,
+        _ => PdfMode::Auto,
+    }
+}
+
+fn resolve_page_selection(
+    cli_pages: &[String],
+    preset_pages: Option<&str>,
+    source_index: usize,
+    source_total: usize,
+) -> anyhow::Result<Option<IndexSelection>> {
+    let raw = if cli_pages.is_empty() {
+        preset_pages
+    } else if cli_pages.len() == 1 {
+        Some(cli_pages[0].as_str())
+    } else if cli_pages.len() == source_total {
+        Some(cli_pages[source_index].as_str())
+    } else {
+        anyhow::bail!(
+            "--pages provided {} time(s), but {} source(s) were supplied. Pass --pages once to apply to all sources, or pass it once per source.",
+            cli_pages.len(),
+            source_total
+        );
+    };
+
+    if let Some(value) = raw {
+        return Ok(Some(IndexSelection::parse(value)?));
+    }
+
+    Ok(None)
+}
+
+fn resolve_pdf_chunk_pages(
+    cfg: &config::AppConfig,
+    preset_config: &HashMap<String, YamlValue>,
+    cli_value: Option<u32>,
+) -> Option<u32> {
+    let preset_value = preset_config
+        .get("pdf_chunk_pages")
+        .and_then(|value| value.as_u64())
+        .and_then(|value| u32::try_from(value).ok());
+    cli_value
+        .or(preset_value)
+        .or(cfg.pdf_chunk_pages)
+        .filter(|pages| *pages > 0)
+}
+
+/// The estimator for `cfg`: `pricing_file` when it exists, otherwise the built-in
+/// prices with a downloaded `pricing.url` table on top.
+async fn cost_estimator(cfg: &config::AppConfig) -> anyhow::Result<cost::CostEstimator> {
+    let mut pricing = cfg.pricing_defaults.clone();
+    if let Some(url) = &cfg.pricing_url {
+        let max_age = std::time::Duration::from_secs(cfg.pricing_max_age_hours * 3600);
+        match cost::fetch_pricing(url, max_age).await {
+            Ok(table) => pricing.extend(table),
+            Err(err) => tracing::warn!("{err:#}; using the built-in prices"),
+        }
+    }
+    Ok(
+        cost::CostEstimator::from_path(cfg.pricing_file.as_deref(), pricing)?
+            .with_currency(cfg.currency.clone()),
+    )
+}
+
+fn build_ingestion_stack(
+    cfg: &config::AppConfig,
+    job: &Job,
+    cookies: &YtDlpCookies,
+) -> anyhow::Result<(CompositeIngestor, CompositeNormalizer)> {
+    let model_key = job.model.clone();
+    let capability_checker = move |capability: &str| models::supports(&model_key, capability);
+
+    let normalizer = CompositeNormalizer::new(
+        None,
+        cfg.video_encoder_preference,
+        Some(cfg.video_max_chunk_seconds),
+        Some(cfg.video_max_chunk_bytes),
+        cfg.video_token_limit,
+        Some(planned_tokens_per_second(
+            cfg,
+            job.media_resolution.as_deref().unwrap_or("default"),
+        )),
+        Some(job.pdf_dpi),
+        Some(Box::new(capability_checker)),
+    )?
+    .with_cookies(cookies.clone())
+    .with_ytdlp_options(cfg.ytdlp_options.clone())
+    .with_silence_removal(cfg.video_silence)
+    .with_encoding(cfg.video_encode.clone())
+    .with_chunk_boundaries(cfg.video_chunk_boundaries)
+    .with_ocr(cfg.pdf_ocr)
+    .with_image_prep(cfg.image_prep);
+    let ingestor = CompositeIngestor::new()?
+        .with_media_hosts(&cfg.media_url_hosts)
+        .with_max_download(cfg.download_max_bytes);
+    Ok((ingestor, normalizer))
+}
+
+fn infer_kind_from_assets(assets: &[Asset]) -> Kind {
+    if let Some(first) = assets.first() {
+        match first.media.as_str() {
+            "video" | "subtitles" => Kind::Lecture,
+            "audio" => Kind::Audio,
+            "image" | "presentation" => Kind::Slides,
+            _ => Kind::Document,
+        }
+    } else {
+        Kind::Document
+    }
+}
+
+fn modality_for_assets(assets: &[Asset]) -> Option<String> {
+    assets.first().map(|asset| match asset.media.as_str() {
+        "video" => "video".to_string(),
+        "audio" => "audio".to_string(),
+        "pdf" => "pdf".to_string(),
+        "text" => "text".to_string(),
+        _ => "image".to_string(),
+    })
+}
+
+fn pdf_mode_to_str(mode: PdfMode) -> &'static str {
+    match mode {
+        PdfMode::Auto => "auto",
+        PdfMode::Images => "images",
+        PdfMode::Pdf => "pdf",
+        PdfMode::Text => "text",
+    }
+}
+
+fn expand_tilde(path: &Path) -> PathBuf {
+    if let Some(raw) = path.to_str() {
+        if let Some(stripped) = raw.strip_prefix("~/") {
+            if let Some(home) = dirs::home_dir() {
+                return home.join(stripped);
+            }
+        } else if raw == "~" {
+            if let Some(home) = dirs::home_dir() {
+                return home;
+            }
+        }
+    }
+    path.to_path_buf()
+}
@@ -0,0 +1,185 @@
+//! `--dry-run`: what a job would send and cost, without calling the model.
+
+use serde_json::{json, Value};
+
+use super::{build_ingestion_stack, infer_kind_from_assets, modality_for_assets, pdf_mode_to_str};
+use crate::core::{Asset, Ingestor, Job, Normalizer};
+use crate::ingest::YtDlpCookies;
+use crate::{config, cost, video};
+
+pub(super) fn run_plan(
+    cfg: &config::AppConfig,
+    job: Job,
+    cookies: &YtDlpCookies,
+    estimator: &cost::CostEstimator,
+    json_output: bool,
+) -> anyhow::Result<()> {
+    let (ingestor, mut normalizer) = build_ingestion_stack(cfg, &job, cookies)?;
+
+    normalizer.prepare(&job)?;
+    let assets = ingestor.discover(&job)?;
+    let normalized = normalizer.normalize(&assets, job.pdf_mode)?;
+    let final_kind = job.kind.unwrap_or_else(|| infer_kind_from_assets(&assets));
+    let modality = modality_for_assets(&normalized);
+    let chunks = normalizer.chunk_descriptors();
+    let estimate = estimator.plan(
+        &job.model,
+        modality.as_deref().unwrap_or("text"),
+        &normalized,
+    );
+
+    let report = json!({
+        "job": {
+            "source": job.source,
+            "recursive": job.recursive,
+            "include": job.include,
+            "exclude": job.exclude,
+            "max_depth": job.max_depth,
+            "kind": final_kind.as_str(),
+            "pdf_mode": pdf_mode_to_str(job.pdf_mode),
+            "pages": job.page_selection.as_ref().map(|value| value.to_string()),
+            "model": job.model,
+            "preset": job.preset,
+            "export": job.export,
+            "skip_existing": job.skip_existing,
+            "on_conflict": job.on_conflict.as_str(),
+            "media_resolution": job.media_resolution,
+            "format": job.format.as_str(),
+            "pdf_dpi": job.pdf_dpi,
+            "pdf_chunk_pages": job.pdf_chunk_pages,
+            "pdf_split_outline": job.pdf_split_outline,
+            "chunk_concurrency": job.chunk_concurrency,
+            "prompt_override": job.prompt_override.is_some(),
+            "vars": job.vars,
+            "language": job.language,
+            "detail": job.detail.as_str(),
+            "glossary": job.glossary.is_some(),
+            "chunk_context_lines": job.chunk_context_lines,
+            "video_mode": job.video_mode.as_str(),
+            "group": job.grouping.as_str(),
+            "diarize": job.diarize,
+        },
+        "kind": final_kind.as_str(),
+        "modality": modality,
+        "assets": assets.iter().map(asset_to_value).collect::<Vec<_>>(),
+        "normalized": normalized
+            .iter()
+            .map(asset_to_value)
+            .collect::<Vec<_>>(),
+        "chunks": chunks,
+        "estimate": estimate,
+    });
+
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_plan_human(&report, estimator.currency())?;
+    }
+    Ok(())
+}
+
+/// Seconds-to-tokens rate for planning video chunks: the rate measured by earlier
+/// `countTokens` calls at this media resolution when token counting is on, otherwise
+/// `video.tokens_per_second` (at most 100 at low resolution).
+pub(super) fn planned_tokens_per_second(cfg: &config::AppConfig, media_resolution: &str) -> f64 {
+    if cfg.count_tokens {
+        if let Some(rate) = video::TokenRates::load().get("video", media_resolution) {
+            return rate;
+        }
+    }
+    if media_resolution == "low" {
+        cfg.video_tokens_per_second.min(100.0)
+    } else {
+        cfg.video_tokens_per_second
+    }
+}
+
+pub(super) fn asset_to_value(asset: &Asset) -> Value {
+    let mut meta = Value::Null;
+    if !asset.meta.is_null() {
+        meta = asset.meta.clone();
+    }
+    json!({
+        "path": asset.path.to_string_lossy(),
+        "media": asset.media,
+        "page_index": asset.page_index,
+        "source_kind": format!("{:?}", asset.source_kind),
+        "mime": asset.mime,
+        "meta": meta,
+    })
+}
+
+pub(super) fn print_plan_human(report: &Value, currency: &cost::Currency) -> anyhow::Result<()> {
+    let job = report
+        .get("job")
+        .and_then(|v| v.as_object())
+        .cloned()
+        .unwrap_or_default();
+    let source = job
+        .get("source")
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown");
+    let kind = report
+        .get("kind")
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown");
+    let modality = report
+        .get("modality")
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown");
+    let assets = report
+        .get("assets")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let chunks_len = report
+        .get("chunks")
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.len())
+        .unwrap_or(0);
+
+    println!("Source: {}", source);
+    println!("Kind:   {}", kind);
+    println!("Modality: {}", modality);
+    println!("Assets: {}", assets.len());
+    for asset in assets.iter().take(10) {
+        let path = asset
+            .get("path")
+            .and_then(|v| v.as_str())
+            .unwrap_or("<unknown>");
+        let media = asset.get("media").and_then(|v| v.as_str()).unwrap_or("?");
+        println!("  - {} ({})", path, media);
+    }
+    if assets.len() > 10 {
+        println!("  ... {} more", assets.len() - 10);
+    }
+    println!("Chunks planned: {}", chunks_len);
+    if let Some(estimate) = report.get("estimate") {
+        let requests = estimate
+            .get("requests")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default();
+        println!(
+            "Estimated cost ({}): ~{} input + ~{} output tokens, {}",
+            estimate["model"].as_str().unwrap_or("?"),
+            estimate["input_tokens"],
+            estimate["output_tokens"],
+            currency.format(estimate["total_cost"].as_f64().unwrap_or(0.0), 4)
+        );
+        if requests.len() > 1 {
+            for request in requests.iter().take(10) {
+                println!(
+                    "  - {}: ~{} tokens, {}",
+                    request["label"].as_str().unwrap_or("?"),
+                    request["input_tokens"],
+                    currency.format(request["cost"].as_f64().unwrap_or(0.0), 4)
+                );
+            }
+            if requests.len() > 10 {
+                println!("  ... {} more", requests.len() - 10);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,293 @@
+//! Expanding the command line's sources into jobs (playlists, mirrored folders) and
+//! the summaries written over several jobs.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde_json::json;
+
+use super::{expand_tilde, JobSummary};
+use crate::batch::{JobOverrides, SourceInput};
+use crate::ingest::{
+    self, discover_files, expand_playlist, is_playlist_url, YtDlpCookies, YtDlpOptions,
+};
+use crate::utils::{self, slugify};
+
+/// A source as it will be run; playlist and channel URLs expand to one spec per video,
+/// and with `--mirror-structure` folders expand to one spec per file.
+pub(super) struct SourceSpec {
+    pub(super) source: String,
+    pub(super) label: String,
+    pub(super) output_dir: Option<PathBuf>,
+    pub(super) playlist: Option<usize>,
+    pub(super) mirror: Option<usize>,
+    pub(super) overrides: JobOverrides,
+}
+
+pub(super) struct PlaylistRun {
+    pub(super) url: String,
+    pub(super) title: String,
+    pub(super) dir: PathBuf,
+}
+
+/// How `--mirror-structure` lists the files of a folder source.
+pub(super) struct FolderWalk {
+    pub(super) recursive: bool,
+    pub(super) max_depth: Option<usize>,
+    pub(super) filter: ingest::PathFilter,
+}
+
+/// A folder expanded by `--mirror-structure`; `dir` is where its outputs are mirrored.
+pub(super) struct MirrorRun {
+    pub(super) root: PathBuf,
+    pub(super) dir: PathBuf,
+}
+
+pub(super) fn expand_sources(
+    inputs: &[SourceInput],
+    output_dir: Option<&Path>,
+    cookies: &YtDlpCookies,
+    ytdlp: &YtDlpOptions,
+    quiet: bool,
+    folder_walk: Option<&FolderWalk>,
+) -> anyhow::Result<(Vec<SourceSpec>, Vec<PlaylistRun>, Vec<MirrorRun>)> {
+    let mut specs = Vec::new();
+    let mut playlists = Vec::new();
+    let mut mirrors = Vec::new();
+    for input in inputs {
+        let source = &input.source;
+        let local = expand_tilde(Path::new(source));
+        if let Some(walk) = folder_walk.filter(|_| local.is_dir()) {
+            let mut files = discover_files(&local, walk.recursive, walk.max_depth, &walk.filter)?;
+            files.sort();
+            let dir = input
+                .overrides
+                .output_dir
+                .as_deref()
+                .or(output_dir)
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| local.clone());
+            if !quiet {
+                println!(
+                    "folder {}: {} files -> {}",
+                    local.display(),
+                    files.len(),
+                    dir.display()
+                );
+            }
+            for file in files {
+                let relative = file.strip_prefix(&local).unwrap_or(&file).to_path_buf();
+                specs.push(SourceSpec {
+                    source: file.to_string_lossy().to_string(),
+                    label: relative.to_string_lossy().to_string(),
+                    output_dir: Some(dir.join(relative.parent().unwrap_or(Path::new("")))),
+                    playlist: None,
+                    mirror: Some(mirrors.len()),
+                    overrides: input.overrides.clone(),
+                });
+            }
+            mirrors.push(MirrorRun { root: local, dir });
+            continue;
+        }
+        if !is_playlist_url(source) {
+            specs.push(SourceSpec {
+                source: source.clone(),
+                label: input.label.clone().unwrap_or_else(|| source.clone()),
+                output_dir: input.overrides.output_dir.clone(),
+                playlist: None,
+                mirror: None,
+                overrides: input.overrides.clone(),
+            });
+            continue;
+        }
+        let playlist = expand_playlist(source, cookies, ytdlp)
+            .with_context(|| format!("expanding playlist {source}"))?;
+        let title = input
+            .label
+            .clone()
+            .or(playlist.title.clone())
+            .or(playlist.id.clone())
+            .unwrap_or_else(|| source.clone());
+        let dir = input
+            .overrides
+            .output_dir
+            .as_deref()
+            .or(output_dir)
+            .unwrap_or_else(|| Path::new("."))
+            .join(slugify(&title));
+        if !quiet {
+            println!(
+                "playlist {}: {} videos -> {}",
+                title,
+                playlist.entries.len(),
+                dir.display()
+            );
+        }
+        for (position, entry) in playlist.entries.iter().enumerate() {
+            let name = entry.title.clone().unwrap_or_else(|| entry.id.clone());
+            specs.push(SourceSpec {
+                source: entry.url.clone(),
+                label: format!("{title} #{}: {name}", position + 1),
+                output_dir: Some(dir.join(format!("{:03}-{}", position + 1, slugify(&name)))),
+                playlist: Some(playlists.len()),
+                mirror: None,
+                overrides: input.overrides.clone(),
+            });
+        }
+        playlists.push(PlaylistRun {
+            url: source.clone(),
+            title,
+            dir,
+        });
+    }
+    Ok((specs, playlists, mirrors))
+}
+
+pub(super) fn write_playlist_summary(
+    playlist: &PlaylistRun,
+    group: usize,
+    specs: &[SourceSpec],
+    summaries: &[JobSummary],
+) -> anyhow::Result<()> {
+    let mut videos = Vec::new();
+    let mut total_tokens = 0;
+    let mut total_cost = 0.0;
+    for (spec, (label, output, summary, costs)) in specs.iter().zip(summaries) {
+        if spec.playlist != Some(group) {
+            continue;
+        }
+        total_tokens += summary.total_tokens;
+        total_cost += costs.total_cost;
+        videos.push(json!({
+            "label": label,
+            "url": spec.source,
+            "output": output,
+            "input_tokens": summary.total_input_tokens,
+            "output_tokens": summary.total_output_tokens,
+            "total_tokens": summary.total_tokens,
+            "estimated_cost": costs.total_cost,
+            "elapsed_seconds": summary.total_duration_seconds,
+        }));
+    }
+    if videos.is_empty() {
+        return Ok(());
+    }
+    utils::ensure_dir(&playlist.dir)?;
+    let payload = json!({
+        "playlist": playlist.title,
+        "url": playlist.url,
+        "videos": videos,
+        "total_tokens": total_tokens,
+        "estimated_cost": total_cost,
+    });
+    fs::write(
+        playlist.dir.join("playlist-summary.json"),
+        serde_json::to_string_pretty(&payload)?,
+    )?;
+    Ok(())
+}
+
+/// `recapit-index.json` and `recapit-index.md` for a `--mirror-structure` folder,
+/// linking each source file to its transcript.
+pub(super) fn write_mirror_index(
+    mirror: &MirrorRun,
+    group: usize,
+    specs: &[SourceSpec],
+    summaries: &[JobSummary],
+) -> anyhow::Result<()> {
+    let mut files = Vec::new();
+    let mut lines = vec![format!("# {}\n", mirror.root.display())];
+    let mut total_tokens = 0;
+    let mut total_cost = 0.0;
+    for (spec, (label, output, summary, costs)) in specs.iter().zip(summaries) {
+        if spec.mirror != Some(group) {
+            continue;
+        }
+        total_tokens += summary.total_tokens;
+        total_cost += costs.total_cost;
+        let relative_output = output
+            .as_deref()
+            .map(|path| path.strip_prefix(&mirror.dir).unwrap_or(path));
+        match relative_output {
+            Some(path) => lines.push(format!("- [{label}](<{}>)", path.display())),
+            None => lines.push(format!("- {label} (no transcript)")),
+        }
+        files.push(json!({
+            "source": label,
+            "output": relative_output,
+            "total_tokens": summary.total_tokens,
+            "estimated_cost": costs.total_cost,
+            "elapsed_seconds": summary.total_duration_seconds,
+        }));
+    }
+    if files.is_empty() {
+        return Ok(());
+    }
+    utils::ensure_dir(&mirror.dir)?;
+    let payload = json!({
+        "root": mirror.root,
+        "files": files,
+        "total_tokens": total_tokens,
+        "estimated_cost": total_cost,
+    });
+    fs::write(
+        mirror.dir.join("recapit-index.json"),
+        serde_json::to_string_pretty(&payload)?,
+    )?;
+    lines.push(String::new());
+    fs::write(mirror.dir.join("recapit-index.md"), lines.join("\n"))?;
+    Ok(())
+}
+
+/// `batch-summary.json` for a `--jobs` run, written to `--output-dir` or beside the jobs file.
+pub(super) fn write_batch_summary(
+    jobs_path: &Path,
+    output_dir: Option<&Path>,
+    specs: &[SourceSpec],
+    summaries: &[JobSummary],
+    outcomes: &[(&str, Option<String>)],
+) -> anyhow::Result<()> {
+    let mut jobs = Vec::new();
+    let mut total_tokens = 0;
+    let mut total_cost = 0.0;
+    let mut total_time = 0.0;
+    for ((spec, (label, output, summary, costs)), (status, error)) in
+        specs.iter().zip(summaries).zip(outcomes)
+    {
+        total_tokens += summary.total_tokens;
+        total_cost += costs.total_cost;
+        total_time += summary.total_duration_seconds;
+        jobs.push(json!({
+            "label": label,
+            "source": spec.source,
+            "status": status,
+            "error": error,
+            "output": output,
+            "input_tokens": summary.total_input_tokens,
+            "output_tokens": summary.total_output_tokens,
+            "total_tokens": summary.total_tokens,
+            "estimated_cost": costs.total_cost,
+            "elapsed_seconds": summary.total_duration_seconds,
+        }));
+    }
+    let dir = output_dir
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| jobs_path.parent().unwrap_or(Path::new(".")).to_path_buf());
+    utils::ensure_dir(&dir)?;
+    let payload = json!({
+        "jobs_file": jobs_path,
+        "jobs": jobs,
+        "completed": outcomes.iter().filter(|(status, _)| *status == "ok").count(),
+        "failed": outcomes.iter().filter(|(status, _)| *status == "failed").count(),
+        "planned": specs.len(),
+        "total_tokens": total_tokens,
+        "estimated_cost": total_cost,
+        "elapsed_seconds": total_time,
+    });
+    fs::write(
+        dir.join("batch-summary.json"),
+        serde_json::to_string_pretty(&payload)?,
+    )?;
+    Ok(())
+}
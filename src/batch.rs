@@ -0,0 +1,334 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Per-source settings that take precedence over CLI flags, presets and config.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct JobOverrides {
+    #[serde(default)]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub pdf_mode: Option<String>,
+    #[serde(default)]
+    pub pages: Option<String>,
+    #[serde(default)]
+    pub output_dir: Option<PathBuf>,
+    #[serde(default, alias = "export")]
+    pub exports: Option<Vec<String>>,
+}
+
+/// One source to run, either from the command line or a `--jobs` file.
+#[derive(Debug, Clone)]
+pub struct SourceInput {
+    pub source: String,
+    pub label: Option<String>,
+    pub overrides: JobOverrides,
+}
+
+impl SourceInput {
+    pub fn plain(source: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            label: None,
+            overrides: JobOverrides::default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BatchFile {
+    List(Vec<BatchEntry>),
+    Document {
+        #[serde(default)]
+        defaults: JobOverrides,
+        jobs: Vec<BatchEntry>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BatchEntry {
+    Source(String),
+    Job {
+        source: String,
+        #[serde(default)]
+        label: Option<String>,
+        #[serde(flatten)]
+        overrides: JobOverrides,
+    },
+}
+
+/// Parse a batch manifest (`jobs.yaml`).
+///
+/// Accepts either a bare list of sources/jobs or a mapping with `defaults` and `jobs`.
+/// Relative local sources and output directories resolve against the file's directory.
+pub fn load_jobs(path: &Path) -> Result<Vec<SourceInput>> {
+    let text = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let parsed: BatchFile =
+        serde_yaml::from_str(&text).with_context(|| format!("parsing {}", path.display()))?;
+    let (defaults, entries) = match parsed {
+        BatchFile::List(entries) => (JobOverrides::default(), entries),
+        BatchFile::Document { defaults, jobs } => (defaults, jobs),
+    };
+    if entries.is_empty() {
+        bail!("{} lists no jobs", path.display());
+    }
+    let root = path.parent().unwrap_or(Path::new("."));
+    Ok(entries
+        .into_iter()
+        .map(|entry| {
+            let (source, label, overrides) = match entry {
+                BatchEntry::Source(source) => (source, None, JobOverrides::default()),
+                BatchEntry::Job {
+                    source,
+                    label,
+                    overrides,
+                } => (source, label, overrides),
+            };
+            let mut overrides = overrides.or(&defaults);
+            overrides.output_dir = overrides.output_dir.map(|dir| resolve(root, &dir));
+            SourceInput {
+                source: resolve_source(root, &source),
+                label,
+                overrides,
+            }
+        })
+        .collect())
+}
+
+/// An unfinished chunked run to pick up with `--resume`, read from its chunk manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumePoint {
+    pub manifest: PathBuf,
+    /// The source as the run was given it.
+    pub source: String,
+    /// The output folder the run wrote its transcript and chunk responses to.
+    pub folder: PathBuf,
+    /// Chunks not marked `done`.
+    pub unfinished: usize,
+}
+
+/// Find the manifest `--resume` points at: a chunk manifest itself, or a folder
+/// holding one, such as the run's output folder or its `--output-dir`.
+pub fn load_resume(path: &Path) -> Result<ResumePoint> {
+    if path.is_file() {
+        return read_resume(path);
+    }
+    if !path.is_dir() {
+        bail!("{} does not exist", path.display());
+    }
+    let folder = path.canonicalize()?;
+    let mut candidates = vec![path.join("chunks.json")];
+    candidates.extend(manifests_in(&path.join("manifests")));
+    for dir in subfolders(path)
+        .into_iter()
+        .chain(path.parent().map(subfolders).unwrap_or_default())
+    {
+        candidates.extend(manifests_in(&dir.join("manifests")));
+    }
+    let mut found: Vec<ResumePoint> = Vec::new();
+    for candidate in candidates {
+        let Ok(point) = read_resume(&candidate) else {
+            continue;
+        };
+        let inside = point.manifest.starts_with(&folder);
+        if (inside || point.folder == folder)
+            && point.unfinished > 0
+            && !found.iter().any(|seen| seen.manifest == point.manifest)
+        {
+            found.push(point);
+        }
+    }
+    match found.len() {
+        0 => bail!(
+            "no chunk manifest with failed or pending chunks found in {}",
+            path.display()
+        ),
+        1 => Ok(found.remove(0)),
+        _ => bail!(
+            "{} holds several unfinished runs; pass one manifest to --resume:\n{}",
+            path.display(),
+            found
+                .iter()
+                .map(|point| format!("  {}", point.manifest.display()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+    }
+}
+
+fn read_resume(path: &Path) -> Result<ResumePoint> {
+    let text = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let manifest: Value =
+        serde_json::from_str(&text).with_context(|| format!("parsing {}", path.display()))?;
+    let field = |key: &str| manifest.get(key).and_then(Value::as_str);
+    let (Some(source), Some(folder)) = (field("job_source"), field("output_base")) else {
+        bail!(
+            "{} does not record the run it belongs to; it was written without save_intermediates or before --resume existed",
+            path.display()
+        );
+    };
+    let chunks = manifest
+        .get("chunks")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    // Finished chunks are reused from their saved responses; without them a resume
+    // would send every chunk again.
+    if !chunks
+        .iter()
+        .any(|chunk| chunk.get("response_path").is_some_and(Value::is_string))
+    {
+        bail!(
+            "{} was written without save_intermediates, so no chunk responses were kept to resume from",
+            path.display()
+        );
+    }
+    let unfinished = chunks
+        .iter()
+        .filter(|chunk| chunk.get("status").and_then(Value::as_str) != Some("done"))
+        .count();
+    Ok(ResumePoint {
+        manifest: path.canonicalize()?,
+        source: source.to_string(),
+        folder: PathBuf::from(folder),
+        unfinished,
+    })
+}
+
+fn manifests_in(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn subfolders(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl JobOverrides {
+    fn or(self, defaults: &JobOverrides) -> JobOverrides {
+        JobOverrides {
+            kind: self.kind.or(defaults.kind.clone()),
+            model: self.model.or(defaults.model.clone()),
+            format: self.format.or(defaults.format.clone()),
+            pdf_mode: self.pdf_mode.or(defaults.pdf_mode.clone()),
+            pages: self.pages.or(defaults.pages.clone()),
+            output_dir: self.output_dir.or(defaults.output_dir.clone()),
+            exports: self.exports.or(defaults.exports.clone()),
+        }
+    }
+}
+
+fn resolve(root: &Path, path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        root.join(path)
+    }
+}
+
+fn resolve_source(root: &Path, source: &str) -> String {
+    if source.contains("://") {
+        return source.to_string();
+    }
+    let expanded = if let Some(rest) = source.strip_prefix("~/") {
+        dirs::home_dir()
+            .map(|home| home.join(rest))
+            .unwrap_or_else(|| PathBuf::from(source))
+    } else {
+        PathBuf::from(source)
+    };
+    resolve(root, &expanded).to_string_lossy().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_list_and_document_forms() {
+        let dir = std::env::temp_dir().join("recapit-batch-test");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("jobs.yaml");
+        fs::write(
+            &path,
+            "defaults:\n  model: gemini-2.5-flash\n  exports: [srt]\njobs:\n  - lectures/week1.mp4\n  - source: https://youtu.be/abc\n    kind: lecture\n    export: [vtt]\n    output_dir: out\n",
+        )
+        .unwrap();
+        let jobs = load_jobs(&path).unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(
+            jobs[0].source,
+            dir.join("lectures/week1.mp4").to_string_lossy()
+        );
+        assert_eq!(jobs[0].overrides.model.as_deref(), Some("gemini-2.5-flash"));
+        assert_eq!(jobs[1].source, "https://youtu.be/abc");
+        assert_eq!(jobs[1].overrides.exports, Some(vec!["vtt".to_string()]));
+        assert_eq!(jobs[1].overrides.output_dir, Some(dir.join("out")));
+
+        fs::write(&path, "- a.pdf\n- source: b.pdf\n  pages: 1-3\n").unwrap();
+        let jobs = load_jobs(&path).unwrap();
+        assert_eq!(jobs[1].overrides.pages.as_deref(), Some("1-3"));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn finds_the_manifest_of_an_unfinished_run() {
+        let dir = std::env::temp_dir().join("recapit-resume-test");
+        let out = dir.join("lecture-transcribed");
+        fs::create_dir_all(&out).unwrap();
+        fs::create_dir_all(dir.join("lecture-mp4").join("manifests")).unwrap();
+        let folder = out.canonicalize().unwrap();
+        let manifest = dir
+            .join("lecture-mp4")
+            .join("manifests")
+            .join("lecture.json");
+        let chunk = |status: &str| serde_json::json!({"status": status, "response_path": folder.join("full-response/chunks/a.txt")});
+        let body = serde_json::json!({
+            "job_source": "/videos/lecture.mp4",
+            "output_base": folder,
+            "chunks": [chunk("done"), chunk("failed"), chunk("pending")],
+        });
+        fs::write(&manifest, body.to_string()).unwrap();
+
+        // The manifest itself, the output folder, and the --output-dir above both.
+        for path in [&manifest, &out, &dir] {
+            let point = load_resume(path).unwrap();
+            assert_eq!(point.source, "/videos/lecture.mp4");
+            assert_eq!(point.folder, folder);
+            assert_eq!(point.unfinished, 2);
+        }
+
+        let body = serde_json::json!({
+            "job_source": "/videos/lecture.mp4",
+            "output_base": folder,
+            "chunks": [{"status": "failed", "response_path": null}],
+        });
+        fs::write(&manifest, body.to_string()).unwrap();
+        assert!(load_resume(&manifest).is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
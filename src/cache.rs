@@ -0,0 +1,384 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::core::Asset;
+use crate::utils::ensure_dir;
+use crate::video::sha256sum;
+
+/// Content-addressed store of model responses.
+///
+/// The key covers the bytes of every normalized asset plus the model, instruction and
+/// any other settings that change the output, so a hit is safe to reuse verbatim.
+#[derive(Clone)]
+pub struct TranscriptCache {
+    root: PathBuf,
+}
+
+impl Default for TranscriptCache {
+    fn default() -> Self {
+        Self { root: cache_root() }
+    }
+}
+
+pub fn cache_root() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("recapit")
+        .join("responses")
+}
+
+impl TranscriptCache {
+    pub fn key(
+        &self,
+        assets: &[Asset],
+        model: &str,
+        instruction: &str,
+        settings: &[&str],
+    ) -> Result<String> {
+        let mut hasher = Sha256::new();
+        hasher.update(model.as_bytes());
+        hasher.update([0]);
+        hasher.update(Sha256::digest(instruction.as_bytes()));
+        for setting in settings {
+            hasher.update(setting.as_bytes());
+            hasher.update([0]);
+        }
+        for asset in assets {
+            // Remote pass-through assets (YouTube URLs) have no local bytes; their URL is the identity.
+            let identity = if asset.path.is_file() {
+                sha256sum(&asset.path)?
+            } else {
+                asset.path.to_string_lossy().to_string()
+            };
+            hasher.update(identity.as_bytes());
+            hasher.update(asset.page_index.unwrap_or(u32::MAX).to_le_bytes());
+            // Hints sent alongside the asset change the request, so they belong in the key.
+            if let Some(ocr) = asset.meta.get("ocr_text") {
+                hasher.update(ocr.to_string().as_bytes());
+            }
+        }
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(&key[..2]).join(format!("{key}.txt"))
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let path = self.path(key);
+        let text = fs::read_to_string(&path)
+            .ok()
+            .filter(|text| !text.trim().is_empty())?;
+        touch(&path);
+        Some(text)
+    }
+
+    pub fn put(&self, key: &str, text: &str) -> Result<()> {
+        let path = self.path(key);
+        ensure_dir(path.parent().unwrap_or(Path::new(".")))?;
+        // Write then rename so an interrupted run never leaves a truncated hit behind.
+        let partial = path.with_extension("partial");
+        fs::write(&partial, text).with_context(|| format!("writing {}", partial.display()))?;
+        fs::rename(&partial, &path)?;
+        Ok(())
+    }
+}
+
+/// Mark a cache entry as used now, so eviction keeps it over entries nobody has
+/// read in a while. Access times are unreliable (`noatime`, `relatime`), so this
+/// moves the modification time instead.
+pub fn touch(path: &Path) {
+    let _ = fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+}
+
+/// One folder recapit caches into, and how deep its entries sit: responses are
+/// `<root>/<prefix>/<key>.txt`, everything else is one file or folder per entry.
+#[derive(Debug, Clone)]
+pub struct CacheArea {
+    pub name: &'static str,
+    pub root: PathBuf,
+    depth: usize,
+}
+
+/// A file or folder that is removed as a whole.
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub area: &'static str,
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub last_used: SystemTime,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AreaStats {
+    pub name: &'static str,
+    pub root: PathBuf,
+    pub entries: usize,
+    pub bytes: u64,
+    /// Days since the least recently used entry was touched.
+    pub oldest_days: Option<u64>,
+}
+
+/// Every on-disk cache recapit keeps, sized and evicted together.
+///
+/// Entries are ranked by their newest access or modification time, so
+/// `max_bytes` eviction drops the least recently used entries first whichever
+/// cache they belong to.
+#[derive(Debug, Clone)]
+pub struct CacheManager {
+    areas: Vec<CacheArea>,
+}
+
+impl Default for CacheManager {
+    fn default() -> Self {
+        let mut areas = vec![CacheArea {
+            name: "responses",
+            root: cache_root(),
+            depth: 2,
+        }];
+        areas.extend(
+            crate::ingest::cache_dirs()
+                .into_iter()
+                .map(|(name, root)| CacheArea {
+                    name,
+                    root,
+                    depth: 1,
+                }),
+        );
+        Self { areas }
+    }
+}
+
+impl CacheManager {
+    /// Only the transcription responses.
+    pub fn responses() -> Self {
+        let mut manager = Self::default();
+        manager.areas.truncate(1);
+        manager
+    }
+
+    /// Every entry, least recently used first.
+    pub fn entries(&self) -> Vec<CacheEntry> {
+        let mut entries = Vec::new();
+        for area in &self.areas {
+            collect_entries(area, &area.root, area.depth, &mut entries);
+        }
+        entries.sort_by_key(|entry| entry.last_used);
+        entries
+    }
+
+    pub fn stats(&self) -> Vec<AreaStats> {
+        let entries = self.entries();
+        let now = SystemTime::now();
+        self.areas
+            .iter()
+            .map(|area| {
+                let mine: Vec<&CacheEntry> = entries
+                    .iter()
+                    .filter(|entry| entry.area == area.name)
+                    .collect();
+                AreaStats {
+                    name: area.name,
+                    root: area.root.clone(),
+                    entries: mine.len(),
+                    bytes: mine.iter().map(|entry| entry.bytes).sum(),
+                    oldest_days: mine.first().map(|entry| {
+                        now.duration_since(entry.last_used)
+                            .unwrap_or_default()
+                            .as_secs()
+                            / 86_400
+                    }),
+                }
+            })
+            .collect()
+    }
+
+    /// The entries to remove: everything unused for `older_than`, then the least
+    /// recently used of the rest until what remains fits in `max_bytes`.
+    pub fn plan(&self, older_than: Option<Duration>, max_bytes: Option<u64>) -> Vec<CacheEntry> {
+        plan_eviction(self.entries(), SystemTime::now(), older_than, max_bytes)
+    }
+
+    /// Delete `entries`, returning the bytes freed. Entries that vanished in the
+    /// meantime are skipped.
+    pub fn remove(&self, entries: &[CacheEntry]) -> Result<u64> {
+        let mut freed = 0;
+        for entry in entries {
+            let result = if entry.path.is_dir() {
+                fs::remove_dir_all(&entry.path)
+            } else {
+                fs::remove_file(&entry.path)
+            };
+            match result {
+                Ok(()) => freed += entry.bytes,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    return Err(err).with_context(|| format!("removing {}", entry.path.display()))
+                }
+            }
+        }
+        Ok(freed)
+    }
+}
+
+fn plan_eviction(
+    entries: Vec<CacheEntry>,
+    now: SystemTime,
+    older_than: Option<Duration>,
+    max_bytes: Option<u64>,
+) -> Vec<CacheEntry> {
+    let (mut evict, keep): (Vec<_>, Vec<_>) = entries.into_iter().partition(|entry| {
+        older_than.is_some_and(|age| now.duration_since(entry.last_used).unwrap_or_default() > age)
+    });
+    let mut total: u64 = keep.iter().map(|entry| entry.bytes).sum();
+    for entry in keep {
+        if max_bytes.is_none_or(|limit| total <= limit) {
+            break;
+        }
+        total -= entry.bytes;
+        evict.push(entry);
+    }
+    evict
+}
+
+fn collect_entries(area: &CacheArea, dir: &Path, depth: usize, out: &mut Vec<CacheEntry>) {
+    let Ok(children) = fs::read_dir(dir) else {
+        return;
+    };
+    for child in children.flatten() {
+        let path = child.path();
+        if depth > 1 && path.is_dir() {
+            collect_entries(area, &path, depth - 1, out);
+            continue;
+        }
+        let (bytes, last_used) = usage(&path);
+        out.push(CacheEntry {
+            area: area.name,
+            path,
+            bytes,
+            last_used,
+        });
+    }
+}
+
+/// Total size and newest access or modification time under `path`.
+fn usage(path: &Path) -> (u64, SystemTime) {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return (0, SystemTime::UNIX_EPOCH);
+    };
+    let touched = [meta.accessed(), meta.modified()]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    if !meta.is_dir() {
+        return (meta.len(), touched);
+    }
+    fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|child| usage(&child.path()))
+        .fold((0, touched), |(bytes, newest), (size, used)| {
+            (bytes + size, newest.max(used))
+        })
+}
+
+/// A size such as `10G`, `500MB`, or `1048576`; suffixes are powers of 1024.
+pub fn parse_size(text: &str) -> Result<u64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number
+        .parse()
+        .with_context(|| format!("{text:?} is not a size such as 10G or 500M"))?;
+    let shift = match unit
+        .trim()
+        .to_ascii_uppercase()
+        .trim_end_matches("IB")
+        .trim_end_matches('B')
+    {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => anyhow::bail!("{text:?} has an unknown unit; use K, M, G, or T"),
+    };
+    Ok((number * (1u64 << shift) as f64) as u64)
+}
+
+/// `bytes` with a binary unit, such as `1.5 GB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eviction_drops_stale_then_least_recent() {
+        let now = SystemTime::now();
+        let entry = |name: &str, bytes, days_ago: u64| CacheEntry {
+            area: "url",
+            path: PathBuf::from(name),
+            bytes,
+            last_used: now - Duration::from_secs(days_ago * 86_400),
+        };
+        // Oldest first, as `entries` returns them.
+        let entries = vec![
+            entry("a", 10, 40),
+            entry("b", 30, 5),
+            entry("c", 30, 2),
+            entry("d", 30, 1),
+        ];
+        let names = |plan: Vec<CacheEntry>| {
+            plan.into_iter()
+                .map(|entry| entry.path.display().to_string())
+                .collect::<Vec<_>>()
+        };
+        let month = Some(Duration::from_secs(30 * 86_400));
+        assert_eq!(
+            names(plan_eviction(entries.clone(), now, month, None)),
+            ["a"]
+        );
+        assert_eq!(
+            names(plan_eviction(entries.clone(), now, month, Some(60))),
+            ["a", "b"]
+        );
+        assert_eq!(
+            names(plan_eviction(entries.clone(), now, None, Some(100))),
+            Vec::<String>::new()
+        );
+        assert_eq!(names(plan_eviction(entries, now, None, Some(0))).len(), 4);
+
+        assert_eq!(parse_size("10G").unwrap(), 10 << 30);
+        assert_eq!(parse_size("1.5 MiB").unwrap(), 3 << 19);
+        assert_eq!(parse_size("2048").unwrap(), 2048);
+        assert!(parse_size("5 parsecs").is_err());
+        assert_eq!(format_size(3 << 19), "1.5 MB");
+    }
+}
@@ -1,7 +1,8 @@
 use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use std::io::IsTerminal;
 use std::path::PathBuf;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(
     name = "recapit",
     version,
@@ -9,11 +10,17 @@ use std::path::PathBuf;
 )]
 pub struct Cli {
     /// Primary action: transcribe/convert the given source(s) unless a subcommand is used
-    #[arg(required_unless_present = "cmd", num_args = 1.., value_name = "SOURCE")]
+    #[arg(num_args = 1.., value_name = "SOURCE")]
     pub source: Vec<String>,
 
     #[arg(short = 'o', long)]
     pub output_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Batch file (YAML) listing sources with per-job kind, model, format, output_dir, and exports"
+    )]
+    pub jobs: Option<PathBuf>,
     #[arg(long, default_value = "auto")]
     pub kind: String,
     #[arg(long, default_value = "auto")]
@@ -28,18 +35,250 @@ pub struct Cli {
         help = "DPI for PDF -> PNG rasterization (default 200)"
     )]
     pub pdf_dpi: Option<u32>,
+    #[arg(
+        long = "pdf-chunk-pages",
+        help = "Split PDFs longer than N pages into N-page chunks transcribed separately"
+    )]
+    pub pdf_chunk_pages: Option<u32>,
+    #[arg(
+        long = "pdf-split-outline",
+        action = ArgAction::SetTrue,
+        help = "Split PDFs with an outline into one section per top-level bookmark, and write an index of the sections"
+    )]
+    pub pdf_split_outline: bool,
+    #[arg(
+        long = "chunk-concurrency",
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Transcribe up to N chunks of a long video or PDF at once; output stays in chunk order [default: 1]"
+    )]
+    pub chunk_concurrency: Option<u32>,
+    #[arg(
+        long = "chunk-boundaries",
+        help = "Where to split long recordings: fixed lengths, or snapped back to the nearest silence or scene change"
+    )]
+    pub chunk_boundaries: Option<ChunkBoundaryArg>,
+    #[arg(
+        long = "video-mode",
+        help = "Send videos in full, as distinct slide frames only, both (hybrid), or just their audio"
+    )]
+    pub video_mode: Option<VideoModeArg>,
+    #[arg(
+        long = "remove-silence",
+        action = ArgAction::SetTrue,
+        help = "Cut long silences out of recordings before chunking; subtitle and chapter times still match the original"
+    )]
+    pub remove_silence: bool,
+    #[arg(
+        long = "youtube-captions",
+        action = ArgAction::SetTrue,
+        help = "Send a YouTube video's captions and sampled frames instead of uploading the video (falls back when it has none)"
+    )]
+    pub youtube_captions: bool,
+    #[arg(
+        long = "group",
+        value_enum,
+        help = "Treat a folder's images as photos of one document, ordered by EXIF time or name"
+    )]
+    pub group: Option<GroupArg>,
+    #[arg(
+        long = "cookies-from-browser",
+        value_name = "BROWSER[:PROFILE]",
+        help = "Load cookies from a browser for yt-dlp downloads (Panopto, Kaltura, Zoom, private YouTube)"
+    )]
+    pub cookies_from_browser: Option<String>,
+    #[arg(
+        long = "cookies",
+        value_name = "FILE",
+        help = "Netscape-format cookie file passed to yt-dlp"
+    )]
+    pub cookies: Option<PathBuf>,
+    #[arg(
+        long = "keep-extracted",
+        action = ArgAction::SetTrue,
+        help = "Keep files unpacked from .zip/.tar/.tar.gz sources after the run"
+    )]
+    pub keep_extracted: bool,
+    #[arg(
+        long = "mirror-structure",
+        action = ArgAction::SetTrue,
+        help = "Transcribe each file of a folder source separately, writing outputs next to the files (or under --output-dir by relative path) plus recapit-index.json/.md"
+    )]
+    pub mirror_structure: bool,
     #[arg(long)]
     pub model: Option<String>,
     #[arg(long)]
     pub format: Option<OutputFormatArg>,
+    #[arg(
+        long,
+        value_name = "TEXT",
+        conflicts_with = "prompt_file",
+        help = "Replace the template instruction for this run ({{PREAMBLE}} is still substituted)"
+    )]
+    pub prompt: Option<String>,
+    #[arg(
+        long = "prompt-file",
+        value_name = "PATH",
+        help = "Read the replacement instruction from a file ({{PREAMBLE}} is still substituted)"
+    )]
+    pub prompt_file: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Sampling temperature (0-2); lower is more literal [default: the model's]"
+    )]
+    pub temperature: Option<f32>,
+    #[arg(
+        long = "top-p",
+        help = "Nucleus sampling cutoff (0-1) [default: the model's]"
+    )]
+    pub top_p: Option<f32>,
+    #[arg(
+        long = "max-output-tokens",
+        value_name = "N",
+        help = "Cap the tokens generated per request"
+    )]
+    pub max_output_tokens: Option<u32>,
+    #[arg(
+        long = "stop",
+        value_name = "TEXT",
+        help = "Stop generating at this sequence (repeatable, up to 5)"
+    )]
+    pub stop_sequences: Vec<String>,
+    #[arg(
+        long,
+        value_enum,
+        help = "How much to keep: a brief bullet summary, the standard transcript, or everything verbatim"
+    )]
+    pub detail: Option<DetailArg>,
+    #[arg(
+        long,
+        value_name = "CODE",
+        help = "Write transcripts and summaries in this language (e.g. es, de, ja), translating if needed"
+    )]
+    pub language: Option<String>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Terms file (names, notation, acronyms) added to every prompt so they are spelled consistently"
+    )]
+    pub glossary: Option<PathBuf>,
+    #[arg(
+        long = "var",
+        value_name = "NAME=VALUE",
+        help = "Set a prompt variable used as {{NAME}} in templates (repeatable)"
+    )]
+    pub vars: Vec<String>,
     #[arg(long, action = ArgAction::SetTrue)]
     pub recursive: bool,
     #[arg(long = "no-recursive", action = ArgAction::SetTrue)]
     pub no_recursive: bool,
+    #[arg(
+        long,
+        value_name = "GLOB",
+        help = "Only take files from folder sources whose relative path matches (repeatable)"
+    )]
+    pub include: Vec<String>,
+    #[arg(
+        long,
+        value_name = "GLOB",
+        help = "Skip files in folder sources whose relative path matches (repeatable)"
+    )]
+    pub exclude: Vec<String>,
+    #[arg(
+        long = "max-depth",
+        value_name = "N",
+        help = "With --recursive, descend at most N subfolder levels"
+    )]
+    pub max_depth: Option<usize>,
     #[arg(long, default_value_t = true)]
     pub skip_existing: bool,
+    #[arg(
+        long = "on-conflict",
+        value_enum,
+        help = "When an output already exists: prompt (default on a terminal), overwrite, rename, skip, or fail"
+    )]
+    pub on_conflict: Option<ConflictArg>,
     #[arg(long)]
     pub export: Vec<String>,
+    #[arg(
+        long = "embed-chapters",
+        action = ArgAction::SetTrue,
+        help = "With --export chapters, remux the chapters (and VTT subtitles) into a copy of the normalized MP4"
+    )]
+    pub embed_chapters: bool,
+    #[arg(
+        long,
+        action = ArgAction::SetTrue,
+        help = "After transcribing, run a synthesis pass that writes <name>-summary alongside the transcript"
+    )]
+    pub synthesize: bool,
+    #[arg(
+        long,
+        action = ArgAction::SetTrue,
+        help = "Label speakers (Speaker 1:, or names when stated) in audio and video transcripts"
+    )]
+    pub diarize: bool,
+    #[arg(
+        long = "no-cache",
+        action = ArgAction::SetTrue,
+        help = "Always call the model instead of reusing a cached transcript of identical input"
+    )]
+    pub no_cache: bool,
+    #[arg(
+        long = "continue-on-error",
+        action = ArgAction::SetTrue,
+        help = "Finish the remaining chunks when one fails, then exit non-zero listing the failures"
+    )]
+    pub continue_on_error: bool,
+    #[arg(
+        long,
+        value_name = "MANIFEST|DIR",
+        conflicts_with_all = ["source", "jobs"],
+        help = "Rerun only the failed and pending chunks of an earlier run, from its chunk manifest or output folder"
+    )]
+    pub resume: Option<PathBuf>,
+    #[arg(
+        long = "delete-uploads",
+        action = ArgAction::SetTrue,
+        help = "Delete Files API uploads after the run instead of keeping them for reuse until they expire"
+    )]
+    pub delete_uploads: bool,
+    #[arg(
+        long = "max-cost",
+        value_name = "USD",
+        help = "Stop before a request would take the run's estimated cost past this many dollars"
+    )]
+    pub max_cost: Option<f64>,
+    #[arg(
+        long = "count-tokens",
+        action = ArgAction::SetTrue,
+        help = "Measure each request with countTokens before sending it (exact budget checks and token pacing; also count_tokens in recapit.yaml)"
+    )]
+    pub count_tokens: bool,
+    #[arg(
+        long = "refresh-models",
+        action = ArgAction::SetTrue,
+        help = "Fetch the model listing again instead of using the copy cached for up to a week"
+    )]
+    pub refresh_models: bool,
+    #[arg(
+        long = "fallback-model",
+        value_name = "MODEL",
+        help = "Model to retry a failed request with, in order; repeatable (replaces model_fallbacks)"
+    )]
+    pub fallback_model: Vec<String>,
+    #[arg(
+        long = "convert-model",
+        value_name = "MODEL",
+        help = "Model for export conversions and repairs (models.convert; defaults to the transcription model)"
+    )]
+    pub convert_model: Option<String>,
+    #[arg(
+        long = "synthesize-model",
+        value_name = "MODEL",
+        help = "Model for the --synthesize pass (models.synthesize; defaults to the transcription model)"
+    )]
+    pub synthesize_model: Option<String>,
     #[arg(long = "to", help = "Convert instead of transcribe: markdown|json")]
     pub to: Option<ConversionTarget>,
     #[arg(
@@ -56,16 +295,66 @@ pub struct Cli {
         help = "Preset profile (basic, speed [pdf_mode=images], quality [pdf_mode=pdf], plus entries from recapit.yaml)"
     )]
     pub preset: String,
+    #[arg(
+        long,
+        value_name = "NAME",
+        env = "RECAPIT_PROFILE",
+        help = "Apply a named profile from the profiles section of recapit.yaml"
+    )]
+    pub profile: Option<String>,
     #[arg(long)]
     pub config: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        env = "GEMINI_API_KEY_FILE",
+        help = "Read the Gemini API key (or several, one per line) from a file"
+    )]
+    pub api_key_file: Option<PathBuf>,
     #[arg(long)]
     pub media_resolution: Option<String>,
+    #[arg(
+        long,
+        value_name = "DIR",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "recapit-recording",
+        conflicts_with = "replay",
+        help = "Save every generateContent response under DIR for --replay [default: recapit-recording]; implies --no-cache"
+    )]
+    pub record: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Answer generateContent requests from a --record folder instead of calling the API (no key or uploads needed); implies --no-cache"
+    )]
+    pub replay: Option<PathBuf>,
+    #[arg(
+        long = "no-llm",
+        action = ArgAction::SetTrue,
+        conflicts_with_all = ["record", "replay", "dry_run", "to"],
+        help = "Only download, extract, and normalize (page images, normalized video, chunks, manifests), writing <name>-staged.json per job; needs no API key"
+    )]
+    pub no_llm: bool,
     #[arg(long, action = ArgAction::SetTrue, help = "Plan normalization only (no Gemini calls)")]
     pub dry_run: bool,
     #[arg(long = "json", action = ArgAction::SetTrue, help = "Machine-readable output for --dry-run")]
     pub json: bool,
-    #[arg(long, action = ArgAction::SetTrue, help = "Suppress TUI/progress and final summary")]
+    #[arg(long, action = ArgAction::SetTrue, help = "Suppress the TUI and final summary; progress is written to stderr as JSON lines unless --progress none")]
     pub quiet: bool,
+    #[arg(
+        long = "log-file",
+        value_name = "FILE",
+        env = "RECAPIT_LOG_FILE",
+        help = "Append every log line to FILE as JSON, with the job and chunk it belongs to (log_file in recapit.yaml)"
+    )]
+    pub log_file: Option<PathBuf>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Progress display: tui, plain (periodic status lines on stderr), json (newline-delimited JSON on stderr), or none [default: tui; plain when stdout is not a terminal; json with --quiet]"
+    )]
+    pub progress: Option<ProgressMode>,
     #[arg(long, action = ArgAction::SetTrue, help = "Write run metadata (summary, events) alongside transcript in an output folder")]
     pub save_metadata: bool,
 
@@ -73,8 +362,74 @@ pub struct Cli {
     pub cmd: Option<Command>,
 }
 
-#[derive(Subcommand, Debug)]
+#[derive(Subcommand, Debug, Clone)]
 pub enum Command {
+    /// Answer a question about a source, transcribing it first unless a prior transcript exists
+    Ask {
+        #[arg(value_name = "SOURCE")]
+        source: String,
+        #[arg(value_name = "QUESTION")]
+        question: String,
+        #[arg(
+            long,
+            action = ArgAction::SetTrue,
+            help = "Transcribe again even if an earlier transcript of the source exists"
+        )]
+        refresh: bool,
+        /// Cache a large transcript with Gemini context caching so follow-up questions cost less
+        #[arg(long, action = ArgAction::SetTrue)]
+        cache_context: bool,
+    },
+    /// Watch a folder and transcribe new files as they appear
+    Watch {
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+        #[arg(long, action = ArgAction::SetTrue, help = "Watch subfolders too")]
+        recursive: bool,
+        #[arg(
+            long,
+            action = ArgAction::SetTrue,
+            help = "Also transcribe files already in the folder at startup"
+        )]
+        existing: bool,
+        #[arg(
+            long = "settle-seconds",
+            default_value_t = 5,
+            help = "Wait until a file's size is unchanged for this long before transcribing it"
+        )]
+        settle_seconds: u64,
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "NDJSON results log (default <DIR>/.recapit-watch.ndjson)"
+        )]
+        log: Option<PathBuf>,
+    },
+    /// Serve an HTTP API for submitting jobs and following their progress
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8787", value_name = "ADDR")]
+        listen: std::net::SocketAddr,
+        #[arg(
+            long = "max-concurrent",
+            default_value_t = 1,
+            help = "Jobs allowed to run at the same time; the rest wait in a queue"
+        )]
+        max_concurrent: usize,
+        #[arg(
+            long,
+            env = "RECAPIT_SERVE_TOKEN",
+            help = "Require this bearer token on every request"
+        )]
+        token: Option<String>,
+    },
+    /// List past jobs from the run history
+    History {
+        /// Only jobs finished within this long (e.g. 7d, 12h)
+        #[arg(long, value_name = "AGE", value_parser = humantime::parse_duration)]
+        since: Option<std::time::Duration>,
+        #[arg(long = "json", action = ArgAction::SetTrue)]
+        json: bool,
+    },
     /// Cost and telemetry reports
     Report {
         #[command(subcommand)]
@@ -85,6 +440,54 @@ pub enum Command {
         #[command(subcommand)]
         command: CleanupCommand,
     },
+    /// Inspect the on-disk caches
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    /// Sign in to Google Drive with your own account
+    Drive {
+        #[command(subcommand)]
+        command: DriveCommand,
+    },
+    /// Create, inspect, and check recapit.yaml
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// List Gemini models with their context size, modalities, rate limits, and pricing
+    Models {
+        #[arg(long, action = ArgAction::SetTrue, help = "Print the catalog as JSON")]
+        json: bool,
+        #[arg(
+            long,
+            action = ArgAction::SetTrue,
+            help = "Include models that cannot generate content (embeddings, AQA)"
+        )]
+        all: bool,
+        #[arg(
+            long,
+            action = ArgAction::SetTrue,
+            help = "Skip the models endpoint and show only the built-in tables"
+        )]
+        local: bool,
+    },
+    /// Check external tools, API keys, the cache folder, and video encoders
+    Doctor,
+    /// Print a tab-completion script for SHELL
+    Completions {
+        #[arg(value_enum, value_name = "SHELL")]
+        shell: clap_complete::Shell,
+    },
+    /// Print a man page generated from the command-line definition
+    Manpage {
+        #[arg(
+            long,
+            value_name = "DIR",
+            help = "Write recapit.1 and a recapit-<command>.1 page per subcommand into DIR"
+        )]
+        dir: Option<PathBuf>,
+    },
 }
 
 #[derive(Clone, Debug, ValueEnum)]
@@ -100,31 +503,171 @@ pub enum ConversionSource {
     Markdown,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ProgressMode {
+    Tui,
+    Plain,
+    Json,
+    None,
+}
+
+impl Cli {
+    pub fn progress_mode(&self) -> ProgressMode {
+        self.progress.unwrap_or(if self.quiet {
+            ProgressMode::Json
+        } else if !std::io::stdout().is_terminal() {
+            // CI logs and `nohup` sessions cannot show the raw-mode dashboard.
+            ProgressMode::Plain
+        } else {
+            ProgressMode::Tui
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ChunkBoundaryArg {
+    Fixed,
+    Silence,
+    Scene,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum VideoModeArg {
+    Full,
+    Slides,
+    Hybrid,
+    Audio,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ConflictArg {
+    Prompt,
+    Overwrite,
+    Rename,
+    Skip,
+    Fail,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum DetailArg {
+    Brief,
+    Standard,
+    Verbatim,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum GroupArg {
+    Separate,
+    ImagesAsDocument,
+}
+
 #[derive(Clone, Debug, ValueEnum)]
 pub enum OutputFormatArg {
     Markdown,
     Latex,
+    Org,
+    Rst,
+    Json,
 }
 
-#[derive(Subcommand, Debug)]
+#[derive(Subcommand, Debug, Clone)]
 pub enum ReportCommand {
-    /// Summarize run costs from run-summary.json
+    /// Summarize run costs from run-summary.json, or from the run history with --aggregate
     Cost {
         #[arg(short = 'i', long, default_value = "run-summary.json")]
         input: PathBuf,
         #[arg(long = "json", action = ArgAction::SetTrue)]
         json: bool,
+        /// Total every job in the run history instead of reading one summary file
+        #[arg(long, action = ArgAction::SetTrue, conflicts_with = "input")]
+        aggregate: bool,
+        /// Add a table of what each chunk cost (requests, tokens, upload, retries, latency)
+        #[arg(long, action = ArgAction::SetTrue, conflicts_with = "aggregate")]
+        per_chunk: bool,
+        /// With --aggregate, only jobs finished within this long (e.g. 30d)
+        #[arg(long, value_name = "AGE", value_parser = humantime::parse_duration, requires = "aggregate")]
+        since: Option<std::time::Duration>,
     },
 }
 
-#[derive(Subcommand, Debug)]
+#[derive(Subcommand, Debug, Clone)]
+pub enum CacheCommand {
+    /// Size, entry count, and oldest entry of each cache
+    Stats {
+        #[arg(long = "json", action = ArgAction::SetTrue)]
+        json: bool,
+    },
+}
+
+fn parse_size(text: &str) -> Result<u64, String> {
+    crate::cache::parse_size(text).map_err(|err| format!("{err:#}"))
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum DriveCommand {
+    /// Sign in through the browser and cache the token for drive:// and share-link sources
+    Login {
+        /// OAuth client secrets JSON for a Desktop app client (overrides `drive` in recapit.yaml)
+        #[arg(long, value_name = "FILE")]
+        client_secrets: Option<PathBuf>,
+        /// Print the sign-in link instead of opening a browser
+        #[arg(long = "no-browser", action = ArgAction::SetTrue)]
+        no_browser: bool,
+    },
+    /// Remove the cached Drive sign-in
+    Logout,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommand {
+    /// Write a commented starter recapit.yaml
+    Init {
+        #[arg(value_name = "PATH", default_value = "recapit.yaml")]
+        path: PathBuf,
+        #[arg(long, action = ArgAction::SetTrue, help = "Replace an existing file")]
+        force: bool,
+    },
+    /// Print the settings in effect after merging recapit.yaml, the profile, and environment variables
+    Show {
+        #[arg(long = "json", action = ArgAction::SetTrue)]
+        json: bool,
+    },
+    /// Check recapit.yaml for unknown keys, invalid values, and missing files
+    Validate,
+}
+
+#[derive(Subcommand, Debug, Clone)]
 pub enum CleanupCommand {
-    /// Remove the global recapit cache directory
+    /// Remove the global recapit cache directory, or trim the caches with --older-than/--max-size
     Cache {
         #[arg(long = "dry-run", action = ArgAction::SetTrue)]
         dry_run: bool,
         #[arg(long = "yes", action = ArgAction::SetTrue)]
         yes: bool,
+        /// Only remove cached transcription responses, keeping downloads and extractions
+        #[arg(long = "transcripts", action = ArgAction::SetTrue)]
+        transcripts: bool,
+        /// Remove entries not used for this long (e.g. 30d, 12h)
+        #[arg(long, value_name = "AGE", value_parser = humantime::parse_duration)]
+        older_than: Option<std::time::Duration>,
+        /// Remove least recently used entries until the caches fit in this size (e.g. 10G)
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        max_size: Option<u64>,
+    },
+    /// List uploads in the Gemini Files API and delete stale ones
+    Remote {
+        /// Only files uploaded longer ago than this (e.g. 6h, 2d)
+        #[arg(long, value_name = "AGE", value_parser = humantime::parse_duration)]
+        older_than: Option<std::time::Duration>,
+        /// Only files whose display name or id starts with this
+        #[arg(long, value_name = "PREFIX")]
+        prefix: Option<String>,
+        #[arg(long = "dry-run", action = ArgAction::SetTrue)]
+        dry_run: bool,
+        #[arg(long = "yes", action = ArgAction::SetTrue)]
+        yes: bool,
+        #[arg(long = "json", action = ArgAction::SetTrue)]
+        json: bool,
     },
     /// Prune job-local downloads (e.g., normalized videos)
     Downloads {
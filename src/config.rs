@@ -3,14 +3,28 @@ use crate::constants::{
     DEFAULT_PDF_DPI, DEFAULT_VIDEO_TOKENS_PER_SECOND, DEFAULT_VIDEO_TOKEN_LIMIT,
 };
 use crate::core::OutputFormat;
-use crate::video::{VideoEncoderPreference, DEFAULT_MAX_CHUNK_BYTES, DEFAULT_MAX_CHUNK_SECONDS};
+use crate::cost::{BudgetAction, Currency, DEFAULT_PRICING_MAX_AGE_HOURS};
+use crate::hooks::Hook;
+use crate::imaging::{ImagePrep, DEFAULT_IMAGE_MAX_DIMENSION};
+use crate::pdf::{OcrMode, PdfRenderer};
+use crate::providers::context_cache::ContextCacheConfig;
+use crate::providers::keys::{ApiKeys, KeyRotation};
+use crate::providers::{GenerationConfig, SafetyConfig};
+use crate::templates::TemplateVars;
+use crate::tools::{Tool, ToolPaths};
+use crate::video::{
+    ChunkBoundaries, ChunkBoundaryMode, ChunkExtraction, EncodeSettings, SilenceRemoval,
+    VideoEncoderPreference, VideoMode, DEFAULT_BOUNDARY_TOLERANCE_SECONDS, DEFAULT_MAX_CHUNK_BYTES,
+    DEFAULT_MAX_CHUNK_SECONDS,
+};
 use anyhow::{Context, Result};
 use serde::Deserialize;
 use serde_yaml::Value;
 use std::collections::HashMap;
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 fn get_env(names: &[&str]) -> Option<String> {
     for name in names {
@@ -31,10 +45,19 @@ struct DefaultsConfig {
     exports: Option<Vec<String>>,
 }
 
+/// `models` in `recapit.yaml`: a model per stage of a run.
+#[derive(Debug, Deserialize, Clone, Default)]
+struct StageModelsConfig {
+    transcribe: Option<String>,
+    convert: Option<String>,
+    synthesize: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Clone, Default)]
 struct SaveConfig {
     full_response: Option<bool>,
     intermediates: Option<bool>,
+    sidecar: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
@@ -45,58 +68,854 @@ struct VideoConfig {
     max_chunk_bytes: Option<u64>,
     encoder: Option<String>,
     media_resolution: Option<String>,
+    chunk_boundaries: Option<String>,
+    boundary_tolerance: Option<f64>,
+    chunk_extraction: Option<String>,
+    mode: Option<String>,
+    remove_silence: Option<bool>,
+    silence_min_seconds: Option<f64>,
+    silence_threshold_db: Option<f64>,
+    silence_padding_seconds: Option<f64>,
+    crf: Option<u32>,
+    video_bitrate: Option<String>,
+    audio_bitrate: Option<String>,
+    max_height: Option<u32>,
+    max_fps: Option<f64>,
+    hwaccel: Option<String>,
+    skip_normalize_when_compatible: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
 struct PdfConfig {
     dpi: Option<u32>,
+    chunk_pages: Option<u32>,
+    ocr: Option<String>,
+    renderer: Option<String>,
+    split_outline: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+struct ImageConfig {
+    max_dimension: Option<u32>,
+    grayscale: Option<bool>,
+    contrast: Option<bool>,
+    deskew: Option<bool>,
+    sharpen: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+struct YtDlpConfig {
+    cookies_from_browser: Option<String>,
+    cookies_file: Option<PathBuf>,
+    hosts: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+struct YouTubeConfig {
+    max_resolution: Option<u32>,
+    audio_only: Option<bool>,
+    limit_rate: Option<String>,
+    proxy: Option<String>,
+    cookies_file: Option<PathBuf>,
+    captions: Option<bool>,
+    caption_languages: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+struct ApiKeysConfig {
+    rotation: Option<KeyRotation>,
+    /// Environment variables holding additional keys, one or more each.
+    env: Option<Vec<String>>,
+    file: Option<PathBuf>,
+    command: Option<String>,
+    keychain: Option<String>,
+}
+
+/// Where the Gemini API keys come from. Nothing is read until a command needs the
+/// API, so dry runs and local commands work without a key.
+#[derive(Debug, Clone, Default)]
+pub struct KeySource {
+    /// `api_keys.file`, replaced by `--api-key-file` or `GEMINI_API_KEY_FILE`.
+    pub file: Option<PathBuf>,
+    pub env: Vec<String>,
+    pub command: Option<String>,
+    pub keychain: Option<String>,
+    pub rotation: KeyRotation,
+}
+
+impl KeySource {
+    /// Keys from the key file and the environment; the command and keychain are
+    /// only consulted when those give none.
+    fn resolve(&self) -> Result<ApiKeys> {
+        let mut keys = Vec::new();
+        if let Some(path) = &self.file {
+            let text = fs::read_to_string(path.clone().expand())
+                .with_context(|| format!("reading API key file {}", path.display()))?;
+            keys.extend(
+                text.lines()
+                    .filter(|line| !line.trim_start().starts_with('#'))
+                    .flat_map(split_list),
+            );
+        }
+        let mut vars = vec!["GEMINI_API_KEY".to_string(), "GEMINI_API_KEYS".to_string()];
+        vars.extend(self.env.iter().cloned());
+        keys.extend(
+            vars.iter()
+                .filter_map(|name| env::var(name).ok())
+                .flat_map(|value| split_list(&value)),
+        );
+        if keys.is_empty() {
+            if let Some(command) = &self.command {
+                keys.extend(split_list(&secret_output("sh", &["-c", command])?));
+            }
+        }
+        if keys.is_empty() {
+            if let Some(service) = &self.keychain {
+                keys.extend(split_list(&keychain_secret(service)?));
+            }
+        }
+        let keys = ApiKeys::new(keys, self.rotation);
+        if keys.is_empty() {
+            anyhow::bail!(
+                "No Gemini API key found. Set GEMINI_API_KEY, pass --api-key-file, or set api_keys.file, api_keys.command, or api_keys.keychain in recapit.yaml"
+            );
+        }
+        Ok(keys)
+    }
+}
+
+/// Standard output of a secret-printing command such as `pass show gemini`.
+fn secret_output(program: &str, args: &[&str]) -> Result<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .with_context(|| format!("running {program} for the API key"))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{program} exited with {} while reading the API key",
+            output.status
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The password stored under `service` in the macOS keychain or, elsewhere, the
+/// Secret Service (`secret-tool store --label=recapit service SERVICE`).
+fn keychain_secret(service: &str) -> Result<String> {
+    if cfg!(target_os = "macos") {
+        secret_output("security", &["find-generic-password", "-s", service, "-w"])
+    } else {
+        secret_output("secret-tool", &["lookup", "service", service])
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+struct BudgetConfig {
+    max_cost: Option<f64>,
+    on_exceed: Option<BudgetAction>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+struct CacheConfig {
+    max_size: Option<SizeSetting>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+struct DownloadConfig {
+    max_size: Option<SizeSetting>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+struct DriveConfig {
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    client_secrets_file: Option<PathBuf>,
 }
 
+#[derive(Debug, Deserialize, Clone, Default)]
+struct ToolsConfig {
+    ffmpeg: Option<PathBuf>,
+    ffprobe: Option<PathBuf>,
+    yt_dlp: Option<PathBuf>,
+    pdftoppm: Option<PathBuf>,
+    pdfinfo: Option<PathBuf>,
+    pdftohtml: Option<PathBuf>,
+    pdfseparate: Option<PathBuf>,
+    pdfunite: Option<PathBuf>,
+    pdftotext: Option<PathBuf>,
+    tesseract: Option<PathBuf>,
+    pdfium: Option<PathBuf>,
+    auto_download: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+struct PricingConfig {
+    url: Option<String>,
+    max_age_hours: Option<u64>,
+    currency: Option<String>,
+    exchange_rates: Option<HashMap<String, f64>>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+struct MetricsConfig {
+    otlp_endpoint: Option<String>,
+    interval_seconds: Option<u64>,
+}
+
+/// A size written as bytes or with a unit, such as `10G`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+enum SizeSetting {
+    Bytes(u64),
+    Text(String),
+}
+
+/// Top-level `recapit.yaml` keys a profile can override. Every other key in a
+/// profile is a per-job setting, read the same way as a preset.
+const PROFILE_CONFIG_KEYS: &[&str] = &[
+    "defaults",
+    "save",
+    "video",
+    "pdf",
+    "image",
+    "ytdlp",
+    "youtube",
+    "budget",
+    "cache",
+    "download",
+    "drive",
+    "tools",
+    "pricing",
+    "metrics",
+    "api_keys",
+    "generation",
+    "safety",
+    "context_cache",
+    "templates_dir",
+    "pricing_file",
+    "glossary",
+    "log_file",
+    "on_complete",
+    "chunk_context_lines",
+    "count_tokens",
+    "model_fallbacks",
+    "models",
+    "vars",
+];
+
+/// Keys each section of `recapit.yaml` accepts, for `recapit config validate`.
+/// Sections not listed here take any keys.
+const SECTION_KEYS: &[(&str, &[&str])] = &[
+    ("defaults", &["model", "output_dir", "format", "exports"]),
+    ("models", &["transcribe", "convert", "synthesize"]),
+    ("save", &["full_response", "intermediates", "sidecar"]),
+    (
+        "video",
+        &[
+            "token_limit",
+            "tokens_per_second",
+            "max_chunk_seconds",
+            "max_chunk_bytes",
+            "encoder",
+            "media_resolution",
+            "chunk_boundaries",
+            "boundary_tolerance",
+            "chunk_extraction",
+            "mode",
+            "remove_silence",
+            "silence_min_seconds",
+            "silence_threshold_db",
+            "silence_padding_seconds",
+            "crf",
+            "video_bitrate",
+            "audio_bitrate",
+            "max_height",
+            "max_fps",
+            "hwaccel",
+            "skip_normalize_when_compatible",
+        ],
+    ),
+    (
+        "pdf",
+        &["dpi", "chunk_pages", "ocr", "renderer", "split_outline"],
+    ),
+    (
+        "image",
+        &[
+            "max_dimension",
+            "grayscale",
+            "contrast",
+            "deskew",
+            "sharpen",
+        ],
+    ),
+    ("ytdlp", &["cookies_from_browser", "cookies_file", "hosts"]),
+    (
+        "youtube",
+        &[
+            "max_resolution",
+            "audio_only",
+            "limit_rate",
+            "proxy",
+            "cookies_file",
+            "captions",
+            "caption_languages",
+        ],
+    ),
+    ("budget", &["max_cost", "on_exceed"]),
+    ("cache", &["max_size"]),
+    ("download", &["max_size"]),
+    (
+        "drive",
+        &["client_id", "client_secret", "client_secrets_file"],
+    ),
+    (
+        "tools",
+        &[
+            "ffmpeg",
+            "ffprobe",
+            "yt_dlp",
+            "pdftoppm",
+            "pdfinfo",
+            "pdftohtml",
+            "pdfseparate",
+            "pdfunite",
+            "pdftotext",
+            "tesseract",
+            "pdfium",
+            "auto_download",
+        ],
+    ),
+    (
+        "pricing",
+        &["url", "max_age_hours", "currency", "exchange_rates"],
+    ),
+    ("metrics", &["otlp_endpoint", "interval_seconds"]),
+    (
+        "api_keys",
+        &["rotation", "env", "file", "command", "keychain"],
+    ),
+    (
+        "generation",
+        &[
+            "temperature",
+            "top_p",
+            "max_output_tokens",
+            "stop_sequences",
+        ],
+    ),
+    ("safety", &["settings", "retry_relaxed"]),
+    ("context_cache", &["enabled", "ttl_minutes", "min_tokens"]),
+];
+
+/// Top-level keys that are not sections with fixed keys.
+const OTHER_ROOT_KEYS: &[&str] = &[
+    "presets",
+    "profiles",
+    "templates_dir",
+    "pricing_file",
+    "glossary",
+    "log_file",
+    "on_complete",
+    "chunk_context_lines",
+    "count_tokens",
+    "model_fallbacks",
+    "vars",
+];
+
+/// Per-job keys a preset or profile may set.
+const JOB_SETTING_KEYS: &[&str] = &[
+    "kind",
+    "model",
+    "format",
+    "pdf_mode",
+    "pdf_dpi",
+    "pdf_chunk_pages",
+    "pdf_split_outline",
+    "pages",
+    "exports",
+    "media_resolution",
+    "recursive",
+    "max_workers",
+    "max_video_workers",
+    "save_full_response",
+    "save_intermediates",
+    "save_metadata",
+    "output_dir",
+    "on_conflict",
+    "prompt",
+    "prompt_file",
+    "language",
+    "detail",
+    "include",
+    "exclude",
+    "max_depth",
+    "group",
+    "chunk_concurrency",
+    "diarize",
+    "synthesize",
+    "embed_chapters",
+];
+
+/// Written by `recapit config init`.
+pub const STARTER_CONFIG: &str = r#"# recapit.yaml - every setting is optional; delete what you do not need.
+# `recapit config show` prints the settings in effect, `recapit config validate`
+# checks this file.
+
+defaults:
+  # model: gemini-3-pro-preview
+  # format: markdown          # markdown, latex, org, rst, json
+  # output_dir: ~/Notes/recapit
+  # exports: [srt]
+
+# model_fallbacks: [gemini-2.5-flash, gemini-2.5-flash-lite]   # tried in order when a request fails
+
+# models:                     # per stage; unset stages use defaults.model
+#   transcribe: gemini-2.5-flash
+#   convert: gemini-2.5-pro   # exports and repairs
+#   synthesize: gemini-2.5-pro
+
+save:
+  full_response: false        # raw model text under full-response/
+  intermediates: false        # keep normalized media and manifests
+  sidecar: true               # NAME.recapit.json provenance file
+
+video:
+  # token_limit: 300000
+  # max_chunk_seconds: 7200
+  # chunk_boundaries: fixed   # fixed, silence, scene
+  # chunk_extraction: copy    # copy (keyframe cuts) or accurate (re-encode chunks)
+  # mode: full                # full, slides, hybrid, audio
+  # remove_silence: false     # cut silences of silence_min_seconds (2) or more
+  # silence_threshold_db: -35 # quieter than this counts as silence
+  # max_height: 720           # downscale 1080p/4K before chunking
+  # max_fps: 15               # lectures rarely need more
+  # crf: 28                   # encoder quality; or video_bitrate: 1500k
+  # audio_bitrate: 128k
+  # hwaccel: auto             # ffmpeg -hwaccel for decoding
+  # skip_normalize_when_compatible: false  # copy H.264/AAC sources instead of re-encoding
+
+pdf:
+  dpi: 200
+  # chunk_pages: 40
+  # ocr: auto                 # auto, off, force
+  # renderer: poppler         # or pdfium, rendering in-process without pdftoppm
+  # split_outline: false      # one section per top-level bookmark, plus an index
+
+image:
+  max_dimension: 3072
+  # grayscale: false
+  # contrast: false
+  # deskew: false
+  # sharpen: false
+
+# ytdlp:
+#   cookies_from_browser: firefox
+#   hosts: [lectures.example.edu]
+
+# youtube:                    # yt-dlp downloads of YouTube and lecture-capture links
+#   max_resolution: 720       # tallest video stream to fetch
+#   audio_only: true          # fetch the m4a audio track alone (not for slides/hybrid)
+#   limit_rate: 2M            # yt-dlp --limit-rate
+#   proxy: socks5://127.0.0.1:1080
+#   cookies_file: ~/cookies.txt   # same as ytdlp.cookies_file
+#   captions: true            # send captions plus sampled frames instead of the video
+#   caption_languages: [en]   # yt-dlp --sub-langs; English by default
+
+# budget:
+#   max_cost: 5.00
+#   on_exceed: abort          # abort, prompt
+
+# cache:
+#   max_size: 10G             # least recently used entries go after each run
+
+# download:
+#   max_size: 2G              # URL and Drive downloads larger than this are refused
+
+# drive:                      # OAuth desktop client for `recapit drive login`
+#   client_secrets_file: ~/client_secret.json   # or client_id and client_secret
+
+# tools:                      # pinned paths; otherwise PATH
+#   ffmpeg: /opt/ffmpeg/bin/ffmpeg
+#   pdfium: /opt/pdfium/lib/libpdfium.so   # for pdf.renderer: pdfium
+#   auto_download: false      # fetch yt-dlp into the cache if missing
+
+# pricing:                    # pricing_file, if set, still takes precedence
+#   url: https://example.com/gemini-pricing.yaml   # same format as pricing_file
+#   max_age_hours: 24         # how long the downloaded table is reused
+#   currency: EUR             # or RECAPIT_CURRENCY; costs are shown converted
+#   exchange_rates:           # units per US dollar
+#     EUR: 0.92
+
+# metrics:                    # `recapit serve` also exposes GET /metrics
+#   otlp_endpoint: http://localhost:4318   # or OTEL_EXPORTER_OTLP_ENDPOINT
+#   interval_seconds: 30
+
+# api_keys:
+#   file: ~/.config/recapit/key   # or command: pass show gemini, or keychain: recapit
+#   env: [GEMINI_KEY_LAB]
+#   rotation: round_robin     # round_robin, on_429
+
+# generation:
+#   temperature: 0.2
+#   max_output_tokens: 32768
+
+# safety:
+#   settings:
+#     dangerous_content: block_only_high
+#   retry_relaxed: false
+
+# context_cache:              # `recapit ask` caches large transcripts with cachedContents
+#   enabled: true             # or `recapit ask --cache-context`
+#   ttl_minutes: 60           # storage is billed per hour
+#   min_tokens: 4096
+
+# chunk_context_lines: 20
+# count_tokens: true          # exact counts from countTokens before each request
+# glossary: glossary.txt
+# log_file: ~/recapit.log     # JSON lines with job and chunk context (--log-file)
+# templates_dir: templates
+
+# vars:
+#   course: CS 101
+
+# on_complete:
+#   - type: command
+#     cmd: notify-send "recapit" "$RECAPIT_SOURCE: $RECAPIT_STATUS"
+
+# presets:
+#   slides-hq:
+#     kind: slides
+#     pdf_mode: pdf
+
+# profiles:
+#   lectures:
+#     kind: lecture
+#     output_dir: ~/Lectures/{date}-{stem}
+#     video:
+#       chunk_boundaries: silence
+"#;
+
 #[derive(Debug, Deserialize, Clone, Default)]
 struct RootConfig {
     defaults: Option<DefaultsConfig>,
+    models: Option<StageModelsConfig>,
     save: Option<SaveConfig>,
     video: Option<VideoConfig>,
     pdf: Option<PdfConfig>,
+    image: Option<ImageConfig>,
+    ytdlp: Option<YtDlpConfig>,
+    youtube: Option<YouTubeConfig>,
     presets: Option<HashMap<String, HashMap<String, Value>>>,
     templates_dir: Option<PathBuf>,
     pricing_file: Option<PathBuf>,
+    glossary: Option<PathBuf>,
+    log_file: Option<PathBuf>,
+    on_complete: Option<Vec<Hook>>,
+    budget: Option<BudgetConfig>,
+    cache: Option<CacheConfig>,
+    download: Option<DownloadConfig>,
+    drive: Option<DriveConfig>,
+    tools: Option<ToolsConfig>,
+    pricing: Option<PricingConfig>,
+    metrics: Option<MetricsConfig>,
+    api_keys: Option<ApiKeysConfig>,
+    generation: Option<GenerationConfig>,
+    safety: Option<SafetyConfig>,
+    context_cache: Option<ContextCacheConfig>,
+    chunk_context_lines: Option<usize>,
+    count_tokens: Option<bool>,
+    model_fallbacks: Option<Vec<String>>,
+    vars: Option<HashMap<String, Value>>,
 }
 
 #[derive(Debug, Clone)]
 pub struct AppConfig {
-    pub api_key: String,
+    /// The `recapit.yaml` that was read, if any.
+    pub config_path: Option<PathBuf>,
+    pub key_source: KeySource,
+    api_keys: OnceLock<ApiKeys>,
     pub output_dir: Option<PathBuf>,
     pub templates_dir: PathBuf,
+    /// Transcription model: `models.transcribe`, else `defaults.model`.
     pub default_model: String,
+    /// Model for export conversions and transcript repairs; `default_model` when unset.
+    pub convert_model: Option<String>,
+    /// Model for the `--synthesize` pass; `default_model` when unset.
+    pub synthesize_model: Option<String>,
     pub default_format: OutputFormat,
     pub save_full_response: bool,
     pub save_intermediates: bool,
+    /// Write a `<name>.recapit.json` provenance sidecar beside every output.
+    pub save_sidecar: bool,
     pub video_token_limit: Option<u32>,
     pub video_tokens_per_second: f64,
     pub video_max_chunk_seconds: f64,
     pub video_max_chunk_bytes: u64,
+    pub video_chunk_boundaries: ChunkBoundaries,
+    /// Cutting long silences out of recordings before chunking.
+    pub video_silence: SilenceRemoval,
+    /// Quality, downscaling, and decode settings for re-encoding videos.
+    pub video_encode: EncodeSettings,
+    pub video_mode: VideoMode,
     pub media_resolution: String,
     pub pdf_dpi: u32,
+    pub pdf_chunk_pages: Option<u32>,
+    /// Split PDFs by their top-level bookmarks.
+    pub pdf_split_outline: bool,
+    pub pdf_ocr: OcrMode,
+    /// What rasterizes and counts PDF pages.
+    pub pdf_renderer: PdfRenderer,
+    pub image_prep: ImagePrep,
+    /// Trailing lines of each chunk's transcript handed to the next chunk's prompt.
+    pub chunk_context_lines: usize,
+    /// Ask `countTokens` for each request's exact input size before sending it.
+    pub count_tokens: bool,
+    /// Models tried in order when a request still fails after its retries.
+    pub model_fallbacks: Vec<String>,
+    /// `vars` from `recapit.yaml`, available to prompts as `{{NAME}}`.
+    pub template_vars: TemplateVars,
+    pub cookies_from_browser: Option<String>,
+    pub cookies_file: Option<PathBuf>,
+    pub media_url_hosts: Vec<String>,
+    /// Format, rate, and proxy settings for yt-dlp downloads.
+    pub ytdlp_options: crate::ingest::YtDlpOptions,
     pub max_workers: usize,
     pub max_video_workers: usize,
     pub video_encoder_preference: VideoEncoderPreference,
     pub presets: HashMap<String, HashMap<String, Value>>,
     pub exports: Vec<String>,
     pub pricing_file: Option<PathBuf>,
+    /// Terms file whose contents are added to every transcription prompt.
+    pub glossary_file: Option<PathBuf>,
+    /// Where every log line is appended as JSON; `--log-file` takes precedence.
+    pub log_file: Option<PathBuf>,
     pub pricing_defaults: HashMap<String, crate::constants::ModelPricing>,
+    /// Where an up-to-date pricing table is downloaded from; it overrides the
+    /// built-in prices but not `pricing_file`.
+    pub pricing_url: Option<String>,
+    pub pricing_max_age_hours: u64,
+    /// The currency costs are displayed in.
+    pub currency: Currency,
+    pub on_complete: Vec<Hook>,
+    pub max_cost: Option<f64>,
+    pub budget_action: BudgetAction,
+    /// Combined size the on-disk caches are trimmed to after each run.
+    pub cache_max_bytes: Option<u64>,
+    /// Largest file a URL or Drive source may download.
+    pub download_max_bytes: u64,
+    /// OAuth desktop client that `recapit drive login` signs in with.
+    pub drive_client_id: Option<String>,
+    pub drive_client_secret: Option<String>,
+    pub drive_client_secrets_file: Option<PathBuf>,
+    /// Pinned paths for ffmpeg, ffprobe, yt-dlp, and poppler, and whether
+    /// missing ones are downloaded.
+    pub tools: ToolPaths,
+    /// OTLP/HTTP collector that request, token, retry, and quota metrics are pushed to.
+    pub otlp_endpoint: Option<String>,
+    pub metrics_interval_seconds: u64,
+    /// Sampling settings sent with every generateContent request.
+    pub generation: GenerationConfig,
+    pub safety: SafetyConfig,
+    pub context_cache: ContextCacheConfig,
+    /// The profile selected with `--profile`, if any.
+    pub profile: Option<String>,
+    /// Per-job settings from the selected profile; these take precedence over the preset.
+    pub profile_settings: HashMap<String, Value>,
 }
 
 impl AppConfig {
     pub fn load(explicit: Option<&Path>) -> Result<Self> {
-        let api_key = env::var("GEMINI_API_KEY")
-            .map_err(|_| anyhow::anyhow!("GEMINI_API_KEY environment variable not set"))?;
+        Self::load_with_profile(explicit, None)
+    }
 
+    /// The settings in effect after merging the file, the profile, and environment
+    /// overrides, laid out like `recapit.yaml`. API keys are never included.
+    pub fn effective(&self) -> serde_json::Value {
+        let path = |path: &Option<PathBuf>| path.as_ref().map(|p| p.display().to_string());
+        let lower = |value: &dyn std::fmt::Debug| format!("{value:?}").to_lowercase();
+        let hooks: Vec<serde_json::Value> = self
+            .on_complete
+            .iter()
+            .map(|hook| match hook {
+                Hook::Webhook { url, headers } => serde_json::json!({
+                    "type": "webhook",
+                    "url": url,
+                    "headers": headers.keys().collect::<Vec<_>>(),
+                }),
+                Hook::Command { cmd } => serde_json::json!({"type": "command", "cmd": cmd}),
+            })
+            .collect();
+        let mut presets: Vec<&String> = self.presets.keys().collect();
+        presets.sort();
+        serde_json::json!({
+            "config_file": path(&self.config_path),
+            "profile": self.profile,
+            "defaults": {
+                "model": self.default_model,
+                "format": self.default_format.as_str(),
+                "output_dir": path(&self.output_dir),
+                "exports": self.exports,
+            },
+            "models": {
+                "transcribe": self.default_model,
+                "convert": self.convert_model.as_deref().unwrap_or(&self.default_model),
+                "synthesize": self.synthesize_model.as_deref().unwrap_or(&self.default_model),
+            },
+            "save": {
+                "full_response": self.save_full_response,
+                "intermediates": self.save_intermediates,
+                "sidecar": self.save_sidecar,
+            },
+            "video": {
+                "token_limit": self.video_token_limit,
+                "tokens_per_second": self.video_tokens_per_second,
+                "max_chunk_seconds": self.video_max_chunk_seconds,
+                "max_chunk_bytes": self.video_max_chunk_bytes,
+                "encoder": lower(&self.video_encoder_preference),
+                "media_resolution": self.media_resolution,
+                "chunk_boundaries": self.video_chunk_boundaries.mode.as_str(),
+                "boundary_tolerance": self.video_chunk_boundaries.tolerance_seconds,
+                "chunk_extraction": self.video_chunk_boundaries.extraction.as_str(),
+                "mode": self.video_mode.as_str(),
+                "remove_silence": self.video_silence.enabled,
+                "silence_min_seconds": self.video_silence.min_seconds,
+                "silence_threshold_db": self.video_silence.threshold_db,
+                "silence_padding_seconds": self.video_silence.padding_seconds,
+                "crf": self.video_encode.crf,
+                "video_bitrate": self.video_encode.video_bitrate,
+                "audio_bitrate": self.video_encode.audio_bitrate,
+                "max_height": self.video_encode.max_height,
+                "max_fps": self.video_encode.max_fps,
+                "hwaccel": self.video_encode.hwaccel,
+                "skip_normalize_when_compatible": self.video_encode.skip_when_compatible,
+            },
+            "pdf": {
+                "dpi": self.pdf_dpi,
+                "chunk_pages": self.pdf_chunk_pages,
+                "split_outline": self.pdf_split_outline,
+                "ocr": lower(&self.pdf_ocr),
+                "renderer": self.pdf_renderer.as_str(),
+            },
+            "image": {
+                "max_dimension": self.image_prep.max_dimension,
+                "grayscale": self.image_prep.grayscale,
+                "contrast": self.image_prep.contrast,
+                "deskew": self.image_prep.deskew,
+                "sharpen": self.image_prep.sharpen,
+            },
+            "ytdlp": {
+                "cookies_from_browser": self.cookies_from_browser,
+                "cookies_file": path(&self.cookies_file),
+                "hosts": self.media_url_hosts,
+            },
+            "youtube": {
+                "max_resolution": self.ytdlp_options.max_height,
+                "audio_only": self.ytdlp_options.audio_only,
+                "limit_rate": self.ytdlp_options.limit_rate,
+                "proxy": self.ytdlp_options.proxy,
+                "captions": self.ytdlp_options.captions,
+                "caption_languages": self.ytdlp_options.caption_languages,
+            },
+            "budget": {
+                "max_cost": self.max_cost,
+                "on_exceed": lower(&self.budget_action),
+            },
+            "cache": {
+                "max_size": self.cache_max_bytes,
+            },
+            "download": {
+                "max_size": self.download_max_bytes,
+            },
+            "drive": {
+                "client_id": self.drive_client_id,
+                "client_secret": self.drive_client_secret.as_ref().map(|_| "<set>"),
+                "client_secrets_file": path(&self.drive_client_secrets_file),
+            },
+            "tools": {
+                "ffmpeg": path(&self.tools.ffmpeg),
+                "ffprobe": path(&self.tools.ffprobe),
+                "yt_dlp": path(&self.tools.yt_dlp),
+                "pdftoppm": path(&self.tools.pdftoppm),
+                "pdfinfo": path(&self.tools.pdfinfo),
+                "pdftohtml": path(&self.tools.pdftohtml),
+                "pdfseparate": path(&self.tools.pdfseparate),
+                "pdfunite": path(&self.tools.pdfunite),
+                "pdftotext": path(&self.tools.pdftotext),
+                "tesseract": path(&self.tools.tesseract),
+                "pdfium": path(&self.tools.pdfium),
+                "auto_download": self.tools.auto_download,
+            },
+            "metrics": {
+                "otlp_endpoint": self.otlp_endpoint,
+                "interval_seconds": self.metrics_interval_seconds,
+            },
+            "api_keys": {
+                "rotation": self.key_source.rotation.as_str(),
+                "file": path(&self.key_source.file),
+                "env": self.key_source.env,
+                "command": self.key_source.command,
+                "keychain": self.key_source.keychain,
+            },
+            "generation": {
+                "temperature": self.generation.temperature,
+                "top_p": self.generation.top_p,
+                "max_output_tokens": self.generation.max_output_tokens,
+                "stop_sequences": self.generation.stop_sequences,
+            },
+            "safety": {
+                "settings": self.safety.settings,
+                "retry_relaxed": self.safety.retry_relaxed,
+            },
+            "context_cache": {
+                "enabled": self.context_cache.enabled,
+                "ttl_minutes": self.context_cache.ttl_minutes,
+                "min_tokens": self.context_cache.min_tokens,
+            },
+            "templates_dir": self.templates_dir.display().to_string(),
+            "pricing_file": path(&self.pricing_file),
+            "pricing": {
+                "url": self.pricing_url,
+                "max_age_hours": self.pricing_max_age_hours,
+                "currency": self.currency.code,
+                "per_usd": self.currency.per_usd,
+            },
+            "glossary": path(&self.glossary_file),
+            "log_file": path(&self.log_file),
+            "chunk_context_lines": self.chunk_context_lines,
+            "count_tokens": self.count_tokens,
+            "model_fallbacks": self.model_fallbacks,
+            "vars": self.template_vars,
+            "on_complete": hooks,
+            "presets": presets,
+            "profile_settings": serde_json::to_value(&self.profile_settings).unwrap_or_default(),
+            "max_workers": self.max_workers,
+            "max_video_workers": self.max_video_workers,
+        })
+    }
+
+    /// The run's API keys, read from `key_source` the first time they are needed.
+    pub fn api_keys(&self) -> Result<ApiKeys> {
+        if let Some(keys) = self.api_keys.get() {
+            return Ok(keys.clone());
+        }
+        let keys = self.key_source.resolve()?;
+        Ok(self.api_keys.get_or_init(|| keys).clone())
+    }
+
+    /// Load the configuration with the named entry under `profiles:` merged over it.
+    pub fn load_with_profile(explicit: Option<&Path>, profile: Option<&str>) -> Result<Self> {
         let config_path = resolve_config_path(explicit)?;
-        let root = if let Some(path) = &config_path {
-            Some(read_config(path)?)
-        } else {
-            None
+        let (root, profile_settings) = match (&config_path, profile) {
+            (Some(path), _) => {
+                let (root, settings) = read_config(path, profile)?;
+                (Some(root), settings)
+            }
+            (None, Some(name)) => {
+                anyhow::bail!("Profile '{name}' requested but no recapit.yaml was found")
+            }
+            (None, None) => (None, HashMap::new()),
         };
 
         let defaults = root
@@ -119,21 +938,135 @@ impl AppConfig {
             .and_then(|r| r.pdf.as_ref())
             .cloned()
             .unwrap_or_default();
+        let image = root
+            .as_ref()
+            .and_then(|r| r.image.as_ref())
+            .cloned()
+            .unwrap_or_default();
+        let ytdlp = root
+            .as_ref()
+            .and_then(|r| r.ytdlp.as_ref())
+            .cloned()
+            .unwrap_or_default();
+        let youtube = root
+            .as_ref()
+            .and_then(|r| r.youtube.as_ref())
+            .cloned()
+            .unwrap_or_default();
         let presets = root
             .as_ref()
             .and_then(|r| r.presets.clone())
             .unwrap_or_default();
 
+        let on_complete = root
+            .as_ref()
+            .and_then(|r| r.on_complete.clone())
+            .unwrap_or_default();
+
+        let budget = root
+            .as_ref()
+            .and_then(|r| r.budget.clone())
+            .unwrap_or_default();
+
+        let cache_max_bytes = match root
+            .as_ref()
+            .and_then(|r| r.cache.as_ref())
+            .and_then(|c| c.max_size.clone())
+        {
+            Some(SizeSetting::Bytes(bytes)) => Some(bytes),
+            Some(SizeSetting::Text(text)) => {
+                Some(crate::cache::parse_size(&text).context("cache.max_size")?)
+            }
+            None => None,
+        };
+
+        let download_max_bytes = match get_env(&["RECAPIT_MAX_DOWNLOAD_SIZE"])
+            .map(SizeSetting::Text)
+            .or_else(|| {
+                root.as_ref()
+                    .and_then(|r| r.download.as_ref())
+                    .and_then(|d| d.max_size.clone())
+            }) {
+            Some(SizeSetting::Bytes(bytes)) => bytes,
+            Some(SizeSetting::Text(text)) => {
+                crate::cache::parse_size(&text).context("download.max_size")?
+            }
+            None => crate::ingest::DEFAULT_MAX_DOWNLOAD_BYTES,
+        };
+
+        let drive = root
+            .as_ref()
+            .and_then(|r| r.drive.clone())
+            .unwrap_or_default();
+        let tools_section = root
+            .as_ref()
+            .and_then(|r| r.tools.clone())
+            .unwrap_or_default();
+        let tools = ToolPaths {
+            ffmpeg: tools_section.ffmpeg.map(|p| p.expand()),
+            ffprobe: tools_section.ffprobe.map(|p| p.expand()),
+            yt_dlp: tools_section.yt_dlp.map(|p| p.expand()),
+            pdftoppm: tools_section.pdftoppm.map(|p| p.expand()),
+            pdfinfo: tools_section.pdfinfo.map(|p| p.expand()),
+            pdftohtml: tools_section.pdftohtml.map(|p| p.expand()),
+            pdfseparate: tools_section.pdfseparate.map(|p| p.expand()),
+            pdfunite: tools_section.pdfunite.map(|p| p.expand()),
+            pdftotext: tools_section.pdftotext.map(|p| p.expand()),
+            tesseract: tools_section.tesseract.map(|p| p.expand()),
+            pdfium: tools_section.pdfium.map(|p| p.expand()),
+            auto_download: get_env(&["RECAPIT_AUTO_DOWNLOAD_TOOLS"])
+                .map(|value| parse_bool(&value))
+                .or(tools_section.auto_download)
+                .unwrap_or(false),
+        };
+        crate::tools::configure(tools.clone());
+
+        let pricing = root
+            .as_ref()
+            .and_then(|r| r.pricing.clone())
+            .unwrap_or_default();
+        let currency = match get_env(&["RECAPIT_CURRENCY"]).or(pricing.currency) {
+            Some(code) => Currency::new(&code, &pricing.exchange_rates.unwrap_or_default())
+                .context("pricing.currency")?,
+            None => Currency::default(),
+        };
+
+        let metrics = root
+            .as_ref()
+            .and_then(|r| r.metrics.clone())
+            .unwrap_or_default();
+
+        let key_settings = root
+            .as_ref()
+            .and_then(|r| r.api_keys.clone())
+            .unwrap_or_default();
+        let key_source = KeySource {
+            file: key_settings.file,
+            env: key_settings.env.unwrap_or_default(),
+            command: key_settings.command,
+            keychain: key_settings.keychain,
+            rotation: key_settings.rotation.unwrap_or_default(),
+        };
+
         let mut output_dir = defaults.output_dir.clone();
         let mut templates_dir = root
             .as_ref()
             .and_then(|r| r.templates_dir.clone())
             .unwrap_or_else(|| PathBuf::from("templates"));
-        let mut default_model = defaults.model.unwrap_or_else(|| DEFAULT_MODEL.to_string());
+        let stage_models = root
+            .as_ref()
+            .and_then(|r| r.models.clone())
+            .unwrap_or_default();
+        let mut default_model = stage_models
+            .transcribe
+            .or(defaults.model)
+            .unwrap_or_else(|| DEFAULT_MODEL.to_string());
+        let mut convert_model = stage_models.convert;
+        let mut synthesize_model = stage_models.synthesize;
         let mut default_format = defaults
             .format
             .as_deref()
-            .and_then(OutputFormat::from_str)
+            .and_then(OutputFormat::parse)
             .unwrap_or(OutputFormat::Markdown);
         let mut exports = defaults
             .exports
@@ -146,6 +1079,7 @@ impl AppConfig {
 
         let mut save_full_response = save.full_response.unwrap_or(false);
         let mut save_intermediates = save.intermediates.unwrap_or(false);
+        let mut save_sidecar = save.sidecar.unwrap_or(true);
 
         let mut video_token_limit = video.token_limit.or(Some(DEFAULT_VIDEO_TOKEN_LIMIT));
         let mut video_tokens_per_second = video
@@ -158,12 +1092,66 @@ impl AppConfig {
             .media_resolution
             .unwrap_or_else(|| "default".to_string());
         let mut pdf_dpi = pdf.dpi.unwrap_or(DEFAULT_PDF_DPI);
+        let mut pdf_chunk_pages = pdf.chunk_pages.filter(|pages| *pages > 0);
+        let mut pdf_ocr = pdf.ocr.clone();
+        let mut image_max_dimension = image.max_dimension.unwrap_or(DEFAULT_IMAGE_MAX_DIMENSION);
+        let mut chunk_context_lines = root
+            .as_ref()
+            .and_then(|r| r.chunk_context_lines)
+            .unwrap_or(0);
+        let mut count_tokens = root.as_ref().and_then(|r| r.count_tokens).unwrap_or(false);
+        let mut model_fallbacks = root
+            .as_ref()
+            .and_then(|r| r.model_fallbacks.clone())
+            .unwrap_or_default();
+        let template_vars = root
+            .as_ref()
+            .and_then(|r| r.vars.as_ref())
+            .map(|vars| {
+                vars.iter()
+                    .filter_map(|(name, value)| {
+                        let value = match value {
+                            Value::String(text) => text.clone(),
+                            Value::Number(number) => number.to_string(),
+                            Value::Bool(flag) => flag.to_string(),
+                            _ => return None,
+                        };
+                        Some((name.to_ascii_uppercase(), value))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
 
+        let mut cookies_from_browser = ytdlp.cookies_from_browser.clone();
+        let mut cookies_file = ytdlp
+            .cookies_file
+            .clone()
+            .or(youtube.cookies_file.clone())
+            .map(|p| p.expand());
+        let ytdlp_options = crate::ingest::YtDlpOptions {
+            max_height: youtube.max_resolution,
+            audio_only: youtube.audio_only.unwrap_or(false),
+            limit_rate: youtube.limit_rate.clone(),
+            proxy: youtube.proxy.clone(),
+            captions: youtube.captions.unwrap_or(false),
+            caption_languages: youtube.caption_languages.clone().unwrap_or_default(),
+        };
+        let media_url_hosts = ytdlp.hosts.clone().unwrap_or_default();
+
+        let mut boundary_mode = video.chunk_boundaries.clone();
+        let mut video_mode = video.mode.clone();
         let mut encoder_pref = video.encoder.clone();
         let pricing_file = root
             .as_ref()
             .and_then(|r| r.pricing_file.clone())
             .map(|p| p.expand());
+        let mut glossary_file = root
+            .as_ref()
+            .and_then(|r| r.glossary.clone())
+            .map(|p| p.expand());
+        if let Some(env_glossary) = get_env(&["RECAPIT_GLOSSARY"]) {
+            glossary_file = Some(PathBuf::from(env_glossary).expand());
+        }
 
         if media_resolution != "default" && media_resolution != "low" {
             media_resolution = "default".to_string();
@@ -185,12 +1173,18 @@ impl AppConfig {
         {
             default_model = env_model;
         }
+        if let Some(env_model) = get_env(&["RECAPIT_CONVERT_MODEL"]) {
+            convert_model = Some(env_model);
+        }
+        if let Some(env_model) = get_env(&["RECAPIT_SYNTHESIZE_MODEL"]) {
+            synthesize_model = Some(env_model);
+        }
 
         if let Some(env_format) = get_env(&[
             "RECAPIT_DEFAULT_FORMAT",
             "LECTURE_SUMMARIZER_DEFAULT_FORMAT",
         ]) {
-            if let Some(parsed) = OutputFormat::from_str(&env_format) {
+            if let Some(parsed) = OutputFormat::parse(&env_format) {
                 default_format = parsed;
             }
         }
@@ -207,6 +1201,9 @@ impl AppConfig {
         ]) {
             save_intermediates = parse_bool(&env_inter);
         }
+        if let Some(env_sidecar) = get_env(&["RECAPIT_SAVE_SIDECAR"]) {
+            save_sidecar = parse_bool(&env_sidecar);
+        }
 
         if let Some(video_limit) = get_env(&[
             "RECAPIT_VIDEO_TOKEN_LIMIT",
@@ -262,6 +1259,70 @@ impl AppConfig {
             }
         }
 
+        if let Some(chunk_pages_env) = get_env(&[
+            "RECAPIT_PDF_CHUNK_PAGES",
+            "LECTURE_SUMMARIZER_PDF_CHUNK_PAGES",
+        ]) {
+            if let Ok(parsed) = chunk_pages_env.parse::<u32>() {
+                pdf_chunk_pages = (parsed > 0).then_some(parsed);
+            }
+        }
+
+        if let Some(ocr_env) = get_env(&["RECAPIT_PDF_OCR"]) {
+            pdf_ocr = Some(ocr_env);
+        }
+        let pdf_ocr = pdf_ocr
+            .as_deref()
+            .map(OcrMode::parse)
+            .transpose()?
+            .unwrap_or_default();
+        let pdf_split_outline = get_env(&["RECAPIT_PDF_SPLIT_OUTLINE"])
+            .map(|value| parse_bool(&value))
+            .or(pdf.split_outline)
+            .unwrap_or(false);
+        let pdf_renderer = get_env(&["RECAPIT_PDF_RENDERER"])
+            .or(pdf.renderer.clone())
+            .as_deref()
+            .map(PdfRenderer::parse)
+            .transpose()?
+            .unwrap_or_default();
+        crate::pdf::set_renderer(pdf_renderer);
+
+        if let Some(dimension_env) = get_env(&["RECAPIT_IMAGE_MAX_DIMENSION"]) {
+            if let Ok(parsed) = dimension_env.parse::<u32>() {
+                image_max_dimension = parsed;
+            }
+        }
+        let image_prep = ImagePrep {
+            max_dimension: (image_max_dimension > 0).then_some(image_max_dimension),
+            grayscale: image.grayscale.unwrap_or(false),
+            contrast: image.contrast.unwrap_or(false),
+            deskew: image.deskew.unwrap_or(false),
+            sharpen: image.sharpen.unwrap_or(false),
+        };
+
+        if let Some(context_env) = get_env(&["RECAPIT_CHUNK_CONTEXT_LINES"]) {
+            if let Ok(parsed) = context_env.parse::<usize>() {
+                chunk_context_lines = parsed;
+            }
+        }
+        if let Some(count_env) = get_env(&["RECAPIT_COUNT_TOKENS"]) {
+            count_tokens = parse_bool(&count_env);
+        }
+        if let Some(fallbacks) = get_env(&["RECAPIT_MODEL_FALLBACKS"]) {
+            model_fallbacks = split_list(&fallbacks);
+        }
+
+        if let Some(browser) = get_env(&[
+            "RECAPIT_COOKIES_FROM_BROWSER",
+            "LECTURE_SUMMARIZER_COOKIES_FROM_BROWSER",
+        ]) {
+            cookies_from_browser = Some(browser);
+        }
+        if let Some(file) = get_env(&["RECAPIT_COOKIES_FILE", "LECTURE_SUMMARIZER_COOKIES_FILE"]) {
+            cookies_file = Some(PathBuf::from(file).expand());
+        }
+
         if let Some(res_override) = get_env(&[
             "RECAPIT_VIDEO_MEDIA_RESOLUTION",
             "LECTURE_SUMMARIZER_VIDEO_MEDIA_RESOLUTION",
@@ -280,40 +1341,311 @@ impl AppConfig {
             encoder_pref = Some(encoder_override);
         }
 
+        if let Some(boundaries) = get_env(&["RECAPIT_VIDEO_CHUNK_BOUNDARIES"]) {
+            boundary_mode = Some(boundaries);
+        }
+        if let Some(mode) = get_env(&["RECAPIT_VIDEO_MODE"]) {
+            video_mode = Some(mode);
+        }
+
         let video_encoder_preference = VideoEncoderPreference::parse(encoder_pref.as_deref())?;
+        let video_chunk_boundaries = ChunkBoundaries {
+            mode: boundary_mode
+                .as_deref()
+                .map(ChunkBoundaryMode::parse)
+                .transpose()?
+                .unwrap_or_default(),
+            tolerance_seconds: video
+                .boundary_tolerance
+                .filter(|seconds| *seconds >= 0.0)
+                .unwrap_or(DEFAULT_BOUNDARY_TOLERANCE_SECONDS),
+            extraction: video
+                .chunk_extraction
+                .as_deref()
+                .map(ChunkExtraction::parse)
+                .transpose()?
+                .unwrap_or_default(),
+        };
+        let video_mode = video_mode
+            .as_deref()
+            .map(VideoMode::parse)
+            .transpose()?
+            .unwrap_or_default();
+        let silence_defaults = SilenceRemoval::default();
+        let video_silence = SilenceRemoval {
+            enabled: get_env(&["RECAPIT_REMOVE_SILENCE"])
+                .map(|value| parse_bool(&value))
+                .or(video.remove_silence)
+                .unwrap_or(false),
+            min_seconds: video
+                .silence_min_seconds
+                .filter(|seconds| *seconds > 0.0)
+                .unwrap_or(silence_defaults.min_seconds),
+            threshold_db: video
+                .silence_threshold_db
+                .unwrap_or(silence_defaults.threshold_db),
+            padding_seconds: video
+                .silence_padding_seconds
+                .filter(|seconds| *seconds >= 0.0)
+                .unwrap_or(silence_defaults.padding_seconds),
+        };
+        let non_empty = |value: Option<String>| value.filter(|text| !text.trim().is_empty());
+        let video_encode = EncodeSettings {
+            crf: video.crf,
+            video_bitrate: non_empty(video.video_bitrate.clone()),
+            audio_bitrate: non_empty(video.audio_bitrate.clone()),
+            max_height: video.max_height.filter(|height| *height > 0),
+            max_fps: video.max_fps.filter(|fps| *fps > 0.0),
+            hwaccel: non_empty(get_env(&["RECAPIT_HWACCEL"]).or(video.hwaccel.clone())),
+            skip_when_compatible: video.skip_normalize_when_compatible.unwrap_or(false),
+        };
 
         exports.sort();
         exports.dedup();
 
         Ok(Self {
-            api_key,
+            config_path: config_path.clone(),
+            key_source,
+            api_keys: OnceLock::new(),
             output_dir,
             templates_dir,
             default_model,
+            convert_model,
+            synthesize_model,
             default_format,
             save_full_response,
             save_intermediates,
+            save_sidecar,
             video_token_limit,
             video_tokens_per_second,
             video_max_chunk_seconds,
             video_max_chunk_bytes,
+            video_chunk_boundaries,
+            video_silence,
+            video_encode,
+            video_mode,
             media_resolution,
             pdf_dpi,
+            pdf_chunk_pages,
+            pdf_split_outline,
+            pdf_ocr,
+            pdf_renderer,
+            image_prep,
+            chunk_context_lines,
+            count_tokens,
+            model_fallbacks,
+            template_vars,
+            cookies_from_browser,
+            cookies_file,
+            media_url_hosts,
+            ytdlp_options,
             max_workers,
             max_video_workers,
             video_encoder_preference,
             presets,
             exports,
             pricing_file,
+            glossary_file,
+            log_file: root
+                .as_ref()
+                .and_then(|r| r.log_file.clone())
+                .map(|p| p.expand()),
             pricing_defaults: default_model_pricing()
                 .into_iter()
                 .map(|(k, v)| (k.to_string(), v))
                 .collect(),
+            pricing_url: get_env(&["RECAPIT_PRICING_URL"]).or(pricing.url),
+            pricing_max_age_hours: pricing
+                .max_age_hours
+                .unwrap_or(DEFAULT_PRICING_MAX_AGE_HOURS),
+            currency,
+            on_complete,
+            max_cost: budget.max_cost.filter(|limit| *limit > 0.0),
+            budget_action: budget.on_exceed.unwrap_or_default(),
+            cache_max_bytes,
+            download_max_bytes,
+            drive_client_id: get_env(&["RECAPIT_DRIVE_CLIENT_ID"]).or(drive.client_id),
+            drive_client_secret: get_env(&["RECAPIT_DRIVE_CLIENT_SECRET"]).or(drive.client_secret),
+            drive_client_secrets_file: drive.client_secrets_file.map(|p| p.expand()),
+            tools,
+            otlp_endpoint: get_env(&["OTEL_EXPORTER_OTLP_ENDPOINT"]).or(metrics.otlp_endpoint),
+            metrics_interval_seconds: metrics.interval_seconds.unwrap_or(30).max(1),
+            generation: root
+                .as_ref()
+                .and_then(|r| r.generation.clone())
+                .unwrap_or_default(),
+            safety: root
+                .as_ref()
+                .and_then(|r| r.safety.clone())
+                .unwrap_or_default(),
+            context_cache: root
+                .as_ref()
+                .and_then(|r| r.context_cache.clone())
+                .unwrap_or_default(),
+            profile: profile.map(str::to_string),
+            profile_settings,
         })
     }
 }
 
-fn resolve_config_path(explicit: Option<&Path>) -> Result<Option<PathBuf>> {
+/// Problems in the config file at `path`: YAML or type errors, unknown keys, bad
+/// values in any profile, and referenced files that do not exist. Empty when the
+/// file is valid.
+pub fn validate_config(path: &Path) -> Vec<String> {
+    let mut problems = Vec::new();
+    let value: Value = match fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|text| serde_yaml::from_str(&text).map_err(anyhow::Error::from))
+    {
+        Ok(value) => value,
+        Err(err) => return vec![format!("{err:#}")],
+    };
+    let Some(root) = value.as_mapping() else {
+        return vec!["the file must be a mapping of settings".into()];
+    };
+
+    let mut profiles = Vec::new();
+    for (key, entry) in root {
+        let Some(key) = key.as_str() else { continue };
+        if let Some((_, allowed)) = SECTION_KEYS.iter().find(|(name, _)| *name == key) {
+            unknown_keys(entry, allowed, key, &mut problems);
+        } else if !OTHER_ROOT_KEYS.contains(&key) {
+            problems.push(format!("unknown top-level key `{key}`"));
+        }
+    }
+    if let Some(presets) = value.get("presets").and_then(Value::as_mapping) {
+        for (name, preset) in presets {
+            let name = name.as_str().unwrap_or_default();
+            unknown_keys(
+                preset,
+                JOB_SETTING_KEYS,
+                &format!("presets.{name}"),
+                &mut problems,
+            );
+        }
+    }
+    if let Some(entries) = value.get("profiles").and_then(Value::as_mapping) {
+        for (name, profile) in entries {
+            let Some(name) = name.as_str() else { continue };
+            profiles.push(name.to_string());
+            let Some(settings) = profile.as_mapping() else {
+                problems.push(format!("profiles.{name} must be a mapping"));
+                continue;
+            };
+            for (key, entry) in settings {
+                let Some(key) = key.as_str() else { continue };
+                let scope = format!("profiles.{name}.{key}");
+                match SECTION_KEYS.iter().find(|(section, _)| *section == key) {
+                    Some((_, allowed)) => unknown_keys(entry, allowed, &scope, &mut problems),
+                    None if PROFILE_CONFIG_KEYS.contains(&key)
+                        || JOB_SETTING_KEYS.contains(&key) => {}
+                    None => problems.push(format!("unknown key `{scope}`")),
+                }
+            }
+        }
+    }
+
+    // Loading catches type errors and values the enums reject, for the file alone
+    // and with each profile applied.
+    let mut loaded = Vec::new();
+    let mut file_error = None;
+    for profile in std::iter::once(None).chain(profiles.iter().map(|name| Some(name.as_str()))) {
+        match AppConfig::load_with_profile(Some(path), profile) {
+            Ok(config) => loaded.push(config),
+            // A profile inherits the file's own errors; report those once.
+            Err(err) if file_error.as_ref() == Some(&format!("{err:#}")) => {}
+            Err(err) => match profile {
+                Some(name) => problems.push(format!("profile `{name}`: {err:#}")),
+                None => {
+                    file_error = Some(format!("{err:#}"));
+                    problems.push(format!("{err:#}"));
+                }
+            },
+        }
+    }
+    for config in &loaded {
+        let scope = config
+            .profile
+            .as_deref()
+            .map(|name| format!("profile `{name}`: "))
+            .unwrap_or_default();
+        if let Err(err) = config.generation.validate() {
+            problems.push(format!("{scope}{err:#}"));
+        }
+        let files = [
+            ("pricing_file", config.pricing_file.as_ref()),
+            ("glossary", config.glossary_file.as_ref()),
+            ("api_keys.file", config.key_source.file.as_ref()),
+            ("ytdlp.cookies_file", config.cookies_file.as_ref()),
+            (
+                "drive.client_secrets_file",
+                config.drive_client_secrets_file.as_ref(),
+            ),
+        ];
+        for (key, file) in files {
+            if let Some(file) = file.filter(|file| !file.as_path().expand().exists()) {
+                problems.push(format!("{scope}{key} {} does not exist", file.display()));
+            }
+        }
+        for tool in Tool::ALL {
+            if let Some(pinned) = config.tools.pinned(tool) {
+                if which::which(pinned).is_err() {
+                    problems.push(format!(
+                        "{scope}tools.{} {} does not exist",
+                        tool.name().replace('-', "_"),
+                        pinned.display()
+                    ));
+                }
+            }
+        }
+    }
+    problems.dedup();
+    problems
+}
+
+fn unknown_keys(section: &Value, allowed: &[&str], scope: &str, problems: &mut Vec<String>) {
+    let Some(entries) = section.as_mapping() else {
+        if !section.is_null() {
+            problems.push(format!("`{scope}` must be a mapping"));
+        }
+        return;
+    };
+    for key in entries.keys().filter_map(Value::as_str) {
+        if !allowed.contains(&key) {
+            problems.push(format!("unknown key `{scope}.{key}`"));
+        }
+    }
+}
+
+/// Environment variables that currently change recapit's behavior, with secrets
+/// masked.
+pub fn env_overrides(config: &AppConfig) -> Vec<(String, String)> {
+    let secrets: Vec<&str> = ["GEMINI_API_KEY", "GEMINI_API_KEYS", "RECAPIT_SERVE_TOKEN"]
+        .into_iter()
+        .chain(config.key_source.env.iter().map(String::as_str))
+        .collect();
+    let mut found: Vec<(String, String)> = env::vars()
+        .filter(|(name, _)| {
+            name.starts_with("RECAPIT_")
+                || name.starts_with("LECTURE_SUMMARIZER_")
+                || name.starts_with("GEMINI_API_KEY")
+                || name == "OTEL_EXPORTER_OTLP_ENDPOINT"
+                || secrets.contains(&name.as_str())
+        })
+        .map(|(name, value)| {
+            let shown = if secrets.contains(&name.as_str()) {
+                "(set)".to_string()
+            } else {
+                value
+            };
+            (name, shown)
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+pub fn resolve_config_path(explicit: Option<&Path>) -> Result<Option<PathBuf>> {
     if let Some(path) = explicit {
         let expanded = path.expand();
         if expanded.exists() {
@@ -339,11 +1671,85 @@ fn resolve_config_path(explicit: Option<&Path>) -> Result<Option<PathBuf>> {
     Ok(None)
 }
 
-fn read_config(path: &Path) -> Result<RootConfig> {
+/// Items in one value (keys, model names), separated by commas or whitespace.
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parse `path`, merging the selected profile's config sections over the file and
+/// returning the profile's remaining, per-job keys separately.
+fn read_config(path: &Path, profile: Option<&str>) -> Result<(RootConfig, HashMap<String, Value>)> {
     let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
-    let root: RootConfig =
+    let mut value: Value =
         serde_yaml::from_reader(file).with_context(|| format!("parsing {}", path.display()))?;
-    Ok(root)
+    let mut settings = HashMap::new();
+    if let Some(name) = profile {
+        let profiles = value.get("profiles").and_then(Value::as_mapping);
+        let selected = profiles
+            .and_then(|profiles| {
+                profiles.iter().find(|(key, _)| {
+                    key.as_str()
+                        .is_some_and(|key| key.eq_ignore_ascii_case(name))
+                })
+            })
+            .map(|(_, profile)| profile.clone())
+            .ok_or_else(|| {
+                let available = profiles
+                    .map(|profiles| {
+                        profiles
+                            .keys()
+                            .filter_map(Value::as_str)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    })
+                    .unwrap_or_default();
+                anyhow::anyhow!(
+                    "Unknown profile '{name}' in {}. Available profiles: {available}",
+                    path.display()
+                )
+            })?;
+        let Value::Mapping(entries) = selected else {
+            anyhow::bail!("Profile '{name}' in {} must be a mapping", path.display());
+        };
+        for (key, entry) in entries {
+            let Some(key) = key.as_str() else { continue };
+            if PROFILE_CONFIG_KEYS.contains(&key) {
+                merge_yaml(&mut value, key, entry);
+            } else {
+                settings.insert(key.to_string(), entry);
+            }
+        }
+    }
+    let root: RootConfig =
+        serde_yaml::from_value(value).with_context(|| format!("parsing {}", path.display()))?;
+    Ok((root, settings))
+}
+
+/// Set `key` in the mapping `base`, merging nested mappings key by key so a profile
+/// only replaces the settings it names.
+fn merge_yaml(base: &mut Value, key: &str, overlay: Value) {
+    if !base.is_mapping() {
+        *base = Value::Mapping(Default::default());
+    }
+    let Some(mapping) = base.as_mapping_mut() else {
+        return;
+    };
+    match (mapping.get_mut(key), overlay) {
+        (Some(existing @ Value::Mapping(_)), Value::Mapping(entries)) => {
+            for (child, value) in entries {
+                if let Some(child) = child.as_str() {
+                    merge_yaml(existing, child, value);
+                }
+            }
+        }
+        (_, overlay) => {
+            mapping.insert(Value::String(key.to_string()), overlay);
+        }
+    }
 }
 
 fn parse_bool(value: &str) -> bool {
@@ -392,3 +1798,44 @@ impl PathExpand for String {
         PathBuf::from(self).expand()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_merge_sections_and_keep_job_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recapit.yaml");
+        std::fs::write(
+            &path,
+            "video:\n  max_chunk_seconds: 3600\n  mode: full\nprofiles:\n  Notes:\n    video:\n      mode: slides\n    kind: lecture\n",
+        )
+        .unwrap();
+        let (root, settings) = read_config(&path, Some("notes")).unwrap();
+        let video = root.video.unwrap();
+        assert_eq!(video.mode.as_deref(), Some("slides"));
+        assert_eq!(video.max_chunk_seconds, Some(3600.0));
+        assert_eq!(settings["kind"].as_str(), Some("lecture"));
+        assert!(read_config(&path, Some("missing")).is_err());
+    }
+
+    #[test]
+    fn validate_flags_unknown_keys_and_bad_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recapit.yaml");
+        std::fs::write(
+            &path,
+            "pdf:\n  dpii: 300\nvideo:\n  mode: full\nprofiles:\n  fast:\n    kindd: lecture\n    video:\n      mode: sideways\n",
+        )
+        .unwrap();
+        let problems = validate_config(&path);
+        assert_eq!(problems.len(), 3, "{problems:?}");
+        assert!(problems[0].contains("pdf.dpii"));
+        assert!(problems[1].contains("profiles.fast.kindd"));
+        assert!(problems[2].starts_with("profile `fast`"));
+
+        std::fs::write(&path, STARTER_CONFIG).unwrap();
+        assert!(validate_config(&path).is_empty());
+    }
+}
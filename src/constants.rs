@@ -48,19 +48,27 @@ pub fn default_model_pricing() -> HashMap<&'static str, ModelPricing> {
     HashMap::from([
         (
             GEMINI_2_5_PRO,
-            ModelPricing::new((3.50, 10.00), (3.00, 15.00)),
+            ModelPricing::new((1.25, 10.00), (1.25, 10.00))
+                .with_tier(200_000, (2.50, 15.00))
+                .with_cache_discount(0.25),
         ),
         (
             GEMINI_2_5_FLASH,
-            ModelPricing::new((0.35, 1.05), (0.70, 2.10)),
+            ModelPricing::new((0.35, 1.05), (0.70, 2.10))
+                .with_audio((1.00, 2.10))
+                .with_cache_discount(0.25),
         ),
         (
             GEMINI_2_5_FLASH_LITE,
-            ModelPricing::new((0.10, 0.40), (0.30, 1.20)),
+            ModelPricing::new((0.10, 0.40), (0.30, 1.20))
+                .with_audio((0.30, 1.20))
+                .with_cache_discount(0.25),
         ),
         (
             GEMINI_3_PRO_PREVIEW,
-            ModelPricing::new((4.00, 18.00), (4.00, 18.00)),
+            ModelPricing::new((2.00, 12.00), (2.00, 12.00))
+                .with_tier(200_000, (4.00, 18.00))
+                .with_cache_discount(0.10),
         ),
         ("default", ModelPricing::new((0.0, 0.0), (0.0, 0.0))),
     ])
@@ -70,6 +78,12 @@ pub fn default_model_pricing() -> HashMap<&'static str, ModelPricing> {
 pub struct ModelPricing {
     pub text: PricePair,
     pub audio_video: PricePair,
+    /// Audio-only input, where it is billed differently from video; falls back to `audio_video`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<PricePair>,
+    /// Rates for long prompts, which Gemini bills higher.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tiers: Vec<PriceTier>,
 }
 
 impl ModelPricing {
@@ -77,6 +91,90 @@ impl ModelPricing {
         Self {
             text: PricePair::new(text.0, text.1),
             audio_video: PricePair::new(audio_video.0, audio_video.1),
+            audio: None,
+            tiers: Vec::new(),
+        }
+    }
+
+    /// Bill prompts longer than `above_tokens` at `rates`, whatever their modality.
+    fn with_tier(mut self, above_tokens: u64, rates: (f64, f64)) -> Self {
+        self.tiers.push(PriceTier {
+            above_tokens,
+            text: PricePair::new(rates.0, rates.1),
+            audio_video: None,
+            audio: None,
+        });
+        self
+    }
+
+    const fn with_audio(mut self, audio: (f64, f64)) -> Self {
+        self.audio = Some(PricePair::new(audio.0, audio.1));
+        self
+    }
+
+    /// Bill tokens read from a context cache at `ratio` of the input rate.
+    fn with_cache_discount(mut self, ratio: f64) -> Self {
+        let pairs = [&mut self.text, &mut self.audio_video]
+            .into_iter()
+            .chain(self.audio.as_mut())
+            .chain(self.tiers.iter_mut().flat_map(|tier| {
+                [
+                    Some(&mut tier.text),
+                    tier.audio_video.as_mut(),
+                    tier.audio.as_mut(),
+                ]
+                .into_iter()
+                .flatten()
+            }));
+        for pair in pairs {
+            pair.cached_input = Some(pair.input * ratio);
+        }
+        self
+    }
+
+    /// The rates that apply to a request of the given modality.
+    pub fn rates(&self, modality: &str) -> &PricePair {
+        match modality {
+            "audio" => self.audio.as_ref().unwrap_or(&self.audio_video),
+            "video" => &self.audio_video,
+            _ => &self.text,
+        }
+    }
+
+    /// The rates for a request of the given modality whose prompt is
+    /// `prompt_tokens` long: those of the highest tier it is above, if any.
+    pub fn rates_for(&self, modality: &str, prompt_tokens: u64) -> &PricePair {
+        let tier = self
+            .tiers
+            .iter()
+            .filter(|tier| prompt_tokens > tier.above_tokens)
+            .max_by_key(|tier| tier.above_tokens);
+        match tier {
+            Some(tier) => tier.rates(modality),
+            None => self.rates(modality),
+        }
+    }
+}
+
+/// Rates that replace a model's base rates for prompts longer than `above_tokens`.
+/// `audio_video` falls back to `text`, and `audio` to `audio_video`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceTier {
+    pub above_tokens: u64,
+    pub text: PricePair,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_video: Option<PricePair>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<PricePair>,
+}
+
+impl PriceTier {
+    fn rates(&self, modality: &str) -> &PricePair {
+        let audio_video = self.audio_video.as_ref().unwrap_or(&self.text);
+        match modality {
+            "audio" => self.audio.as_ref().unwrap_or(audio_video),
+            "video" => audio_video,
+            _ => &self.text,
         }
     }
 }
@@ -85,11 +183,22 @@ impl ModelPricing {
 pub struct PricePair {
     pub input: f64,
     pub output: f64,
+    /// Input tokens read from a context cache; falls back to `input`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_input: Option<f64>,
 }
 
 impl PricePair {
     const fn new(input: f64, output: f64) -> Self {
-        Self { input, output }
+        Self {
+            input,
+            output,
+            cached_input: None,
+        }
+    }
+
+    pub fn cached_input(&self) -> f64 {
+        self.cached_input.unwrap_or(self.input)
     }
 }
 
@@ -1,26 +1,40 @@
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::thread;
 use std::time::Duration;
 
 use anyhow::{anyhow, bail, Context, Result};
 use glob::Pattern;
 use rand::Rng;
-use reqwest::blocking::Client;
+use reqwest::Client;
 use reqwest::StatusCode;
 use serde_json::{json, Map, Value};
 use time::OffsetDateTime;
 use walkdir::WalkDir;
 
+use crate::providers::context_cache::{
+    cache_key, create_cached_content, ContextCacheConfig, ContextCacheIndex,
+};
+use crate::providers::keys::{references_uploads, ApiKeys};
+use crate::providers::replay::{Recording, ReplayMode};
+use crate::providers::{
+    block_reason, finish_reason, push_continuation, response_text, stitch_continuation,
+    GenerationConfig, ResponseSchema, SafetyConfig, TokenUsage, MAX_CONTINUATIONS,
+};
 use crate::quota::QuotaMonitor;
+use crate::render::structured::StructuredTranscript;
+use crate::render::tables::TableSet;
 use crate::telemetry::{RequestEvent, RunMonitor};
 
 pub struct LatexConverter {
     http: Client,
-    api_key: String,
+    keys: ApiKeys,
     monitor: RunMonitor,
     quota: Option<QuotaMonitor>,
+    generation: GenerationConfig,
+    safety: SafetyConfig,
+    context_cache: ContextCacheConfig,
+    recording: Option<Recording>,
 }
 
 const MAX_RETRIES: usize = 3;
@@ -28,19 +42,48 @@ const BACKOFF_BASE_SECONDS: f64 = 1.0;
 const BACKOFF_CAP_SECONDS: f64 = 8.0;
 
 impl LatexConverter {
-    pub fn new(api_key: String, monitor: RunMonitor, quota: Option<QuotaMonitor>) -> Result<Self> {
+    pub fn new(keys: ApiKeys, monitor: RunMonitor, quota: Option<QuotaMonitor>) -> Result<Self> {
         let client = Client::builder()
             .timeout(std::time::Duration::from_secs(600))
             .build()?;
         Ok(Self {
             http: client,
-            api_key,
+            keys,
             monitor,
             quota,
+            generation: GenerationConfig::default(),
+            safety: SafetyConfig::default(),
+            context_cache: ContextCacheConfig::default(),
+            recording: None,
         })
     }
 
-    pub fn latex_to_markdown(
+    /// Save every response to `recording`, or answer from it without calling the
+    /// API. Context caching is skipped either way so recorded requests carry
+    /// their whole prompt.
+    pub fn with_recording(mut self, recording: Option<Recording>) -> Self {
+        self.recording = recording;
+        self
+    }
+
+    /// Cache large transcripts with `cachedContents` so repeated questions about
+    /// them are billed at the cache-read rate.
+    pub fn with_context_cache(mut self, context_cache: ContextCacheConfig) -> Self {
+        self.context_cache = context_cache;
+        self
+    }
+
+    pub fn with_safety(mut self, safety: SafetyConfig) -> Self {
+        self.safety = safety;
+        self
+    }
+
+    pub fn with_generation(mut self, generation: GenerationConfig) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    pub async fn latex_to_markdown(
         &self,
         model: &str,
         prompt: &str,
@@ -51,10 +94,11 @@ impl LatexConverter {
             return Ok(String::new());
         }
         let body_text = format!("Instructions:\n{prompt}\n\nLaTeX:\n{latex_text}");
-        self.generate(model, &body_text, "latex_to_markdown", metadata)
+        self.generate(model, &body_text, "latex_to_markdown", metadata, None)
+            .await
     }
 
-    pub fn latex_to_json(
+    pub async fn latex_to_json(
         &self,
         model: &str,
         prompt: &str,
@@ -65,10 +109,11 @@ impl LatexConverter {
             return Ok("[]".to_string());
         }
         let body_text = format!("Instructions:\n{prompt}\n\n```\n{latex_text}\n```");
-        self.generate(model, &body_text, "latex_to_json", metadata)
+        self.generate_tables(model, &body_text, "latex_to_json", metadata)
+            .await
     }
 
-    pub fn markdown_to_json(
+    pub async fn markdown_to_json(
         &self,
         model: &str,
         prompt: &str,
@@ -79,21 +124,225 @@ impl LatexConverter {
             return Ok("[]".to_string());
         }
         let body_text = format!("Instructions:\n{prompt}\n\n```\n{markdown_text}\n```");
-        self.generate(model, &body_text, "markdown_to_json", metadata)
+        self.generate_tables(model, &body_text, "markdown_to_json", metadata)
+            .await
+    }
+
+    /// Ask the model to rewrite malformed structured output so it matches the schema.
+    pub async fn repair_json(
+        &self,
+        model: &str,
+        prompt: &str,
+        broken_text: &str,
+        metadata: Map<String, Value>,
+    ) -> Result<String> {
+        let body_text = format!("Instructions:\n{prompt}\n\nText:\n{broken_text}");
+        self.generate(
+            model,
+            &body_text,
+            "validation.json_repair",
+            metadata,
+            Some(StructuredTranscript::response_schema()),
+        )
+        .await
+    }
+
+    /// Ask the model to fix the environment structure of a LaTeX transcript body.
+    pub async fn repair_latex(
+        &self,
+        model: &str,
+        prompt: &str,
+        body: &str,
+        metadata: Map<String, Value>,
+    ) -> Result<String> {
+        let body_text = format!("Instructions:\n{prompt}\n\nLaTeX:\n{body}");
+        self.generate(model, &body_text, "latex_repair", metadata, None)
+            .await
+    }
+
+    /// Second pass over a finished (possibly chunked) transcript that produces one global summary.
+    pub async fn synthesize(
+        &self,
+        model: &str,
+        prompt: &str,
+        transcript: &str,
+        metadata: Map<String, Value>,
+    ) -> Result<String> {
+        if transcript.trim().is_empty() {
+            return Ok(String::new());
+        }
+        let body_text = format!("Instructions:\n{prompt}\n\nTranscript:\n{transcript}");
+        self.generate(model, &body_text, "synthesize", metadata, None)
+            .await
+    }
+
+    /// Answer a question grounded in a finished transcript.
+    pub async fn answer(
+        &self,
+        model: &str,
+        prompt: &str,
+        transcript: &str,
+        question: &str,
+        metadata: Map<String, Value>,
+    ) -> Result<String> {
+        let context = format!("Transcript:\n{transcript}");
+        if let Some(cached) = self.cached_context(model, &context).await {
+            let body_text = format!(
+                "Instructions:\n{prompt}\n\nQuestion:\n{question}\n\nAnswer from the transcript above."
+            );
+            match self
+                .generate_in(
+                    model,
+                    &body_text,
+                    "ask",
+                    metadata.clone(),
+                    None,
+                    Some(&cached),
+                )
+                .await
+            {
+                Ok(answer) => return Ok(answer),
+                // Deleted or expired early; the inline request below still works.
+                Err(err) => {
+                    tracing::warn!(
+                        "answering from {cached} failed ({err:#}); sending the transcript inline"
+                    );
+                    let _ = ContextCacheIndex::forget(&cache_key(model, &context));
+                }
+            }
+        }
+        let body_text = format!(
+            "Instructions:\n{prompt}\n\nQuestion:\n{question}\n\nTranscript:\n{transcript}"
+        );
+        self.generate(model, &body_text, "ask", metadata, None)
+            .await
     }
 
-    fn generate(
+    /// The name of a live cache holding `text` for `model`, creating one when
+    /// context caching is on and `text` is large enough. Failures fall back to
+    /// sending the text inline.
+    async fn cached_context(&self, model: &str, text: &str) -> Option<String> {
+        let settings = &self.context_cache;
+        if !settings.enabled
+            || self.recording.is_some()
+            || (text.len() as u64 / 4) < settings.min_tokens
+        {
+            return None;
+        }
+        let key = cache_key(model, text);
+        if let Some(cached) = ContextCacheIndex::load().lookup(&key) {
+            self.monitor.note_event(
+                "context_cache.hit",
+                json!({"model": model, "name": cached.name, "tokens": cached.tokens}),
+            );
+            return Some(cached.name.clone());
+        }
+        let ttl = Duration::from_secs(settings.ttl_minutes * 60);
+        match create_cached_content(
+            &self.http,
+            self.keys.primary(),
+            model,
+            text,
+            ttl,
+            "recapit ask",
+        )
+        .await
+        {
+            Ok(cached) => {
+                self.monitor.note_event(
+                    "context_cache.created",
+                    json!({
+                        "model": model,
+                        "name": cached.name,
+                        "tokens": cached.tokens,
+                        "ttl_minutes": settings.ttl_minutes,
+                    }),
+                );
+                let name = cached.name.clone();
+                if let Err(err) = ContextCacheIndex::record(&key, cached) {
+                    tracing::warn!("could not remember context cache {name}: {err:#}");
+                }
+                Some(name)
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "context caching unavailable ({err:#}); sending the transcript inline"
+                );
+                None
+            }
+        }
+    }
+
+    /// Ask for tables under the `TableSet` response schema and flatten them into a
+    /// JSON array of row objects.
+    async fn generate_tables(
         &self,
         model: &str,
         user_text: &str,
         modality: &str,
         metadata: Map<String, Value>,
+    ) -> Result<String> {
+        let text = self
+            .generate(
+                model,
+                user_text,
+                modality,
+                metadata.clone(),
+                Some(TableSet::response_schema()),
+            )
+            .await?;
+        let tables = match TableSet::parse(&text) {
+            Ok(tables) => tables,
+            Err(err) => {
+                // Schema-constrained output rarely fails to parse; when it does, one
+                // fresh request usually succeeds.
+                self.monitor.note_event(
+                    "validation.json_retry",
+                    json!({"model": model, "operation": modality, "error": format!("{err:#}")}),
+                );
+                let text = self
+                    .generate(
+                        model,
+                        user_text,
+                        modality,
+                        metadata,
+                        Some(TableSet::response_schema()),
+                    )
+                    .await?;
+                TableSet::parse(&text).with_context(|| format!("{modality} response"))?
+            }
+        };
+        Ok(serde_json::to_string_pretty(&tables.to_records())?)
+    }
+
+    async fn generate(
+        &self,
+        model: &str,
+        user_text: &str,
+        modality: &str,
+        metadata: Map<String, Value>,
+        schema: Option<Value>,
+    ) -> Result<String> {
+        self.generate_in(model, user_text, modality, metadata, schema, None)
+            .await
+    }
+
+    /// [`generate`](Self::generate), optionally after the prefix stored in
+    /// `cached_content`.
+    async fn generate_in(
+        &self,
+        model: &str,
+        user_text: &str,
+        modality: &str,
+        metadata: Map<String, Value>,
+        schema: Option<Value>,
+        cached_content: Option<&str>,
     ) -> Result<String> {
         let url = format!(
             "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent",
             model
         );
-        let request_body = json!({
+        let mut request_body = json!({
             "contents": [
                 {
                     "role": "user",
@@ -103,86 +352,84 @@ impl LatexConverter {
                 }
             ]
         });
+        let mut generation_config = self.generation.to_value();
+        if let Some(schema) = schema {
+            generation_config["responseMimeType"] = json!("application/json");
+            generation_config["responseSchema"] = schema;
+        }
+        if generation_config
+            .as_object()
+            .is_some_and(|config| !config.is_empty())
+        {
+            request_body["generationConfig"] = generation_config;
+        }
+        if let Some(settings) = self.safety.to_value() {
+            request_body["safetySettings"] = settings;
+        }
+        if let Some(name) = cached_content {
+            request_body["cachedContent"] = json!(name);
+        }
 
-        let (payload, started, finished, retries) = {
-            let mut attempt = 0;
-            let mut retries = 0;
-            loop {
-                self.apply_quota_delay(model);
-                let started_at = OffsetDateTime::now_utc();
-                match self
-                    .http
-                    .post(&url)
-                    .query(&[("key", self.api_key.as_str())])
-                    .json(&request_body)
-                    .send()
-                {
-                    Ok(resp) => {
-                        if resp.status().is_success() {
-                            let finished_at = OffsetDateTime::now_utc();
-                            let payload: Value =
-                                resp.json().context("parsing generateContent response")?;
-                            break (payload, started_at, finished_at, retries);
-                        }
-
-                        if should_retry_status(resp.status()) && attempt < MAX_RETRIES {
-                            let delay = backoff_delay(attempt);
-                            self.monitor.note_event(
-                                "retry.generateContent",
-                                json!({
-                                    "attempt": attempt + 1,
-                                    "delay_ms": delay.as_millis(),
-                                    "status": resp.status().as_u16(),
-                                    "model": model,
-                                    "operation": modality,
-                                }),
-                            );
-                            thread::sleep(delay);
-                            attempt += 1;
-                            retries += 1;
-                            continue;
-                        }
-
-                        let status = resp.status();
-                        let text = resp.text().unwrap_or_default();
-                        return Err(anyhow!(
-                            "generateContent failed with status {}: {}",
-                            status,
-                            text
-                        ));
-                    }
-                    Err(err) => {
-                        if is_retryable_error(&err) && attempt < MAX_RETRIES {
-                            let delay = backoff_delay(attempt);
-                            self.monitor.note_event(
-                                "retry.generateContent",
-                                json!({
-                                    "attempt": attempt + 1,
-                                    "delay_ms": delay.as_millis(),
-                                    "error": err.to_string(),
-                                    "model": model,
-                                    "operation": modality,
-                                }),
-                            );
-                            thread::sleep(delay);
-                            attempt += 1;
-                            retries += 1;
-                            continue;
-                        }
-                        return Err(err).context("calling generateContent");
-                    }
-                }
+        let (mut payload, started, mut finished, mut retries) = self
+            .send_generate(&url, model, modality, &mut request_body)
+            .await?;
+        let mut text = response_text(&payload);
+        let mut usage = TokenUsage::from_payload(&payload);
+        let mut continuations = 0;
+        let can_continue = request_body
+            .pointer("/generationConfig/responseSchema")
+            .is_none();
+        while finish_reason(&payload) == Some("MAX_TOKENS") {
+            if !can_continue || continuations >= MAX_CONTINUATIONS {
+                self.monitor.note_event(
+                    "response.truncated",
+                    json!({"model": model, "operation": modality, "continuations": continuations}),
+                );
+                tracing::warn!(
+                    "{modality} response from {model} hit the output token limit after {continuations} continuation(s)"
+                );
+                break;
             }
-        };
-
-        let text =
-            extract_text(&payload).ok_or_else(|| anyhow!("response missing candidate text"))?;
-        let usage = payload.get("usageMetadata");
-        let (input_tokens, output_tokens, total_tokens) = extract_usage(usage);
+            continuations += 1;
+            self.monitor.note_event(
+                "response.continued",
+                json!({"model": model, "operation": modality, "continuation": continuations}),
+            );
+            push_continuation(&mut request_body, &text);
+            let (next, _, next_finished, next_retries) = self
+                .send_generate(&url, model, modality, &mut request_body)
+                .await?;
+            text = stitch_continuation(&text, &response_text(&next));
+            usage.add(&TokenUsage::from_payload(&next));
+            finished = next_finished;
+            retries += next_retries;
+            payload = next;
+        }
+        if text.is_empty() {
+            bail!("response missing candidate text");
+        }
+        let TokenUsage {
+            input_tokens,
+            output_tokens,
+            total_tokens,
+            cached_tokens,
+        } = usage;
 
         let mut meta_value = metadata.clone();
         meta_value.insert("operation".into(), Value::String(modality.to_string()));
         meta_value.insert("retries".into(), Value::from(retries as u64));
+        if continuations > 0 {
+            meta_value.insert("continuations".into(), Value::from(continuations as u64));
+        }
+        if let Some(cached) = cached_tokens {
+            meta_value.insert("cached_input_tokens".into(), Value::from(cached));
+        }
+        if let Some(name) = cached_content {
+            meta_value.insert("cached_content".into(), Value::String(name.to_string()));
+        }
+        if !self.generation.is_empty() {
+            meta_value.insert("generation_config".into(), self.generation.to_value());
+        }
         let metadata_map: HashMap<String, Value> = meta_value.into_iter().collect();
 
         let event = RequestEvent {
@@ -195,15 +442,145 @@ impl LatexConverter {
             total_tokens,
             metadata: metadata_map.clone(),
         };
-        self.monitor.record(event.clone());
-        if let Some(quota) = &self.quota {
-            quota.register_tokens(model, event.total_tokens);
-        }
+        self.monitor.record(event);
 
         Ok(text.trim().to_string())
     }
 
-    fn apply_quota_delay(&self, bucket: &str) {
+    /// POST a generateContent request, retrying transient failures and, when
+    /// `safety.retry_relaxed` is set, a blocked response once with relaxed settings.
+    async fn send_generate(
+        &self,
+        url: &str,
+        model: &str,
+        modality: &str,
+        request_body: &mut Value,
+    ) -> Result<(Value, OffsetDateTime, OffsetDateTime, usize)> {
+        let mut relaxed = false;
+        let pinned = references_uploads(request_body);
+        loop {
+            let response =
+                if let Some(recording) = self.recording.as_ref().filter(|r| r.is_replay()) {
+                    let now = OffsetDateTime::now_utc();
+                    (recording.load(model, request_body)?, now, now, 0)
+                } else {
+                    let mut attempt = 0;
+                    let mut retries = 0;
+                    loop {
+                        let (key_index, key) = if pinned {
+                            (0, self.keys.primary())
+                        } else {
+                            self.keys.next()
+                        };
+                        let bucket = self.keys.quota_bucket(model, key_index);
+                        self.apply_quota_delay(&bucket).await;
+                        let started_at = OffsetDateTime::now_utc();
+                        match self
+                            .http
+                            .post(url)
+                            .query(&[("key", key)])
+                            .json(&*request_body)
+                            .send()
+                            .await
+                        {
+                            Ok(resp) => {
+                                if resp.status().is_success() {
+                                    let finished_at = OffsetDateTime::now_utc();
+                                    let payload: Value = resp
+                                        .json()
+                                        .await
+                                        .context("parsing generateContent response")?;
+                                    if let Some(quota) = &self.quota {
+                                        let usage = TokenUsage::from_payload(&payload);
+                                        quota.register_tokens(&bucket, usage.total_tokens);
+                                    }
+                                    if let Some(recording) = &self.recording {
+                                        if recording.mode() == ReplayMode::Record {
+                                            recording.save(model, request_body, &payload)?;
+                                        }
+                                    }
+                                    break (payload, started_at, finished_at, retries);
+                                }
+
+                                if should_retry_status(resp.status()) && attempt < MAX_RETRIES {
+                                    let delay = backoff_delay(attempt);
+                                    // Another key can take the retry straight away.
+                                    let rotated = resp.status() == StatusCode::TOO_MANY_REQUESTS
+                                        && self.keys.throttled(key_index);
+                                    self.monitor.note_event(
+                                        "retry.generateContent",
+                                        json!({
+                                            "attempt": attempt + 1,
+                                            "delay_ms": delay.as_millis(),
+                                            "status": resp.status().as_u16(),
+                                            "model": model,
+                                            "operation": modality,
+                                            "key_rotated": rotated,
+                                        }),
+                                    );
+                                    if !rotated {
+                                        tokio::time::sleep(delay).await;
+                                    }
+                                    attempt += 1;
+                                    retries += 1;
+                                    continue;
+                                }
+
+                                let status = resp.status();
+                                let text = resp.text().await.unwrap_or_default();
+                                return Err(anyhow!(
+                                    "generateContent failed with status {}: {}",
+                                    status,
+                                    text
+                                ));
+                            }
+                            Err(err) => {
+                                if is_retryable_error(&err) && attempt < MAX_RETRIES {
+                                    let delay = backoff_delay(attempt);
+                                    self.monitor.note_event(
+                                        "retry.generateContent",
+                                        json!({
+                                            "attempt": attempt + 1,
+                                            "delay_ms": delay.as_millis(),
+                                            "error": err.to_string(),
+                                            "model": model,
+                                            "operation": modality,
+                                        }),
+                                    );
+                                    tokio::time::sleep(delay).await;
+                                    attempt += 1;
+                                    retries += 1;
+                                    continue;
+                                }
+                                return Err(err).context("calling generateContent");
+                            }
+                        }
+                    }
+                };
+            let Some(reason) = block_reason(&response.0) else {
+                return Ok(response);
+            };
+            self.monitor.note_event(
+                "response.blocked",
+                json!({
+                    "reason": reason,
+                    "model": model,
+                    "operation": modality,
+                    "relaxed": relaxed,
+                }),
+            );
+            if self.safety.retry_relaxed && !relaxed {
+                relaxed = true;
+                request_body["safetySettings"] = SafetyConfig::relaxed();
+                continue;
+            }
+            bail!(
+                "Gemini returned no text: {reason}; adjust `safety` in recapit.yaml or set safety.retry_relaxed"
+            );
+        }
+    }
+
+    async fn apply_quota_delay(&self, bucket: &str) {
         if let Some(quota) = &self.quota {
             if let Some(delay) = quota.register_request(bucket) {
                 if !delay.is_zero() {
@@ -214,30 +591,13 @@ impl LatexConverter {
                             "delay_ms": delay.as_millis(),
                         }),
                     );
-                    thread::sleep(delay);
+                    tokio::time::sleep(delay).await;
                 }
             }
         }
     }
 }
 
-fn extract_text(payload: &Value) -> Option<String> {
-    let candidates = payload.get("candidates")?.as_array()?;
-    let first = candidates.first()?;
-    let content = first.get("content")?.get("parts")?.as_array()?;
-    let mut pieces = Vec::new();
-    for part in content {
-        if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
-            pieces.push(text);
-        }
-    }
-    if pieces.is_empty() {
-        None
-    } else {
-        Some(pieces.join("\n"))
-    }
-}
-
 fn should_retry_status(status: StatusCode) -> bool {
     status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
 }
@@ -259,28 +619,6 @@ fn backoff_delay(attempt: usize) -> Duration {
     Duration::from_secs_f64((capped * jitter).min(BACKOFF_CAP_SECONDS))
 }
 
-fn extract_usage(usage: Option<&Value>) -> (Option<u32>, Option<u32>, Option<u32>) {
-    let Some(usage) = usage else {
-        return (None, None, None);
-    };
-    let prompt = usage
-        .get("promptTokenCount")
-        .or_else(|| usage.get("inputTokenCount"))
-        .and_then(|v| v.as_u64())
-        .map(|v| v as u32);
-    let output = usage
-        .get("candidatesTokenCount")
-        .or_else(|| usage.get("outputTokenCount"))
-        .and_then(|v| v.as_u64())
-        .map(|v| v as u32);
-    let total = usage
-        .get("totalTokenCount")
-        .or_else(|| usage.get("totalTokens"))
-        .and_then(|v| v.as_u64())
-        .map(|v| v as u32);
-    (prompt, output, total)
-}
-
 pub fn collect_tex_files(source: &Path, pattern: &str, recursive: bool) -> Result<Vec<PathBuf>> {
     if source.is_file() {
         return Ok(vec![source.to_path_buf()]);
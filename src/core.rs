@@ -1,9 +1,13 @@
 use anyhow::Result;
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::fmt;
 use std::path::{Path, PathBuf};
 
 use crate::selection::IndexSelection;
+use crate::templates::TemplateVars;
+use crate::video::VideoMode;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
 #[serde(rename_all = "lowercase")]
@@ -13,6 +17,7 @@ pub enum Kind {
     Document,
     Image,
     Video,
+    Audio,
 }
 
 impl Kind {
@@ -23,6 +28,7 @@ impl Kind {
             Kind::Document => "document",
             Kind::Image => "image",
             Kind::Video => "video",
+            Kind::Audio => "audio",
         }
     }
 }
@@ -32,13 +38,19 @@ impl Kind {
 pub enum OutputFormat {
     Markdown,
     Latex,
+    Org,
+    Rst,
+    Json,
 }
 
 impl OutputFormat {
-    pub fn from_str(value: &str) -> Option<Self> {
+    pub fn parse(value: &str) -> Option<Self> {
         match value.to_lowercase().as_str() {
             "markdown" | "md" => Some(Self::Markdown),
             "latex" | "tex" => Some(Self::Latex),
+            "org" | "orgmode" | "org-mode" => Some(Self::Org),
+            "rst" | "rest" | "restructuredtext" => Some(Self::Rst),
+            "json" => Some(Self::Json),
             _ => None,
         }
     }
@@ -47,6 +59,19 @@ impl OutputFormat {
         match self {
             OutputFormat::Markdown => "markdown",
             OutputFormat::Latex => "latex",
+            OutputFormat::Org => "org",
+            OutputFormat::Rst => "rst",
+            OutputFormat::Json => "json",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Markdown => "md",
+            OutputFormat::Latex => "tex",
+            OutputFormat::Org => "org",
+            OutputFormat::Rst => "rst",
+            OutputFormat::Json => "json",
         }
     }
 }
@@ -57,6 +82,77 @@ pub enum PdfMode {
     Auto,
     Images,
     Pdf,
+    /// Send the extracted text layer instead of pages.
+    Text,
+}
+
+/// What to do when a job's output path already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Ask on the terminal; fail when there is no terminal to ask.
+    #[default]
+    Prompt,
+    Overwrite,
+    /// Write to the first free `name-N` instead.
+    Rename,
+    Skip,
+    Fail,
+}
+
+impl ConflictPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Prompt => "prompt",
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::Rename => "rename",
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::Fail => "fail",
+        }
+    }
+}
+
+/// How much of the source the transcript keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Detail {
+    /// A short bullet summary instead of a transcript.
+    Brief,
+    /// The kind's own prompt, unchanged.
+    #[default]
+    Standard,
+    /// Everything, with nothing condensed or left out.
+    Verbatim,
+}
+
+impl Detail {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Detail::Brief => "brief",
+            Detail::Standard => "standard",
+            Detail::Verbatim => "verbatim",
+        }
+    }
+}
+
+/// How the assets discovered for one job are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Grouping {
+    /// Each image is its own slide, in discovery order.
+    #[default]
+    Separate,
+    /// The job's images are photos of one document, ordered by capture time or name.
+    ImagesAsDocument,
+}
+
+impl Grouping {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Grouping::Separate => "separate",
+            Grouping::ImagesAsDocument => "images-as-document",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -66,6 +162,8 @@ pub enum SourceKind {
     Url,
     Youtube,
     Drive,
+    #[serde(rename = "media_url")]
+    MediaUrl,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,32 +177,148 @@ pub struct Asset {
     pub meta: Value,
 }
 
+/// One source to transcribe and how. Start from [`Job::new`] and set the fields that
+/// differ; more fields are added as features are.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct Job {
     pub source: String,
     pub job_label: String,
     pub job_id: String,
-    #[allow(dead_code)]
     pub job_index: usize,
-    #[allow(dead_code)]
     pub job_total: usize,
     pub recursive: bool,
+    /// Globs a folder source's files must match (any of) and must not match.
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// Subfolder levels a recursive folder search descends.
+    pub max_depth: Option<usize>,
     pub kind: Option<Kind>,
     pub pdf_mode: PdfMode,
     pub output_dir: Option<PathBuf>,
     pub model: String,
+    /// Model for export conversions and transcript repairs, when not `model`.
+    pub convert_model: Option<String>,
+    /// Model for the `--synthesize` pass, when not `model`.
+    pub synthesize_model: Option<String>,
     pub preset: Option<String>,
     pub export: Vec<String>,
     pub format: OutputFormat,
     pub skip_existing: bool,
+    pub on_conflict: ConflictPolicy,
     pub page_selection: Option<IndexSelection>,
     pub media_resolution: Option<String>,
     pub save_full_response: bool,
     pub save_intermediates: bool,
     pub save_metadata: bool,
+    pub save_sidecar: bool,
     pub max_workers: usize,
     pub max_video_workers: usize,
     pub pdf_dpi: u32,
+    pub pdf_chunk_pages: Option<u32>,
+    /// One request per top-level bookmark of a PDF, instead of page chunks.
+    pub pdf_split_outline: bool,
+    /// Chunks of one document transcribed at once.
+    pub chunk_concurrency: usize,
+    /// Lines of the previous chunk's transcript carried into the next chunk's prompt.
+    pub chunk_context_lines: usize,
+    pub video_mode: VideoMode,
+    pub grouping: Grouping,
+    /// Replaces the template instruction; `{{PREAMBLE}}` is still substituted.
+    pub prompt_override: Option<String>,
+    /// User-defined prompt variables (`vars` in config, `--var`), keyed by upper-case name.
+    pub vars: TemplateVars,
+    /// Language the transcript and summaries are written in, when not the source's.
+    pub language: Option<String>,
+    pub detail: Detail,
+    /// Names, notation, and acronyms the model should spell as given.
+    pub glossary: Option<String>,
+    /// Ask the model to label speakers in audio and video transcripts.
+    pub diarize: bool,
+    pub embed_chapters: bool,
+    pub synthesize: bool,
+    pub use_cache: bool,
+    /// Keep going when a chunk still fails after retries, leaving a placeholder in
+    /// the transcript.
+    pub continue_on_error: bool,
+    /// Output folder of an earlier run to finish in place, reusing its saved chunk
+    /// responses; the conflict policy is not consulted.
+    pub resume_folder: Option<PathBuf>,
+}
+
+impl Job {
+    /// A job for `source` with the CLI's defaults, except that an existing output is
+    /// renamed instead of asked about.
+    pub fn new(source: impl Into<String>) -> Self {
+        let source = source.into();
+        Self {
+            job_label: source.clone(),
+            job_id: crate::utils::slugify(&source),
+            source,
+            ..Self::default()
+        }
+    }
+
+    pub fn conversion_model(&self) -> &str {
+        self.convert_model.as_deref().unwrap_or(&self.model)
+    }
+
+    pub fn synthesis_model(&self) -> &str {
+        self.synthesize_model.as_deref().unwrap_or(&self.model)
+    }
+}
+
+impl Default for Job {
+    fn default() -> Self {
+        Self {
+            source: String::new(),
+            job_label: String::new(),
+            job_id: "job".into(),
+            job_index: 0,
+            job_total: 1,
+            recursive: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            max_depth: None,
+            kind: None,
+            pdf_mode: PdfMode::Auto,
+            output_dir: None,
+            model: crate::constants::DEFAULT_MODEL.into(),
+            convert_model: None,
+            synthesize_model: None,
+            preset: None,
+            export: Vec::new(),
+            format: OutputFormat::Markdown,
+            skip_existing: true,
+            on_conflict: ConflictPolicy::Rename,
+            page_selection: None,
+            media_resolution: None,
+            save_full_response: false,
+            save_intermediates: false,
+            save_metadata: false,
+            save_sidecar: true,
+            max_workers: crate::constants::DEFAULT_MAX_WORKERS,
+            max_video_workers: crate::constants::DEFAULT_MAX_VIDEO_WORKERS,
+            pdf_dpi: crate::constants::DEFAULT_PDF_DPI,
+            pdf_chunk_pages: None,
+            pdf_split_outline: false,
+            chunk_concurrency: 1,
+            chunk_context_lines: 0,
+            video_mode: VideoMode::default(),
+            grouping: Grouping::default(),
+            prompt_override: None,
+            vars: TemplateVars::new(),
+            language: None,
+            detail: Detail::default(),
+            glossary: None,
+            diarize: false,
+            embed_chapters: false,
+            synthesize: false,
+            use_cache: true,
+            continue_on_error: false,
+            resume_folder: None,
+        }
+    }
 }
 
 pub trait Ingestor: Send + Sync {
@@ -132,8 +346,22 @@ pub trait PromptStrategy: Send + Sync {
     fn instruction(&self, format: OutputFormat, preamble: &str) -> String;
 }
 
+/// The error a job stops with after Ctrl+C, once its in-flight requests have
+/// finished and its manifest has been written.
+#[derive(Debug, Clone, Copy)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("run cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+#[async_trait]
 pub trait Provider: Send + Sync {
-    fn transcribe(
+    async fn transcribe(
         &self,
         instruction: &str,
         assets: &[Asset],
@@ -141,7 +369,7 @@ pub trait Provider: Send + Sync {
         meta: &Value,
     ) -> anyhow::Result<String>;
 
-    fn cleanup(&self) -> Result<()> {
+    async fn cleanup(&self) -> Result<()> {
         Ok(())
     }
 }
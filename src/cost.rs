@@ -1,28 +1,48 @@
 use crate::constants::ModelPricing;
-use crate::telemetry::RequestEvent;
-use crate::video::DEFAULT_TOKENS_PER_SECOND;
+use crate::core::Asset;
+use crate::telemetry::{RequestEvent, RunMonitor};
+use crate::video::{DEFAULT_AUDIO_TOKENS_PER_SECOND, DEFAULT_TOKENS_PER_SECOND};
 use anyhow::{Context, Result};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
+use std::io::{self, IsTerminal, Write};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Gemini bills each image and PDF page at a flat token count.
+const TOKENS_PER_PAGE: u64 = 258;
+/// Response length assumed for a request before any output has been seen.
+pub const DEFAULT_OUTPUT_TOKENS: u64 = 2_048;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct CostSummary {
     pub total_input_cost: f64,
     pub total_output_cost: f64,
+    /// Input tokens read from a context cache, billed at the cache-read rate and
+    /// not included in `total_input_cost`.
+    pub total_cache_read_cost: f64,
     pub total_cost: f64,
     pub per_model: HashMap<String, ModelCostBreakdown>,
     pub estimated: bool,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ModelCostBreakdown {
     pub input_cost: f64,
     pub output_cost: f64,
     pub total_cost: f64,
     pub input_tokens: u64,
     pub output_tokens: u64,
+    #[serde(default)]
+    pub cached_input_tokens: u64,
+    #[serde(default)]
+    pub cache_read_cost: f64,
 }
 
 impl Default for CostSummary {
@@ -30,6 +50,7 @@ impl Default for CostSummary {
         Self {
             total_input_cost: 0.0,
             total_output_cost: 0.0,
+            total_cache_read_cost: 0.0,
             total_cost: 0.0,
             per_model: HashMap::new(),
             estimated: false,
@@ -40,6 +61,7 @@ impl Default for CostSummary {
 #[derive(Clone)]
 pub struct CostEstimator {
     pricing: HashMap<String, ModelPricing>,
+    currency: Currency,
 }
 
 impl CostEstimator {
@@ -50,10 +72,27 @@ impl CostEstimator {
                     .with_context(|| format!("reading pricing file {}", path.display()))?;
                 let map: HashMap<String, ModelPricing> = serde_yaml::from_str(&text)
                     .with_context(|| format!("parsing pricing file {}", path.display()))?;
-                return Ok(Self { pricing: map });
+                return Ok(Self {
+                    pricing: map,
+                    currency: Currency::default(),
+                });
             }
         }
-        Ok(Self { pricing: defaults })
+        Ok(Self {
+            pricing: defaults,
+            currency: Currency::default(),
+        })
+    }
+
+    /// Show costs in `currency`. Estimates are still computed, budgeted, and
+    /// recorded in US dollars.
+    pub fn with_currency(mut self, currency: Currency) -> Self {
+        self.currency = currency;
+        self
+    }
+
+    pub fn currency(&self) -> &Currency {
+        &self.currency
     }
 
     pub fn estimate(&self, events: &[RequestEvent]) -> CostSummary {
@@ -62,23 +101,17 @@ impl CostEstimator {
             if should_skip_modality(&event.modality) {
                 continue;
             }
-            let price_key = modality_price_key(&event.modality);
             let pricing = self
-                .pricing
-                .get(&event.model)
+                .model_pricing(&event.model)
                 .or_else(|| self.pricing.get("default"));
             let Some(model_pricing) = pricing else {
                 continue;
             };
-            let pair = match price_key {
-                PriceKey::Text => &model_pricing.text,
-                PriceKey::AudioVideo => &model_pricing.audio_video,
-            };
-
             let input_tokens =
                 determine_input_tokens(event).unwrap_or_else(|| estimate_tokens(event));
             let output_tokens =
                 determine_output_tokens(event).unwrap_or_else(|| estimate_tokens(event));
+            let pair = model_pricing.rates_for(&event.modality, input_tokens as u64);
 
             if input_tokens == 0 && output_tokens == 0 {
                 continue;
@@ -87,49 +120,465 @@ impl CostEstimator {
                 summary.estimated = true;
             }
 
-            let input_cost = (input_tokens as f64 / 1_000_000.0) * pair.input;
+            let cached_tokens = event
+                .metadata
+                .get("cached_input_tokens")
+                .and_then(|value| value.as_u64())
+                .unwrap_or(0)
+                .min(input_tokens as u64);
+            let input_cost =
+                ((input_tokens as u64 - cached_tokens) as f64 / 1_000_000.0) * pair.input;
+            let cache_read_cost = (cached_tokens as f64 / 1_000_000.0) * pair.cached_input();
             let output_cost = (output_tokens as f64 / 1_000_000.0) * pair.output;
+            let cost = input_cost + cache_read_cost + output_cost;
             summary.total_input_cost += input_cost;
+            summary.total_cache_read_cost += cache_read_cost;
             summary.total_output_cost += output_cost;
-            summary.total_cost += input_cost + output_cost;
-
-            let bucket =
-                summary
-                    .per_model
-                    .entry(event.model.clone())
-                    .or_insert(ModelCostBreakdown {
-                        input_cost: 0.0,
-                        output_cost: 0.0,
-                        total_cost: 0.0,
-                        input_tokens: 0,
-                        output_tokens: 0,
-                    });
+            summary.total_cost += cost;
+
+            let bucket = summary.per_model.entry(event.model.clone()).or_default();
             bucket.input_cost += input_cost;
+            bucket.cache_read_cost += cache_read_cost;
             bucket.output_cost += output_cost;
-            bucket.total_cost += input_cost + output_cost;
+            bucket.total_cost += cost;
             bucket.input_tokens += input_tokens as u64;
+            bucket.cached_input_tokens += cached_tokens;
             bucket.output_tokens += output_tokens as u64;
         }
         summary
     }
+
+    /// Dollar cost of one recorded request, priced the same way as [`Self::estimate`].
+    pub fn event_cost(&self, event: &RequestEvent) -> f64 {
+        self.estimate(std::slice::from_ref(event)).total_cost
+    }
+
+    /// The rates configured for `model`, or for the model it is a variant of
+    /// (`gemini-2.5-flash` for `gemini-2.5-flash-preview-09-2025`), without the
+    /// `default` fallback.
+    pub fn model_pricing(&self, model: &str) -> Option<&ModelPricing> {
+        self.pricing.get(model).or_else(|| {
+            let known = self.pricing.keys().map(String::as_str);
+            crate::models::family(model, known).and_then(|family| self.pricing.get(family))
+        })
+    }
+
+    /// Dollar cost of one request with the given token counts.
+    pub fn price(&self, model: &str, modality: &str, input_tokens: u64, output_tokens: u64) -> f64 {
+        let Some(pricing) = self
+            .model_pricing(model)
+            .or_else(|| self.pricing.get("default"))
+        else {
+            return 0.0;
+        };
+        let pair = pricing.rates_for(modality, input_tokens);
+        (input_tokens as f64 / 1_000_000.0) * pair.input
+            + (output_tokens as f64 / 1_000_000.0) * pair.output
+    }
 }
 
-fn should_skip_modality(modality: &str) -> bool {
-    matches!(modality, "video_token_count")
+/// How often a downloaded pricing table is fetched again.
+pub const DEFAULT_PRICING_MAX_AGE_HOURS: u64 = 24;
+
+/// Fetch a pricing table (the `pricing_file` format, YAML or JSON) from `url`.
+///
+/// The table is cached under the user cache folder and reused until it is
+/// `max_age` old. When the download fails, an older cached copy is used instead.
+pub async fn fetch_pricing(url: &str, max_age: Duration) -> Result<HashMap<String, ModelPricing>> {
+    let dir = dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("recapit")
+        .join("pricing");
+    let digest = Sha256::digest(url.as_bytes());
+    let cached = dir.join(format!("{}.yaml", hex::encode(&digest[..8])));
+    let age = fs::metadata(&cached)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok());
+    if age.is_some_and(|age| age < max_age) {
+        return read_pricing(&cached);
+    }
+    let downloaded = async {
+        let response = reqwest::get(url).await?.error_for_status()?;
+        anyhow::Ok(response.text().await?)
+    }
+    .await;
+    match downloaded {
+        Ok(text) => {
+            let table: HashMap<String, ModelPricing> = serde_yaml::from_str(&text)
+                .with_context(|| format!("parsing pricing table from {url}"))?;
+            crate::utils::ensure_dir(&dir)?;
+            fs::write(&cached, text)
+                .with_context(|| format!("caching pricing table at {}", cached.display()))?;
+            Ok(table)
+        }
+        Err(err) if age.is_some() => {
+            tracing::warn!("could not refresh pricing from {url} ({err:#}); using the cached copy");
+            read_pricing(&cached)
+        }
+        Err(err) => Err(err.context(format!("downloading pricing table from {url}"))),
+    }
+}
+
+fn read_pricing(path: &Path) -> Result<HashMap<String, ModelPricing>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("reading pricing file {}", path.display()))?;
+    serde_yaml::from_str(&text).with_context(|| format!("parsing pricing file {}", path.display()))
+}
+
+/// The currency costs are shown in (`pricing.currency`), as units per US dollar.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Currency {
+    pub code: String,
+    pub per_usd: f64,
+}
+
+impl Default for Currency {
+    fn default() -> Self {
+        Self {
+            code: "USD".into(),
+            per_usd: 1.0,
+        }
+    }
+}
+
+impl Currency {
+    /// `code` priced with `rates` (units of each currency per US dollar).
+    pub fn new(code: &str, rates: &HashMap<String, f64>) -> Result<Self> {
+        let code = code.trim().to_uppercase();
+        if code == "USD" {
+            return Ok(Self::default());
+        }
+        let per_usd = rates
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(&code))
+            .map(|(_, rate)| *rate)
+            .with_context(|| format!("no exchange rate for {code} in pricing.exchange_rates"))?;
+        anyhow::ensure!(
+            per_usd > 0.0,
+            "the exchange rate for {code} must be positive"
+        );
+        Ok(Self { code, per_usd })
+    }
+
+    pub fn is_usd(&self) -> bool {
+        self.code == "USD"
+    }
+
+    pub fn convert(&self, usd: f64) -> f64 {
+        usd * self.per_usd
+    }
+
+    /// `usd` converted and written with the currency's symbol, or its code when it
+    /// has no common one: `$0.0123`, `€0.0113`, `0.0110 CHF`.
+    pub fn format(&self, usd: f64, decimals: usize) -> String {
+        let amount = self.convert(usd);
+        let symbol = match self.code.as_str() {
+            "USD" => "$",
+            "EUR" => "€",
+            "GBP" => "£",
+            "JPY" | "CNY" => "¥",
+            "INR" => "₹",
+            "KRW" => "₩",
+            _ => return format!("{amount:.decimals$} {}", self.code),
+        };
+        format!("{symbol}{amount:.decimals$}")
+    }
 }
 
-enum PriceKey {
-    Text,
-    AudioVideo,
+/// Projected usage of one model request.
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestEstimate {
+    pub label: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost: f64,
 }
 
-fn modality_price_key(modality: &str) -> PriceKey {
-    match modality {
-        "video" => PriceKey::AudioVideo,
-        _ => PriceKey::Text,
+/// Projected usage of a whole job, as shown by `--dry-run`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanEstimate {
+    pub model: String,
+    pub requests: Vec<RequestEstimate>,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_cost: f64,
+}
+
+impl CostEstimator {
+    /// Estimate the requests the provider will make for `assets`: one per chunk of a
+    /// chunked source, and one for all unchunked assets together.
+    pub fn plan(&self, model: &str, modality: &str, assets: &[Asset]) -> PlanEstimate {
+        let (chunks, whole): (Vec<&Asset>, Vec<&Asset>) = assets
+            .iter()
+            .partition(|asset| asset.meta.get("chunk_index").is_some());
+        let mut groups: Vec<(String, Vec<&Asset>)> = chunks
+            .into_iter()
+            .map(|asset| (chunk_label(asset), vec![asset]))
+            .collect();
+        if !whole.is_empty() {
+            let label = match whole.as_slice() {
+                [asset] => asset.path.to_string_lossy().to_string(),
+                many => format!("{} assets", many.len()),
+            };
+            groups.insert(0, (label, whole));
+        }
+
+        let requests: Vec<RequestEstimate> = groups
+            .into_iter()
+            .map(|(label, group)| {
+                let input_tokens = group.iter().map(|asset| estimate_input_tokens(asset)).sum();
+                RequestEstimate {
+                    label,
+                    input_tokens,
+                    output_tokens: DEFAULT_OUTPUT_TOKENS,
+                    cost: self.price(model, modality, input_tokens, DEFAULT_OUTPUT_TOKENS),
+                }
+            })
+            .collect();
+        PlanEstimate {
+            model: model.to_string(),
+            input_tokens: requests.iter().map(|r| r.input_tokens).sum(),
+            output_tokens: requests.iter().map(|r| r.output_tokens).sum(),
+            total_cost: requests.iter().fold(0.0, |total, r| total + r.cost),
+            requests,
+        }
     }
 }
 
+fn chunk_label(asset: &Asset) -> String {
+    let meta = |key: &str| asset.meta.get(key).and_then(|value| value.as_f64());
+    let index = meta("chunk_index").unwrap_or(0.0) as u64 + 1;
+    if let (Some(start), Some(end)) = (meta("chunk_start_seconds"), meta("chunk_end_seconds")) {
+        format!("chunk {index} ({start:.0}s-{end:.0}s)")
+    } else if let (Some(start), Some(end)) = (meta("chunk_start_page"), meta("chunk_end_page")) {
+        format!("chunk {index} (pages {start}-{end})")
+    } else {
+        format!("chunk {index}")
+    }
+}
+
+/// Rough input-token count for one normalized asset, from its duration, pages, or size.
+pub fn estimate_input_tokens(asset: &Asset) -> u64 {
+    let meta = |key: &str| asset.meta.get(key).and_then(|value| value.as_f64());
+    let seconds = match (meta("chunk_start_seconds"), meta("chunk_end_seconds")) {
+        (Some(start), Some(end)) if end > start => end - start,
+        _ => meta("duration_seconds").unwrap_or(0.0),
+    };
+    let slides = asset
+        .meta
+        .get("slides")
+        .and_then(|value| value.as_array())
+        .map_or(0, |slides| slides.len() as u64);
+    // Chunks carry the rate they were planned with, which may have been measured.
+    let rate = |default: f64| meta("tokens_per_second").unwrap_or(default);
+    match asset.media.as_str() {
+        "video" => (seconds * rate(DEFAULT_TOKENS_PER_SECOND)) as u64 + slides * TOKENS_PER_PAGE,
+        "audio" => (seconds * rate(DEFAULT_AUDIO_TOKENS_PER_SECOND)) as u64,
+        "image" => {
+            let ocr_bytes = asset
+                .meta
+                .get("ocr_text")
+                .and_then(|value| value.as_str())
+                .map_or(0, str::len) as u64;
+            TOKENS_PER_PAGE + ocr_bytes / 4
+        }
+        "pdf" => {
+            let pages = match (meta("chunk_start_page"), meta("chunk_end_page")) {
+                (Some(start), Some(end)) if end >= start => (end - start) as u64 + 1,
+                _ => meta("page_total")
+                    .map(|pages| pages as u64)
+                    .unwrap_or_else(|| {
+                        crate::pdf::page_count(&asset.path).map_or(1, |pages| pages as u64)
+                    }),
+            };
+            pages * TOKENS_PER_PAGE
+        }
+        // Text-like assets: about four bytes per token, plus any attached frames.
+        _ => asset.path.metadata().map_or(0, |meta| meta.len() / 4) + slides * TOKENS_PER_PAGE,
+    }
+}
+
+/// What to do when the next request would push a run past its budget.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetAction {
+    #[default]
+    Abort,
+    Prompt,
+}
+
+/// A spending cap shared by every job in a run.
+///
+/// Before each request the provider projects the run total (finished jobs, the
+/// current job so far, and an estimate for the request) and stops once it would
+/// exceed the limit, or asks first with [`BudgetAction::Prompt`].
+#[derive(Clone)]
+pub struct CostBudget {
+    limit: f64,
+    on_exceed: BudgetAction,
+    estimator: CostEstimator,
+    committed: Arc<Mutex<f64>>,
+    approved: Arc<AtomicBool>,
+    /// Whether the terminal is free to ask on; the dashboard and JSON progress own it.
+    can_prompt: bool,
+    /// One question at a time when several chunks go over together.
+    prompt_turn: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl CostBudget {
+    pub fn new(limit: f64, on_exceed: BudgetAction, estimator: CostEstimator) -> Self {
+        Self {
+            limit,
+            on_exceed,
+            estimator,
+            committed: Arc::new(Mutex::new(0.0)),
+            approved: Arc::new(AtomicBool::new(false)),
+            can_prompt: true,
+            prompt_turn: Arc::default(),
+        }
+    }
+
+    /// Whether `on_exceed: prompt` may ask on the terminal; when not, it aborts.
+    pub fn with_prompt(mut self, can_prompt: bool) -> Self {
+        self.can_prompt = can_prompt;
+        self
+    }
+
+    /// Count a finished job's cost against the budget.
+    pub fn commit(&self, cost: f64) {
+        *self.committed.lock().unwrap() += cost;
+    }
+
+    /// Check whether sending `assets` keeps the run within budget, recording the
+    /// decision on `monitor` whenever the projection goes over.
+    pub async fn check(
+        &self,
+        monitor: &RunMonitor,
+        model: &str,
+        modality: &str,
+        assets: &[&Asset],
+    ) -> Result<()> {
+        let input_tokens = assets
+            .iter()
+            .map(|asset| estimate_input_tokens(asset))
+            .sum();
+        self.check_tokens(monitor, model, modality, input_tokens)
+            .await
+    }
+
+    /// [`check`](Self::check) for a request whose input size is already known, such
+    /// as one measured with `countTokens`.
+    pub async fn check_tokens(
+        &self,
+        monitor: &RunMonitor,
+        model: &str,
+        modality: &str,
+        input_tokens: u64,
+    ) -> Result<()> {
+        if self.approved.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let committed = *self.committed.lock().unwrap();
+        let events = monitor.events();
+        let job_cost = self.estimator.estimate(&events).total_cost;
+        let output_tokens = average_output_tokens(&events).unwrap_or(DEFAULT_OUTPUT_TOKENS);
+        let next = self
+            .estimator
+            .price(model, modality, input_tokens, output_tokens);
+        let projected = committed + job_cost + next;
+        if projected <= self.limit {
+            return Ok(());
+        }
+
+        let proceed = match self.on_exceed {
+            BudgetAction::Abort => false,
+            BudgetAction::Prompt if !self.can_prompt => {
+                tracing::warn!(
+                    "budget.on_exceed is prompt, but the progress display owns the terminal; \
+                     stopping instead (run with --progress plain to be asked)"
+                );
+                false
+            }
+            BudgetAction::Prompt => self.ask(projected).await?,
+        };
+        monitor.note_event(
+            "budget.exceeded",
+            json!({
+                "limit": self.limit,
+                "spent": committed + job_cost,
+                "projected": projected,
+                "action": if proceed { "continue" } else { "abort" },
+            }),
+        );
+        if proceed {
+            self.approved.store(true, Ordering::SeqCst);
+            return Ok(());
+        }
+        Err(BudgetExceeded {
+            projected,
+            limit: self.limit,
+        }
+        .into())
+    }
+}
+
+impl CostBudget {
+    /// Ask on the terminal off the async workers, one chunk at a time.
+    async fn ask(&self, projected: f64) -> Result<bool> {
+        let _turn = self.prompt_turn.lock().await;
+        // Someone may have said yes while this chunk waited its turn.
+        if self.approved.load(Ordering::SeqCst) {
+            return Ok(true);
+        }
+        let limit = self.limit;
+        tokio::task::spawn_blocking(move || confirm_overrun(projected, limit)).await?
+    }
+}
+
+/// The error [`CostBudget::check`] returns when the next request would go over budget.
+#[derive(Debug, Clone, Copy)]
+pub struct BudgetExceeded {
+    pub projected: f64,
+    pub limit: f64,
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "projected cost ${:.4} exceeds the ${:.4} budget (--max-cost)",
+            self.projected, self.limit
+        )
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+fn average_output_tokens(events: &[RequestEvent]) -> Option<u64> {
+    let outputs: Vec<u64> = events
+        .iter()
+        .filter_map(|event| event.output_tokens.map(u64::from))
+        .collect();
+    (!outputs.is_empty()).then(|| outputs.iter().sum::<u64>() / outputs.len() as u64)
+}
+
+/// Ask on the terminal; without one there is nobody to ask, so the budget holds.
+fn confirm_overrun(projected: f64, limit: f64) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Ok(false);
+    }
+    print!("projected cost ${projected:.4} exceeds the ${limit:.4} budget. Continue? [y/N] ");
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(matches!(input.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn should_skip_modality(modality: &str) -> bool {
+    matches!(modality, "video_token_count")
+}
+
 fn determine_input_tokens(event: &RequestEvent) -> Option<u32> {
     if let Some(value) = event.input_tokens {
         return Some(value);
@@ -137,7 +586,11 @@ fn determine_input_tokens(event: &RequestEvent) -> Option<u32> {
     if let (Some(total), Some(output)) = (event.total_tokens, event.output_tokens) {
         return total.checked_sub(output);
     }
-    None
+    event
+        .metadata
+        .get("counted_input_tokens")
+        .and_then(|value| value.as_u64())
+        .and_then(|value| u32::try_from(value).ok())
 }
 
 fn determine_output_tokens(event: &RequestEvent) -> Option<u32> {
@@ -151,9 +604,11 @@ fn determine_output_tokens(event: &RequestEvent) -> Option<u32> {
 }
 
 fn estimate_tokens(event: &RequestEvent) -> u32 {
-    if event.modality != "video" {
-        return 0;
-    }
+    let tokens_per_second = match event.modality.as_str() {
+        "video" => DEFAULT_TOKENS_PER_SECOND,
+        "audio" => DEFAULT_AUDIO_TOKENS_PER_SECOND,
+        _ => return 0,
+    };
     let start = event
         .metadata
         .get("chunk_start_seconds")
@@ -167,5 +622,106 @@ fn estimate_tokens(event: &RequestEvent) -> u32 {
     if end <= start {
         return 0;
     }
-    ((end - start) * DEFAULT_TOKENS_PER_SECOND) as u32
+    ((end - start) * tokens_per_second) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::SourceKind;
+
+    #[tokio::test]
+    async fn budget_stops_before_an_overrun() {
+        let estimator = CostEstimator::from_path(
+            None,
+            crate::constants::default_model_pricing()
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        )
+        .unwrap();
+        // Ten minutes of video at 300 tokens/s is 180k input tokens.
+        let chunk = Asset {
+            path: "chunk.mp4".into(),
+            media: "video".into(),
+            page_index: None,
+            source_kind: SourceKind::Local,
+            mime: None,
+            meta: json!({"chunk_start_seconds": 600.0, "chunk_end_seconds": 1200.0}),
+        };
+        assert_eq!(estimate_input_tokens(&chunk), 180_000);
+
+        let monitor = RunMonitor::new();
+        let model = crate::constants::GEMINI_2_5_PRO;
+        let budget = CostBudget::new(1.0, BudgetAction::Abort, estimator);
+        assert!(budget
+            .check(&monitor, model, "video", &[&chunk])
+            .await
+            .is_ok());
+        budget.commit(0.9);
+        let err = budget
+            .check(&monitor, model, "video", &[&chunk])
+            .await
+            .unwrap_err();
+        assert!(err.is::<BudgetExceeded>());
+    }
+
+    #[test]
+    fn long_prompts_use_the_higher_tier() {
+        let pricing: HashMap<String, ModelPricing> = serde_yaml::from_str(
+            "m:\n  text: {input: 1.0, output: 2.0}\n  audio_video: {input: 1.0, output: 2.0}\n  tiers:\n    - above_tokens: 1000\n      text: {input: 3.0, output: 4.0}\n    - above_tokens: 2000\n      text: {input: 5.0, output: 6.0}\n",
+        )
+        .unwrap();
+        let estimator = CostEstimator::from_path(None, pricing).unwrap();
+        let rate = |tokens: u64| estimator.price("m", "video", tokens, 0) / tokens as f64 * 1e6;
+        assert!((rate(1_000) - 1.0).abs() < 1e-9);
+        assert!((rate(1_001) - 3.0).abs() < 1e-9);
+        assert!((rate(5_000) - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cached_tokens_bill_at_the_cache_read_rate() {
+        let estimator = CostEstimator::from_path(
+            None,
+            crate::constants::default_model_pricing()
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        )
+        .unwrap();
+        let now = time::OffsetDateTime::now_utc();
+        let event = RequestEvent {
+            model: crate::constants::GEMINI_2_5_PRO.into(),
+            modality: "text".into(),
+            started_at: now,
+            finished_at: now,
+            input_tokens: Some(1_000_000),
+            output_tokens: Some(0),
+            total_tokens: Some(1_000_000),
+            metadata: HashMap::from([("cached_input_tokens".to_string(), json!(800_000))]),
+        };
+        let summary = estimator.estimate(&[event]);
+        // 200k fresh tokens at the long-prompt tier, 800k at a quarter of it.
+        assert!((summary.total_input_cost - 0.5).abs() < 1e-9);
+        assert!((summary.total_cache_read_cost - 0.5).abs() < 1e-9);
+        assert!((summary.total_cost - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn currencies_convert_from_dollars() {
+        let rates = HashMap::from([("eur".to_string(), 0.9), ("CHF".to_string(), 0.8)]);
+        assert_eq!(
+            Currency::new("usd", &rates).unwrap().format(1.5, 2),
+            "$1.50"
+        );
+        assert_eq!(
+            Currency::new("EUR", &rates).unwrap().format(1.5, 2),
+            "€1.35"
+        );
+        assert_eq!(
+            Currency::new("chf", &rates).unwrap().format(1.5, 2),
+            "1.20 CHF"
+        );
+        assert!(Currency::new("GBP", &rates).is_err());
+    }
 }
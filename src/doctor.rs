@@ -0,0 +1,357 @@
+use std::env;
+use std::fs;
+use std::process::Command;
+
+use crate::config::AppConfig;
+use crate::pdf::{OcrMode, PdfRenderer};
+use crate::providers::gemini::list_models;
+use crate::tools::{self, Tool};
+use crate::utils::ensure_dir;
+use crate::video::{ffmpeg_encoder_names, select_encoder_chain, VideoEncoderPreference};
+use crossterm::style::Stylize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// One line of the report, with what to do about it when it is not `Ok`.
+struct Check {
+    status: Status,
+    name: String,
+    detail: String,
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            status: Status::Ok,
+            name: name.into(),
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn problem(
+        status: Status,
+        name: impl Into<String>,
+        detail: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Self {
+            status,
+            name: name.into(),
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// External programs recapit shells out to: name, version flag, what needs it, and
+/// how to install it.
+const TOOLS: &[(Tool, &str, &str, &str)] = &[
+    (
+        Tool::Ffmpeg,
+        "-version",
+        "video and audio normalization",
+        "install FFmpeg (brew install ffmpeg, apt install ffmpeg, or winget install ffmpeg)",
+    ),
+    (
+        Tool::Ffprobe,
+        "-version",
+        "reading media duration and streams",
+        "ffprobe ships with FFmpeg (brew install ffmpeg or apt install ffmpeg)",
+    ),
+    (
+        Tool::YtDlp,
+        "--version",
+        "YouTube and media URL downloads",
+        "install yt-dlp (brew install yt-dlp or pipx install yt-dlp)",
+    ),
+    (
+        Tool::Pdftoppm,
+        "-v",
+        "rendering PDF pages to images",
+        "install Poppler (brew install poppler or apt install poppler-utils)",
+    ),
+    (
+        Tool::Pdfinfo,
+        "-v",
+        "PDF page counts",
+        "install Poppler (brew install poppler or apt install poppler-utils)",
+    ),
+    (
+        Tool::Pdfseparate,
+        "-v",
+        "cutting PDFs to --pages and into chunks",
+        "install Poppler (brew install poppler or apt install poppler-utils)",
+    ),
+    (
+        Tool::Pdfunite,
+        "-v",
+        "cutting PDFs to --pages and into chunks",
+        "install Poppler (brew install poppler or apt install poppler-utils)",
+    ),
+    (
+        Tool::Pdftotext,
+        "-v",
+        "--pdf-mode text and the text-layer check for pdf.ocr: auto",
+        "install Poppler (brew install poppler or apt install poppler-utils)",
+    ),
+    (
+        Tool::Pdftohtml,
+        "-v",
+        "reading PDF bookmarks for pdf.split_outline",
+        "install Poppler (brew install poppler or apt install poppler-utils)",
+    ),
+    (
+        Tool::Tesseract,
+        "--version",
+        "OCR of scanned pages for pdf.ocr",
+        "install Tesseract (brew install tesseract or apt install tesseract-ocr)",
+    ),
+];
+
+/// `recapit doctor`: check the tools, key, cache, and encoders a run depends on,
+/// and fail when anything a run would trip over is missing.
+pub async fn run_doctor(cfg: &AppConfig) -> anyhow::Result<()> {
+    let mut checks: Vec<Check> = TOOLS
+        .iter()
+        .map(|&(tool, flag, purpose, fix)| check_tool(cfg, tool, flag, purpose, fix))
+        .collect();
+    let has_ffmpeg = checks[0].status == Status::Ok;
+    checks.push(check_encoders(cfg.video_encoder_preference, has_ffmpeg));
+    if cfg.pdf_renderer == PdfRenderer::Pdfium {
+        checks.push(check_pdfium());
+    }
+    checks.push(check_cache_dir());
+    checks.extend(check_api_keys(cfg).await);
+
+    for check in &checks {
+        let label = match check.status {
+            Status::Ok => "ok  ".green(),
+            Status::Warn => "warn".yellow(),
+            Status::Fail => "FAIL".red(),
+        };
+        println!("{label}  {:<9} {}", check.name, check.detail);
+        if let Some(fix) = &check.fix {
+            println!("            {} {fix}", "->".dim());
+        }
+    }
+    let failures = checks
+        .iter()
+        .filter(|check| check.status == Status::Fail)
+        .count();
+    if failures > 0 {
+        anyhow::bail!("{failures} check(s) failed");
+    }
+    println!("\nEverything recapit needs is in place.");
+    Ok(())
+}
+
+fn check_tool(cfg: &AppConfig, tool: Tool, flag: &str, purpose: &str, fix: &str) -> Check {
+    let path = match tools::locate(tool) {
+        Ok(path) => path,
+        Err(_) if cfg.tools.auto_download && tool.downloadable() => {
+            return Check::problem(
+                Status::Warn,
+                tool.name(),
+                format!(
+                    "not installed; downloaded into {} on first use",
+                    tools::tools_dir().display()
+                ),
+                fix,
+            );
+        }
+        Err(_)
+            if cfg.pdf_renderer == PdfRenderer::Pdfium
+                && matches!(
+                    tool,
+                    Tool::Pdftoppm
+                        | Tool::Pdfinfo
+                        | Tool::Pdfseparate
+                        | Tool::Pdfunite
+                        | Tool::Pdftotext
+                ) =>
+        {
+            return Check::problem(
+                Status::Warn,
+                tool.name(),
+                "not installed; pdf.renderer: pdfium reads PDFs without it",
+                fix,
+            );
+        }
+        Err(_)
+            if tool == Tool::Pdftohtml
+                && (!cfg.pdf_split_outline || cfg.pdf_renderer == PdfRenderer::Pdfium) =>
+        {
+            return Check::problem(
+                Status::Warn,
+                tool.name(),
+                "not installed; only pdf.split_outline with pdf.renderer: poppler needs it",
+                fix,
+            );
+        }
+        Err(_) if tool == Tool::Tesseract && cfg.pdf_ocr != OcrMode::Force => {
+            return Check::problem(
+                Status::Warn,
+                tool.name(),
+                "not installed; scanned pages are sent without OCR text",
+                fix,
+            );
+        }
+        Err(err) => {
+            let fix = match tool.downloadable() {
+                true => format!("{fix}, or set tools.auto_download: true"),
+                false => fix.to_string(),
+            };
+            return Check::problem(
+                Status::Fail,
+                tool.name(),
+                format!("{err:#} (needed for {purpose})"),
+                fix,
+            );
+        }
+    };
+    let tool = tool.name();
+    match Command::new(&path).arg(flag).output() {
+        Ok(output) => {
+            // Poppler prints its version to stderr.
+            let text = [output.stdout, output.stderr]
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                .join("\n");
+            let version = text
+                .lines()
+                .map(str::trim)
+                .find(|line| !line.is_empty())
+                .unwrap_or("version unknown")
+                .to_string();
+            Check::ok(tool, format!("{version} ({})", path.display()))
+        }
+        Err(err) => Check::problem(
+            Status::Fail,
+            tool,
+            format!("{} could not be run: {err}", path.display()),
+            fix,
+        ),
+    }
+}
+
+/// Only finds the library; loading it happens on the first PDF.
+fn check_pdfium() -> Check {
+    let fix =
+        "download libpdfium from github.com/bblanchon/pdfium-binaries and set tools.pdfium to it";
+    match tools::locate_pdfium() {
+        Ok(Some(path)) => Check::ok("pdfium", path.display().to_string()),
+        Ok(None) => Check::problem(
+            Status::Warn,
+            "pdfium",
+            "left to the system library path",
+            fix,
+        ),
+        Err(err) => Check::problem(Status::Fail, "pdfium", format!("{err:#}"), fix),
+    }
+}
+
+fn check_encoders(preference: VideoEncoderPreference, has_ffmpeg: bool) -> Check {
+    if !has_ffmpeg {
+        return Check::problem(
+            Status::Warn,
+            "encoders",
+            "skipped: ffmpeg not found",
+            "install FFmpeg first",
+        );
+    }
+    let supported = ffmpeg_encoder_names();
+    let usable: Vec<_> = select_encoder_chain(preference)
+        .into_iter()
+        .filter(|spec| supported.contains(spec.codec))
+        .collect();
+    let Some(first) = usable.first() else {
+        return Check::problem(
+            Status::Fail,
+            "encoders",
+            "ffmpeg has no H.264 encoder recapit can use",
+            "install an FFmpeg build with libx264 (the brew and apt packages include it)",
+        );
+    };
+    let names = usable
+        .iter()
+        .map(|spec| spec.codec)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let wanted = !matches!(
+        preference,
+        VideoEncoderPreference::Auto | VideoEncoderPreference::Cpu
+    );
+    if wanted && first.preference != preference {
+        return Check::problem(
+            Status::Warn,
+            "encoders",
+            format!(
+                "video.encoder is {} but ffmpeg does not offer it; using {names}",
+                format!("{preference:?}").to_lowercase()
+            ),
+            "install the GPU driver and an FFmpeg build with that encoder, or set video.encoder to auto",
+        );
+    }
+    Check::ok("encoders", names)
+}
+
+fn check_cache_dir() -> Check {
+    let dir = dirs::cache_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("recapit");
+    let probe = dir.join(".doctor-write-test");
+    let result = ensure_dir(&dir)
+        .and_then(|_| fs::write(&probe, b"ok"))
+        .and_then(|_| fs::remove_file(&probe));
+    match result {
+        Ok(()) => Check::ok("cache", format!("{} is writable", dir.display())),
+        Err(err) => Check::problem(
+            Status::Fail,
+            "cache",
+            format!("{} is not writable: {err:#}", dir.display()),
+            "fix the folder's permissions, or point XDG_CACHE_HOME at a writable folder",
+        ),
+    }
+}
+
+/// A `models.list` call per key: free, and it fails the same way generation would
+/// for a revoked or mistyped key.
+async fn check_api_keys(cfg: &AppConfig) -> Vec<Check> {
+    let keys = match cfg.api_keys() {
+        Ok(keys) => keys,
+        Err(err) => {
+            return vec![Check::problem(
+                Status::Fail,
+                "api key",
+                format!("{err:#}"),
+                "create a key in Google AI Studio and export GEMINI_API_KEY=...",
+            )]
+        }
+    };
+    let mut checks = Vec::new();
+    for key in keys.iter() {
+        let name = format!("key …{}", &key[key.len().saturating_sub(4)..]);
+        checks.push(match list_models(key).await {
+            Ok(models) => Check::ok(name, format!("valid; {} models listed", models.len())),
+            Err(err) => {
+                let message = format!("{err:#}");
+                let fix = if message.contains("API_KEY_INVALID")
+                    || message.contains("400")
+                    || message.contains("403")
+                {
+                    "the key was rejected; check it for typos or create a new one in Google AI Studio"
+                } else {
+                    "the models endpoint could not be reached; check the network connection or proxy settings"
+                };
+                Check::problem(Status::Fail, name, message, fix)
+            }
+        });
+    }
+    checks
+}
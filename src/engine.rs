@@ -2,23 +2,35 @@ use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use anyhow::{anyhow, Result};
+use anyhow::{bail, Context, Result};
 use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
 use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
 
+use crate::cache::TranscriptCache;
 use crate::config::AppConfig;
 use crate::conversion::LatexConverter;
 use crate::core::{
-    Asset, Ingestor, Job, Kind, Normalizer, OutputFormat, PromptStrategy, Provider, Writer,
+    Asset, Cancelled, ConflictPolicy, Grouping, Ingestor, Job, Kind, Normalizer, OutputFormat,
+    PromptStrategy, Provider, Writer,
 };
 use crate::cost::CostEstimator;
 use crate::pdf;
 use crate::progress::{Progress, ProgressScope, ProgressStage};
-use crate::prompts::TemplatePromptStrategy;
+use crate::prompts::{render_instruction, TemplatePromptStrategy};
+use crate::providers::GenerationConfig;
+use crate::render::chapters::{chapters_from_transcript, embed_chapters, write_ffmetadata};
+use crate::render::structured::StructuredTranscript;
 use crate::render::subtitles::SubtitleExporter;
+use crate::render::validate::{latex_problems, strip_wrapper_fences};
+use crate::render::writer::latex_document;
 use crate::telemetry::RunMonitor;
-use crate::templates::TemplateLoader;
+use crate::templates::{render_template, TemplateLoader, TemplateVars};
 use crate::utils::ensure_dir;
+use crate::video::VideoMode;
 
 pub struct Engine {
     pub ingestor: Box<dyn Ingestor>,
@@ -29,12 +41,18 @@ pub struct Engine {
     pub monitor: RunMonitor,
     pub cost: CostEstimator,
     pub subtitles: Option<SubtitleExporter>,
+    cache: TranscriptCache,
     pub progress: UnboundedSender<Progress>,
     converter: Option<LatexConverter>,
     templates: TemplateLoader,
+    generation: GenerationConfig,
+    cancel: CancellationToken,
+    extract_only: bool,
+    can_prompt: bool,
 }
 
 impl Engine {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         ingestor: Box<dyn Ingestor>,
         normalizer: Box<dyn Normalizer>,
@@ -54,6 +72,7 @@ impl Engine {
             Kind::Document,
             Kind::Image,
             Kind::Video,
+            Kind::Audio,
         ] {
             prompts.insert(
                 kind,
@@ -69,12 +88,38 @@ impl Engine {
             monitor,
             cost,
             subtitles: Some(SubtitleExporter),
+            cache: TranscriptCache::default(),
             progress,
             converter,
             templates: loader,
+            generation: config.generation.clone(),
+            cancel: CancellationToken::new(),
+            extract_only: false,
+            can_prompt: true,
         })
     }
 
+    /// Stop before transcribing once `cancel` fires; the provider should share the
+    /// token so chunked jobs stop between requests.
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Stop after normalization and describe the prepared files in
+    /// `<name>-staged.json` instead of calling the provider (`--no-llm`).
+    pub fn with_extract_only(mut self, extract_only: bool) -> Self {
+        self.extract_only = extract_only;
+        self
+    }
+
+    /// Whether `--on-conflict prompt` may ask on the terminal; the dashboard, JSON
+    /// progress, and `recapit serve` leave no one to answer.
+    pub fn with_prompt(mut self, can_prompt: bool) -> Self {
+        self.can_prompt = can_prompt;
+        self
+    }
+
     pub async fn run(&mut self, job: &Job) -> Result<Option<PathBuf>> {
         self.normalizer.prepare(job)?;
 
@@ -114,7 +159,22 @@ impl Engine {
             finished: false,
         });
 
-        let kind = job.kind.unwrap_or_else(|| infer_kind(&assets));
+        let kind = job.kind.unwrap_or_else(|| match infer_kind(&assets) {
+            // The model only hears the soundtrack, so use the audio prompts.
+            Kind::Lecture
+                if job.video_mode == VideoMode::Audio
+                    && assets.first().is_some_and(|asset| asset.media == "video") =>
+            {
+                Kind::Audio
+            }
+            Kind::Slides
+                if job.grouping == Grouping::ImagesAsDocument
+                    && assets.iter().all(|asset| asset.media == "image") =>
+            {
+                Kind::Document
+            }
+            kind => kind,
+        });
 
         // Normalize
         self.emit(Progress {
@@ -129,6 +189,9 @@ impl Engine {
             finished: false,
         });
         let normalized = self.normalizer.normalize(&assets, job.pdf_mode)?;
+        if self.cancel.is_cancelled() {
+            return Err(Cancelled.into());
+        }
         let normalize_total = normalized.len() as u64;
         let page_total = estimate_page_total(&normalized);
         self.emit(Progress {
@@ -143,6 +206,10 @@ impl Engine {
             finished: false,
         });
 
+        if self.extract_only {
+            return self.write_staged(job, kind, &assets, &normalized).map(Some);
+        }
+
         if normalize_total > 1 {
             self.emit(Progress {
                 scope: ProgressScope::ChunkProgress {
@@ -178,17 +245,28 @@ impl Engine {
             base_root
         };
 
-        if needs_folder {
-            let resolved = crate::utils::resolve_path_with_prompt(&base_dir, true)?
-                .ok_or_else(|| anyhow!("operation cancelled for {}", base_dir.display()))?;
+        if let Some(folder) = &job.resume_folder {
+            base_dir = folder.clone();
+            ensure_dir(&base_dir)?;
+        } else if needs_folder {
+            let Some(resolved) = self
+                .resolve_output(&base_dir, true, job.on_conflict)
+                .await?
+            else {
+                if job.on_conflict == ConflictPolicy::Skip {
+                    self.monitor.note_event(
+                        "output.skipped",
+                        json!({"source": job.source.clone(), "path": base_dir.clone()}),
+                    );
+                    return Ok(None);
+                }
+                bail!("operation cancelled for {}", base_dir.display());
+            };
             base_dir = resolved;
             ensure_dir(&base_dir)?;
         } else {
-            let target = match output_format {
-                OutputFormat::Markdown => base_dir.join(format!("{output_name}.md")),
-                OutputFormat::Latex => base_dir.join(format!("{output_name}.tex")),
-            };
-            if let Some(resolved) = crate::utils::resolve_path_with_prompt(&target, false)? {
+            let target = base_dir.join(format!("{output_name}.{}", output_format.extension()));
+            if let Some(resolved) = self.resolve_output(&target, false, job.on_conflict).await? {
                 let parent = resolved.parent().unwrap_or(Path::new(".")).to_path_buf();
                 output_name = resolved
                     .file_stem()
@@ -197,14 +275,44 @@ impl Engine {
                     .to_string();
                 base_dir = parent;
             } else {
-                return Ok(None);
+                if job.on_conflict == ConflictPolicy::Skip {
+                    self.monitor.note_event(
+                        "output.skipped",
+                        json!({"source": job.source.clone(), "path": target}),
+                    );
+                    return Ok(None);
+                }
+                bail!("operation cancelled for {}", target.display());
             }
             ensure_dir(&base_dir)?;
         }
 
         let prompt = self.prompts.get(&kind).expect("prompt strategy missing");
-        let preamble = prompt.preamble(output_format);
-        let instruction = prompt.instruction(output_format, &preamble);
+        let vars = template_vars(job);
+        let preamble = render_template(&prompt.preamble(output_format), &vars);
+        let mut instruction = match &job.prompt_override {
+            Some(template) => render_instruction(template, &preamble),
+            None => prompt.instruction(output_format, &preamble),
+        };
+        if let Some(glossary) = &job.glossary {
+            instruction = format!(
+                "{instruction}\n\n{}",
+                self.templates.glossary_prompt(glossary)
+            );
+        }
+        if let Some(detail) = self.templates.detail_prompt(kind, job.detail) {
+            instruction = format!("{instruction}\n\n{detail}");
+        }
+        if job.diarize {
+            if matches!(modality, "audio" | "video") {
+                instruction = format!("{instruction}\n\n{}", self.templates.diarize_prompt());
+            } else {
+                self.monitor
+                    .note_event("diarize.skipped", json!({ "modality": modality }));
+            }
+        }
+
+        let instruction = render_template(&with_language(instruction, job), &vars);
 
         let segment_total = normalized.len() as u64;
         self.emit(Progress {
@@ -225,7 +333,8 @@ impl Engine {
         let meta = serde_json::json!({
             "kind": kind.as_str(),
             "source": job.source,
-            "skip_existing": job.skip_existing,
+            // A resume rebuilds the transcript and exports but keeps the finished chunks.
+            "skip_existing": job.skip_existing || job.resume_folder.is_some(),
             "media_resolution": job.media_resolution,
             "format": output_format.as_str(),
             "output_base": base_dir_str,
@@ -233,15 +342,104 @@ impl Engine {
             "save_full_response": job.save_full_response,
             "save_intermediates": job.save_intermediates,
             "save_metadata": job.save_metadata,
+            "language": job.language,
+            "detail": job.detail.as_str(),
             "max_workers": job.max_workers,
             "max_video_workers": job.max_video_workers,
+            "chunk_concurrency": job.chunk_concurrency,
+            "chunk_context_lines": job.chunk_context_lines,
+            "continue_on_error": job.continue_on_error,
             "pdf_dpi": job.pdf_dpi,
             "job_id": job_id,
             "job_label": job_label,
         });
-        let text = self
-            .provider
-            .transcribe(&instruction, &normalized, modality, &meta)?;
+        let mut cache_key = if job.use_cache {
+            let context = format!("chunk_context_lines={}", job.chunk_context_lines);
+            let generation = format!("generation={}", self.generation.to_value());
+            let mut settings = vec![
+                modality,
+                output_format.as_str(),
+                job.media_resolution.as_deref().unwrap_or_default(),
+            ];
+            // Only when enabled, so turning these off keeps earlier cache entries valid.
+            if job.chunk_context_lines > 0 {
+                settings.push(&context);
+            }
+            if job.video_mode != VideoMode::Full {
+                settings.push(job.video_mode.as_str());
+            }
+            if !self.generation.is_empty() {
+                settings.push(&generation);
+            }
+            match self
+                .cache
+                .key(&normalized, &job.model, &instruction, &settings)
+            {
+                Ok(key) => Some(key),
+                Err(err) => {
+                    tracing::warn!("transcript cache disabled for {}: {err:#}", job.source);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let cached = cache_key.as_deref().and_then(|key| self.cache.get(key));
+        let from_cache = cached.is_some();
+        let response = match cached {
+            Some(text) => {
+                self.monitor
+                    .note_event("cache.hit", json!({"source": job.source, "key": cache_key}));
+                text
+            }
+            None => {
+                let response = match self
+                    .provider
+                    .transcribe(&instruction, &normalized, modality, &meta)
+                    .await
+                {
+                    Ok(response) => response,
+                    Err(err) if err.is::<Cancelled>() => {
+                        self.finish_cancelled(job, &base_dir).await;
+                        return Err(err);
+                    }
+                    Err(err) => return Err(err),
+                };
+                // A transcript with placeholders for missing chunks is not worth reusing.
+                if !self.monitor.notes("chunk.failed").is_empty()
+                    || !self.monitor.notes("chunk.skipped").is_empty()
+                {
+                    cache_key = None;
+                }
+                if let Some(key) = &cache_key {
+                    if let Err(err) = self.cache.put(key, &response) {
+                        tracing::warn!("caching transcript: {err:#}");
+                    }
+                }
+                response
+            }
+        };
+        let cleaned = self
+            .validate_output(job, output_format, &preamble, &response)
+            .await;
+        if cleaned != response {
+            if let Some(key) = &cache_key {
+                if let Err(err) = self.cache.put(key, &cleaned) {
+                    tracing::warn!("caching transcript: {err:#}");
+                }
+            }
+        }
+        // JSON runs write the validated document as the primary artifact and use a
+        // Markdown rendering of it for subtitle and Markdown exports.
+        let (text, body) = if output_format == OutputFormat::Json {
+            let transcript = self.structured_transcript(job, &cleaned).await?;
+            (
+                transcript.to_markdown(),
+                serde_json::to_string_pretty(&transcript)?,
+            )
+        } else {
+            (cleaned.clone(), cleaned)
+        };
         self.emit(Progress {
             scope: ProgressScope::Job {
                 id: meta["job_id"].as_str().unwrap_or_default().to_string(),
@@ -267,7 +465,7 @@ impl Engine {
         });
         let output_path =
             self.writer
-                .write(output_format, &base_dir, &output_name, &preamble, &text)?;
+                .write(output_format, &base_dir, &output_name, &preamble, &body)?;
         self.emit(Progress {
             scope: ProgressScope::Job {
                 id: meta["job_id"].as_str().unwrap_or_default().to_string(),
@@ -285,7 +483,7 @@ impl Engine {
             let full_dir = base_dir.join("full-response");
             fs::create_dir_all(&full_dir)?;
             let full_path = full_dir.join(format!("{output_name}.txt"));
-            let mut content = text.trim_end().to_string();
+            let mut content = response.trim_end().to_string();
             content.push('\n');
             fs::write(&full_path, content)?;
             extra_files.push(full_path);
@@ -303,6 +501,33 @@ impl Engine {
             }
         }
 
+        if job.synthesize {
+            if let Some(path) = self
+                .synthesize(
+                    job,
+                    output_format,
+                    &base_dir,
+                    &output_name,
+                    &preamble,
+                    &text,
+                )
+                .await?
+            {
+                extra_files.push(path);
+            }
+        }
+
+        if job
+            .export
+            .iter()
+            .any(|fmt| fmt.trim().eq_ignore_ascii_case("chapters"))
+        {
+            self.export_chapters(job, &base_dir, &output_name, &text, &mut extra_files)?;
+        }
+        if let Some(path) = self.write_section_index(&base_dir, &output_name, &output_path)? {
+            extra_files.push(path);
+        }
+
         match output_format {
             OutputFormat::Markdown => {
                 let mut markdown_source: Option<String> = None;
@@ -330,12 +555,14 @@ impl Engine {
                                 );
                                 metadata.insert("export".into(), Value::String("json".into()));
                                 let prompt = self.templates.markdown_to_json_prompt();
-                                let rendered = converter.markdown_to_json(
-                                    &job.model,
-                                    &prompt,
-                                    markdown_text,
-                                    metadata,
-                                )?;
+                                let rendered = converter
+                                    .markdown_to_json(
+                                        job.conversion_model(),
+                                        &prompt,
+                                        markdown_text,
+                                        metadata,
+                                    )
+                                    .await?;
                                 let mut value = rendered.trim_end().to_string();
                                 value.push('\n');
                                 fs::write(&target, value)?;
@@ -380,7 +607,13 @@ impl Engine {
                                 metadata.insert("export".into(), Value::String("markdown".into()));
                                 let prompt = self.templates.latex_to_md_prompt();
                                 let rendered = converter
-                                    .latex_to_markdown(&job.model, &prompt, latex_text, metadata)?;
+                                    .latex_to_markdown(
+                                        job.conversion_model(),
+                                        &prompt,
+                                        latex_text,
+                                        metadata,
+                                    )
+                                    .await?;
                                 let mut value = rendered.trim_end().to_string();
                                 value.push('\n');
                                 fs::write(&target, value)?;
@@ -410,7 +643,13 @@ impl Engine {
                                 metadata.insert("export".into(), Value::String("json".into()));
                                 let prompt = self.templates.latex_to_json_prompt();
                                 let rendered = converter
-                                    .latex_to_json(&job.model, &prompt, latex_text, metadata)?;
+                                    .latex_to_json(
+                                        job.conversion_model(),
+                                        &prompt,
+                                        latex_text,
+                                        metadata,
+                                    )
+                                    .await?;
                                 let mut value = rendered.trim_end().to_string();
                                 value.push('\n');
                                 fs::write(&target, value)?;
@@ -428,6 +667,62 @@ impl Engine {
                     }
                 }
             }
+            OutputFormat::Json => {
+                let wants_markdown = job
+                    .export
+                    .iter()
+                    .any(|fmt| matches!(fmt.trim().to_lowercase().as_str(), "markdown" | "md"));
+                let target = base_dir.join(format!("{output_name}.md"));
+                if wants_markdown && !(job.skip_existing && target.exists()) {
+                    fs::write(&target, format!("{}\n", text.trim_end()))?;
+                    extra_files.push(target);
+                }
+            }
+            OutputFormat::Org | OutputFormat::Rst => {
+                // There are no conversion prompts for these formats; JSON carries the raw text.
+                let wants_json = job
+                    .export
+                    .iter()
+                    .any(|fmt| fmt.trim().eq_ignore_ascii_case("json"));
+                let target = base_dir.join(format!("{output_name}.json"));
+                if wants_json && !(job.skip_existing && target.exists()) {
+                    let payload = json!({
+                        "source": job.source,
+                        "model": job.model,
+                        "format": output_format.as_str(),
+                        "text": text,
+                    });
+                    fs::write(&target, serde_json::to_string_pretty(&payload)?)?;
+                    extra_files.push(target);
+                }
+            }
+        }
+
+        if job
+            .export
+            .iter()
+            .any(|fmt| fmt.trim().eq_ignore_ascii_case("pdf"))
+        {
+            let target = output_path.with_extension("pdf");
+            if job.skip_existing && target.exists() {
+                extra_files.push(target);
+            } else {
+                // A broken TeX toolchain should not cost the transcript that was just paid for.
+                match crate::render::pdf::compile_pdf(output_format, &output_path) {
+                    Ok(path) => extra_files.push(path),
+                    Err(err) => {
+                        tracing::warn!("{err:#}");
+                        self.monitor.note_event(
+                            "export.pdf_failed",
+                            json!({
+                                "source": output_path,
+                                "format": output_format.as_str(),
+                                "error": format!("{err:#}"),
+                            }),
+                        );
+                    }
+                }
+            }
         }
 
         let artifacts = self.normalizer.artifact_paths();
@@ -435,7 +730,49 @@ impl Engine {
         files.extend(artifacts.clone());
         files.extend(extra_files.clone());
 
-        self.provider.cleanup()?;
+        if job.save_sidecar {
+            let sidecar = base_dir.join(format!("{output_name}.recapit.json"));
+            let sources = assets
+                .iter()
+                .filter(|asset| asset.path.is_file())
+                .map(|asset| {
+                    json!({
+                        "path": asset.path,
+                        "media": asset.media,
+                        "sha256": crate::video::sha256sum(&asset.path).ok(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            let summary = self.monitor.summarize();
+            let payload = json!({
+                "recapit_version": env!("CARGO_PKG_VERSION"),
+                "created_at": OffsetDateTime::now_utc().format(&Rfc3339).ok(),
+                "source": job.source,
+                "sources": sources,
+                "output": output_path,
+                "files": files,
+                "kind": kind.as_str(),
+                "format": output_format.as_str(),
+                "modality": modality,
+                "model": job.model,
+                "language": job.language,
+                "detail": job.detail.as_str(),
+                "prompt_sha256": hex::encode(Sha256::digest(instruction.as_bytes())),
+                "cached": from_cache,
+                "chunks": self.normalizer.chunk_descriptors(),
+                "usage": {
+                    "requests": summary.total_requests,
+                    "input_tokens": summary.total_input_tokens,
+                    "output_tokens": summary.total_output_tokens,
+                    "total_tokens": summary.total_tokens,
+                },
+                "cost": self.cost.estimate(&self.monitor.events()),
+            });
+            fs::write(&sidecar, serde_json::to_string_pretty(&payload)?)?;
+            files.push(sidecar);
+        }
+
+        self.provider.cleanup().await?;
 
         self.emit(Progress {
             scope: ProgressScope::Run,
@@ -465,11 +802,428 @@ impl Engine {
         Ok(Some(output_path))
     }
 
+    /// Write `<name>-staged.json` listing the discovered and normalized assets (with
+    /// their probe metadata), the chunk plan, and the normalizer's manifests.
+    fn write_staged(
+        &self,
+        job: &Job,
+        kind: Kind,
+        assets: &[Asset],
+        normalized: &[Asset],
+    ) -> Result<PathBuf> {
+        let base_dir = job.output_dir.clone().unwrap_or_else(|| PathBuf::from("."));
+        ensure_dir(&base_dir)?;
+        let stem = Path::new(&job.source)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("output");
+        let path = base_dir.join(format!("{stem}-staged.json"));
+        let payload = json!({
+            "recapit_version": env!("CARGO_PKG_VERSION"),
+            "created_at": OffsetDateTime::now_utc().format(&Rfc3339).ok(),
+            "source": job.source,
+            "kind": kind.as_str(),
+            "modality": modality_for(normalized),
+            "model": job.model,
+            "assets": assets,
+            "normalized": normalized,
+            "chunks": self.normalizer.chunk_descriptors(),
+            "manifests": self.normalizer.artifact_paths(),
+        });
+        fs::write(&path, serde_json::to_string_pretty(&payload)?)
+            .with_context(|| format!("writing {}", path.display()))?;
+        self.monitor.note_event(
+            "extract.staged",
+            json!({"source": job.source, "assets": normalized.len(), "path": path}),
+        );
+        self.emit(Progress {
+            scope: ProgressScope::Run,
+            stage: ProgressStage::Write,
+            current: 1,
+            total: 1,
+            status: path.display().to_string(),
+            finished: false,
+        });
+        Ok(path)
+    }
+
+    /// Tidy up after a cancelled transcription: delete uploads as a finished run
+    /// would, and write the usage so far to `run-summary.json` when metadata is on.
+    async fn finish_cancelled(&self, job: &Job, base_dir: &Path) {
+        if let Err(err) = self.provider.cleanup().await {
+            tracing::warn!("cleaning up uploads: {err:#}");
+        }
+        if job.save_metadata {
+            let limits = crate::constants::rate_limits_per_minute()
+                .into_iter()
+                .map(|(k, v)| (k, Some(v)))
+                .collect::<HashMap<_, _>>();
+            if let Err(err) = self.monitor.flush_summary(
+                &base_dir.join("run-summary.json"),
+                &self.cost,
+                job,
+                &[],
+                &limits,
+                Some(&base_dir.join("run-events.ndjson")),
+            ) {
+                tracing::warn!("writing the partial run summary: {err:#}");
+            }
+        }
+    }
+
+    /// Summary-of-summaries pass: feed the stitched chunk outputs back through the
+    /// synthesize prompt and write `<name>-summary` in the transcript's format.
+    async fn synthesize(
+        &self,
+        job: &Job,
+        output_format: OutputFormat,
+        base_dir: &Path,
+        output_name: &str,
+        preamble: &str,
+        text: &str,
+    ) -> Result<Option<PathBuf>> {
+        let Some(converter) = &self.converter else {
+            tracing::warn!("--synthesize needs the conversion client; skipping");
+            return Ok(None);
+        };
+        // JSON runs synthesize from their Markdown rendering.
+        let format = match output_format {
+            OutputFormat::Json => OutputFormat::Markdown,
+            other => other,
+        };
+        let name = format!("{output_name}-summary");
+        let target = base_dir.join(format!("{name}.{}", format.extension()));
+        if job.skip_existing && target.exists() {
+            return Ok(Some(target));
+        }
+        self.emit(Progress {
+            scope: ProgressScope::Job {
+                id: job.job_id.clone(),
+                label: job.job_label.clone(),
+            },
+            stage: ProgressStage::Transcribe,
+            current: 0,
+            total: 1,
+            status: "synthesizing summary".into(),
+            finished: false,
+        });
+        let mut metadata = Map::new();
+        metadata.insert("source".into(), Value::String(job.source.clone()));
+        metadata.insert("export".into(), Value::String("synthesize".into()));
+        let summary = converter
+            .synthesize(
+                job.synthesis_model(),
+                &render_template(
+                    &with_language(self.templates.synthesize_prompt(format), job),
+                    &template_vars(job),
+                ),
+                text,
+                metadata,
+            )
+            .await?;
+        let preamble = if format == output_format {
+            preamble
+        } else {
+            ""
+        };
+        let path = self
+            .writer
+            .write(format, base_dir, &name, preamble, &summary)?;
+        Ok(Some(path))
+    }
+
+    /// For PDFs split by their outline, write `<name>-index.md` listing each
+    /// section and its pages, in the order they appear in the transcript.
+    fn write_section_index(
+        &self,
+        base_dir: &Path,
+        output_name: &str,
+        transcript: &Path,
+    ) -> Result<Option<PathBuf>> {
+        let sections: Vec<Value> = self
+            .normalizer
+            .chunk_descriptors()
+            .into_iter()
+            .filter(|chunk| chunk.get("chapter_title").is_some())
+            .filter(|chunk| chunk.get("chunk_start_page").is_some())
+            .collect();
+        if sections.is_empty() {
+            return Ok(None);
+        }
+        let transcript = transcript
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut index =
+            format!("# {output_name}\n\nSections summarized in [{transcript}]({transcript}).\n");
+        let mut source = None;
+        let mut number = 0;
+        for section in &sections {
+            let pdf = section.get("source_pdf").and_then(Value::as_str);
+            if number == 0 || pdf != source {
+                source = pdf;
+                number = 0;
+                let name = pdf
+                    .and_then(|pdf| Path::new(pdf).file_name())
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                index.push_str(&format!(
+                    "\n## {name}\n\n| # | Section | Pages |\n| --- | --- | --- |\n"
+                ));
+            }
+            let page = |key: &str| section.get(key).and_then(Value::as_u64).unwrap_or(0);
+            let mut title = section["chapter_title"]
+                .as_str()
+                .unwrap_or_default()
+                .replace('|', "\\|");
+            if let Some(parts) = section.get("chapter_parts").and_then(Value::as_u64) {
+                let part = section.get("chapter_part").and_then(Value::as_u64);
+                title.push_str(&format!(" (part {} of {parts})", part.unwrap_or(1)));
+            }
+            number += 1;
+            index.push_str(&format!(
+                "| {number} | {title} | {}-{} |\n",
+                page("chunk_start_page"),
+                page("chunk_end_page")
+            ));
+        }
+        fs::create_dir_all(base_dir)?;
+        let path = base_dir.join(format!("{output_name}-index.md"));
+        fs::write(&path, index).with_context(|| format!("writing {}", path.display()))?;
+        Ok(Some(path))
+    }
+
+    /// [`resolve_output_path`](crate::utils::resolve_output_path), asking on a blocking
+    /// thread when the policy is to prompt, and refusing to when the terminal is taken.
+    async fn resolve_output(
+        &self,
+        path: &Path,
+        is_dir: bool,
+        policy: ConflictPolicy,
+    ) -> Result<Option<PathBuf>> {
+        if policy != ConflictPolicy::Prompt || !path.exists() {
+            return crate::utils::resolve_output_path(path, is_dir, policy);
+        }
+        if !self.can_prompt {
+            bail!(
+                "{} already exists and the progress display owns the terminal; pass --on-conflict overwrite|rename|skip to decide without a prompt",
+                path.display()
+            );
+        }
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            crate::utils::resolve_output_path(&path, is_dir, policy)
+        })
+        .await?
+    }
+
+    /// Write `<name>.ffmetadata` from the transcript timestamps and, with
+    /// `--embed-chapters`, remux it into a copy of the normalized video.
+    fn export_chapters(
+        &self,
+        job: &Job,
+        base_dir: &Path,
+        output_name: &str,
+        text: &str,
+        extra_files: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        let chunks = self.normalizer.chunk_descriptors();
+        let chapters = chapters_from_transcript(text, &chunks);
+        if chapters.is_empty() {
+            tracing::warn!(
+                "transcript for {} has no timestamps; skipping chapters export",
+                job.source
+            );
+            return Ok(());
+        }
+        let metadata_path = base_dir.join(format!("{output_name}.ffmetadata"));
+        write_ffmetadata(&chapters, &metadata_path)?;
+        extra_files.push(metadata_path.clone());
+        if !job.embed_chapters {
+            return Ok(());
+        }
+
+        let video = chunks
+            .iter()
+            .find_map(|chunk| chunk.get("normalized_path").and_then(Value::as_str))
+            .map(PathBuf::from)
+            .filter(|path| path.extension().is_some_and(|ext| ext == "mp4"));
+        let Some(video) = video else {
+            tracing::warn!(
+                "no normalized video for {}; wrote chapters without embedding",
+                job.source
+            );
+            return Ok(());
+        };
+        let target = base_dir.join(format!("{output_name}-chapters.mp4"));
+        if job.skip_existing && target.exists() {
+            extra_files.push(target);
+            return Ok(());
+        }
+        // Only captions written by this run; an older .vtt may be from another transcript.
+        let subtitles = extra_files
+            .iter()
+            .find(|path| path.extension().is_some_and(|ext| ext == "vtt"))
+            .cloned();
+        match embed_chapters(&video, &metadata_path, subtitles.as_deref(), &target) {
+            Ok(path) => extra_files.push(path),
+            Err(err) => {
+                tracing::warn!("{err:#}");
+                self.monitor.note_event(
+                    "export.chapters_failed",
+                    json!({
+                        "source": video,
+                        "error": format!("{err:#}"),
+                    }),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Clean up a transcript before it is written: unwrap code fences around the
+    /// reply and, for LaTeX, check the document structure and ask for one repair.
+    ///
+    /// Problems that survive the repair are logged and recorded, and the transcript
+    /// is still written.
+    async fn validate_output(
+        &self,
+        job: &Job,
+        format: OutputFormat,
+        preamble: &str,
+        response: &str,
+    ) -> String {
+        let mut text = response.to_string();
+        if let Some(unwrapped) = strip_wrapper_fences(&text, format) {
+            self.monitor.note_event(
+                "validation.fences_stripped",
+                json!({ "source": job.source, "format": format.as_str() }),
+            );
+            text = unwrapped;
+        }
+        if format != OutputFormat::Latex {
+            return text;
+        }
+
+        let problems = latex_problems(&latex_document(preamble, &text));
+        if problems.is_empty() {
+            return text;
+        }
+        self.monitor.note_event(
+            "validation.latex_problems",
+            json!({ "source": job.source, "problems": problems }),
+        );
+        let Some(converter) = &self.converter else {
+            tracing::warn!(
+                "LaTeX transcript for {} has structural problems: {}",
+                job.source,
+                problems.join("; ")
+            );
+            return text;
+        };
+        let mut metadata = Map::new();
+        metadata.insert("source".into(), Value::String(job.source.clone()));
+        metadata.insert("export".into(), Value::String("latex_repair".into()));
+        let prompt = self
+            .templates
+            .latex_repair_prompt()
+            .replace("{{PROBLEMS}}", &format!("- {}", problems.join("\n- ")));
+        let repaired = match converter
+            .repair_latex(job.conversion_model(), &prompt, &text, metadata)
+            .await
+        {
+            Ok(repaired) => strip_wrapper_fences(&repaired, format).unwrap_or(repaired),
+            Err(err) => {
+                tracing::warn!("LaTeX repair for {} failed: {err:#}", job.source);
+                return text;
+            }
+        };
+        let remaining = latex_problems(&latex_document(preamble, &repaired));
+        self.monitor.note_event(
+            "validation.latex_repaired",
+            json!({ "source": job.source, "remaining": remaining }),
+        );
+        if !remaining.is_empty() {
+            tracing::warn!(
+                "LaTeX transcript for {} still has structural problems after repair: {}",
+                job.source,
+                remaining.join("; ")
+            );
+        }
+        if remaining.len() < problems.len() {
+            repaired
+        } else {
+            text
+        }
+    }
+
+    /// Validate a `--format json` response, giving the model one repair attempt.
+    async fn structured_transcript(
+        &self,
+        job: &Job,
+        response: &str,
+    ) -> Result<StructuredTranscript> {
+        let first_error = match StructuredTranscript::parse(response) {
+            Ok(transcript) => return Ok(transcript),
+            Err(err) => err,
+        };
+        let Some(converter) = &self.converter else {
+            return Err(first_error.context("structured transcript failed validation"));
+        };
+        self.monitor.note_event(
+            "validation.json_repair",
+            json!({ "source": job.source, "error": format!("{first_error:#}") }),
+        );
+        let mut metadata = Map::new();
+        metadata.insert("source".into(), Value::String(job.source.clone()));
+        metadata.insert("export".into(), Value::String("json_repair".into()));
+        let repaired = converter
+            .repair_json(
+                job.conversion_model(),
+                &self.templates.json_repair_prompt(),
+                response,
+                metadata,
+            )
+            .await?;
+        StructuredTranscript::parse(&repaired).with_context(|| {
+            format!(
+                "structured transcript still invalid after repair (first error: {first_error:#})"
+            )
+        })
+    }
+
     fn emit(&self, progress: Progress) {
         let _ = self.progress.send(progress);
     }
 }
 
+/// Built-in prompt variables for `job`, overridden by the user's own.
+fn template_vars(job: &Job) -> TemplateVars {
+    let mut vars = TemplateVars::new();
+    let source_name = Path::new(&job.source)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| job.source.clone());
+    vars.insert("SOURCE_NAME".into(), source_name);
+    vars.insert("DATE".into(), OffsetDateTime::now_utc().date().to_string());
+    vars.extend(job.vars.clone());
+    if let Some(language) = &job.language {
+        vars.insert("LANGUAGE".into(), language.clone());
+    }
+    vars
+}
+
+/// Ask for output in the job's language, unless the prompt already places
+/// `{{LANGUAGE}}` itself.
+fn with_language(prompt: String, job: &Job) -> String {
+    match &job.language {
+        Some(language) if !prompt.to_lowercase().contains("{{language") => format!(
+            "{prompt}\n\nWrite all output in the language '{language}'. Translate source content in any other language, keeping math, code, proper names, and timestamps unchanged."
+        ),
+        _ => prompt,
+    }
+}
+
 fn media_summary(assets: &[Asset]) -> String {
     let mut counts: HashMap<String, u64> = HashMap::new();
     for asset in assets {
@@ -544,8 +1298,9 @@ fn estimate_page_total(assets: &[Asset]) -> Option<u64> {
 fn infer_kind(assets: &[Asset]) -> Kind {
     if let Some(first) = assets.first() {
         match first.media.as_str() {
-            "video" => return Kind::Lecture,
-            "image" => return Kind::Slides,
+            "video" | "subtitles" => return Kind::Lecture,
+            "audio" => return Kind::Audio,
+            "image" | "presentation" => return Kind::Slides,
             _ => {}
         }
     }
@@ -556,8 +1311,10 @@ fn modality_for(assets: &[Asset]) -> &str {
     assets
         .first()
         .map(|asset| match asset.media.as_str() {
-            "video" | "audio" => "video",
+            "video" => "video",
+            "audio" => "audio",
             "pdf" => "pdf",
+            "text" => "text",
             _ => "image",
         })
         .unwrap_or("image")
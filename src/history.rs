@@ -0,0 +1,204 @@
+//! Every job recapit has finished, one JSON line each in
+//! `<data>/recapit/history.jsonl`, behind `recapit history` and
+//! `recapit report cost --aggregate`.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::cost::{CostSummary, ModelCostBreakdown};
+use crate::telemetry::RunSummary;
+use crate::utils::ensure_dir;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// RFC 3339.
+    pub finished_at: String,
+    pub source: String,
+    pub label: String,
+    pub model: String,
+    /// `succeeded`, `failed`, or `cancelled`.
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub requests: usize,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+    pub duration_seconds: f64,
+    #[serde(default)]
+    pub by_model: BTreeMap<String, ModelCostBreakdown>,
+}
+
+impl HistoryEntry {
+    /// A succeeded entry for a job that just finished; set `status`, `output`, and
+    /// `error` as needed.
+    pub fn new(
+        source: &str,
+        label: &str,
+        model: &str,
+        summary: &RunSummary,
+        costs: &CostSummary,
+    ) -> Self {
+        Self {
+            finished_at: OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+            source: source.to_string(),
+            label: label.to_string(),
+            model: model.to_string(),
+            status: "succeeded".into(),
+            output: None,
+            error: None,
+            requests: summary.total_requests,
+            input_tokens: summary.total_input_tokens,
+            output_tokens: summary.total_output_tokens,
+            cost_usd: costs.total_cost,
+            duration_seconds: summary.total_duration_seconds,
+            by_model: costs
+                .per_model
+                .iter()
+                .map(|(model, breakdown)| (model.clone(), breakdown.clone()))
+                .collect(),
+        }
+    }
+
+    fn finished(&self) -> Option<OffsetDateTime> {
+        OffsetDateTime::parse(&self.finished_at, &Rfc3339).ok()
+    }
+}
+
+pub fn history_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("recapit")
+        .join("history.jsonl")
+}
+
+/// Append `entry` as one line, so concurrent runs never interleave partial records.
+pub fn record(entry: &HistoryEntry) -> Result<()> {
+    let path = history_path();
+    ensure_dir(path.parent().unwrap_or(Path::new(".")))?;
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+/// Entries finished at or after `since`, oldest first. Missing history is empty.
+pub fn load(since: Option<OffsetDateTime>) -> Result<Vec<HistoryEntry>> {
+    let path = history_path();
+    match fs::read_to_string(&path) {
+        Ok(text) => Ok(parse(&text, since)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err).with_context(|| format!("reading {}", path.display())),
+    }
+}
+
+/// Lines that do not parse, such as one cut short by a crash, are skipped.
+fn parse(text: &str, since: Option<OffsetDateTime>) -> Vec<HistoryEntry> {
+    text.lines()
+        .filter_map(|line| serde_json::from_str::<HistoryEntry>(line).ok())
+        .filter(|entry| {
+            since.is_none_or(|since| entry.finished().is_some_and(|finished| finished >= since))
+        })
+        .collect()
+}
+
+/// Totals over a set of history entries.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Aggregate {
+    pub jobs: usize,
+    pub failed: usize,
+    pub requests: usize,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+    pub duration_seconds: f64,
+    pub by_model: BTreeMap<String, ModelCostBreakdown>,
+}
+
+pub fn aggregate(entries: &[HistoryEntry]) -> Aggregate {
+    let mut totals = Aggregate::default();
+    for entry in entries {
+        totals.jobs += 1;
+        if entry.status != "succeeded" {
+            totals.failed += 1;
+        }
+        totals.requests += entry.requests;
+        totals.input_tokens += entry.input_tokens;
+        totals.output_tokens += entry.output_tokens;
+        totals.cost_usd += entry.cost_usd;
+        totals.duration_seconds += entry.duration_seconds;
+        for (model, usage) in &entry.by_model {
+            let bucket = totals.by_model.entry(model.clone()).or_default();
+            bucket.input_cost += usage.input_cost;
+            bucket.output_cost += usage.output_cost;
+            bucket.total_cost += usage.total_cost;
+            bucket.input_tokens += usage.input_tokens;
+            bucket.output_tokens += usage.output_tokens;
+            bucket.cached_input_tokens += usage.cached_input_tokens;
+            bucket.cache_read_cost += usage.cache_read_cost;
+        }
+    }
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_lines_filter_and_aggregate() {
+        let entry = |finished_at: &str, status: &str, cost: f64| {
+            let mut entry = HistoryEntry::new(
+                "lecture.mp4",
+                "lecture",
+                "gemini-2.5-flash",
+                &RunSummary::default(),
+                &CostSummary::default(),
+            );
+            entry.finished_at = finished_at.into();
+            entry.status = status.into();
+            entry.cost_usd = cost;
+            entry.input_tokens = 100;
+            entry.by_model.insert(
+                "gemini-2.5-flash".into(),
+                ModelCostBreakdown {
+                    total_cost: cost,
+                    ..Default::default()
+                },
+            );
+            serde_json::to_string(&entry).unwrap()
+        };
+        let text = [
+            entry("2026-01-01T00:00:00Z", "succeeded", 0.5),
+            "{\"finished_at\": \"2026-01-0".into(),
+            entry("2026-02-01T00:00:00Z", "failed", 0.25),
+        ]
+        .join("\n");
+        assert_eq!(parse(&text, None).len(), 2);
+        let since = OffsetDateTime::parse("2026-01-15T00:00:00Z", &Rfc3339).unwrap();
+        let recent = parse(&text, Some(since));
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].status, "failed");
+
+        let totals = aggregate(&parse(&text, None));
+        assert_eq!((totals.jobs, totals.failed), (2, 1));
+        assert_eq!(totals.input_tokens, 200);
+        assert_eq!(totals.by_model["gemini-2.5-flash"].total_cost, 0.75);
+    }
+}
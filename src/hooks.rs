@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::io::AsyncWriteExt;
+
+use crate::cost::CostSummary;
+use crate::telemetry::RunSummary;
+
+const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// An action from the `on_complete` config list, run after every job.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Hook {
+    /// POST the payload as JSON.
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    /// Run through `sh -c` with the payload on stdin.
+    Command { cmd: String },
+}
+
+/// The JSON handed to hooks when a job succeeds or fails.
+pub fn job_payload(
+    source: &str,
+    label: &str,
+    output: Option<&Path>,
+    error: Option<&str>,
+    summary: &RunSummary,
+    costs: &CostSummary,
+) -> Value {
+    json!({
+        "event": "job_finished",
+        "status": if error.is_some() { "failed" } else { "succeeded" },
+        "source": source,
+        "label": label,
+        "output": output,
+        "error": error,
+        "summary": summary,
+        "cost": costs,
+        "timestamp": OffsetDateTime::now_utc().format(&Rfc3339).unwrap_or_default(),
+    })
+}
+
+/// Run every hook; failures are logged so a broken hook never fails the job itself.
+pub async fn fire(hooks: &[Hook], payload: &Value) {
+    for hook in hooks {
+        if let Err(err) = run_hook(hook, payload).await {
+            tracing::warn!("on_complete hook failed: {err:#}");
+        }
+    }
+}
+
+async fn run_hook(hook: &Hook, payload: &Value) -> Result<()> {
+    match hook {
+        Hook::Webhook { url, headers } => {
+            let client = reqwest::Client::builder().timeout(HOOK_TIMEOUT).build()?;
+            let mut request = client.post(url).json(payload);
+            for (name, value) in headers {
+                request = request.header(name, value);
+            }
+            request
+                .send()
+                .await
+                .with_context(|| format!("POST {url}"))?
+                .error_for_status()
+                .with_context(|| format!("POST {url}"))?;
+        }
+        Hook::Command { cmd } => {
+            let mut child = tokio::process::Command::new("sh")
+                .arg("-c")
+                .arg(cmd)
+                .env("RECAPIT_STATUS", payload["status"].as_str().unwrap_or(""))
+                .env("RECAPIT_SOURCE", payload["source"].as_str().unwrap_or(""))
+                .env("RECAPIT_OUTPUT", payload["output"].as_str().unwrap_or(""))
+                .stdin(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn()
+                .with_context(|| format!("running `{cmd}`"))?;
+            if let Some(mut stdin) = child.stdin.take() {
+                // A command that ignores stdin may close it early; that is not an error.
+                let _ = stdin.write_all(payload.to_string().as_bytes()).await;
+            }
+            let status = tokio::time::timeout(HOOK_TIMEOUT, child.wait())
+                .await
+                .with_context(|| format!("`{cmd}` timed out"))??;
+            if !status.success() {
+                bail!("`{cmd}` exited with {status}");
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,288 @@
+use anyhow::{Context, Result};
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, ImageBuffer, Pixel, Rgba, RgbaImage};
+use std::path::Path;
+
+/// Longest side images are downscaled to by default; larger inputs cost tokens and
+/// upload time without improving recognition.
+pub const DEFAULT_IMAGE_MAX_DIMENSION: u32 = 3072;
+/// Largest tilt the deskew pass corrects; anything beyond is probably intentional.
+const MAX_SKEW_DEGREES: f32 = 5.0;
+const SKEW_STEP_DEGREES: f32 = 0.25;
+/// Skew is estimated on a thumbnail this size, which is plenty for line angles.
+const SKEW_SAMPLE_DIMENSION: u32 = 800;
+/// Fraction of the darkest and lightest pixels clipped when stretching contrast.
+const CONTRAST_CLIP: f32 = 0.01;
+
+/// Cleanup applied to images before upload (`image` in `recapit.yaml`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImagePrep {
+    pub max_dimension: Option<u32>,
+    pub grayscale: bool,
+    pub contrast: bool,
+    pub deskew: bool,
+    pub sharpen: bool,
+}
+
+impl Default for ImagePrep {
+    fn default() -> Self {
+        Self {
+            max_dimension: Some(DEFAULT_IMAGE_MAX_DIMENSION),
+            grayscale: false,
+            contrast: false,
+            deskew: false,
+            sharpen: false,
+        }
+    }
+}
+
+impl ImagePrep {
+    pub fn is_noop(&self) -> bool {
+        self.max_dimension.is_none()
+            && !self.grayscale
+            && !self.contrast
+            && !self.deskew
+            && !self.sharpen
+    }
+
+    fn only_resizes(&self) -> bool {
+        !self.grayscale && !self.contrast && !self.deskew && !self.sharpen
+    }
+
+    /// Write the prepared version of `source` to `dest`.
+    ///
+    /// Returns `false` without writing when the image needs no changes, i.e. only
+    /// downscaling is enabled and it already fits.
+    pub fn apply(&self, source: &Path, dest: &Path) -> Result<bool> {
+        if self.only_resizes() {
+            let (width, height) = image::image_dimensions(source)
+                .with_context(|| format!("reading {}", source.display()))?;
+            if self
+                .max_dimension
+                .is_none_or(|max| width.max(height) <= max)
+            {
+                return Ok(false);
+            }
+        }
+
+        let mut img =
+            image::open(source).with_context(|| format!("decoding {}", source.display()))?;
+        if self.deskew {
+            let angle = skew_angle(&img.to_luma8());
+            if angle.abs() >= SKEW_STEP_DEGREES {
+                img = level(&img, angle);
+            }
+        }
+        if self.grayscale {
+            img = DynamicImage::ImageLuma8(img.to_luma8());
+        }
+        if self.contrast {
+            img = stretch_contrast(img);
+        }
+        if let Some(max) = self.max_dimension {
+            if img.width().max(img.height()) > max {
+                img = img.resize(max, max, FilterType::Lanczos3);
+            }
+        }
+        if self.sharpen {
+            img = img.unsharpen(1.0, 2);
+        }
+
+        let is_jpeg = dest
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg"));
+        if is_jpeg && img.color().has_alpha() {
+            img = DynamicImage::ImageRgb8(img.to_rgb8());
+        }
+        img.save(dest)
+            .with_context(|| format!("writing {}", dest.display()))?;
+        Ok(true)
+    }
+}
+
+/// Tilt of the text lines in degrees (positive when lines fall to the right), found
+/// by the projection profile: rows of ink line up sharpest at the true angle.
+fn skew_angle(gray: &GrayImage) -> f32 {
+    let sample = image::imageops::thumbnail(
+        gray,
+        gray.width().min(SKEW_SAMPLE_DIMENSION),
+        gray.height().min(SKEW_SAMPLE_DIMENSION),
+    );
+    let mean = sample.pixels().map(|p| p.0[0] as f32).sum::<f32>()
+        / (sample.width() * sample.height()).max(1) as f32;
+    let ink: Vec<(f32, f32)> = sample
+        .enumerate_pixels()
+        .filter(|(_, _, p)| (p.0[0] as f32) < mean * 0.75)
+        .map(|(x, y, _)| (x as f32, y as f32))
+        .collect();
+    if ink.len() < 100 {
+        return 0.0;
+    }
+
+    let offset = (sample.width() as f32 * MAX_SKEW_DEGREES.to_radians().tan()).ceil() + 1.0;
+    let rows = sample.height() as usize + 2 * offset as usize + 2;
+    let steps = (MAX_SKEW_DEGREES / SKEW_STEP_DEGREES).round() as i32;
+    // Try small angles first so ties resolve toward leaving the image alone.
+    let mut candidates: Vec<i32> = (-steps..=steps).collect();
+    candidates.sort_by_key(|step| step.abs());
+
+    let mut best = (0.0_f32, f64::MIN);
+    let mut histogram = vec![0u32; rows];
+    for step in candidates {
+        let angle = step as f32 * SKEW_STEP_DEGREES;
+        let slope = angle.to_radians().tan();
+        histogram.iter_mut().for_each(|count| *count = 0);
+        for (x, y) in &ink {
+            let row = (y - x * slope + offset).round() as usize;
+            histogram[row] += 1;
+        }
+        let score: f64 = histogram.iter().map(|&c| (c as f64) * (c as f64)).sum();
+        if score > best.1 {
+            best = (angle, score);
+        }
+    }
+    best.0
+}
+
+/// Rotate the content so lines tilted by `degrees` come out horizontal, filling the
+/// exposed corners with white.
+fn level(img: &DynamicImage, degrees: f32) -> DynamicImage {
+    let source = img.to_rgba8();
+    let (width, height) = source.dimensions();
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let white = Rgba([255, 255, 255, 255]);
+    let rotated: RgbaImage = ImageBuffer::from_fn(width, height, |x, y| {
+        let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+        let sx = cx + dx * cos - dy * sin;
+        let sy = cy + dx * sin + dy * cos;
+        bilinear(&source, sx, sy).unwrap_or(white)
+    });
+    if img.color().has_alpha() {
+        DynamicImage::ImageRgba8(rotated)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(rotated).to_rgb8())
+    }
+}
+
+fn bilinear(image: &RgbaImage, x: f32, y: f32) -> Option<Rgba<u8>> {
+    if x < 0.0 || y < 0.0 || x > (image.width() - 1) as f32 || y > (image.height() - 1) as f32 {
+        return None;
+    }
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = (
+        (x0 + 1).min(image.width() - 1),
+        (y0 + 1).min(image.height() - 1),
+    );
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+    let corners = [
+        (image.get_pixel(x0, y0), (1.0 - fx) * (1.0 - fy)),
+        (image.get_pixel(x1, y0), fx * (1.0 - fy)),
+        (image.get_pixel(x0, y1), (1.0 - fx) * fy),
+        (image.get_pixel(x1, y1), fx * fy),
+    ];
+    let mut out = [0u8; 4];
+    for (channel, value) in out.iter_mut().enumerate() {
+        let sum: f32 = corners
+            .iter()
+            .map(|(pixel, weight)| pixel.0[channel] as f32 * weight)
+            .sum();
+        *value = sum.round().clamp(0.0, 255.0) as u8;
+    }
+    Some(Rgba(out))
+}
+
+/// Stretch brightness so the darkest and lightest percentile map to black and white,
+/// which lifts faded ink and grey whiteboard backgrounds.
+fn stretch_contrast(img: DynamicImage) -> DynamicImage {
+    let gray = img.to_luma8();
+    let mut histogram = [0usize; 256];
+    for pixel in gray.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+    let clip = (gray.len() as f32 * CONTRAST_CLIP) as usize;
+    let low = clip_level(&histogram, clip, 0..256);
+    let high = clip_level(&histogram, clip, (0..256).rev());
+    if high <= low + 1 {
+        return img;
+    }
+    let scale = 255.0 / (high - low) as f32;
+    let map = |value: u8| {
+        ((value as f32 - low as f32) * scale)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    };
+    match img {
+        DynamicImage::ImageLuma8(mut buffer) => {
+            stretch_pixels(&mut buffer, map);
+            DynamicImage::ImageLuma8(buffer)
+        }
+        DynamicImage::ImageRgba8(mut buffer) => {
+            stretch_pixels(&mut buffer, map);
+            DynamicImage::ImageRgba8(buffer)
+        }
+        other => {
+            let mut buffer = other.to_rgb8();
+            stretch_pixels(&mut buffer, map);
+            DynamicImage::ImageRgb8(buffer)
+        }
+    }
+}
+
+/// The first level, walking `levels`, past which more than `clip` pixels lie.
+fn clip_level(histogram: &[usize; 256], clip: usize, levels: impl Iterator<Item = usize>) -> usize {
+    let mut seen = 0;
+    for level in levels {
+        seen += histogram[level];
+        if seen > clip {
+            return level;
+        }
+    }
+    0
+}
+
+fn stretch_pixels<P: Pixel<Subpixel = u8>>(
+    buffer: &mut ImageBuffer<P, Vec<u8>>,
+    map: impl Fn(u8) -> u8,
+) {
+    for pixel in buffer.pixels_mut() {
+        pixel.apply_without_alpha(&map);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    #[test]
+    fn skew_angle_finds_tilted_lines() {
+        let tilt = 2.0_f32;
+        let slope = tilt.to_radians().tan();
+        let page = GrayImage::from_fn(600, 400, |x, y| {
+            let row = y as f32 - x as f32 * slope;
+            if row > 0.0 && (row as u32) % 40 < 3 {
+                Luma([0])
+            } else {
+                Luma([255])
+            }
+        });
+        let estimate = skew_angle(&page);
+        assert!((estimate - tilt).abs() <= SKEW_STEP_DEGREES, "{estimate}");
+
+        let level_page =
+            GrayImage::from_fn(
+                600,
+                400,
+                |_, y| {
+                    if y % 40 < 3 {
+                        Luma([0])
+                    } else {
+                        Luma([255])
+                    }
+                },
+            );
+        assert_eq!(skew_angle(&level_page), 0.0);
+    }
+}
@@ -0,0 +1,170 @@
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use flate2::read::GzDecoder;
+use tar::Archive;
+use zip::ZipArchive;
+
+use crate::utils::{ensure_dir, slugify};
+use crate::video::sha256sum;
+
+const COMPLETE_MARKER: &str = ".recapit-extracted";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn archive_format(path: &Path) -> Option<ArchiveFormat> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    if name.ends_with(".zip") {
+        Some(ArchiveFormat::Zip)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveFormat::TarGz)
+    } else if name.ends_with(".tar") {
+        Some(ArchiveFormat::Tar)
+    } else {
+        None
+    }
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    path.is_file() && archive_format(path).is_some()
+}
+
+pub(crate) fn archives_root() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("recapit")
+        .join("archives")
+}
+
+/// Unpack an archive into the cache, reusing a previous extraction of the same bytes.
+///
+/// Returns the directory discovery should start from: the archive's single
+/// top-level folder when it has one, otherwise the extraction root.
+pub fn extract_archive(path: &Path) -> Result<PathBuf> {
+    let format = archive_format(path)
+        .with_context(|| format!("{} is not a supported archive", path.display()))?;
+    let dest = extraction_dir(path)?;
+    let marker = dest.join(COMPLETE_MARKER);
+    if marker.exists() {
+        crate::cache::touch(&marker);
+    } else {
+        if dest.exists() {
+            fs::remove_dir_all(&dest)?;
+        }
+        ensure_dir(&dest)?;
+        let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        match format {
+            ArchiveFormat::Zip => extract_zip(file, &dest)?,
+            ArchiveFormat::Tar => Archive::new(file).unpack(&dest)?,
+            ArchiveFormat::TarGz => Archive::new(GzDecoder::new(file)).unpack(&dest)?,
+        }
+        fs::write(&marker, path.to_string_lossy().as_bytes())?;
+    }
+    content_root(&dest)
+}
+
+/// Where `path` is (or would be) extracted. Keyed by content hash so edits re-extract.
+pub fn extraction_dir(path: &Path) -> Result<PathBuf> {
+    let stem = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stem = stem
+        .trim_end_matches(".gz")
+        .trim_end_matches(".tgz")
+        .trim_end_matches(".tar")
+        .trim_end_matches(".zip");
+    let hash = sha256sum(path)?;
+    Ok(archives_root().join(format!("{}-{}", slugify(stem), &hash[..12])))
+}
+
+/// Remove the extracted copy of `path`, if any.
+pub fn remove_extracted(path: &Path) -> Result<()> {
+    if !is_archive(path) {
+        return Ok(());
+    }
+    let dir = extraction_dir(path)?;
+    if dir.exists() {
+        fs::remove_dir_all(&dir)
+            .with_context(|| format!("removing extracted files in {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+fn extract_zip(file: File, dest: &Path) -> Result<()> {
+    let mut archive = ZipArchive::new(file).context("reading zip archive")?;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        // `enclosed_name` rejects absolute paths and `..` traversal.
+        let Some(relative) = entry.enclosed_name() else {
+            bail!("zip entry {} escapes the archive root", entry.name());
+        };
+        if is_metadata_entry(&relative) {
+            continue;
+        }
+        let target = dest.join(relative);
+        if entry.is_dir() {
+            ensure_dir(&target)?;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            ensure_dir(parent)?;
+        }
+        let mut out = File::create(&target)?;
+        io::copy(&mut entry, &mut out)?;
+    }
+    Ok(())
+}
+
+/// macOS resource forks and Finder metadata that zip tools add alongside real files.
+pub(super) fn is_metadata_entry(path: &Path) -> bool {
+    path.components().any(|component| match component {
+        Component::Normal(name) => {
+            let name = name.to_string_lossy();
+            name == "__MACOSX" || name == ".DS_Store" || name.starts_with("._")
+        }
+        _ => false,
+    })
+}
+
+fn content_root(dest: &Path) -> Result<PathBuf> {
+    let entries: Vec<_> = fs::read_dir(dest)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name();
+            name != COMPLETE_MARKER && !is_metadata_entry(Path::new(&name))
+        })
+        .collect();
+    match entries.as_slice() {
+        [single] if single.path().is_dir() => Ok(single.path()),
+        _ => Ok(dest.to_path_buf()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_archive_suffixes_and_metadata() {
+        assert_eq!(
+            archive_format(Path::new("week1.tar.gz")),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(
+            archive_format(Path::new("Slides.ZIP")),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(archive_format(Path::new("notes.pdf")), None);
+        assert!(is_metadata_entry(Path::new("__MACOSX/deck/._a.pdf")));
+        assert!(!is_metadata_entry(Path::new("deck/a.pdf")));
+    }
+}
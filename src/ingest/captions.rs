@@ -0,0 +1,143 @@
+use std::sync::OnceLock;
+
+use anyhow::{bail, Result};
+use regex::Regex;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+    pub text: String,
+}
+
+fn timing_line() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"^\s*((?:\d+:)?\d{1,2}:\d{2}[.,]\d{1,3})\s*-->\s*((?:\d+:)?\d{1,2}:\d{2}[.,]\d{1,3})",
+        )
+        .unwrap()
+    })
+}
+
+fn markup() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"<[^>]*>|\{\\[^}]*\}").unwrap())
+}
+
+/// Parse SRT or WebVTT captions into cues. Styling tags and positioning hints are dropped.
+pub fn parse_captions(text: &str) -> Result<Vec<Cue>> {
+    let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    let mut cues = Vec::new();
+    for block in text.split("\n\n") {
+        let mut lines = block.lines().skip_while(|line| line.trim().is_empty());
+        let mut timing = None;
+        for line in lines.by_ref() {
+            if let Some(captures) = timing_line().captures(line) {
+                timing = Some((parse_timestamp(&captures[1]), parse_timestamp(&captures[2])));
+                break;
+            }
+        }
+        // Headers, NOTE/STYLE/REGION blocks and stray numbering carry no timing line.
+        let Some((Some(start_seconds), Some(end_seconds))) = timing else {
+            continue;
+        };
+        let body = lines
+            .map(|line| markup().replace_all(line, "").trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if body.is_empty() {
+            continue;
+        }
+        cues.push(Cue {
+            start_seconds,
+            end_seconds,
+            text: decode_entities(&body),
+        });
+    }
+    if cues.is_empty() {
+        bail!("no caption cues found");
+    }
+    Ok(cues)
+}
+
+/// Render cues as `[MM:SS] text` lines (hours are added once the track passes an hour).
+pub fn cues_to_transcript(cues: &[Cue]) -> String {
+    let with_hours = cues
+        .last()
+        .map(|cue| cue.end_seconds >= 3600.0)
+        .unwrap_or(false);
+    let mut out = String::new();
+    let mut previous: Option<&str> = None;
+    for cue in cues {
+        // Rolling captions (common in auto-generated VTT) repeat the previous line.
+        if previous == Some(cue.text.as_str()) {
+            continue;
+        }
+        out.push_str(&format!(
+            "[{}] {}\n",
+            format_clock(cue.start_seconds, with_hours),
+            cue.text
+        ));
+        previous = Some(cue.text.as_str());
+    }
+    out
+}
+
+fn parse_timestamp(value: &str) -> Option<f64> {
+    let value = value.replace(',', ".");
+    let mut total = 0.0;
+    for part in value.split(':') {
+        total = total * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(total)
+}
+
+fn format_clock(seconds: f64, with_hours: bool) -> String {
+    let total = seconds.max(0.0).floor() as u64;
+    let (hours, minutes, secs) = (total / 3600, (total % 3600) / 60, total % 60);
+    if with_hours {
+        format!("{hours:02}:{minutes:02}:{secs:02}")
+    } else {
+        format!("{:02}:{secs:02}", minutes + hours * 60)
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&#39;", "'")
+        .replace("&quot;", "\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_srt_blocks() {
+        let srt = "1\n00:00:01,000 --> 00:00:04,500\n<i>Hello</i> there\nclass\n\n2\n00:01:05,250 --> 00:01:07,000\n{\\an8}Next &amp; last\n";
+        let cues = parse_captions(srt).unwrap();
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].text, "Hello there class");
+        assert_eq!(cues[1].start_seconds, 65.25);
+        assert_eq!(cues[1].text, "Next & last");
+        assert_eq!(
+            cues_to_transcript(&cues),
+            "[00:01] Hello there class\n[01:05] Next & last\n"
+        );
+    }
+
+    #[test]
+    fn parses_vtt_with_headers_and_settings() {
+        let vtt = "WEBVTT\nKind: captions\n\nNOTE produced by hand\n\nintro\n00:05.000 --> 00:07.000 align:start position:0%\n<c.yellow>Welcome</c>\n\n01:00:00.000 --> 01:00:02.000\nWelcome\n";
+        let cues = parse_captions(vtt).unwrap();
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].start_seconds, 5.0);
+        assert_eq!(cues[1].start_seconds, 3600.0);
+        assert_eq!(cues_to_transcript(&cues), "[00:00:05] Welcome\n");
+    }
+}
@@ -0,0 +1,153 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use reqwest::blocking::RequestBuilder;
+use reqwest::header::{HeaderMap, CONTENT_TYPE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE};
+use reqwest::StatusCode;
+
+use crate::cache::format_size;
+use crate::progress::DownloadMeter;
+
+/// Largest download taken from a URL or Drive unless `download.max_size` says
+/// otherwise; the Files API accepts nothing bigger.
+pub const DEFAULT_MAX_DOWNLOAD_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Download into `part`, continuing from its length with a `Range` request when an
+/// earlier attempt left it behind. The resource's ETag or Last-Modified is kept in
+/// `<part>.validator` and sent as `If-Range`, so a file that changed since is sent
+/// whole and replaces the partial one, as it does from a server that ignores the
+/// range. A partial file without a validator is started over. Returns the
+/// response's Content-Type.
+///
+/// `request` builds the GET; it is called again when the partial file has to be
+/// thrown away. A body larger than `max_bytes` is refused up front when the server
+/// states its length, and otherwise abandoned once it passes the limit.
+pub(crate) fn fetch_resumable(
+    request: impl Fn() -> RequestBuilder,
+    part: &Path,
+    meter: &mut DownloadMeter,
+    max_bytes: u64,
+) -> Result<Option<String>> {
+    let validator_path = validator_path(part);
+    let validator = fs::read_to_string(&validator_path).ok();
+    let have = match &validator {
+        Some(_) => fs::metadata(part).map(|meta| meta.len()).unwrap_or(0),
+        None => 0,
+    };
+    let mut response = match validator.as_deref().filter(|_| have > 0) {
+        Some(validator) => request()
+            .header(RANGE, format!("bytes={have}-"))
+            .header(IF_RANGE, validator.trim())
+            .send()?,
+        None => request().send()?,
+    };
+    if have > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        // The partial file is stale or longer than the resource; start over.
+        fs::remove_file(part)?;
+        let _ = fs::remove_file(&validator_path);
+        return fetch_resumable(request, part, meter, max_bytes);
+    }
+    if !response.status().is_success() {
+        bail!("download failed with status {}", response.status());
+    }
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let resumed = response.status() == StatusCode::PARTIAL_CONTENT;
+    let offset = if resumed { have } else { 0 };
+    let total = response.content_length().map(|length| length + offset);
+    if let Some(total) = total.filter(|&total| total > max_bytes) {
+        let _ = fs::remove_file(part);
+        bail!(too_large(Some(total), max_bytes));
+    }
+    let mut file = if resumed {
+        meter.resume_from(offset);
+        OpenOptions::new().append(true).open(part)?
+    } else {
+        match response_validator(response.headers()) {
+            Some(validator) => fs::write(&validator_path, validator)?,
+            None => {
+                let _ = fs::remove_file(&validator_path);
+            }
+        }
+        File::create(part)?
+    };
+
+    let mut done = offset;
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = response.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buffer[..read])?;
+        done += read as u64;
+        if done > max_bytes {
+            drop(file);
+            let _ = fs::remove_file(part);
+            bail!(too_large(None, max_bytes));
+        }
+        meter.update(done, total);
+    }
+    file.flush()?;
+    if let Some(total) = total.filter(|&total| done < total) {
+        bail!("download stopped at byte {done} of {total}; run again to resume it");
+    }
+    meter.finish(done);
+    let _ = fs::remove_file(&validator_path);
+    Ok(content_type)
+}
+
+fn validator_path(part: &Path) -> PathBuf {
+    let mut path = part.as_os_str().to_owned();
+    path.push(".validator");
+    PathBuf::from(path)
+}
+
+/// What `If-Range` can name the response's version by: a strong ETag, or else
+/// Last-Modified. Weak ETags do not qualify.
+fn response_validator(headers: &HeaderMap) -> Option<String> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    header(ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(LAST_MODIFIED))
+        .map(str::to_string)
+}
+
+/// `size` is the stated length, or `None` when the body ran past the limit
+/// without one.
+fn too_large(size: Option<u64>, limit: u64) -> String {
+    let size = size
+        .map(|size| format!("{}, ", format_size(size)))
+        .unwrap_or_default();
+    format!(
+        "file is {size}over the {} download limit; raise download.max_size in recapit.yaml or RECAPIT_MAX_DOWNLOAD_SIZE",
+        format_size(limit)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resumes_are_validated_by_strong_etag_or_date() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            LAST_MODIFIED,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        headers.insert(ETAG, "W/\"weak\"".parse().unwrap());
+        assert_eq!(
+            response_validator(&headers).as_deref(),
+            Some("Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+        headers.insert(ETAG, "\"v2\"".parse().unwrap());
+        assert_eq!(response_validator(&headers).as_deref(), Some("\"v2\""));
+        assert!(response_validator(&HeaderMap::new()).is_none());
+    }
+}
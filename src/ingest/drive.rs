@@ -1,5 +1,4 @@
-use std::fs::{self, File};
-use std::io::copy;
+use std::fs;
 use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, bail, Context, Result};
@@ -7,88 +6,316 @@ use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
 use time::{Duration, OffsetDateTime};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::debug;
+use url::Url;
 
+use super::download::{fetch_resumable, DEFAULT_MAX_DOWNLOAD_BYTES};
+use super::drive_auth::user_access_token;
 use crate::core::{Asset, Job, SourceKind};
+use crate::progress::{DownloadMeter, Progress};
 use crate::utils::ensure_dir;
 use crate::video::sha256sum;
 
-const SCOPE: &str = "https://www.googleapis.com/auth/drive.readonly";
+pub(crate) const SCOPE: &str = "https://www.googleapis.com/auth/drive.readonly";
+const FILES_URL: &str = "https://www.googleapis.com/drive/v3/files";
+const FOLDER_MIME: &str = "application/vnd.google-apps.folder";
+/// Google-native formats that have no binary content and must be exported.
+const EXPORT_AS_PDF: &[&str] = &[
+    "application/vnd.google-apps.document",
+    "application/vnd.google-apps.presentation",
+];
 
 #[derive(Debug, Clone)]
 pub struct DriveIngestor {
     cache_dir: PathBuf,
     client: Client,
+    progress: Option<UnboundedSender<Progress>>,
+    max_bytes: u64,
 }
 
 impl DriveIngestor {
     pub fn new(cache_dir: Option<PathBuf>) -> Result<Self> {
-        let cache = cache_dir.unwrap_or_else(|| std::env::temp_dir().join("recapit-drive-cache"));
+        let cache = cache_dir.unwrap_or_else(default_cache_dir);
         ensure_dir(&cache)?;
         Ok(Self {
             cache_dir: cache,
             client: Client::builder()
                 .timeout(std::time::Duration::from_secs(120))
                 .build()?,
+            progress: None,
+            max_bytes: DEFAULT_MAX_DOWNLOAD_BYTES,
         })
     }
 
+    /// Refuse downloads larger than `max_bytes`.
+    pub fn with_max_download(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Report download progress on `tx`.
+    pub fn with_progress(mut self, tx: UnboundedSender<Progress>) -> Self {
+        self.progress = Some(tx);
+        self
+    }
+
     pub fn discover(&self, job: &Job) -> Result<Vec<Asset>> {
-        let source = job.source.trim();
-        let file_id = if let Some(rest) = source.strip_prefix("drive://") {
-            rest
-        } else if let Some(rest) = source.strip_prefix("gdrive://") {
-            rest
-        } else {
-            return Ok(vec![]);
-        }
-        .trim();
+        let file_id = match parse_source(&job.source) {
+            Some(DriveSource::Folder(folder_id)) => return self.discover_folder(&folder_id, job),
+            Some(DriveSource::File(file_id)) => file_id,
+            None => return Ok(vec![]),
+        };
         if file_id.is_empty() {
             bail!("Drive URI missing file identifier");
         }
-        let destination = self.cache_dir.join(file_id);
-        if !destination.exists() {
-            let creds = ServiceAccountCredentials::load_from_env()?;
-            let token = creds.fetch_token(&self.client)?;
-            self.download_file(file_id, &destination, &token)?;
-        }
-
-        let media = infer_media(&destination);
-        let mime = guess_mime(&destination);
+        let token = self.access_token()?;
+        let file = self.file_metadata(&file_id, &token)?;
+        let Some((destination, media, export)) = self.fetch_file(&file, &token, &job.job_id)?
+        else {
+            bail!(
+                "Drive file {} ({}) is not a format recapit can transcribe",
+                file.name,
+                file.mime_type
+            );
+        };
         let meta = serde_json::json!({
-            "drive_file_id": file_id,
+            "drive_file_id": file.id,
+            "drive_mime": file.mime_type,
+            "drive_exported": export,
             "sha256": sha256sum(&destination)?,
             "size_bytes": destination.metadata().ok().map(|m| m.len()),
         });
         Ok(vec![Asset {
+            mime: Some(guess_mime(&destination).into()),
             path: destination,
             media: media.into(),
             page_index: None,
             source_kind: SourceKind::Drive,
-            mime: Some(mime.into()),
             meta,
         }])
     }
 
-    fn download_file(&self, file_id: &str, destination: &Path, token: &str) -> Result<()> {
-        ensure_dir(destination.parent().unwrap_or_else(|| Path::new(".")))?;
-        let url = format!("https://www.googleapis.com/drive/v3/files/{file_id}?alt=media");
-        let mut response = self
+    /// A token from the service account in `GOOGLE_APPLICATION_CREDENTIALS` or,
+    /// without one, from the sign-in cached by `recapit drive login`.
+    fn access_token(&self) -> Result<String> {
+        if std::env::var_os("GOOGLE_APPLICATION_CREDENTIALS").is_some() {
+            return ServiceAccountCredentials::load_from_env()?.fetch_token(&self.client);
+        }
+        user_access_token(&self.client)?.ok_or_else(|| {
+            anyhow!(
+                "Drive sources need credentials: run `recapit drive login` to sign in with your Google account, or set GOOGLE_APPLICATION_CREDENTIALS to a service account key"
+            )
+        })
+    }
+
+    fn file_metadata(&self, file_id: &str, token: &str) -> Result<DriveFile> {
+        let response = self
             .client
-            .get(url)
+            .get(format!("{FILES_URL}/{file_id}"))
             .bearer_auth(token)
+            .query(&[
+                ("fields", "id, name, mimeType, modifiedTime"),
+                ("supportsAllDrives", "true"),
+            ])
             .send()
-            .with_context(|| format!("Downloading Drive file {file_id}"))?;
+            .with_context(|| format!("Looking up Drive file {file_id}"))?;
         if !response.status().is_success() {
-            bail!("Drive download failed with status {}", response.status());
+            bail!(
+                "Drive file lookup for {file_id} failed with status {}; share the file with the service account",
+                response.status()
+            );
         }
+        Ok(response.json()?)
+    }
+
+    /// Download `file` into the cache, or export it to PDF when it is a Google Doc
+    /// or Slides deck. Returns the local path, its media, and whether it was
+    /// exported, or `None` for formats recapit cannot read.
+    fn fetch_file(
+        &self,
+        file: &DriveFile,
+        token: &str,
+        job_id: &str,
+    ) -> Result<Option<(PathBuf, &'static str, bool)>> {
+        let export = EXPORT_AS_PDF.contains(&file.mime_type.as_str());
+        let name = if export {
+            format!("{}.pdf", sanitize_name(&file.name))
+        } else {
+            sanitize_name(&file.name)
+        };
+        let Some(media) = infer_media(Path::new(&name)) else {
+            return Ok(None);
+        };
+        // Keyed by id and revision time so edits in Drive invalidate the cache.
+        let revision = file
+            .modified_time
+            .as_deref()
+            .map(sanitize_name)
+            .unwrap_or_else(|| "current".into());
+        let destination = self.cache_dir.join(&file.id).join(revision).join(&name);
+        if !destination.exists() {
+            if export {
+                self.export_pdf(&file.id, &destination, token, job_id)?;
+            } else {
+                self.download_file(&file.id, &destination, token, job_id)?;
+            }
+        }
+        Ok(Some((destination, media, export)))
+    }
+
+    /// One asset per supported file in the folder, descending into subfolders when
+    /// `recursive` is set. Google Docs and Slides are exported to PDF.
+    fn discover_folder(&self, folder_id: &str, job: &Job) -> Result<Vec<Asset>> {
+        if folder_id.is_empty() {
+            bail!("Drive URI missing folder identifier");
+        }
+        let token = self.access_token()?;
+
+        let mut files = Vec::new();
+        let mut pending = vec![(folder_id.to_string(), PathBuf::new())];
+        while let Some((parent, prefix)) = pending.pop() {
+            for file in self.list_folder(&parent, &token)? {
+                if file.mime_type == FOLDER_MIME {
+                    if job.recursive {
+                        pending.push((file.id.clone(), prefix.join(sanitize_name(&file.name))));
+                    }
+                    continue;
+                }
+                files.push((prefix.clone(), file));
+            }
+        }
+        files.sort_by_key(|(prefix, file)| prefix.join(&file.name));
+
+        let mut assets = Vec::new();
+        for (prefix, file) in files {
+            let Some((destination, media, export)) = self.fetch_file(&file, &token, &job.job_id)?
+            else {
+                debug!("skipping unsupported Drive file {}", file.name);
+                continue;
+            };
+            let meta = serde_json::json!({
+                "drive_file_id": file.id,
+                "drive_folder_id": folder_id,
+                "drive_path": prefix.join(&file.name),
+                "drive_mime": file.mime_type,
+                "drive_exported": export,
+                "sha256": sha256sum(&destination)?,
+                "size_bytes": destination.metadata().ok().map(|m| m.len()),
+            });
+            assets.push(Asset {
+                mime: Some(guess_mime(&destination).into()),
+                path: destination,
+                media: media.into(),
+                page_index: None,
+                source_kind: SourceKind::Drive,
+                meta,
+            });
+        }
+        Ok(assets)
+    }
+
+    fn list_folder(&self, folder_id: &str, token: &str) -> Result<Vec<DriveFile>> {
+        let query = format!("'{folder_id}' in parents and trashed = false");
+        let mut files = Vec::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut request = self.client.get(FILES_URL).bearer_auth(token).query(&[
+                ("q", query.as_str()),
+                (
+                    "fields",
+                    "nextPageToken, files(id, name, mimeType, modifiedTime)",
+                ),
+                ("orderBy", "name"),
+                ("pageSize", "1000"),
+                ("supportsAllDrives", "true"),
+                ("includeItemsFromAllDrives", "true"),
+            ]);
+            if let Some(page) = &page_token {
+                request = request.query(&[("pageToken", page.as_str())]);
+            }
+            let response = request
+                .send()
+                .with_context(|| format!("Listing Drive folder {folder_id}"))?;
+            if !response.status().is_success() {
+                bail!(
+                    "Drive folder listing failed with status {}",
+                    response.status()
+                );
+            }
+            let page: FileList = response.json()?;
+            files.extend(page.files);
+            match page.next_page_token {
+                Some(next) => page_token = Some(next),
+                None => break,
+            }
+        }
+        Ok(files)
+    }
+
+    fn export_pdf(
+        &self,
+        file_id: &str,
+        destination: &Path,
+        token: &str,
+        job_id: &str,
+    ) -> Result<()> {
+        let url = format!("{FILES_URL}/{file_id}/export?mimeType=application/pdf");
+        self.fetch_to(&url, destination, token, job_id)
+            .with_context(|| format!("Exporting Drive file {file_id} to PDF"))
+    }
+
+    fn download_file(
+        &self,
+        file_id: &str,
+        destination: &Path,
+        token: &str,
+        job_id: &str,
+    ) -> Result<()> {
+        let url = format!("{FILES_URL}/{file_id}?alt=media&supportsAllDrives=true");
+        self.fetch_to(&url, destination, token, job_id)
+            .with_context(|| format!("Downloading Drive file {file_id}"))
+    }
+
+    /// Download through a `.part` file, which a later run continues if this one is
+    /// interrupted. Drive serves ranges for file content but not for exports, which
+    /// start over.
+    fn fetch_to(&self, url: &str, destination: &Path, token: &str, job_id: &str) -> Result<()> {
+        ensure_dir(destination.parent().unwrap_or_else(|| Path::new(".")))?;
+        let name = destination
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut meter = DownloadMeter::new(self.progress.clone(), job_id, &name);
         let temp = destination.with_extension("part");
-        let mut file = File::create(&temp)?;
-        copy(&mut response, &mut file)?;
+        fetch_resumable(
+            || self.client.get(url).bearer_auth(token),
+            &temp,
+            &mut meter,
+            self.max_bytes,
+        )?;
         fs::rename(temp, destination)?;
         Ok(())
     }
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DriveFile {
+    id: String,
+    name: String,
+    mime_type: String,
+    modified_time: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileList {
+    #[serde(default)]
+    files: Vec<DriveFile>,
+    next_page_token: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct ServiceAccountCredentials {
     client_email: String,
@@ -149,17 +376,104 @@ struct TokenResponse {
     access_token: String,
 }
 
-fn infer_media(path: &Path) -> &'static str {
+/// What a Drive source names.
+#[derive(Debug, PartialEq, Eq)]
+enum DriveSource {
+    File(String),
+    Folder(String),
+}
+
+/// The file or folder named by a `drive://` URI (`drive://<id>`,
+/// `drive://folder/<id>`) or by a Drive or Docs share link.
+fn parse_source(source: &str) -> Option<DriveSource> {
+    let source = source.trim();
+    if let Some(rest) = source
+        .strip_prefix("drive://")
+        .or_else(|| source.strip_prefix("gdrive://"))
+    {
+        let rest = rest.trim();
+        if let Some(folder_id) = rest.strip_prefix("folder/") {
+            return Some(DriveSource::Folder(folder_id.trim_matches('/').to_string()));
+        }
+        return Some(DriveSource::File(
+            rest.strip_prefix("file/").unwrap_or(rest).to_string(),
+        ));
+    }
+    let url = Url::parse(source).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+    // The id follows `d/` in `/file/d/<id>/view` and `/document/d/<id>/edit`.
+    let after_d = || {
+        segments
+            .iter()
+            .position(|segment| *segment == "d")
+            .and_then(|index| segments.get(index + 1))
+            .map(|id| id.to_string())
+    };
+    match url.host_str()? {
+        "drive.google.com" => {
+            if let Some(index) = segments.iter().position(|segment| *segment == "folders") {
+                return segments
+                    .get(index + 1)
+                    .map(|id| DriveSource::Folder(id.to_string()));
+            }
+            if segments.first() == Some(&"file") {
+                return after_d().map(DriveSource::File);
+            }
+            // `/open?id=<id>` and `/uc?id=<id>&export=download`.
+            url.query_pairs()
+                .find(|(key, _)| key == "id")
+                .map(|(_, id)| DriveSource::File(id.into_owned()))
+        }
+        "docs.google.com" if matches!(segments.first(), Some(&"document" | &"presentation")) => {
+            after_d().map(DriveSource::File)
+        }
+        _ => None,
+    }
+}
+
+/// Whether `url` is a Drive or Docs share link [`DriveIngestor`] can fetch.
+pub(crate) fn is_share_link(url: &Url) -> bool {
+    parse_source(url.as_str()).is_some()
+}
+
+pub(crate) fn default_cache_dir() -> PathBuf {
+    std::env::temp_dir().join("recapit-drive-cache")
+}
+
+fn infer_media(path: &Path) -> Option<&'static str> {
     let ext = path
         .extension()
         .map(|ext| ext.to_string_lossy().to_lowercase())
         .unwrap_or_default();
-    match ext.as_str() {
+    let media = match ext.as_str() {
         "pdf" => "pdf",
         "png" | "jpg" | "jpeg" | "gif" => "image",
         "mp4" | "mov" | "mkv" => "video",
         "mp3" | "wav" | "m4a" => "audio",
-        _ => "pdf",
+        "pptx" | "ppt" | "odp" => "presentation",
+        "docx" | "doc" | "odt" | "rtf" => "document",
+        "epub" => "epub",
+        "srt" | "vtt" => "subtitles",
+        _ => return None,
+    };
+    Some(media)
+}
+
+/// Drive names may contain path separators; keep them inside a single component.
+fn sanitize_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|ch| match ch {
+            '/' | '\\' | ':' => '_',
+            other => other,
+        })
+        .collect();
+    match cleaned.trim() {
+        "" | "." | ".." => "untitled".into(),
+        other => other.to_string(),
     }
 }
 
@@ -178,3 +492,35 @@ fn guess_mime(path: &Path) -> &'static str {
         _ => "application/octet-stream",
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn share_links_name_files_and_folders() {
+        let file = |id: &str| Some(DriveSource::File(id.into()));
+        assert_eq!(
+            parse_source("https://drive.google.com/file/d/1AbC/view?usp=sharing"),
+            file("1AbC")
+        );
+        assert_eq!(
+            parse_source("https://drive.google.com/open?id=1AbC"),
+            file("1AbC")
+        );
+        assert_eq!(
+            parse_source("https://docs.google.com/presentation/d/1AbC/edit#slide=id.p"),
+            file("1AbC")
+        );
+        assert_eq!(
+            parse_source("https://drive.google.com/drive/u/0/folders/1Fol"),
+            Some(DriveSource::Folder("1Fol".into()))
+        );
+        assert_eq!(parse_source("drive://file/1AbC"), file("1AbC"));
+        assert_eq!(
+            parse_source("https://docs.google.com/forms/d/1AbC/viewform"),
+            None
+        );
+        assert_eq!(parse_source("https://example.com/file/d/1AbC"), None);
+    }
+}
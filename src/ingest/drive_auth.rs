@@ -0,0 +1,352 @@
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use url::Url;
+
+use super::drive::SCOPE;
+use crate::utils::ensure_dir;
+
+const AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+/// Access tokens this close to expiry are refreshed before use.
+const EXPIRY_MARGIN_SECONDS: i64 = 60;
+
+/// An OAuth client of type "Desktop app" from the Google Cloud console, used to
+/// sign in to Drive as yourself instead of through a service account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OAuthClient {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl OAuthClient {
+    /// Read the `client_secret_*.json` the console downloads for a desktop client.
+    pub fn from_secrets_file(path: &Path) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct Secrets {
+            installed: Option<OAuthClient>,
+            web: Option<OAuthClient>,
+        }
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("reading OAuth client secrets {}", path.display()))?;
+        let secrets: Secrets = serde_json::from_str(&text)
+            .with_context(|| format!("parsing OAuth client secrets {}", path.display()))?;
+        secrets.installed.or(secrets.web).ok_or_else(|| {
+            anyhow!(
+                "{} is not an OAuth client secrets file; download one for a Desktop app client",
+                path.display()
+            )
+        })
+    }
+}
+
+/// The refresh token saved by `recapit drive login`, with the client it was
+/// issued to so it can be refreshed without recapit.yaml.
+#[derive(Debug, Serialize, Deserialize)]
+struct UserToken {
+    #[serde(flatten)]
+    client: OAuthClient,
+    refresh_token: String,
+    access_token: Option<String>,
+    /// Unix seconds.
+    expires_at: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<i64>,
+    refresh_token: Option<String>,
+}
+
+/// Where the Drive sign-in is cached.
+pub fn token_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("recapit")
+        .join("drive-token.json")
+}
+
+/// Sign in to Google Drive in the browser and cache the refresh token.
+///
+/// Runs the installed-app flow: Google redirects back to a one-shot listener on
+/// 127.0.0.1, and PKCE ties the returned code to this process.
+pub fn login_drive(client: &OAuthClient, open_browser: bool) -> Result<PathBuf> {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).context("starting the sign-in listener")?;
+    let redirect_uri = format!("http://127.0.0.1:{}", listener.local_addr()?.port());
+    let verifier = random_string(64);
+    let challenge = BASE64_URL.encode(Sha256::digest(verifier.as_bytes()));
+    let state = random_string(24);
+    let auth_url = Url::parse_with_params(
+        AUTH_URL,
+        [
+            ("client_id", client.client_id.as_str()),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("response_type", "code"),
+            ("scope", SCOPE),
+            ("access_type", "offline"),
+            ("prompt", "consent"),
+            ("code_challenge", challenge.as_str()),
+            ("code_challenge_method", "S256"),
+            ("state", state.as_str()),
+        ],
+    )?;
+
+    eprintln!("Sign in to Google Drive at:\n\n  {auth_url}\n");
+    if open_browser && !open_in_browser(auth_url.as_str()) {
+        eprintln!("Could not open a browser; open the link above yourself.");
+    }
+    eprintln!("Waiting for the browser to return to {redirect_uri} ...");
+
+    let code = wait_for_code(&listener, &state)?;
+    let http = Client::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .build()?;
+    let response = token_request(
+        &http,
+        &[
+            ("grant_type", "authorization_code"),
+            ("code", code.as_str()),
+            ("client_id", client.client_id.as_str()),
+            ("client_secret", client.client_secret.as_str()),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("code_verifier", verifier.as_str()),
+        ],
+    )?;
+    let refresh_token = response.refresh_token.clone().ok_or_else(|| {
+        anyhow!("Google returned no refresh token; remove recapit's access at https://myaccount.google.com/permissions and sign in again")
+    })?;
+    let token = UserToken {
+        client: client.clone(),
+        refresh_token,
+        access_token: Some(response.access_token),
+        expires_at: response.expires_in.map(expires_at),
+    };
+    let path = token_path();
+    save_token(&path, &token)?;
+    Ok(path)
+}
+
+/// Forget the cached Drive sign-in. Returns whether there was one.
+pub fn logout_drive() -> Result<bool> {
+    let path = token_path();
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err).with_context(|| format!("removing {}", path.display())),
+    }
+}
+
+/// An access token from the cached sign-in, refreshed when it has expired, or
+/// `None` when `recapit drive login` has not been run.
+pub(crate) fn user_access_token(http: &Client) -> Result<Option<String>> {
+    let path = token_path();
+    let Ok(text) = fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    let mut token: UserToken = serde_json::from_str(&text)
+        .with_context(|| format!("parsing Drive sign-in {}", path.display()))?;
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    if let (Some(access), Some(expires)) = (&token.access_token, token.expires_at) {
+        if expires - EXPIRY_MARGIN_SECONDS > now {
+            return Ok(Some(access.clone()));
+        }
+    }
+    let response = token_request(
+        http,
+        &[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", token.refresh_token.as_str()),
+            ("client_id", token.client.client_id.as_str()),
+            ("client_secret", token.client.client_secret.as_str()),
+        ],
+    )
+    .context("refreshing the Drive sign-in; run `recapit drive login` again if it was revoked")?;
+    token.access_token = Some(response.access_token.clone());
+    token.expires_at = response.expires_in.map(expires_at);
+    if let Some(refresh) = response.refresh_token {
+        token.refresh_token = refresh;
+    }
+    save_token(&path, &token)?;
+    Ok(Some(response.access_token))
+}
+
+fn token_request(http: &Client, params: &[(&str, &str)]) -> Result<TokenResponse> {
+    let response = http.post(TOKEN_URL).form(params).send()?;
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().unwrap_or_default();
+        bail!("OAuth token request failed with status {status}: {text}");
+    }
+    Ok(response.json()?)
+}
+
+/// Serve the redirect and return the authorization code. Requests without a
+/// code, such as a favicon fetch, are answered and skipped.
+fn wait_for_code(listener: &TcpListener, state: &str) -> Result<String> {
+    for stream in listener.incoming() {
+        let mut stream = stream?;
+        stream.set_read_timeout(Some(std::time::Duration::from_secs(10)))?;
+        let mut request_line = String::new();
+        BufReader::new(&stream).read_line(&mut request_line)?;
+        let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+        let url = Url::parse("http://127.0.0.1")?.join(target)?;
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+        let (code, error) = (param("code"), param("error"));
+        if code.is_none() && error.is_none() {
+            respond(&mut stream, "404 Not Found", "");
+            continue;
+        }
+        if let Some(error) = error {
+            respond(
+                &mut stream,
+                "200 OK",
+                "Sign-in failed. You can close this tab.",
+            );
+            bail!("Google sign-in failed: {error}");
+        }
+        if param("state").as_deref() != Some(state) {
+            respond(&mut stream, "400 Bad Request", "Sign-in state mismatch.");
+            bail!("Google sign-in returned an unexpected state; try again");
+        }
+        respond(
+            &mut stream,
+            "200 OK",
+            "Signed in to recapit. You can close this tab.",
+        );
+        return Ok(code.unwrap_or_default());
+    }
+    bail!("sign-in listener closed before Google redirected back")
+}
+
+fn respond(stream: &mut impl Write, status: &str, body: &str) {
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+}
+
+/// Write the token to a private temporary file and rename it into place, so the file
+/// is never readable by others, not even briefly, and a crash leaves the old one.
+fn save_token(path: &Path, token: &UserToken) -> Result<()> {
+    ensure_dir(path.parent().unwrap())?;
+    let temp = path.with_extension("json.tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let written = options.open(&temp).and_then(|mut file| {
+        // `mode` only applies when the file is created; narrow a leftover one too.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+        file.write_all(serde_json::to_string_pretty(token)?.as_bytes())?;
+        file.sync_all()
+    });
+    if let Err(err) = written.and_then(|()| fs::rename(&temp, path)) {
+        let _ = fs::remove_file(&temp);
+        return Err(err).with_context(|| format!("writing {}", path.display()));
+    }
+    Ok(())
+}
+
+fn expires_at(expires_in: i64) -> i64 {
+    OffsetDateTime::now_utc().unix_timestamp() + expires_in
+}
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+fn open_in_browser(url: &str) -> bool {
+    let mut command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    command
+        .arg(url)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn desktop_client_secrets_are_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("client_secret.json");
+        fs::write(
+            &path,
+            r#"{"installed": {"client_id": "id.apps.googleusercontent.com", "client_secret": "s3cret", "redirect_uris": ["http://localhost"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            OAuthClient::from_secrets_file(&path).unwrap(),
+            OAuthClient {
+                client_id: "id.apps.googleusercontent.com".into(),
+                client_secret: "s3cret".into(),
+            }
+        );
+        fs::write(&path, r#"{"type": "service_account"}"#).unwrap();
+        assert!(OAuthClient::from_secrets_file(&path).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saved_tokens_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("drive-token.json");
+        fs::write(&path, "{}").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        let token = UserToken {
+            client: OAuthClient {
+                client_id: "id".into(),
+                client_secret: "secret".into(),
+            },
+            refresh_token: "refresh".into(),
+            access_token: None,
+            expires_at: None,
+        };
+        save_token(&path, &token).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(fs::read_to_string(&path).unwrap().contains("refresh"));
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use roxmltree::Document;
+use scraper::{Html, Selector};
+use zip::ZipArchive;
+
+use super::html::body_markdown;
+
+#[derive(Debug, Clone)]
+pub struct EpubBook {
+    pub title: Option<String>,
+    pub chapters: Vec<EpubChapter>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EpubChapter {
+    pub title: String,
+    pub href: String,
+    pub markdown: String,
+}
+
+/// Read the spine of an EPUB and return each non-empty chapter as Markdown.
+pub fn read_book(path: &Path) -> Result<EpubBook> {
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut archive =
+        ZipArchive::new(file).with_context(|| format!("reading {}", path.display()))?;
+
+    let container = read_entry(&mut archive, "META-INF/container.xml")?;
+    let container = Document::parse(&container).context("parsing EPUB container.xml")?;
+    let opf_path = container
+        .descendants()
+        .find(|node| node.has_tag_name("rootfile"))
+        .and_then(|node| node.attribute("full-path"))
+        .ok_or_else(|| anyhow!("EPUB container.xml has no rootfile"))?
+        .to_string();
+    let opf_dir = parent_dir(&opf_path);
+
+    let opf_text = read_entry(&mut archive, &opf_path)?;
+    let opf = Document::parse(&opf_text).with_context(|| format!("parsing {opf_path}"))?;
+    let book_title = opf
+        .descendants()
+        .find(|node| node.has_tag_name("title"))
+        .and_then(|node| node.text())
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty());
+
+    let mut manifest: HashMap<&str, (&str, &str, Option<&str>)> = HashMap::new();
+    for item in opf.descendants().filter(|node| node.has_tag_name("item")) {
+        if let (Some(id), Some(href)) = (item.attribute("id"), item.attribute("href")) {
+            manifest.insert(
+                id,
+                (
+                    href,
+                    item.attribute("media-type").unwrap_or_default(),
+                    item.attribute("properties"),
+                ),
+            );
+        }
+    }
+
+    let toc_titles = read_toc_titles(&mut archive, &opf, &manifest, &opf_dir);
+
+    let mut chapters = Vec::new();
+    for itemref in opf
+        .descendants()
+        .filter(|node| node.has_tag_name("itemref"))
+    {
+        if itemref.attribute("linear") == Some("no") {
+            continue;
+        }
+        let Some((href, media_type, _)) = itemref
+            .attribute("idref")
+            .and_then(|idref| manifest.get(idref))
+        else {
+            continue;
+        };
+        if !media_type.contains("html") {
+            continue;
+        }
+        let entry = resolve_href(&opf_dir, href);
+        let html = read_entry(&mut archive, &entry)?;
+        let markdown = body_markdown(&html);
+        if markdown.trim().is_empty() {
+            continue;
+        }
+        let title = toc_titles
+            .get(&entry)
+            .cloned()
+            .or_else(|| first_heading(&html))
+            .unwrap_or_else(|| format!("Chapter {}", chapters.len() + 1));
+        chapters.push(EpubChapter {
+            title,
+            href: entry,
+            markdown,
+        });
+    }
+
+    Ok(EpubBook {
+        title: book_title,
+        chapters,
+    })
+}
+
+/// Map chapter entries to their table-of-contents labels (EPUB 3 nav or EPUB 2 NCX).
+fn read_toc_titles<R: std::io::Read + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+    opf: &Document<'_>,
+    manifest: &HashMap<&str, (&str, &str, Option<&str>)>,
+    opf_dir: &str,
+) -> HashMap<String, String> {
+    let mut titles = HashMap::new();
+
+    let nav = manifest.values().find(|(_, _, properties)| {
+        properties
+            .map(|value| value.split_whitespace().any(|p| p == "nav"))
+            .unwrap_or(false)
+    });
+    if let Some((href, _, _)) = nav {
+        let nav_path = resolve_href(opf_dir, href);
+        if let Ok(text) = read_entry(archive, &nav_path) {
+            let nav_dir = parent_dir(&nav_path);
+            let document = Html::parse_document(&text);
+            let links = Selector::parse("nav a[href]").unwrap();
+            for link in document.select(&links) {
+                let label = link.text().collect::<String>().trim().to_string();
+                if let Some(href) = link.value().attr("href") {
+                    if !label.is_empty() {
+                        titles.entry(resolve_href(&nav_dir, href)).or_insert(label);
+                    }
+                }
+            }
+        }
+    }
+    if !titles.is_empty() {
+        return titles;
+    }
+
+    let ncx_id = opf
+        .descendants()
+        .find(|node| node.has_tag_name("spine"))
+        .and_then(|node| node.attribute("toc"));
+    let ncx = ncx_id.and_then(|id| manifest.get(id)).or_else(|| {
+        manifest
+            .values()
+            .find(|(_, media_type, _)| *media_type == "application/x-dtbncx+xml")
+    });
+    if let Some((href, _, _)) = ncx {
+        let ncx_path = resolve_href(opf_dir, href);
+        if let Ok(text) = read_entry(archive, &ncx_path) {
+            if let Ok(document) = Document::parse(&text) {
+                let ncx_dir = parent_dir(&ncx_path);
+                for point in document
+                    .descendants()
+                    .filter(|node| node.has_tag_name("navPoint"))
+                {
+                    let label = point
+                        .descendants()
+                        .find(|node| node.has_tag_name("text"))
+                        .and_then(|node| node.text())
+                        .map(|text| text.trim().to_string());
+                    let src = point
+                        .children()
+                        .find(|node| node.has_tag_name("content"))
+                        .and_then(|node| node.attribute("src"));
+                    if let (Some(label), Some(src)) = (label, src) {
+                        if !label.is_empty() {
+                            titles.entry(resolve_href(&ncx_dir, src)).or_insert(label);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    titles
+}
+
+fn read_entry<R: std::io::Read + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<String> {
+    let mut entry = archive
+        .by_name(name)
+        .with_context(|| format!("EPUB entry {name} missing"))?;
+    let mut bytes = Vec::new();
+    entry.read_to_end(&mut bytes)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn first_heading(html: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    let headings = Selector::parse("h1, h2, h3").unwrap();
+    document
+        .select(&headings)
+        .map(|node| node.text().collect::<Vec<_>>().join(" "))
+        .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+        .find(|text| !text.is_empty())
+}
+
+fn parent_dir(path: &str) -> String {
+    path.rsplit_once('/')
+        .map(|(dir, _)| dir.to_string())
+        .unwrap_or_default()
+}
+
+/// Resolve an href relative to `base` inside the archive, dropping any fragment.
+fn resolve_href(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let href = decode_percent(href);
+    let mut parts: Vec<&str> = if base.is_empty() {
+        Vec::new()
+    } else {
+        base.split('/').collect()
+    };
+    for segment in href.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            other => parts.push(other),
+        }
+    }
+    parts.join("/")
+}
+
+fn decode_percent(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'%' && idx + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[idx + 1..idx + 3]).unwrap_or_default();
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                out.push(byte);
+                idx += 3;
+                continue;
+            }
+        }
+        out.push(bytes[idx]);
+        idx += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_relative_hrefs() {
+        assert_eq!(
+            resolve_href("OEBPS", "text/ch1.xhtml#top"),
+            "OEBPS/text/ch1.xhtml"
+        );
+        assert_eq!(
+            resolve_href("OEBPS/nav", "../text/ch%202.xhtml"),
+            "OEBPS/text/ch 2.xhtml"
+        );
+        assert_eq!(resolve_href("", "ch3.html"), "ch3.html");
+    }
+}
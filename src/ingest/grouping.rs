@@ -0,0 +1,122 @@
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use serde_json::json;
+
+use crate::core::Asset;
+
+/// Gather the job's images into one ordered block, placed where the first image was,
+/// so photos of a whiteboard or notebook read as consecutive pages of one document.
+pub fn group_images_as_document(assets: &mut Vec<Asset>) {
+    let Some(first) = assets.iter().position(|asset| asset.media == "image") else {
+        return;
+    };
+    let (mut images, others): (Vec<Asset>, Vec<Asset>) =
+        assets.drain(..).partition(|asset| asset.media == "image");
+    if images.len() > 1 {
+        order_photos(&mut images);
+    }
+    assets.extend(others);
+    assets.splice(first..first, images);
+}
+
+/// Sort photos as they were taken and tag each with its position so the provider can
+/// label them as pages.
+///
+/// Photos are sorted by EXIF capture time when every one of them has it, otherwise
+/// by file name in natural order (`IMG_2` before `IMG_10`).
+fn order_photos(images: &mut [Asset]) {
+    let taken: Vec<Option<String>> = images
+        .iter()
+        .map(|asset| capture_time(&asset.path))
+        .collect();
+    let mut order: Vec<usize> = (0..images.len()).collect();
+    if taken.iter().all(Option::is_some) {
+        order.sort_by(|&a, &b| {
+            taken[a]
+                .cmp(&taken[b])
+                .then_with(|| compare_names(&images[a].path, &images[b].path))
+        });
+    } else {
+        order.sort_by(|&a, &b| compare_names(&images[a].path, &images[b].path));
+    }
+
+    let mut sorted: Vec<Asset> = order.iter().map(|&index| images[index].clone()).collect();
+    let total = sorted.len();
+    for (position, asset) in sorted.iter_mut().enumerate() {
+        let name = asset
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        asset.page_index = Some(position as u32);
+        if !asset.meta.is_object() {
+            asset.meta = json!({});
+        }
+        asset.meta["group_index"] = json!(position);
+        asset.meta["group_total"] = json!(total);
+        asset.meta["group_label"] = json!(format!("Page {} of {total} ({name}):", position + 1));
+        if let Some(time) = &taken[order[position]] {
+            asset.meta["captured_at"] = json!(time);
+        }
+    }
+    images.clone_from_slice(&sorted);
+}
+
+fn compare_names(a: &Path, b: &Path) -> Ordering {
+    let name = |path: &Path| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    };
+    natord::compare(&name(a), &name(b))
+}
+
+/// EXIF `DateTimeOriginal` (falling back to `DateTime`) as `YYYY-MM-DD HH:MM:SS`,
+/// which sorts chronologically as text.
+fn capture_time(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+    [exif::Tag::DateTimeOriginal, exif::Tag::DateTime]
+        .iter()
+        .find_map(|tag| exif.get_field(*tag, exif::In::PRIMARY))
+        .map(|field| field.display_value().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::SourceKind;
+
+    #[test]
+    fn photos_without_exif_sort_by_natural_name() {
+        let image = |name: &str| Asset {
+            path: format!("/nonexistent/{name}").into(),
+            media: "image".into(),
+            page_index: None,
+            source_kind: SourceKind::Local,
+            mime: None,
+            meta: json!({}),
+        };
+        let mut images = vec![image("IMG_10.jpg"), image("img_2.jpg"), image("IMG_1.jpg")];
+        order_photos(&mut images);
+        let names: Vec<_> = images
+            .iter()
+            .map(|asset| {
+                asset
+                    .path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(names, ["IMG_1.jpg", "img_2.jpg", "IMG_10.jpg"]);
+        assert_eq!(images[2].meta["group_label"], "Page 3 of 3 (IMG_10.jpg):");
+        assert_eq!(images[1].page_index, Some(1));
+    }
+}
@@ -0,0 +1,166 @@
+use scraper::{ElementRef, Html, Selector};
+
+/// Elements that never carry article content.
+const BOILERPLATE: &str =
+    "script, style, noscript, template, iframe, svg, canvas, form, nav, header, footer, aside, button";
+
+/// Containers tried, in order, before falling back to paragraph scoring.
+const CONTENT_ROOTS: &[&str] = &["article", "main", "[role=main]", "#content", ".content"];
+
+const MIN_ROOT_TEXT: usize = 200;
+
+#[derive(Debug, Clone)]
+pub struct ReadableDocument {
+    pub title: Option<String>,
+    pub markdown: String,
+}
+
+/// Pull the main readable content out of an HTML page and render it as Markdown.
+pub fn extract_readable(html: &str) -> ReadableDocument {
+    let mut document = Html::parse_document(html);
+    let title = page_title(&document);
+    strip_boilerplate(&mut document);
+
+    let root = content_root(&document);
+    let body = root
+        .map(|element| element.html())
+        .unwrap_or_else(|| document.root_element().html());
+    let markdown = collapse_blank_lines(&html2md::parse_html(&body));
+
+    ReadableDocument { title, markdown }
+}
+
+/// Render the whole `<body>` as Markdown, minus scripts and page chrome.
+///
+/// Used for documents that are already content-only, such as EPUB chapters.
+pub fn body_markdown(html: &str) -> String {
+    let mut document = Html::parse_document(html);
+    strip_boilerplate(&mut document);
+    let body = Selector::parse("body").unwrap();
+    let content = document
+        .select(&body)
+        .next()
+        .map(|element| element.html())
+        .unwrap_or_else(|| document.root_element().html());
+    collapse_blank_lines(&html2md::parse_html(&content))
+}
+
+fn page_title(document: &Html) -> Option<String> {
+    let og = Selector::parse(r#"meta[property="og:title"]"#).unwrap();
+    if let Some(content) = document
+        .select(&og)
+        .next()
+        .and_then(|meta| meta.value().attr("content"))
+    {
+        return non_empty(content);
+    }
+    for selector in ["title", "h1"] {
+        let selector = Selector::parse(selector).unwrap();
+        if let Some(element) = document.select(&selector).next() {
+            if let Some(text) = non_empty(&element.text().collect::<String>()) {
+                return Some(text);
+            }
+        }
+    }
+    None
+}
+
+fn strip_boilerplate(document: &mut Html) {
+    let selector = Selector::parse(BOILERPLATE).unwrap();
+    let ids: Vec<_> = document
+        .select(&selector)
+        .map(|element| element.id())
+        .collect();
+    for id in ids {
+        if let Some(mut node) = document.tree.get_mut(id) {
+            node.detach();
+        }
+    }
+}
+
+fn content_root(document: &Html) -> Option<ElementRef<'_>> {
+    for candidate in CONTENT_ROOTS {
+        let selector = Selector::parse(candidate).unwrap();
+        if let Some(element) = document
+            .select(&selector)
+            .find(|element| text_len(element) >= MIN_ROOT_TEXT)
+        {
+            return Some(element);
+        }
+    }
+
+    // Readability-style fallback: the container whose direct paragraphs hold the most text.
+    let containers = Selector::parse("div, section, td, body").unwrap();
+    document
+        .select(&containers)
+        .map(|element| {
+            let score: usize = element
+                .children()
+                .filter_map(ElementRef::wrap)
+                .filter(|child| child.value().name() == "p")
+                .map(|p| text_len(&p))
+                .sum();
+            (score, element)
+        })
+        .filter(|(score, _)| *score > 0)
+        .max_by_key(|(score, _)| *score)
+        .map(|(_, element)| element)
+}
+
+fn text_len(element: &ElementRef<'_>) -> usize {
+    element.text().map(|chunk| chunk.trim().len()).sum()
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let trimmed = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!trimmed.is_empty()).then_some(trimmed)
+}
+
+fn collapse_blank_lines(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut blank_run = 0;
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            blank_run += 1;
+            if blank_run > 1 {
+                continue;
+            }
+        } else {
+            blank_run = 0;
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_article_and_drops_chrome() {
+        let html = r#"<html><head><title>Fallback</title>
+            <meta property="og:title" content="Lecture 4: Entropy"></head>
+            <body><nav><a href="/">Home</a></nav>
+            <article><h2>Definition</h2><p>Entropy measures the average information content of a random variable, and it is maximised by the uniform distribution over a finite alphabet of outcomes.</p>
+            <script>track()</script><p>It is additive for independent variables.</p></article>
+            <footer>Copyright</footer></body></html>"#;
+        let readable = extract_readable(html);
+        assert_eq!(readable.title.as_deref(), Some("Lecture 4: Entropy"));
+        assert!(readable.markdown.contains("Definition"));
+        assert!(readable.markdown.contains("additive for independent"));
+        assert!(!readable.markdown.contains("Home"));
+        assert!(!readable.markdown.contains("track()"));
+        assert!(!readable.markdown.contains("Copyright"));
+    }
+
+    #[test]
+    fn falls_back_to_densest_paragraph_container() {
+        let html = r#"<body><div class="sidebar"><p>Short.</p></div>
+            <div class="post"><p>First long paragraph of the post body.</p><p>Second paragraph continues the argument.</p></div></body>"#;
+        let readable = extract_readable(html);
+        assert!(readable.markdown.contains("Second paragraph"));
+        assert!(!readable.markdown.contains("Short."));
+    }
+}
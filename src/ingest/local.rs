@@ -1,8 +1,11 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use glob::Pattern;
+use tracing::warn;
 
+use super::archive::{extract_archive, is_archive, is_metadata_entry};
 use crate::core::{Asset, Job, SourceKind};
 use crate::utils::slugify;
 
@@ -21,8 +24,101 @@ const MEDIA_BY_SUFFIX: &[(&str, &str)] = &[
     (".mp3", "audio"),
     (".wav", "audio"),
     (".m4a", "audio"),
+    (".pptx", "presentation"),
+    (".ppt", "presentation"),
+    (".odp", "presentation"),
+    (".docx", "document"),
+    (".doc", "document"),
+    (".odt", "document"),
+    (".rtf", "document"),
+    (".epub", "epub"),
+    (".srt", "subtitles"),
+    (".vtt", "subtitles"),
 ];
 
+/// Whether discovery would pick `path` up as an asset.
+pub fn is_supported_file(path: &Path) -> bool {
+    let Some(extension) = path.extension() else {
+        return false;
+    };
+    let extension = extension.to_string_lossy();
+    MEDIA_BY_SUFFIX.iter().any(|(suffix, _)| {
+        suffix
+            .trim_start_matches('.')
+            .eq_ignore_ascii_case(&extension)
+    })
+}
+
+/// `--include`/`--exclude` globs for folder sources.
+///
+/// A pattern matches a file when it matches the file's path relative to the source
+/// folder, or any trailing part of it, so `*.pdf` matches at every depth and
+/// `solutions/*` matches a `solutions` folder wherever it sits.
+#[derive(Debug, Default)]
+pub struct PathFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl PathFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    Pattern::new(pattern).with_context(|| format!("invalid glob '{pattern}'"))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+        })
+    }
+
+    pub fn allows(&self, relative: &Path) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| matches_tail(p, relative)))
+            && !self.exclude.iter().any(|p| matches_tail(p, relative))
+    }
+}
+
+fn matches_tail(pattern: &Pattern, relative: &Path) -> bool {
+    let components: Vec<_> = relative.components().collect();
+    (0..components.len()).any(|start| {
+        let tail: PathBuf = components[start..].iter().collect();
+        pattern.matches_path(&tail)
+    })
+}
+
+/// Supported files in the folder `root` that pass `filter`, in directory order.
+pub fn discover_files(
+    root: &Path,
+    recursive: bool,
+    max_depth: Option<usize>,
+    filter: &PathFilter,
+) -> Result<Vec<PathBuf>> {
+    let paths: Vec<PathBuf> = if recursive {
+        // Depth 1 is the folder itself, so `--max-depth N` allows N levels of subfolders.
+        let max_depth = max_depth.map_or(usize::MAX, |depth| depth + 1);
+        walkdir::WalkDir::new(root)
+            .max_depth(max_depth)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .collect()
+    } else {
+        fs::read_dir(root)?
+            .map(|res| res.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    Ok(paths
+        .into_iter()
+        .filter(|path| is_supported_file(path))
+        .filter(|path| filter.allows(path.strip_prefix(root).unwrap_or(path)))
+        .collect())
+}
+
 pub struct LocalIngestor;
 
 impl Default for LocalIngestor {
@@ -37,40 +133,53 @@ impl LocalIngestor {
         if !root.exists() {
             return Ok(vec![]);
         }
+        if is_archive(&root) {
+            return self.discover_archive(&root, job);
+        }
         if root.is_file() {
             if let Some(asset) = self.asset_from_path(&root) {
                 return Ok(vec![asset]);
             }
             return Ok(vec![]);
         }
-        let mut assets = Vec::new();
-        let iterator: Box<dyn Iterator<Item = Result<PathBuf, std::io::Error>>> = if job.recursive {
-            Box::new(
-                walkdir::WalkDir::new(&root)
-                    .into_iter()
-                    .filter_map(|entry| entry.ok())
-                    .filter(|entry| entry.file_type().is_file())
-                    .map(|entry| Ok(entry.into_path())),
-            )
-        } else {
-            Box::new(
-                fs::read_dir(&root)?
-                    .map(|res| res.map(|entry| entry.path()))
-                    .collect::<Result<Vec<_>, _>>()?
-                    .into_iter()
-                    .map(Ok),
-            )
-        };
+        self.discover_dir(&root, job)
+    }
 
-        for path in iterator {
-            let path = path?;
-            if let Some(asset) = self.asset_from_path(&path) {
-                assets.push(asset);
+    /// Extract into the cache and discover inside it as if it were a local folder.
+    fn discover_archive(&self, archive: &Path, job: &Job) -> Result<Vec<Asset>> {
+        let root = extract_archive(archive)?;
+        let mut assets = self.discover_dir(&root, job)?;
+        assets.retain(|asset| {
+            !is_metadata_entry(asset.path.strip_prefix(&root).unwrap_or(&asset.path))
+        });
+        if assets.is_empty() && !job.recursive {
+            warn!(
+                "no supported files at the top level of {}; pass --recursive to search subfolders",
+                archive.display()
+            );
+        }
+        for asset in &mut assets {
+            let member = asset
+                .path
+                .strip_prefix(&root)
+                .unwrap_or(&asset.path)
+                .to_path_buf();
+            if let Some(meta) = asset.meta.as_object_mut() {
+                meta.insert("archive".into(), serde_json::json!(archive));
+                meta.insert("archive_member".into(), serde_json::json!(member));
             }
         }
         Ok(assets)
     }
 
+    fn discover_dir(&self, root: &Path, job: &Job) -> Result<Vec<Asset>> {
+        let filter = PathFilter::new(&job.include, &job.exclude)?;
+        Ok(discover_files(root, job.recursive, job.max_depth, &filter)?
+            .iter()
+            .filter_map(|path| self.asset_from_path(path))
+            .collect())
+    }
+
     fn asset_from_path(&self, path: &Path) -> Option<Asset> {
         let extension = path.extension()?.to_string_lossy().to_lowercase();
         let media = MEDIA_BY_SUFFIX
@@ -108,3 +217,18 @@ impl ExpandPath for &Path {
         self.to_path_buf()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_filter_matches_any_trailing_part() {
+        let filter = PathFilter::new(&["*.pdf".into()], &["solutions/*".into()]).unwrap();
+        assert!(filter.allows(Path::new("week1/notes.pdf")));
+        assert!(!filter.allows(Path::new("week1/notes.mp4")));
+        assert!(!filter.allows(Path::new("solutions/hw1.pdf")));
+        assert!(!filter.allows(Path::new("week1/solutions/hw1.pdf")));
+        assert!(PathFilter::default().allows(Path::new("anything.mp4")));
+    }
+}
@@ -1,36 +1,76 @@
+mod archive;
+mod captions;
+mod download;
 mod drive;
+mod drive_auth;
+mod epub;
+mod grouping;
+mod html;
 mod local;
+mod media_url;
 mod normalize;
+mod sniff;
 mod url;
 mod youtube;
 
+pub use archive::remove_extracted;
+pub use download::DEFAULT_MAX_DOWNLOAD_BYTES;
 pub use drive::DriveIngestor;
-pub use local::LocalIngestor;
+pub use drive_auth::{login_drive, logout_drive, token_path as drive_token_path, OAuthClient};
+pub use local::{discover_files, is_supported_file, LocalIngestor, PathFilter};
+pub use media_url::MediaUrlIngestor;
 pub use normalize::CompositeNormalizer;
 pub use url::UrlIngestor;
-pub use youtube::YouTubeIngestor;
+pub use youtube::{expand_playlist, is_playlist_url, YouTubeIngestor, YtDlpCookies, YtDlpOptions};
 
 use ::url::Url;
 use anyhow::Result;
+use std::path::PathBuf;
+
+use tokio::sync::mpsc::UnboundedSender;
 
 use crate::core::{Asset, Ingestor, Job};
+use crate::progress::Progress;
 
 pub struct CompositeIngestor {
     local: LocalIngestor,
     url: UrlIngestor,
     youtube: YouTubeIngestor,
+    media: MediaUrlIngestor,
     drive: DriveIngestor,
 }
 
 impl CompositeIngestor {
     pub fn new() -> Result<Self> {
-        Ok(Self {
-            local: LocalIngestor,
-            url: UrlIngestor::new(None)?,
-            youtube: YouTubeIngestor::default(),
-            drive: DriveIngestor::new(None)?,
+        off_runtime(|| {
+            Ok(Self {
+                local: LocalIngestor,
+                url: UrlIngestor::new(None)?,
+                youtube: YouTubeIngestor::default(),
+                media: MediaUrlIngestor::default(),
+                drive: DriveIngestor::new(None)?,
+            })
         })
     }
+
+    pub fn with_media_hosts(mut self, hosts: &[String]) -> Self {
+        self.media = self.media.with_hosts(hosts);
+        self
+    }
+
+    /// Refuse URL and Drive downloads larger than `max_bytes`.
+    pub fn with_max_download(mut self, max_bytes: u64) -> Self {
+        self.url = self.url.with_max_download(max_bytes);
+        self.drive = self.drive.with_max_download(max_bytes);
+        self
+    }
+
+    /// Report URL and Drive download progress on `tx`.
+    pub fn with_progress(mut self, tx: UnboundedSender<Progress>) -> Self {
+        self.url = self.url.with_progress(tx.clone());
+        self.drive = self.drive.with_progress(tx);
+        self
+    }
 }
 
 impl Default for CompositeIngestor {
@@ -39,8 +79,32 @@ impl Default for CompositeIngestor {
     }
 }
 
+/// The folders discovery and normalization cache into, by name, for
+/// [`crate::cache::CacheManager`].
+pub(crate) fn cache_dirs() -> Vec<(&'static str, PathBuf)> {
+    vec![
+        ("url", url::default_cache_dir()),
+        ("drive", drive::default_cache_dir()),
+        ("youtube", youtube::default_cache_dir()),
+        ("archives", archive::archives_root()),
+        ("video", normalize::default_video_root()),
+    ]
+}
+
+/// The URL and Drive ingestors use reqwest's blocking client, which panics when built or
+/// driven from an async worker thread; step off the runtime for those calls.
+fn off_runtime<T>(f: impl FnOnce() -> T) -> T {
+    tokio::task::block_in_place(f)
+}
+
 impl Ingestor for CompositeIngestor {
     fn discover(&self, job: &Job) -> Result<Vec<Asset>> {
+        off_runtime(|| self.discover_source(job))
+    }
+}
+
+impl CompositeIngestor {
+    fn discover_source(&self, job: &Job) -> Result<Vec<Asset>> {
         let parsed = Url::parse(&job.source);
         if let Ok(url) = parsed {
             match url.scheme() {
@@ -48,6 +112,12 @@ impl Ingestor for CompositeIngestor {
                     if self.youtube.supports(&url) {
                         return self.youtube.discover(job);
                     }
+                    if self.media.supports(&url) {
+                        return self.media.discover(job);
+                    }
+                    if drive::is_share_link(&url) {
+                        return self.drive.discover(job);
+                    }
                     return self.url.discover(job);
                 }
                 "yt" | "youtube" => return self.youtube.discover(job),
@@ -1,22 +1,48 @@
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use rayon::prelude::*;
 use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
 use time::OffsetDateTime;
+use tokio::sync::mpsc::UnboundedSender;
 use tracing::warn;
 
-use super::youtube::{YouTubeDownload, YouTubeDownloadError, YouTubeDownloader};
+use super::captions::{cues_to_transcript, parse_captions};
+use super::epub::read_book;
+use super::grouping::group_images_as_document;
+use super::youtube::{
+    YouTubeDownload, YouTubeDownloadError, YouTubeDownloader, YtDlpCookies, YtDlpOptions,
+    KEYFRAME_MAX_HEIGHT,
+};
 use crate::constants::DEFAULT_PDF_DPI;
-use crate::core::{Asset, Job, PdfMode, SourceKind};
-use crate::pdf::pdf_to_png;
+use crate::core::{Asset, Grouping, Job, Kind, PdfMode, SourceKind};
+use crate::imaging::ImagePrep;
+use crate::office::convert_to_pdf;
+use crate::pdf::{
+    extract_pages, has_text_layer, ocr_image, outline_sections, page_count, pdf_outline,
+    pdf_page_text, pdf_to_png, split_long_sections, OcrMode, OutlineSection,
+};
+use crate::progress::{DownloadMeter, EncodeMeter, Progress, ProgressScope, ProgressStage};
+use crate::selection::IndexSelection;
 use crate::utils::{ensure_dir, slugify};
 use crate::video::{
-    plan_video_chunks, probe_video, select_encoder_chain, sha256sum, VideoChunkPlan,
-    VideoEncoderPreference, DEFAULT_MAX_CHUNK_BYTES, DEFAULT_MAX_CHUNK_SECONDS,
-    DEFAULT_TOKENS_PER_SECOND,
+    extract_slides, normalize_audio, plan_video_chunks, probe_video, remove_silence,
+    select_encoder_chain, sha256sum, ChunkBoundaries, ChunkBoundaryMode, ChunkExtraction,
+    EncodeProgress, EncodeSettings, OnEncodeProgress, SilenceRemoval, SlideFrame, TimeMap,
+    VideoChunkPlan, VideoEncoderPreference, VideoMode, DEFAULT_AUDIO_TOKENS_PER_SECOND,
+    DEFAULT_MAX_CHUNK_BYTES, DEFAULT_MAX_CHUNK_SECONDS, DEFAULT_TOKENS_PER_SECOND,
 };
 
+pub type CapabilityChecker = Box<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Where normalized media goes for jobs without an output folder to keep it in.
+pub(crate) fn default_video_root() -> PathBuf {
+    std::env::temp_dir().join("recapit-video")
+}
+
 pub struct CompositeNormalizer {
     video_root: PathBuf,
     encoder_preference: VideoEncoderPreference,
@@ -25,14 +51,21 @@ pub struct CompositeNormalizer {
     token_limit: Option<u32>,
     tokens_per_second: f64,
     pdf_dpi: u32,
-    supports: Box<dyn Fn(&str) -> bool + Send + Sync>,
+    chunk_boundaries: ChunkBoundaries,
+    ocr: OcrMode,
+    image_prep: ImagePrep,
+    supports: CapabilityChecker,
     job: Option<Job>,
     chunk_info: Vec<Value>,
     manifest_path: Option<PathBuf>,
     youtube_downloader: YouTubeDownloader,
+    silence: SilenceRemoval,
+    encode: EncodeSettings,
+    progress: Option<UnboundedSender<Progress>>,
 }
 
 impl CompositeNormalizer {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         video_root: Option<PathBuf>,
         encoder_preference: VideoEncoderPreference,
@@ -41,9 +74,9 @@ impl CompositeNormalizer {
         token_limit: Option<u32>,
         tokens_per_second: Option<f64>,
         pdf_dpi: Option<u32>,
-        capability_checker: Option<Box<dyn Fn(&str) -> bool + Send + Sync>>,
+        capability_checker: Option<CapabilityChecker>,
     ) -> Result<Self> {
-        let video_root = video_root.unwrap_or_else(|| std::env::temp_dir().join("recapit-video"));
+        let video_root = video_root.unwrap_or_else(default_video_root);
         ensure_dir(&video_root)?;
         Ok(Self {
             video_root,
@@ -53,33 +86,157 @@ impl CompositeNormalizer {
             token_limit,
             tokens_per_second: tokens_per_second.unwrap_or(DEFAULT_TOKENS_PER_SECOND),
             pdf_dpi: pdf_dpi.unwrap_or(DEFAULT_PDF_DPI),
+            chunk_boundaries: ChunkBoundaries::default(),
+            ocr: OcrMode::default(),
+            image_prep: ImagePrep::default(),
             supports: capability_checker.unwrap_or_else(|| Box::new(|_| true)),
             job: None,
             chunk_info: Vec::new(),
             manifest_path: None,
             youtube_downloader: YouTubeDownloader::new(None)?,
+            silence: SilenceRemoval::default(),
+            encode: EncodeSettings::default(),
+            progress: None,
         })
     }
 
+    /// Report yt-dlp download progress on `tx`.
+    pub fn with_progress(mut self, tx: UnboundedSender<Progress>) -> Self {
+        self.progress = Some(tx);
+        self
+    }
+
+    /// Cookies for yt-dlp downloads of YouTube and lecture-capture URLs.
+    pub fn with_cookies(mut self, cookies: YtDlpCookies) -> Self {
+        self.youtube_downloader = self.youtube_downloader.with_cookies(cookies);
+        self
+    }
+
+    /// Format, rate, and proxy settings for yt-dlp downloads.
+    pub fn with_ytdlp_options(mut self, options: YtDlpOptions) -> Self {
+        self.youtube_downloader = self.youtube_downloader.with_options(options);
+        self
+    }
+
+    /// Cut long silences out of recordings before they are chunked.
+    pub fn with_silence_removal(mut self, silence: SilenceRemoval) -> Self {
+        self.silence = silence;
+        self
+    }
+
+    /// Quality, downscaling, and decode settings for re-encoding videos.
+    pub fn with_encoding(mut self, encode: EncodeSettings) -> Self {
+        self.encode = encode;
+        self
+    }
+
+    /// Snap video chunk cuts to silences or scene changes instead of fixed lengths.
+    pub fn with_chunk_boundaries(mut self, boundaries: ChunkBoundaries) -> Self {
+        self.chunk_boundaries = boundaries;
+        self
+    }
+
+    /// When to OCR rasterized PDF pages and attach the text as a hint.
+    pub fn with_ocr(mut self, ocr: OcrMode) -> Self {
+        self.ocr = ocr;
+        self
+    }
+
+    pub fn with_image_prep(mut self, image_prep: ImagePrep) -> Self {
+        self.image_prep = image_prep;
+        self
+    }
+
     fn normalize_inner(&mut self, assets: &[Asset], pdf_mode: PdfMode) -> Result<Vec<Asset>> {
         self.chunk_info.clear();
         self.manifest_path = None;
         let resolved = self.resolve_pdf_mode(pdf_mode)?;
+        let mut inputs = assets.to_vec();
+        if self
+            .job
+            .as_ref()
+            .is_some_and(|job| job.grouping == Grouping::ImagesAsDocument)
+        {
+            group_images_as_document(&mut inputs);
+        }
         let mut normalized = Vec::new();
-        for asset in assets {
+        for asset in &inputs {
             match asset.media.as_str() {
                 "pdf" => normalized.extend(self.normalize_pdf(asset, resolved)?),
+                "presentation" => normalized.extend(self.normalize_presentation(asset)?),
+                "document" => normalized.extend(self.normalize_document(asset, resolved)?),
+                "epub" => normalized.extend(self.normalize_epub(asset)?),
+                "subtitles" => normalized.push(self.normalize_subtitles(asset)?),
                 "video" | "audio" => normalized.extend(self.normalize_video(asset)?),
                 _ => normalized.push(asset.clone()),
             }
         }
+        self.prepare_images(&mut normalized)?;
         Ok(normalized)
     }
 
-    fn normalize_pdf(&self, asset: &Asset, mode: PdfMode) -> Result<Vec<Asset>> {
+    /// Run image assets (photos, rasterized pages, slide frames) through the
+    /// preprocessing stage. Images that need no changes, or that cannot be decoded,
+    /// are sent as they are.
+    fn prepare_images(&self, assets: &mut [Asset]) -> Result<()> {
+        if self.image_prep.is_noop() || !assets.iter().any(|asset| asset.media == "image") {
+            return Ok(());
+        }
+        let prepared_dir = self.job_root().join("prepared-images");
+        ensure_dir(&prepared_dir)?;
+        let prep = self.image_prep;
+        assets
+            .par_iter_mut()
+            .filter(|asset| asset.media == "image")
+            .for_each(|asset| {
+                let dest = prepared_dir.join(prepared_image_name(&asset.path));
+                match prep.apply(&asset.path, &dest) {
+                    Ok(true) => {
+                        if let Value::Object(meta) = &mut asset.meta {
+                            meta.remove("inline_bytes");
+                            meta.remove("upload_cache_key");
+                        }
+                        asset.meta["preprocessed_from"] = json!(asset.path);
+                        asset.mime = mime_guess::from_path(&dest).first_raw().map(String::from);
+                        asset.path = dest;
+                    }
+                    Ok(false) => {}
+                    Err(err) => warn!("image preprocessing skipped: {err:#}"),
+                }
+            });
+        Ok(())
+    }
+
+    fn normalize_pdf(&mut self, asset: &Asset, mode: PdfMode) -> Result<Vec<Asset>> {
+        let selection = self
+            .job
+            .as_ref()
+            .and_then(|job| job.page_selection.as_ref())
+            .filter(|selection| !selection.is_full());
         match mode {
-            PdfMode::Pdf => Ok(vec![asset.clone()]),
-            PdfMode::Auto => Ok(vec![asset.clone()]),
+            PdfMode::Pdf | PdfMode::Auto => {
+                let split_outline = self.job.as_ref().is_some_and(|job| job.pdf_split_outline);
+                let document = match selection {
+                    Some(selection) => {
+                        if split_outline {
+                            warn!(
+                                target: "recapit::ingest::pdf",
+                                "Not splitting {} by its outline because only some pages are selected",
+                                asset.path.display()
+                            );
+                        }
+                        self.trim_pdf(asset, selection)?
+                    }
+                    None if split_outline => {
+                        if let Some(sections) = self.split_pdf_outline(asset)? {
+                            return Ok(sections);
+                        }
+                        asset.clone()
+                    }
+                    None => asset.clone(),
+                };
+                self.chunk_pdf(document)
+            }
             PdfMode::Images => {
                 let output_dir = self.pdf_output_dir(asset);
                 let prefix = asset
@@ -87,50 +244,562 @@ impl CompositeNormalizer {
                     .file_stem()
                     .map(|s| s.to_string_lossy().to_string())
                     .unwrap_or_else(|| "page".into());
-                let selection = self
-                    .job
-                    .as_ref()
-                    .and_then(|job| job.page_selection.as_ref());
+                let scope = ProgressScope::Encode {
+                    job_id: self
+                        .job
+                        .as_ref()
+                        .map(|job| job.job_id.clone())
+                        .unwrap_or_default(),
+                    name: format!("render {prefix}"),
+                };
+                let progress = self.progress.clone();
+                let mut on_page = |done: usize, total: usize| {
+                    if let Some(tx) = &progress {
+                        let _ = tx.send(Progress {
+                            scope: scope.clone(),
+                            stage: ProgressStage::Normalize,
+                            current: done as u64,
+                            total: total.max(1) as u64,
+                            status: format!("page {done}/{total}"),
+                            finished: done >= total,
+                        });
+                    }
+                };
                 let pages = match pdf_to_png(
                     &asset.path,
                     &output_dir,
                     Some(&prefix),
                     self.pdf_dpi,
                     selection,
+                    &mut on_page,
                 ) {
                     Ok(pages) => pages,
-                    Err(_) => return Ok(vec![asset.clone()]),
+                    Err(err) => {
+                        warn!("rendering {} failed: {err:#}", asset.path.display());
+                        return Ok(vec![asset.clone()]);
+                    }
                 };
+                let ocr_texts = self.ocr_pages(asset, &pages);
                 let mut result = Vec::new();
                 for (idx, page) in pages.iter().enumerate() {
                     let page_index = page.page_number.saturating_sub(1);
+                    let mut meta = json!({
+                        "source_pdf": asset.path,
+                        "page_number": page.page_number,
+                        "page_index": page_index,
+                        "page_selected_index": idx,
+                        "page_total": pages.len(),
+                    });
+                    if let Some(text) = ocr_texts.get(idx).filter(|text| !text.is_empty()) {
+                        meta["ocr_text"] = Value::String(text.clone());
+                    }
                     result.push(Asset {
                         path: page.path.clone(),
                         media: "image".into(),
                         page_index: Some(page_index),
                         source_kind: asset.source_kind,
                         mime: Some("image/png".into()),
-                        meta: json!({
-                            "source_pdf": asset.path,
-                            "page_number": page.page_number,
-                            "page_index": page_index,
-                            "page_selected_index": idx,
-                            "page_total": pages.len(),
-                        }),
+                        meta,
                     });
                 }
                 Ok(result)
             }
+            PdfMode::Text => match self.pdf_text_asset(asset, selection)? {
+                Some(text) => Ok(vec![text]),
+                None => {
+                    let fallback = self.resolve_pdf_mode(PdfMode::Auto)?;
+                    self.normalize_pdf(asset, fallback)
+                }
+            },
+        }
+    }
+
+    /// The PDF's text layer as a single text asset, or `None` when the job is not a
+    /// document or the PDF has no usable text (a scan), so the caller falls back to
+    /// sending pages.
+    fn pdf_text_asset(
+        &self,
+        asset: &Asset,
+        selection: Option<&IndexSelection>,
+    ) -> Result<Option<Asset>> {
+        let kind = self.job.as_ref().and_then(|job| job.kind);
+        if kind.is_some_and(|kind| kind != Kind::Document) {
+            warn!(
+                "pdf_mode text only applies to documents; sending pages of {}",
+                asset.path.display()
+            );
+            return Ok(None);
+        }
+        match has_text_layer(&asset.path) {
+            Ok(true) => {}
+            Ok(false) => {
+                warn!(
+                    "{} has no text layer; sending pages instead",
+                    asset.path.display()
+                );
+                return Ok(None);
+            }
+            Err(err) => {
+                warn!("pdf_mode text unavailable: {err:#}");
+                return Ok(None);
+            }
+        }
+
+        let text = pdf_page_text(&asset.path, selection)?;
+        let text_dir = self.job_root().join("pdf-text");
+        ensure_dir(&text_dir)?;
+        let path = text_dir.join(format!("{}.txt", self.pdf_slug(asset)));
+        fs::write(&path, text)?;
+
+        let mut meta = value_to_map(&asset.meta);
+        meta.remove("inline_bytes");
+        meta.remove("upload_cache_key");
+        meta.insert("source_pdf".into(), json!(asset.path));
+        Ok(Some(Asset {
+            path,
+            media: "text".into(),
+            page_index: None,
+            source_kind: asset.source_kind,
+            mime: Some("text/plain".into()),
+            meta: Value::Object(meta),
+        }))
+    }
+
+    /// Per-page OCR text, or nothing when OCR is off, the PDF already has a text
+    /// layer (in `auto`), or tesseract is unavailable.
+    fn ocr_pages(&self, asset: &Asset, pages: &[crate::pdf::PdfPage]) -> Vec<String> {
+        let wanted = match self.ocr {
+            OcrMode::Off => false,
+            OcrMode::Force => true,
+            OcrMode::Auto => has_text_layer(&asset.path).is_ok_and(|has_text| !has_text),
+        };
+        if !wanted {
+            return Vec::new();
+        }
+        match pages
+            .par_iter()
+            .map(|page| ocr_image(&page.path))
+            .collect::<Result<Vec<_>>>()
+        {
+            Ok(texts) => texts,
+            Err(err) => {
+                warn!("OCR skipped for {}: {err:#}", asset.path.display());
+                Vec::new()
+            }
+        }
+    }
+
+    /// Slides are always rasterized so each one becomes its own page-indexed image,
+    /// matching how a slide PDF is handled in images mode.
+    fn normalize_presentation(&mut self, asset: &Asset) -> Result<Vec<Asset>> {
+        let pdf_asset = self.office_pdf(asset)?;
+        let mut slides = self.normalize_pdf(&pdf_asset, PdfMode::Images)?;
+        tag_office_source(&mut slides, "source_presentation", &asset.path);
+        Ok(slides)
+    }
+
+    /// Word/ODT files go through the regular PDF pipeline once converted, so page
+    /// selection, chunking, and the resolved PDF mode all apply.
+    fn normalize_document(&mut self, asset: &Asset, mode: PdfMode) -> Result<Vec<Asset>> {
+        let pdf_asset = self.office_pdf(asset)?;
+        let mut pages = self.normalize_pdf(&pdf_asset, mode)?;
+        tag_office_source(&mut pages, "source_document", &asset.path);
+        Ok(pages)
+    }
+
+    /// One text asset per spine chapter, chunked like video so each chapter is its
+    /// own request and long books can be resumed from the manifest.
+    fn normalize_epub(&mut self, asset: &Asset) -> Result<Vec<Asset>> {
+        let book = read_book(&asset.path)?;
+        if book.chapters.is_empty() {
+            bail!("No readable chapters found in {}", asset.path.display());
+        }
+
+        let job_root = self.job_root();
+        let slug = self.pdf_slug(asset);
+        let chapter_dir = job_root.join("epub").join(&slug);
+        ensure_dir(&chapter_dir)?;
+        let manifest_path = job_root
+            .join("manifests")
+            .join(format!("{slug}-chapters.json"));
+
+        let chunk_total = book.chapters.len();
+        let mut outputs = Vec::new();
+        for (index, chapter) in book.chapters.iter().enumerate() {
+            let path = chapter_dir.join(format!("{slug}-chapter{index:03}.md"));
+            let mut text = format!("## {}\n\n", chapter.title);
+            text.push_str(&chapter.markdown);
+            text.push('\n');
+            fs::write(&path, text)?;
+
+            let mut meta = value_to_map(&asset.meta);
+            meta.insert("source_epub".into(), json!(asset.path));
+            meta.insert("book_title".into(), json!(book.title));
+            meta.insert("chapter_title".into(), json!(chapter.title));
+            meta.insert("chapter_href".into(), json!(chapter.href));
+            meta.insert("chunk_index".into(), Value::from(index));
+            meta.insert("chunk_total".into(), Value::from(chunk_total));
+            meta.insert("manifest_path".into(), json!(manifest_path));
+            outputs.push(Asset {
+                path,
+                media: "text".into(),
+                page_index: None,
+                source_kind: asset.source_kind,
+                mime: Some("text/markdown".into()),
+                meta: Value::Object(meta),
+            });
+        }
+
+        ensure_dir(manifest_path.parent().unwrap())?;
+        let entries: Vec<Value> = outputs
+            .iter()
+            .map(|chunk| {
+                json!({
+                    "index": chunk.meta.get("chunk_index"),
+                    "title": chunk.meta.get("chapter_title"),
+                    "href": chunk.meta.get("chapter_href"),
+                    "path": chunk.path,
+                    "status": "pending",
+                })
+            })
+            .collect();
+        let source_hash = sha256sum(&asset.path)?;
+        let payload = json!({
+            "version": 1,
+            "source": asset.path,
+            "source_hash": format!("sha256:{source_hash}"),
+            "source_kind": asset.source_kind,
+            "title": book.title,
+            "created_utc": OffsetDateTime::now_utc(),
+            "updated_utc": OffsetDateTime::now_utc(),
+            "chunks": entries,
+        });
+        fs::write(&manifest_path, serde_json::to_string_pretty(&payload)?)?;
+        self.manifest_path = Some(manifest_path);
+        Ok(outputs)
+    }
+
+    /// Existing captions stand in for the recording: cues become a timestamped
+    /// transcript sent as text, so no video is downloaded, encoded, or uploaded.
+    fn normalize_subtitles(&self, asset: &Asset) -> Result<Asset> {
+        let raw = fs::read(&asset.path)?;
+        let cues = parse_captions(&String::from_utf8_lossy(&raw))
+            .with_context(|| format!("parsing captions in {}", asset.path.display()))?;
+        let caption_dir = self.job_root().join("captions");
+        ensure_dir(&caption_dir)?;
+        let path = caption_dir.join(format!("{}.md", self.pdf_slug(asset)));
+        fs::write(&path, cues_to_transcript(&cues))?;
+
+        let duration = cues
+            .iter()
+            .map(|cue| cue.end_seconds)
+            .fold(0.0_f64, f64::max);
+        let mut meta = value_to_map(&asset.meta);
+        meta.remove("inline_bytes");
+        meta.remove("upload_cache_key");
+        meta.insert("source_subtitles".into(), json!(asset.path));
+        meta.insert("cue_count".into(), Value::from(cues.len()));
+        meta.insert("duration_seconds".into(), json!(duration));
+        Ok(Asset {
+            path,
+            media: "text".into(),
+            page_index: None,
+            source_kind: asset.source_kind,
+            mime: Some("text/markdown".into()),
+            meta: Value::Object(meta),
+        })
+    }
+
+    fn office_pdf(&self, asset: &Asset) -> Result<Asset> {
+        let office_dir = self.job_root().join("office").join(self.pdf_slug(asset));
+        let pdf_path = convert_to_pdf(&asset.path, &office_dir)?;
+        // Inline bytes/upload keys from URL ingestion describe the office file, not the PDF.
+        let mut meta = value_to_map(&asset.meta);
+        meta.remove("inline_bytes");
+        meta.remove("upload_cache_key");
+        Ok(Asset {
+            path: pdf_path,
+            media: "pdf".into(),
+            page_index: None,
+            source_kind: asset.source_kind,
+            mime: Some("application/pdf".into()),
+            meta: Value::Object(meta),
+        })
+    }
+
+    fn trim_pdf(&self, asset: &Asset, selection: &IndexSelection) -> Result<Asset> {
+        let dest = self
+            .job_root()
+            .join("pdf-pages")
+            .join(format!("{}.pdf", self.pdf_slug(asset)));
+        // Sending the whole document instead would ignore --pages and bill for it.
+        let page_total = extract_pages(&asset.path, selection, &dest)
+            .with_context(|| format!("trimming {} to pages {selection}", asset.path.display()))?;
+
+        // The original bytes/upload key describe the untrimmed file; drop them so
+        // the provider reads and uploads the trimmed copy instead.
+        let mut meta = value_to_map(&asset.meta);
+        meta.remove("inline_bytes");
+        meta.remove("upload_cache_key");
+        meta.insert("source_pdf".into(), json!(asset.path));
+        meta.insert("pages".into(), Value::String(selection.to_string()));
+        meta.insert("page_total".into(), Value::from(page_total));
+
+        let mut trimmed = asset.clone();
+        trimmed.path = dest;
+        trimmed.meta = Value::Object(meta);
+        Ok(trimmed)
+    }
+
+    fn chunk_pdf(&mut self, document: Asset) -> Result<Vec<Asset>> {
+        let Some(pages_per_chunk) = self
+            .job
+            .as_ref()
+            .and_then(|job| job.pdf_chunk_pages)
+            .filter(|pages| *pages > 0)
+        else {
+            return Ok(vec![document]);
+        };
+        // A PDF sent whole when it should be split can run past the token limit, so
+        // failing to split it fails the job.
+        let page_total = page_count(&document.path)
+            .with_context(|| format!("counting pages in {}", document.path.display()))?
+            as u32;
+        if page_total <= pages_per_chunk {
+            return Ok(vec![document]);
+        }
+
+        let job_root = self.job_root();
+        let slug = self.pdf_slug(&document);
+        let chunk_dir = job_root.join("pdf-chunks").join(&slug);
+        ensure_dir(&chunk_dir)?;
+        let manifest_path = job_root
+            .join("manifests")
+            .join(format!("{slug}-pages.json"));
+
+        let chunk_total = page_total.div_ceil(pages_per_chunk);
+        let source_pdf = document
+            .meta
+            .get("source_pdf")
+            .cloned()
+            .unwrap_or_else(|| json!(document.path));
+        let mut outputs = Vec::new();
+        for index in 0..chunk_total {
+            let start_page = index * pages_per_chunk + 1;
+            let end_page = (start_page + pages_per_chunk - 1).min(page_total);
+            let selection = IndexSelection::parse(&format!("{start_page}-{end_page}"))?;
+            let path = chunk_dir.join(format!("{slug}-p{start_page:04}-{end_page:04}.pdf"));
+            extract_pages(&document.path, &selection, &path).with_context(|| {
+                format!(
+                    "splitting {} into {pages_per_chunk}-page chunks",
+                    document.path.display()
+                )
+            })?;
+
+            let mut meta = value_to_map(&document.meta);
+            meta.remove("inline_bytes");
+            meta.remove("upload_cache_key");
+            meta.insert("source_pdf".into(), source_pdf.clone());
+            meta.insert("page_total".into(), Value::from(page_total));
+            meta.insert("chunk_index".into(), Value::from(index));
+            meta.insert("chunk_total".into(), Value::from(chunk_total));
+            meta.insert("chunk_start_page".into(), Value::from(start_page));
+            meta.insert("chunk_end_page".into(), Value::from(end_page));
+            meta.insert("manifest_path".into(), json!(manifest_path));
+
+            let mut chunk = document.clone();
+            chunk.path = path;
+            chunk.page_index = None;
+            chunk.meta = Value::Object(meta);
+            outputs.push(chunk);
+        }
+
+        self.write_pdf_manifest(
+            &document,
+            &outputs,
+            page_total,
+            Some(pages_per_chunk),
+            &manifest_path,
+        )?;
+        self.manifest_path = Some(manifest_path);
+        Ok(outputs)
+    }
+
+    /// One chunk per top-level bookmark, so a long coursepack is summarized chapter
+    /// by chapter; a chapter longer than `pdf_chunk_pages` is sent in parts. `None`,
+    /// after a warning, when there is no outline to split by; a section that cannot
+    /// be cut out fails the job.
+    fn split_pdf_outline(&mut self, document: &Asset) -> Result<Option<Vec<Asset>>> {
+        let sections = pdf_outline(&document.path).and_then(|entries| {
+            let page_total = page_count(&document.path)? as u32;
+            Ok((outline_sections(&entries, page_total), page_total))
+        });
+        let (sections, page_total) = match sections {
+            Ok((sections, page_total)) if sections.len() > 1 => (sections, page_total),
+            Ok(_) => {
+                warn!(
+                    target: "recapit::ingest::pdf",
+                    "{} has no outline to split by; sending it whole",
+                    document.path.display()
+                );
+                return Ok(None);
+            }
+            Err(err) => {
+                warn!(
+                    target: "recapit::ingest::pdf",
+                    "Unable to read the outline of {}: {}; sending it whole",
+                    document.path.display(),
+                    err
+                );
+                return Ok(None);
+            }
+        };
+
+        let pages_per_chunk = self.job.as_ref().and_then(|job| job.pdf_chunk_pages);
+        let sections = match pages_per_chunk {
+            Some(pages) => split_long_sections(sections, pages),
+            None => sections,
+        };
+
+        let job_root = self.job_root();
+        let slug = self.pdf_slug(document);
+        let chunk_dir = job_root.join("pdf-chunks").join(&slug);
+        ensure_dir(&chunk_dir)?;
+        let manifest_path = job_root
+            .join("manifests")
+            .join(format!("{slug}-outline.json"));
+
+        let chunk_total = sections.len();
+        let source_pdf = document
+            .meta
+            .get("source_pdf")
+            .cloned()
+            .unwrap_or_else(|| json!(document.path));
+        let mut outputs = Vec::new();
+        for (index, section) in sections.iter().enumerate() {
+            let OutlineSection {
+                title,
+                start_page,
+                end_page,
+                part,
+                parts,
+            } = section;
+            let selection = IndexSelection::parse(&format!("{start_page}-{end_page}"))?;
+            let path = chunk_dir.join(format!(
+                "{slug}-section{index:02}-p{start_page:04}-{end_page:04}.pdf"
+            ));
+            extract_pages(&document.path, &selection, &path).with_context(|| {
+                format!(
+                    "splitting {} at its bookmark \"{title}\"",
+                    document.path.display()
+                )
+            })?;
+
+            let mut meta = value_to_map(&document.meta);
+            meta.remove("inline_bytes");
+            meta.remove("upload_cache_key");
+            meta.insert("source_pdf".into(), source_pdf.clone());
+            meta.insert("page_total".into(), Value::from(page_total));
+            meta.insert("chunk_index".into(), Value::from(index));
+            meta.insert("chunk_total".into(), Value::from(chunk_total));
+            meta.insert("chunk_start_page".into(), Value::from(*start_page));
+            meta.insert("chunk_end_page".into(), Value::from(*end_page));
+            meta.insert("chapter_title".into(), json!(title));
+            // Sent ahead of the section so each summary opens under its own heading.
+            let label = if *parts > 1 {
+                meta.insert("chapter_part".into(), Value::from(*part));
+                meta.insert("chapter_parts".into(), Value::from(*parts));
+                format!(
+                    "Section {} of {chunk_total}: part {part} of {parts} of \"{title}\" (pages {start_page}-{end_page}); head its summary with \"{title} (part {part} of {parts})\":",
+                    index + 1
+                )
+            } else {
+                format!(
+                    "Section {} of {chunk_total}, \"{title}\" (pages {start_page}-{end_page}); head its summary with that title:",
+                    index + 1
+                )
+            };
+            meta.insert("group_label".into(), json!(label));
+            meta.insert("manifest_path".into(), json!(manifest_path));
+            let mut info = json!({
+                "chunk_index": index,
+                "chapter_title": title,
+                "chunk_start_page": start_page,
+                "chunk_end_page": end_page,
+                "source_pdf": source_pdf,
+            });
+            if *parts > 1 {
+                info["chapter_part"] = json!(part);
+                info["chapter_parts"] = json!(parts);
+            }
+            self.chunk_info.push(info);
+
+            let mut chunk = document.clone();
+            chunk.path = path;
+            chunk.page_index = None;
+            chunk.meta = Value::Object(meta);
+            outputs.push(chunk);
         }
+
+        self.write_pdf_manifest(document, &outputs, page_total, None, &manifest_path)?;
+        self.manifest_path = Some(manifest_path);
+        Ok(Some(outputs))
+    }
+
+    fn write_pdf_manifest(
+        &self,
+        document: &Asset,
+        chunks: &[Asset],
+        page_total: u32,
+        pages_per_chunk: Option<u32>,
+        manifest_path: &Path,
+    ) -> Result<()> {
+        ensure_dir(manifest_path.parent().unwrap())?;
+        let entries: Vec<Value> = chunks
+            .iter()
+            .map(|chunk| {
+                let mut entry = json!({
+                    "index": chunk.meta.get("chunk_index"),
+                    "start_page": chunk.meta.get("chunk_start_page"),
+                    "end_page": chunk.meta.get("chunk_end_page"),
+                    "path": chunk.path,
+                    "status": "pending",
+                });
+                if let Some(title) = chunk.meta.get("chapter_title") {
+                    entry["title"] = title.clone();
+                }
+                entry
+            })
+            .collect();
+        let source_hash = sha256sum(&document.path)?;
+        let payload = json!({
+            "version": 1,
+            "source": document.meta.get("source_pdf").cloned().unwrap_or_else(|| json!(document.path)),
+            "source_hash": format!("sha256:{source_hash}"),
+            "source_kind": document.source_kind,
+            "pages": document.meta.get("pages"),
+            "page_total": page_total,
+            "pages_per_chunk": pages_per_chunk,
+            "created_utc": OffsetDateTime::now_utc(),
+            "updated_utc": OffsetDateTime::now_utc(),
+            "chunks": entries,
+        });
+        fs::write(manifest_path, serde_json::to_string_pretty(&payload)?)?;
+        Ok(())
     }
 
     fn pdf_output_dir(&self, asset: &Asset) -> PathBuf {
-        let slug = asset
+        self.job_root()
+            .join("page-images")
+            .join(self.pdf_slug(asset))
+    }
+
+    fn pdf_slug(&self, asset: &Asset) -> String {
+        asset
             .path
             .file_stem()
             .map(|s| slugify(s.to_string_lossy()))
-            .unwrap_or_else(|| "document".into());
-        self.job_root().join("page-images").join(slug)
+            .unwrap_or_else(|| "document".into())
     }
 
     fn job_root(&self) -> PathBuf {
@@ -144,7 +813,13 @@ impl CompositeNormalizer {
                         .map(|(_, tail)| tail.to_string())
                         .unwrap_or_else(|| job.source.clone())
                 };
-                return output_dir.join(slugify(slug));
+                let root = output_dir.join(slugify(&slug));
+                // Outputs mirrored beside their sources would otherwise name the source
+                // file itself.
+                if root.is_file() {
+                    return output_dir.join(format!(".recapit-{}", slugify(slug)));
+                }
+                return root;
             }
         }
         self.video_root.clone()
@@ -164,6 +839,11 @@ impl CompositeNormalizer {
     }
 
     fn normalize_video(&mut self, asset: &Asset) -> Result<Vec<Asset>> {
+        if asset.source_kind == SourceKind::Youtube && self.youtube_downloader.options().captions {
+            if let Some(transcript) = self.youtube_captions(asset)? {
+                return Ok(vec![transcript]);
+            }
+        }
         let realized = self.materialize_video(asset)?;
         if realized
             .meta
@@ -175,61 +855,168 @@ impl CompositeNormalizer {
             return Ok(vec![realized]);
         }
 
+        let video_mode = self
+            .job
+            .as_ref()
+            .map(|job| job.video_mode)
+            .unwrap_or_default();
+        // `--video-mode audio` drops the picture: normalize_audio re-encodes with `-vn`.
+        let audio_only = realized.media == "audio" || video_mode == VideoMode::Audio;
+        let (media, mime, tokens_per_second) = if audio_only {
+            ("audio", "audio/mp4", DEFAULT_AUDIO_TOKENS_PER_SECOND)
+        } else {
+            ("video", "video/mp4", self.tokens_per_second)
+        };
+
         let job_root = self.job_root();
         ensure_dir(&job_root)?;
         let slug = realized
             .path
             .file_stem()
             .map(|s| slugify(s.to_string_lossy()))
-            .unwrap_or_else(|| "video".into());
+            .unwrap_or_else(|| media.into());
         let normalized_dir = job_root
             .join("pickles")
-            .join("video-chunks")
+            .join(format!("{media}-chunks"))
             .join(slug.clone());
         ensure_dir(&normalized_dir)?;
 
+        let slides_dir = job_root.join("pickles").join("slides").join(&slug);
+        if !audio_only && video_mode == VideoMode::Slides {
+            let slides = extract_slides(&realized.path, &slides_dir)?;
+            return Ok(slides
+                .iter()
+                .map(|slide| Asset {
+                    path: slide.path.clone(),
+                    media: "image".into(),
+                    page_index: Some(slide.index as u32),
+                    source_kind: realized.source_kind,
+                    mime: Some("image/png".into()),
+                    meta: json!({
+                        "slide_seconds": slide.seconds,
+                        "source_video": realized.path,
+                    }),
+                })
+                .collect());
+        }
+
         let encoder_specs = select_encoder_chain(self.encoder_preference);
-        let normalization =
-            crate::video::normalize_video(&realized.path, &normalized_dir, &encoder_specs)?;
+        let file_name = realized
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| slug.clone());
+        let normalization = self.with_encode_meter(&format!("encode {file_name}"), |report| {
+            if audio_only {
+                normalize_audio(&realized.path, &normalized_dir, report)
+            } else {
+                crate::video::normalize_video(
+                    &realized.path,
+                    &normalized_dir,
+                    &encoder_specs,
+                    &self.encode,
+                    report,
+                )
+            }
+        })?;
+        // Chunks are cut from the condensed recording; the map translates their
+        // times back onto the original.
+        let (normalization, time_map) = match self.silence.enabled {
+            true => {
+                match self.with_encode_meter(&format!("remove silence {file_name}"), |report| {
+                    remove_silence(
+                        &normalization.path,
+                        &normalized_dir,
+                        &self.silence,
+                        audio_only,
+                        &encoder_specs,
+                        &self.encode,
+                        report,
+                    )
+                })? {
+                    Some((condensed, map)) => (condensed, Some(map)),
+                    None => (normalization, None),
+                }
+            }
+            false => (normalization, None),
+        };
         let normalized_path = normalization.path.clone();
+        // Hybrid slides are sampled from what is sent, so their times match the chunks.
+        let slides = if !audio_only && video_mode == VideoMode::Hybrid {
+            extract_slides(&normalized_path, &slides_dir)?
+        } else {
+            Vec::new()
+        };
         let metadata = probe_video(&normalized_path)?;
         let manifest_path = job_root.join("manifests").join(format!("{slug}.json"));
 
         ensure_dir(manifest_path.parent().unwrap())?;
-        let chunk_plan = plan_video_chunks(
-            &metadata,
-            &normalized_path,
-            self.max_chunk_seconds,
-            self.max_chunk_bytes,
-            self.token_limit,
-            self.tokens_per_second,
-            &normalized_dir.join("chunks"),
-            self.job
-                .as_ref()
-                .map(|job| job.max_video_workers)
-                .unwrap_or(1),
+        // Keep differently-cut chunks apart so a mode change never reuses stale segments.
+        let mut chunk_dir_name = String::from("chunks");
+        if self.chunk_boundaries.mode != ChunkBoundaryMode::Fixed {
+            chunk_dir_name = format!("{chunk_dir_name}-{}", self.chunk_boundaries.mode.as_str());
+        }
+        if time_map.is_some() {
+            chunk_dir_name.push_str("-condensed");
+        }
+        if self.chunk_boundaries.extraction == ChunkExtraction::Accurate {
+            chunk_dir_name.push_str("-accurate");
+        }
+        let chunk_dir = normalized_dir.join(chunk_dir_name);
+        let max_workers = self
+            .job
+            .as_ref()
+            .map(|job| job.max_video_workers)
+            .unwrap_or(1);
+        let chunk_plan = self.with_encode_meter(&format!("cut {file_name}"), |report| {
+            plan_video_chunks(
+                &metadata,
+                &normalized_path,
+                self.max_chunk_seconds,
+                self.max_chunk_bytes,
+                self.token_limit,
+                tokens_per_second,
+                &chunk_dir,
+                max_workers,
+                self.chunk_boundaries,
+                report,
+            )
+        })?;
+        self.write_manifest(
+            &chunk_plan,
+            &realized,
+            tokens_per_second,
+            time_map.as_ref(),
+            &manifest_path,
         )?;
-        self.write_manifest(&chunk_plan, &realized, &manifest_path)?;
         self.manifest_path = Some(manifest_path.clone());
 
         let chunk_total = chunk_plan.chunks.len();
         let mut outputs = Vec::new();
         for chunk in &chunk_plan.chunks {
-            let meta = json!({
+            let mut meta = json!({
                 "chunk_index": chunk.index,
                 "chunk_total": chunk_total,
                 "chunk_start_seconds": chunk.start_seconds,
                 "chunk_end_seconds": chunk.end_seconds,
+                "tokens_per_second": tokens_per_second,
                 "manifest_path": manifest_path,
                 "normalized_path": chunk_plan.normalized_path,
                 "source_video": realized.path,
             });
+            if !slides.is_empty() {
+                meta["slides"] = chunk_slides(&slides, chunk.start_seconds, chunk.end_seconds);
+            }
+            if let Some(map) = &time_map {
+                meta["time_map"] =
+                    serde_json::to_value(map.slice(chunk.start_seconds, chunk.end_seconds))?;
+            }
             outputs.push(Asset {
                 path: chunk.path.clone(),
-                media: "video".into(),
+                media: media.into(),
                 page_index: None,
                 source_kind: realized.source_kind,
-                mime: Some("video/mp4".into()),
+                mime: Some(mime.into()),
                 meta: meta.clone(),
             });
             self.chunk_info.push(meta);
@@ -237,8 +1024,142 @@ impl CompositeNormalizer {
         Ok(outputs)
     }
 
+    /// Run `pass` with an `Encode` progress row named `name` fed by its ffmpeg reports.
+    fn with_encode_meter<T>(
+        &self,
+        name: &str,
+        pass: impl FnOnce(OnEncodeProgress) -> Result<T>,
+    ) -> Result<T> {
+        let job_id = self
+            .job
+            .as_ref()
+            .map(|job| job.job_id.clone())
+            .unwrap_or_default();
+        let meter = Mutex::new(EncodeMeter::new(self.progress.clone(), &job_id, name));
+        let report = |progress: EncodeProgress| {
+            if let Ok(mut meter) = meter.lock() {
+                meter.update(progress.done_seconds, progress.total_seconds, progress.fps);
+            }
+        };
+        let result = pass(&report);
+        if let Ok(mut meter) = meter.into_inner() {
+            meter.finish();
+        }
+        result
+    }
+
+    /// Download `url` into `dir` with `downloader`, reporting progress.
+    fn fetch_with(
+        &self,
+        downloader: &YouTubeDownloader,
+        url: &str,
+        dir: &Path,
+        audio_only: bool,
+    ) -> std::result::Result<YouTubeDownload, YouTubeDownloadError> {
+        let job_id = self
+            .job
+            .as_ref()
+            .map(|job| job.job_id.clone())
+            .unwrap_or_default();
+        let name = url.split_once("://").map_or(url, |(_, rest)| rest);
+        let mut meter = DownloadMeter::new(self.progress.clone(), &job_id, name);
+        let download = downloader.download(url, Some(dir), audio_only, |done, total| {
+            meter.update(done, total)
+        });
+        if let Ok(download) = download.as_ref() {
+            if !download.cached {
+                meter.finish(download.size_bytes.unwrap_or_default());
+            }
+        }
+        download
+    }
+
+    /// With `youtube.captions`, a YouTube video's captions stand in for it: the cues
+    /// become a timestamped transcript, and frames sampled from a low-resolution
+    /// copy go along as slides, so the video is never uploaded. `None` when the
+    /// video has no captions, and it is processed as usual.
+    fn youtube_captions(&mut self, asset: &Asset) -> Result<Option<Asset>> {
+        let source_url = asset
+            .meta
+            .get("source_url")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| asset.path.to_string_lossy().to_string());
+        let downloads_dir = self.job_root().join("downloads").join("youtube");
+        let captions_dir = downloads_dir.join("captions").join(slugify(&source_url));
+        let captions = match self
+            .youtube_downloader
+            .download_captions(&source_url, &captions_dir)
+        {
+            Ok(Some(path)) => path,
+            Ok(None) => {
+                warn!(
+                    target: "recapit::ingest::youtube",
+                    "No captions for {}; sending the video instead",
+                    source_url
+                );
+                return Ok(None);
+            }
+            Err(err) => {
+                warn!(
+                    target: "recapit::ingest::youtube",
+                    "Fetching captions for {} failed ({}); sending the video instead",
+                    source_url,
+                    err
+                );
+                return Ok(None);
+            }
+        };
+        let mut transcript = self.normalize_subtitles(&Asset {
+            path: captions,
+            media: "subtitles".into(),
+            mime: Some("text/vtt".into()),
+            ..asset.clone()
+        })?;
+
+        let video_mode = self
+            .job
+            .as_ref()
+            .map(|job| job.video_mode)
+            .unwrap_or_default();
+        let mut meta = value_to_map(&transcript.meta);
+        meta.insert("source_url".into(), Value::String(source_url.clone()));
+        meta.insert("captions_only".into(), Value::Bool(true));
+        if video_mode != VideoMode::Audio {
+            let options = YtDlpOptions {
+                max_height: Some(KEYFRAME_MAX_HEIGHT),
+                audio_only: false,
+                ..self.youtube_downloader.options().clone()
+            };
+            let downloader = self.youtube_downloader.clone().with_options(options);
+            match self.fetch_with(&downloader, &source_url, &downloads_dir, false) {
+                Ok(download) => {
+                    let slug = download
+                        .path
+                        .file_stem()
+                        .map(|stem| slugify(stem.to_string_lossy()))
+                        .unwrap_or_else(|| "video".into());
+                    let slides_dir = self.job_root().join("pickles").join("slides").join(slug);
+                    let slides = extract_slides(&download.path, &slides_dir)?;
+                    meta.insert("slides".into(), chunk_slides(&slides, 0.0, f64::INFINITY));
+                }
+                Err(err) => warn!(
+                    target: "recapit::ingest::youtube",
+                    "Sampling frames of {} failed ({}); sending the captions alone",
+                    source_url,
+                    err
+                ),
+            }
+        }
+        transcript.meta = Value::Object(meta);
+        Ok(Some(transcript))
+    }
+
     fn materialize_video(&mut self, asset: &Asset) -> Result<Asset> {
-        if asset.source_kind != SourceKind::Youtube {
+        if !matches!(
+            asset.source_kind,
+            SourceKind::Youtube | SourceKind::MediaUrl
+        ) {
             return Ok(asset.clone());
         }
 
@@ -257,17 +1178,55 @@ impl CompositeNormalizer {
             .map(|s| s.to_string())
             .unwrap_or_else(|| asset.path.to_string_lossy().to_string());
 
-        let downloads_dir = self.job_root().join("downloads").join("youtube");
+        let downloads_dir =
+            self.job_root()
+                .join("downloads")
+                .join(if asset.source_kind == SourceKind::MediaUrl {
+                    "media"
+                } else {
+                    "youtube"
+                });
         ensure_dir(&downloads_dir)?;
 
-        match self
-            .youtube_downloader
-            .download(&source_url, Some(&downloads_dir))
-        {
+        // Slides and hybrid modes need the picture even when audio is preferred.
+        let video_mode = self
+            .job
+            .as_ref()
+            .map(|job| job.video_mode)
+            .unwrap_or_default();
+        let audio_only = video_mode == VideoMode::Audio
+            || (self.youtube_downloader.options().audio_only
+                && !matches!(video_mode, VideoMode::Slides | VideoMode::Hybrid));
+        let download = self.fetch_with(
+            &self.youtube_downloader,
+            &source_url,
+            &downloads_dir,
+            audio_only,
+        );
+        // Only YouTube URLs can be handed to Gemini directly; other hosts must download.
+        if asset.source_kind == SourceKind::MediaUrl {
+            match &download {
+                Err(
+                    err @ (YouTubeDownloadError::Metadata(_) | YouTubeDownloadError::Download(_)),
+                ) => {
+                    bail!(
+                        "{err}\nLecture-capture links usually need a signed-in session; \
+                         pass --cookies-from-browser or --cookies."
+                    )
+                }
+                Err(err) => bail!("{err}"),
+                Ok(_) => {}
+            }
+        }
+
+        match download {
             Ok(download) => {
                 let updated = apply_download_metadata(meta_map, &download, &source_url);
                 let mut realized = asset.clone();
                 realized.path = download.path.clone();
+                if download.mime.starts_with("audio/") {
+                    realized.media = "audio".into();
+                }
                 realized.mime = Some(download.mime.clone());
                 realized.meta = Value::Object(updated);
                 Ok(realized)
@@ -315,6 +1274,8 @@ impl CompositeNormalizer {
         &self,
         plan: &VideoChunkPlan,
         asset: &Asset,
+        tokens_per_second: f64,
+        time_map: Option<&TimeMap>,
         manifest_path: &Path,
     ) -> Result<()> {
         ensure_dir(manifest_path.parent().unwrap())?;
@@ -324,6 +1285,7 @@ impl CompositeNormalizer {
                 "index": chunk.index,
                 "start_seconds": chunk.start_seconds,
                 "end_seconds": chunk.end_seconds,
+                "actual_seconds": chunk.actual_seconds,
                 "start_iso": crate::video::seconds_to_iso(chunk.start_seconds),
                 "end_iso": crate::video::seconds_to_iso(chunk.end_seconds),
                 "path": chunk.path,
@@ -363,10 +1325,11 @@ impl CompositeNormalizer {
             "duration_seconds": plan.metadata.duration_seconds,
             "size_bytes": plan.metadata.size_bytes,
             "fps": plan.metadata.fps,
-            "tokens_per_second": self.tokens_per_second,
+            "tokens_per_second": tokens_per_second,
             "created_utc": OffsetDateTime::now_utc(),
             "updated_utc": OffsetDateTime::now_utc(),
             "chunks": chunks,
+            "time_map": time_map,
         });
         fs::write(manifest_path, serde_json::to_string_pretty(&payload)?)?;
         Ok(())
@@ -392,6 +1355,40 @@ impl crate::core::Normalizer for CompositeNormalizer {
     }
 }
 
+fn tag_office_source(assets: &mut [Asset], key: &str, source: &Path) {
+    for asset in assets {
+        if let Some(meta) = asset.meta.as_object_mut() {
+            meta.insert(key.into(), json!(source));
+        }
+    }
+}
+
+/// A unique file name for the prepared copy of `source`, keeping JPEGs as JPEG and
+/// writing everything else as PNG.
+fn prepared_image_name(source: &Path) -> String {
+    let digest = Sha256::digest(source.to_string_lossy().as_bytes());
+    let stem = source
+        .file_stem()
+        .map(|s| slugify(s.to_string_lossy()))
+        .unwrap_or_else(|| "image".into());
+    let extension = match source.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg") => "jpg",
+        _ => "png",
+    };
+    format!("{stem}-{}.{extension}", &hex::encode(digest)[..8])
+}
+
+/// Slides first shown inside `[start, end)`, as `{path, seconds}` for the provider.
+fn chunk_slides(slides: &[SlideFrame], start: f64, end: f64) -> Value {
+    Value::Array(
+        slides
+            .iter()
+            .filter(|slide| slide.seconds >= start && (slide.seconds < end || end <= start))
+            .map(|slide| json!({ "path": slide.path, "seconds": slide.seconds }))
+            .collect(),
+    )
+}
+
 fn value_to_map(value: &Value) -> Map<String, Value> {
     value.as_object().cloned().unwrap_or_else(Map::new)
 }
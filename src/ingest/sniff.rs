@@ -0,0 +1,142 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::Result;
+
+/// Bytes read from the start of a file for sniffing.
+const HEAD_BYTES: usize = 4096;
+
+/// The MIME type of a downloaded file according to its leading bytes, or `None`
+/// when nothing recognisable is found and the Content-Type or extension decides.
+///
+/// ZIP payloads are opened to tell EPUB, OpenDocument, and Office Open XML apart.
+pub(crate) fn sniff_file(path: &Path) -> Result<Option<&'static str>> {
+    let mut head = Vec::with_capacity(HEAD_BYTES);
+    File::open(path)?
+        .take(HEAD_BYTES as u64)
+        .read_to_end(&mut head)?;
+    Ok(match sniff_bytes(&head) {
+        Some("application/zip") => sniff_zip(path),
+        other => other,
+    })
+}
+
+fn sniff_bytes(head: &[u8]) -> Option<&'static str> {
+    let at = |offset: usize, magic: &[u8]| head.get(offset..offset + magic.len()) == Some(magic);
+    if at(0, b"%PDF-") {
+        return Some("application/pdf");
+    }
+    if at(0, b"\x89PNG\r\n\x1a\n") {
+        return Some("image/png");
+    }
+    if at(0, b"\xff\xd8\xff") {
+        return Some("image/jpeg");
+    }
+    if at(0, b"GIF87a") || at(0, b"GIF89a") {
+        return Some("image/gif");
+    }
+    if at(0, b"II*\0") || at(0, b"MM\0*") {
+        return Some("image/tiff");
+    }
+    if at(0, b"BM") && head.len() > 14 {
+        return Some("image/bmp");
+    }
+    if at(0, b"RIFF") && at(8, b"WAVE") {
+        return Some("audio/wav");
+    }
+    if at(4, b"ftyp") {
+        return Some(match head.get(8..12) {
+            Some(b"M4A ") | Some(b"M4B ") => "audio/mp4",
+            Some(b"qt  ") => "video/quicktime",
+            _ => "video/mp4",
+        });
+    }
+    if at(0, b"\x1a\x45\xdf\xa3") {
+        return Some("video/x-matroska");
+    }
+    if at(0, b"ID3") || (head.len() > 1 && head[0] == 0xff && head[1] & 0xe0 == 0xe0) {
+        return Some("audio/mpeg");
+    }
+    if at(0, b"PK\x03\x04") {
+        return Some("application/zip");
+    }
+    if at(0, b"{\\rtf") {
+        return Some("application/rtf");
+    }
+    let text = String::from_utf8_lossy(head);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    if text.starts_with("WEBVTT") {
+        return Some("text/vtt");
+    }
+    let lower = text.to_ascii_lowercase();
+    if lower.starts_with("<!doctype html")
+        || lower.starts_with("<html")
+        || (lower.starts_with('<') && (lower.contains("<head") || lower.contains("<body")))
+    {
+        return Some("text/html");
+    }
+    None
+}
+
+fn sniff_zip(path: &Path) -> Option<&'static str> {
+    let mut archive = zip::ZipArchive::new(File::open(path).ok()?).ok()?;
+    if let Ok(mut entry) = archive.by_name("mimetype") {
+        let mut declared = String::new();
+        entry.read_to_string(&mut declared).ok()?;
+        return match declared.trim() {
+            "application/epub+zip" => Some("application/epub+zip"),
+            "application/vnd.oasis.opendocument.text" => {
+                Some("application/vnd.oasis.opendocument.text")
+            }
+            "application/vnd.oasis.opendocument.presentation" => {
+                Some("application/vnd.oasis.opendocument.presentation")
+            }
+            _ => None,
+        };
+    }
+    let has = |prefix: &str| archive.file_names().any(|name| name.starts_with(prefix));
+    if has("word/") {
+        return Some("application/vnd.openxmlformats-officedocument.wordprocessingml.document");
+    }
+    if has("ppt/") {
+        return Some("application/vnd.openxmlformats-officedocument.presentationml.presentation");
+    }
+    None
+}
+
+/// Whether an HTML page is Google Drive's "can't scan this file for viruses"
+/// warning, which Drive serves instead of large files on direct download links.
+pub(crate) fn is_drive_interstitial(html: &str) -> bool {
+    [
+        "uc-download-link",
+        "download_warning",
+        "can't scan this file for viruses",
+        "can&#39;t scan this file for viruses",
+    ]
+    .iter()
+    .any(|marker| html.contains(marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payloads_are_recognised_by_their_leading_bytes() {
+        assert_eq!(sniff_bytes(b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(
+            sniff_bytes(b"\0\0\0\x20ftypisom\0\0\x02\0"),
+            Some("video/mp4")
+        );
+        assert_eq!(sniff_bytes(b"\0\0\0\x20ftypM4A \0\0"), Some("audio/mp4"));
+        assert_eq!(
+            sniff_bytes(b"\n  <!DOCTYPE html><html><head>"),
+            Some("text/html")
+        );
+        assert_eq!(sniff_bytes(b"plain notes"), None);
+        assert!(is_drive_interstitial(
+            r#"<a id="uc-download-link" href="/uc?export=download&confirm=t">"#
+        ));
+    }
+}
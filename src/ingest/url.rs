@@ -1,16 +1,19 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
-use reqwest::blocking::{Client, Response};
-use reqwest::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use reqwest::blocking::Client;
 use sha2::{Digest, Sha256};
-use std::fs::File;
-use std::io::{copy, Read, Write};
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use tokio::sync::mpsc::UnboundedSender;
 use url::Url;
 
+use super::download::{fetch_resumable, DEFAULT_MAX_DOWNLOAD_BYTES};
+use super::html::extract_readable;
+use super::sniff::{is_drive_interstitial, sniff_file};
 use crate::core::{Asset, Job, SourceKind};
+use crate::progress::{DownloadMeter, Progress};
 use crate::utils::ensure_dir;
 
 const INLINE_THRESHOLD: usize = 20 * 1024 * 1024;
@@ -18,76 +21,96 @@ const INLINE_THRESHOLD: usize = 20 * 1024 * 1024;
 pub struct UrlIngestor {
     client: Client,
     cache_dir: PathBuf,
+    progress: Option<UnboundedSender<Progress>>,
+    max_bytes: u64,
 }
 
 impl UrlIngestor {
     pub fn new(cache_dir: Option<PathBuf>) -> Result<Self> {
-        let cache = cache_dir.unwrap_or_else(|| std::env::temp_dir().join("recapit-url-cache"));
+        let cache = cache_dir.unwrap_or_else(default_cache_dir);
         ensure_dir(&cache)?;
         Ok(Self {
             client: Client::builder()
                 .timeout(std::time::Duration::from_secs(30))
                 .build()?,
             cache_dir: cache,
+            progress: None,
+            max_bytes: DEFAULT_MAX_DOWNLOAD_BYTES,
         })
     }
 
+    /// Refuse downloads larger than `max_bytes`.
+    pub fn with_max_download(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Report download progress on `tx`.
+    pub fn with_progress(mut self, tx: UnboundedSender<Progress>) -> Self {
+        self.progress = Some(tx);
+        self
+    }
+
     pub fn discover(&self, job: &Job) -> Result<Vec<Asset>> {
         let parsed = Url::parse(&job.source)?;
         if parsed.scheme() != "http" && parsed.scheme() != "https" {
             return Ok(vec![]);
         }
 
-        let size_hint = self.head_size(&parsed).unwrap_or(None);
-        let inline_allowed = size_hint
-            .map(|size| size <= INLINE_THRESHOLD)
-            .unwrap_or(false);
-
-        let (path, mime, meta) = if inline_allowed {
-            let mut response = self.client.get(parsed.clone()).send()?;
-            let mime = response
-                .headers()
-                .get(CONTENT_TYPE)
-                .and_then(|value| value.to_str().ok())
-                .map(|s| s.to_string());
-            let bytes = read_all(&mut response)?;
-            let cache_key = cache_key(parsed.as_str());
-            let dest = self.cache_dir.join(format!(
-                "{cache_key}{}",
-                guess_suffix(&parsed, mime.as_deref())
-            ));
-            let encoded = BASE64.encode(&bytes);
-            ensure_dir(dest.parent().unwrap())?;
-            File::create(&dest)?.write_all(&bytes)?;
-            let meta = serde_json::json!({
-                "url": job.source,
-                "size_bytes": bytes.len(),
-                "inline_bytes": encoded,
-                "upload_cache_key": cache_key,
-            });
-            (dest, mime, meta)
-        } else {
-            let mut response = self.client.get(parsed.clone()).send()?;
-            let mime = response
-                .headers()
-                .get(CONTENT_TYPE)
-                .and_then(|value| value.to_str().ok())
-                .map(|s| s.to_string());
-            let target = self.cache_dir.join(format!(
-                "{}{}",
-                cache_key(parsed.as_str()),
-                guess_suffix(&parsed, mime.as_deref())
-            ));
-            ensure_dir(target.parent().unwrap())?;
-            let mut file = File::create(&target)?;
-            let size = copy(&mut response, &mut file)? as usize;
-            let meta = serde_json::json!({
-                "url": job.source,
-                "size_bytes": size,
-                "upload_cache_key": cache_key(parsed.as_str()),
-            });
-            (target, mime, meta)
+        // A `.part` file left by an interrupted run is continued rather than restarted.
+        let key = cache_key(parsed.as_str());
+        let part = self.cache_dir.join(format!("{key}.part"));
+        let mut meter =
+            DownloadMeter::new(self.progress.clone(), &job.job_id, &download_name(&parsed));
+        let declared = fetch_resumable(
+            || self.client.get(parsed.clone()),
+            &part,
+            &mut meter,
+            self.max_bytes,
+        )?;
+        // The payload decides over the Content-Type and extension, which file hosts
+        // often get wrong.
+        let sniffed = sniff_file(&part)?;
+        let html = match sniffed {
+            Some(kind) => kind == "text/html",
+            None => is_html(declared.as_deref()),
         };
+        if html {
+            let expected = infer_media(&parsed, declared.as_deref().filter(|d| !is_html(Some(d))));
+            let page = String::from_utf8_lossy(&fs::read(&part)?).into_owned();
+            let interstitial = is_drive_interstitial(&page);
+            if interstitial || expected.is_some() {
+                fs::remove_file(&part)?;
+            }
+            if interstitial {
+                bail!(
+                    "{parsed} returned Google Drive's virus-scan warning page instead of the file; use the file's share link or a drive://<file id> source, which download through the Drive API"
+                );
+            }
+            if let Some(expected) = expected {
+                bail!(
+                    "{parsed} returned an HTML page instead of the expected {expected}; the link may need a login or lead to a landing page rather than the file itself"
+                );
+            }
+        }
+        let mime = sniffed.map(str::to_string).or(declared);
+        let path = self
+            .cache_dir
+            .join(format!("{key}{}", guess_suffix(&parsed, mime.as_deref())));
+        fs::rename(&part, &path)?;
+        let size = fs::metadata(&path)?.len() as usize;
+        let mut meta = serde_json::json!({
+            "url": job.source,
+            "size_bytes": size,
+            "upload_cache_key": key,
+        });
+        if size <= INLINE_THRESHOLD {
+            meta["inline_bytes"] = BASE64.encode(fs::read(&path)?).into();
+        }
+
+        if is_html(mime.as_deref()) {
+            return self.readable_asset(job, &parsed, &path, mime.as_deref());
+        }
 
         let media = infer_media(&parsed, mime.as_deref());
         if media.is_none() {
@@ -103,23 +126,60 @@ impl UrlIngestor {
         }])
     }
 
-    fn head_size(&self, url: &Url) -> Result<Option<usize>> {
-        let response = self.client.head(url.clone()).send();
-        match response {
-            Ok(resp) => Ok(resp
-                .headers()
-                .get(CONTENT_LENGTH)
-                .and_then(|value| value.to_str().ok())
-                .and_then(|value| value.parse().ok())),
-            Err(_) => Ok(None),
+    fn readable_asset(
+        &self,
+        job: &Job,
+        url: &Url,
+        html_path: &Path,
+        mime: Option<&str>,
+    ) -> Result<Vec<Asset>> {
+        let bytes = fs::read(html_path)?;
+        let readable = extract_readable(&String::from_utf8_lossy(&bytes));
+        if readable.markdown.trim().is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut markdown = String::new();
+        if let Some(title) = &readable.title {
+            markdown.push_str(&format!("# {title}\n\n"));
         }
+        markdown.push_str(&format!("Source: <{url}>\n\n"));
+        markdown.push_str(&readable.markdown);
+        markdown.push('\n');
+
+        let dest = self
+            .cache_dir
+            .join(format!("{}.md", cache_key(url.as_str())));
+        fs::write(&dest, &markdown)?;
+        Ok(vec![Asset {
+            path: dest,
+            media: "text".into(),
+            page_index: None,
+            source_kind: SourceKind::Url,
+            mime: Some("text/markdown".into()),
+            meta: serde_json::json!({
+                "url": job.source,
+                "title": readable.title,
+                "source_html": html_path,
+                "source_mime": mime,
+                "size_bytes": markdown.len(),
+            }),
+        }])
     }
 }
 
-fn read_all(response: &mut Response) -> Result<Vec<u8>> {
-    let mut bytes = Vec::new();
-    response.read_to_end(&mut bytes)?;
-    Ok(bytes)
+pub(crate) fn default_cache_dir() -> PathBuf {
+    std::env::temp_dir().join("recapit-url-cache")
+}
+
+/// What the progress row calls the download: the file name, or the host.
+fn download_name(url: &Url) -> String {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| !name.is_empty())
+        .or(url.host_str())
+        .unwrap_or("download")
+        .to_string()
 }
 
 fn guess_suffix(url: &Url, mime: Option<&str>) -> String {
@@ -138,13 +198,35 @@ fn guess_suffix(url: &Url, mime: Option<&str>) -> String {
     String::new()
 }
 
+fn is_html(mime: Option<&str>) -> bool {
+    mime.and_then(|value| value.split(';').next())
+        .map(|value| {
+            matches!(
+                value.trim().to_lowercase().as_str(),
+                "text/html" | "application/xhtml+xml"
+            )
+        })
+        .unwrap_or(false)
+}
+
 fn infer_media(url: &Url, mime: Option<&str>) -> Option<&'static str> {
     if let Some(mime) = mime {
         match mime {
             "application/pdf" => return Some("pdf"),
-            "image/png" | "image/jpeg" | "image/gif" | "image/tiff" => return Some("image"),
-            "video/mp4" => return Some("video"),
-            "audio/mpeg" => return Some("audio"),
+            "image/png" | "image/jpeg" | "image/gif" | "image/tiff" | "image/bmp" => {
+                return Some("image")
+            }
+            "video/mp4" | "video/quicktime" | "video/x-matroska" => return Some("video"),
+            "audio/mpeg" | "audio/wav" | "audio/mp4" => return Some("audio"),
+            "application/vnd.openxmlformats-officedocument.presentationml.presentation"
+            | "application/vnd.ms-powerpoint"
+            | "application/vnd.oasis.opendocument.presentation" => return Some("presentation"),
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+            | "application/msword"
+            | "application/vnd.oasis.opendocument.text"
+            | "application/rtf" => return Some("document"),
+            "application/epub+zip" => return Some("epub"),
+            "text/vtt" | "application/x-subrip" => return Some("subtitles"),
             _ => {}
         }
     }
@@ -155,6 +237,10 @@ fn infer_media(url: &Url, mime: Option<&str>) -> Option<&'static str> {
             "png" | "jpg" | "jpeg" | "gif" | "tif" | "tiff" | "bmp" => Some("image"),
             "mp4" | "mov" | "mkv" => Some("video"),
             "mp3" | "wav" | "m4a" => Some("audio"),
+            "pptx" | "ppt" | "odp" => Some("presentation"),
+            "docx" | "doc" | "odt" | "rtf" => Some("document"),
+            "epub" => Some("epub"),
+            "srt" | "vtt" => Some("subtitles"),
             _ => None,
         })
 }
@@ -2,13 +2,14 @@ use anyhow::{Context, Result};
 use serde_json::{json, Value};
 use std::collections::HashSet;
 use std::env;
+use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
 use thiserror::Error;
 use url::Url;
-use which::which;
 
 use crate::core::{Asset, Job, SourceKind};
+use crate::tools::{self, Tool};
 use crate::utils::ensure_dir;
 use crate::video::sha256sum;
 
@@ -61,9 +62,212 @@ impl YouTubeIngestor {
     }
 }
 
+/// Channel paths whose bare form lists tabs rather than videos.
+const CHANNEL_PREFIXES: [&str; 4] = ["/@", "/channel/", "/c/", "/user/"];
+
+#[derive(Debug, Clone)]
+pub struct Playlist {
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlaylistEntry {
+    pub id: String,
+    pub url: String,
+    pub title: Option<String>,
+}
+
+/// True for playlist and channel pages. A watch URL carrying `list=` is still a single video.
+pub fn is_playlist_url(source: &str) -> bool {
+    let Ok(url) = Url::parse(source) else {
+        return false;
+    };
+    if !YOUTUBE_HOSTS.contains(&url.host_str().unwrap_or_default()) {
+        return false;
+    }
+    let path = url.path();
+    if path.starts_with("/playlist") {
+        return url.query_pairs().any(|(key, _)| key == "list");
+    }
+    CHANNEL_PREFIXES
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+}
+
+/// Cookie sources handed to yt-dlp for sites that need a signed-in session.
+#[derive(Debug, Clone, Default)]
+pub struct YtDlpCookies {
+    pub from_browser: Option<String>,
+    pub file: Option<PathBuf>,
+}
+
+impl YtDlpCookies {
+    fn apply(&self, command: &mut Command) {
+        if let Some(browser) = &self.from_browser {
+            command.arg("--cookies-from-browser").arg(browser);
+        }
+        if let Some(file) = &self.file {
+            command.arg("--cookies").arg(file);
+        }
+    }
+}
+
+/// Format, rate, and proxy settings for yt-dlp downloads (`youtube` in
+/// `recapit.yaml`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct YtDlpOptions {
+    /// Tallest video stream to download, in pixels (`720` for 720p).
+    pub max_height: Option<u32>,
+    /// Download only the audio track, preferring m4a.
+    pub audio_only: bool,
+    /// Download speed cap in yt-dlp's notation, such as `2M` or `500K`.
+    pub limit_rate: Option<String>,
+    pub proxy: Option<String>,
+    /// Send a YouTube video's captions and sampled frames instead of the video.
+    pub captions: bool,
+    /// Caption languages in yt-dlp's `--sub-langs` notation; English when empty.
+    pub caption_languages: Vec<String>,
+}
+
+impl YtDlpOptions {
+    fn apply(&self, command: &mut Command) {
+        if let Some(proxy) = &self.proxy {
+            command.arg("--proxy").arg(proxy);
+        }
+    }
+
+    /// The `-f` selection; the metadata probe uses it too so its `ext` names
+    /// the file the download produces.
+    fn apply_format(&self, command: &mut Command, audio_only: bool) {
+        if audio_only {
+            command.arg("-f").arg("bestaudio[ext=m4a]/bestaudio");
+        } else {
+            if let Some(height) = self.max_height {
+                command.arg("-f").arg(format!(
+                    "bestvideo*[height<={height}]+bestaudio/best[height<={height}]/best"
+                ));
+            }
+            command.arg("--merge-output-format").arg("mp4");
+        }
+    }
+
+    /// The download's file name, so a change of format does not reuse a file
+    /// downloaded with other settings.
+    fn file_stem(&self, video_id: &str, audio_only: bool) -> String {
+        match (audio_only, self.max_height) {
+            (true, _) => format!("{video_id}.audio"),
+            (false, Some(height)) => format!("{video_id}.{height}p"),
+            (false, None) => video_id.to_string(),
+        }
+    }
+}
+
+/// Height of the copy downloaded only to sample frames from when captions stand
+/// in for the video.
+pub(crate) const KEYFRAME_MAX_HEIGHT: u32 = 360;
+
+/// List the videos of a playlist or channel with `yt-dlp --flat-playlist` (nothing is downloaded).
+pub fn expand_playlist(
+    source: &str,
+    cookies: &YtDlpCookies,
+    options: &YtDlpOptions,
+) -> Result<Playlist> {
+    let ytdlp = tools::resolve(Tool::YtDlp).map_err(|_| YouTubeDownloadError::MissingYtDlp)?;
+    let mut command = Command::new(ytdlp);
+    cookies.apply(&mut command);
+    options.apply(&mut command);
+    let output = command
+        .arg("--flat-playlist")
+        .arg("--dump-single-json")
+        .arg("--no-warnings")
+        .arg(channel_videos_url(source))
+        .output()
+        .context("failed to execute yt-dlp")?;
+    if !output.status.success() {
+        return Err(YouTubeDownloadError::Metadata(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )
+        .into());
+    }
+    let metadata: Value =
+        serde_json::from_slice(&output.stdout).context("unable to parse yt-dlp playlist JSON")?;
+    Ok(parse_playlist(&metadata))
+}
+
+fn channel_videos_url(source: &str) -> String {
+    let Ok(mut url) = Url::parse(source) else {
+        return source.to_string();
+    };
+    let path = url.path().trim_end_matches('/').to_string();
+    let segments = path.split('/').filter(|part| !part.is_empty()).count();
+    let bare_channel = if path.starts_with("/@") {
+        segments == 1
+    } else {
+        segments == 2
+            && CHANNEL_PREFIXES
+                .iter()
+                .any(|prefix| path.starts_with(prefix))
+    };
+    // A bare channel page lists its Videos/Shorts/Live tabs; point at the uploads instead.
+    if bare_channel {
+        url.set_path(&format!("{path}/videos"));
+    }
+    url.to_string()
+}
+
+fn parse_playlist(metadata: &Value) -> Playlist {
+    let mut entries = Vec::new();
+    collect_entries(metadata, &mut entries);
+    let mut seen = HashSet::new();
+    entries.retain(|entry: &PlaylistEntry| seen.insert(entry.id.clone()));
+    Playlist {
+        id: metadata
+            .get("id")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        title: metadata
+            .get("title")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        entries,
+    }
+}
+
+fn collect_entries(node: &Value, out: &mut Vec<PlaylistEntry>) {
+    let Some(items) = node.get("entries").and_then(Value::as_array) else {
+        return;
+    };
+    for item in items {
+        if item.get("entries").is_some() {
+            collect_entries(item, out);
+            continue;
+        }
+        let Some(id) = item.get("id").and_then(Value::as_str) else {
+            continue;
+        };
+        // Unavailable/private videos come back without a title and cannot be downloaded.
+        let title = item
+            .get("title")
+            .and_then(Value::as_str)
+            .filter(|title| !matches!(*title, "[Private video]" | "[Deleted video]"));
+        if title.is_none() && item.get("title").is_some() {
+            continue;
+        }
+        out.push(PlaylistEntry {
+            id: id.to_string(),
+            url: format!("https://www.youtube.com/watch?v={id}"),
+            title: title.map(str::to_string),
+        });
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct YouTubeDownloader {
     cache_dir: PathBuf,
+    cookies: YtDlpCookies,
+    options: YtDlpOptions,
 }
 
 #[derive(Debug, Clone)]
@@ -92,30 +296,55 @@ pub enum YouTubeDownloadError {
 
 impl YouTubeDownloader {
     pub fn new(cache_dir: Option<PathBuf>) -> Result<Self> {
-        let base = cache_dir.unwrap_or_else(|| {
-            dirs::cache_dir()
-                .unwrap_or_else(env::temp_dir)
-                .join("recapit")
-                .join("youtube")
-        });
+        let base = cache_dir.unwrap_or_else(default_cache_dir);
         ensure_dir(&base)?;
-        Ok(Self { cache_dir: base })
+        Ok(Self {
+            cache_dir: base,
+            cookies: YtDlpCookies::default(),
+            options: YtDlpOptions::default(),
+        })
     }
 
+    pub fn with_cookies(mut self, cookies: YtDlpCookies) -> Self {
+        self.cookies = cookies;
+        self
+    }
+
+    pub fn with_options(mut self, options: YtDlpOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn options(&self) -> &YtDlpOptions {
+        &self.options
+    }
+
+    /// Download `url` with yt-dlp, calling `on_progress` with bytes done and the
+    /// expected total as it goes. yt-dlp continues its own `.part` files, so a
+    /// download interrupted by an earlier run picks up where it stopped.
+    ///
+    /// With `audio_only` only the audio track is fetched.
     pub fn download(
         &self,
         url: &str,
         target_dir: Option<&Path>,
+        audio_only: bool,
+        mut on_progress: impl FnMut(u64, Option<u64>),
     ) -> std::result::Result<YouTubeDownload, YouTubeDownloadError> {
-        let ytdlp = which("yt-dlp").map_err(|_| YouTubeDownloadError::MissingYtDlp)?;
-        let ffmpeg = which("ffmpeg").map_err(|_| YouTubeDownloadError::MissingFfmpeg)?;
+        let ytdlp = tools::resolve(Tool::YtDlp).map_err(|_| YouTubeDownloadError::MissingYtDlp)?;
+        let ffmpeg =
+            tools::resolve(Tool::Ffmpeg).map_err(|_| YouTubeDownloadError::MissingFfmpeg)?;
 
         let base_dir = target_dir
             .map(PathBuf::from)
             .unwrap_or_else(|| self.cache_dir.clone());
         ensure_dir(&base_dir).map_err(|err| YouTubeDownloadError::Other(err.to_string()))?;
 
-        let metadata_output = Command::new(&ytdlp)
+        let mut metadata_command = Command::new(&ytdlp);
+        self.cookies.apply(&mut metadata_command);
+        self.options.apply(&mut metadata_command);
+        self.options.apply_format(&mut metadata_command, audio_only);
+        let metadata_output = metadata_command
             .arg("--dump-json")
             .arg("--skip-download")
             .arg("--no-warnings")
@@ -147,35 +376,65 @@ impl YouTubeDownloader {
             .and_then(|value| value.as_str())
             .unwrap_or("mp4");
 
-        let expected_mp4 = base_dir.join(format!("{video_id}.mp4"));
-        let expected_ext = base_dir.join(format!("{video_id}.{ext}"));
+        let stem = self.options.file_stem(&video_id, audio_only);
+        let expected_mp4 = base_dir.join(format!("{stem}.mp4"));
+        let expected_ext = base_dir.join(format!("{stem}.{ext}"));
 
         let (path, cached) = if expected_mp4.exists() {
+            crate::cache::touch(&expected_mp4);
             (expected_mp4.clone(), true)
         } else if expected_ext.exists() {
+            crate::cache::touch(&expected_ext);
             (expected_ext.clone(), true)
         } else {
-            let template = base_dir.join(format!("{video_id}.%(ext)s"));
-            let status = Command::new(&ytdlp)
-                .arg("--quiet")
+            let template = base_dir.join(format!("{stem}.%(ext)s"));
+            let mut download_command = Command::new(&ytdlp);
+            self.cookies.apply(&mut download_command);
+            self.options.apply(&mut download_command);
+            self.options.apply_format(&mut download_command, audio_only);
+            if let Some(rate) = &self.options.limit_rate {
+                download_command.arg("--limit-rate").arg(rate);
+            }
+            let mut child = download_command
                 .arg("--no-warnings")
-                .arg("--no-progress")
-                .arg("--merge-output-format")
-                .arg("mp4")
+                .arg("--newline")
+                .arg("--progress-template")
+                .arg(PROGRESS_TEMPLATE)
                 .arg("--ffmpeg-location")
                 .arg(ffmpeg.to_string_lossy().to_string())
                 .arg("-o")
                 .arg(template.to_string_lossy().to_string())
                 .arg(url)
-                .status()
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
                 .map_err(|err| {
                     YouTubeDownloadError::Other(format!("failed to execute yt-dlp: {err}"))
                 })?;
+            let mut stderr = child.stderr.take().expect("piped stderr");
+            let errors = std::thread::spawn(move || {
+                let mut text = String::new();
+                let _ = stderr.read_to_string(&mut text);
+                text
+            });
+            let stdout = child.stdout.take().expect("piped stdout");
+            for line in BufReader::new(stdout).lines().map_while(|line| line.ok()) {
+                if let Some((done, total)) = parse_progress_line(&line) {
+                    on_progress(done, total);
+                }
+            }
+            let status = child.wait().map_err(|err| {
+                YouTubeDownloadError::Other(format!("failed to wait for yt-dlp: {err}"))
+            })?;
+            let errors = errors.join().unwrap_or_default();
 
             if !status.success() {
-                return Err(YouTubeDownloadError::Download(format!(
-                    "yt-dlp exit status {status}"
-                )));
+                let detail = errors.trim();
+                return Err(YouTubeDownloadError::Download(if detail.is_empty() {
+                    format!("yt-dlp exit status {status}")
+                } else {
+                    format!("yt-dlp exit status {status}: {detail}")
+                }));
             }
 
             if expected_mp4.exists() {
@@ -191,7 +450,11 @@ impl YouTubeDownloader {
 
         let size_bytes = path.metadata().ok().map(|meta| meta.len());
         let sha = sha256sum(&path).ok();
-        let mime = format!("video/{}", ext.trim_start_matches('.'));
+        let mime = match (audio_only, ext.trim_start_matches('.')) {
+            (true, "m4a") => "audio/mp4".to_string(),
+            (true, ext) => format!("audio/{ext}"),
+            (false, ext) => format!("video/{ext}"),
+        };
 
         Ok(YouTubeDownload {
             path,
@@ -202,6 +465,85 @@ impl YouTubeDownloader {
             size_bytes,
         })
     }
+
+    /// Fetch the video's captions as WebVTT into `target_dir`, preferring
+    /// uploaded subtitles over automatic ones. Returns `None` when the video has
+    /// no captions in the requested languages. Files already in `target_dir`
+    /// are reused.
+    pub fn download_captions(
+        &self,
+        url: &str,
+        target_dir: &Path,
+    ) -> std::result::Result<Option<PathBuf>, YouTubeDownloadError> {
+        if let Some(existing) = find_captions(target_dir) {
+            crate::cache::touch(&existing);
+            return Ok(Some(existing));
+        }
+        let ytdlp = tools::resolve(Tool::YtDlp).map_err(|_| YouTubeDownloadError::MissingYtDlp)?;
+        ensure_dir(target_dir).map_err(|err| YouTubeDownloadError::Other(err.to_string()))?;
+        let languages = if self.options.caption_languages.is_empty() {
+            "en.*,en".to_string()
+        } else {
+            self.options.caption_languages.join(",")
+        };
+        let mut command = Command::new(ytdlp);
+        self.cookies.apply(&mut command);
+        self.options.apply(&mut command);
+        let output = command
+            .arg("--skip-download")
+            .arg("--write-subs")
+            .arg("--write-auto-subs")
+            .arg("--sub-langs")
+            .arg(languages)
+            .arg("--sub-format")
+            .arg("vtt/best")
+            .arg("--convert-subs")
+            .arg("vtt")
+            .arg("--no-warnings")
+            .arg("-o")
+            .arg(target_dir.join("%(id)s.%(ext)s"))
+            .arg(url)
+            .output()
+            .map_err(|err| {
+                YouTubeDownloadError::Other(format!("failed to execute yt-dlp: {err}"))
+            })?;
+        if !output.status.success() {
+            return Err(YouTubeDownloadError::Download(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(find_captions(target_dir))
+    }
+}
+
+/// The first `.vtt` file in `dir`, by name.
+fn find_captions(dir: &Path) -> Option<PathBuf> {
+    let mut found: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "vtt"))
+        .collect();
+    found.sort();
+    found.into_iter().next()
+}
+
+pub(crate) fn default_cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("recapit")
+        .join("youtube")
+}
+
+/// Machine-readable progress lines on yt-dlp's stdout: bytes done, total, and
+/// estimated total, any of which may be `NA`.
+const PROGRESS_TEMPLATE: &str = "download:recapit-progress %(progress.downloaded_bytes)s %(progress.total_bytes)s %(progress.total_bytes_estimate)s";
+
+fn parse_progress_line(line: &str) -> Option<(u64, Option<u64>)> {
+    let mut fields = line.strip_prefix("recapit-progress ")?.split_whitespace();
+    let number = |field: Option<&str>| field.and_then(|value| value.parse::<f64>().ok());
+    let done = number(fields.next())?;
+    let total = number(fields.next()).or(number(fields.next()));
+    Some((done as u64, total.map(|total| total as u64)))
 }
 
 fn parse_url(input: &str) -> Result<Url> {
@@ -210,3 +552,91 @@ fn parse_url(input: &str) -> Result<Url> {
         Err(_) => Url::parse(&format!("https://{input}")).context("unable to parse YouTube URL"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_playlists_and_channels() {
+        assert!(is_playlist_url(
+            "https://www.youtube.com/playlist?list=PL123"
+        ));
+        assert!(is_playlist_url("https://www.youtube.com/@mitocw"));
+        assert!(!is_playlist_url(
+            "https://www.youtube.com/watch?v=abc&list=PL123"
+        ));
+        assert!(!is_playlist_url("https://example.com/playlist?list=PL123"));
+        assert_eq!(
+            channel_videos_url("https://www.youtube.com/@mitocw"),
+            "https://www.youtube.com/@mitocw/videos"
+        );
+        assert_eq!(
+            channel_videos_url("https://www.youtube.com/@mitocw/streams"),
+            "https://www.youtube.com/@mitocw/streams"
+        );
+    }
+
+    #[test]
+    fn reads_progress_lines() {
+        assert_eq!(
+            parse_progress_line("recapit-progress 1024 4096 NA"),
+            Some((1024, Some(4096)))
+        );
+        assert_eq!(
+            parse_progress_line("recapit-progress 1024 NA 8192.5"),
+            Some((1024, Some(8192)))
+        );
+        assert_eq!(parse_progress_line("[download] Destination: a.mp4"), None);
+    }
+
+    #[test]
+    fn format_settings_select_streams_and_file_names() {
+        let args = |options: &YtDlpOptions, audio_only: bool| {
+            let mut command = Command::new("yt-dlp");
+            options.apply_format(&mut command, audio_only);
+            command
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+        };
+        let capped = YtDlpOptions {
+            max_height: Some(720),
+            ..Default::default()
+        };
+        assert_eq!(
+            args(&capped, false),
+            [
+                "-f",
+                "bestvideo*[height<=720]+bestaudio/best[height<=720]/best",
+                "--merge-output-format",
+                "mp4"
+            ]
+        );
+        assert_eq!(args(&capped, true), ["-f", "bestaudio[ext=m4a]/bestaudio"]);
+        assert_eq!(capped.file_stem("abc", false), "abc.720p");
+        assert_eq!(capped.file_stem("abc", true), "abc.audio");
+        assert_eq!(YtDlpOptions::default().file_stem("abc", false), "abc");
+    }
+
+    #[test]
+    fn flattens_playlist_entries() {
+        let metadata = json!({
+            "id": "PL123",
+            "title": "Lectures",
+            "entries": [
+                {"id": "a1", "title": "Intro"},
+                {"id": "b2", "title": "[Private video]"},
+                {"entries": [{"id": "c3", "title": "Nested"}, {"id": "a1", "title": "Intro"}]},
+            ],
+        });
+        let playlist = parse_playlist(&metadata);
+        assert_eq!(playlist.title.as_deref(), Some("Lectures"));
+        let ids: Vec<_> = playlist.entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["a1", "c3"]);
+        assert_eq!(
+            playlist.entries[0].url,
+            "https://www.youtube.com/watch?v=a1"
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! Recapit turns PDFs, slides, images, video, and URLs into cleaned transcripts with
+//! Gemini models.
+//!
+//! The `recapit` binary is a thin CLI over this crate: it parses a [`cli::Cli`] and
+//! hands it to [`app::run`], and [`app::run_jobs`] runs the transcribe workflow with
+//! progress sent to a channel instead of the terminal. Embedders that want one job
+//! without the CLI build a [`Job`] with [`Job::new`] and run it through an [`Engine`]
+//! assembled from an [`Ingestor`], a [`Normalizer`], a [`Provider`], and a [`Writer`];
+//! each stage is a trait so any of them can be swapped out.
+
+pub mod app;
+mod batch;
+mod cache;
+pub mod cli;
+pub mod config;
+mod constants;
+mod conversion;
+mod core;
+pub mod cost;
+mod doctor;
+pub mod engine;
+mod history;
+mod hooks;
+mod imaging;
+pub mod ingest;
+mod metrics;
+mod models;
+mod office;
+mod pdf;
+mod pdfium;
+pub mod progress;
+mod prompts;
+pub mod providers;
+pub mod quota;
+pub mod render;
+mod selection;
+mod server;
+pub mod telemetry;
+pub mod templates;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod tools;
+mod transcripts;
+mod tui;
+mod utils;
+mod video;
+mod watch;
+
+pub use crate::app::JobSummary;
+pub use crate::config::AppConfig;
+pub use crate::conversion::LatexConverter;
+pub use crate::core::{
+    Asset, Cancelled, ConflictPolicy, Detail, Grouping, Ingestor, Job, Kind, Normalizer,
+    OutputFormat, PdfMode, PromptStrategy, Provider, SourceKind, Writer,
+};
+pub use crate::engine::Engine;
+pub use crate::progress::{Progress, ProgressScope, ProgressStage};
+pub use crate::selection::IndexSelection;
+pub use crate::templates::{TemplateLoader, TemplateVars};
+pub use crate::video::VideoMode;
@@ -0,0 +1,385 @@
+//! Process-wide request, token, retry, and quota-sleep counters for monitoring
+//! recapit at scale.
+//!
+//! [`RunMonitor`](crate::telemetry::RunMonitor) feeds every request and note into
+//! [`METRICS`]. `recapit serve` exposes them in the Prometheus text format on
+//! `GET /metrics`, and with `metrics.otlp_endpoint` set they are pushed as OTLP/HTTP
+//! JSON to `<endpoint>/v1/metrics`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+
+use crate::telemetry::RequestEvent;
+
+/// Upper bounds, in seconds, of the request latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 9] = [1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0];
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+pub struct Metrics {
+    started: OffsetDateTime,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Keyed by (model, modality).
+    requests: BTreeMap<(String, String), RequestStats>,
+    /// Keyed by the retried operation, such as `generateContent`.
+    retries: BTreeMap<String, u64>,
+    quota_sleeps: u64,
+    quota_sleep_seconds: f64,
+    /// Keyed by `succeeded`, `failed`, or `cancelled`.
+    jobs: BTreeMap<String, u64>,
+}
+
+#[derive(Default)]
+struct RequestStats {
+    count: u64,
+    input_tokens: u64,
+    output_tokens: u64,
+    latency_sum: f64,
+    /// One count per [`LATENCY_BUCKETS`] bound, plus the overflow bucket.
+    latency_buckets: [u64; LATENCY_BUCKETS.len() + 1],
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            started: OffsetDateTime::now_utc(),
+            state: Mutex::new(State::default()),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn observe_request(&self, event: &RequestEvent) {
+        let mut state = self.state.lock().unwrap();
+        let stats = state
+            .requests
+            .entry((event.model.clone(), event.modality.clone()))
+            .or_default();
+        let latency = event.duration_seconds();
+        stats.count += 1;
+        stats.input_tokens += event.input_tokens.unwrap_or(0) as u64;
+        stats.output_tokens += event.output_tokens.unwrap_or(0) as u64;
+        stats.latency_sum += latency;
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| latency <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        stats.latency_buckets[bucket] += 1;
+    }
+
+    /// Count the telemetry notes that matter for monitoring: `retry.*` and
+    /// `quota.sleep`.
+    pub fn observe_note(&self, name: &str, payload: &Value) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(operation) = name.strip_prefix("retry.") {
+            *state.retries.entry(operation.to_string()).or_default() += 1;
+        } else if name == "quota.sleep" {
+            state.quota_sleeps += 1;
+            state.quota_sleep_seconds += payload
+                .get("delay_ms")
+                .and_then(Value::as_f64)
+                .unwrap_or(0.0)
+                / 1000.0;
+        }
+    }
+
+    pub fn job_finished(&self, status: &str) {
+        *self
+            .state
+            .lock()
+            .unwrap()
+            .jobs
+            .entry(status.to_string())
+            .or_default() += 1;
+    }
+
+    /// Everything so far in the Prometheus text exposition format.
+    pub fn prometheus(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut out = String::new();
+        fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+            let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}");
+        }
+        family(
+            &mut out,
+            "recapit_requests_total",
+            "counter",
+            "generateContent requests that returned a response.",
+        );
+        for ((model, modality), stats) in &state.requests {
+            let _ = writeln!(
+                out,
+                "recapit_requests_total{{model=\"{model}\",modality=\"{modality}\"}} {}",
+                stats.count
+            );
+        }
+        family(
+            &mut out,
+            "recapit_tokens_total",
+            "counter",
+            "Tokens billed, by direction.",
+        );
+        for ((model, modality), stats) in &state.requests {
+            for (direction, tokens) in [
+                ("input", stats.input_tokens),
+                ("output", stats.output_tokens),
+            ] {
+                let _ = writeln!(
+                    out,
+                    "recapit_tokens_total{{model=\"{model}\",modality=\"{modality}\",direction=\"{direction}\"}} {tokens}"
+                );
+            }
+        }
+        family(
+            &mut out,
+            "recapit_request_duration_seconds",
+            "histogram",
+            "Latency of generateContent requests.",
+        );
+        for ((model, modality), stats) in &state.requests {
+            let labels = format!("model=\"{model}\",modality=\"{modality}\"");
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(stats.latency_buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "recapit_request_duration_seconds_bucket{{{labels},le=\"{bound}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "recapit_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {}\nrecapit_request_duration_seconds_sum{{{labels}}} {}\nrecapit_request_duration_seconds_count{{{labels}}} {}",
+                stats.count, stats.latency_sum, stats.count
+            );
+        }
+        family(
+            &mut out,
+            "recapit_retries_total",
+            "counter",
+            "Retried API calls, by operation.",
+        );
+        for (operation, count) in &state.retries {
+            let _ = writeln!(
+                out,
+                "recapit_retries_total{{operation=\"{operation}\"}} {count}"
+            );
+        }
+        family(
+            &mut out,
+            "recapit_quota_sleeps_total",
+            "counter",
+            "Waits for a rate-limit window to open.",
+        );
+        let _ = writeln!(out, "recapit_quota_sleeps_total {}", state.quota_sleeps);
+        family(
+            &mut out,
+            "recapit_quota_sleep_seconds_total",
+            "counter",
+            "Time spent waiting for rate-limit windows.",
+        );
+        let _ = writeln!(
+            out,
+            "recapit_quota_sleep_seconds_total {}",
+            state.quota_sleep_seconds
+        );
+        family(
+            &mut out,
+            "recapit_jobs_total",
+            "counter",
+            "Finished jobs, by status.",
+        );
+        for (status, count) in &state.jobs {
+            let _ = writeln!(out, "recapit_jobs_total{{status=\"{status}\"}} {count}");
+        }
+        out
+    }
+
+    /// Everything so far as an OTLP `ExportMetricsServiceRequest`, in the JSON
+    /// encoding of OTLP/HTTP. Sums and histograms are cumulative since start-up.
+    pub fn otlp(&self) -> Value {
+        let state = self.state.lock().unwrap();
+        let start = self.started.unix_timestamp_nanos().to_string();
+        let now = OffsetDateTime::now_utc().unix_timestamp_nanos().to_string();
+        let attributes = |pairs: &[(&str, &str)]| -> Value {
+            pairs
+                .iter()
+                .map(|(key, value)| json!({"key": key, "value": {"stringValue": value}}))
+                .collect()
+        };
+        let point = |attrs: Value, value: Value| {
+            json!({
+                "attributes": attrs,
+                "startTimeUnixNano": start,
+                "timeUnixNano": now,
+                "asInt": value.to_string(),
+            })
+        };
+        let sum = |name: &str, unit: &str, points: Vec<Value>| {
+            json!({
+                "name": name,
+                "unit": unit,
+                "sum": {"aggregationTemporality": 2, "isMonotonic": true, "dataPoints": points},
+            })
+        };
+
+        let mut requests = Vec::new();
+        let mut tokens = Vec::new();
+        let mut latency = Vec::new();
+        for ((model, modality), stats) in &state.requests {
+            let labels = [("model", model.as_str()), ("modality", modality.as_str())];
+            requests.push(point(attributes(&labels), json!(stats.count)));
+            for (direction, count) in [
+                ("input", stats.input_tokens),
+                ("output", stats.output_tokens),
+            ] {
+                let mut labels = labels.to_vec();
+                labels.push(("direction", direction));
+                tokens.push(point(attributes(&labels), json!(count)));
+            }
+            latency.push(json!({
+                "attributes": attributes(&labels),
+                "startTimeUnixNano": start,
+                "timeUnixNano": now,
+                "count": stats.count.to_string(),
+                "sum": stats.latency_sum,
+                "bucketCounts": stats.latency_buckets.iter().map(u64::to_string).collect::<Vec<_>>(),
+                "explicitBounds": LATENCY_BUCKETS,
+            }));
+        }
+        let retries = state
+            .retries
+            .iter()
+            .map(|(operation, count)| point(attributes(&[("operation", operation)]), json!(count)))
+            .collect();
+        let jobs = state
+            .jobs
+            .iter()
+            .map(|(status, count)| point(attributes(&[("status", status)]), json!(count)))
+            .collect();
+        let quota_seconds = json!({
+            "attributes": [],
+            "startTimeUnixNano": start,
+            "timeUnixNano": now,
+            "asDouble": state.quota_sleep_seconds,
+        });
+        let metrics = vec![
+            sum("recapit.requests", "1", requests),
+            sum("recapit.tokens", "1", tokens),
+            json!({
+                "name": "recapit.request.duration",
+                "unit": "s",
+                "histogram": {"aggregationTemporality": 2, "dataPoints": latency},
+            }),
+            sum("recapit.retries", "1", retries),
+            sum(
+                "recapit.quota.sleeps",
+                "1",
+                vec![point(json!([]), json!(state.quota_sleeps))],
+            ),
+            sum("recapit.quota.sleep_time", "s", vec![quota_seconds]),
+            sum("recapit.jobs", "1", jobs),
+        ];
+        json!({
+            "resourceMetrics": [{
+                "resource": {"attributes": attributes(&[("service.name", "recapit")])},
+                "scopeMetrics": [{
+                    "scope": {"name": "recapit", "version": env!("CARGO_PKG_VERSION")},
+                    "metrics": metrics,
+                }],
+            }],
+        })
+    }
+
+    /// POST [`Metrics::otlp`] to `<endpoint>/v1/metrics`.
+    pub async fn push_otlp(&self, endpoint: &str) -> Result<()> {
+        let url = format!("{}/v1/metrics", endpoint.trim_end_matches('/'));
+        let response = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?
+            .post(&url)
+            .json(&self.otlp())
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!("OTLP export to {url} failed with {status}: {}", body.trim());
+        }
+        Ok(())
+    }
+}
+
+/// Push [`METRICS`] to `endpoint` every `interval` until `stop` is cancelled.
+/// Failed pushes are logged and retried at the next tick.
+pub async fn export_periodically(endpoint: String, interval: Duration, stop: CancellationToken) {
+    let mut ticks = tokio::time::interval(interval);
+    ticks.tick().await;
+    loop {
+        tokio::select! {
+            _ = stop.cancelled() => return,
+            _ = ticks.tick() => {
+                if let Err(err) = METRICS.push_otlp(&endpoint).await {
+                    tracing::warn!("{err:#}");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_and_notes_reach_both_formats() {
+        let metrics = Metrics::default();
+        let now = OffsetDateTime::now_utc();
+        metrics.observe_request(&RequestEvent {
+            model: "gemini-2.5-flash".into(),
+            modality: "video".into(),
+            started_at: now - time::Duration::seconds(4),
+            finished_at: now,
+            input_tokens: Some(1200),
+            output_tokens: Some(300),
+            total_tokens: Some(1500),
+            metadata: Default::default(),
+        });
+        metrics.observe_note("retry.generateContent", &json!({"status": 503}));
+        metrics.observe_note("quota.sleep", &json!({"delay_ms": 1500}));
+        metrics.observe_note("chunk.failed", &json!({}));
+
+        let text = metrics.prometheus();
+        let labels = "model=\"gemini-2.5-flash\",modality=\"video\"";
+        assert!(text.contains(&format!("recapit_requests_total{{{labels}}} 1")));
+        assert!(text.contains(&format!(
+            "recapit_tokens_total{{{labels},direction=\"input\"}} 1200"
+        )));
+        assert!(text.contains(&format!(
+            "recapit_request_duration_seconds_bucket{{{labels},le=\"2.5\"}} 0"
+        )));
+        assert!(text.contains(&format!(
+            "recapit_request_duration_seconds_bucket{{{labels},le=\"5\"}} 1"
+        )));
+        assert!(text.contains("recapit_retries_total{operation=\"generateContent\"} 1"));
+        assert!(text.contains("recapit_quota_sleep_seconds_total 1.5"));
+
+        let otlp = metrics.otlp();
+        let exported = &otlp["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+        assert_eq!(exported[0]["sum"]["dataPoints"][0]["asInt"], "1");
+        assert_eq!(
+            exported[2]["histogram"]["dataPoints"][0]["bucketCounts"][2],
+            "1"
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! The model catalog behind `recapit models`: what the models endpoint reports,
+//! merged with the local capability, rate-limit, and pricing tables.
+//!
+//! The endpoint's listing is cached in `<cache>/recapit/models.json` and refreshed
+//! when it is a week old (or on `--refresh-models`), so new model variants pick up
+//! the capabilities, limits, and prices of the model they belong to.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::{Duration, OffsetDateTime};
+
+use crate::constants::{self, ModelPricing};
+use crate::cost::CostEstimator;
+use crate::utils::ensure_dir;
+
+/// How old the cached listing may get before a run fetches it again.
+const MAX_CACHE_AGE: Duration = Duration::days(7);
+
+/// The last `models.list` response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelCache {
+    /// Unix seconds.
+    pub fetched_at: i64,
+    pub models: Vec<Value>,
+}
+
+fn cache_path() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("recapit")
+        .join("models.json")
+}
+
+impl ModelCache {
+    pub fn load() -> Option<Self> {
+        fs::read_to_string(cache_path())
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = cache_path();
+        ensure_dir(path.parent().unwrap())?;
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("writing {}", path.display()))
+    }
+
+    pub fn is_stale(&self) -> bool {
+        OffsetDateTime::now_utc().unix_timestamp() - self.fetched_at > MAX_CACHE_AGE.whole_seconds()
+    }
+
+    /// The cached listing, fetched again first when it is missing, stale, or `force`
+    /// is set. A failed fetch is logged and falls back to the cache, which may be
+    /// `None`, leaving only the built-in tables.
+    pub async fn refresh(api_key: &str, force: bool) -> Option<Self> {
+        let cached = Self::load();
+        if !force && cached.as_ref().is_some_and(|cache| !cache.is_stale()) {
+            return cached;
+        }
+        match crate::providers::gemini::list_models(api_key).await {
+            Ok(models) => {
+                let cache = Self {
+                    fetched_at: OffsetDateTime::now_utc().unix_timestamp(),
+                    models,
+                };
+                if let Err(err) = cache.save() {
+                    tracing::warn!("could not cache the model listing: {err:#}");
+                }
+                Some(cache)
+            }
+            Err(err) => {
+                tracing::warn!("could not refresh the model listing ({err:#}); using cached or built-in model tables");
+                cached
+            }
+        }
+    }
+
+    /// Whether the listing includes `model`.
+    pub fn lists(&self, model: &str) -> bool {
+        self.models.iter().any(|entry| {
+            entry
+                .get("name")
+                .and_then(Value::as_str)
+                .is_some_and(|name| name.trim_start_matches("models/") == model)
+        })
+    }
+}
+
+/// The longest of `known` that `model` is a variant of, such as `gemini-2.5-flash`
+/// for `gemini-2.5-flash-preview-09-2025`.
+pub fn family<'a>(model: &str, known: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    known
+        .into_iter()
+        .filter(|name| {
+            model
+                .strip_prefix(*name)
+                .is_some_and(|rest| rest.starts_with('-'))
+        })
+        .max_by_key(|name| name.len())
+}
+
+/// Whether recapit may send `capability` (`pdf`, `video`, ...) to `model`. Models
+/// missing from the built-in table use their family's entry, then the default
+/// model's.
+pub fn supports(model: &str, capability: &str) -> bool {
+    let table = constants::model_capabilities();
+    table
+        .get(model)
+        .or_else(|| family(model, table.keys().copied()).and_then(|name| table.get(name)))
+        .or_else(|| table.get(constants::DEFAULT_MODEL))
+        .map(|caps| caps.contains(&capability))
+        .unwrap_or(true)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelInfo {
+    /// Model id as passed to `--model`, without the `models/` prefix.
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// Context window in tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_token_limit: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_token_limit: Option<u64>,
+    /// Input modalities recapit sends to the model; empty when it has no entry in the
+    /// capability table.
+    pub modalities: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens_per_minute: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<ModelPricing>,
+    pub is_default: bool,
+    /// Whether the models endpoint returned it; `false` for models only in the local
+    /// tables, or when the endpoint was not queried.
+    pub listed: bool,
+}
+
+/// Merge `listed` (raw `models.list` entries, or `None` when the endpoint was not
+/// queried) with the local tables, sorted by name.
+///
+/// Listed models that cannot `generateContent` (embedding and AQA models) are left
+/// out unless `all` is set. Models in the local tables are always included.
+pub fn catalog(listed: Option<&[Value]>, pricing: &CostEstimator, all: bool) -> Vec<ModelInfo> {
+    let capabilities = constants::model_capabilities();
+    let request_limits = constants::rate_limits_per_minute();
+    let token_limits = constants::token_limits_per_minute();
+    let local = |name: String| {
+        let known = if capabilities.contains_key(name.as_str()) {
+            Some(name.as_str())
+        } else {
+            family(&name, capabilities.keys().copied())
+        };
+        ModelInfo {
+            modalities: known
+                .and_then(|known| capabilities.get(known))
+                .map(|caps| caps.iter().map(|cap| cap.to_string()).collect())
+                .unwrap_or_default(),
+            requests_per_minute: known.and_then(|known| request_limits.get(known).copied()),
+            tokens_per_minute: known.and_then(|known| token_limits.get(known).copied()),
+            pricing: pricing.model_pricing(&name).cloned(),
+            is_default: name == constants::DEFAULT_MODEL,
+            display_name: None,
+            input_token_limit: None,
+            output_token_limit: None,
+            listed: false,
+            name,
+        }
+    };
+
+    let mut models: Vec<ModelInfo> = Vec::new();
+    for entry in listed.unwrap_or_default() {
+        let Some(name) = entry.get("name").and_then(Value::as_str) else {
+            continue;
+        };
+        let generates = entry
+            .get("supportedGenerationMethods")
+            .and_then(Value::as_array)
+            .is_some_and(|methods| methods.iter().any(|m| m == "generateContent"));
+        if !generates && !all {
+            continue;
+        }
+        let mut info = local(name.trim_start_matches("models/").to_string());
+        info.display_name = entry
+            .get("displayName")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        info.input_token_limit = entry.get("inputTokenLimit").and_then(Value::as_u64);
+        info.output_token_limit = entry.get("outputTokenLimit").and_then(Value::as_u64);
+        info.listed = true;
+        models.push(info);
+    }
+    for name in capabilities.keys() {
+        if !models.iter().any(|model| model.name == *name) {
+            models.push(local(name.to_string()));
+        }
+    }
+    models.sort_by(|a, b| a.name.cmp(&b.name));
+    models
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn listed_models_merge_with_local_tables() {
+        let defaults = constants::default_model_pricing()
+            .into_iter()
+            .map(|(model, pricing)| (model.to_string(), pricing))
+            .collect();
+        let pricing = CostEstimator::from_path(None, defaults).unwrap();
+        let listed = [
+            json!({
+                "name": "models/gemini-2.5-pro",
+                "displayName": "Gemini 2.5 Pro",
+                "inputTokenLimit": 1048576,
+                "outputTokenLimit": 65536,
+                "supportedGenerationMethods": ["generateContent", "countTokens"],
+            }),
+            json!({"name": "models/gemini-new", "supportedGenerationMethods": ["generateContent"]}),
+            json!({
+                "name": "models/gemini-2.5-flash-lite-preview-09-2025",
+                "supportedGenerationMethods": ["generateContent"],
+            }),
+            json!({"name": "models/text-embedding-004", "supportedGenerationMethods": ["embedContent"]}),
+        ];
+        let models = catalog(Some(&listed), &pricing, false);
+        let pro = models.iter().find(|m| m.name == "gemini-2.5-pro").unwrap();
+        assert!(pro.listed);
+        assert_eq!(pro.input_token_limit, Some(1_048_576));
+        assert_eq!(pro.requests_per_minute, Some(150));
+        assert!(pro.pricing.is_some());
+        assert!(pro.modalities.iter().any(|m| m == "pdf"));
+
+        let new = models.iter().find(|m| m.name == "gemini-new").unwrap();
+        assert!(new.modalities.is_empty() && new.pricing.is_none());
+        assert!(!models.iter().any(|m| m.name == "text-embedding-004"));
+        // New variants inherit from the longest built-in name they extend.
+        let variant = models
+            .iter()
+            .find(|m| m.name == "gemini-2.5-flash-lite-preview-09-2025")
+            .unwrap();
+        assert_eq!(variant.requests_per_minute, Some(4_000));
+        assert_eq!(variant.pricing.as_ref().unwrap().text.input, 0.10);
+        assert!(supports("gemini-2.5-flash-lite-preview-09-2025", "pdf"));
+        assert!(!supports("gemini-2.5-flash-preview-09-2025", "pdf"));
+        assert_eq!(family("gemini-2.50-pro", ["gemini-2.5"]), None);
+        // Local-only models are still shown, marked as not listed.
+        let default = models.iter().find(|m| m.is_default).unwrap();
+        assert_eq!(default.name, constants::DEFAULT_MODEL);
+        assert!(!default.listed);
+    }
+}
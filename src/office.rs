@@ -0,0 +1,44 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::utils::ensure_dir;
+
+fn soffice() -> Result<PathBuf> {
+    which::which("soffice")
+        .or_else(|_| which::which("libreoffice"))
+        .map_err(|_| anyhow!("soffice not found; install LibreOffice to ingest office documents"))
+}
+
+/// Convert an office document (pptx, docx, odt, ...) to PDF with LibreOffice headless.
+///
+/// The converted file is reused while it is newer than the source.
+pub fn convert_to_pdf(source: &Path, out_dir: &Path) -> Result<PathBuf> {
+    ensure_dir(out_dir)?;
+    let stem = source
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .ok_or_else(|| anyhow!("invalid office document path {}", source.display()))?;
+    let target = out_dir.join(format!("{stem}.pdf"));
+    if target.exists() && target.metadata()?.modified()? >= source.metadata()?.modified()? {
+        return Ok(target);
+    }
+
+    let output = Command::new(soffice()?)
+        .arg("--headless")
+        .arg("--convert-to")
+        .arg("pdf")
+        .arg("--outdir")
+        .arg(out_dir)
+        .arg(source)
+        .output()
+        .context("invoking soffice")?;
+    if !output.status.success() || !target.exists() {
+        bail!(
+            "soffice failed to convert {}: {}",
+            source.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(target)
+}
@@ -2,8 +2,75 @@ use anyhow::{anyhow, bail, Context, Result};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::RwLock;
 
 use crate::selection::IndexSelection;
+use crate::tools::{self, Tool};
+
+/// Alphanumeric characters the sampled pages must yield to count as a real text layer.
+const TEXT_LAYER_MIN_CHARS: usize = 200;
+const TEXT_LAYER_SAMPLE_PAGES: u32 = 5;
+
+/// When to OCR rasterized PDF pages and attach the text as a hint (`pdf.ocr`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OcrMode {
+    /// Only for PDFs without a text layer, i.e. scans.
+    #[default]
+    Auto,
+    Off,
+    Force,
+}
+
+impl OcrMode {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "auto" | "" => Ok(Self::Auto),
+            "off" | "false" | "no" => Ok(Self::Off),
+            "force" | "on" => Ok(Self::Force),
+            other => bail!("Unknown pdf.ocr mode '{}'", other),
+        }
+    }
+}
+
+/// What rasterizes PDF pages and counts them (`pdf.renderer`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PdfRenderer {
+    /// Poppler's `pdftoppm` and `pdfinfo`.
+    #[default]
+    Poppler,
+    /// PDFium loaded in-process; needs no external programs.
+    Pdfium,
+}
+
+impl PdfRenderer {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "poppler" | "" => Ok(Self::Poppler),
+            "pdfium" => Ok(Self::Pdfium),
+            other => bail!("Unknown pdf.renderer '{}'", other),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Poppler => "poppler",
+            Self::Pdfium => "pdfium",
+        }
+    }
+}
+
+static RENDERER: RwLock<PdfRenderer> = RwLock::new(PdfRenderer::Poppler);
+
+/// Use `renderer` for every later render and page count. Loading the config calls this.
+pub fn set_renderer(renderer: PdfRenderer) {
+    if let Ok(mut current) = RENDERER.write() {
+        *current = renderer;
+    }
+}
+
+pub fn renderer() -> PdfRenderer {
+    RENDERER.read().map(|current| *current).unwrap_or_default()
+}
 
 #[derive(Debug, Clone)]
 pub struct PdfPage {
@@ -17,6 +84,7 @@ pub fn pdf_to_png(
     prefix: Option<&str>,
     dpi: u32,
     selection: Option<&IndexSelection>,
+    on_page: &mut dyn FnMut(usize, usize),
 ) -> Result<Vec<PdfPage>> {
     if out_dir.exists() {
         fs::remove_dir_all(out_dir)?;
@@ -24,8 +92,36 @@ pub fn pdf_to_png(
 
     fs::create_dir_all(out_dir)?;
 
-    let pdftoppm = which::which("pdftoppm")
-        .map_err(|_| anyhow!("pdftoppm not found; install poppler-utils"))?;
+    if renderer() == PdfRenderer::Pdfium {
+        let stem = prefix
+            .map(str::to_string)
+            .or_else(|| pdf.file_stem().map(|s| s.to_string_lossy().to_string()))
+            .unwrap_or_else(|| "page".into());
+        let pages = match selection {
+            Some(selection) => {
+                let total_pages = page_count(pdf)? as u32;
+                let ranges = selection.merged_ranges(total_pages)?;
+                Some(
+                    ranges
+                        .into_iter()
+                        .flat_map(|(start, end)| start..=end)
+                        .collect(),
+                )
+            }
+            None => None,
+        };
+        let pages = crate::pdfium::render_pages(pdf, out_dir, &stem, dpi, pages, on_page)?;
+        if pages.is_empty() {
+            bail!("No PNG pages rendered for {}", pdf.display());
+        }
+        return Ok(pages
+            .into_iter()
+            .map(|(page_number, path)| PdfPage { path, page_number })
+            .collect());
+    }
+
+    let pdftoppm = tools::resolve(Tool::Pdftoppm)
+        .map_err(|err| anyhow!("{err:#}; install poppler-utils or set tools.pdftoppm"))?;
     let stem = prefix
         .map(|s| s.to_string())
         .or_else(|| pdf.file_stem().map(|s| s.to_string_lossy().to_string()))
@@ -87,11 +183,177 @@ pub fn pdf_to_png(
     if pages.is_empty() {
         bail!("No PNG pages rendered for {}", pdf.display());
     }
+    // pdftoppm renders a whole range per call, so pages are reported at the end.
+    on_page(pages.len(), pages.len());
     Ok(pages)
 }
 
+/// Write a copy of `pdf` containing only the selected pages to `dest`.
+///
+/// Uses poppler's `pdfseparate`/`pdfunite`, or PDFium with `pdf.renderer: pdfium`;
+/// returns the number of pages kept.
+pub fn extract_pages(pdf: &Path, selection: &IndexSelection, dest: &Path) -> Result<u32> {
+    if renderer() == PdfRenderer::Pdfium {
+        let ranges = selection.merged_ranges(page_count(pdf)? as u32)?;
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        return crate::pdfium::extract_pages(pdf, &ranges, dest);
+    }
+    let pdfseparate = tools::resolve(Tool::Pdfseparate)
+        .map_err(|err| anyhow!("{err:#}; install poppler-utils"))?;
+    let pdfunite =
+        tools::resolve(Tool::Pdfunite).map_err(|err| anyhow!("{err:#}; install poppler-utils"))?;
+
+    let total_pages = page_count(pdf)? as u32;
+    let ranges = selection.merged_ranges(total_pages)?;
+
+    let scratch = tempfile::tempdir().context("creating scratch dir for pdfseparate")?;
+    let pattern = scratch.path().join("page-%d.pdf");
+    for (start, end) in &ranges {
+        let status = Command::new(&pdfseparate)
+            .arg("-f")
+            .arg(start.to_string())
+            .arg("-l")
+            .arg(end.to_string())
+            .arg(pdf)
+            .arg(&pattern)
+            .status()?;
+        if !status.success() {
+            bail!(
+                "pdfseparate failed for {} (pages {start}-{end})",
+                pdf.display()
+            );
+        }
+    }
+
+    let mut pages: Vec<(u32, PathBuf)> = Vec::new();
+    for entry in fs::read_dir(scratch.path())? {
+        let path = entry?.path();
+        if let Some(number) = parse_pdftoppm_page_number(&path) {
+            pages.push((number, path));
+        }
+    }
+    pages.sort_by_key(|(number, _)| *number);
+    if pages.is_empty() {
+        bail!("No pages extracted from {}", pdf.display());
+    }
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let status = Command::new(&pdfunite)
+        .args(pages.iter().map(|(_, path)| path))
+        .arg(dest)
+        .status()?;
+    if !status.success() {
+        bail!("pdfunite failed while writing {}", dest.display());
+    }
+    Ok(pages.len() as u32)
+}
+
+/// Plain text of the PDF via `pdftotext` (or PDFium), optionally only up to
+/// `last_page`.
+pub fn pdf_text(path: &Path, last_page: Option<u32>) -> Result<String> {
+    if renderer() == PdfRenderer::Pdfium {
+        let total = crate::pdfium::page_count(path)?;
+        let last = last_page.map_or(total, |last| last.min(total));
+        if last == 0 {
+            return Ok(String::new());
+        }
+        let pages = crate::pdfium::page_texts(path, &[(1, last)])?;
+        return Ok(pages
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect::<Vec<_>>()
+            .join("\x0c"));
+    }
+    let mut command = tools::command(Tool::Pdftotext);
+    if let Some(last) = last_page {
+        command.arg("-l").arg(last.to_string());
+    }
+    let output = command
+        .arg(path)
+        .arg("-")
+        .output()
+        .context("invoking pdftotext")?;
+    if !output.status.success() {
+        bail!("pdftotext failed for {}", path.display());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Text of the selected pages, each introduced by a `--- Page N ---` marker so the
+/// model can still cite page numbers.
+pub fn pdf_page_text(path: &Path, selection: Option<&IndexSelection>) -> Result<String> {
+    let total_pages = page_count(path)? as u32;
+    let ranges = match selection {
+        Some(selection) => selection.merged_ranges(total_pages)?,
+        None => vec![(1, total_pages)],
+    };
+    let mut text = String::new();
+    if renderer() == PdfRenderer::Pdfium {
+        for (page, body) in crate::pdfium::page_texts(path, &ranges)? {
+            text.push_str(&format!("--- Page {page} ---\n{}\n\n", body.trim_end()));
+        }
+        return Ok(text);
+    }
+    for (start, end) in ranges {
+        let output = tools::command(Tool::Pdftotext)
+            .arg("-layout")
+            .arg("-f")
+            .arg(start.to_string())
+            .arg("-l")
+            .arg(end.to_string())
+            .arg(path)
+            .arg("-")
+            .output()
+            .context("invoking pdftotext")?;
+        if !output.status.success() {
+            bail!(
+                "pdftotext failed for {} (pages {start}-{end})",
+                path.display()
+            );
+        }
+        let range_text = String::from_utf8_lossy(&output.stdout);
+        // pdftotext ends every page with a form feed.
+        for (page, body) in (start..=end).zip(range_text.split('\x0c')) {
+            text.push_str(&format!("--- Page {page} ---\n{}\n\n", body.trim_end()));
+        }
+    }
+    Ok(text)
+}
+
+/// Whether the first few pages carry real text, as opposed to a scan with none.
+pub fn has_text_layer(path: &Path) -> Result<bool> {
+    let text = pdf_text(path, Some(TEXT_LAYER_SAMPLE_PAGES))?;
+    Ok(text.chars().filter(|c| c.is_alphanumeric()).count() >= TEXT_LAYER_MIN_CHARS)
+}
+
+/// Recognize the text in a page image with `tesseract`.
+pub fn ocr_image(path: &Path) -> Result<String> {
+    let tesseract =
+        tools::resolve(Tool::Tesseract).map_err(|err| anyhow!("{err:#}; install it for OCR"))?;
+    let output = Command::new(tesseract)
+        .arg(path)
+        .arg("stdout")
+        .output()
+        .context("invoking tesseract")?;
+    if !output.status.success() {
+        bail!(
+            "tesseract failed for {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
 pub fn page_count(path: &Path) -> Result<usize> {
-    let output = Command::new("pdfinfo")
+    if renderer() == PdfRenderer::Pdfium {
+        return Ok(crate::pdfium::page_count(path)? as usize);
+    }
+    let output = tools::command(Tool::Pdfinfo)
         .arg(path)
         .output()
         .context("invoking pdfinfo")?;
@@ -109,8 +371,188 @@ pub fn page_count(path: &Path) -> Result<usize> {
     page_count.ok_or_else(|| anyhow!("pdfinfo missing page count"))
 }
 
+/// A top-level bookmark: its title and the 1-based page it points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineEntry {
+    pub title: String,
+    pub page: u32,
+}
+
+/// A run of pages under one top-level bookmark (`pdf.split_outline`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineSection {
+    pub title: String,
+    pub start_page: u32,
+    pub end_page: u32,
+    /// 1-based part of a section cut by [`split_long_sections`], of `parts`.
+    pub part: u32,
+    pub parts: u32,
+}
+
+/// The top-level bookmarks of the PDF in document order; empty when it has no
+/// outline. Bookmarks that point nowhere are left out.
+pub fn pdf_outline(path: &Path) -> Result<Vec<OutlineEntry>> {
+    if renderer() == PdfRenderer::Pdfium {
+        return crate::pdfium::outline(path);
+    }
+    // pdftohtml writes the outline after the pages, so render only the first.
+    let output = tools::command(Tool::Pdftohtml)
+        .args(["-xml", "-stdout", "-q", "-i", "-f", "1", "-l", "1"])
+        .arg(path)
+        .output()
+        .context("invoking pdftohtml")?;
+    if !output.status.success() {
+        bail!("pdftohtml failed for {}", path.display());
+    }
+    parse_pdftohtml_outline(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Top-level `<item page="N">` entries of the `<outline>` in pdftohtml's XML.
+/// Nested levels follow their parent item as `<outline>` siblings.
+fn parse_pdftohtml_outline(xml: &str) -> Result<Vec<OutlineEntry>> {
+    let (Some(start), Some(end)) = (xml.find("<outline>"), xml.rfind("</outline>")) else {
+        return Ok(Vec::new());
+    };
+    let outline = roxmltree::Document::parse(&xml[start..end + "</outline>".len()])
+        .context("parsing the pdftohtml outline")?;
+    Ok(outline
+        .root_element()
+        .children()
+        .filter(|node| node.has_tag_name("item"))
+        .filter_map(|item| {
+            let page = item.attribute("page")?.parse::<u32>().ok()?;
+            let title = item.text().unwrap_or_default();
+            Some(OutlineEntry {
+                title: title.split_whitespace().collect::<Vec<_>>().join(" "),
+                page,
+            })
+        })
+        .collect())
+}
+
+/// Page ranges between consecutive bookmarks, the last running to `page_total`.
+/// Pages before the first bookmark become a "Front matter" section, and bookmarks
+/// sharing a page with an earlier one are folded into it.
+pub fn outline_sections(entries: &[OutlineEntry], page_total: u32) -> Vec<OutlineSection> {
+    let mut starts: Vec<(u32, String)> = Vec::new();
+    let mut sorted: Vec<&OutlineEntry> = entries
+        .iter()
+        .filter(|entry| (1..=page_total).contains(&entry.page))
+        .collect();
+    sorted.sort_by_key(|entry| entry.page);
+    for entry in sorted {
+        if starts.last().is_some_and(|(page, _)| *page == entry.page) {
+            continue;
+        }
+        let title = match entry.title.trim() {
+            "" => format!("Page {}", entry.page),
+            title => title.to_string(),
+        };
+        starts.push((entry.page, title));
+    }
+    if starts.first().is_some_and(|(page, _)| *page > 1) {
+        starts.insert(0, (1, "Front matter".into()));
+    }
+    let ends: Vec<u32> = starts
+        .iter()
+        .skip(1)
+        .map(|(page, _)| page - 1)
+        .chain(std::iter::once(page_total))
+        .collect();
+    starts
+        .into_iter()
+        .zip(ends)
+        .map(|((start_page, title), end_page)| OutlineSection {
+            title,
+            start_page,
+            end_page,
+            part: 1,
+            parts: 1,
+        })
+        .collect()
+}
+
+/// Sections longer than `max_pages` cut into even runs of at most that many pages,
+/// each keeping its section's title.
+pub fn split_long_sections(sections: Vec<OutlineSection>, max_pages: u32) -> Vec<OutlineSection> {
+    if max_pages == 0 {
+        return sections;
+    }
+    sections
+        .into_iter()
+        .flat_map(|section| {
+            let pages = section.end_page - section.start_page + 1;
+            let parts = pages.div_ceil(max_pages);
+            (0..parts).map(move |index| {
+                let start_page = section.start_page + index * max_pages;
+                OutlineSection {
+                    title: section.title.clone(),
+                    start_page,
+                    end_page: (start_page + max_pages - 1).min(section.end_page),
+                    part: index + 1,
+                    parts,
+                }
+            })
+        })
+        .collect()
+}
+
 fn parse_pdftoppm_page_number(path: &Path) -> Option<u32> {
     let stem = path.file_stem()?.to_string_lossy();
     let (_, suffix) = stem.rsplit_once('-')?;
     suffix.parse::<u32>().ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_level_bookmarks_become_page_ranges() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE pdf2xml SYSTEM "pdf2xml.dtd">
+<pdf2xml producer="poppler" version="23.08.0">
+<page number="1" position="absolute" top="0" left="0" height="1188" width="918">
+</page>
+<outline>
+<item page="5">Chapter 1: Limits</item>
+<outline>
+<item page="6">1.1 Definitions</item>
+</outline>
+<item page="40">Chapter 2: Derivatives &amp;
+ Rates</item>
+<item>Unresolved</item>
+<item page="40">Chapter 2 again</item>
+<item page="90">Index</item>
+</outline>
+</pdf2xml>
+"#;
+        let entries = parse_pdftohtml_outline(xml).unwrap();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[1].title, "Chapter 2: Derivatives & Rates");
+
+        let sections = outline_sections(&entries, 95);
+        let ranges: Vec<(&str, u32, u32)> = sections
+            .iter()
+            .map(|s| (s.title.as_str(), s.start_page, s.end_page))
+            .collect();
+        assert_eq!(
+            ranges,
+            [
+                ("Front matter", 1, 4),
+                ("Chapter 1: Limits", 5, 39),
+                ("Chapter 2: Derivatives & Rates", 40, 89),
+                ("Index", 90, 95),
+            ]
+        );
+        let parts: Vec<(u32, u32, u32, u32)> = split_long_sections(sections, 20)
+            .iter()
+            .filter(|s| s.title == "Chapter 2: Derivatives & Rates")
+            .map(|s| (s.start_page, s.end_page, s.part, s.parts))
+            .collect();
+        assert_eq!(parts, [(40, 59, 1, 3), (60, 79, 2, 3), (80, 89, 3, 3)]);
+        assert!(parse_pdftohtml_outline("<pdf2xml></pdf2xml>")
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,182 @@
+//! Reading PDFs in-process with PDFium (`pdf.renderer: pdfium`) through
+//! `pdfium-render`, bound at run time to the shared library so there is no
+//! build-time dependency on it: page counts, bookmarks, rendering, page copies,
+//! and text.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, Context, Result};
+use pdfium_render::prelude::*;
+
+use crate::pdf::OutlineEntry;
+
+static PDFIUM: OnceLock<std::result::Result<Pdfium, String>> = OnceLock::new();
+
+/// The library, bound on first use. `pdfium-render` serializes calls into it.
+fn pdfium() -> Result<&'static Pdfium> {
+    PDFIUM
+        .get_or_init(|| {
+            let path = crate::tools::pdfium_library().map_err(|err| format!("{err:#}"))?;
+            let bindings = Pdfium::bind_to_library(&path).map_err(|err| {
+                format!(
+                    "loading PDFium from {}: {}; set tools.pdfium to libpdfium",
+                    path.display(),
+                    describe_error(&err)
+                )
+            })?;
+            Ok(Pdfium::new(bindings))
+        })
+        .as_ref()
+        .map_err(|err| anyhow!("{err}"))
+}
+
+/// What went wrong, worded for someone who only has the file.
+fn describe_error(err: &PdfiumError) -> String {
+    match err {
+        PdfiumError::PdfiumLibraryInternalError(internal) => match internal {
+            PdfiumInternalError::FileError => "the file could not be opened".into(),
+            PdfiumInternalError::FormatError => "it is not a PDF or is damaged".into(),
+            PdfiumInternalError::PasswordError => "it is password-protected".into(),
+            PdfiumInternalError::SecurityError => "it uses an unsupported security handler".into(),
+            PdfiumInternalError::PageError => "a page could not be read".into(),
+            PdfiumInternalError::Unknown => "PDFium reported an unknown error".into(),
+        },
+        PdfiumError::LoadLibraryError(err) => err.to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+fn open<'a>(pdfium: &'a Pdfium, pdf: &Path) -> Result<PdfDocument<'a>> {
+    pdfium
+        .load_pdf_from_file(pdf, None)
+        .map_err(|err| anyhow!("cannot open {}: {}", pdf.display(), describe_error(&err)))
+}
+
+pub(crate) fn page_count(pdf: &Path) -> Result<u32> {
+    let document = open(pdfium()?, pdf)?;
+    let count = document.pages().len() as u32;
+    Ok(count)
+}
+
+/// Top-level bookmarks that lead to a page, through a destination or a GoTo action.
+pub(crate) fn outline(pdf: &Path) -> Result<Vec<OutlineEntry>> {
+    let document = open(pdfium()?, pdf)?;
+    let bookmarks = document.bookmarks();
+    let Some(first) = bookmarks.root() else {
+        return Ok(Vec::new());
+    };
+    let entries = first
+        .iter_siblings()
+        .filter_map(|bookmark| {
+            let index = match bookmark.destination() {
+                Some(destination) => destination.page_index().ok()?,
+                None => bookmark
+                    .action()?
+                    .as_local_destination_action()?
+                    .destination()
+                    .ok()?
+                    .page_index()
+                    .ok()?,
+            };
+            Some(OutlineEntry {
+                title: bookmark.title().unwrap_or_default(),
+                page: index as u32 + 1,
+            })
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// Render `pages` (1-based, or every page when `None`) of `pdf` at `dpi` to
+/// `<prefix>-<N>.png` in `out_dir`, named like pdftoppm's output. `on_page`
+/// hears each page as it is written.
+pub(crate) fn render_pages(
+    pdf: &Path,
+    out_dir: &Path,
+    prefix: &str,
+    dpi: u32,
+    pages: Option<Vec<u32>>,
+    on_page: &mut dyn FnMut(usize, usize),
+) -> Result<Vec<(u32, PathBuf)>> {
+    let document = open(pdfium()?, pdf)?;
+    let total = document.pages().len() as u32;
+    let pages = pages.unwrap_or_else(|| (1..=total).collect());
+    let digits = total.max(1).to_string().len();
+    // Annotations such as form fields and highlights are drawn, as a viewer would.
+    let config = PdfRenderConfig::new()
+        .scale_page_by_factor(dpi as f32 / 72.0)
+        .render_form_data(true)
+        .render_annotations(true);
+    let mut written = Vec::with_capacity(pages.len());
+    for (done, page_number) in pages.iter().copied().enumerate() {
+        let image = document
+            .pages()
+            .get((page_number - 1) as PdfPageIndex)
+            .and_then(|page| Ok(page.render_with_config(&config)?.as_image().into_rgb8()))
+            .map_err(|err| {
+                anyhow!(
+                    "rendering page {page_number} of {}: {}",
+                    pdf.display(),
+                    describe_error(&err)
+                )
+            })?;
+        let path = out_dir.join(format!("{prefix}-{page_number:0digits$}.png"));
+        image
+            .save(&path)
+            .with_context(|| format!("writing {}", path.display()))?;
+        written.push((page_number, path));
+        on_page(done + 1, pages.len());
+    }
+    Ok(written)
+}
+
+/// Write the pages in `ranges` (1-based, inclusive) of `pdf` to a new PDF at
+/// `dest`; returns the number of pages written.
+pub(crate) fn extract_pages(pdf: &Path, ranges: &[(u32, u32)], dest: &Path) -> Result<u32> {
+    let pdfium = pdfium()?;
+    let source = open(pdfium, pdf)?;
+    let mut copy = pdfium
+        .create_new_pdf()
+        .map_err(|err| anyhow!("creating {}: {}", dest.display(), describe_error(&err)))?;
+    for (start, end) in ranges {
+        let at = copy.pages().len();
+        copy.pages_mut()
+            .copy_page_range_from_document(
+                &source,
+                (*start - 1) as PdfPageIndex..=(*end - 1) as PdfPageIndex,
+                at,
+            )
+            .map_err(|err| {
+                anyhow!(
+                    "copying pages {start}-{end} of {}: {}",
+                    pdf.display(),
+                    describe_error(&err)
+                )
+            })?;
+    }
+    copy.save_to_file(dest)
+        .map_err(|err| anyhow!("writing {}: {}", dest.display(), describe_error(&err)))?;
+    Ok(copy.pages().len() as u32)
+}
+
+/// The text layer of each page in `ranges` (1-based, inclusive), by page number.
+pub(crate) fn page_texts(pdf: &Path, ranges: &[(u32, u32)]) -> Result<Vec<(u32, String)>> {
+    let document = open(pdfium()?, pdf)?;
+    let pages = document.pages();
+    let mut texts = Vec::new();
+    for page_number in ranges.iter().flat_map(|(start, end)| *start..=*end) {
+        let text = pages
+            .get((page_number - 1) as PdfPageIndex)
+            .and_then(|page| Ok(page.text()?.all()))
+            .map_err(|err| {
+                anyhow!(
+                    "reading the text of page {page_number} of {}: {}",
+                    pdf.display(),
+                    describe_error(&err)
+                )
+            })?;
+        texts.push((page_number, text));
+    }
+    Ok(texts)
+}
@@ -1,6 +1,13 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::watch;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum ProgressScope {
     Run,
     Job {
@@ -16,17 +23,30 @@ pub enum ProgressScope {
         index: u64,
         total: u64,
     },
+    /// A source being downloaded; `current` and `total` count bytes.
+    Download {
+        job_id: String,
+        name: String,
+    },
+    /// An ffmpeg pass over a recording, where `current` and `total` count
+    /// milliseconds of media, or a PDF being rendered, where they count pages.
+    Encode {
+        job_id: String,
+        name: String,
+    },
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ProgressStage {
+    Download,
     Discover,
     Normalize,
     Transcribe,
     Write,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Progress {
     pub scope: ProgressScope,
     pub stage: ProgressStage,
@@ -39,6 +59,7 @@ pub struct Progress {
 impl ProgressStage {
     pub fn label(&self) -> &'static str {
         match self {
+            ProgressStage::Download => "download",
             ProgressStage::Discover => "discover",
             ProgressStage::Normalize => "normalize",
             ProgressStage::Transcribe => "transcribe",
@@ -56,6 +77,358 @@ impl fmt::Display for ProgressScope {
             ProgressScope::ChunkDetail { index, total, .. } => {
                 write!(f, "chunk {} / {}", index + 1, total)
             }
+            ProgressScope::Download { name, .. } | ProgressScope::Encode { name, .. } => {
+                write!(f, "{name}")
+            }
+        }
+    }
+}
+
+/// Pause and skip requests from the dashboard, shared with the provider, which
+/// checks them before and while each chunk request runs. Clones share state.
+#[derive(Clone)]
+pub struct ChunkControl {
+    state: Arc<watch::Sender<ControlState>>,
+}
+
+#[derive(Debug, Default)]
+struct ControlState {
+    paused: bool,
+    /// `(job_id, chunk_index)` of chunks to leave out.
+    skipped: HashSet<(String, u64)>,
+}
+
+impl ControlState {
+    fn is_skipped(&self, job_id: &str, index: u64) -> bool {
+        self.skipped.contains(&(job_id.to_string(), index))
+    }
+}
+
+impl Default for ChunkControl {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(watch::channel(ControlState::default()).0),
         }
     }
 }
+
+impl ChunkControl {
+    /// Stop starting chunk requests, or resume; returns whether requests are now paused.
+    /// Requests already sent are not interrupted.
+    pub fn toggle_pause(&self) -> bool {
+        let mut paused = false;
+        self.state.send_modify(|state| {
+            state.paused = !state.paused;
+            paused = state.paused;
+        });
+        paused
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.borrow().paused
+    }
+
+    /// Leave chunk `index` of `job_id` out of the transcript, abandoning its request
+    /// if it is already running.
+    pub fn skip(&self, job_id: &str, index: u64) {
+        self.state.send_modify(|state| {
+            state.skipped.insert((job_id.to_string(), index));
+        });
+    }
+
+    pub fn is_skipped(&self, job_id: &str, index: u64) -> bool {
+        self.state.borrow().is_skipped(job_id, index)
+    }
+
+    /// Resolves once the chunk may start: at once unless paused, and as soon as it
+    /// is skipped.
+    pub async fn ready(&self, job_id: &str, index: u64) {
+        let mut changes = self.state.subscribe();
+        let _ = changes
+            .wait_for(|state| !state.paused || state.is_skipped(job_id, index))
+            .await;
+    }
+
+    /// Resolves when the chunk is skipped.
+    pub async fn skipped(&self, job_id: &str, index: u64) {
+        let mut changes = self.state.subscribe();
+        let _ = changes
+            .wait_for(|state| state.is_skipped(job_id, index))
+            .await;
+    }
+}
+
+/// Reports one download as a `Download` progress row, a few times a second at most.
+pub struct DownloadMeter {
+    tx: Option<UnboundedSender<Progress>>,
+    scope: ProgressScope,
+    started: Instant,
+    /// Bytes already on disk from an earlier attempt, left out of the speed.
+    resumed: u64,
+    last_sent: Option<Instant>,
+}
+
+impl DownloadMeter {
+    pub fn new(tx: Option<UnboundedSender<Progress>>, job_id: &str, name: &str) -> Self {
+        Self {
+            tx,
+            scope: ProgressScope::Download {
+                job_id: job_id.to_string(),
+                name: name.to_string(),
+            },
+            started: Instant::now(),
+            resumed: 0,
+            last_sent: None,
+        }
+    }
+
+    /// Note that the download continues a partial file of `bytes`.
+    pub fn resume_from(&mut self, bytes: u64) {
+        self.resumed = bytes;
+        self.started = Instant::now();
+    }
+
+    pub fn update(&mut self, done: u64, total: Option<u64>) {
+        let now = Instant::now();
+        if self
+            .last_sent
+            .is_some_and(|sent| now.duration_since(sent) < Duration::from_millis(250))
+        {
+            return;
+        }
+        self.last_sent = Some(now);
+        self.send(done, total, false);
+    }
+
+    pub fn finish(&mut self, done: u64) {
+        self.send(done, Some(done), true);
+    }
+
+    fn send(&self, done: u64, total: Option<u64>, finished: bool) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+        let megabytes = |bytes: u64| bytes as f64 / 1_000_000.0;
+        let seconds = self.started.elapsed().as_secs_f64().max(0.001);
+        let speed = megabytes(done.saturating_sub(self.resumed)) / seconds;
+        let mut status = match total {
+            Some(total) => format!("{:.1}/{:.1} MB", megabytes(done), megabytes(total)),
+            None => format!("{:.1} MB", megabytes(done)),
+        };
+        status.push_str(&format!(" at {speed:.1} MB/s"));
+        if let Some(total) = total.filter(|_| !finished && speed > 0.0) {
+            let left = megabytes(total.saturating_sub(done)) / speed;
+            status.push_str(&format!(", eta {}", format_eta(left.round() as u64)));
+        }
+        if self.resumed > 0 {
+            status.push_str(&format!(" (resumed at {:.1} MB)", megabytes(self.resumed)));
+        }
+        let _ = tx.send(Progress {
+            scope: self.scope.clone(),
+            stage: ProgressStage::Download,
+            current: done,
+            total: total.unwrap_or(done).max(done).max(1),
+            status,
+            finished,
+        });
+    }
+}
+
+/// Reports one ffmpeg pass as a Normalize-stage `Encode` row, a few times a
+/// second at most, with the encode speed and time left.
+pub struct EncodeMeter {
+    tx: Option<UnboundedSender<Progress>>,
+    scope: ProgressScope,
+    started: Instant,
+    last_sent: Option<Instant>,
+    last_done: f64,
+    total: f64,
+}
+
+impl EncodeMeter {
+    pub fn new(tx: Option<UnboundedSender<Progress>>, job_id: &str, name: &str) -> Self {
+        Self {
+            tx,
+            scope: ProgressScope::Encode {
+                job_id: job_id.to_string(),
+                name: name.to_string(),
+            },
+            started: Instant::now(),
+            last_sent: None,
+            last_done: 0.0,
+            total: 0.0,
+        }
+    }
+
+    /// `done` of `total` seconds of media processed, at `fps` frames a second
+    /// when ffmpeg reports it.
+    pub fn update(&mut self, done: f64, total: f64, fps: Option<f64>) {
+        let now = Instant::now();
+        // A second pass over the same file starts again from zero.
+        if done < self.last_done {
+            self.started = now;
+        }
+        self.last_done = done;
+        self.total = total;
+        if self
+            .last_sent
+            .is_some_and(|sent| now.duration_since(sent) < Duration::from_millis(250))
+        {
+            return;
+        }
+        self.last_sent = Some(now);
+        self.send(done, total, fps, false);
+    }
+
+    /// Close the row, if ffmpeg reported anything; cached outputs never do.
+    pub fn finish(&mut self) {
+        if self.last_sent.is_some() {
+            self.send(self.total, self.total, None, true);
+        }
+    }
+
+    fn send(&self, done: f64, total: f64, fps: Option<f64>, finished: bool) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+        let elapsed = self.started.elapsed().as_secs_f64().max(0.001);
+        let speed = done / elapsed;
+        let mut status = format!(
+            "{}/{}",
+            format_eta(done.round() as u64),
+            format_eta(total.round() as u64)
+        );
+        if let Some(fps) = fps.filter(|fps| *fps > 0.0) {
+            status.push_str(&format!(" at {fps:.0} fps"));
+        }
+        if !finished && speed > 0.0 {
+            status.push_str(&format!(" ({speed:.1}x)"));
+            if total > done {
+                let left = (total - done) / speed;
+                status.push_str(&format!(", eta {}", format_eta(left.round() as u64)));
+            }
+        }
+        let millis = |seconds: f64| (seconds.max(0.0) * 1000.0) as u64;
+        let _ = tx.send(Progress {
+            scope: self.scope.clone(),
+            stage: ProgressStage::Normalize,
+            current: millis(done),
+            total: millis(total).max(millis(done)).max(1),
+            status,
+            finished,
+        });
+    }
+}
+
+/// How far back [`Throughput`] looks when working out a rate, so the ETA follows
+/// the current pace rather than the run's average.
+const RATE_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// Completion rate of a progress row and the time left at that rate.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Rate {
+    pub per_minute: f64,
+    /// What the row counts: `jobs` or `chunks`.
+    pub unit: &'static str,
+    pub eta_seconds: u64,
+}
+
+/// Rolling throughput per scope, fed every [`Progress`] event by the TUI and
+/// `--progress json`. Only rows that count whole jobs or chunks get a rate;
+/// job and chunk detail rows step through stages.
+#[derive(Debug, Default)]
+pub struct Throughput {
+    samples: HashMap<ProgressScope, VecDeque<(Instant, u64)>>,
+}
+
+impl Throughput {
+    pub fn record(&mut self, progress: &Progress) -> Option<Rate> {
+        self.record_at(progress, Instant::now())
+    }
+
+    fn record_at(&mut self, progress: &Progress, now: Instant) -> Option<Rate> {
+        let unit = match progress.scope {
+            ProgressScope::Run => "jobs",
+            ProgressScope::ChunkProgress { .. } => "chunks",
+            _ => return None,
+        };
+        if progress.finished {
+            self.samples.remove(&progress.scope);
+            return None;
+        }
+        let samples = self.samples.entry(progress.scope.clone()).or_default();
+        if samples
+            .back()
+            .is_none_or(|&(_, seen)| seen != progress.current)
+        {
+            samples.push_back((now, progress.current));
+        }
+        while samples.len() > 2 && now.duration_since(samples[0].0) > RATE_WINDOW {
+            samples.pop_front();
+        }
+        let &(since, first) = samples.front()?;
+        let done = progress
+            .current
+            .checked_sub(first)
+            .filter(|&done| done > 0)?;
+        // Measured up to now rather than the last completion, so a stall slows
+        // the rate and pushes the ETA out.
+        let minutes = now.duration_since(since).as_secs_f64() / 60.0;
+        if minutes <= 0.0 {
+            return None;
+        }
+        let per_minute = done as f64 / minutes;
+        let left = progress.total.saturating_sub(progress.current) as f64;
+        Some(Rate {
+            per_minute,
+            unit,
+            eta_seconds: (left / per_minute * 60.0).round() as u64,
+        })
+    }
+}
+
+/// `h:mm:ss`, or `m:ss` under an hour.
+pub fn format_eta(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throughput_projects_the_remaining_chunks() {
+        let event = |current| Progress {
+            scope: ProgressScope::ChunkProgress {
+                job_id: "talk".into(),
+                total: 10,
+            },
+            stage: ProgressStage::Transcribe,
+            current,
+            total: 10,
+            status: String::new(),
+            finished: false,
+        };
+        let mut throughput = Throughput::default();
+        let start = Instant::now();
+        assert_eq!(throughput.record_at(&event(2), start), None);
+        let rate = throughput
+            .record_at(&event(4), start + Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(rate.per_minute, 2.0);
+        assert_eq!(rate.unit, "chunks");
+        assert_eq!(rate.eta_seconds, 180);
+        // Nothing finished in the next minute: the rate halves.
+        let stalled = throughput
+            .record_at(&event(4), start + Duration::from_secs(120))
+            .unwrap();
+        assert_eq!(stalled.eta_seconds, 360);
+        assert_eq!(format_eta(3725), "1:02:05");
+        assert_eq!(format_eta(65), "1:05");
+    }
+}
@@ -24,6 +24,26 @@ impl TemplatePromptStrategy {
             (Kind::Document, OutputFormat::Latex) => "{{PREAMBLE}}\nTranscribe the document content verbatim into LaTeX, keeping the original structure, math environments, and tables exactly as given.",
             (Kind::Image, OutputFormat::Latex) => "{{PREAMBLE}}\nTranscribe the image content into LaTeX. Reproduce text in order, render math with LaTeX notation, and annotate unreadable pieces as [illegible].",
             (Kind::Video, OutputFormat::Latex) => "{{PREAMBLE}}\nProduce a LaTeX transcript with a single Transcript section. Use [MM:SS] timestamps for entries, include brief inline speaker notes and key visual descriptions when important, and do not add any non-transcript sections (no timeline, key terms, summary, or analysis).",
+            (Kind::Audio, OutputFormat::Markdown) => "{{PREAMBLE}}\nProduce a Markdown transcript of the audio with a single 'Transcript' section. Use [MM:SS] timestamps for entries, label speaker changes when they are clear, mark inaudible passages as [inaudible], and do not add any non-transcript sections (no summary, key terms, or analysis).",
+            (Kind::Audio, OutputFormat::Latex) => "{{PREAMBLE}}\nProduce a LaTeX transcript of the audio with a single Transcript section. Use [MM:SS] timestamps for entries, label speaker changes when they are clear, mark inaudible passages as [inaudible], and do not add any non-transcript sections (no summary, key terms, or analysis).",
+            (Kind::Slides, OutputFormat::Org) => "{{PREAMBLE}}\nTranscribe each slide faithfully in Emacs Org mode. Use * headings for slide titles when they exist, preserve bullet hierarchies with - items, use Org tables, and keep equations as \\(...\\) or \\[...\\].",
+            (Kind::Lecture, OutputFormat::Org) => "{{PREAMBLE}}\nTranscribe the lecture notes verbatim in Emacs Org mode. Preserve the original order, using * headings, - lists, Org tables, and \\(...\\) or \\[...\\] math, adding timestamps only when present in the source.",
+            (Kind::Document, OutputFormat::Org) => "{{PREAMBLE}}\nTranscribe the document faithfully in Emacs Org mode. Reproduce headings with *, lists, Org tables, and \\(...\\) or \\[...\\] math exactly as they appear without adding extra commentary or structure.",
+            (Kind::Image, OutputFormat::Org) => "{{PREAMBLE}}\nTranscribe text from the image into Emacs Org mode. Keep source ordering, mark unreadable regions as [illegible], and use \\(...\\) or \\[...\\] for math.",
+            (Kind::Video, OutputFormat::Org) => "{{PREAMBLE}}\nProduce an Emacs Org mode transcript with a single '* Transcript' heading. Use [MM:SS] timestamps for entries, include brief inline speaker notes and key visual descriptions when important, and do not add any non-transcript sections (no timeline, key terms, summary, or analysis).",
+            (Kind::Audio, OutputFormat::Org) => "{{PREAMBLE}}\nProduce an Emacs Org mode transcript of the audio with a single '* Transcript' heading. Use [MM:SS] timestamps for entries, label speaker changes when they are clear, mark inaudible passages as [inaudible], and do not add any non-transcript sections (no summary, key terms, or analysis).",
+            (Kind::Slides, OutputFormat::Rst) => "{{PREAMBLE}}\nTranscribe each slide faithfully in reStructuredText. Underline slide titles with - when they exist, preserve bullet hierarchies (blank line before nested lists), use list-table or grid tables, and write math with the :math:`...` role or the .. math:: directive.",
+            (Kind::Lecture, OutputFormat::Rst) => "{{PREAMBLE}}\nTranscribe the lecture notes verbatim in reStructuredText. Preserve the original order, section titles with consistent underlines, lists, tables, and math via :math:`...` or .. math::, adding timestamps only when present in the source.",
+            (Kind::Document, OutputFormat::Rst) => "{{PREAMBLE}}\nTranscribe the document faithfully in reStructuredText. Reproduce section titles with consistent underlines, lists, tables, and math via :math:`...` or .. math:: exactly as they appear without adding extra commentary or structure.",
+            (Kind::Image, OutputFormat::Rst) => "{{PREAMBLE}}\nTranscribe text from the image into reStructuredText. Keep source ordering, mark unreadable regions as [illegible], and use :math:`...` or .. math:: for math.",
+            (Kind::Video, OutputFormat::Rst) => "{{PREAMBLE}}\nProduce a reStructuredText transcript with a single 'Transcript' section. Use [MM:SS] timestamps for entries as separate paragraphs, include brief inline speaker notes and key visual descriptions when important, and do not add any non-transcript sections (no timeline, key terms, summary, or analysis).",
+            (Kind::Audio, OutputFormat::Rst) => "{{PREAMBLE}}\nProduce a reStructuredText transcript of the audio with a single 'Transcript' section. Use [MM:SS] timestamps for entries as separate paragraphs, label speaker changes when they are clear, mark inaudible passages as [inaudible], and do not add any non-transcript sections (no summary, key terms, or analysis).",
+            (Kind::Slides, OutputFormat::Json) => "{{PREAMBLE}}\nTranscribe the slides into a single JSON object matching this schema:\n{{SCHEMA}}\nUse one section per slide with the slide title as the heading and null timestamps. Collect defined terms into key_terms and displayed equations into equations. Return only JSON.",
+            (Kind::Lecture, OutputFormat::Json) => "{{PREAMBLE}}\nTranscribe the lecture into a single JSON object matching this schema:\n{{SCHEMA}}\nFollow the lecture's own structure for sections, filling start/end timestamps when the source is timed. Collect defined terms into key_terms and important equations into equations. Return only JSON.",
+            (Kind::Document, OutputFormat::Json) => "{{PREAMBLE}}\nTranscribe the document into a single JSON object matching this schema:\n{{SCHEMA}}\nMirror the document's headings as sections with null timestamps, keeping content verbatim. Collect defined terms into key_terms and displayed equations into equations. Return only JSON.",
+            (Kind::Image, OutputFormat::Json) => "{{PREAMBLE}}\nTranscribe the image into a single JSON object matching this schema:\n{{SCHEMA}}\nUse sections for visually distinct regions in reading order with null timestamps, and mark unreadable regions as [illegible]. Return only JSON.",
+            (Kind::Video, OutputFormat::Json) => "{{PREAMBLE}}\nTranscribe the video into a single JSON object matching this schema:\n{{SCHEMA}}\nSplit the transcript into topical sections with [MM:SS]-style start and end timestamps, include brief speaker notes and key visual descriptions in content, and collect key terms and equations that appear. Return only JSON.",
+            (Kind::Audio, OutputFormat::Json) => "{{PREAMBLE}}\nTranscribe the audio into a single JSON object matching this schema:\n{{SCHEMA}}\nSplit the transcript into topical sections with start and end timestamps, label speaker changes when clear, mark inaudible passages as [inaudible], and collect key terms that are defined. Return only JSON.",
         }
     }
 }
@@ -34,8 +54,19 @@ impl PromptStrategy for TemplatePromptStrategy {
     }
 
     fn instruction(&self, format: OutputFormat, preamble: &str) -> String {
-        self.loader
-            .prompt(self.kind, format, self.default_prompt(format))
-            .replace("{{PREAMBLE}}", preamble)
+        render_instruction(
+            &self
+                .loader
+                .prompt(self.kind, format, self.default_prompt(format)),
+            preamble,
+        )
     }
 }
+
+/// Fill the placeholders of an instruction template, whether it came from
+/// `templates_dir`, the built-in defaults, or `--prompt`.
+pub fn render_instruction(template: &str, preamble: &str) -> String {
+    template
+        .replace("{{PREAMBLE}}", preamble)
+        .replace("{{SCHEMA}}", crate::render::structured::STRUCTURED_SCHEMA)
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime};
+
+use crate::utils::ensure_dir;
+
+/// Entries this close to expiry are treated as gone, so a request never cites a
+/// cache that expires before the model reads it.
+const EXPIRY_MARGIN: Duration = Duration::minutes(2);
+
+/// Gemini context caching (`context_cache` in `recapit.yaml`): a large prompt
+/// prefix is stored once with `cachedContents` and later requests cite it, paying
+/// the cache-read rate for those tokens instead of the input rate.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ContextCacheConfig {
+    pub enabled: bool,
+    /// How long a cache lives after it is created. Storage is billed per hour.
+    pub ttl_minutes: u64,
+    /// Prompts estimated below this many tokens are sent inline; the API has a
+    /// minimum, and small caches cost more to store than they save.
+    pub min_tokens: u64,
+}
+
+impl Default for ContextCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_minutes: 60,
+            min_tokens: 4_096,
+        }
+    }
+}
+
+/// Caches created by earlier runs, keyed by model and content hash, so repeated
+/// prompts over the same text reuse one cache until it expires.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ContextCacheIndex {
+    #[serde(default)]
+    entries: BTreeMap<String, CachedContext>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedContext {
+    /// `cachedContents/...`
+    pub name: String,
+    pub model: String,
+    /// The cache's `expireTime`, RFC 3339.
+    pub expires_at: Option<String>,
+    /// Tokens stored in the cache, as counted by the API.
+    pub tokens: Option<u64>,
+}
+
+impl CachedContext {
+    fn is_live(&self, now: OffsetDateTime) -> bool {
+        self.expires_at
+            .as_deref()
+            .and_then(|value| OffsetDateTime::parse(value, &Rfc3339).ok())
+            .is_some_and(|expires| expires - EXPIRY_MARGIN > now)
+    }
+}
+
+fn index_path() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("recapit")
+        .join("context-caches.json")
+}
+
+/// The index key for `text` cached for `model`.
+pub fn cache_key(model: &str, text: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(model.as_bytes());
+    hasher.update([0]);
+    hasher.update(text.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+impl ContextCacheIndex {
+    pub fn load() -> Self {
+        fs::read_to_string(index_path())
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = index_path();
+        ensure_dir(path.parent().unwrap())?;
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// A remembered cache that has not yet expired.
+    pub fn lookup(&self, key: &str) -> Option<&CachedContext> {
+        self.entries
+            .get(key)
+            .filter(|cache| cache.is_live(OffsetDateTime::now_utc()))
+    }
+
+    /// Remember a cache, dropping any entries that have since expired.
+    pub fn record(key: &str, cache: CachedContext) -> Result<()> {
+        let mut index = Self::load();
+        let now = OffsetDateTime::now_utc();
+        index.entries.retain(|_, existing| existing.is_live(now));
+        index.entries.insert(key.to_string(), cache);
+        index.save()
+    }
+
+    /// Drop an entry the API no longer serves.
+    pub fn forget(key: &str) -> Result<()> {
+        let mut index = Self::load();
+        if index.entries.remove(key).is_some() {
+            index.save()?;
+        }
+        Ok(())
+    }
+}
+
+/// Store `text` as a user turn in a new `cachedContents` entry for `model`.
+pub async fn create_cached_content(
+    http: &Client,
+    api_key: &str,
+    model: &str,
+    text: &str,
+    ttl: std::time::Duration,
+    display_name: &str,
+) -> Result<CachedContext> {
+    let body = json!({
+        "model": format!("models/{model}"),
+        "displayName": display_name,
+        "contents": [{"role": "user", "parts": [{"text": text}]}],
+        "ttl": format!("{}s", ttl.as_secs().max(60)),
+    });
+    let response = http
+        .post("https://generativelanguage.googleapis.com/v1beta/cachedContents")
+        .query(&[("key", api_key)])
+        .json(&body)
+        .send()
+        .await
+        .context("calling cachedContents.create")?;
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        return Err(anyhow!(
+            "cachedContents.create failed with status {status}: {text}"
+        ));
+    }
+    let payload: Value = response
+        .json()
+        .await
+        .context("parsing cachedContents response")?;
+    let name = payload
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("cachedContents response missing name"))?;
+    Ok(CachedContext {
+        name: name.to_string(),
+        model: model.to_string(),
+        expires_at: payload
+            .get("expireTime")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        tokens: payload
+            .pointer("/usageMetadata/totalTokenCount")
+            .and_then(Value::as_u64),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caches_expire_with_a_margin() {
+        let now = OffsetDateTime::now_utc();
+        let cache = |expires: OffsetDateTime| CachedContext {
+            name: "cachedContents/abc".into(),
+            model: "gemini-2.5-flash".into(),
+            expires_at: Some(expires.format(&Rfc3339).unwrap()),
+            tokens: None,
+        };
+        assert!(cache(now + Duration::minutes(30)).is_live(now));
+        assert!(!cache(now + Duration::minutes(1)).is_live(now));
+        assert_ne!(
+            cache_key("gemini-2.5-flash", "text"),
+            cache_key("gemini-2.5-pro", "text")
+        );
+    }
+}
@@ -2,43 +2,117 @@ use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
-use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use std::io::ErrorKind;
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
+use futures::stream::{self, StreamExt, TryStreamExt};
 use rand::Rng;
-use rayon::prelude::*;
-use rayon::ThreadPoolBuilder;
-use reqwest::blocking::Client;
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use reqwest::Client;
 use reqwest::StatusCode;
 use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
 use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
-use crate::core::{Asset, Provider, SourceKind};
-use crate::progress::{Progress, ProgressScope, ProgressStage};
+use crate::core::{Asset, Cancelled, Provider, SourceKind};
+use crate::cost::{BudgetExceeded, CostBudget};
+use crate::progress::{ChunkControl, Progress, ProgressScope, ProgressStage};
 use crate::telemetry::{RequestEvent, RunMonitor};
 use crate::utils::ensure_dir;
 
+use super::keys::{references_uploads, ApiKeys};
+use super::replay::{Recording, ReplayMode};
+use super::uploads::{UploadIndex, UploadRecord};
+use super::{
+    block_reason, finish_reason, push_continuation, response_text, stitch_continuation,
+    GenerationConfig, ResponseSchema, SafetyConfig, TokenUsage, MAX_CONTINUATIONS,
+};
+use crate::render::structured::StructuredTranscript;
+
 const INLINE_THRESHOLD_BYTES: usize = 20 * 1024 * 1024;
 const MAX_RETRIES: usize = 3;
 const BACKOFF_BASE_SECONDS: f64 = 1.0;
 const BACKOFF_CAP_SECONDS: f64 = 8.0;
+/// Piece size for resumable uploads; non-final pieces must be multiples of 256 KiB.
+const UPLOAD_CHUNK_BYTES: usize = 8 * 1024 * 1024;
+/// Longest server-requested pause we honour before retrying anyway.
+const MAX_SERVER_DELAY: Duration = Duration::from_secs(300);
+/// How many `files.list` pages to scan for an earlier upload of the same bytes.
+const MAX_LISTED_PAGES: usize = 10;
 
 pub struct GeminiProvider {
-    api_key: String,
+    keys: ApiKeys,
     model: String,
     http: Client,
     monitor: RunMonitor,
     progress: Option<tokio::sync::mpsc::UnboundedSender<Progress>>,
     upload_cache: Mutex<HashMap<String, CachedUpload>>,
     cleanup: Mutex<HashSet<String>>,
+    keep_uploads: bool,
     quota: Option<crate::quota::QuotaMonitor>,
+    budget: Option<CostBudget>,
+    generation: GenerationConfig,
+    safety: SafetyConfig,
+    count_tokens: bool,
+    fallbacks: Vec<String>,
+    cancel: CancellationToken,
+    control: ChunkControl,
+    recording: Option<Recording>,
+}
+
+/// One answered request.
+struct Generated {
+    text: String,
+    /// Per-asset metadata, such as the upload's `file_uri`.
+    assets: Vec<Map<String, Value>>,
+    /// The model that answered, which is a fallback when the provider's own failed.
+    model: String,
+}
+
+/// Where an interrupted resumable upload stands, per an `X-Goog-Upload-Command: query`.
+enum UploadStatus {
+    Active(u64),
+    Final(Box<reqwest::Response>),
+}
+
+/// A chunk the user skipped from the dashboard.
+#[derive(Debug)]
+struct ChunkSkipped;
+
+impl std::fmt::Display for ChunkSkipped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("chunk skipped")
+    }
+}
+
+impl std::error::Error for ChunkSkipped {}
+
+/// A chunk waiting on its `generateContent` call in `transcribe_chunks`.
+struct PendingChunk<'a> {
+    position: usize,
+    chunk_index: u64,
+    asset: &'a Asset,
+    meta: Value,
+    response_path: Option<PathBuf>,
+}
+
+/// What every chunk of one `transcribe_chunks` call shares.
+struct ChunkRun<'r> {
+    instruction: &'r str,
+    modality: &'r str,
+    job_id: &'r str,
+    chunk_total: u64,
+    context_lines: usize,
+    /// Finished chunk texts by position; read for context carry-over.
+    responses: &'r Mutex<Vec<Option<String>>>,
 }
 
 #[derive(Clone)]
@@ -46,11 +120,55 @@ struct CachedUpload {
     uri: String,
     mime_type: String,
     name: Option<String>,
+    expires_at: Option<String>,
+}
+
+impl CachedUpload {
+    fn from_file(file: &Value, mime: &str) -> Option<Self> {
+        let field = |key: &str| file.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        Some(Self {
+            uri: field("uri")?,
+            mime_type: field("mimeType").unwrap_or_else(|| mime.to_string()),
+            name: field("name"),
+            expires_at: field("expirationTime"),
+        })
+    }
+
+    fn from_record(record: UploadRecord) -> Self {
+        Self {
+            uri: record.uri,
+            mime_type: record.mime_type,
+            name: record.name,
+            expires_at: record.expires_at,
+        }
+    }
+
+    fn record(&self) -> UploadRecord {
+        UploadRecord {
+            uri: self.uri.clone(),
+            mime_type: self.mime_type.clone(),
+            name: self.name.clone(),
+            expires_at: self.expires_at.clone(),
+        }
+    }
+
+    fn part(&self, metadata: &mut Map<String, Value>) -> Value {
+        metadata.insert("file_uri".into(), Value::String(self.uri.clone()));
+        if let Some(name) = self.name.as_ref() {
+            metadata.insert("file_name".into(), Value::String(name.clone()));
+        }
+        json!({
+            "file_data": {
+                "file_uri": self.uri,
+                "mime_type": self.mime_type,
+            }
+        })
+    }
 }
 
 impl GeminiProvider {
     pub fn new(
-        api_key: String,
+        keys: ApiKeys,
         model: String,
         monitor: RunMonitor,
         quota: Option<crate::quota::QuotaMonitor>,
@@ -60,17 +178,86 @@ impl GeminiProvider {
             .build()
             .expect("failed to build reqwest client");
         Self {
-            api_key,
+            keys,
             model,
             http,
             monitor,
             progress: None,
             upload_cache: Mutex::new(HashMap::new()),
             cleanup: Mutex::new(HashSet::new()),
+            keep_uploads: true,
             quota,
+            budget: None,
+            generation: GenerationConfig::default(),
+            safety: SafetyConfig::default(),
+            count_tokens: false,
+            fallbacks: Vec::new(),
+            cancel: CancellationToken::new(),
+            control: ChunkControl::default(),
+            recording: None,
         }
     }
 
+    /// Save every response to `recording`, or answer from it without calling the
+    /// API or uploading anything.
+    pub fn with_recording(mut self, recording: Option<Recording>) -> Self {
+        self.recording = recording;
+        self
+    }
+
+    fn replaying(&self) -> bool {
+        self.recording.as_ref().is_some_and(Recording::is_replay)
+    }
+
+    /// Let the dashboard pause chunk requests and skip chunks.
+    pub fn with_control(mut self, control: ChunkControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    /// Once `cancel` fires, no new request starts; requests already sent finish and
+    /// are recorded as usual.
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Models to try in order when a request to the provider's model still fails
+    /// after its retries.
+    pub fn with_fallbacks(mut self, fallbacks: Vec<String>) -> Self {
+        self.fallbacks = fallbacks;
+        self
+    }
+
+    /// Measure each request with `countTokens` before sending it, and use the count
+    /// for the budget check, token pacing, and the stored per-second media rates.
+    pub fn with_token_count(mut self, enabled: bool) -> Self {
+        self.count_tokens = enabled;
+        self
+    }
+
+    pub fn with_safety(mut self, safety: SafetyConfig) -> Self {
+        self.safety = safety;
+        self
+    }
+
+    pub fn with_generation(mut self, generation: GenerationConfig) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    pub fn with_budget(mut self, budget: Option<CostBudget>) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// With `false`, uploads are deleted at the end of the run instead of being
+    /// remembered for reuse until they expire.
+    pub fn with_keep_uploads(mut self, keep: bool) -> Self {
+        self.keep_uploads = keep;
+        self
+    }
+
     pub fn with_progress(mut self, progress: tokio::sync::mpsc::UnboundedSender<Progress>) -> Self {
         self.progress = Some(progress);
         self
@@ -82,7 +269,35 @@ impl GeminiProvider {
         }
     }
 
-    fn part_for_asset(&self, asset: &Asset) -> Result<(Value, Map<String, Value>)> {
+    /// Show what a chunk's request is doing (`uploading`, `generating`, ...) on its
+    /// detail row. A no-op outside chunked runs, whose `meta` has no chunk index.
+    fn chunk_phase(&self, meta: &Value, step: u64, status: &str) {
+        let (Some(job_id), Some(index), Some(total)) = (
+            meta_string(meta, "job_id"),
+            meta_u64(meta, "chunk_index"),
+            meta_u64(meta, "chunk_total"),
+        ) else {
+            return;
+        };
+        self.send_progress(Progress {
+            scope: ProgressScope::ChunkDetail {
+                job_id,
+                index,
+                total,
+            },
+            stage: ProgressStage::Transcribe,
+            current: step,
+            total: 4,
+            status: status.into(),
+            finished: false,
+        });
+    }
+
+    async fn part_for_asset(
+        &self,
+        asset: &Asset,
+        meta: &Value,
+    ) -> Result<(Value, Map<String, Value>)> {
         let mut metadata = Map::new();
         if let Some(obj) = asset.meta.as_object() {
             for (key, value) in obj {
@@ -119,6 +334,13 @@ impl GeminiProvider {
             }
         }
 
+        if asset.media == "text" {
+            let text = tokio::fs::read_to_string(&asset.path)
+                .await
+                .with_context(|| format!("reading asset {}", asset.path.display()))?;
+            return Ok((json!({ "text": text }), metadata));
+        }
+
         if let Some(inline_bytes) = asset
             .meta
             .get("inline_bytes")
@@ -133,7 +355,8 @@ impl GeminiProvider {
             return Ok((part, metadata));
         }
 
-        let bytes = fs::read(&asset.path)
+        let bytes = tokio::fs::read(&asset.path)
+            .await
             .with_context(|| format!("reading asset {}", asset.path.display()))?;
         if bytes.len() <= INLINE_THRESHOLD_BYTES {
             let encoded = BASE64.encode(&bytes);
@@ -146,50 +369,198 @@ impl GeminiProvider {
             return Ok((part, metadata));
         }
 
-        if let Some(key) = asset.meta.get("upload_cache_key").and_then(|v| v.as_str()) {
-            if let Some(cached) = self.upload_cache.lock().unwrap().get(key).cloned() {
-                let part = json!({
-                    "file_data": {
-                        "file_uri": cached.uri,
-                        "mime_type": cached.mime_type,
+        let digest = Sha256::digest(&bytes);
+        // Uploads remembered across runs are found by content, so a changed file
+        // behind the same URL is uploaded afresh.
+        let content_key = format!("sha256:{}:{}:{mime}", hex::encode(digest), bytes.len());
+        let cache_key = asset
+            .meta
+            .get("upload_cache_key")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("sha256:{}", hex::encode(digest)));
+        if self.replaying() {
+            // Recorded requests name uploads by cache key; see `Recording::key`.
+            metadata.insert("file_uri".into(), Value::String(cache_key.clone()));
+            let part = json!({
+                "file_data": {
+                    "file_uri": cache_key,
+                    "mime_type": mime,
+                }
+            });
+            return Ok((part, metadata));
+        }
+        if let Some(cached) = self.upload_cache.lock().unwrap().get(&cache_key).cloned() {
+            self.alias_upload(&cached, &cache_key);
+            return Ok((cached.part(&mut metadata), metadata));
+        }
+        if self.keep_uploads {
+            if let Some(found) = self
+                .find_existing_upload(&content_key, &BASE64.encode(digest), bytes.len(), &mime)
+                .await
+            {
+                self.alias_upload(&found, &cache_key);
+                self.upload_cache
+                    .lock()
+                    .unwrap()
+                    .insert(cache_key, found.clone());
+                return Ok((found.part(&mut metadata), metadata));
+            }
+        }
+
+        self.chunk_phase(meta, 1, "uploading");
+        let upload = self.upload_file(asset, &bytes, &mime, meta).await?;
+        metadata.insert("upload_bytes".into(), Value::from(bytes.len() as u64));
+        if self.keep_uploads {
+            if let Err(err) = UploadIndex::record(&content_key, upload.record()) {
+                tracing::warn!("could not remember upload: {err:#}");
+            }
+        }
+        self.alias_upload(&upload, &cache_key);
+        self.upload_cache
+            .lock()
+            .unwrap()
+            .insert(cache_key, upload.clone());
+        Ok((upload.part(&mut metadata), metadata))
+    }
+
+    fn alias_upload(&self, upload: &CachedUpload, cache_key: &str) {
+        if let Some(recording) = &self.recording {
+            recording.alias_upload(&upload.uri, cache_key);
+        }
+    }
+
+    /// An ACTIVE file from an earlier run: first the local upload index, then a
+    /// `files.list` scan for the same SHA-256, size, and type. Lookup failures just
+    /// mean uploading again.
+    async fn find_existing_upload(
+        &self,
+        key: &str,
+        sha256: &str,
+        size: usize,
+        mime: &str,
+    ) -> Option<CachedUpload> {
+        if let Some(record) = UploadIndex::load().lookup(key).cloned() {
+            let cached = CachedUpload::from_record(record);
+            if let Some(name) = cached.name.as_deref() {
+                match self.get_file(name).await {
+                    Ok(Some(file)) if file_state(&file) == "ACTIVE" => {
+                        self.monitor
+                            .note_event("files.reuse", json!({ "name": name, "source": "index" }));
+                        return Some(cached);
                     }
-                });
-                metadata.insert("file_uri".into(), Value::String(cached.uri));
-                if let Some(name) = cached.name.as_ref() {
-                    metadata.insert("file_name".into(), Value::String(name.clone()));
+                    Ok(_) => {}
+                    Err(err) => tracing::warn!("checking remembered upload {name}: {err:#}"),
                 }
-                return Ok((part, metadata));
+            }
+            if let Err(err) = UploadIndex::forget(key) {
+                tracing::warn!("could not update upload index: {err:#}");
             }
         }
 
-        let upload = self.upload_file(asset, &bytes, &mime)?;
-        if let Some(cache_key) = asset.meta.get("upload_cache_key").and_then(|v| v.as_str()) {
-            self.upload_cache.lock().unwrap().insert(
-                cache_key.to_string(),
-                CachedUpload {
-                    uri: upload.uri.clone(),
-                    mime_type: upload.mime_type.clone(),
-                    name: upload.name.clone(),
-                },
-            );
+        match self.find_listed_file(sha256, size, mime).await {
+            Ok(Some(file)) => {
+                let cached = CachedUpload::from_file(&file, mime)?;
+                self.monitor.note_event(
+                    "files.reuse",
+                    json!({ "name": cached.name, "source": "list" }),
+                );
+                if let Err(err) = UploadIndex::record(key, cached.record()) {
+                    tracing::warn!("could not remember upload: {err:#}");
+                }
+                Some(cached)
+            }
+            Ok(None) => None,
+            Err(err) => {
+                tracing::warn!("listing uploaded files: {err:#}");
+                None
+            }
         }
-        metadata.insert("file_uri".into(), Value::String(upload.uri.clone()));
-        if let Some(name) = upload.name.as_ref() {
-            metadata.insert("file_name".into(), Value::String(name.clone()));
+    }
+
+    /// `files.get`, with `None` when the file has expired or been deleted.
+    async fn get_file(&self, name: &str) -> Result<Option<Value>> {
+        let url = format!(
+            "https://generativelanguage.googleapis.com/v1beta/{}?key={}",
+            name,
+            self.keys.primary()
+        );
+        self.apply_quota_delay("files").await;
+        let resp = self.http.get(&url).send().await?;
+        if resp.status() == StatusCode::NOT_FOUND || resp.status() == StatusCode::FORBIDDEN {
+            return Ok(None);
         }
-        let part = json!({
-            "file_data": {
-                "file_uri": upload.uri,
-                "mime_type": upload.mime_type,
+        let resp = resp.error_for_status()?;
+        Ok(Some(
+            resp.json().await.context("parsing files.get response")?,
+        ))
+    }
+
+    /// The first ACTIVE file whose `sha256Hash` (base64), `sizeBytes`, and
+    /// `mimeType` match.
+    async fn find_listed_file(
+        &self,
+        sha256: &str,
+        size: usize,
+        mime: &str,
+    ) -> Result<Option<Value>> {
+        let mut page_token: Option<String> = None;
+        for _ in 0..MAX_LISTED_PAGES {
+            let mut url = format!(
+                "https://generativelanguage.googleapis.com/v1beta/files?pageSize=100&key={}",
+                self.keys.primary()
+            );
+            if let Some(token) = &page_token {
+                url.push_str("&pageToken=");
+                url.push_str(token);
             }
-        });
-        Ok((part, metadata))
+            self.apply_quota_delay("files").await;
+            let page: Value = self
+                .http
+                .get(&url)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await
+                .context("parsing files.list response")?;
+            let found = page
+                .get("files")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+                .find(|file| {
+                    file.get("sha256Hash").and_then(|v| v.as_str()) == Some(sha256)
+                        && file.get("sizeBytes").and_then(|v| v.as_str())
+                            == Some(size.to_string().as_str())
+                        && file.get("mimeType").and_then(|v| v.as_str()) == Some(mime)
+                        && file_state(file) == "ACTIVE"
+                });
+            if let Some(file) = found {
+                return Ok(Some(file.clone()));
+            }
+            page_token = page
+                .get("nextPageToken")
+                .and_then(|v| v.as_str())
+                .filter(|token| !token.is_empty())
+                .map(str::to_string);
+            if page_token.is_none() {
+                break;
+            }
+        }
+        Ok(None)
     }
 
-    fn upload_file(&self, asset: &Asset, bytes: &[u8], mime: &str) -> Result<CachedUpload> {
+    async fn upload_file(
+        &self,
+        asset: &Asset,
+        bytes: &[u8],
+        mime: &str,
+        meta: &Value,
+    ) -> Result<CachedUpload> {
         let start_url = format!(
             "https://generativelanguage.googleapis.com/v1beta/files:upload?key={}",
-            self.api_key
+            self.keys.primary()
         );
 
         let display_name = asset
@@ -202,7 +573,7 @@ impl GeminiProvider {
         let upload_url = {
             let mut attempt = 0;
             loop {
-                self.apply_quota_delay("files");
+                self.apply_quota_delay("files").await;
 
                 let mut headers = HeaderMap::new();
                 headers.insert(
@@ -226,6 +597,7 @@ impl GeminiProvider {
                     .headers(headers)
                     .json(&start_payload)
                     .send()
+                    .await
                 {
                     Ok(resp) => {
                         if resp.status().is_success() {
@@ -253,13 +625,13 @@ impl GeminiProvider {
                                     "path": asset.path,
                                 }),
                             );
-                            thread::sleep(delay);
+                            tokio::time::sleep(delay).await;
                             attempt += 1;
                             continue;
                         }
 
                         let status = resp.status();
-                        let text = resp.text().unwrap_or_default();
+                        let text = resp.text().await.unwrap_or_default();
                         return Err(anyhow!(
                             "files:upload start failed with status {}: {}",
                             status,
@@ -278,7 +650,7 @@ impl GeminiProvider {
                                     "path": asset.path,
                                 }),
                             );
-                            thread::sleep(delay);
+                            tokio::time::sleep(delay).await;
                             attempt += 1;
                             continue;
                         }
@@ -288,16 +660,6 @@ impl GeminiProvider {
             }
         };
 
-        let mut upload_headers = HeaderMap::new();
-        upload_headers.insert(
-            "X-Goog-Upload-Command",
-            HeaderValue::from_static("upload, finalize"),
-        );
-        upload_headers.insert("X-Goog-Upload-Offset", HeaderValue::from_static("0"));
-        upload_headers.insert(CONTENT_TYPE, HeaderValue::from_str(mime)?);
-        let upload_length = bytes.len().to_string();
-        upload_headers.insert(CONTENT_LENGTH, HeaderValue::from_str(&upload_length)?);
-
         let guard = match &self.quota {
             Some(quota) => {
                 Some(quota.track_upload(&asset.path.to_string_lossy(), bytes.len() as u64)?)
@@ -305,71 +667,112 @@ impl GeminiProvider {
             None => None,
         };
 
-        let finalize_resp = {
-            let mut attempt = 0;
-            loop {
-                self.apply_quota_delay("files");
-                match self
-                    .http
-                    .post(&upload_url)
-                    .headers(upload_headers.clone())
-                    .body(bytes.to_owned())
-                    .send()
-                {
-                    Ok(resp) => {
-                        if resp.status().is_success() {
-                            break resp;
-                        }
-
-                        if should_retry_status(resp.status()) && attempt < MAX_RETRIES {
-                            let delay = backoff_delay(attempt);
-                            self.monitor.note_event(
-                                "retry.files.upload_finalize",
-                                json!({
-                                    "attempt": attempt + 1,
-                                    "delay_ms": delay.as_millis(),
-                                    "status": resp.status().as_u16(),
-                                    "path": asset.path,
-                                }),
-                            );
-                            thread::sleep(delay);
-                            attempt += 1;
-                            continue;
-                        }
-
-                        let status = resp.status();
-                        let text = resp.text().unwrap_or_default();
-                        return Err(anyhow!(
-                            "files:upload finalize failed with status {}: {}",
-                            status,
-                            text
-                        ));
-                    }
-                    Err(err) => {
-                        if is_retryable_error(&err) && attempt < MAX_RETRIES {
-                            let delay = backoff_delay(attempt);
-                            self.monitor.note_event(
-                                "retry.files.upload_finalize",
-                                json!({
-                                    "attempt": attempt + 1,
-                                    "delay_ms": delay.as_millis(),
-                                    "error": err.to_string(),
-                                    "path": asset.path,
-                                }),
-                            );
-                            thread::sleep(delay);
-                            attempt += 1;
-                            continue;
-                        }
-                        return Err(err).context("uploading file data");
+        // Send the file in pieces so a dropped connection only costs the current piece:
+        // after a failure, ask the session how much it has and continue from there.
+        let total = bytes.len();
+        let mut offset = 0usize;
+        let mut attempt = 0;
+        let started = Instant::now();
+        let finalize_resp = loop {
+            self.apply_quota_delay("files").await;
+            let end = (offset + UPLOAD_CHUNK_BYTES).min(total);
+            let command = if end == total {
+                "upload, finalize"
+            } else {
+                "upload"
+            };
+            let mut headers = HeaderMap::new();
+            headers.insert("X-Goog-Upload-Command", HeaderValue::from_static(command));
+            headers.insert("X-Goog-Upload-Offset", HeaderValue::from(offset as u64));
+            headers.insert(CONTENT_TYPE, HeaderValue::from_str(mime)?);
+            headers.insert(CONTENT_LENGTH, HeaderValue::from((end - offset) as u64));
+            let result = self
+                .http
+                .post(&upload_url)
+                .headers(headers)
+                .body(bytes::Bytes::copy_from_slice(&bytes[offset..end]))
+                .send()
+                .await;
+
+            let failure = match result {
+                Ok(resp) if resp.status().is_success() => {
+                    if end == total {
+                        break resp;
                     }
+                    offset = end;
+                    attempt = 0;
+                    let megabytes = |bytes: usize| bytes as f64 / 1_000_000.0;
+                    let seconds = started.elapsed().as_secs_f64().max(0.001);
+                    self.chunk_phase(
+                        meta,
+                        1,
+                        &format!(
+                            "uploading {:.1}/{:.1} MB at {:.1} MB/s",
+                            megabytes(offset),
+                            megabytes(total),
+                            megabytes(offset) / seconds
+                        ),
+                    );
+                    continue;
+                }
+                Ok(resp) if should_retry_status(resp.status()) => {
+                    json!({ "status": resp.status().as_u16() })
+                }
+                Ok(resp) => {
+                    let status = resp.status();
+                    let text = resp.text().await.unwrap_or_default();
+                    return Err(anyhow!(
+                        "files:upload failed at byte {} with status {}: {}",
+                        offset,
+                        status,
+                        text
+                    ));
+                }
+                Err(err) if is_retryable_error(&err) => json!({ "error": err.to_string() }),
+                Err(err) => return Err(err).context("uploading file data"),
+            };
+            if attempt >= MAX_RETRIES {
+                return Err(anyhow!(
+                    "files:upload gave up at byte {} of {}: {}",
+                    offset,
+                    total,
+                    failure
+                ));
+            }
+            let delay = backoff_delay(attempt);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+            match self.query_upload(&upload_url).await {
+                Ok(UploadStatus::Final(resp)) => break *resp,
+                Ok(UploadStatus::Active(received)) => {
+                    self.monitor.note_event(
+                        "files.upload.resume",
+                        json!({
+                            "attempt": attempt,
+                            "delay_ms": delay.as_millis(),
+                            "failure": failure,
+                            "sent": offset,
+                            "received": received,
+                            "total": total,
+                            "path": asset.path,
+                        }),
+                    );
+                    offset = (received as usize).min(total);
                 }
+                // The status query failed too; resend the current piece.
+                Err(err) => self.monitor.note_event(
+                    "files.upload.query_failed",
+                    json!({ "error": format!("{err:#}"), "path": asset.path }),
+                ),
             }
         };
 
         drop(guard);
 
-        let response_value: Value = finalize_resp.json().context("decoding upload response")?;
+        let response_value: Value = finalize_resp
+            .json()
+            .await
+            .context("decoding upload response")?;
         let mut file_value = response_value
             .get("file")
             .cloned()
@@ -393,7 +796,8 @@ impl GeminiProvider {
                         "path": asset.path,
                     }),
                 );
-                file_value = self.await_active_file(name)?;
+                self.chunk_phase(meta, 1, "waiting for ACTIVE");
+                file_value = self.await_active_file(name).await?;
             }
         }
 
@@ -408,81 +812,144 @@ impl GeminiProvider {
             ));
         }
 
-        let uri = file_value
-            .get("uri")
-            .and_then(|value| value.as_str())
-            .ok_or_else(|| anyhow!("upload response missing file.uri"))?
-            .to_string();
-        let name = file_value
-            .get("name")
-            .and_then(|value| value.as_str())
-            .map(|s| s.to_string());
-        if let Some(name_ref) = &name {
-            self.register_cleanup(name_ref);
+        let upload = CachedUpload::from_file(&file_value, mime)
+            .ok_or_else(|| anyhow!("upload response missing file.uri"))?;
+        if !self.keep_uploads {
+            if let Some(name_ref) = &upload.name {
+                self.register_cleanup(name_ref);
+            }
         }
+        Ok(upload)
+    }
 
-        Ok(CachedUpload {
-            uri,
-            mime_type: mime.to_string(),
-            name,
-        })
+    async fn query_upload(&self, upload_url: &str) -> Result<UploadStatus> {
+        let resp = self
+            .http
+            .post(upload_url)
+            .header("X-Goog-Upload-Command", "query")
+            .header(CONTENT_LENGTH, "0")
+            .send()
+            .await
+            .context("querying upload status")?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("upload status query returned {}", resp.status()));
+        }
+        let header = |name: &str| {
+            resp.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string())
+        };
+        if header("x-goog-upload-status").as_deref() == Some("final") {
+            return Ok(UploadStatus::Final(Box::new(resp)));
+        }
+        let received = header("x-goog-upload-size-received")
+            .and_then(|value| value.parse::<u64>().ok())
+            .ok_or_else(|| anyhow!("upload status query missing X-Goog-Upload-Size-Received"))?;
+        Ok(UploadStatus::Active(received))
     }
 
-    fn generate(
+    /// Send one request to the provider's model, then to each fallback that accepts
+    /// the modality while the previous one fails. Budget stops are not retried.
+    async fn generate(
         &self,
         instruction: &str,
         assets: &[&Asset],
         modality: &str,
         meta: &Value,
+    ) -> Result<Generated> {
+        let mut model = self.model.as_str();
+        let mut remaining = self
+            .fallbacks
+            .iter()
+            .filter(|fallback| **fallback != self.model)
+            .filter(|fallback| crate::models::supports(fallback, modality));
+        loop {
+            let err = match self
+                .generate_with(model, instruction, assets, modality, meta)
+                .await
+            {
+                Ok((text, assets)) => {
+                    return Ok(Generated {
+                        text,
+                        assets,
+                        model: model.to_string(),
+                    })
+                }
+                Err(err) => err,
+            };
+            if err.is::<BudgetExceeded>() || self.cancel.is_cancelled() {
+                return Err(err);
+            }
+            let Some(next) = remaining.next() else {
+                return Err(err);
+            };
+            tracing::warn!("{model} failed ({err:#}); retrying with {next}");
+            self.chunk_phase(meta, 2, &format!("retrying with {next}"));
+            self.monitor.note_event(
+                "model.fallback",
+                json!({
+                    "from": model,
+                    "to": next,
+                    "error": format!("{err:#}"),
+                    "chunk_index": meta_u64(meta, "chunk_index"),
+                }),
+            );
+            model = next;
+        }
+    }
+
+    async fn generate_with(
+        &self,
+        model: &str,
+        instruction: &str,
+        assets: &[&Asset],
+        modality: &str,
+        meta: &Value,
     ) -> Result<(String, Vec<Map<String, Value>>)> {
+        if self.cancel.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        if let Some(budget) = self.budget.as_ref().filter(|_| !self.count_tokens) {
+            budget.check(&self.monitor, model, modality, assets).await?;
+        }
         let mut parts = Vec::new();
         let mut asset_metadata = Vec::new();
         let mut event_metadata = meta.as_object().cloned().unwrap_or_default();
-        let enumerated: Vec<(usize, &Asset)> = assets
-            .iter()
-            .enumerate()
-            .map(|(index, asset)| (index, *asset))
-            .collect();
         let worker_limit = meta_u64(meta, "max_workers")
             .and_then(|value| usize::try_from(value).ok())
             .unwrap_or(crate::constants::DEFAULT_MAX_WORKERS)
             .max(1);
-        let asset_results: Vec<(usize, Value, Map<String, Value>)> =
-            if worker_limit <= 1 || enumerated.len() <= 1 {
-                enumerated
-                    .into_iter()
-                    .map(|(index, asset)| {
-                        let (part, metadata) = self.part_for_asset(asset)?;
-                        Ok((index, part, metadata))
-                    })
-                    .collect::<Result<Vec<_>>>()?
-            } else {
-                let pool = ThreadPoolBuilder::new()
-                    .num_threads(worker_limit.min(enumerated.len()))
-                    .build()?;
-                pool.install(|| {
-                    enumerated
-                        .par_iter()
-                        .map(|(index, asset)| {
-                            let (part, metadata) = self.part_for_asset(asset)?;
-                            Ok((*index, part, metadata))
-                        })
-                        .collect::<Result<Vec<_>>>()
-                })?
-            };
+        // `buffered` keeps results in input order while running up to `worker_limit` uploads at once.
+        let pending: Vec<_> = assets
+            .iter()
+            .map(|asset| self.part_for_asset(asset, meta))
+            .collect();
+        let ordered: Vec<(Value, Map<String, Value>)> = stream::iter(pending)
+            .buffered(worker_limit)
+            .try_collect()
+            .await?;
 
-        let mut ordered = asset_results;
-        ordered.sort_by_key(|(index, _, _)| *index);
-        for (_, part, metadata) in ordered {
+        for (asset, (part, metadata)) in assets.iter().zip(ordered) {
             for (key, value) in metadata.iter() {
                 event_metadata.entry(key.clone()).or_insert(value.clone());
             }
+            if let Some(label) = asset.meta.get("group_label").and_then(Value::as_str) {
+                parts.push(json!({ "text": label }));
+            }
             parts.push(part);
+            parts.extend(slide_parts(asset).await?);
+            if let Some(text) = asset.meta.get("ocr_text").and_then(Value::as_str) {
+                parts.push(json!({
+                    "text": format!("OCR text of the page above (may contain recognition errors):\n{text}"),
+                }));
+            }
             asset_metadata.push(metadata);
         }
         parts.push(json!({"text": instruction}));
+        self.chunk_phase(meta, 2, "generating");
 
-        let request = json!({
+        let mut request = json!({
             "contents": [
                 {
                     "role": "user",
@@ -490,110 +957,92 @@ impl GeminiProvider {
                 }
             ]
         });
+        let mut generation_config = self.generation.to_value();
+        if meta_string(meta, "format").as_deref() == Some("json") {
+            generation_config["responseMimeType"] = json!("application/json");
+            generation_config["responseSchema"] = StructuredTranscript::response_schema();
+        }
+        if generation_config
+            .as_object()
+            .is_some_and(|config| !config.is_empty())
+        {
+            request["generationConfig"] = generation_config;
+        }
+        if let Some(settings) = self.safety.to_value() {
+            request["safetySettings"] = settings;
+        }
 
         let url = format!(
             "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent",
-            self.model
+            model
         );
 
-        let (payload, started, finished, retries) = {
-            let mut attempt = 0;
-            let mut retries = 0;
-            loop {
-                self.apply_quota_delay(&self.model);
-                let started_at = OffsetDateTime::now_utc();
-                match self
-                    .http
-                    .post(&url)
-                    .query(&[("key", self.api_key.as_str())])
-                    .json(&request)
-                    .send()
-                {
-                    Ok(resp) => {
-                        if resp.status().is_success() {
-                            let finished_at = OffsetDateTime::now_utc();
-                            let payload: Value =
-                                resp.json().context("parsing generateContent response")?;
-                            break (payload, started_at, finished_at, retries);
-                        }
-
-                        if should_retry_status(resp.status()) && attempt < MAX_RETRIES {
-                            let delay = backoff_delay(attempt);
-                            self.monitor.note_event(
-                                "retry.generateContent",
-                                json!({
-                                    "attempt": attempt + 1,
-                                    "delay_ms": delay.as_millis(),
-                                    "status": resp.status().as_u16(),
-                                    "model": self.model,
-                                }),
-                            );
-                            thread::sleep(delay);
-                            attempt += 1;
-                            retries += 1;
-                            continue;
-                        }
-
-                        let status = resp.status();
-                        let text = resp.text().unwrap_or_default();
-                        return Err(anyhow!(
-                            "generateContent failed with status {}: {}",
-                            status,
-                            text
-                        ));
-                    }
-                    Err(err) => {
-                        if is_retryable_error(&err) && attempt < MAX_RETRIES {
-                            let delay = backoff_delay(attempt);
-                            self.monitor.note_event(
-                                "retry.generateContent",
-                                json!({
-                                    "attempt": attempt + 1,
-                                    "delay_ms": delay.as_millis(),
-                                    "error": err.to_string(),
-                                    "model": self.model,
-                                }),
-                            );
-                            thread::sleep(delay);
-                            attempt += 1;
-                            retries += 1;
-                            continue;
-                        }
-                        return Err(err).context("calling generateContent");
-                    }
+        let counted = if self.count_tokens && !self.replaying() {
+            self.count_input_tokens(model, &request, assets, meta).await
+        } else {
+            None
+        };
+        if let Some(budget) = &self.budget {
+            match counted {
+                Some(tokens) => {
+                    budget
+                        .check_tokens(&self.monitor, model, modality, tokens)
+                        .await?
+                }
+                None if self.count_tokens => {
+                    budget.check(&self.monitor, model, modality, assets).await?
                 }
+                None => {}
             }
-        };
+        }
+        if let Some(tokens) = counted {
+            event_metadata.insert("counted_input_tokens".into(), Value::from(tokens));
+        }
 
-        let text = payload
-            .get("candidates")
-            .and_then(|candidates| candidates.as_array())
-            .and_then(|array| array.first())
-            .and_then(|cand| cand.get("content"))
-            .and_then(|content| content.get("parts"))
-            .and_then(|parts| parts.as_array())
-            .map(|parts| {
-                parts
-                    .iter()
-                    .filter_map(|part| part.get("text").and_then(|t| t.as_str()))
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            })
-            .unwrap_or_default();
-
-        let usage = payload.get("usageMetadata");
-        let input_tokens = usage
-            .and_then(|u| u.get("promptTokenCount"))
-            .and_then(|v| v.as_u64())
-            .map(|v| v as u32);
-        let output_tokens = usage
-            .and_then(|u| u.get("candidatesTokenCount"))
-            .and_then(|v| v.as_u64())
-            .map(|v| v as u32);
-        let total_tokens = usage
-            .and_then(|u| u.get("totalTokenCount"))
-            .and_then(|v| v.as_u64())
-            .map(|v| v as u32);
+        let (mut payload, started, mut finished, mut retries) = self
+            .send_generate(model, &url, &mut request, counted, meta)
+            .await?;
+        let mut text = response_text(&payload);
+        let mut usage = TokenUsage::from_payload(&payload);
+        let mut continuations = 0;
+        // A response schema forces each reply to be a whole document, so those are
+        // left for the JSON repair pass instead.
+        let can_continue = request
+            .pointer("/generationConfig/responseSchema")
+            .is_none();
+        while finish_reason(&payload) == Some("MAX_TOKENS") {
+            if !can_continue || continuations >= MAX_CONTINUATIONS {
+                self.monitor.note_event(
+                    "response.truncated",
+                    json!({"model": model, "continuations": continuations}),
+                );
+                tracing::warn!(
+                    "response from {} hit the output token limit after {continuations} continuation(s); the transcript may be cut short",
+                    model
+                );
+                break;
+            }
+            continuations += 1;
+            self.monitor.note_event(
+                "response.continued",
+                json!({"model": model, "continuation": continuations}),
+            );
+            push_continuation(&mut request, &text);
+            let (next, _, next_finished, next_retries) = self
+                .send_generate(model, &url, &mut request, None, meta)
+                .await?;
+            text = stitch_continuation(&text, &response_text(&next));
+            usage.add(&TokenUsage::from_payload(&next));
+            finished = next_finished;
+            retries += next_retries;
+            payload = next;
+        }
+        let TokenUsage {
+            input_tokens,
+            output_tokens,
+            total_tokens,
+            cached_tokens,
+        } = usage;
 
         let asset_values: Vec<Value> = asset_metadata
             .iter()
@@ -601,6 +1050,17 @@ impl GeminiProvider {
             .collect();
         event_metadata.insert("assets".into(), Value::Array(asset_values));
         event_metadata.insert("retries".into(), Value::from(retries as u64));
+        let upload_bytes: u64 = asset_metadata
+            .iter()
+            .filter_map(|meta| meta.get("upload_bytes").and_then(Value::as_u64))
+            .sum();
+        event_metadata.insert("upload_bytes".into(), Value::from(upload_bytes));
+        if continuations > 0 {
+            event_metadata.insert("continuations".into(), Value::from(continuations as u64));
+        }
+        if !self.generation.is_empty() {
+            event_metadata.insert("generation_config".into(), self.generation.to_value());
+        }
         if let Some(uri) = asset_metadata
             .iter()
             .find_map(|meta| meta.get("file_uri").and_then(|v| v.as_str()))
@@ -609,10 +1069,16 @@ impl GeminiProvider {
                 .entry("file_uri".to_string())
                 .or_insert(Value::String(uri.to_string()));
         }
+        if let Some(cached) = cached_tokens {
+            event_metadata.insert("cached_input_tokens".into(), Value::from(cached));
+        }
+        if self.replaying() {
+            event_metadata.insert("replayed".into(), Value::Bool(true));
+        }
 
         let metadata_map: HashMap<String, Value> = event_metadata.clone().into_iter().collect();
         let event = RequestEvent {
-            model: self.model.clone(),
+            model: model.to_string(),
             modality: modality.to_string(),
             started_at: started,
             finished_at: finished,
@@ -621,26 +1087,281 @@ impl GeminiProvider {
             total_tokens,
             metadata: metadata_map,
         };
-        self.monitor.record(event.clone());
-        if let Some(quota) = &self.quota {
-            quota.register_tokens(&self.model, event.total_tokens);
-        }
+        self.monitor.record(event);
 
         Ok((text, asset_metadata))
     }
 
-    fn await_active_file(&self, name: &str) -> Result<Value> {
+    /// Input tokens of a generateContent body according to `countTokens`, or `None`
+    /// (with a warning) when the call fails.
+    ///
+    /// A request for one chunk of a recording also updates the stored tokens per
+    /// second for its media and resolution, which later runs plan chunks from.
+    async fn count_input_tokens(
+        &self,
+        model: &str,
+        request: &Value,
+        assets: &[&Asset],
+        meta: &Value,
+    ) -> Option<u64> {
+        let url = format!(
+            "https://generativelanguage.googleapis.com/v1beta/models/{}:countTokens",
+            model
+        );
+        let mut body = request.clone();
+        body["model"] = json!(format!("models/{}", model));
+        let body = json!({ "generateContentRequest": body });
+        let result: Result<Value> = async {
+            let response = self
+                .http
+                .post(&url)
+                .query(&[("key", self.keys.primary())])
+                .json(&body)
+                .send()
+                .await?;
+            let status = response.status();
+            if !status.is_success() {
+                let text = response.text().await.unwrap_or_default();
+                bail!("countTokens failed with status {status}: {}", text.trim());
+            }
+            response
+                .json()
+                .await
+                .context("parsing countTokens response")
+        }
+        .await;
+        let tokens = match result {
+            Ok(payload) => payload.get("totalTokens").and_then(Value::as_u64),
+            Err(err) => {
+                tracing::warn!("{err:#}; falling back to estimated token counts");
+                self.monitor.note_event(
+                    "tokens.count_failed",
+                    json!({"model": model, "error": format!("{err:#}")}),
+                );
+                return None;
+            }
+        }?;
+
+        let estimated: u64 = assets
+            .iter()
+            .map(|asset| crate::cost::estimate_input_tokens(asset))
+            .sum();
+        let mut event = json!({
+            "model": model,
+            "input_tokens": tokens,
+            "estimated": estimated,
+        });
+        if let [asset] = assets {
+            let start = meta_f64(&asset.meta, "chunk_start_seconds");
+            let end = meta_f64(&asset.meta, "chunk_end_seconds");
+            let timed = matches!(asset.media.as_str(), "video" | "audio");
+            if let (true, Some(start), Some(end)) = (timed, start, end) {
+                if end > start {
+                    let rate = tokens as f64 / (end - start);
+                    let resolution =
+                        meta_string(meta, "media_resolution").unwrap_or_else(|| "default".into());
+                    event["tokens_per_second"] = json!(rate);
+                    if let Err(err) =
+                        crate::video::TokenRates::record(&asset.media, &resolution, rate)
+                    {
+                        tracing::warn!("could not store the measured token rate: {err:#}");
+                    }
+                }
+            }
+        }
+        self.monitor.note_event("tokens.counted", event);
+        Some(tokens)
+    }
+
+    /// POST a generateContent request, retrying transient failures and, when
+    /// `safety.retry_relaxed` is set, a blocked response once with relaxed settings.
+    ///
+    /// `input_tokens`, when known, holds the request until the key's per-minute
+    /// token window has room for it.
+    async fn send_generate(
+        &self,
+        model: &str,
+        url: &str,
+        request: &mut Value,
+        input_tokens: Option<u64>,
+        meta: &Value,
+    ) -> Result<(Value, OffsetDateTime, OffsetDateTime, usize)> {
+        let mut relaxed = false;
+        // Uploaded files are only visible to the key that uploaded them.
+        let pinned = references_uploads(request);
+        loop {
+            let response = if let Some(recording) =
+                self.recording.as_ref().filter(|r| r.is_replay())
+            {
+                let now = OffsetDateTime::now_utc();
+                (recording.load(model, request)?, now, now, 0)
+            } else {
+                let mut attempt = 0;
+                let mut retries = 0;
+                loop {
+                    let (key_index, key) = if pinned {
+                        (0, self.keys.primary())
+                    } else {
+                        self.keys.next()
+                    };
+                    let bucket = self.keys.quota_bucket(model, key_index);
+                    if let Some(tokens) = input_tokens {
+                        self.apply_token_delay(&bucket, tokens).await;
+                    }
+                    self.apply_quota_delay(&bucket).await;
+                    let started_at = OffsetDateTime::now_utc();
+                    match self
+                        .http
+                        .post(url)
+                        .query(&[("key", key)])
+                        .json(&*request)
+                        .send()
+                        .await
+                    {
+                        Ok(resp) => {
+                            if resp.status().is_success() {
+                                let finished_at = OffsetDateTime::now_utc();
+                                let payload: Value = resp
+                                    .json()
+                                    .await
+                                    .context("parsing generateContent response")?;
+                                if let Some(quota) = &self.quota {
+                                    let usage = TokenUsage::from_payload(&payload);
+                                    quota.register_tokens(&bucket, usage.total_tokens);
+                                }
+                                if let Some(recording) = &self.recording {
+                                    if recording.mode() == ReplayMode::Record {
+                                        recording.save(model, request, &payload)?;
+                                    }
+                                }
+                                break (payload, started_at, finished_at, retries);
+                            }
+
+                            if should_retry_status(resp.status()) && attempt < MAX_RETRIES {
+                                let status = resp.status();
+                                let headers = resp.headers().clone();
+                                let body = resp.text().await.unwrap_or_default();
+                                let hint = throttle_hint(&headers, &body);
+                                let delay =
+                                    hint.retry_after.unwrap_or_else(|| backoff_delay(attempt));
+                                self.monitor.note_event(
+                                    "retry.generateContent",
+                                    json!({
+                                        "attempt": attempt + 1,
+                                        "delay_ms": delay.as_millis(),
+                                        "status": status.as_u16(),
+                                        "model": model,
+                                        "server_delay": hint.retry_after.is_some(),
+                                        "request_limit": hint.request_limit,
+                                        "chunk_index": meta.get("chunk_index"),
+                                    }),
+                                );
+                                self.chunk_phase(
+                                    meta,
+                                    2,
+                                    &format!(
+                                        "retrying after {}, waiting {:.0}s",
+                                        status.as_u16(),
+                                        delay.as_secs_f64()
+                                    ),
+                                );
+                                let throttled = status == StatusCode::TOO_MANY_REQUESTS
+                                    || hint.retry_after.is_some();
+                                let rotated =
+                                    throttled && !pinned && self.keys.throttled(key_index);
+                                if rotated {
+                                    self.monitor.note_event(
+                                        "api_key.rotated",
+                                        json!({"model": model, "from": key_index + 1}),
+                                    );
+                                }
+                                match &self.quota {
+                                    // The quota monitor holds every request to this model
+                                    // and key until the delay passes; the next loop waits
+                                    // on it unless it moves to another key.
+                                    Some(quota) if throttled => {
+                                        quota.register_throttle(&bucket, delay, hint.request_limit)
+                                    }
+                                    _ if rotated => {}
+                                    _ => tokio::time::sleep(delay).await,
+                                }
+                                attempt += 1;
+                                retries += 1;
+                                continue;
+                            }
+
+                            let status = resp.status();
+                            let text = resp.text().await.unwrap_or_default();
+                            return Err(anyhow!(
+                                "generateContent failed with status {}: {}",
+                                status,
+                                text
+                            ));
+                        }
+                        Err(err) => {
+                            if is_retryable_error(&err) && attempt < MAX_RETRIES {
+                                let delay = backoff_delay(attempt);
+                                self.monitor.note_event(
+                                    "retry.generateContent",
+                                    json!({
+                                        "attempt": attempt + 1,
+                                        "delay_ms": delay.as_millis(),
+                                        "error": err.to_string(),
+                                        "model": model,
+                                        "chunk_index": meta.get("chunk_index"),
+                                    }),
+                                );
+                                self.chunk_phase(
+                                    meta,
+                                    2,
+                                    &format!("retrying, waiting {:.0}s", delay.as_secs_f64()),
+                                );
+                                tokio::time::sleep(delay).await;
+                                attempt += 1;
+                                retries += 1;
+                                continue;
+                            }
+                            return Err(err).context("calling generateContent");
+                        }
+                    }
+                }
+            };
+            let Some(reason) = block_reason(&response.0) else {
+                return Ok(response);
+            };
+            self.monitor.note_event(
+                "response.blocked",
+                json!({
+                    "reason": reason,
+                    "model": model,
+                    "relaxed": relaxed,
+                }),
+            );
+            if self.safety.retry_relaxed && !relaxed {
+                relaxed = true;
+                request["safetySettings"] = SafetyConfig::relaxed();
+                continue;
+            }
+            bail!(
+                "Gemini returned no text: {reason}; adjust `safety` in recapit.yaml or set safety.retry_relaxed"
+            );
+        }
+    }
+
+    async fn await_active_file(&self, name: &str) -> Result<Value> {
         let url = format!(
             "https://generativelanguage.googleapis.com/v1beta/{}?key={}",
-            name, self.api_key
+            name,
+            self.keys.primary()
         );
         let mut attempt = 0;
         loop {
-            self.apply_quota_delay("files");
-            match self.http.get(&url).send() {
+            self.apply_quota_delay("files").await;
+            match self.http.get(&url).send().await {
                 Ok(resp) => {
                     if resp.status().is_success() {
-                        let value: Value = resp.json().context("parsing files.get response")?;
+                        let value: Value =
+                            resp.json().await.context("parsing files.get response")?;
                         let state = value
                             .get("state")
                             .and_then(|v| v.as_str())
@@ -659,7 +1380,7 @@ impl GeminiProvider {
                                     "name": name,
                                 }),
                             );
-                            thread::sleep(delay);
+                            tokio::time::sleep(delay).await;
                             attempt += 1;
                             continue;
                         }
@@ -676,12 +1397,12 @@ impl GeminiProvider {
                                 "name": name,
                             }),
                         );
-                        thread::sleep(delay);
+                        tokio::time::sleep(delay).await;
                         attempt += 1;
                         continue;
                     }
                     let status = resp.status();
-                    let text = resp.text().unwrap_or_default();
+                    let text = resp.text().await.unwrap_or_default();
                     return Err(anyhow!("files.get failed with status {}: {}", status, text));
                 }
                 Err(err) => {
@@ -696,7 +1417,7 @@ impl GeminiProvider {
                                 "name": name,
                             }),
                         );
-                        thread::sleep(delay);
+                        tokio::time::sleep(delay).await;
                         attempt += 1;
                         continue;
                     }
@@ -706,7 +1427,7 @@ impl GeminiProvider {
         }
     }
 
-    fn apply_quota_delay(&self, bucket: &str) {
+    async fn apply_quota_delay(&self, bucket: &str) {
         if let Some(quota) = &self.quota {
             if let Some(delay) = quota.register_request(bucket) {
                 if !delay.is_zero() {
@@ -717,12 +1438,33 @@ impl GeminiProvider {
                             "delay_ms": delay.as_millis(),
                         }),
                     );
-                    thread::sleep(delay);
+                    tokio::time::sleep(delay).await;
                 }
             }
         }
     }
 
+    async fn apply_token_delay(&self, bucket: &str, tokens: u64) {
+        let Some(delay) = self
+            .quota
+            .as_ref()
+            .and_then(|quota| quota.token_delay(bucket, tokens))
+        else {
+            return;
+        };
+        if !delay.is_zero() {
+            self.monitor.note_event(
+                "quota.sleep",
+                json!({
+                    "bucket": bucket,
+                    "delay_ms": delay.as_millis(),
+                    "tokens": tokens,
+                }),
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
     fn register_cleanup(&self, name: &str) {
         let inserted = self.cleanup.lock().unwrap().insert(name.to_string());
         if inserted {
@@ -731,13 +1473,13 @@ impl GeminiProvider {
         }
     }
 
-    fn cleanup_uploads(&self) -> Result<()> {
+    async fn cleanup_uploads(&self) -> Result<()> {
         let names: Vec<String> = {
             let mut guard = self.cleanup.lock().unwrap();
             guard.drain().collect()
         };
         for name in names {
-            match self.delete_file(&name) {
+            match self.delete_file(&name).await {
                 Ok(()) => {
                     self.monitor
                         .note_event("files.cleanup.deleted", json!({ "name": name }));
@@ -753,15 +1495,16 @@ impl GeminiProvider {
         Ok(())
     }
 
-    fn delete_file(&self, name: &str) -> Result<()> {
+    async fn delete_file(&self, name: &str) -> Result<()> {
         let url = format!(
             "https://generativelanguage.googleapis.com/v1beta/{}?key={}",
-            name, self.api_key
+            name,
+            self.keys.primary()
         );
         let mut attempt = 0;
         loop {
-            self.apply_quota_delay("files");
-            match self.http.delete(&url).send() {
+            self.apply_quota_delay("files").await;
+            match self.http.delete(&url).send().await {
                 Ok(resp) => {
                     if resp.status().is_success() {
                         return Ok(());
@@ -782,12 +1525,12 @@ impl GeminiProvider {
                                 "name": name,
                             }),
                         );
-                        thread::sleep(delay);
+                        tokio::time::sleep(delay).await;
                         attempt += 1;
                         continue;
                     }
                     let status = resp.status();
-                    let text = resp.text().unwrap_or_default();
+                    let text = resp.text().await.unwrap_or_default();
                     return Err(anyhow!(
                         "files.delete failed with status {}: {}",
                         status,
@@ -806,7 +1549,7 @@ impl GeminiProvider {
                                 "name": name,
                             }),
                         );
-                        thread::sleep(delay);
+                        tokio::time::sleep(delay).await;
                         attempt += 1;
                         continue;
                     }
@@ -816,7 +1559,7 @@ impl GeminiProvider {
         }
     }
 
-    fn transcribe_chunks(
+    async fn transcribe_chunks(
         &self,
         instruction: &str,
         assets: &[&Asset],
@@ -837,14 +1580,13 @@ impl GeminiProvider {
             .unwrap_or_else(|| PathBuf::from("output"));
         let name = meta_string(meta, "output_name").unwrap_or_else(|| "output".into());
         let skip_existing = meta_bool(meta, "skip_existing").unwrap_or(false);
+        let continue_on_error = meta_bool(meta, "continue_on_error").unwrap_or(false);
         let save_intermediates = meta_bool(meta, "save_intermediates").unwrap_or(false);
         let save_metadata = meta_bool(meta, "save_metadata").unwrap_or(false);
         let chunk_dir = if save_intermediates {
             let dir = base.join("full-response").join("chunks");
             ensure_dir(&dir)?;
             Some(dir)
-        } else if save_metadata {
-            None
         } else {
             None
         };
@@ -898,6 +1640,20 @@ impl GeminiProvider {
                         json!({"version": 1, "chunks": []})
                     }
                 };
+                // Lets `--resume` find the run this manifest belongs to.
+                if let Some(obj) = manifest.as_object_mut() {
+                    let source = meta_string(meta, "source").unwrap_or_default();
+                    let source = match fs::canonicalize(&source) {
+                        Ok(path) if !source.contains("://") => path.to_string_lossy().to_string(),
+                        _ => source,
+                    };
+                    let base = fs::canonicalize(&base).unwrap_or_else(|_| base.clone());
+                    obj.insert("job_source".into(), Value::String(source));
+                    obj.insert(
+                        "output_base".into(),
+                        Value::String(base.to_string_lossy().to_string()),
+                    );
+                }
                 let chunks_array = manifest_chunks(&mut manifest)?;
                 let mut chunk_index_lookup = HashMap::new();
                 for (idx, entry) in chunks_array.iter().enumerate() {
@@ -908,8 +1664,9 @@ impl GeminiProvider {
                 (manifest_path_str, manifest, chunk_index_lookup)
             };
 
-        let mut responses = Vec::new();
-        for asset in assets {
+        let responses: Mutex<Vec<Option<String>>> = Mutex::new(vec![None; assets.len()]);
+        let mut pending = Vec::new();
+        for (position, asset) in assets.iter().enumerate() {
             let chunk_index = meta_u64(&asset.meta, "chunk_index").unwrap_or(0);
             let entry_obj = if manifest_path.as_os_str().is_empty() {
                 None
@@ -951,18 +1708,30 @@ impl GeminiProvider {
                     "path".into(),
                     Value::String(asset.path.to_string_lossy().to_string()),
                 );
-                entry_obj.insert(
-                    "start_seconds".into(),
-                    meta_f64(&asset.meta, "chunk_start_seconds")
-                        .map(Value::from)
-                        .unwrap_or(Value::Null),
-                );
-                entry_obj.insert(
-                    "end_seconds".into(),
-                    meta_f64(&asset.meta, "chunk_end_seconds")
-                        .map(Value::from)
-                        .unwrap_or(Value::Null),
-                );
+                if let Some(title) = meta_string(&asset.meta, "chapter_title") {
+                    entry_obj.insert("title".into(), Value::String(title));
+                } else if let Some(start_page) = meta_u64(&asset.meta, "chunk_start_page") {
+                    entry_obj.insert("start_page".into(), Value::from(start_page));
+                    entry_obj.insert(
+                        "end_page".into(),
+                        meta_u64(&asset.meta, "chunk_end_page")
+                            .map(Value::from)
+                            .unwrap_or(Value::Null),
+                    );
+                } else {
+                    entry_obj.insert(
+                        "start_seconds".into(),
+                        meta_f64(&asset.meta, "chunk_start_seconds")
+                            .map(Value::from)
+                            .unwrap_or(Value::Null),
+                    );
+                    entry_obj.insert(
+                        "end_seconds".into(),
+                        meta_f64(&asset.meta, "chunk_end_seconds")
+                            .map(Value::from)
+                            .unwrap_or(Value::Null),
+                    );
+                }
             }
 
             let response_path = chunk_dir
@@ -1003,7 +1772,7 @@ impl GeminiProvider {
             {
                 let path = response_path.as_ref().unwrap();
                 let text = fs::read_to_string(path)?;
-                responses.push(text.trim().to_string());
+                responses.lock().unwrap()[position] = Some(text.trim().to_string());
                 if let Some(entry_obj) = entry_obj.as_mut() {
                     entry_obj.insert("status".into(), Value::String("done".into()));
                 }
@@ -1027,6 +1796,14 @@ impl GeminiProvider {
             if let Some(end) = meta_f64(&asset.meta, "chunk_end_seconds") {
                 chunk_meta_map.insert("chunk_end_seconds".into(), Value::from(end));
             }
+            for key in ["chunk_start_page", "chunk_end_page"] {
+                if let Some(page) = meta_u64(&asset.meta, key) {
+                    chunk_meta_map.insert(key.into(), Value::from(page));
+                }
+            }
+            if let Some(title) = meta_string(&asset.meta, "chapter_title") {
+                chunk_meta_map.insert("chapter_title".into(), Value::String(title));
+            }
             chunk_meta_map.insert(
                 "manifest_path".into(),
                 Value::String(manifest_path.to_string_lossy().to_string()),
@@ -1041,56 +1818,167 @@ impl GeminiProvider {
                 chunk_meta_map.insert("file_uri".into(), Value::String(uri));
             }
 
-            let chunk_meta_value = Value::Object(chunk_meta_map.clone());
             if let Some(entry_obj) = entry_obj.as_mut() {
                 entry_obj.insert("status".into(), Value::String("running".into()));
             }
-
-            let chunk_scope = ProgressScope::ChunkDetail {
-                job_id: job_id.clone(),
-                index: chunk_index,
-                total: chunk_total_meta,
-            };
-
-            self.send_progress(Progress {
-                scope: chunk_scope.clone(),
-                stage: ProgressStage::Discover,
-                current: 1,
-                total: 4,
-                status: "discover".into(),
-                finished: false,
-            });
-            self.send_progress(Progress {
-                scope: chunk_scope.clone(),
-                stage: ProgressStage::Normalize,
-                current: 2,
-                total: 4,
-                status: "normalize".into(),
-                finished: false,
+            pending.push(PendingChunk {
+                position,
+                chunk_index,
+                asset,
+                meta: Value::Object(chunk_meta_map),
+                response_path,
             });
+        }
 
-            let (text, event_assets) = self.generate(
-                instruction,
-                std::slice::from_ref(asset),
-                modality,
-                &chunk_meta_value,
-            )?;
-            if let Some(path) = response_path.as_ref() {
+        // Requests still pass through the quota monitor, so a high concurrency only
+        // runs as fast as the model's RPM/TPM allow.
+        let concurrency = meta_u64(meta, "chunk_concurrency")
+            .and_then(|value| usize::try_from(value).ok())
+            .unwrap_or(1)
+            .max(1);
+        let mut completed = (assets.len() - pending.len()) as u64;
+        // List every chunk up front so the dashboard can skip ones not yet started.
+        for chunk in &pending {
+            self.chunk_phase(&chunk.meta, 0, "queued");
+        }
+        let run = ChunkRun {
+            instruction,
+            modality,
+            job_id: &job_id,
+            chunk_total: chunk_total_meta,
+            context_lines: meta_u64(meta, "chunk_context_lines").unwrap_or(0) as usize,
+            responses: &responses,
+        };
+        let calls: Vec<_> = pending
+            .into_iter()
+            .map(|chunk| {
+                // Carried by every log line about this chunk, including `--log-file`.
+                let span = tracing::info_span!(
+                    "chunk",
+                    index = chunk.chunk_index,
+                    total = run.chunk_total,
+                );
+                self.transcribe_chunk(chunk, &run).instrument(span)
+            })
+            .collect();
+        let mut finished = stream::iter(calls).buffer_unordered(concurrency);
+
+        while let Some((chunk, chunk_scope, result)) = finished.next().await {
+            let generated = match result {
+                Ok(generated) => generated,
+                // Never sent: leave it for a rerun over the same output.
+                Err(err) if err.is::<Cancelled>() => {
+                    if let Some(&idx) = chunk_index_lookup.get(&chunk.chunk_index) {
+                        if let Some(entry_obj) = manifest_chunks(&mut manifest)?
+                            .get_mut(idx)
+                            .and_then(Value::as_object_mut)
+                        {
+                            entry_obj.insert("status".into(), Value::String("pending".into()));
+                        }
+                    }
+                    continue;
+                }
+                // A spent budget fails every later chunk too, so it still ends the run.
+                Err(err)
+                    if err.is::<ChunkSkipped>()
+                        || (continue_on_error && !err.is::<BudgetExceeded>()) =>
+                {
+                    let skipped = err.is::<ChunkSkipped>();
+                    let (status, reason) = if skipped {
+                        ("skipped", "was skipped")
+                    } else {
+                        ("failed", "could not be transcribed")
+                    };
+                    let error = format!("{err:#}");
+                    self.monitor.note_event(
+                        &format!("chunk.{status}"),
+                        json!({
+                            "job_label": job_label,
+                            "chunk_index": chunk.chunk_index,
+                            "chunk_total": chunk_total_meta,
+                            "manifest_path": manifest_path_str,
+                            // Only saved chunk responses let `--resume` skip the finished chunks.
+                            "resumable": chunk_dir.is_some(),
+                            "error": error,
+                        }),
+                    );
+                    if let Some(&idx) = chunk_index_lookup.get(&chunk.chunk_index) {
+                        if let Some(entry_obj) = manifest_chunks(&mut manifest)?
+                            .get_mut(idx)
+                            .and_then(Value::as_object_mut)
+                        {
+                            entry_obj.insert("status".into(), Value::String(status.into()));
+                            if !skipped {
+                                entry_obj.insert("error".into(), Value::String(error));
+                            }
+                        }
+                        write_manifest(&manifest_path, &mut manifest)?;
+                    }
+                    // No response file is written, so a rerun over the same output
+                    // folder retries just this chunk.
+                    responses.lock().unwrap()[chunk.position] = Some(missing_chunk_placeholder(
+                        &chunk.meta,
+                        chunk.chunk_index + 1,
+                        chunk_total_meta,
+                        reason,
+                    ));
+                    completed += 1;
+                    self.send_progress(Progress {
+                        scope: chunk_scope,
+                        stage: ProgressStage::Transcribe,
+                        current: 4,
+                        total: 4,
+                        status: status.into(),
+                        finished: true,
+                    });
+                    if show_chunk_progress {
+                        self.send_progress(Progress {
+                            scope: ProgressScope::ChunkProgress {
+                                job_id: job_id.clone(),
+                                total: chunk_total_meta,
+                            },
+                            stage: ProgressStage::Transcribe,
+                            current: completed,
+                            total: chunk_total_meta,
+                            status: format!(
+                                "{job_label}: chunk {} of {chunk_total_meta} {status}",
+                                chunk.chunk_index + 1
+                            ),
+                            finished: completed == chunk_total_meta,
+                        });
+                    }
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            let Generated {
+                text,
+                assets: event_assets,
+                model,
+            } = generated;
+            if let Some(path) = chunk.response_path.as_ref() {
                 save_chunk_text(path, &text)?;
             }
-            if let Some(entry_obj) = entry_obj.as_mut() {
-                entry_obj.insert("status".into(), Value::String("done".into()));
-            }
-            if let Some(file_uri) = event_assets
-                .first()
-                .and_then(|meta| meta.get("file_uri"))
-                .and_then(|v| v.as_str())
-            {
-                if let Some(entry_obj) = entry_obj.as_mut() {
-                    entry_obj.insert("file_uri".into(), Value::String(file_uri.to_string()));
+            if let Some(&idx) = chunk_index_lookup.get(&chunk.chunk_index) {
+                if let Some(entry_obj) = manifest_chunks(&mut manifest)?
+                    .get_mut(idx)
+                    .and_then(Value::as_object_mut)
+                {
+                    entry_obj.insert("status".into(), Value::String("done".into()));
+                    entry_obj.insert("model".into(), Value::String(model));
+                    if let Some(file_uri) = event_assets
+                        .first()
+                        .and_then(|meta| meta.get("file_uri"))
+                        .and_then(|v| v.as_str())
+                    {
+                        entry_obj.insert("file_uri".into(), Value::String(file_uri.to_string()));
+                    }
                 }
+                // Record each chunk as it lands so an interrupted run resumes from here.
+                write_manifest(&manifest_path, &mut manifest)?;
             }
-            responses.push(text.trim().to_string());
+            responses.lock().unwrap()[chunk.position] = Some(text.trim().to_string());
+            completed += 1;
 
             self.send_progress(Progress {
                 scope: chunk_scope.clone(),
@@ -1101,7 +1989,7 @@ impl GeminiProvider {
                 finished: false,
             });
             self.send_progress(Progress {
-                scope: chunk_scope.clone(),
+                scope: chunk_scope,
                 stage: ProgressStage::Write,
                 current: 4,
                 total: 4,
@@ -1116,50 +2004,236 @@ impl GeminiProvider {
                         total: chunk_total_meta,
                     },
                     stage: ProgressStage::Transcribe,
-                    current: chunk_index + 1,
+                    current: completed,
                     total: chunk_total_meta,
-                    status: format!(
-                        "{job_label}: chunk {} of {}",
-                        chunk_index + 1,
-                        chunk_total_meta
-                    ),
-                    finished: chunk_index + 1 == chunk_total_meta,
+                    status: format!("{job_label}: chunk {completed} of {chunk_total_meta}"),
+                    finished: completed == chunk_total_meta,
                 });
             }
         }
+        drop(finished);
 
         if save_intermediates || save_metadata {
             write_manifest(&manifest_path, &mut manifest)?;
         }
+        if self.cancel.is_cancelled() && completed < assets.len() as u64 {
+            self.monitor.note_event(
+                "run.cancelled",
+                json!({
+                    "job_label": job_label,
+                    "chunks_done": completed,
+                    "chunk_total": assets.len(),
+                    "manifest_path": manifest_path_str,
+                }),
+            );
+            return Err(Cancelled.into());
+        }
+        let responses: Vec<String> = responses
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
         Ok(responses.join("\n\n"))
     }
+
+    /// Runs when the stream first polls it, so a chunk sees whichever earlier
+    /// responses have landed by then; with a concurrency of 1 that is always its predecessor.
+    async fn transcribe_chunk<'a>(
+        &self,
+        chunk: PendingChunk<'a>,
+        run: &ChunkRun<'_>,
+    ) -> (PendingChunk<'a>, ProgressScope, Result<Generated>) {
+        let chunk_scope = ProgressScope::ChunkDetail {
+            job_id: run.job_id.to_string(),
+            index: chunk.chunk_index,
+            total: run.chunk_total,
+        };
+        if self.control.is_paused() {
+            self.chunk_phase(&chunk.meta, 0, "paused");
+        }
+        tokio::select! {
+            _ = self.control.ready(run.job_id, chunk.chunk_index) => {}
+            _ = self.cancel.cancelled() => {}
+        }
+        if self.control.is_skipped(run.job_id, chunk.chunk_index) {
+            return (chunk, chunk_scope, Err(ChunkSkipped.into()));
+        }
+        let previous = match chunk.position.checked_sub(1) {
+            Some(before) if run.context_lines > 0 => run.responses.lock().unwrap()[before].clone(),
+            _ => None,
+        };
+        let instruction = match previous {
+            Some(text) => with_previous_context(run.instruction, &text, run.context_lines),
+            None => run.instruction.to_string(),
+        };
+        let result = tokio::select! {
+            result = self.generate(
+                &instruction,
+                std::slice::from_ref(&chunk.asset),
+                run.modality,
+                &chunk.meta,
+            ) => result,
+            _ = self.control.skipped(run.job_id, chunk.chunk_index) => Err(ChunkSkipped.into()),
+        };
+        (chunk, chunk_scope, result)
+    }
 }
 
+#[async_trait]
 impl Provider for GeminiProvider {
-    fn transcribe(
+    async fn transcribe(
         &self,
         instruction: &str,
         assets: &[Asset],
         modality: &str,
         meta: &serde_json::Value,
     ) -> Result<String> {
-        let mut chunk_assets: Vec<&Asset> = assets
-            .iter()
-            .filter(|asset| meta_u64(&asset.meta, "chunk_index").is_some())
-            .collect();
-        if !chunk_assets.is_empty() {
-            chunk_assets.sort_by_key(|asset| meta_u64(&asset.meta, "chunk_index").unwrap_or(0));
-            return self.transcribe_chunks(instruction, &chunk_assets, modality, meta);
+        let groups = group_chunked_assets(assets);
+        if groups.len() == 1 {
+            let (manifest, mut group) = groups.into_iter().next().unwrap();
+            if manifest.is_some() {
+                group.sort_by_key(|asset| meta_u64(&asset.meta, "chunk_index").unwrap_or(0));
+                return self
+                    .transcribe_chunks(instruction, &group, modality, meta)
+                    .await;
+            }
+            return Ok(self
+                .generate(instruction, &group, modality, meta)
+                .await?
+                .text);
         }
 
-        let asset_refs: Vec<&Asset> = assets.iter().collect();
-        let (text, _) = self.generate(instruction, &asset_refs, modality, meta)?;
-        Ok(text)
+        // Several documents in one job: stitch each chunked document separately so
+        // chunk indexes from different sources never interleave.
+        let name = meta_string(meta, "output_name").unwrap_or_else(|| "output".into());
+        let mut responses = Vec::new();
+        for (manifest, mut group) in groups {
+            let text = match manifest {
+                Some(manifest) => {
+                    group.sort_by_key(|asset| meta_u64(&asset.meta, "chunk_index").unwrap_or(0));
+                    let stem = Path::new(&manifest)
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    let mut group_meta = meta.as_object().cloned().unwrap_or_default();
+                    group_meta.insert(
+                        "output_name".into(),
+                        Value::String(format!("{name}-{stem}")),
+                    );
+                    self.transcribe_chunks(
+                        instruction,
+                        &group,
+                        modality,
+                        &Value::Object(group_meta),
+                    )
+                    .await?
+                }
+                None => {
+                    self.generate(instruction, &group, modality, meta)
+                        .await?
+                        .text
+                }
+            };
+            responses.push(text.trim().to_string());
+        }
+        Ok(responses.join("\n\n"))
+    }
+
+    async fn cleanup(&self) -> Result<()> {
+        self.cleanup_uploads().await
     }
+}
 
-    fn cleanup(&self) -> Result<()> {
-        self.cleanup_uploads()
+/// Every model the key can see, from the `models.list` endpoint.
+pub async fn list_models(api_key: &str) -> Result<Vec<Value>> {
+    list_all(api_key, "models", 1000).await
+}
+
+/// Every file the key has in the Files API, from the `files.list` endpoint.
+pub async fn list_files(api_key: &str) -> Result<Vec<Value>> {
+    list_all(api_key, "files", 100).await
+}
+
+/// Delete the upload `name` (`files/...`). One that is already gone counts as deleted.
+pub async fn delete_remote_file(api_key: &str, name: &str) -> Result<()> {
+    let http = Client::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .build()?;
+    let response = http
+        .delete(format!(
+            "https://generativelanguage.googleapis.com/v1beta/{name}?key={api_key}"
+        ))
+        .send()
+        .await?;
+    let status = response.status();
+    if status.is_success() || status == StatusCode::NOT_FOUND {
+        return Ok(());
     }
+    let body = response.text().await.unwrap_or_default();
+    bail!(
+        "files.delete of {name} failed with {status}: {}",
+        body.trim()
+    )
+}
+
+/// Every entry of a paged `<resource>.list` endpoint.
+async fn list_all(api_key: &str, resource: &str, page_size: u32) -> Result<Vec<Value>> {
+    let http = Client::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .build()?;
+    let mut entries = Vec::new();
+    let mut page_token: Option<String> = None;
+    for _ in 0..MAX_LISTED_PAGES {
+        let mut url = format!(
+            "https://generativelanguage.googleapis.com/v1beta/{resource}?pageSize={page_size}&key={api_key}"
+        );
+        if let Some(token) = &page_token {
+            url.push_str("&pageToken=");
+            url.push_str(token);
+        }
+        let response = http.get(&url).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!("{resource}.list failed with {status}: {}", body.trim());
+        }
+        let page: Value = response
+            .json()
+            .await
+            .with_context(|| format!("parsing {resource}.list response"))?;
+        entries.extend(
+            page.get(resource)
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .cloned(),
+        );
+        page_token = page
+            .get("nextPageToken")
+            .and_then(|v| v.as_str())
+            .filter(|token| !token.is_empty())
+            .map(str::to_string);
+        if page_token.is_none() {
+            break;
+        }
+    }
+    Ok(entries)
+}
+
+/// Split assets into runs that share a chunk manifest, preserving input order.
+/// Unchunked assets keep travelling together under a `None` key.
+fn group_chunked_assets(assets: &[Asset]) -> Vec<(Option<String>, Vec<&Asset>)> {
+    let mut groups: Vec<(Option<String>, Vec<&Asset>)> = Vec::new();
+    for asset in assets {
+        let key = meta_u64(&asset.meta, "chunk_index")
+            .map(|_| meta_string(&asset.meta, "manifest_path").unwrap_or_default());
+        match groups.iter_mut().find(|(existing, _)| *existing == key) {
+            Some((_, members)) => members.push(asset),
+            None => groups.push((key, vec![asset])),
+        }
+    }
+    groups
 }
 
 fn meta_u64(value: &Value, key: &str) -> Option<u64> {
@@ -1191,6 +2265,73 @@ fn is_retryable_error(err: &reqwest::Error) -> bool {
     err.is_timeout() || err.is_connect() || err.is_request()
 }
 
+/// What a throttled response says about when to retry and how fast to go.
+#[derive(Debug, Default, PartialEq)]
+struct ThrottleHint {
+    retry_after: Option<Duration>,
+    request_limit: Option<u32>,
+}
+
+/// Read `Retry-After`/`x-ratelimit-*` headers and the `RetryInfo`/`QuotaFailure`
+/// details Gemini puts in 429 bodies.
+fn throttle_hint(headers: &HeaderMap, body: &str) -> ThrottleHint {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let mut hint = ThrottleHint {
+        retry_after: header("retry-after").and_then(parse_retry_after),
+        request_limit: header("x-ratelimit-limit-requests").and_then(|v| v.trim().parse().ok()),
+    };
+
+    let parsed: Value = serde_json::from_str(body).unwrap_or(Value::Null);
+    let details = parsed
+        .pointer("/error/details")
+        .and_then(|details| details.as_array())
+        .cloned()
+        .unwrap_or_default();
+    for detail in &details {
+        let kind = detail.get("@type").and_then(|t| t.as_str()).unwrap_or("");
+        if kind.ends_with("RetryInfo") && hint.retry_after.is_none() {
+            hint.retry_after = detail
+                .get("retryDelay")
+                .and_then(|d| d.as_str())
+                .and_then(|d| d.trim().strip_suffix('s'))
+                .and_then(|secs| secs.parse::<f64>().ok())
+                .map(Duration::from_secs_f64);
+        } else if kind.ends_with("QuotaFailure") && hint.request_limit.is_none() {
+            hint.request_limit = detail
+                .get("violations")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+                .filter(|violation| {
+                    let id = violation
+                        .get("quotaId")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("");
+                    id.contains("PerMinute") && id.contains("Request")
+                })
+                .filter_map(|violation| match violation.get("quotaValue") {
+                    Some(Value::String(value)) => value.parse().ok(),
+                    Some(value) => value.as_u64().and_then(|v| u32::try_from(v).ok()),
+                    None => None,
+                })
+                .min();
+        }
+    }
+    hint.retry_after = hint.retry_after.map(|delay| delay.min(MAX_SERVER_DELAY));
+    hint
+}
+
+/// `Retry-After` is either delta-seconds or an HTTP date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<f64>() {
+        return (seconds >= 0.0).then(|| Duration::from_secs_f64(seconds));
+    }
+    let at =
+        OffsetDateTime::parse(value.trim(), &time::format_description::well_known::Rfc2822).ok()?;
+    let wait = at - OffsetDateTime::now_utc();
+    Some(Duration::from_secs_f64(wait.as_seconds_f64().max(0.0)))
+}
+
 fn backoff_delay(attempt: usize) -> Duration {
     let exp = BACKOFF_BASE_SECONDS * 2f64.powi(attempt as i32);
     let capped = exp.min(BACKOFF_CAP_SECONDS);
@@ -1199,6 +2340,95 @@ fn backoff_delay(attempt: usize) -> Duration {
     Duration::from_secs_f64((capped * jitter).min(BACKOFF_CAP_SECONDS))
 }
 
+/// Inline images for the slide frames `--video-mode hybrid` attaches to a video chunk,
+/// each labelled with when it appears.
+async fn slide_parts(asset: &Asset) -> Result<Vec<Value>> {
+    let Some(slides) = asset.meta.get("slides").and_then(Value::as_array) else {
+        return Ok(Vec::new());
+    };
+    let mut parts = Vec::with_capacity(slides.len() * 2);
+    for slide in slides {
+        let Some(path) = slide.get("path").and_then(Value::as_str) else {
+            continue;
+        };
+        let bytes = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("reading slide {path}"))?;
+        let seconds = slide.get("seconds").and_then(Value::as_f64).unwrap_or(0.0);
+        let seconds = seconds.max(0.0).round() as u64;
+        parts.push(json!({
+            "text": format!("Slide shown at {}:{:02}:", seconds / 60, seconds % 60),
+        }));
+        parts.push(json!({
+            "inline_data": {
+                "data": BASE64.encode(&bytes),
+                "mime_type": "image/png",
+            }
+        }));
+    }
+    Ok(parts)
+}
+
+/// Append the tail of the previous chunk's transcript so terminology, speaker names,
+/// and numbering carry across the boundary.
+fn with_previous_context(instruction: &str, previous: &str, lines: usize) -> String {
+    let kept: Vec<&str> = previous
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    let tail = kept[kept.len().saturating_sub(lines)..].join("\n");
+    if tail.is_empty() {
+        return instruction.to_string();
+    }
+    format!(
+        "{instruction}\n\nThis part continues an earlier one, whose transcript ended with the lines below. \
+         Keep its terminology, speaker names, and section numbering consistent, and do not repeat it.\n\n\
+         <previous_part>\n{tail}\n</previous_part>"
+    )
+}
+
+/// What a chunk that failed under `--continue-on-error`, or was skipped from the
+/// dashboard, contributes to the stitched transcript: a one-section document for
+/// JSON runs, otherwise a bracketed note naming the part of the source it covers.
+fn missing_chunk_placeholder(meta: &Value, number: u64, total: u64, reason: &str) -> String {
+    let clock = |seconds: f64| {
+        let seconds = seconds.max(0.0).round() as u64;
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    };
+    let span = if let Some(title) = meta_string(meta, "chapter_title") {
+        format!(" ({title})")
+    } else if let Some(start) = meta_u64(meta, "chunk_start_page") {
+        match meta_u64(meta, "chunk_end_page") {
+            Some(end) if end != start => format!(" (pages {start}-{end})"),
+            _ => format!(" (page {start})"),
+        }
+    } else if let Some(start) = meta_f64(meta, "chunk_start_seconds") {
+        match meta_f64(meta, "chunk_end_seconds") {
+            Some(end) => format!(" ({}-{})", clock(start), clock(end)),
+            None => format!(" (from {})", clock(start)),
+        }
+    } else {
+        String::new()
+    };
+    let note = format!("Part {number} of {total}{span} {reason}.");
+    if meta_string(meta, "format").as_deref() == Some("json") {
+        json!({"sections": [{"heading": "Missing part", "content": note}]}).to_string()
+    } else {
+        format!("[{note}]")
+    }
+}
+
+fn file_state(file: &Value) -> &str {
+    file.get("state")
+        .and_then(|v| v.as_str())
+        .unwrap_or("STATE_UNSPECIFIED")
+}
+
 fn is_retryable_file_state(state: &str) -> bool {
     matches!(state, "PROCESSING" | "INTERNAL")
 }
@@ -1238,3 +2468,58 @@ fn write_manifest(path: &Path, manifest: &mut Value) -> Result<()> {
     fs::write(path, serde_json::to_string_pretty(manifest)?)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_retry_hints_from_headers_and_body() {
+        let body = r#"{"error": {"code": 429, "details": [
+            {"@type": "type.googleapis.com/google.rpc.QuotaFailure", "violations": [
+                {"quotaId": "GenerateRequestsPerMinutePerProjectPerModel-FreeTier", "quotaValue": "5"}
+            ]},
+            {"@type": "type.googleapis.com/google.rpc.RetryInfo", "retryDelay": "37s"}
+        ]}}"#;
+        let hint = throttle_hint(&HeaderMap::new(), body);
+        assert_eq!(hint.retry_after, Some(Duration::from_secs(37)));
+        assert_eq!(hint.request_limit, Some(5));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", HeaderValue::from_static("3"));
+        let hint = throttle_hint(&headers, body);
+        assert_eq!(hint.retry_after, Some(Duration::from_secs(3)));
+        assert_eq!(
+            throttle_hint(&HeaderMap::new(), "oops"),
+            ThrottleHint::default()
+        );
+    }
+
+    #[test]
+    fn previous_context_keeps_the_last_non_empty_lines() {
+        let prompt = with_previous_context("Transcribe.", "one\n\ntwo\nthree\n\n", 2);
+        assert!(prompt.starts_with("Transcribe.\n\n"));
+        assert!(prompt.contains("<previous_part>\ntwo\nthree\n</previous_part>"));
+        assert_eq!(
+            with_previous_context("Transcribe.", "\n\n", 5),
+            "Transcribe."
+        );
+    }
+
+    #[test]
+    fn missing_chunk_placeholder_names_the_span() {
+        let meta = json!({"chunk_start_seconds": 600.0, "chunk_end_seconds": 1200.0});
+        assert_eq!(
+            missing_chunk_placeholder(&meta, 2, 4, "could not be transcribed"),
+            "[Part 2 of 4 (0:10:00-0:20:00) could not be transcribed.]"
+        );
+        let meta = json!({"chunk_start_page": 11, "chunk_end_page": 20, "format": "json"});
+        let transcript =
+            StructuredTranscript::parse(&missing_chunk_placeholder(&meta, 2, 3, "was skipped"))
+                .unwrap();
+        assert_eq!(
+            transcript.sections[0].content,
+            "Part 2 of 3 (pages 11-20) was skipped."
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// How `generateContent` requests are spread over several API keys
+/// (`api_keys.rotation` in `recapit.yaml`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum KeyRotation {
+    /// Each request uses the next key in turn.
+    #[default]
+    #[serde(rename = "round_robin", alias = "round-robin")]
+    RoundRobin,
+    /// Stay on one key until it is throttled, then move to the next.
+    #[serde(rename = "on_429", alias = "on-429")]
+    On429,
+}
+
+impl KeyRotation {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KeyRotation::RoundRobin => "round_robin",
+            KeyRotation::On429 => "on_429",
+        }
+    }
+}
+
+/// The Gemini API keys a run may use. Clones share the rotation position.
+///
+/// Files API uploads belong to the project of the key that made them, so file
+/// operations and requests that reference uploads always use the primary key.
+#[derive(Clone)]
+pub struct ApiKeys {
+    keys: Arc<Vec<String>>,
+    rotation: KeyRotation,
+    cursor: Arc<AtomicUsize>,
+}
+
+impl ApiKeys {
+    /// `keys` in order of preference, with blanks and duplicates dropped. The first
+    /// is the primary key.
+    pub fn new(keys: Vec<String>, rotation: KeyRotation) -> Self {
+        let mut unique: Vec<String> = Vec::new();
+        for key in keys {
+            let key = key.trim().to_string();
+            if !key.is_empty() && !unique.contains(&key) {
+                unique.push(key);
+            }
+        }
+        Self {
+            keys: Arc::new(unique),
+            rotation,
+            cursor: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn single(key: String) -> Self {
+        Self::new(vec![key], KeyRotation::default())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Every key, primary first.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.keys.iter().map(String::as_str)
+    }
+
+    pub fn primary(&self) -> &str {
+        self.keys.first().map(String::as_str).unwrap_or_default()
+    }
+
+    /// The key for the next `generateContent` request, with its position.
+    pub fn next(&self) -> (usize, &str) {
+        if self.keys.len() <= 1 {
+            return (0, self.primary());
+        }
+        let index = match self.rotation {
+            KeyRotation::RoundRobin => self.cursor.fetch_add(1, Ordering::Relaxed),
+            KeyRotation::On429 => self.cursor.load(Ordering::Relaxed),
+        } % self.keys.len();
+        (index, &self.keys[index])
+    }
+
+    /// Note that key `index` was throttled. Returns whether the next request will
+    /// go to a different key.
+    pub fn throttled(&self, index: usize) -> bool {
+        if self.keys.len() <= 1 {
+            return false;
+        }
+        if self.rotation == KeyRotation::On429 {
+            let _ = self
+                .cursor
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |cursor| {
+                    (cursor % self.keys.len() == index).then_some(index + 1)
+                });
+        }
+        true
+    }
+
+    /// Quota bucket for `model` under key `index`, so each key gets its own request
+    /// and token windows. A lone key keeps the bare model name.
+    pub fn quota_bucket(&self, model: &str, index: usize) -> String {
+        if self.keys.len() <= 1 {
+            return model.to_string();
+        }
+        let digest = Sha256::digest(self.keys[index].as_bytes());
+        format!("{model}#{}", hex::encode(&digest[..4]))
+    }
+}
+
+impl fmt::Debug for ApiKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiKeys")
+            .field("keys", &self.keys.len())
+            .field("rotation", &self.rotation)
+            .finish()
+    }
+}
+
+/// Whether a `generateContent` body points at Files API uploads or a cached
+/// context, which only the key that created them can read.
+pub fn references_uploads(request: &Value) -> bool {
+    request.get("cachedContent").is_some()
+        || request
+            .get("contents")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|content| content.get("parts").and_then(Value::as_array))
+            .flatten()
+            .any(|part| part.get("file_data").is_some() || part.get("fileData").is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_modes_pick_keys() {
+        let keys = || {
+            vec![
+                "a".to_string(),
+                "b".to_string(),
+                "a".to_string(),
+                " ".into(),
+            ]
+        };
+        let round_robin = ApiKeys::new(keys(), KeyRotation::RoundRobin);
+        assert_eq!(round_robin.len(), 2);
+        let picked: Vec<usize> = (0..3).map(|_| round_robin.next().0).collect();
+        assert_eq!(picked, [0, 1, 0]);
+
+        let on_429 = ApiKeys::new(keys(), KeyRotation::On429);
+        assert_eq!(on_429.next(), (0, "a"));
+        assert_eq!(on_429.next(), (0, "a"));
+        assert!(on_429.throttled(0));
+        // A second report for the key we already left does not skip ahead.
+        on_429.throttled(0);
+        assert_eq!(on_429.next(), (1, "b"));
+        assert_ne!(on_429.quota_bucket("m", 0), on_429.quota_bucket("m", 1));
+        assert_eq!(ApiKeys::single("k".into()).quota_bucket("m", 0), "m");
+    }
+}
@@ -1 +1,297 @@
+pub(crate) mod context_cache;
 pub mod gemini;
+pub mod keys;
+pub(crate) mod replay;
+pub(crate) mod uploads;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Sampling settings sent as `generationConfig` with every request
+/// (`generation` in `recapit.yaml`). Unset fields keep the model's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct GenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+}
+
+impl GenerationConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// These settings with every field set in `overrides` taking its place.
+    pub fn merged(&self, overrides: &GenerationConfig) -> GenerationConfig {
+        GenerationConfig {
+            temperature: overrides.temperature.or(self.temperature),
+            top_p: overrides.top_p.or(self.top_p),
+            max_output_tokens: overrides.max_output_tokens.or(self.max_output_tokens),
+            stop_sequences: if overrides.stop_sequences.is_empty() {
+                self.stop_sequences.clone()
+            } else {
+                overrides.stop_sequences.clone()
+            },
+        }
+    }
+
+    /// Check the ranges the API accepts so mistakes fail before any upload.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(temperature) = self.temperature {
+            anyhow::ensure!(
+                (0.0..=2.0).contains(&temperature),
+                "temperature must be between 0 and 2, got {temperature}"
+            );
+        }
+        if let Some(top_p) = self.top_p {
+            anyhow::ensure!(
+                (0.0..=1.0).contains(&top_p),
+                "top_p must be between 0 and 1, got {top_p}"
+            );
+        }
+        anyhow::ensure!(
+            self.max_output_tokens != Some(0),
+            "max_output_tokens must be at least 1"
+        );
+        anyhow::ensure!(
+            self.stop_sequences.len() <= 5,
+            "at most 5 stop sequences are allowed"
+        );
+        Ok(())
+    }
+
+    /// The request's `generationConfig` object.
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_else(|_| Value::Object(Default::default()))
+    }
+}
+
+/// A type the model is asked to return as JSON, described in the `responseSchema`
+/// dialect (an OpenAPI subset: upper-case types, `nullable` instead of type unions).
+pub trait ResponseSchema {
+    fn response_schema() -> Value;
+}
+
+/// Harm categories relaxed to `BLOCK_NONE` when a blocked request is retried.
+const RELAXED_CATEGORIES: [&str; 4] = [
+    "HARM_CATEGORY_HARASSMENT",
+    "HARM_CATEGORY_HATE_SPEECH",
+    "HARM_CATEGORY_SEXUALLY_EXPLICIT",
+    "HARM_CATEGORY_DANGEROUS_CONTENT",
+];
+
+/// Finish reasons that mean the model refused rather than ran out of room.
+const BLOCKED_FINISH_REASONS: [&str; 6] = [
+    "SAFETY",
+    "RECITATION",
+    "BLOCKLIST",
+    "PROHIBITED_CONTENT",
+    "SPII",
+    "IMAGE_SAFETY",
+];
+
+/// `safetySettings` sent with every request (`safety` in `recapit.yaml`).
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct SafetyConfig {
+    /// Threshold per harm category, e.g. `dangerous_content: block_only_high`.
+    #[serde(default)]
+    pub settings: std::collections::BTreeMap<String, String>,
+    /// Retry a blocked request once with every category set to `BLOCK_NONE`.
+    #[serde(default)]
+    pub retry_relaxed: bool,
+}
+
+impl SafetyConfig {
+    /// The request's `safetySettings` list, or `None` to keep the API defaults.
+    pub fn to_value(&self) -> Option<Value> {
+        if self.settings.is_empty() {
+            return None;
+        }
+        let settings: Vec<Value> = self
+            .settings
+            .iter()
+            .map(|(category, threshold)| {
+                let category = category.trim().to_ascii_uppercase();
+                let category = if category.starts_with("HARM_CATEGORY_") {
+                    category
+                } else {
+                    format!("HARM_CATEGORY_{category}")
+                };
+                serde_json::json!({
+                    "category": category,
+                    "threshold": threshold.trim().to_ascii_uppercase(),
+                })
+            })
+            .collect();
+        Some(Value::Array(settings))
+    }
+
+    pub fn relaxed() -> Value {
+        RELAXED_CATEGORIES
+            .iter()
+            .map(|category| serde_json::json!({"category": category, "threshold": "BLOCK_NONE"}))
+            .collect()
+    }
+}
+
+/// Why a generateContent response carries no text: a blocked prompt, no candidates,
+/// or a candidate stopped by a safety or recitation filter. `None` for usable responses.
+pub fn block_reason(payload: &Value) -> Option<String> {
+    if let Some(reason) = payload
+        .pointer("/promptFeedback/blockReason")
+        .and_then(Value::as_str)
+    {
+        return Some(format!("prompt blocked ({reason})"));
+    }
+    let Some(candidate) = payload
+        .get("candidates")
+        .and_then(Value::as_array)
+        .and_then(|candidates| candidates.first())
+    else {
+        return Some("no candidates returned".into());
+    };
+    let has_text = candidate
+        .pointer("/content/parts")
+        .and_then(Value::as_array)
+        .is_some_and(|parts| {
+            parts.iter().any(|part| {
+                part.get("text")
+                    .and_then(Value::as_str)
+                    .is_some_and(|text| !text.trim().is_empty())
+            })
+        });
+    let finish = candidate
+        .get("finishReason")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    (!has_text && BLOCKED_FINISH_REASONS.contains(&finish))
+        .then(|| format!("response stopped ({finish})"))
+}
+
+/// Follow-up requests made for one response that keeps hitting `MAX_TOKENS`.
+pub const MAX_CONTINUATIONS: usize = 4;
+
+const CONTINUE_PROMPT: &str = "Your previous reply was cut off by the output length limit. Continue exactly where it stopped, mid-sentence if needed. Do not repeat anything already written and do not add any preamble.";
+
+/// Text of the first candidate, parts joined by newlines.
+pub fn response_text(payload: &Value) -> String {
+    payload
+        .pointer("/candidates/0/content/parts")
+        .and_then(Value::as_array)
+        .map(|parts| {
+            parts
+                .iter()
+                .filter_map(|part| part.get("text").and_then(Value::as_str))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .unwrap_or_default()
+}
+
+pub fn finish_reason(payload: &Value) -> Option<&str> {
+    payload
+        .pointer("/candidates/0/finishReason")
+        .and_then(Value::as_str)
+}
+
+/// Extend a request's conversation with the truncated reply so far and a request
+/// to carry on from it.
+pub fn push_continuation(request: &mut Value, so_far: &str) {
+    if let Some(contents) = request.get_mut("contents").and_then(Value::as_array_mut) {
+        // Earlier continuation turns are replaced so the model sees one whole reply.
+        if contents.len() > 1 {
+            contents.truncate(1);
+        }
+        contents.push(serde_json::json!({"role": "model", "parts": [{"text": so_far}]}));
+        contents.push(serde_json::json!({"role": "user", "parts": [{"text": CONTINUE_PROMPT}]}));
+    }
+}
+
+/// Append a continuation to the text so far, dropping any stretch the model
+/// repeated from the end of the previous piece.
+pub fn stitch_continuation(previous: &str, next: &str) -> String {
+    let max_overlap = next.len().min(previous.len()).min(400);
+    let overlap = (1..=max_overlap)
+        .rev()
+        .filter(|&len| next.is_char_boundary(len))
+        .find(|&len| len >= 16 && previous.ends_with(&next[..len]))
+        .unwrap_or(0);
+    format!("{previous}{}", &next[overlap..])
+}
+
+/// Token counts from `usageMetadata`, summed across continuation requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub input_tokens: Option<u32>,
+    pub output_tokens: Option<u32>,
+    pub total_tokens: Option<u32>,
+    /// The part of `input_tokens` read from a context cache.
+    pub cached_tokens: Option<u32>,
+}
+
+impl TokenUsage {
+    pub fn from_payload(payload: &Value) -> Self {
+        let count = |key: &str, fallback: &str| {
+            let usage = payload.get("usageMetadata")?;
+            usage
+                .get(key)
+                .or_else(|| usage.get(fallback))
+                .and_then(Value::as_u64)
+                .map(|value| value as u32)
+        };
+        Self {
+            input_tokens: count("promptTokenCount", "inputTokenCount"),
+            output_tokens: count("candidatesTokenCount", "outputTokenCount"),
+            total_tokens: count("totalTokenCount", "totalTokens"),
+            cached_tokens: count("cachedContentTokenCount", "cachedTokenCount"),
+        }
+    }
+
+    pub fn add(&mut self, other: &TokenUsage) {
+        let sum = |a: Option<u32>, b: Option<u32>| match (a, b) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+        };
+        self.input_tokens = sum(self.input_tokens, other.input_tokens);
+        self.output_tokens = sum(self.output_tokens, other.output_tokens);
+        self.total_tokens = sum(self.total_tokens, other.total_tokens);
+        self.cached_tokens = sum(self.cached_tokens, other.cached_tokens);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn block_reason_flags_empty_refusals_only() {
+        let blocked = json!({"promptFeedback": {"blockReason": "SAFETY"}});
+        assert_eq!(
+            block_reason(&blocked).as_deref(),
+            Some("prompt blocked (SAFETY)")
+        );
+        let stopped =
+            json!({"candidates": [{"finishReason": "RECITATION", "content": {"parts": []}}]});
+        assert!(block_reason(&stopped).is_some());
+        let truncated = json!({"candidates": [{"finishReason": "MAX_TOKENS", "content": {"parts": [{"text": "partial"}]}}]});
+        assert_eq!(block_reason(&truncated), None);
+    }
+
+    #[test]
+    fn continuations_drop_repeated_overlap() {
+        let previous = "The gradient of the loss with respect to the weights is";
+        let next = "with respect to the weights is computed by backpropagation.";
+        assert_eq!(
+            stitch_continuation(previous, next),
+            "The gradient of the loss with respect to the weights is computed by backpropagation."
+        );
+        assert_eq!(stitch_continuation("abc", "def"), "abcdef");
+    }
+}
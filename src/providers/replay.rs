@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::utils::ensure_dir;
+
+/// Whether `generateContent` responses are written to or read from a recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayMode {
+    /// Call the API as usual and save every successful response (`--record`).
+    Record,
+    /// Answer requests from the recording without calling the API (`--replay`).
+    Replay,
+}
+
+/// A folder of recorded `generateContent` responses, one JSON file per request,
+/// named by a hash of the model and request body.
+///
+/// Files API URIs change from upload to upload, so uploaded parts are hashed by
+/// the asset's upload cache key instead; a replay never uploads anything.
+#[derive(Debug, Clone)]
+pub struct Recording {
+    mode: ReplayMode,
+    dir: PathBuf,
+    /// File URI to the upload cache key it was uploaded under.
+    uploads: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl Recording {
+    pub fn record(dir: &Path) -> Result<Self> {
+        ensure_dir(dir)?;
+        Ok(Self::new(ReplayMode::Record, dir))
+    }
+
+    pub fn replay(dir: &Path) -> Result<Self> {
+        if !dir.is_dir() {
+            bail!("no recording at {}", dir.display());
+        }
+        Ok(Self::new(ReplayMode::Replay, dir))
+    }
+
+    fn new(mode: ReplayMode, dir: &Path) -> Self {
+        Self {
+            mode,
+            dir: dir.to_path_buf(),
+            uploads: Arc::default(),
+        }
+    }
+
+    pub fn mode(&self) -> ReplayMode {
+        self.mode
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.mode == ReplayMode::Replay
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Note that `uri` holds the upload stored under `cache_key`.
+    pub fn alias_upload(&self, uri: &str, cache_key: &str) {
+        self.uploads
+            .lock()
+            .unwrap()
+            .insert(uri.to_string(), cache_key.to_string());
+    }
+
+    /// The recording key of `request` sent to `model`.
+    pub fn key(&self, model: &str, request: &Value) -> String {
+        let mut request = request.clone();
+        let uploads = self.uploads.lock().unwrap();
+        for part in parts_mut(&mut request) {
+            for (data, uri) in [("file_data", "file_uri"), ("fileData", "fileUri")] {
+                if let Some(value) = part.get_mut(data).and_then(|data| data.get_mut(uri)) {
+                    if let Some(key) = value.as_str().and_then(|current| uploads.get(current)) {
+                        *value = Value::String(key.clone());
+                    }
+                }
+            }
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(model.as_bytes());
+        hasher.update([0]);
+        hasher.update(request.to_string().as_bytes());
+        hex::encode(&hasher.finalize()[..16])
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+
+    /// The recorded response to `request`, or an error naming the request that has
+    /// no recording, which means the prompt, inputs, or settings changed.
+    pub fn load(&self, model: &str, request: &Value) -> Result<Value> {
+        let key = self.key(model, request);
+        let path = self.path(&key);
+        let text = fs::read_to_string(&path).map_err(|_| {
+            anyhow!(
+                "no recorded response for this {model} request in {} (key {key}); the prompt, inputs, or settings differ from the recorded run",
+                self.dir.display()
+            )
+        })?;
+        let entry: Value = serde_json::from_str(&text)
+            .with_context(|| format!("parsing recorded response {}", path.display()))?;
+        entry
+            .get("response")
+            .cloned()
+            .ok_or_else(|| anyhow!("{} has no response", path.display()))
+    }
+
+    /// Save `response` as the answer to `request`.
+    pub fn save(&self, model: &str, request: &Value, response: &Value) -> Result<()> {
+        let key = self.key(model, request);
+        let path = self.path(&key);
+        let entry = json!({
+            "model": model,
+            "recorded_at": OffsetDateTime::now_utc().format(&Rfc3339).ok(),
+            "prompt": prompt_text(request),
+            "response": response,
+        });
+        fs::write(&path, serde_json::to_string_pretty(&entry)?)
+            .with_context(|| format!("writing {}", path.display()))
+    }
+}
+
+fn parts_mut(request: &mut Value) -> impl Iterator<Item = &mut Value> {
+    request
+        .get_mut("contents")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(|content| content.get_mut("parts").and_then(Value::as_array_mut))
+        .flatten()
+}
+
+/// The request's text parts, kept beside the response so a recording can be
+/// read and diffed without the media it was made from.
+fn prompt_text(request: &Value) -> String {
+    request
+        .get("contents")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|content| content.get("parts").and_then(Value::as_array))
+        .flatten()
+        .filter_map(|part| part.get("text").and_then(Value::as_str))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uploads_are_keyed_by_content_not_uri() {
+        let dir = tempfile::tempdir().unwrap();
+        let request = |uri: &str| {
+            json!({"contents": [{"role": "user", "parts": [
+                {"file_data": {"file_uri": uri, "mime_type": "video/mp4"}},
+                {"text": "Transcribe."},
+            ]}]})
+        };
+        let recorded = Recording::record(dir.path()).unwrap();
+        recorded.alias_upload("https://files/abc", "sha256:1234");
+        recorded
+            .save("m", &request("https://files/abc"), &json!({"ok": true}))
+            .unwrap();
+
+        let replay = Recording::replay(dir.path()).unwrap();
+        assert_eq!(
+            replay.load("m", &request("sha256:1234")).unwrap(),
+            json!({"ok": true})
+        );
+        assert!(replay.load("other", &request("sha256:1234")).is_err());
+        assert!(replay.load("m", &request("sha256:5678")).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime};
+
+use crate::utils::ensure_dir;
+
+/// Entries this close to expiry are treated as gone, so a request never cites a
+/// file the Files API deletes mid-generation.
+const EXPIRY_MARGIN: Duration = Duration::hours(1);
+
+/// Files API uploads that outlive the run, keyed by content (SHA-256, size, and
+/// MIME type), so the next run of the same file can reference them instead of
+/// uploading again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UploadIndex {
+    #[serde(default)]
+    entries: BTreeMap<String, UploadRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadRecord {
+    pub uri: String,
+    pub mime_type: String,
+    pub name: Option<String>,
+    /// The file's `expirationTime`, RFC 3339.
+    pub expires_at: Option<String>,
+}
+
+impl UploadRecord {
+    fn is_live(&self, now: OffsetDateTime) -> bool {
+        self.expires_at
+            .as_deref()
+            .and_then(|value| OffsetDateTime::parse(value, &Rfc3339).ok())
+            .is_some_and(|expires| expires - EXPIRY_MARGIN > now)
+    }
+}
+
+fn index_path() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("recapit")
+        .join("uploads.json")
+}
+
+impl UploadIndex {
+    pub fn load() -> Self {
+        fs::read_to_string(index_path())
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = index_path();
+        ensure_dir(path.parent().unwrap())?;
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// A remembered upload that has not yet expired.
+    pub fn lookup(&self, key: &str) -> Option<&UploadRecord> {
+        self.entries
+            .get(key)
+            .filter(|record| record.is_live(OffsetDateTime::now_utc()))
+    }
+
+    /// Remember an upload, dropping any entries that have since expired.
+    pub fn record(key: &str, record: UploadRecord) -> Result<()> {
+        let mut index = Self::load();
+        let now = OffsetDateTime::now_utc();
+        index.entries.retain(|_, existing| existing.is_live(now));
+        index.entries.insert(key.to_string(), record);
+        index.save()
+    }
+
+    /// Drop an entry the Files API no longer serves.
+    pub fn forget(key: &str) -> Result<()> {
+        let mut index = Self::load();
+        if index.entries.remove(key).is_some() {
+            index.save()?;
+        }
+        Ok(())
+    }
+
+    /// Drop the entries for uploads deleted by name (`files/...`).
+    pub fn forget_files(names: &[&str]) -> Result<()> {
+        let mut index = Self::load();
+        let before = index.entries.len();
+        index.entries.retain(|_, record| {
+            !record
+                .name
+                .as_deref()
+                .is_some_and(|name| names.contains(&name))
+        });
+        if index.entries.len() != before {
+            index.save()?;
+        }
+        Ok(())
+    }
+}
+
+/// One entry of the Files API listing, for `recapit cleanup remote`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteFile {
+    /// `files/...`
+    pub name: String,
+    pub display_name: Option<String>,
+    pub size_bytes: u64,
+    pub state: Option<String>,
+    /// RFC 3339.
+    pub created_at: Option<String>,
+    pub expires_at: Option<String>,
+}
+
+impl RemoteFile {
+    pub fn from_listing(entry: &Value) -> Option<Self> {
+        let text = |key: &str| entry.get(key).and_then(Value::as_str).map(str::to_string);
+        Some(Self {
+            name: text("name")?,
+            display_name: text("displayName"),
+            // int64 fields arrive as strings.
+            size_bytes: text("sizeBytes")
+                .and_then(|size| size.parse().ok())
+                .unwrap_or_default(),
+            state: text("state"),
+            created_at: text("createTime"),
+            expires_at: text("expirationTime"),
+        })
+    }
+
+    /// How long ago it was uploaded, when the listing says.
+    pub fn age(&self, now: OffsetDateTime) -> Option<Duration> {
+        self.created_at
+            .as_deref()
+            .and_then(|value| OffsetDateTime::parse(value, &Rfc3339).ok())
+            .map(|created| now - created)
+    }
+
+    /// Whether it is older than `older_than` and its display name (or id) starts
+    /// with `prefix`. With neither set, every file matches.
+    pub fn matches(
+        &self,
+        now: OffsetDateTime,
+        older_than: Option<std::time::Duration>,
+        prefix: Option<&str>,
+    ) -> bool {
+        let old_enough =
+            older_than.is_none_or(|limit| self.age(now).is_some_and(|age| age > limit));
+        let named = prefix.is_none_or(|prefix| {
+            self.display_name
+                .as_deref()
+                .is_some_and(|name| name.starts_with(prefix))
+                || self.name.trim_start_matches("files/").starts_with(prefix)
+        });
+        old_enough && named
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_near_expiry_are_not_live() {
+        let now = OffsetDateTime::now_utc();
+        let record = |expires: Option<OffsetDateTime>| UploadRecord {
+            uri: "https://example.invalid/files/abc".into(),
+            mime_type: "video/mp4".into(),
+            name: Some("files/abc".into()),
+            expires_at: expires.map(|at| at.format(&Rfc3339).unwrap()),
+        };
+        assert!(record(Some(now + Duration::hours(47))).is_live(now));
+        assert!(!record(Some(now + Duration::minutes(30))).is_live(now));
+        assert!(!record(None).is_live(now));
+    }
+
+    #[test]
+    fn remote_files_match_by_age_and_prefix() {
+        let now = OffsetDateTime::now_utc();
+        let created = (now - Duration::hours(30)).format(&Rfc3339).unwrap();
+        let file = RemoteFile::from_listing(&serde_json::json!({
+            "name": "files/abc123",
+            "displayName": "lecture-01.mp4",
+            "sizeBytes": "2048",
+            "createTime": created,
+        }))
+        .unwrap();
+        assert_eq!(file.size_bytes, 2048);
+        let hours = |n: u64| Some(std::time::Duration::from_secs(n * 3600));
+        assert!(file.matches(now, None, None));
+        assert!(file.matches(now, hours(24), Some("lecture")));
+        assert!(file.matches(now, None, Some("abc")));
+        assert!(!file.matches(now, hours(48), None));
+        assert!(!file.matches(now, hours(24), Some("slides")));
+    }
+}
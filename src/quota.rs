@@ -1,10 +1,16 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
 use tracing::warn;
 
+use crate::utils::ensure_dir;
+
 #[derive(Debug, Clone)]
 pub struct QuotaConfig {
     pub request_limits: HashMap<String, u32>,
@@ -46,12 +52,36 @@ struct QuotaState {
     last_token_warn: HashMap<String, Instant>,
     uploaded_bytes: u64,
     active_uploads: u32,
+    /// Earliest time the server will accept another request, from `Retry-After`/`RetryInfo`.
+    blocked_until: HashMap<String, Instant>,
+    /// Per-minute request limits reported by the server, tighter than the static table.
+    learned_limits: HashMap<String, u32>,
+}
+
+/// Request and token timestamps as written to the quota state file, in Unix milliseconds.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PersistedWindows {
+    #[serde(default)]
+    requests: BTreeMap<String, Vec<u64>>,
+    #[serde(default)]
+    tokens: BTreeMap<String, Vec<(u64, u32)>>,
+}
+
+/// Where `recapit` keeps recent request/token timestamps between invocations.
+pub fn default_state_path() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("recapit")
+        .join("quota.json")
 }
 
 #[derive(Clone)]
 pub struct QuotaMonitor {
     config: Arc<QuotaConfig>,
     state: Arc<Mutex<QuotaState>>,
+    state_file: Option<Arc<PathBuf>>,
+    /// One clock reading shared by every conversion, so a stamp round-trips exactly.
+    epoch: (Instant, u64),
 }
 
 impl QuotaMonitor {
@@ -59,17 +89,142 @@ impl QuotaMonitor {
         Self {
             config: Arc::new(config),
             state: Arc::new(Mutex::new(QuotaState::default())),
+            state_file: None,
+            epoch: (Instant::now(), unix_millis(SystemTime::now())),
         }
     }
 
-    pub fn register_request(&self, model: &str) -> Option<Duration> {
-        let per_minute = match self.config.request_limits.get(model) {
-            Some(value) if *value > 0 => *value,
-            _ => return None,
+    /// Seed the sliding windows from `path` and keep it updated, so back-to-back
+    /// runs see each other's requests. A missing or unreadable file starts empty.
+    pub fn with_state_file(mut self, path: PathBuf) -> Self {
+        let persisted = read_windows(&path);
+        {
+            let mut state = self.state.lock().unwrap();
+            for (model, stamps) in persisted.requests {
+                let window = state.request_windows.entry(model).or_default();
+                window.extend(stamps.into_iter().filter_map(|ms| self.recent_instant(ms)));
+                window.make_contiguous().sort();
+            }
+            for (model, entries) in persisted.tokens {
+                let window = state.token_windows.entry(model).or_default();
+                window.extend(
+                    entries
+                        .into_iter()
+                        .filter_map(|(ms, tokens)| self.recent_instant(ms).map(|at| (at, tokens))),
+                );
+                window.make_contiguous().sort_by_key(|(at, _)| *at);
+            }
+        }
+        self.state_file = Some(Arc::new(path));
+        self
+    }
+
+    /// The `Instant` matching a Unix-millisecond stamp, if it is still inside the window.
+    fn recent_instant(&self, ms: u64) -> Option<Instant> {
+        let (epoch_instant, epoch_ms) = self.epoch;
+        let at = epoch_instant.checked_sub(Duration::from_millis(epoch_ms.checked_sub(ms)?))?;
+        (at.elapsed() <= self.config.request_window).then_some(at)
+    }
+
+    fn to_millis(&self, at: &Instant) -> u64 {
+        let (epoch_instant, epoch_ms) = self.epoch;
+        if *at >= epoch_instant {
+            epoch_ms + at.duration_since(epoch_instant).as_millis() as u64
+        } else {
+            epoch_ms.saturating_sub(epoch_instant.duration_since(*at).as_millis() as u64)
+        }
+    }
+
+    /// Merge this process's windows into the state file; concurrent runs each add
+    /// their own stamps, and anything older than the window is dropped.
+    fn persist(&self, state: &QuotaState) {
+        let Some(path) = &self.state_file else {
+            return;
         };
+        let to_ms = |at: &Instant| self.to_millis(at);
+        let cutoff = self
+            .to_millis(&Instant::now())
+            .saturating_sub(self.config.request_window.as_millis() as u64);
+
+        let mut merged = read_windows(path);
+        for (model, window) in &state.request_windows {
+            merged
+                .requests
+                .entry(model.clone())
+                .or_default()
+                .extend(window.iter().map(to_ms));
+        }
+        for (model, window) in &state.token_windows {
+            merged
+                .tokens
+                .entry(model.clone())
+                .or_default()
+                .extend(window.iter().map(|(at, tokens)| (to_ms(at), *tokens)));
+        }
+        for stamps in merged.requests.values_mut() {
+            stamps.retain(|ms| *ms >= cutoff);
+            stamps.sort_unstable();
+            stamps.dedup();
+        }
+        for entries in merged.tokens.values_mut() {
+            entries.retain(|(ms, _)| *ms >= cutoff);
+            entries.sort_unstable();
+            entries.dedup();
+        }
+        merged.requests.retain(|_, stamps| !stamps.is_empty());
+        merged.tokens.retain(|_, entries| !entries.is_empty());
+        if let Err(err) = write_windows(path, &merged) {
+            warn!("saving quota state: {err:#}");
+        }
+    }
+
+    /// Feed back a throttling response: hold `model` for `retry_after` and, when the
+    /// server named its per-minute request limit, pace against that from now on.
+    pub fn register_throttle(
+        &self,
+        model: &str,
+        retry_after: Duration,
+        request_limit: Option<u32>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let until = Instant::now() + retry_after;
+        let blocked = state
+            .blocked_until
+            .entry(model.to_string())
+            .or_insert(until);
+        *blocked = (*blocked).max(until);
+        if let Some(limit) = request_limit.filter(|limit| *limit > 0) {
+            let learned = state
+                .learned_limits
+                .entry(model.to_string())
+                .or_insert(limit);
+            *learned = (*learned).min(limit);
+        }
+    }
+
+    pub fn register_request(&self, model: &str) -> Option<Duration> {
         let mut state = self.state.lock().unwrap();
-        let window = state.request_windows.entry(model.to_string()).or_default();
         let now = Instant::now();
+        // A pause the server asked for is honoured in full, unlike the short
+        // preemptive sleep used for our own estimates.
+        let hold = match state.blocked_until.get(model).copied() {
+            Some(until) if until > now => Some(until - now),
+            Some(_) => {
+                state.blocked_until.remove(model);
+                None
+            }
+            None => None,
+        };
+        let configured = self.config.request_limits.get(bucket_model(model)).copied();
+        let learned = state.learned_limits.get(model).copied();
+        let per_minute = match (configured, learned) {
+            (Some(a), Some(b)) => a.min(b),
+            (a, b) => a.or(b).unwrap_or(0),
+        };
+        if per_minute == 0 {
+            return hold;
+        }
+        let window = state.request_windows.entry(model.to_string()).or_default();
         window.push_back(now);
         while let Some(front) = window.front() {
             if now.duration_since(*front) > self.config.request_window {
@@ -79,6 +234,7 @@ impl QuotaMonitor {
             }
         }
         let utilization = window.len() as f64 / per_minute as f64;
+        self.persist(&state);
         if utilization >= self.config.rpm_warn_threshold {
             let entry = state
                 .last_rpm_warn
@@ -94,20 +250,50 @@ impl QuotaMonitor {
                 *entry = now;
             }
         }
+        let mut pace = None;
         if utilization >= self.config.rpm_sleep_threshold {
-            let window_seconds = self.config.request_window.as_secs_f64();
-            let per_request = if per_minute > 0 {
-                window_seconds / per_minute as f64
+            let per_request = self.config.request_window.as_secs_f64() / per_minute as f64;
+            // Limits the server reported are real, so space requests out fully.
+            let sleep = if learned.is_some() {
+                per_request
             } else {
-                0.0
+                self.config
+                    .max_preemptive_sleep
+                    .as_secs_f64()
+                    .min(per_request)
             };
-            let sleep = self
-                .config
-                .max_preemptive_sleep
-                .as_secs_f64()
-                .min(per_request);
             if sleep > 0.0 {
-                return Some(Duration::from_secs_f64(sleep));
+                pace = Some(Duration::from_secs_f64(sleep));
+            }
+        }
+        hold.max(pace)
+    }
+
+    /// How long to wait before sending a request of `tokens` input tokens so the
+    /// model's per-minute token window has room for it. `None` when it fits now.
+    pub fn token_delay(&self, model: &str, tokens: u64) -> Option<Duration> {
+        let limit = match self.config.token_limits.get(bucket_model(model)) {
+            Some(value) if *value > 0 => *value as u64,
+            _ => return None,
+        };
+        let state = self.state.lock().unwrap();
+        let window = state.token_windows.get(model)?;
+        let now = Instant::now();
+        let live: Vec<&(Instant, u32)> = window
+            .iter()
+            .filter(|(instant, _)| now.duration_since(*instant) <= self.config.request_window)
+            .collect();
+        let mut used: u64 = live.iter().map(|(_, tokens)| *tokens as u64).sum();
+        // A request larger than the whole window only has to wait for an empty one.
+        let needed = tokens.min(limit);
+        for (instant, spent) in live {
+            if used + needed <= limit {
+                break;
+            }
+            used -= *spent as u64;
+            let expires = *instant + self.config.request_window;
+            if used + needed <= limit {
+                return Some(expires.saturating_duration_since(now));
             }
         }
         None
@@ -117,7 +303,7 @@ impl QuotaMonitor {
         let Some(total_tokens) = total_tokens else {
             return;
         };
-        let limit = match self.config.token_limits.get(model) {
+        let limit = match self.config.token_limits.get(bucket_model(model)) {
             Some(value) if *value > 0 => *value,
             _ => return,
         };
@@ -134,6 +320,7 @@ impl QuotaMonitor {
         }
         let used: u64 = window.iter().map(|(_, tokens)| *tokens as u64).sum();
         let utilization = used as f64 / limit as f64;
+        self.persist(&state);
         if utilization >= self.config.token_warn_threshold {
             let entry = state
                 .last_token_warn
@@ -206,3 +393,66 @@ impl Drop for UploadGuard {
         self.monitor.finish_upload(self.size_bytes);
     }
 }
+
+/// The model whose limits a bucket counts against. With several API keys each key
+/// has its own `model#key` bucket, all paced against the model's per-key limits.
+fn bucket_model(bucket: &str) -> &str {
+    bucket.split_once('#').map_or(bucket, |(model, _)| model)
+}
+
+fn unix_millis(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn read_windows(path: &Path) -> PersistedWindows {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn write_windows(path: &Path, windows: &PersistedWindows) -> Result<()> {
+    ensure_dir(path.parent().unwrap_or(Path::new(".")))?;
+    let partial = path.with_extension("partial");
+    fs::write(&partial, serde_json::to_string(windows)?)
+        .with_context(|| format!("writing {}", partial.display()))?;
+    fs::rename(&partial, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_survive_a_restart() {
+        let path = env::temp_dir().join(format!("recapit-quota-{}.json", std::process::id()));
+        let config = || {
+            QuotaConfig::new(
+                HashMap::from([("m".to_string(), 10)]),
+                HashMap::from([("m".to_string(), 1_000)]),
+            )
+        };
+        let first = QuotaMonitor::new(config()).with_state_file(path.clone());
+        first.register_request("m");
+        first.register_tokens("m", Some(400));
+
+        // Stamps are kept at millisecond resolution; keep the two requests apart.
+        std::thread::sleep(Duration::from_millis(5));
+        let second = QuotaMonitor::new(config()).with_state_file(path.clone());
+        second.register_request("m");
+        {
+            let state = second.state.lock().unwrap();
+            assert_eq!(state.request_windows["m"].len(), 2);
+            assert_eq!(state.token_windows["m"].len(), 1);
+        }
+        let saved = read_windows(&path);
+        assert_eq!(saved.requests["m"].len(), 2);
+        // 400 of 1,000 tokens used: 600 more fit now, 700 wait for the window.
+        assert_eq!(second.token_delay("m", 600), None);
+        assert!(second.token_delay("m", 700).unwrap() > Duration::from_secs(50));
+        fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,171 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+use super::subtitles::{timestamped_cues, Cue};
+use crate::tools::{self, Tool};
+
+/// Without timestamped headings, consecutive cues are merged until a chapter spans this long.
+const MIN_CHAPTER_SECONDS: f64 = 120.0;
+const MAX_TITLE_CHARS: usize = 60;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub start: f64,
+    pub end: f64,
+    pub title: String,
+}
+
+/// Derive a chapter timeline from the transcript's timestamps.
+///
+/// Timestamped headings (`## [12:30] Entropy`) become chapters directly; otherwise
+/// timestamped lines are grouped into chapters of at least two minutes, titled by
+/// their opening words.
+pub fn chapters_from_transcript(text: &str, chunks: &[Value]) -> Vec<Chapter> {
+    let cues = timestamped_cues(text, chunks);
+    let Some(last_end) = cues.iter().map(|cue| cue.end).reduce(f64::max) else {
+        return Vec::new();
+    };
+    let anchors: Vec<&Cue> = if cues.iter().any(|cue| cue.heading.is_some()) {
+        cues.iter().filter(|cue| cue.heading.is_some()).collect()
+    } else {
+        let mut anchors: Vec<&Cue> = Vec::new();
+        for cue in &cues {
+            let far_enough = anchors
+                .last()
+                .is_none_or(|prev| cue.start - prev.start >= MIN_CHAPTER_SECONDS);
+            if far_enough {
+                anchors.push(cue);
+            }
+        }
+        anchors
+    };
+    anchors
+        .iter()
+        .enumerate()
+        .map(|(idx, cue)| Chapter {
+            start: cue.start,
+            end: anchors
+                .get(idx + 1)
+                .map(|next| next.start)
+                .unwrap_or(last_end)
+                .max(cue.start),
+            title: chapter_title(cue.heading.as_deref().unwrap_or(&cue.text), idx),
+        })
+        .collect()
+}
+
+fn chapter_title(text: &str, idx: usize) -> String {
+    let title = text.trim();
+    if title.is_empty() {
+        return format!("Chapter {}", idx + 1);
+    }
+    if title.chars().count() <= MAX_TITLE_CHARS {
+        return title.to_string();
+    }
+    let cut: String = title.chars().take(MAX_TITLE_CHARS).collect();
+    let cut = cut.rsplit_once(' ').map(|(head, _)| head).unwrap_or(&cut);
+    format!("{}…", cut.trim_end_matches([',', ';', ':', '.']))
+}
+
+/// Write an FFMETADATA1 file that `ffmpeg -map_chapters` understands.
+pub fn write_ffmetadata(chapters: &[Chapter], target: &Path) -> Result<()> {
+    let mut out = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        out.push_str("\n[CHAPTER]\nTIMEBASE=1/1000\n");
+        out.push_str(&format!(
+            "START={}\n",
+            (chapter.start * 1000.0).round() as i64
+        ));
+        out.push_str(&format!("END={}\n", (chapter.end * 1000.0).round() as i64));
+        out.push_str(&format!("title={}\n", escape_metadata(&chapter.title)));
+    }
+    fs::write(target, out).with_context(|| format!("writing {}", target.display()))
+}
+
+fn escape_metadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if matches!(ch, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+/// Remux `video` with the chapters (and optionally a WebVTT track) into `target`
+/// without re-encoding.
+pub fn embed_chapters(
+    video: &Path,
+    metadata: &Path,
+    subtitles: Option<&Path>,
+    target: &Path,
+) -> Result<PathBuf> {
+    let mut command = tools::command(Tool::Ffmpeg);
+    command
+        .arg("-y")
+        .arg("-i")
+        .arg(video)
+        .arg("-i")
+        .arg(metadata);
+    if let Some(subtitles) = subtitles {
+        command.arg("-i").arg(subtitles);
+    }
+    command.args(["-map", "0:v?", "-map", "0:a?"]);
+    if subtitles.is_some() {
+        command.args([
+            "-map",
+            "2:s",
+            "-c:s",
+            "mov_text",
+            "-metadata:s:s:0",
+            "language=und",
+        ]);
+    }
+    command
+        .args([
+            "-map_metadata",
+            "1",
+            "-map_chapters",
+            "1",
+            "-c:v",
+            "copy",
+            "-c:a",
+            "copy",
+        ])
+        .arg(target);
+    let output = command.output().context("invoking ffmpeg")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let tail: Vec<&str> = stderr.lines().rev().take(5).collect();
+        bail!(
+            "ffmpeg failed to embed chapters into {}: {}",
+            video.display(),
+            tail.into_iter().rev().collect::<Vec<_>>().join("\n")
+        );
+    }
+    Ok(target.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_timestamped_headings_for_chapters() {
+        let text = "## [00:00] Introduction\nOpening remarks.\n[00:10] Welcome.\n\n## [05:30] Entropy; a = b\n[06:00] Surprise.";
+        let chapters = chapters_from_transcript(text, &[]);
+        assert_eq!(chapters.len(), 2);
+        assert_eq!((chapters[0].start, chapters[0].end), (0.0, 330.0));
+        assert_eq!(chapters[1].title, "Entropy; a = b");
+        assert_eq!(escape_metadata("a = b; c"), "a \\= b\\; c");
+
+        let plain = "[00:00] One.\n[00:30] Two.\n[02:05] Three.";
+        let grouped = chapters_from_transcript(plain, &[]);
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped[1].start, 125.0);
+    }
+}
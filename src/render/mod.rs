@@ -1,2 +1,7 @@
-pub mod subtitles;
+pub(crate) mod chapters;
+pub(crate) mod pdf;
+pub(crate) mod structured;
+pub(crate) mod subtitles;
+pub(crate) mod tables;
+pub(crate) mod validate;
 pub mod writer;
@@ -0,0 +1,89 @@
+use crate::core::OutputFormat;
+use anyhow::{anyhow, bail, Context};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Compile a written transcript to a PDF next to it.
+///
+/// LaTeX goes through `tectonic` (preferred, self-contained) or `latexmk`; Markdown, Org and
+/// reST go through `pandoc`, using `typst` as the PDF engine when it is installed.
+pub fn compile_pdf(format: OutputFormat, source: &Path) -> anyhow::Result<PathBuf> {
+    let dir = source
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let target = source.with_extension("pdf");
+    let output = match format {
+        OutputFormat::Latex => compile_latex(source, dir)?,
+        OutputFormat::Markdown | OutputFormat::Org | OutputFormat::Rst => {
+            render_with_pandoc(source, &target)?
+        }
+        OutputFormat::Json => bail!("PDF export is not available for JSON output"),
+    };
+    if !output.status.success() || !target.exists() {
+        bail!(
+            "PDF compilation of {} failed: {}",
+            source.display(),
+            failure_excerpt(&output)
+        );
+    }
+    Ok(target)
+}
+
+fn compile_latex(source: &Path, dir: &Path) -> anyhow::Result<Output> {
+    if let Ok(tectonic) = which::which("tectonic") {
+        return Command::new(tectonic)
+            .arg("--keep-logs")
+            .arg("--outdir")
+            .arg(dir)
+            .arg(source)
+            .output()
+            .context("invoking tectonic");
+    }
+    let latexmk = which::which("latexmk")
+        .map_err(|_| anyhow!("neither tectonic nor latexmk found; install one to export PDF"))?;
+    let output = Command::new(&latexmk)
+        .arg("-pdf")
+        .arg("-interaction=nonstopmode")
+        .arg("-halt-on-error")
+        .arg(format!("-outdir={}", dir.display()))
+        .arg(source)
+        .output()
+        .context("invoking latexmk")?;
+    if output.status.success() {
+        // Drop .aux/.fls/.fdb_latexmk but keep the PDF and log.
+        let _ = Command::new(&latexmk)
+            .arg("-c")
+            .arg(format!("-outdir={}", dir.display()))
+            .arg(source)
+            .output();
+    }
+    Ok(output)
+}
+
+fn render_with_pandoc(source: &Path, target: &Path) -> anyhow::Result<Output> {
+    let pandoc = which::which("pandoc")
+        .map_err(|_| anyhow!("pandoc not found; install pandoc (and typst) to export PDF"))?;
+    let mut command = Command::new(pandoc);
+    command.arg(source).arg("-o").arg(target);
+    if which::which("typst").is_ok() {
+        command.arg("--pdf-engine=typst");
+    } else if which::which("tectonic").is_ok() {
+        command.arg("--pdf-engine=tectonic");
+    }
+    command.output().context("invoking pandoc")
+}
+
+/// The tail of the tool output, which is where TeX and pandoc report the actual error.
+fn failure_excerpt(output: &Output) -> String {
+    let mut text = String::from_utf8_lossy(&output.stderr).to_string();
+    if text.trim().is_empty() {
+        text = String::from_utf8_lossy(&output.stdout).to_string();
+    }
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    let start = lines.len().saturating_sub(8);
+    lines[start..].join("\n")
+}
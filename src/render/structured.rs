@@ -0,0 +1,240 @@
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::providers::ResponseSchema;
+
+/// Shape requested from the model for `--format json`; substituted for `{{SCHEMA}}` in prompts.
+pub const STRUCTURED_SCHEMA: &str = r#"{
+  "title": "string or null",
+  "summary": "string or null (two or three sentences)",
+  "sections": [
+    {
+      "heading": "string",
+      "start": "MM:SS or HH:MM:SS, or null when the source has no timing",
+      "end": "MM:SS or HH:MM:SS, or null",
+      "content": "string (Markdown; math as $...$ or $$...$$)"
+    }
+  ],
+  "key_terms": [{"term": "string", "definition": "string or null"}],
+  "equations": [{"latex": "string", "description": "string or null"}]
+}"#;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StructuredTranscript {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub summary: Option<String>,
+    pub sections: Vec<TranscriptSection>,
+    #[serde(default)]
+    pub key_terms: Vec<KeyTerm>,
+    #[serde(default)]
+    pub equations: Vec<Equation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptSection {
+    pub heading: String,
+    #[serde(default)]
+    pub start: Option<String>,
+    #[serde(default)]
+    pub end: Option<String>,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyTerm {
+    pub term: String,
+    #[serde(default)]
+    pub definition: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Equation {
+    pub latex: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl ResponseSchema for StructuredTranscript {
+    fn response_schema() -> Value {
+        let nullable_string = json!({"type": "STRING", "nullable": true});
+        json!({
+            "type": "OBJECT",
+            "properties": {
+                "title": nullable_string,
+                "summary": nullable_string,
+                "sections": {
+                    "type": "ARRAY",
+                    "items": {
+                        "type": "OBJECT",
+                        "properties": {
+                            "heading": {"type": "STRING"},
+                            "start": nullable_string,
+                            "end": nullable_string,
+                            "content": {"type": "STRING"},
+                        },
+                        "required": ["heading", "content"],
+                        "propertyOrdering": ["heading", "start", "end", "content"],
+                    },
+                },
+                "key_terms": {
+                    "type": "ARRAY",
+                    "items": {
+                        "type": "OBJECT",
+                        "properties": {
+                            "term": {"type": "STRING"},
+                            "definition": nullable_string,
+                        },
+                        "required": ["term"],
+                    },
+                },
+                "equations": {
+                    "type": "ARRAY",
+                    "items": {
+                        "type": "OBJECT",
+                        "properties": {
+                            "latex": {"type": "STRING"},
+                            "description": nullable_string,
+                        },
+                        "required": ["latex"],
+                    },
+                },
+            },
+            "required": ["sections"],
+            "propertyOrdering": ["title", "summary", "sections", "key_terms", "equations"],
+        })
+    }
+}
+
+fn timestamp_pattern() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^\d{1,2}:\d{2}(:\d{2})?$").unwrap())
+}
+
+impl StructuredTranscript {
+    /// Parse and validate a model response.
+    ///
+    /// Chunked runs return one JSON document per chunk; those are merged in order,
+    /// keeping the first title and de-duplicating key terms.
+    pub fn parse(text: &str) -> Result<Self> {
+        let cleaned = strip_code_fences(text);
+        let mut merged: Option<StructuredTranscript> = None;
+        let mut seen_terms = HashSet::new();
+        for document in serde_json::Deserializer::from_str(&cleaned).into_iter::<Self>() {
+            let document = document.context("response is not a valid structured transcript")?;
+            let target = merged.get_or_insert_with(Self::default);
+            if target.title.is_none() {
+                target.title = document.title;
+            }
+            target.summary = match (target.summary.take(), document.summary) {
+                (Some(existing), Some(next)) => Some(format!("{existing}\n\n{next}")),
+                (existing, next) => existing.or(next),
+            };
+            target.sections.extend(document.sections);
+            for term in document.key_terms {
+                if seen_terms.insert(term.term.to_lowercase()) {
+                    target.key_terms.push(term);
+                }
+            }
+            target.equations.extend(document.equations);
+        }
+        let transcript = merged.ok_or_else(|| anyhow!("response contained no JSON document"))?;
+        transcript.validate()?;
+        Ok(transcript)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.sections.is_empty() {
+            bail!("structured transcript has no sections");
+        }
+        for (idx, section) in self.sections.iter().enumerate() {
+            for stamp in [&section.start, &section.end].into_iter().flatten() {
+                if !timestamp_pattern().is_match(stamp.trim()) {
+                    bail!("section {} has malformed timestamp {stamp:?}", idx + 1);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Markdown rendering used for subtitle and Markdown exports of a JSON run.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        if let Some(title) = &self.title {
+            out.push_str(&format!("# {title}\n\n"));
+        }
+        if let Some(summary) = &self.summary {
+            out.push_str(summary.trim());
+            out.push_str("\n\n");
+        }
+        for section in &self.sections {
+            match &section.start {
+                Some(start) => out.push_str(&format!("## [{start}] {}\n\n", section.heading)),
+                None => out.push_str(&format!("## {}\n\n", section.heading)),
+            }
+            out.push_str(section.content.trim());
+            out.push_str("\n\n");
+        }
+        if !self.key_terms.is_empty() {
+            out.push_str("## Key Terms\n\n");
+            for term in &self.key_terms {
+                match &term.definition {
+                    Some(definition) => {
+                        out.push_str(&format!("- **{}**: {definition}\n", term.term))
+                    }
+                    None => out.push_str(&format!("- **{}**\n", term.term)),
+                }
+            }
+            out.push('\n');
+        }
+        if !self.equations.is_empty() {
+            out.push_str("## Equations\n\n");
+            for equation in &self.equations {
+                out.push_str(&format!("$$\n{}\n$$\n", equation.latex.trim()));
+                if let Some(description) = &equation.description {
+                    out.push_str(&format!("\n{description}\n"));
+                }
+                out.push('\n');
+            }
+        }
+        out.trim_end().to_string()
+    }
+}
+
+pub(crate) fn strip_code_fences(text: &str) -> String {
+    text.lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_chunk_documents_and_validates() {
+        let text = r#"```json
+{"title": "Lecture 3", "sections": [{"heading": "Intro", "start": "00:00", "content": "Welcome."}],
+ "key_terms": [{"term": "Entropy", "definition": "Average surprise."}]}
+```
+
+{"title": "ignored", "sections": [{"heading": "Proof", "start": "12:30", "end": "1:02:00", "content": "$H(X) \\ge 0$"}],
+ "key_terms": [{"term": "entropy"}], "equations": [{"latex": "H(X) = -\\sum p \\log p"}]}"#;
+        let transcript = StructuredTranscript::parse(text).unwrap();
+        assert_eq!(transcript.title.as_deref(), Some("Lecture 3"));
+        assert_eq!(transcript.sections.len(), 2);
+        assert_eq!(transcript.key_terms.len(), 1);
+        assert!(transcript.to_markdown().contains("## [12:30] Proof"));
+
+        let bad = r#"{"sections": [{"heading": "x", "start": "soon", "content": ""}]}"#;
+        assert!(StructuredTranscript::parse(bad).is_err());
+        assert!(StructuredTranscript::parse("not json").is_err());
+    }
+}
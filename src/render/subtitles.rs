@@ -1,7 +1,14 @@
 use anyhow::Result;
+use regex::Regex;
 use serde_json::Value;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::video::TimeMap;
+
+/// Length given to a cue whose end cannot be inferred from the transcript or chunks.
+const MIN_CUE_SECONDS: f64 = 2.0;
 
 #[derive(Default, Clone)]
 pub struct SubtitleExporter;
@@ -16,79 +23,215 @@ impl SubtitleExporter {
         chunks: &[Value],
     ) -> Result<Option<PathBuf>> {
         let fmt = fmt.trim().to_lowercase();
-        if fmt != "srt" && fmt != "vtt" {
-            return Ok(None);
-        }
+        let format = match fmt.as_str() {
+            "srt" => Format::Srt,
+            "vtt" => Format::Vtt,
+            _ => return Ok(None),
+        };
         fs::create_dir_all(base)?;
         let target = base.join(format!("{name}.{fmt}"));
-        let segments = split_text(text, chunks.len());
+        let mut cues = timestamped_cues(text, chunks);
+        if cues.is_empty() {
+            cues = chunk_cues(text, chunks);
+        }
         let mut lines = Vec::new();
-        if fmt == "vtt" {
+        if let Format::Vtt = format {
             lines.push("WEBVTT".to_string());
             lines.push(String::new());
         }
-        for (idx, chunk) in chunks.iter().enumerate() {
-            let segment = segments.get(idx).cloned().unwrap_or_default();
-            let start = chunk
-                .get("start_seconds")
-                .and_then(Value::as_f64)
-                .unwrap_or((idx * 5) as f64);
-            let end = chunk
-                .get("end_seconds")
-                .and_then(Value::as_f64)
-                .unwrap_or(start + 5.0);
-            if fmt == "srt" {
+        for (idx, cue) in cues.iter().enumerate() {
+            if let Format::Srt = format {
                 lines.push((idx + 1).to_string());
-                lines.push(format!(
-                    "{} --> {}",
-                    format_timestamp(start, Format::Srt),
-                    format_timestamp(end, Format::Srt)
-                ));
-                lines.push(if segment.is_empty() {
-                    "[No content]".to_string()
-                } else {
-                    segment
-                });
-                lines.push(String::new());
-            } else {
-                lines.push(format!(
-                    "{} --> {}",
-                    format_timestamp(start, Format::Vtt),
-                    format_timestamp(end, Format::Vtt)
-                ));
-                lines.push(if segment.is_empty() {
-                    "[No content]".to_string()
-                } else {
-                    segment
-                });
-                lines.push(String::new());
             }
-        }
-        if chunks.is_empty() {
-            let start = 0.0;
-            let end = 5.0;
-            if fmt == "srt" {
-                lines.push("1".into());
-                lines.push(format!(
-                    "{} --> {}",
-                    format_timestamp(start, Format::Srt),
-                    format_timestamp(end, Format::Srt)
-                ));
-                lines.push(text.trim().to_string());
+            lines.push(format!(
+                "{} --> {}",
+                format_timestamp(cue.start, format),
+                format_timestamp(cue.end, format)
+            ));
+            lines.push(if cue.text.is_empty() {
+                "[No content]".to_string()
             } else {
-                lines.push(format!(
-                    "{} --> {}",
-                    format_timestamp(start, Format::Vtt),
-                    format_timestamp(end, Format::Vtt)
-                ));
-                lines.push(text.trim().to_string());
-            }
+                cue.text.clone()
+            });
+            lines.push(String::new());
         }
         std::fs::write(&target, lines.join("\n"))?;
         Ok(Some(target))
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Cue {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    /// Heading text when the timestamp introduced a heading (`## [12:30] Topic`).
+    pub heading: Option<String>,
+}
+
+/// A timestamp found in the transcript, already shifted onto the source timeline.
+struct Marker {
+    start: f64,
+    end: Option<f64>,
+    chunk_end: Option<f64>,
+    heading: Option<String>,
+    lines: Vec<String>,
+}
+
+fn timestamp_line() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"^\s*(?:#{1,6}\s+|[-*+]\s+|\\item\s*)?(?:\*\*|__)?\[(?:(\d{1,2}):)?(\d{1,2}):(\d{2})(?:\s*[-–]\s*(?:(\d{1,2}):)?(\d{1,2}):(\d{2}))?\](?:\*\*|__)?[\s:–-]*(.*)$",
+        )
+        .unwrap()
+    })
+}
+
+fn clock_seconds(hours: Option<regex::Match>, minutes: &str, seconds: &str) -> f64 {
+    let hours = hours
+        .and_then(|h| h.as_str().parse::<f64>().ok())
+        .unwrap_or(0.0);
+    hours * 3600.0
+        + minutes.parse::<f64>().unwrap_or(0.0) * 60.0
+        + seconds.parse::<f64>().unwrap_or(0.0)
+}
+
+fn chunk_seconds(chunk: &Value, key: &str) -> Option<f64> {
+    chunk
+        .get(format!("chunk_{key}"))
+        .or_else(|| chunk.get(key))
+        .and_then(Value::as_f64)
+}
+
+/// The chunk's map from condensed to original times, when silences were cut.
+fn chunk_time_map(chunk: &Value) -> Option<TimeMap> {
+    chunk
+        .get("time_map")
+        .and_then(|map| serde_json::from_value(map.clone()).ok())
+}
+
+/// Build cues from `[MM:SS]` / `[HH:MM:SS]` markers in the transcript.
+///
+/// Chunked runs ask the model about each clip separately, so its timestamps restart
+/// at zero for every chunk. A marker that jumps backwards is taken as the start of
+/// the next chunk and shifted by that chunk's start offset. Chunks cut from a
+/// recording with its silences removed carry a time map back to the original.
+pub(crate) fn timestamped_cues(text: &str, chunks: &[Value]) -> Vec<Cue> {
+    let bounds: Vec<(f64, Option<f64>)> = chunks
+        .iter()
+        .filter_map(|chunk| {
+            chunk_seconds(chunk, "start_seconds")
+                .map(|start| (start, chunk_seconds(chunk, "end_seconds")))
+        })
+        .collect();
+    let maps: Vec<Option<TimeMap>> = chunks
+        .iter()
+        .filter(|chunk| chunk_seconds(chunk, "start_seconds").is_some())
+        .map(chunk_time_map)
+        .collect();
+    let mut chunk_idx = 0usize;
+    let mut previous_raw: Option<f64> = None;
+    let mut markers: Vec<Marker> = Vec::new();
+    for line in text.lines() {
+        let Some(caps) = timestamp_line().captures(line) else {
+            let trimmed = line.trim();
+            if let Some(marker) = markers.last_mut() {
+                if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                    marker.lines.push(trimmed.to_string());
+                }
+            }
+            continue;
+        };
+        let raw = clock_seconds(caps.get(1), &caps[2], &caps[3]);
+        let raw_end = caps
+            .get(5)
+            .zip(caps.get(6))
+            .map(|(m, s)| clock_seconds(caps.get(4), m.as_str(), s.as_str()));
+        if previous_raw.is_some_and(|prev| raw + 1.0 < prev) && chunk_idx + 1 < bounds.len() {
+            chunk_idx += 1;
+        }
+        previous_raw = Some(raw);
+        let (offset, chunk_end) = bounds.get(chunk_idx).copied().unwrap_or((0.0, None));
+        // Models sometimes report absolute times for later chunks; leave those alone.
+        let already_absolute = chunk_idx > 0
+            && raw >= offset
+            && chunk_end.is_some_and(|end| raw <= end + 1.0 && raw + offset > end + 1.0);
+        let shift = if already_absolute { 0.0 } else { offset };
+        let to_source = |seconds: f64| match maps.get(chunk_idx) {
+            Some(Some(map)) => map.to_source(seconds),
+            _ => seconds,
+        };
+        let body = caps[7].trim().to_string();
+        markers.push(Marker {
+            start: to_source(raw + shift),
+            end: raw_end.map(|end| to_source(end + shift)),
+            chunk_end: chunk_end.map(to_source),
+            heading: line.trim_start().starts_with('#').then(|| body.clone()),
+            lines: if body.is_empty() { vec![] } else { vec![body] },
+        });
+    }
+
+    let mut cues = Vec::with_capacity(markers.len());
+    for (idx, marker) in markers.iter().enumerate() {
+        let start = marker.start;
+        let next_start = markers.get(idx + 1).map(|next| next.start);
+        let end = marker
+            .end
+            .or(next_start)
+            .or(marker.chunk_end)
+            .filter(|end| *end > start)
+            .unwrap_or(start + MIN_CUE_SECONDS);
+        let text = marker
+            .lines
+            .join(" ")
+            .replace("**", "")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        cues.push(Cue {
+            start,
+            end,
+            text,
+            heading: marker.heading.clone(),
+        });
+    }
+    cues
+}
+
+/// Fallback for transcripts without timestamps: spread paragraphs across the chunk
+/// windows in order, one cue per chunk.
+fn chunk_cues(text: &str, chunks: &[Value]) -> Vec<Cue> {
+    if chunks.is_empty() {
+        return vec![Cue {
+            start: 0.0,
+            end: 5.0,
+            text: text.trim().to_string(),
+            heading: None,
+        }];
+    }
+    let segments = split_text(text, chunks.len());
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(idx, chunk)| {
+            let start = chunk_seconds(chunk, "start_seconds").unwrap_or((idx * 5) as f64);
+            let end = chunk_seconds(chunk, "end_seconds").unwrap_or(start + 5.0);
+            let (start, end) = match chunk_time_map(chunk) {
+                Some(map) => (map.to_source(start), map.to_source(end)),
+                None => (start, end),
+            };
+            Cue {
+                start,
+                end,
+                text: segments.get(idx).cloned().unwrap_or_default(),
+                heading: None,
+            }
+        })
+        .collect()
+}
+
+/// Split paragraphs into `parts` contiguous groups of roughly equal size.
 fn split_text(text: &str, parts: usize) -> Vec<String> {
     let trimmed = text.trim();
     if trimmed.is_empty() {
@@ -104,7 +247,7 @@ fn split_text(text: &str, parts: usize) -> Vec<String> {
     }
     let mut segments = vec![String::new(); parts];
     for (idx, para) in paragraphs.iter().enumerate() {
-        let slot = idx % parts;
+        let slot = idx * parts / paragraphs.len();
         if !segments[slot].is_empty() {
             segments[slot].push_str("\n\n");
         }
@@ -130,3 +273,41 @@ fn format_timestamp(seconds: f64, fmt: Format) -> String {
         Format::Vtt => format!("{hours:02}:{minutes:02}:{secs:02}.{millis:03}"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn aligns_cues_to_transcript_timestamps_across_chunks() {
+        let text = "## Transcript\n\n[00:00] Welcome back.\n\n- **[00:42]** Today: entropy.\nIt measures surprise.\n\n## Transcript\n\n[00:05] Second clip starts.\n[01:02:03] Late absolute marker.";
+        let chunks = vec![
+            json!({"chunk_start_seconds": 0.0, "chunk_end_seconds": 600.0}),
+            json!({"chunk_start_seconds": 600.0, "chunk_end_seconds": 4000.0}),
+        ];
+        let cues = timestamped_cues(text, &chunks);
+        assert_eq!(cues.len(), 4);
+        assert_eq!((cues[0].start, cues[0].end), (0.0, 42.0));
+        assert_eq!(cues[1].text, "Today: entropy. It measures surprise.");
+        assert_eq!(cues[1].end, 605.0);
+        assert_eq!(cues[2].start, 605.0);
+        assert_eq!((cues[3].start, cues[3].end), (3723.0, 4000.0));
+
+        assert!(timestamped_cues("No markers here.", &chunks).is_empty());
+        assert_eq!(chunk_cues("a\n\nb\n\nc\n\nd", &chunks)[0].text, "a\n\nb");
+
+        // 30 seconds of silence were cut at 0:20 of the original.
+        let condensed = vec![json!({
+            "chunk_start_seconds": 0.0,
+            "chunk_end_seconds": 60.0,
+            "time_map": {"spans": [
+                {"start": 0.0, "source_start": 0.0, "duration": 20.0},
+                {"start": 20.0, "source_start": 50.0, "duration": 40.0},
+            ]},
+        })];
+        let cues = timestamped_cues("[00:10] Before.\n[00:30] After.", &condensed);
+        assert_eq!((cues[0].start, cues[0].end), (10.0, 60.0));
+        assert_eq!((cues[1].start, cues[1].end), (60.0, 90.0));
+    }
+}
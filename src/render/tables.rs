@@ -0,0 +1,134 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::providers::ResponseSchema;
+use crate::render::structured::strip_code_fences;
+
+/// Tables pulled out of a LaTeX or Markdown file by `--to json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TableSet {
+    #[serde(default)]
+    pub tables: Vec<Table>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Table {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub headers: Vec<String>,
+    #[serde(default)]
+    pub rows: Vec<Vec<String>>,
+}
+
+impl ResponseSchema for TableSet {
+    fn response_schema() -> Value {
+        json!({
+            "type": "OBJECT",
+            "properties": {
+                "tables": {
+                    "type": "ARRAY",
+                    "items": {
+                        "type": "OBJECT",
+                        "properties": {
+                            "title": {"type": "STRING", "nullable": true},
+                            "headers": {"type": "ARRAY", "items": {"type": "STRING"}},
+                            "rows": {
+                                "type": "ARRAY",
+                                "items": {"type": "ARRAY", "items": {"type": "STRING"}},
+                            },
+                        },
+                        "required": ["headers", "rows"],
+                        "propertyOrdering": ["title", "headers", "rows"],
+                    },
+                },
+            },
+            "required": ["tables"],
+        })
+    }
+}
+
+impl TableSet {
+    pub fn parse(text: &str) -> Result<Self> {
+        serde_json::from_str(text.trim())
+            .or_else(|_| serde_json::from_str(&strip_code_fences(text)))
+            .context("response does not match the table schema")
+    }
+
+    /// Every row of every table as an object keyed by its table's headers, with
+    /// numeric cells written as numbers.
+    pub fn to_records(&self) -> Vec<Value> {
+        let mut records = Vec::new();
+        for table in &self.tables {
+            let headers = unique_headers(&table.headers);
+            for row in &table.rows {
+                let mut record = Map::new();
+                for (idx, cell) in row.iter().enumerate() {
+                    let key = headers
+                        .get(idx)
+                        .cloned()
+                        .unwrap_or_else(|| format!("column_{}", idx + 1));
+                    record.insert(key, cell_value(cell));
+                }
+                records.push(Value::Object(record));
+            }
+        }
+        records
+    }
+}
+
+/// Blank headers become `column_N` and repeats get a `_2`, `_3`, ... suffix so no
+/// cell overwrites another.
+fn unique_headers(headers: &[String]) -> Vec<String> {
+    let mut seen: Vec<String> = Vec::with_capacity(headers.len());
+    for (idx, header) in headers.iter().enumerate() {
+        let base = match header.trim() {
+            "" => format!("column_{}", idx + 1),
+            name => name.to_string(),
+        };
+        let mut name = base.clone();
+        let mut suffix = 2;
+        while seen.contains(&name) {
+            name = format!("{base}_{suffix}");
+            suffix += 1;
+        }
+        seen.push(name);
+    }
+    seen
+}
+
+fn cell_value(cell: &str) -> Value {
+    let trimmed = cell.trim();
+    if let Ok(integer) = trimmed.parse::<i64>() {
+        return json!(integer);
+    }
+    match trimmed.parse::<f64>() {
+        Ok(float) if float.is_finite() && trimmed.contains(|c: char| c.is_ascii_digit()) => {
+            json!(float)
+        }
+        _ => Value::String(cell.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_become_typed_records() {
+        let text = r#"{"tables": [{"title": "Results", "headers": ["Model", "Acc", "", "Acc"],
+            "rows": [["base", "0.91", "12", "n/a", "extra"]]}]}"#;
+        let records = TableSet::parse(text).unwrap().to_records();
+        assert_eq!(
+            records,
+            vec![json!({
+                "Model": "base",
+                "Acc": 0.91,
+                "column_3": 12,
+                "Acc_2": "n/a",
+                "column_5": "extra",
+            })]
+        );
+    }
+}
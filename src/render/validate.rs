@@ -0,0 +1,193 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::core::OutputFormat;
+
+/// Environments whose bodies are taken literally, so `\begin`/`\end` inside them
+/// are not structure.
+const VERBATIM_ENVIRONMENTS: &[&str] =
+    &["verbatim", "verbatim*", "lstlisting", "minted", "comment"];
+
+/// Remove code fences the model wrapped around its whole reply (or around each
+/// chunk's reply), such as a leading ```` ```markdown ```` and trailing ```` ``` ````.
+///
+/// Only fences tagged with the output format's own language are unwrapped, plus an
+/// untagged fence around the entire text for formats other than Markdown, so code
+/// blocks that belong to a Markdown transcript are left alone. Returns `None` when
+/// nothing was removed.
+pub fn strip_wrapper_fences(text: &str, format: OutputFormat) -> Option<String> {
+    let tags: &[&str] = match format {
+        OutputFormat::Markdown => &["markdown", "md"],
+        OutputFormat::Latex => &["latex", "tex"],
+        OutputFormat::Org => &["org"],
+        OutputFormat::Rst => &["rst", "rest", "restructuredtext"],
+        OutputFormat::Json => &["json"],
+    };
+    let lines: Vec<&str> = text.lines().collect();
+    let is_bare = |line: &str| line.trim() == "```";
+    let opens = |index: usize| {
+        let line = lines[index].trim();
+        let Some(tag) = line.strip_prefix("```") else {
+            return false;
+        };
+        let tagged = tags.iter().any(|t| tag.trim().eq_ignore_ascii_case(t));
+        let untagged_whole = tag.trim().is_empty() && format != OutputFormat::Markdown;
+        // A wrapper starts the text or follows the previous wrapper's closing fence.
+        let previous = lines[..index].iter().rev().find(|l| !l.trim().is_empty());
+        (tagged || (untagged_whole && previous.is_none()))
+            && previous.is_none_or(|line| is_bare(line))
+    };
+
+    let starts: Vec<usize> = (0..lines.len()).filter(|&index| opens(index)).collect();
+    let mut dropped = vec![false; lines.len()];
+    for (position, &start) in starts.iter().enumerate() {
+        let end = starts.get(position + 1).copied().unwrap_or(lines.len());
+        // The wrapper closes at the last bare fence of its region, with nothing but
+        // blank lines after it.
+        let close = (start + 1..end)
+            .rev()
+            .find(|&index| !lines[index].trim().is_empty())
+            .filter(|&index| is_bare(lines[index]));
+        if let Some(close) = close {
+            dropped[start] = true;
+            dropped[close] = true;
+        }
+    }
+    if !dropped.contains(&true) {
+        return None;
+    }
+    let kept: Vec<&str> = lines
+        .iter()
+        .zip(&dropped)
+        .filter(|(_, dropped)| !**dropped)
+        .map(|(line, _)| *line)
+        .collect();
+    Some(kept.join("\n").trim().to_string())
+}
+
+fn environment_pattern() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\\(begin|end)\{([^}]+)\}").unwrap())
+}
+
+/// Structural problems in a complete LaTeX document: environments that are never
+/// closed or closed out of order, and a missing or misplaced `\end{document}`.
+///
+/// This is not a compile; it catches the damage a model typically does (a
+/// truncated `itemize`, a stray `\end{align}`) before the file is written.
+pub fn latex_problems(document: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut open: Vec<(String, usize)> = Vec::new();
+    let mut document_ended = false;
+    let mut content_after_end = false;
+
+    for (index, raw) in document.lines().enumerate() {
+        let line_number = index + 1;
+        let line = strip_comment(raw);
+        if document_ended && !line.trim().is_empty() {
+            content_after_end = true;
+        }
+        for captures in environment_pattern().captures_iter(line) {
+            let name = captures[2].trim().to_string();
+            let verbatim = open
+                .last()
+                .is_some_and(|(env, _)| VERBATIM_ENVIRONMENTS.contains(&env.as_str()));
+            if verbatim
+                && !(&captures[1] == "end" && open.last().is_some_and(|(env, _)| *env == name))
+            {
+                continue;
+            }
+            if &captures[1] == "begin" {
+                open.push((name, line_number));
+                continue;
+            }
+            match open.iter().rposition(|(env, _)| *env == name) {
+                Some(position) => {
+                    for (env, opened) in open.drain(position + 1..) {
+                        problems.push(format!(
+                            "\\begin{{{env}}} on line {opened} is not closed before \\end{{{name}}} on line {line_number}"
+                        ));
+                    }
+                    open.pop();
+                }
+                None => problems.push(format!(
+                    "\\end{{{name}}} on line {line_number} has no matching \\begin"
+                )),
+            }
+            if name == "document" {
+                document_ended = true;
+            }
+        }
+    }
+
+    for (env, opened) in open.into_iter().rev() {
+        if env != "document" {
+            problems.push(format!("\\begin{{{env}}} on line {opened} is never closed"));
+        }
+    }
+    if document.contains("\\documentclass") && !document_ended {
+        problems.push("\\end{document} is missing".into());
+    }
+    if content_after_end {
+        problems.push("text follows \\end{document}".into());
+    }
+    problems
+}
+
+/// The line up to its first unescaped `%`.
+fn strip_comment(line: &str) -> &str {
+    let bytes = line.as_bytes();
+    for (index, byte) in bytes.iter().enumerate() {
+        if *byte == b'%' {
+            let backslashes = bytes[..index]
+                .iter()
+                .rev()
+                .take_while(|b| **b == b'\\')
+                .count();
+            if backslashes % 2 == 0 {
+                return &line[..index];
+            }
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unwraps_fences_and_checks_latex_structure() {
+        let chunks =
+            "```markdown\n# Part 1\n\n```python\nprint(1)\n```\n```\n\n```markdown\n# Part 2\n```";
+        assert_eq!(
+            strip_wrapper_fences(chunks, OutputFormat::Markdown).as_deref(),
+            Some("# Part 1\n\n```python\nprint(1)\n```\n\n# Part 2")
+        );
+        assert_eq!(
+            strip_wrapper_fences("```\nx\n```", OutputFormat::Markdown),
+            None
+        );
+        assert_eq!(
+            strip_wrapper_fences("```\n\\section{A}\n```", OutputFormat::Latex).as_deref(),
+            Some("\\section{A}")
+        );
+
+        let good = "\\documentclass{article}\n\\begin{document}\n\\begin{itemize}\n\\item 50\\% % note \\end{itemize}\n\\end{itemize}\n\\begin{verbatim}\n\\begin{x}\n\\end{verbatim}\n\\end{document}\n";
+        assert!(
+            latex_problems(good).is_empty(),
+            "{:?}",
+            latex_problems(good)
+        );
+        let bad = "\\documentclass{article}\n\\begin{document}\n\\begin{itemize}\n\\item a\n\\end{align}\n";
+        assert_eq!(
+            latex_problems(bad),
+            [
+                "\\end{align} on line 5 has no matching \\begin",
+                "\\begin{itemize} on line 3 is never closed",
+                "\\end{document} is missing",
+            ]
+        );
+    }
+}
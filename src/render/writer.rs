@@ -7,16 +7,18 @@ use std::{
 };
 
 pub struct CompositeWriter {
-    markdown: MarkdownWriter,
     latex: LatexWriter,
 }
 
 impl CompositeWriter {
     pub fn new() -> Self {
-        Self {
-            markdown: MarkdownWriter,
-            latex: LatexWriter,
-        }
+        Self { latex: LatexWriter }
+    }
+}
+
+impl Default for CompositeWriter {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -30,18 +32,24 @@ impl crate::core::Writer for CompositeWriter {
         body: &str,
     ) -> anyhow::Result<PathBuf> {
         match format {
-            OutputFormat::Markdown => self.markdown.write(base, name, preamble, body),
             OutputFormat::Latex => self.latex.write(base, name, preamble, body),
+            // Markdown, Org and reST are plain markup: optional header, then the body.
+            other => TextWriter {
+                extension: other.extension(),
+            }
+            .write(base, name, preamble, body),
         }
     }
 }
 
-struct MarkdownWriter;
+struct TextWriter {
+    extension: &'static str,
+}
 
-impl MarkdownWriter {
+impl TextWriter {
     fn write(&self, base: &Path, name: &str, header: &str, body: &str) -> anyhow::Result<PathBuf> {
         fs::create_dir_all(base)?;
-        let path = base.join(format!("{name}.md"));
+        let path = base.join(format!("{name}.{}", self.extension));
 
         let mut content = String::new();
         if !header.is_empty() {
@@ -76,20 +84,25 @@ impl LatexWriter {
     ) -> anyhow::Result<PathBuf> {
         fs::create_dir_all(base)?;
         let path = base.join(format!("{name}.tex"));
-
-        let mut content = String::new();
-        content.push_str(preamble);
-        if !preamble.ends_with('\n') {
-            content.push('\n');
-        }
-        content.push_str(body);
-        if !body.contains("\\end{document}") {
-            content.push_str("\n\\end{document}\n");
-        }
-
+        let content = latex_document(preamble, body);
         let mut file =
             File::create(&path).with_context(|| format!("creating {}", path.display()))?;
         file.write_all(content.as_bytes())?;
         Ok(path)
     }
 }
+
+/// The `.tex` file written for a LaTeX transcript: the preamble, the model's body,
+/// and `\end{document}` unless the body already closes the document.
+pub fn latex_document(preamble: &str, body: &str) -> String {
+    let mut content = String::new();
+    content.push_str(preamble);
+    if !preamble.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(body);
+    if !body.contains("\\end{document}") {
+        content.push_str("\n\\end{document}\n");
+    }
+    content
+}
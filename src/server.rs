@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, Result};
+use axum::extract::Request;
+use axum::extract::{Path as UrlPath, Query, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use clap::ValueEnum;
+use futures::{Stream, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::sync::{broadcast, mpsc, Semaphore};
+use tokio_util::sync::CancellationToken;
+
+use crate::batch::JobOverrides;
+use crate::cli::{Cli, ConflictArg, OutputFormatArg};
+use crate::config::AppConfig;
+use crate::metrics;
+use crate::progress::Progress;
+
+/// Progress events kept per job for clients that connect after the job started.
+const EVENT_HISTORY: usize = 500;
+
+pub struct ServeOptions {
+    pub addr: SocketAddr,
+    pub max_concurrent: usize,
+    pub token: Option<String>,
+}
+
+#[derive(Clone)]
+struct AppState {
+    cli: Arc<Cli>,
+    jobs: Arc<Mutex<HashMap<String, JobState>>>,
+    next_id: Arc<AtomicU64>,
+    slots: Arc<Semaphore>,
+    token: Option<Arc<String>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl JobStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+        }
+    }
+
+    fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Succeeded | JobStatus::Failed)
+    }
+}
+
+struct JobState {
+    id: String,
+    source: String,
+    status: JobStatus,
+    submitted_at: String,
+    finished_at: Option<String>,
+    outputs: Vec<PathBuf>,
+    error: Option<String>,
+    summaries: Vec<Value>,
+    history: Vec<(String, Value)>,
+    events: Option<broadcast::Sender<(String, Value)>>,
+}
+
+impl JobState {
+    fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "source": self.source,
+            "status": self.status.as_str(),
+            "submitted_at": self.submitted_at,
+            "finished_at": self.finished_at,
+            "outputs": self.outputs,
+            "error": self.error,
+            "summaries": self.summaries,
+        })
+    }
+
+    fn publish(&mut self, name: &str, payload: Value) {
+        if self.history.len() >= EVENT_HISTORY {
+            self.history.remove(0);
+        }
+        self.history.push((name.to_string(), payload.clone()));
+        if let Some(events) = &self.events {
+            let _ = events.send((name.to_string(), payload));
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SubmitRequest {
+    source: String,
+    #[serde(flatten)]
+    overrides: JobOverrides,
+}
+
+#[derive(Deserialize)]
+struct OutputQuery {
+    #[serde(default)]
+    index: usize,
+}
+
+fn now() -> String {
+    OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_default()
+}
+
+/// Serve the HTTP API until Ctrl+C.
+///
+/// `POST /jobs` queues a transcription, `GET /jobs/{id}` reports its status,
+/// `GET /jobs/{id}/output` returns the transcript, `GET /jobs/{id}/events`
+/// streams progress as server-sent events, and `GET /metrics` serves counters for
+/// Prometheus.
+pub async fn run_server(cli: Cli, options: ServeOptions) -> Result<()> {
+    let cfg = AppConfig::load_with_profile(cli.config.as_deref(), cli.profile.as_deref())?;
+    let stop_export = CancellationToken::new();
+    let _stop_export = stop_export.clone().drop_guard();
+    if let Some(endpoint) = cfg.otlp_endpoint.clone() {
+        tokio::spawn(metrics::export_periodically(
+            endpoint,
+            std::time::Duration::from_secs(cfg.metrics_interval_seconds),
+            stop_export,
+        ));
+    }
+    let state = AppState {
+        cli: Arc::new(cli),
+        jobs: Arc::new(Mutex::new(HashMap::new())),
+        next_id: Arc::new(AtomicU64::new(1)),
+        slots: Arc::new(Semaphore::new(options.max_concurrent.max(1))),
+        token: options.token.map(Arc::new),
+    };
+    let app = Router::new()
+        .route("/jobs", get(list_jobs).post(submit_job))
+        .route("/jobs/{id}", get(job_status))
+        .route("/jobs/{id}/output", get(job_output))
+        .route("/jobs/{id}/events", get(job_events))
+        .route("/metrics", get(prometheus_metrics))
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .route("/health", get(|| async { Json(json!({"status": "ok"})) }))
+        .with_state(state);
+    let listener = tokio::net::TcpListener::bind(options.addr).await?;
+    println!("recapit serving on http://{}", listener.local_addr()?);
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+/// Request, token, retry, quota-sleep, and job counters in the Prometheus text format.
+async fn prometheus_metrics() -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::METRICS.prometheus(),
+    )
+        .into_response()
+}
+
+/// Reject requests without the configured bearer token; a no-op when none is set.
+async fn authorize(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if let Some(token) = &state.token {
+        let presented = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if !presented.is_some_and(|presented| tokens_match(presented, token)) {
+            return error_response(StatusCode::UNAUTHORIZED, "missing or invalid bearer token");
+        }
+    }
+    next.run(request).await
+}
+
+/// Compare digests in constant time, so neither the token's contents nor its length
+/// can be learned from how long a rejection takes.
+fn tokens_match(presented: &str, token: &str) -> bool {
+    let digest = |value: &str| Sha256::digest(value.as_bytes());
+    digest(presented).ct_eq(&digest(token)).into()
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+async fn list_jobs(State(state): State<AppState>) -> Response {
+    let jobs = state.jobs.lock().unwrap();
+    let mut listed: Vec<Value> = jobs.values().map(JobState::to_json).collect();
+    listed.sort_by_key(|job| job["submitted_at"].as_str().unwrap_or_default().to_string());
+    Json(json!({ "jobs": listed })).into_response()
+}
+
+async fn job_status(State(state): State<AppState>, UrlPath(id): UrlPath<String>) -> Response {
+    match state.jobs.lock().unwrap().get(&id) {
+        Some(job) => Json(job.to_json()).into_response(),
+        None => error_response(StatusCode::NOT_FOUND, "unknown job"),
+    }
+}
+
+async fn job_output(
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<String>,
+    Query(query): Query<OutputQuery>,
+) -> Response {
+    let path = {
+        let jobs = state.jobs.lock().unwrap();
+        let Some(job) = jobs.get(&id) else {
+            return error_response(StatusCode::NOT_FOUND, "unknown job");
+        };
+        if !job.status.is_finished() {
+            return error_response(StatusCode::CONFLICT, "job has not finished");
+        }
+        match job.outputs.get(query.index) {
+            Some(path) => path.clone(),
+            None => return error_response(StatusCode::NOT_FOUND, "job produced no such output"),
+        }
+    };
+    match tokio::fs::read(&path).await {
+        Ok(bytes) => {
+            let mime = mime_guess::from_path(&path).first_or_text_plain();
+            ([(header::CONTENT_TYPE, mime.to_string())], bytes).into_response()
+        }
+        Err(err) => error_response(StatusCode::GONE, &format!("{}: {err}", path.display())),
+    }
+}
+
+async fn job_events(State(state): State<AppState>, UrlPath(id): UrlPath<String>) -> Response {
+    let (history, live) = {
+        let jobs = state.jobs.lock().unwrap();
+        let Some(job) = jobs.get(&id) else {
+            return error_response(StatusCode::NOT_FOUND, "unknown job");
+        };
+        (
+            job.history.clone(),
+            job.events.as_ref().map(|events| events.subscribe()),
+        )
+    };
+    Sse::new(event_stream(history, live))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// Replay what happened so far, then follow the job until its sender is dropped.
+fn event_stream(
+    history: Vec<(String, Value)>,
+    live: Option<broadcast::Receiver<(String, Value)>>,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    let replay = futures::stream::iter(history);
+    let follow = futures::stream::unfold(live, |live| async move {
+        let mut live = live?;
+        loop {
+            match live.recv().await {
+                Ok(item) => return Some((item, Some(live))),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    replay
+        .chain(follow)
+        .map(|(name, payload)| Ok(Event::default().event(name).data(payload.to_string())))
+}
+
+async fn submit_job(State(state): State<AppState>, Json(request): Json<SubmitRequest>) -> Response {
+    let job_cli = match job_cli(&state.cli, &request) {
+        Ok(cli) => cli,
+        Err(err) => return error_response(StatusCode::BAD_REQUEST, &format!("{err:#}")),
+    };
+    let id = format!("job-{}", state.next_id.fetch_add(1, Ordering::SeqCst));
+    let (events, _) = broadcast::channel(256);
+    let job = JobState {
+        id: id.clone(),
+        source: request.source.clone(),
+        status: JobStatus::Queued,
+        submitted_at: now(),
+        finished_at: None,
+        outputs: Vec::new(),
+        error: None,
+        summaries: Vec::new(),
+        history: Vec::new(),
+        events: Some(events),
+    };
+    let body = job.to_json();
+    state.jobs.lock().unwrap().insert(id.clone(), job);
+    tokio::spawn(run_job(state.clone(), id, job_cli));
+    (StatusCode::ACCEPTED, Json(body)).into_response()
+}
+
+/// The server's own flags, narrowed to one source with the request's overrides applied.
+fn job_cli(base: &Cli, request: &SubmitRequest) -> Result<Cli> {
+    if request.source.trim().is_empty() {
+        bail!("source is required");
+    }
+    let overrides = &request.overrides;
+    let mut cli = base.clone();
+    cli.cmd = None;
+    cli.jobs = None;
+    cli.dry_run = false;
+    cli.quiet = true;
+    // Nobody is at the server's terminal to answer an overwrite prompt.
+    cli.on_conflict.get_or_insert(ConflictArg::Rename);
+    cli.source = vec![request.source.clone()];
+    if let Some(kind) = &overrides.kind {
+        cli.kind = kind.clone();
+    }
+    if let Some(model) = &overrides.model {
+        cli.model = Some(model.clone());
+    }
+    if let Some(format) = &overrides.format {
+        cli.format = Some(
+            OutputFormatArg::from_str(format, true)
+                .map_err(|_| anyhow::anyhow!("unknown format '{format}'"))?,
+        );
+    }
+    if let Some(pdf_mode) = &overrides.pdf_mode {
+        cli.pdf_mode = pdf_mode.clone();
+    }
+    if let Some(pages) = &overrides.pages {
+        cli.pages = vec![pages.clone()];
+    }
+    if let Some(dir) = &overrides.output_dir {
+        cli.output_dir = Some(dir.clone());
+    }
+    if let Some(exports) = &overrides.exports {
+        cli.export = exports.clone();
+    }
+    Ok(cli)
+}
+
+async fn run_job(state: AppState, id: String, cli: Cli) {
+    let _permit = state.slots.clone().acquire_owned().await;
+    let update = |f: &dyn Fn(&mut JobState)| {
+        if let Some(job) = state.jobs.lock().unwrap().get_mut(&id) {
+            f(job);
+        }
+    };
+    update(&|job| {
+        job.status = JobStatus::Running;
+        job.publish("status", json!({"status": "running"}));
+    });
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Progress>();
+    let forward = {
+        let state = state.clone();
+        let id = id.clone();
+        tokio::spawn(async move {
+            while let Some(progress) = rx.recv().await {
+                let payload = serde_json::to_value(&progress).unwrap_or(Value::Null);
+                if let Some(job) = state.jobs.lock().unwrap().get_mut(&id) {
+                    job.publish("progress", payload);
+                }
+            }
+        })
+    };
+    let result = crate::app::run_jobs(cli, Some(tx)).await;
+    let _ = forward.await;
+
+    update(&|job| {
+        job.finished_at = Some(now());
+        match &result {
+            Ok(summaries) => {
+                job.status = JobStatus::Succeeded;
+                job.outputs = summaries
+                    .iter()
+                    .filter_map(|(_, output, _, _)| output.clone())
+                    .collect();
+                job.summaries = summaries
+                    .iter()
+                    .map(|(label, output, summary, costs)| {
+                        json!({
+                            "label": label,
+                            "output": output,
+                            "input_tokens": summary.total_input_tokens,
+                            "output_tokens": summary.total_output_tokens,
+                            "total_tokens": summary.total_tokens,
+                            "estimated_cost": costs.total_cost,
+                            "elapsed_seconds": summary.total_duration_seconds,
+                        })
+                    })
+                    .collect();
+            }
+            Err(err) => {
+                job.status = JobStatus::Failed;
+                job.error = Some(format!("{err:#}"));
+            }
+        }
+        let done = job.to_json();
+        job.publish("done", done);
+        // Dropping the sender ends every open event stream for this job.
+        job.events = None;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bearer_tokens_must_match_exactly() {
+        assert!(tokens_match("s3cret", "s3cret"));
+        assert!(!tokens_match("s3cre", "s3cret"));
+        assert!(!tokens_match("s3cret-and-more", "s3cret"));
+        assert!(!tokens_match("", "s3cret"));
+    }
+}
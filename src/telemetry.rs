@@ -89,6 +89,15 @@ impl RunMonitor {
     }
 
     pub fn record(&self, event: RequestEvent) {
+        crate::metrics::METRICS.observe_request(&event);
+        tracing::debug!(
+            model = %event.model,
+            modality = %event.modality,
+            input_tokens = event.input_tokens,
+            output_tokens = event.output_tokens,
+            seconds = event.duration_seconds(),
+            "request finished"
+        );
         let mut state = self.inner.lock().unwrap();
         if state.first_started.is_none()
             || event.started_at < state.first_started.unwrap_or(event.started_at)
@@ -104,6 +113,8 @@ impl RunMonitor {
     }
 
     pub fn note_event(&self, name: &str, payload: serde_json::Value) {
+        crate::metrics::METRICS.observe_note(name, &payload);
+        tracing::debug!(note = name, payload = %payload, "telemetry note");
         let mut state = self.inner.lock().unwrap();
         state.notes.push(Note {
             name: name.to_string(),
@@ -112,14 +123,39 @@ impl RunMonitor {
         });
     }
 
+    /// Payloads of the notes recorded under `name`, oldest first.
+    pub fn notes(&self, name: &str) -> Vec<serde_json::Value> {
+        let state = self.inner.lock().unwrap();
+        state
+            .notes
+            .iter()
+            .filter(|note| note.name == name)
+            .map(|note| note.payload.clone())
+            .collect()
+    }
+
+    /// Notes after the first `seen`, as `(name, payload)`, for following a run
+    /// while it goes.
+    pub fn notes_since(&self, seen: usize) -> Vec<(String, serde_json::Value)> {
+        let state = self.inner.lock().unwrap();
+        state
+            .notes
+            .iter()
+            .skip(seen)
+            .map(|note| (note.name.clone(), note.payload.clone()))
+            .collect()
+    }
+
     pub fn events(&self) -> Vec<RequestEvent> {
         self.inner.lock().unwrap().events.clone()
     }
 
     pub fn summarize(&self) -> RunSummary {
         let state = self.inner.lock().unwrap();
-        let mut summary = RunSummary::default();
-        summary.total_requests = state.events.len();
+        let mut summary = RunSummary {
+            total_requests: state.events.len(),
+            ..Default::default()
+        };
         for event in &state.events {
             let input = event.input_tokens.unwrap_or_else(|| {
                 event
@@ -178,6 +214,11 @@ impl RunMonitor {
         let summary = self.summarize();
         let events = self.events();
         let costs = cost.estimate(&events);
+        let cached_input_tokens: u64 = costs
+            .per_model
+            .values()
+            .map(|model| model.cached_input_tokens)
+            .sum();
         let state = self.inner.lock().unwrap();
         let start = state.first_started.map(|t| t.format(&Rfc3339).unwrap());
         let end = state.last_finished.map(|t| t.format(&Rfc3339).unwrap());
@@ -191,12 +232,17 @@ impl RunMonitor {
                 "source": job.source,
                 "kind": job.kind.map(|k| k.as_str().to_string()),
                 "model": job.model,
+                "language": job.language,
             },
             "totals": {
                 "requests": summary.total_requests,
                 "input_tokens": summary.total_input_tokens,
                 "output_tokens": summary.total_output_tokens,
-                "est_cost_usd": (costs.total_cost * 1_000_000.0).round() / 1_000_000.0,
+                "cached_input_tokens": cached_input_tokens,
+                "cache_read_cost_usd": round_usd(costs.total_cache_read_cost),
+                "est_cost_usd": round_usd(costs.total_cost),
+                "currency": cost.currency().code,
+                "est_cost": round_usd(cost.currency().convert(costs.total_cost)),
             },
             "time": {
                 "start": start,
@@ -205,6 +251,7 @@ impl RunMonitor {
             },
             "limits": limits.iter().map(|(k, v)| (k.to_string(), v)).collect::<HashMap<_, _>>(),
             "files": files.iter().map(|p| p.to_string_lossy().to_string()).collect::<Vec<_>>(),
+            "chunks": chunk_costs(&events, cost),
             "warnings": if costs.estimated { vec!["costs include estimates".to_string()] } else { Vec::new() },
             "notes": state.notes.clone(),
         });
@@ -227,6 +274,9 @@ impl RunMonitor {
                     "latency_ms": (event.duration_seconds() * 1000.0).round() as i64,
                     "tokens_in": event.input_tokens,
                     "tokens_out": event.output_tokens,
+                    "cost_usd": round_usd(cost.event_cost(&event)),
+                    "upload_bytes": event.metadata.get("upload_bytes"),
+                    "retries": event.metadata.get("retries"),
                     "video_start": event.metadata.get("chunk_start_seconds"),
                     "video_end": event.metadata.get("chunk_end_seconds"),
                     "file_uri": event.metadata.get("file_uri"),
@@ -241,6 +291,61 @@ impl RunMonitor {
     }
 }
 
+fn round_usd(cost: f64) -> f64 {
+    (cost * 1_000_000.0).round() / 1_000_000.0
+}
+
+/// What each chunk of a chunked source cost: requests, tokens, dollars, bytes
+/// uploaded, retries, and time spent in `generateContent`, in chunk order. A chunk
+/// that needed several requests (repairs, fallbacks) is summed into one entry.
+fn chunk_costs(events: &[RequestEvent], cost: &CostEstimator) -> Vec<serde_json::Value> {
+    #[derive(Default)]
+    struct ChunkCost {
+        model: String,
+        requests: u64,
+        input_tokens: u64,
+        output_tokens: u64,
+        cost: f64,
+        upload_bytes: u64,
+        retries: u64,
+        latency_ms: i64,
+    }
+    let number = |event: &RequestEvent, key: &str| {
+        event.metadata.get(key).and_then(serde_json::Value::as_u64)
+    };
+    let mut chunks: std::collections::BTreeMap<u64, ChunkCost> = Default::default();
+    for event in events {
+        let Some(index) = number(event, "chunk_index") else {
+            continue;
+        };
+        let chunk = chunks.entry(index).or_default();
+        chunk.model = event.model.clone();
+        chunk.requests += 1;
+        chunk.input_tokens += event.input_tokens.unwrap_or(0) as u64;
+        chunk.output_tokens += event.output_tokens.unwrap_or(0) as u64;
+        chunk.cost += cost.event_cost(event);
+        chunk.upload_bytes += number(event, "upload_bytes").unwrap_or(0);
+        chunk.retries += number(event, "retries").unwrap_or(0);
+        chunk.latency_ms += (event.duration_seconds() * 1000.0).round() as i64;
+    }
+    chunks
+        .into_iter()
+        .map(|(index, chunk)| {
+            json!({
+                "chunk_index": index,
+                "model": chunk.model,
+                "requests": chunk.requests,
+                "input_tokens": chunk.input_tokens,
+                "output_tokens": chunk.output_tokens,
+                "cost_usd": round_usd(chunk.cost),
+                "upload_bytes": chunk.upload_bytes,
+                "retries": chunk.retries,
+                "latency_ms": chunk.latency_ms,
+            })
+        })
+        .collect()
+}
+
 fn update_bucket(bucket: &mut SummaryBucket, input: u64, output: u64, total: u64, duration: f64) {
     bucket.requests += 1;
     bucket.input_tokens += input;
@@ -1,14 +1,17 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
-use crate::core::{Kind, OutputFormat};
+use crate::core::{Detail, Kind, OutputFormat};
 
 const PROMPT_DIR: &str = "prompts";
 const PREAMBLE_DIR: &str = "templates/preambles";
 const CONVERSION_DIR: &str = "templates/conversions";
 
+/// Values for `{{NAME}}` placeholders, keyed by upper-case name.
+pub type TemplateVars = BTreeMap<String, String>;
+
 #[derive(Debug, Clone)]
 pub struct TemplateLoader {
     base: Arc<PathBuf>,
@@ -59,39 +62,28 @@ impl TemplateLoader {
     }
 
     pub fn preamble(&self, kind: Kind, format: OutputFormat) -> String {
-        let (filename, default) = match (kind, format) {
-            (Kind::Slides, OutputFormat::Markdown) => {
-                ("slide-template.txt", MARKDOWN_PREAMBLES.slides)
-            }
-            (Kind::Slides, OutputFormat::Latex) => {
-                ("slide-latex-template.txt", LATEX_PREAMBLES.slides)
-            }
-            (Kind::Lecture, OutputFormat::Markdown) => {
-                ("lecture-template.txt", MARKDOWN_PREAMBLES.lecture)
-            }
-            (Kind::Lecture, OutputFormat::Latex) => {
-                ("lecture-latex-template.txt", LATEX_PREAMBLES.lecture)
-            }
-            (Kind::Document, OutputFormat::Markdown) => {
-                ("document-template.txt", MARKDOWN_PREAMBLES.document)
-            }
-            (Kind::Document, OutputFormat::Latex) => {
-                ("document-latex-template.txt", LATEX_PREAMBLES.document)
-            }
-            (Kind::Image, OutputFormat::Markdown) => {
-                ("image-template.txt", MARKDOWN_PREAMBLES.image)
-            }
-            (Kind::Image, OutputFormat::Latex) => {
-                ("image-latex-template.txt", LATEX_PREAMBLES.image)
-            }
-            (Kind::Video, OutputFormat::Markdown) => {
-                ("video-template.txt", MARKDOWN_PREAMBLES.video)
-            }
-            (Kind::Video, OutputFormat::Latex) => {
-                ("video-latex-template.txt", LATEX_PREAMBLES.video)
-            }
+        let preambles = match format {
+            OutputFormat::Markdown => &MARKDOWN_PREAMBLES,
+            OutputFormat::Latex => &LATEX_PREAMBLES,
+            OutputFormat::Org => &ORG_PREAMBLES,
+            OutputFormat::Rst => &RST_PREAMBLES,
+            // JSON output has no document header; the schema lives in the prompt.
+            OutputFormat::Json => &JSON_PREAMBLES,
+        };
+        let default = match kind {
+            Kind::Slides => preambles.slides,
+            Kind::Lecture => preambles.lecture,
+            Kind::Document => preambles.document,
+            Kind::Image => preambles.image,
+            Kind::Video => preambles.video,
+            Kind::Audio => preambles.audio,
         };
-        self.load_or_default(Some(PREAMBLE_DIR), filename, default)
+        // slide-template.txt, slide-latex-template.txt, slide-org-template.txt, ...
+        let filename = match format {
+            OutputFormat::Markdown => format!("{}-template.txt", template_stem(kind)),
+            other => format!("{}-{}-template.txt", template_stem(kind), other.as_str()),
+        };
+        self.load_or_default(Some(PREAMBLE_DIR), &filename, default)
     }
 
     pub fn latex_to_md_prompt(&self) -> String {
@@ -118,20 +110,135 @@ impl TemplateLoader {
         )
     }
 
+    pub fn json_repair_prompt(&self) -> String {
+        self.load_or_default(
+            Some(CONVERSION_DIR),
+            "json-repair-template.txt",
+            DEFAULT_CONVERSIONS.json_repair,
+        )
+        .replace("{{SCHEMA}}", crate::render::structured::STRUCTURED_SCHEMA)
+    }
+
+    /// Prompt for fixing a LaTeX body that failed the structural check; `{{PROBLEMS}}`
+    /// is replaced with the problems found.
+    pub fn latex_repair_prompt(&self) -> String {
+        self.load_or_default(
+            Some(CONVERSION_DIR),
+            "latex-repair-template.txt",
+            DEFAULT_CONVERSIONS.latex_repair,
+        )
+    }
+
+    /// Prompt for the `--synthesize` pass over a finished transcript.
+    pub fn synthesize_prompt(&self, format: OutputFormat) -> String {
+        let filename = match format {
+            OutputFormat::Markdown => "synthesize-prompt.txt".to_string(),
+            other => format!("synthesize-prompt-{}.txt", other.as_str()),
+        };
+        let target = match format {
+            OutputFormat::Latex => "as a LaTeX document body (no preamble, no \\begin{document})",
+            OutputFormat::Org => "in Emacs Org mode",
+            OutputFormat::Rst => "in reStructuredText",
+            OutputFormat::Markdown | OutputFormat::Json => "in Markdown",
+        };
+        self.load_or_default(
+            Some(PROMPT_DIR),
+            &filename,
+            &SYNTHESIZE_PROMPT.replace("{{FORMAT}}", target),
+        )
+    }
+
+    /// Prompt for `recapit ask`, answering a question from a finished transcript.
+    pub fn ask_prompt(&self) -> String {
+        self.load_or_default(Some(PROMPT_DIR), "ask-prompt.txt", ASK_PROMPT)
+    }
+
+    /// Speaker-labelling instructions appended to audio and video prompts by `--diarize`.
+    pub fn diarize_prompt(&self) -> String {
+        self.load_or_default(Some(PROMPT_DIR), "diarize-prompt.txt", DIARIZE_PROMPT)
+    }
+
+    /// Instructions appended to the kind's prompt for `--detail brief|verbatim`;
+    /// `None` for the standard level.
+    pub fn detail_prompt(&self, kind: Kind, detail: Detail) -> Option<String> {
+        let default = match (detail, kind) {
+            (Detail::Standard, _) => return None,
+            (Detail::Brief, Kind::Slides) => BRIEF_SLIDES_PROMPT,
+            (Detail::Brief, Kind::Lecture | Kind::Video | Kind::Audio) => BRIEF_TIMED_PROMPT,
+            (Detail::Brief, Kind::Document | Kind::Image) => BRIEF_DOCUMENT_PROMPT,
+            (Detail::Verbatim, Kind::Lecture | Kind::Video | Kind::Audio) => VERBATIM_TIMED_PROMPT,
+            (Detail::Verbatim, Kind::Slides | Kind::Document | Kind::Image) => {
+                VERBATIM_DOCUMENT_PROMPT
+            }
+        };
+        // slide-brief-prompt.txt, lecture-verbatim-prompt.txt, ...
+        let filename = format!("{}-{}-prompt.txt", template_stem(kind), detail.as_str());
+        Some(self.load_or_default(Some(PROMPT_DIR), &filename, default))
+    }
+
+    /// Wrapper around the `--glossary` terms; `{{GLOSSARY}}` marks where they go.
+    pub fn glossary_prompt(&self, glossary: &str) -> String {
+        self.load_or_default(Some(PROMPT_DIR), "glossary-prompt.txt", GLOSSARY_PROMPT)
+            .replace("{{GLOSSARY}}", glossary)
+    }
+
     pub fn prompt(&self, kind: Kind, format: OutputFormat, default: &str) -> String {
-        let filename = match (kind, format) {
-            (Kind::Slides, OutputFormat::Markdown) => "slide-prompt.txt",
-            (Kind::Slides, OutputFormat::Latex) => "slide-prompt-latex.txt",
-            (Kind::Lecture, OutputFormat::Markdown) => "lecture-prompt.txt",
-            (Kind::Lecture, OutputFormat::Latex) => "lecture-prompt-latex.txt",
-            (Kind::Document, OutputFormat::Markdown) => "document-prompt.txt",
-            (Kind::Document, OutputFormat::Latex) => "document-prompt-latex.txt",
-            (Kind::Image, OutputFormat::Markdown) => "image-prompt.txt",
-            (Kind::Image, OutputFormat::Latex) => "image-prompt-latex.txt",
-            (Kind::Video, OutputFormat::Markdown) => "video-prompt.txt",
-            (Kind::Video, OutputFormat::Latex) => "video-prompt-latex.txt",
+        // slide-prompt.txt, slide-prompt-latex.txt, slide-prompt-org.txt, ...
+        let filename = match format {
+            OutputFormat::Markdown => format!("{}-prompt.txt", template_stem(kind)),
+            other => format!("{}-prompt-{}.txt", template_stem(kind), other.as_str()),
         };
-        self.load_or_default(Some(PROMPT_DIR), filename, default)
+        self.load_or_default(Some(PROMPT_DIR), &filename, default)
+    }
+}
+
+/// Replace `{{NAME}}` placeholders with their values.
+///
+/// Names are matched case-insensitively. `{{NAME|fallback}}` uses `fallback` when
+/// `NAME` has no value. Placeholders without a value or fallback are left as written,
+/// and anything between double braces that is not a name (LaTeX groups, JSON) is
+/// never touched.
+pub fn render_template(template: &str, vars: &TemplateVars) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start + 2..];
+        let replacement = tail.find("}}").and_then(|end| {
+            let (name, fallback) = match tail[..end].split_once('|') {
+                Some((name, fallback)) => (name, Some(fallback)),
+                None => (&tail[..end], None),
+            };
+            let is_name = name.starts_with(|c: char| c.is_ascii_alphabetic())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !is_name {
+                return None;
+            }
+            let value = vars
+                .get(&name.to_ascii_uppercase())
+                .map(String::as_str)
+                .or(fallback)?;
+            Some((value, end + 2))
+        });
+        match replacement {
+            Some((value, consumed)) => {
+                out.push_str(value);
+                rest = &tail[consumed..];
+            }
+            None => {
+                out.push('{');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn template_stem(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Slides => "slide",
+        other => other.as_str(),
     }
 }
 
@@ -145,12 +252,15 @@ struct FormatPreambles {
     document: &'static str,
     image: &'static str,
     video: &'static str,
+    audio: &'static str,
 }
 
 struct DefaultConversions {
     latex_to_md: &'static str,
     latex_to_json: &'static str,
     markdown_to_json: &'static str,
+    json_repair: &'static str,
+    latex_repair: &'static str,
 }
 
 const SLIDES_PREAMBLE_MARKDOWN: &str = "";
@@ -163,6 +273,8 @@ const IMAGE_PREAMBLE_MARKDOWN: &str = "";
 
 const VIDEO_PREAMBLE_MARKDOWN: &str = "";
 
+const AUDIO_PREAMBLE_MARKDOWN: &str = "";
+
 const SLIDES_PREAMBLE_LATEX: &str = r"\documentclass[aspectratio=43]{beamer}
 
 \usepackage{amsmath}
@@ -246,6 +358,18 @@ const VIDEO_PREAMBLE_LATEX: &str = r"\documentclass{article}
 \begin{document}
 ";
 
+const AUDIO_PREAMBLE_LATEX: &str = r"\documentclass{article}
+
+\usepackage{amsmath}
+\usepackage{amssymb}
+\usepackage{amsfonts}
+\usepackage{enumitem}
+\usepackage{geometry}
+\geometry{margin=1in}
+
+\begin{document}
+";
+
 const LATEX_TO_MD_PROMPT: &str = r"Convert the LaTeX source into Markdown while preserving structure.
 - Keep headings mapping section -> #, subsection -> ##.
 - Preserve math using $...$ or $$...$$.
@@ -257,27 +381,105 @@ const LATEX_TO_MD_PROMPT: &str = r"Convert the LaTeX source into Markdown while
 Return only the Markdown.
 ";
 
-const LATEX_TO_JSON_PROMPT: &str = r"Convert the LaTeX table or structured content into well-formed JSON.
-- Use the first row as headers when available.
-- Preserve numeric types where obvious, otherwise use strings.
-- Output a JSON array of objects.
+const LATEX_TO_JSON_PROMPT: &str = r"Extract the LaTeX tables and structured content as tables.
+- Return one entry per table, in document order, with its caption as the title when it has one.
+- Use the first row as headers when available; otherwise leave headers empty.
+- Give every row its cells in column order, as plain text without LaTeX markup except for math.
 - Do not include explanations.
 ";
 
-const MARKDOWN_TO_JSON_PROMPT: &str = r"Convert the Markdown tables or structured lists into well-formed JSON.
-- Use the first row of each table as headers when available.
-- Preserve numeric types where obvious, otherwise use strings.
-- Output a JSON array of objects.
+const MARKDOWN_TO_JSON_PROMPT: &str = r"Extract the Markdown tables and structured lists as tables.
+- Return one entry per table or list, in document order, titled with the nearest heading.
+- Use the first row of each table as headers when available; otherwise leave headers empty.
+- Give every row its cells in column order, as plain text.
 - Ignore narrative sections that do not map cleanly to data rows.
 - Do not include explanations.
 ";
 
+const JSON_REPAIR_PROMPT: &str = r"The text below was meant to be a JSON transcript but does not parse or does not match the schema.
+Return the same content as a single JSON object that matches this schema exactly:
+{{SCHEMA}}
+- Keep every section, term, and equation; do not summarize or drop content.
+- Timestamps must be MM:SS or HH:MM:SS, or null.
+- Output only the JSON object, with no code fences or commentary.
+";
+
+const LATEX_REPAIR_PROMPT: &str = r"The LaTeX document body below does not have a valid structure:
+{{PROBLEMS}}
+Return the same body with these problems fixed.
+- Close or remove unmatched environments; do not otherwise change, summarize, or drop content.
+- Do not add a preamble, \documentclass, or \begin{document}.
+- Output only the LaTeX, with no code fences or commentary.
+";
+
+const SYNTHESIZE_PROMPT: &str = r"You are given the full transcript of a long source, produced chunk by chunk. Headings may repeat at chunk boundaries and ideas may be split across them.
+
+Write a single coherent summary of the whole source {{FORMAT}}.
+
+Guidelines:
+- Open with a one-paragraph overview of what the source covers.
+- Follow with sections in the order topics are introduced, merging material that was split across chunks.
+- Keep key definitions, results, and equations; cite [MM:SS] timestamps or page numbers when the transcript has them.
+- Do not invent content that is not in the transcript, and do not repeat the transcript verbatim.
+";
+
+const DIARIZE_PROMPT: &str = r"Label who is speaking throughout the transcript.
+- Start every speaker turn on its own line with the speaker's label and a colon, e.g. `Speaker 1:`.
+- When a speaker is introduced by name, names themselves, or is named on screen, use that name instead (e.g. `Prof. Smith:`) and keep it for all of their turns.
+- Otherwise number speakers in order of first appearance, and keep each number attached to the same voice.
+- Do not guess names that are never stated or shown, and do not label background noise or applause as a speaker.
+";
+
+const BRIEF_SLIDES_PROMPT: &str = r"Do not transcribe the slides in full. Write a brief summary instead:
+- Group consecutive slides by topic and give each group a heading.
+- Under each heading, give one to three bullets with the key points, definitions, and results.
+- Keep equations that carry a main result; drop examples, agendas, and repeated title slides.
+";
+
+const BRIEF_TIMED_PROMPT: &str = r"Do not write a full transcript. Write a brief summary instead:
+- Five to fifteen bullets covering the main points in the order they come up.
+- Start each bullet with the [MM:SS] timestamp where the point begins, when the source is timed.
+- Keep key definitions, results, and equations; leave out digressions, logistics, and repetition.
+";
+
+const BRIEF_DOCUMENT_PROMPT: &str = r"Do not transcribe the content verbatim. Write a brief summary instead:
+- Follow the source's top-level headings, or its reading order when it has none.
+- Give one to three bullets per part with the key points, definitions, and results.
+- Keep equations that carry a main result and leave out boilerplate such as headers, footers, and references.
+";
+
+const VERBATIM_TIMED_PROMPT: &str = r"Transcribe everything that is said, word for word:
+- Do not summarize, condense, paraphrase, or skip passages, including asides, repetitions, questions from the audience, and false starts.
+- Keep filler words only when they change the meaning; mark unclear words as [inaudible].
+- Describe on-screen content only when the speaker refers to it.
+";
+
+const VERBATIM_DOCUMENT_PROMPT: &str = r"Transcribe every piece of text exactly as it appears:
+- Do not summarize, condense, reorder, or correct anything, including headers, footers, footnotes, captions, labels, and handwritten annotations.
+- Keep the source's spelling, numbering, and line structure where the output format allows.
+- Mark text you cannot read as [illegible] rather than guessing.
+";
+
+const GLOSSARY_PROMPT: &str = r"Terminology for this source. Spell these names, acronyms, and symbols exactly as listed whenever they occur, and prefer them over similar-sounding words:
+{{GLOSSARY}}
+";
+
+const ASK_PROMPT: &str = r#"Answer the question using only the transcript below.
+
+Guidelines:
+- Ground every claim in the transcript and cite where it comes from: [MM:SS] timestamps for audio and video, (p. N) or (slide N) for documents and slides.
+- Quote short passages when the exact wording matters.
+- If the transcript does not contain the answer, say so plainly instead of guessing.
+- Keep the answer concise Markdown, then list the cited locations under a final "Sources" heading.
+"#;
+
 static MARKDOWN_PREAMBLES: FormatPreambles = FormatPreambles {
     slides: SLIDES_PREAMBLE_MARKDOWN,
     lecture: LECTURE_PREAMBLE_MARKDOWN,
     document: DOCUMENT_PREAMBLE_MARKDOWN,
     image: IMAGE_PREAMBLE_MARKDOWN,
     video: VIDEO_PREAMBLE_MARKDOWN,
+    audio: AUDIO_PREAMBLE_MARKDOWN,
 };
 
 static LATEX_PREAMBLES: FormatPreambles = FormatPreambles {
@@ -286,10 +488,61 @@ static LATEX_PREAMBLES: FormatPreambles = FormatPreambles {
     document: DOCUMENT_PREAMBLE_LATEX,
     image: IMAGE_PREAMBLE_LATEX,
     video: VIDEO_PREAMBLE_LATEX,
+    audio: AUDIO_PREAMBLE_LATEX,
+};
+
+static ORG_PREAMBLES: FormatPreambles = FormatPreambles {
+    slides: "",
+    lecture: "",
+    document: "",
+    image: "",
+    video: "",
+    audio: "",
+};
+
+static RST_PREAMBLES: FormatPreambles = FormatPreambles {
+    slides: "",
+    lecture: "",
+    document: "",
+    image: "",
+    video: "",
+    audio: "",
+};
+
+static JSON_PREAMBLES: FormatPreambles = FormatPreambles {
+    slides: "",
+    lecture: "",
+    document: "",
+    image: "",
+    video: "",
+    audio: "",
 };
 
 static DEFAULT_CONVERSIONS: DefaultConversions = DefaultConversions {
     latex_to_md: LATEX_TO_MD_PROMPT,
     latex_to_json: LATEX_TO_JSON_PROMPT,
     markdown_to_json: MARKDOWN_TO_JSON_PROMPT,
+    json_repair: JSON_REPAIR_PROMPT,
+    latex_repair: LATEX_REPAIR_PROMPT,
 };
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_template_fills_names_and_leaves_other_braces() {
+        let vars = TemplateVars::from([
+            ("COURSE".to_string(), "EECS 545".to_string()),
+            ("SOURCE_NAME".to_string(), "lecture07".to_string()),
+        ]);
+        let rendered = render_template(
+            "{{course}}: \\title{{{SOURCE_NAME}}} {{DATE|undated}} {{SCHEMA}} {{ \"a\": 1 }}",
+            &vars,
+        );
+        assert_eq!(
+            rendered,
+            "EECS 545: \\title{lecture07} undated {{SCHEMA}} {{ \"a\": 1 }}"
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! Offline stand-ins for the pipeline stages, so the whole [`Engine`] can run in
+//! tests without an API key, ffmpeg, or the network.
+//!
+//! Built with the `testing` feature. [`MockIngestor`] hands out fixed assets,
+//! [`MockNormalizer`] passes them through or splits media into timed chunks, and
+//! [`MockProvider`] answers every request with scripted text while recording what
+//! it was sent. The real prompts, writers, and exporters run unchanged.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+use crate::config::AppConfig;
+use crate::core::{
+    Asset, ConflictPolicy, Ingestor, Job, Normalizer, PdfMode, Provider, SourceKind,
+};
+use crate::cost::CostEstimator;
+use crate::engine::Engine;
+use crate::progress::Progress;
+use crate::render::writer::CompositeWriter;
+use crate::telemetry::RunMonitor;
+
+/// Discovers the same assets for every job.
+#[derive(Debug, Clone, Default)]
+pub struct MockIngestor {
+    assets: Vec<Asset>,
+}
+
+impl MockIngestor {
+    pub fn new(assets: Vec<Asset>) -> Self {
+        Self { assets }
+    }
+}
+
+impl Ingestor for MockIngestor {
+    fn discover(&self, _job: &Job) -> Result<Vec<Asset>> {
+        Ok(self.assets.clone())
+    }
+}
+
+/// Passes assets through, or cuts each one with a `duration_seconds` into chunks
+/// of at most `chunk_seconds`, tagged the way the real normalizer tags them.
+#[derive(Debug, Clone, Default)]
+pub struct MockNormalizer {
+    chunk_seconds: Option<f64>,
+    chunks: Vec<Value>,
+}
+
+impl MockNormalizer {
+    pub fn passthrough() -> Self {
+        Self::default()
+    }
+
+    pub fn chunked(chunk_seconds: f64) -> Self {
+        Self {
+            chunk_seconds: Some(chunk_seconds),
+            chunks: Vec::new(),
+        }
+    }
+}
+
+impl Normalizer for MockNormalizer {
+    fn normalize(&mut self, assets: &[Asset], _pdf_mode: PdfMode) -> Result<Vec<Asset>> {
+        self.chunks.clear();
+        let mut outputs = Vec::new();
+        for asset in assets {
+            let duration = asset.meta.get("duration_seconds").and_then(Value::as_f64);
+            let (Some(step), Some(duration)) = (self.chunk_seconds, duration) else {
+                outputs.push(asset.clone());
+                continue;
+            };
+            let total = (duration / step).ceil().max(1.0) as u64;
+            for index in 0..total {
+                let start = index as f64 * step;
+                let end = (start + step).min(duration);
+                let mut meta = asset.meta.as_object().cloned().unwrap_or_default();
+                meta.insert("chunk_index".into(), json!(index));
+                meta.insert("chunk_total".into(), json!(total));
+                meta.insert("chunk_start_seconds".into(), json!(start));
+                meta.insert("chunk_end_seconds".into(), json!(end));
+                let stem = asset
+                    .path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                outputs.push(Asset {
+                    path: asset
+                        .path
+                        .with_file_name(format!("{stem}-chunk{index:02}.mp4")),
+                    meta: Value::Object(meta.clone()),
+                    ..asset.clone()
+                });
+                self.chunks.push(Value::Object(meta));
+            }
+        }
+        Ok(outputs)
+    }
+
+    fn chunk_descriptors(&self) -> Vec<Value> {
+        self.chunks.clone()
+    }
+}
+
+/// One request the [`MockProvider`] received.
+#[derive(Debug, Clone)]
+pub struct MockCall {
+    pub instruction: String,
+    pub assets: Vec<Asset>,
+    pub modality: String,
+    pub meta: Value,
+}
+
+type Reply = dyn Fn(&MockCall) -> Result<String> + Send + Sync;
+
+/// Answers every `transcribe` with `reply` and keeps the calls for assertions.
+#[derive(Clone)]
+pub struct MockProvider {
+    reply: Arc<Reply>,
+    calls: Arc<Mutex<Vec<MockCall>>>,
+}
+
+impl MockProvider {
+    pub fn new(reply: impl Fn(&MockCall) -> Result<String> + Send + Sync + 'static) -> Self {
+        Self {
+            reply: Arc::new(reply),
+            calls: Arc::default(),
+        }
+    }
+
+    /// Replies with [`scripted_transcript`].
+    pub fn scripted() -> Self {
+        Self::new(|call| Ok(scripted_transcript(&call.assets)))
+    }
+
+    /// Every call so far; clone the provider before boxing it to keep a handle.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Provider for MockProvider {
+    async fn transcribe(
+        &self,
+        instruction: &str,
+        assets: &[Asset],
+        modality: &str,
+        meta: &Value,
+    ) -> Result<String> {
+        let call = MockCall {
+            instruction: instruction.to_string(),
+            assets: assets.to_vec(),
+            modality: modality.to_string(),
+            meta: meta.clone(),
+        };
+        let reply = (self.reply)(&call);
+        self.calls.lock().unwrap().push(call);
+        reply
+    }
+}
+
+/// A deterministic transcript for `assets`: a heading per asset and, for chunks,
+/// two timestamps that restart at zero as a model's do for each clip.
+pub fn scripted_transcript(assets: &[Asset]) -> String {
+    let mut text = String::new();
+    for asset in assets {
+        let name = asset
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        match asset.meta.get("chunk_index").and_then(Value::as_u64) {
+            Some(index) => {
+                let _ = write!(
+                    text,
+                    "## Chunk {}\n\n[00:00] Opening of {name}\n\n[00:10] Middle of {name}\n\n",
+                    index + 1
+                );
+            }
+            None => {
+                let _ = write!(text, "## {name}\n\nContents of {name}\n\n");
+            }
+        }
+    }
+    text.trim_end().to_string()
+}
+
+/// A local asset of `media` (`video`, `pdf`, `text`, ...) with extra `meta`.
+pub fn asset(path: impl Into<PathBuf>, media: &str, meta: Value) -> Asset {
+    Asset {
+        path: path.into(),
+        media: media.to_string(),
+        page_index: None,
+        source_kind: SourceKind::Local,
+        mime: None,
+        meta,
+    }
+}
+
+/// A job for `source` writing into `output_dir`, with the transcript cache off and
+/// existing outputs overwritten.
+pub fn job(source: &str, output_dir: &Path) -> Job {
+    Job {
+        job_id: "test".into(),
+        output_dir: Some(output_dir.to_path_buf()),
+        model: crate::constants::GEMINI_2_5_FLASH.into(),
+        skip_existing: false,
+        on_conflict: ConflictPolicy::Overwrite,
+        save_sidecar: false,
+        max_workers: 1,
+        max_video_workers: 1,
+        use_cache: false,
+        ..Job::new(source)
+    }
+}
+
+/// Settings read from an empty `recapit.yaml` in `dir`, so the built-in prompt
+/// templates are used whatever the working directory holds.
+pub fn config(dir: &Path) -> Result<AppConfig> {
+    let path = dir.join("recapit.yaml");
+    fs::write(
+        &path,
+        format!("templates_dir: {}\n", dir.join("templates").display()),
+    )?;
+    AppConfig::load(Some(&path))
+}
+
+/// An engine over the mock stages and the real writer, with the progress channel.
+pub fn engine(
+    ingestor: MockIngestor,
+    normalizer: MockNormalizer,
+    provider: MockProvider,
+    config: &AppConfig,
+) -> Result<(Engine, UnboundedReceiver<Progress>)> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let cost = CostEstimator::from_path(None, config.pricing_defaults.clone())?;
+    let engine = Engine::new(
+        Box::new(ingestor),
+        Box::new(normalizer),
+        Box::new(provider),
+        Box::new(CompositeWriter::new()),
+        tx,
+        RunMonitor::new(),
+        cost,
+        None,
+        config,
+    )?;
+    Ok((engine, rx))
+}
@@ -0,0 +1,379 @@
+//! Where recapit finds the external programs it runs: a path pinned under
+//! `tools:` in recapit.yaml, then `PATH`, then, for yt-dlp, a release build
+//! downloaded into the cache when `tools.auto_download` is on.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, RwLock};
+
+use anyhow::{anyhow, bail, Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::utils::ensure_dir;
+
+/// Pinned releases that downloads come from.
+const YT_DLP_RELEASE: &str = "https://github.com/yt-dlp/yt-dlp/releases/download/2024.12.13";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Ffmpeg,
+    Ffprobe,
+    YtDlp,
+    Pdftoppm,
+    Pdfinfo,
+    Pdftohtml,
+    Pdfseparate,
+    Pdfunite,
+    Pdftotext,
+    Tesseract,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 10] = [
+        Tool::Ffmpeg,
+        Tool::Ffprobe,
+        Tool::YtDlp,
+        Tool::Pdftoppm,
+        Tool::Pdfinfo,
+        Tool::Pdftohtml,
+        Tool::Pdfseparate,
+        Tool::Pdfunite,
+        Tool::Pdftotext,
+        Tool::Tesseract,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::Ffmpeg => "ffmpeg",
+            Tool::Ffprobe => "ffprobe",
+            Tool::YtDlp => "yt-dlp",
+            Tool::Pdftoppm => "pdftoppm",
+            Tool::Pdfinfo => "pdfinfo",
+            Tool::Pdftohtml => "pdftohtml",
+            Tool::Pdfseparate => "pdfseparate",
+            Tool::Pdfunite => "pdfunite",
+            Tool::Pdftotext => "pdftotext",
+            Tool::Tesseract => "tesseract",
+        }
+    }
+
+    /// Whether `tools.auto_download` can fetch a build. Only yt-dlp publishes
+    /// checksums to verify a download against.
+    pub fn downloadable(self) -> bool {
+        download_url(self, env::consts::OS, env::consts::ARCH).is_some()
+    }
+
+    fn file_name(self) -> String {
+        format!("{}{}", self.name(), env::consts::EXE_SUFFIX)
+    }
+}
+
+/// The `tools:` section of recapit.yaml.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolPaths {
+    pub ffmpeg: Option<PathBuf>,
+    pub ffprobe: Option<PathBuf>,
+    pub yt_dlp: Option<PathBuf>,
+    pub pdftoppm: Option<PathBuf>,
+    pub pdfinfo: Option<PathBuf>,
+    pub pdftohtml: Option<PathBuf>,
+    pub pdfseparate: Option<PathBuf>,
+    pub pdfunite: Option<PathBuf>,
+    pub pdftotext: Option<PathBuf>,
+    pub tesseract: Option<PathBuf>,
+    /// PDFium shared library for `pdf.renderer: pdfium`.
+    pub pdfium: Option<PathBuf>,
+    /// Download yt-dlp into the cache when it is missing.
+    pub auto_download: bool,
+}
+
+impl ToolPaths {
+    pub fn pinned(&self, tool: Tool) -> Option<&Path> {
+        match tool {
+            Tool::Ffmpeg => self.ffmpeg.as_deref(),
+            Tool::Ffprobe => self.ffprobe.as_deref(),
+            Tool::YtDlp => self.yt_dlp.as_deref(),
+            Tool::Pdftoppm => self.pdftoppm.as_deref(),
+            Tool::Pdfinfo => self.pdfinfo.as_deref(),
+            Tool::Pdftohtml => self.pdftohtml.as_deref(),
+            Tool::Pdfseparate => self.pdfseparate.as_deref(),
+            Tool::Pdfunite => self.pdfunite.as_deref(),
+            Tool::Pdftotext => self.pdftotext.as_deref(),
+            Tool::Tesseract => self.tesseract.as_deref(),
+        }
+    }
+}
+
+static PATHS: RwLock<Option<ToolPaths>> = RwLock::new(None);
+/// Held while downloading so parallel chunks fetch a tool once.
+static DOWNLOAD_LOCK: Mutex<()> = Mutex::new(());
+
+/// Use `paths` for every later lookup. Loading the config calls this.
+pub fn configure(paths: ToolPaths) {
+    if let Ok(mut current) = PATHS.write() {
+        *current = Some(paths);
+    }
+}
+
+fn settings() -> ToolPaths {
+    PATHS
+        .read()
+        .ok()
+        .and_then(|paths| paths.clone())
+        .unwrap_or_default()
+}
+
+/// Folder that auto-downloaded tools are kept in.
+pub fn tools_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("recapit")
+        .join("tools")
+}
+
+/// The pinned path, the one on `PATH`, or an earlier download, without
+/// downloading anything.
+pub fn locate(tool: Tool) -> Result<PathBuf> {
+    if let Some(pinned) = settings().pinned(tool) {
+        // A bare name is looked up on PATH, so `ffmpeg7` works as well as a path.
+        return which::which(pinned).map_err(|_| {
+            anyhow!(
+                "tools.{} is set to {}, which does not exist",
+                tool.name().replace('-', "_"),
+                pinned.display()
+            )
+        });
+    }
+    if let Ok(path) = which::which(tool.name()) {
+        return Ok(path);
+    }
+    let downloaded = tools_dir().join(tool.file_name());
+    if downloaded.is_file() {
+        return Ok(downloaded);
+    }
+    bail!("{} not found on PATH", tool.name())
+}
+
+/// [`locate`], downloading a static build when that fails and
+/// `tools.auto_download` is on.
+pub fn resolve(tool: Tool) -> Result<PathBuf> {
+    let err = match locate(tool) {
+        Ok(path) => return Ok(path),
+        Err(err) => err,
+    };
+    let paths = settings();
+    if !paths.auto_download || paths.pinned(tool).is_some() || !tool.downloadable() {
+        return Err(err);
+    }
+    let _guard = DOWNLOAD_LOCK
+        .lock()
+        .unwrap_or_else(|poison| poison.into_inner());
+    // Another thread may have finished the download while this one waited.
+    if let Ok(path) = locate(tool) {
+        return Ok(path);
+    }
+    download(tool)
+}
+
+/// A command for `tool`. A missing tool still yields a command, named after the
+/// tool, so running it fails with the usual not-found error.
+pub fn command(tool: Tool) -> Command {
+    match resolve(tool) {
+        Ok(path) => Command::new(path),
+        Err(err) => {
+            tracing::debug!("{err:#}");
+            Command::new(tool.name())
+        }
+    }
+}
+
+/// The PDFium library for `pdf.renderer: pdfium`: `tools.pdfium`, a library
+/// placed in [`tools_dir`], or else the bare library name for the system loader
+/// to find. PDFium publishes no checksums, so it is never downloaded.
+pub fn pdfium_library() -> Result<PathBuf> {
+    if let Some(path) = locate_pdfium()? {
+        return Ok(path);
+    }
+    Ok(PathBuf::from(
+        pdfium_render::prelude::Pdfium::pdfium_platform_library_name(),
+    ))
+}
+
+/// `tools.pdfium` or a library placed in [`tools_dir`].
+pub fn locate_pdfium() -> Result<Option<PathBuf>> {
+    if let Some(pinned) = settings().pdfium {
+        if !pinned.is_file() {
+            bail!(
+                "tools.pdfium is set to {}, which does not exist",
+                pinned.display()
+            );
+        }
+        return Ok(Some(pinned));
+    }
+    let downloaded =
+        tools_dir().join(pdfium_render::prelude::Pdfium::pdfium_platform_library_name());
+    Ok(downloaded.is_file().then_some(downloaded))
+}
+
+/// Download `url` beside `dest` and check it against its known SHA-256, deleting
+/// it on a mismatch. Returns the path of the verified download.
+fn fetch_verified(url: &str, dest: &Path, what: &str) -> Result<PathBuf> {
+    let asset = url.rsplit('/').next().unwrap_or(url);
+    let expected = expected_sha256(url, asset)?;
+    let download = dest.with_extension("download");
+    tracing::info!("downloading {what} from {url}");
+    let result = off_runtime(|| -> Result<String> {
+        let mut response = http_get(url, what)?;
+        let mut file = fs::File::create(&download)
+            .with_context(|| format!("writing {}", download.display()))?;
+        let mut hashing = HashingWriter {
+            inner: &mut file,
+            hasher: Sha256::new(),
+        };
+        io::copy(&mut response, &mut hashing).with_context(|| format!("downloading {what}"))?;
+        Ok(hex::encode(hashing.hasher.finalize()))
+    });
+    let actual = match result {
+        Ok(actual) => actual,
+        Err(err) => {
+            let _ = fs::remove_file(&download);
+            return Err(err);
+        }
+    };
+    if !actual.eq_ignore_ascii_case(&expected) {
+        let _ = fs::remove_file(&download);
+        bail!("{asset} from {url} has SHA-256 {actual}, expected {expected}; not using it");
+    }
+    Ok(download)
+}
+
+/// The hash in the `SHA2-256SUMS` published with the pinned yt-dlp release.
+fn expected_sha256(url: &str, asset: &str) -> Result<String> {
+    if url.starts_with(YT_DLP_RELEASE) {
+        let sums = off_runtime(|| -> Result<String> {
+            Ok(http_get(
+                &format!("{YT_DLP_RELEASE}/SHA2-256SUMS"),
+                "yt-dlp checksums",
+            )?
+            .text()?)
+        })?;
+        if let Some(hash) = parse_sha256sums(&sums, asset) {
+            return Ok(hash);
+        }
+    }
+    bail!("no SHA-256 is published for {asset}; not downloading it")
+}
+
+/// The hash for `asset` in a `sha256sum`-style listing.
+fn parse_sha256sums(sums: &str, asset: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let (hash, name) = line.split_once(char::is_whitespace)?;
+        (name.trim().trim_start_matches('*') == asset).then(|| hash.to_lowercase())
+    })
+}
+
+struct HashingWriter<'a, W> {
+    inner: &'a mut W,
+    hasher: Sha256,
+}
+
+impl<W: io::Write> io::Write for HashingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// reqwest's blocking client panics when built or dropped on an async worker, and
+/// normalization reaches here from one; run the download on its own thread.
+fn off_runtime<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| {
+        scope
+            .spawn(f)
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+fn http_get(url: &str, what: &str) -> Result<reqwest::blocking::Response> {
+    let response = reqwest::blocking::Client::builder()
+        .timeout(std::time::Duration::from_secs(600))
+        .build()?
+        .get(url)
+        .send()
+        .with_context(|| format!("downloading {what}"))?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("downloading {what} failed with status {status}");
+    }
+    Ok(response)
+}
+
+fn download_url(tool: Tool, os: &str, arch: &str) -> Option<String> {
+    match tool {
+        Tool::YtDlp => {
+            let asset = match (os, arch) {
+                ("linux", "x86_64") => "yt-dlp_linux",
+                ("linux", "aarch64") => "yt-dlp_linux_aarch64",
+                ("macos", _) => "yt-dlp_macos",
+                ("windows", _) => "yt-dlp.exe",
+                _ => return None,
+            };
+            Some(format!("{YT_DLP_RELEASE}/{asset}"))
+        }
+        _ => None,
+    }
+}
+
+fn download(tool: Tool) -> Result<PathBuf> {
+    let url = download_url(tool, env::consts::OS, env::consts::ARCH)
+        .ok_or_else(|| anyhow!("no {} build to download for this platform", tool.name()))?;
+    let dir = tools_dir();
+    ensure_dir(&dir)?;
+    let dest = dir.join(tool.file_name());
+    let download = fetch_verified(&url, &dest, tool.name())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Err(err) = fs::set_permissions(&download, fs::Permissions::from_mode(0o755)) {
+            let _ = fs::remove_file(&download);
+            return Err(err).with_context(|| format!("installing {}", tool.name()));
+        }
+    }
+    fs::rename(&download, &dest).with_context(|| format!("writing {}", dest.display()))?;
+    Ok(dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_yt_dlp_is_downloaded() {
+        assert_eq!(
+            download_url(Tool::YtDlp, "macos", "aarch64").as_deref(),
+            Some("https://github.com/yt-dlp/yt-dlp/releases/download/2024.12.13/yt-dlp_macos")
+        );
+        assert!(download_url(Tool::Ffmpeg, "linux", "x86_64").is_none());
+        assert!(download_url(Tool::Pdftoppm, "linux", "x86_64").is_none());
+    }
+
+    #[test]
+    fn checksum_listings_are_read_by_asset_name() {
+        let sums = "0A1B  yt-dlp\n2c3d *yt-dlp_linux\nffff  yt-dlp_linux.zip\n";
+        assert_eq!(
+            parse_sha256sums(sums, "yt-dlp_linux").as_deref(),
+            Some("2c3d")
+        );
+        assert_eq!(parse_sha256sums(sums, "yt-dlp").as_deref(), Some("0a1b"));
+        assert!(parse_sha256sums(sums, "yt-dlp_macos").is_none());
+    }
+}
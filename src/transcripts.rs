@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::utils::ensure_dir;
+
+/// Where finished transcripts were written, keyed by source, so `recapit ask`
+/// can answer from an earlier run instead of transcribing again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TranscriptIndex {
+    #[serde(default)]
+    entries: BTreeMap<String, TranscriptEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    pub path: PathBuf,
+    pub model: String,
+    pub recorded_at: String,
+}
+
+fn index_path() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("recapit")
+        .join("transcripts.json")
+}
+
+/// Local paths are canonicalized so `./a.pdf` and `/abs/a.pdf` share an entry.
+pub fn source_key(source: &str) -> String {
+    let path = Path::new(source);
+    if path.exists() {
+        if let Ok(canonical) = path.canonicalize() {
+            return canonical.to_string_lossy().to_string();
+        }
+    }
+    source.trim().to_string()
+}
+
+impl TranscriptIndex {
+    pub fn load() -> Self {
+        fs::read_to_string(index_path())
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = index_path();
+        ensure_dir(path.parent().unwrap())?;
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// A transcript that still exists and is newer than the (local) source.
+    pub fn lookup(&self, source: &str) -> Option<&TranscriptEntry> {
+        let entry = self.entries.get(&source_key(source))?;
+        let written = entry
+            .path
+            .metadata()
+            .and_then(|meta| meta.modified())
+            .ok()?;
+        let stale = Path::new(source)
+            .metadata()
+            .and_then(|meta| meta.modified())
+            .is_ok_and(|modified| modified > written);
+        (!stale).then_some(entry)
+    }
+
+    pub fn record(source: &str, path: &Path, model: &str) -> Result<()> {
+        let mut index = Self::load();
+        index.entries.insert(
+            source_key(source),
+            TranscriptEntry {
+                path: path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+                model: model.to_string(),
+                recorded_at: OffsetDateTime::now_utc()
+                    .format(&Rfc3339)
+                    .unwrap_or_default(),
+            },
+        );
+        index.save()
+    }
+}
@@ -5,24 +5,239 @@ use crossterm::{
     style::{Color, PrintStyledContent, Stylize},
     terminal::{self, Clear, ClearType},
 };
-use std::collections::HashMap;
-use std::io::{stdout, Write};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, stdout, Stdout, Write};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::error::TryRecvError;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
-use crate::progress::{Progress, ProgressScope, ProgressStage};
+use crate::cost::CostEstimator;
+use crate::progress::{
+    format_eta, ChunkControl, Progress, ProgressScope, ProgressStage, Rate, Throughput,
+};
+use crate::telemetry::RunMonitor;
 
 struct RowState {
     stage: ProgressStage,
     cur: u64,
     total: u64,
     status: String,
-    finished_at: Option<std::time::Instant>,
+    finished: bool,
+    rate: Option<Rate>,
+}
+
+/// Token and cost totals for the dashboard header, summed over the monitor of
+/// every job started so far.
+#[derive(Clone)]
+pub struct UsageFeed {
+    monitors: Arc<Mutex<Vec<RunMonitor>>>,
+    cost: CostEstimator,
+}
+
+impl UsageFeed {
+    pub fn new(cost: CostEstimator) -> Self {
+        Self {
+            monitors: Arc::new(Mutex::new(Vec::new())),
+            cost,
+        }
+    }
+
+    pub fn add(&self, monitor: RunMonitor) {
+        self.monitors.lock().unwrap().push(monitor);
+    }
+
+    /// Notes recorded since the last call, `seen` holding how many of each
+    /// monitor's notes were already read.
+    fn notes_since(&self, seen: &mut Vec<usize>) -> Vec<(String, Value)> {
+        let monitors = self.monitors.lock().unwrap();
+        seen.resize(monitors.len(), 0);
+        let mut notes = Vec::new();
+        for (monitor, seen) in monitors.iter().zip(seen.iter_mut()) {
+            let new = monitor.notes_since(*seen);
+            *seen += new.len();
+            notes.extend(new);
+        }
+        notes
+    }
+
+    /// Input tokens, output tokens, and estimated cost.
+    fn totals(&self) -> (u64, u64, f64) {
+        let monitors = self.monitors.lock().unwrap();
+        let (mut input, mut output, mut cost) = (0, 0, 0.0);
+        for monitor in monitors.iter() {
+            let summary = monitor.summarize();
+            input += summary.total_input_tokens;
+            output += summary.total_output_tokens;
+            cost += self.cost.estimate(&monitor.events()).total_cost;
+        }
+        (input, output, cost)
+    }
+}
+
+/// How many lines the log pane keeps, and shows at once.
+const LOG_LINES: usize = 500;
+const LOG_HEIGHT: usize = 8;
+
+/// Log lines shown in the dashboard's log pane: `tracing` output, which would
+/// otherwise be written over the raw-mode display, and run monitor notes.
+pub static LOG: LazyLock<LogPane> = LazyLock::new(LogPane::default);
+
+#[derive(Default)]
+pub struct LogPane {
+    state: Mutex<PaneState>,
+}
+
+#[derive(Default)]
+struct PaneState {
+    /// Whether the dashboard is on screen.
+    active: bool,
+    lines: VecDeque<String>,
+    /// Lines ever pushed, for counting the ones added since the pane was last open.
+    pushed: usize,
+}
+
+impl LogPane {
+    /// Writer for the stderr log layer: silent while the dashboard is up.
+    pub fn stderr_writer(&self) -> LogWriter {
+        if self.state.lock().unwrap().active {
+            LogWriter::Sink
+        } else {
+            LogWriter::Stderr(io::stderr())
+        }
+    }
+
+    /// Writer for the log pane's layer: silent while the dashboard is not up.
+    pub fn pane_writer(&self) -> LogWriter {
+        if self.state.lock().unwrap().active {
+            LogWriter::Pane
+        } else {
+            LogWriter::Sink
+        }
+    }
+
+    fn push(&self, line: String) {
+        let mut state = self.state.lock().unwrap();
+        if state.lines.len() == LOG_LINES {
+            state.lines.pop_front();
+        }
+        state.lines.push_back(line);
+        state.pushed += 1;
+    }
+
+    fn set_active(&self, active: bool) {
+        self.state.lock().unwrap().active = active;
+    }
+
+    /// Up to `height` lines ending `scroll` lines before the newest, and the number
+    /// of lines ever pushed.
+    fn tail(&self, height: usize, scroll: usize) -> (Vec<String>, usize) {
+        let state = self.state.lock().unwrap();
+        let end = state.lines.len().saturating_sub(scroll);
+        let start = end.saturating_sub(height);
+        let lines = state.lines.range(start..end).cloned().collect();
+        (lines, state.pushed)
+    }
+
+    fn len(&self) -> usize {
+        self.state.lock().unwrap().lines.len()
+    }
+}
+
+pub enum LogWriter {
+    Stderr(io::Stderr),
+    Pane,
+    Sink,
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            LogWriter::Stderr(stderr) => stderr.write(buf),
+            LogWriter::Pane => {
+                for line in String::from_utf8_lossy(buf).lines() {
+                    if !line.trim().is_empty() {
+                        LOG.push(line.trim_end().to_string());
+                    }
+                }
+                Ok(buf.len())
+            }
+            LogWriter::Sink => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            LogWriter::Stderr(stderr) => stderr.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A log pane line for a run monitor note, or `None` for routine bookkeeping.
+fn describe_note(name: &str, payload: &Value) -> Option<String> {
+    let seconds = |key: &str| payload.get(key).and_then(Value::as_f64).unwrap_or(0.0) / 1000.0;
+    let chunk = match payload.get("chunk_index").and_then(Value::as_u64) {
+        Some(index) => format!("chunk {} ", index + 1),
+        None => String::new(),
+    };
+    let line = match name {
+        "tokens.counted"
+        | "files.cleanup.register"
+        | "files.cleanup.deleted"
+        | "manifest.chunk.create"
+        | "files.reuse" => return None,
+        "quota.sleep" => format!(
+            "quota sleep {:.0}s ({})",
+            seconds("delay_ms"),
+            payload.get("bucket").and_then(Value::as_str).unwrap_or("?")
+        ),
+        "chunk.failed" => format!(
+            "{chunk}failed: {}",
+            payload.get("error").and_then(Value::as_str).unwrap_or("?")
+        ),
+        "chunk.skipped" => format!("{chunk}skipped"),
+        _ if name.starts_with("retry.") => {
+            let cause = payload
+                .get("status")
+                .map(Value::to_string)
+                .or_else(|| {
+                    payload
+                        .get("error")
+                        .and_then(Value::as_str)
+                        .map(str::to_string)
+                })
+                .unwrap_or_default();
+            format!(
+                "{chunk}{} retrying in {:.0}s (attempt {}, {cause})",
+                &name["retry.".len()..],
+                seconds("delay_ms"),
+                payload.get("attempt").unwrap_or(&Value::Null)
+            )
+        }
+        _ => format!("{name} {payload}"),
+    };
+    Some(line)
+}
+
+/// The job a row belongs to, for expanding and collapsing its chunk rows.
+fn row_job(scope: &ProgressScope) -> Option<&str> {
+    match scope {
+        ProgressScope::Run => None,
+        ProgressScope::Job { id, .. } => Some(id),
+        ProgressScope::ChunkProgress { job_id, .. }
+        | ProgressScope::ChunkDetail { job_id, .. }
+        | ProgressScope::Download { job_id, .. }
+        | ProgressScope::Encode { job_id, .. } => Some(job_id),
+    }
 }
 
 pub async fn run_tui(
     mut rx: UnboundedReceiver<Progress>,
     cancel: UnboundedSender<()>,
+    control: ChunkControl,
+    usage: UsageFeed,
 ) -> anyhow::Result<()> {
     let mut out = stdout();
     let (col, mut row) = cursor::position()?;
@@ -34,10 +249,26 @@ pub async fn run_tui(
     }
     terminal::enable_raw_mode()?;
     execute!(out, cursor::Hide)?;
+    LOG.set_active(true);
 
-    let base_row = row;
+    let mut base_row = row;
     let mut rows: HashMap<ProgressScope, RowState> = HashMap::new();
     let mut order: Vec<ProgressScope> = Vec::new();
+    // Jobs whose chunk rows are shown. Until a job is toggled by hand, the newest
+    // job is the expanded one.
+    let mut expanded: HashSet<String> = HashSet::new();
+    let mut follow_newest = true;
+    let mut selected: usize = 0;
+    let mut offset: usize = 0;
+    let mut totals = (0, 0, 0.0);
+    let mut totals_at: Option<Instant> = None;
+    let mut throughput = Throughput::default();
+    let mut notes_seen = Vec::new();
+    let mut show_log = false;
+    // Whether the arrow keys scroll the log pane rather than the list.
+    let mut log_focus = false;
+    let mut log_scroll: usize = 0;
+    let mut log_read: usize = 0;
     let mut closed = false;
     let frames = ["|", "/", "-", "\\"];
     let mut frame_idx: usize = 0;
@@ -46,24 +277,31 @@ pub async fn run_tui(
         loop {
             match rx.try_recv() {
                 Ok(evt) => {
+                    let rate = throughput.record(&evt);
                     let key = evt.scope.clone();
                     let entry = rows.entry(key.clone()).or_insert(RowState {
                         stage: evt.stage,
                         cur: 0,
                         total: 1,
                         status: String::new(),
-                        finished_at: None,
+                        finished: false,
+                        rate: None,
                     });
                     if !order.contains(&key) {
+                        if let ProgressScope::Job { id, .. } = &key {
+                            if follow_newest {
+                                expanded.clear();
+                                expanded.insert(id.clone());
+                            }
+                        }
                         order.push(key.clone());
                     }
                     entry.stage = evt.stage;
                     entry.cur = evt.current.min(evt.total.max(1));
                     entry.total = evt.total.max(1);
                     entry.status = evt.status;
-                    if evt.finished {
-                        entry.finished_at = Some(std::time::Instant::now());
-                    }
+                    entry.finished |= evt.finished;
+                    entry.rate = rate;
                 }
                 Err(TryRecvError::Empty) => break,
                 Err(TryRecvError::Disconnected) => {
@@ -73,154 +311,232 @@ pub async fn run_tui(
             }
         }
 
+        if totals_at.is_none_or(|at| at.elapsed() >= Duration::from_millis(500)) {
+            totals = usage.totals();
+            for (name, payload) in usage.notes_since(&mut notes_seen) {
+                if let Some(line) = describe_note(&name, &payload) {
+                    LOG.push(line);
+                }
+            }
+            totals_at = Some(Instant::now());
+        }
+
+        // Show the run bar only when there is more than one bar to sum up.
+        let job_count = rows
+            .keys()
+            .filter(|s| matches!(s, ProgressScope::Job { .. }))
+            .count();
+        let has_chunks = rows.keys().any(|s| {
+            matches!(
+                s,
+                ProgressScope::ChunkProgress { .. } | ProgressScope::ChunkDetail { .. }
+            )
+        });
+        let visible: Vec<&ProgressScope> = order
+            .iter()
+            .filter(|scope| match scope {
+                ProgressScope::Run => job_count != 1 || has_chunks,
+                ProgressScope::ChunkDetail { job_id, .. } => expanded.contains(job_id),
+                _ => true,
+            })
+            .collect();
+        selected = selected.min(visible.len().saturating_sub(1));
+
+        // Keep the header, the hint line, and the list on screen, scrolling the
+        // terminal up when the list outgrows the space below the prompt.
+        let (cols, term_rows) = terminal::size()
+            .map(|(c, r)| (c as usize, r))
+            .unwrap_or((80, 24));
+        let log_rows = if show_log { LOG_HEIGHT + 1 } else { 0 };
+        let needed = (visible.len() + 3 + log_rows).min(term_rows as usize) as u16;
+        let room = term_rows.saturating_sub(base_row);
+        if needed > room && base_row > 0 {
+            let shift = (needed - room).min(base_row);
+            queue!(out, terminal::ScrollUp(shift))?;
+            base_row -= shift;
+        }
+        let list_height = (term_rows.saturating_sub(base_row) as usize)
+            .saturating_sub(3 + log_rows)
+            .max(1);
+        if selected < offset {
+            offset = selected;
+        } else if selected >= offset + list_height {
+            offset = selected + 1 - list_height;
+        }
+        offset = offset.min(visible.len().saturating_sub(list_height));
+
         queue!(
             out,
             cursor::MoveTo(0, base_row),
             Clear(ClearType::FromCursorDown)
         )?;
-
         frame_idx = (frame_idx + 1) % frames.len();
 
-        // Trim finished rows after a short delay.
-        let now = std::time::Instant::now();
-        rows.retain(|_, state| match state.finished_at {
-            Some(done) => now.duration_since(done).as_millis() < 1200,
-            None => true,
-        });
-        order.retain(|scope| rows.contains_key(scope));
-
-        // Determine whether to show the run bar when there is only one job and no chunk bars.
-        let job_count = rows
-            .keys()
-            .filter(|s| matches!(s, ProgressScope::Job { .. }))
-            .count();
-        let chunk_progress_count = rows
-            .keys()
-            .filter(|s| matches!(s, ProgressScope::ChunkProgress { .. }))
-            .count();
-        let chunk_detail_count = rows
-            .keys()
-            .filter(|s| matches!(s, ProgressScope::ChunkDetail { .. }))
-            .count();
+        let (input_tokens, output_tokens, cost) = totals;
+        let mut header = format!(
+            "tokens {input_tokens} in / {output_tokens} out · est. {}",
+            usage.cost.currency().format(cost, 4)
+        );
+        if control.is_paused() {
+            header.push_str(" · paused");
+        }
+        let (log_lines, pushed) = LOG.tail(LOG_HEIGHT, log_scroll);
+        if show_log {
+            log_read = pushed;
+        } else if pushed > log_read {
+            header.push_str(&format!(" · {} new log lines (l)", pushed - log_read));
+        }
+        queue!(
+            out,
+            PrintStyledContent(truncate_status(&header, cols).with(Color::Cyan)),
+            cursor::MoveTo(0, base_row + 1),
+            PrintStyledContent(
+                truncate_status(
+                    "up/down select · enter expand · p pause · s skip chunk · l log · tab focus log · q quit",
+                    cols
+                )
+                .with(Color::DarkGrey)
+            )
+        )?;
 
-        let start_row = base_row;
-        let cols = terminal::size().map(|(c, _)| c as usize).unwrap_or(80);
-        let mut render_idx = 0;
-        for scope in order.clone() {
-            if let Some(state) = rows.get(&scope) {
-                if matches!(scope, ProgressScope::Run)
-                    && job_count == 1
-                    && chunk_progress_count == 0
-                    && chunk_detail_count == 0
-                {
-                    // Collapse run bar when single job/chunk to show only one bar.
-                    continue;
+        let mut y = base_row + 2;
+        for (idx, scope) in visible.iter().enumerate().skip(offset).take(list_height) {
+            let Some(state) = rows.get(*scope) else {
+                continue;
+            };
+            let marker = if idx == selected { ">" } else { " " };
+            let prefix = match scope {
+                ProgressScope::Run => format!("{marker} "),
+                ProgressScope::Job { id, .. } => {
+                    let fold = if expanded.contains(id) { "-" } else { "+" };
+                    format!("{marker} {fold} ")
                 }
-
-                let percent = if state.total > 0 {
-                    (state.cur as f64 / state.total as f64).min(1.0)
-                } else {
-                    0.0
-                };
-                let percent_label = format!("{:>3}%", (percent * 100.0).round() as u64);
-
-                let count_label = if state.total > 0 {
-                    format!("{:>5}/{:<5}", state.cur.min(state.total), state.total)
-                } else {
-                    "  -/- ".to_string()
-                };
-
-                let label_text = if !matches!(scope, ProgressScope::Run) {
-                    format!("{} · {}", scope, state.stage.label())
-                } else {
-                    scope.to_string()
-                };
-
-                let spin = if percent >= 1.0 {
-                    " "
-                } else {
-                    frames[frame_idx]
-                };
-
-                let min_bar_width = 10;
-                let base_len = 2 /*spin+space*/
-                    + label_text.len()
-                    + 2 /*leading space+bracket*/
-                    + 2 /*trailing bracket+space*/
-                    + percent_label.len()
-                    + 1 /*space*/
-                    + count_label.len()
-                    + 1; /*space before status*/
-
-                let available = cols.saturating_sub(base_len);
-
-                let mut status_text = state.status.clone();
-
-                if available <= min_bar_width {
-                    status_text.clear();
-                } else {
-                    let max_status_len = available - min_bar_width;
-
-                    if status_text.len() > max_status_len {
-                        status_text = truncate_status(&status_text, max_status_len);
-                    }
+                ProgressScope::ChunkProgress { .. }
+                | ProgressScope::Download { .. }
+                | ProgressScope::Encode { .. } => {
+                    format!("{marker}   ")
                 }
-
-                let status_len = status_text.len();
-                let bar_width = available.saturating_sub(status_len).max(1);
-                let bar = progress_bar(percent, bar_width);
-                let styled_bar = if percent >= 1.0 {
-                    bar.clone().with(Color::Green)
-                } else {
-                    bar.clone().with(Color::Yellow)
-                };
-                let status_style = if percent >= 1.0 {
-                    status_text.clone().with(Color::Green)
-                } else {
-                    status_text.clone().with(Color::White)
-                };
+                ProgressScope::ChunkDetail { .. } => format!("{marker}     "),
+            };
+            render_row(
+                &mut out,
+                y,
+                cols,
+                &prefix,
+                scope,
+                state,
+                frames[frame_idx],
+                idx == selected,
+            )?;
+            y += 1;
+        }
+        if show_log {
+            let title = if log_focus {
+                "── log (up/down scroll, tab back) "
+            } else {
+                "── log "
+            };
+            let rule = format!(
+                "{title}{}",
+                "─".repeat(cols.saturating_sub(title.chars().count()))
+            );
+            queue!(
+                out,
+                cursor::MoveTo(0, y),
+                PrintStyledContent(rule.with(Color::DarkGrey))
+            )?;
+            y += 1;
+            for line in log_lines {
                 queue!(
                     out,
-                    cursor::MoveTo(0, start_row + render_idx as u16),
+                    cursor::MoveTo(0, y),
                     Clear(ClearType::CurrentLine),
-                    PrintStyledContent(format!("{spin} {label_text} ").with(Color::White)),
-                    PrintStyledContent(" [".with(Color::DarkGrey)),
-                    PrintStyledContent(styled_bar),
-                    PrintStyledContent("] ".with(Color::DarkGrey)),
-                    PrintStyledContent(percent_label.with(Color::Cyan)),
-                    PrintStyledContent(" ".with(Color::DarkGrey)),
-                    PrintStyledContent(count_label.with(Color::Magenta)),
-                    PrintStyledContent(" ".with(Color::DarkGrey)),
-                    PrintStyledContent(status_style)
+                    PrintStyledContent(truncate_status(&line, cols).with(Color::Yellow))
                 )?;
-                render_idx += 1;
+                y += 1;
             }
         }
-        queue!(
-            out,
-            cursor::MoveTo(0, start_row + render_idx as u16),
-            Clear(ClearType::CurrentLine)
-        )?;
+        queue!(out, cursor::MoveTo(0, y), Clear(ClearType::CurrentLine))?;
         out.flush()?;
 
-        if closed && rows.values().all(|state| state.cur >= state.total) {
+        if closed {
             break;
         }
 
-        if event::poll(std::time::Duration::from_millis(33))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press
-                    && (key.code == KeyCode::Char('q')
-                        || (key.code == KeyCode::Char('c')
-                            && key.modifiers.contains(KeyModifiers::CONTROL)))
-                {
+        if event::poll(Duration::from_millis(33))? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let current = visible.get(selected).copied();
+            match key.code {
+                KeyCode::Char('q') => {
                     let _ = cancel.send(());
                     break;
                 }
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    let _ = cancel.send(());
+                    break;
+                }
+                KeyCode::Char('l') => {
+                    show_log = !show_log;
+                    log_focus &= show_log;
+                    log_scroll = 0;
+                }
+                KeyCode::Tab if show_log => log_focus = !log_focus,
+                KeyCode::Up | KeyCode::Char('k') if log_focus => {
+                    log_scroll = (log_scroll + 1).min(LOG.len().saturating_sub(LOG_HEIGHT));
+                }
+                KeyCode::Down | KeyCode::Char('j') if log_focus => {
+                    log_scroll = log_scroll.saturating_sub(1);
+                }
+                KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => selected += 1,
+                KeyCode::PageUp => selected = selected.saturating_sub(list_height),
+                KeyCode::PageDown => selected += list_height,
+                KeyCode::Home => selected = 0,
+                KeyCode::End => selected = visible.len(),
+                KeyCode::Enter | KeyCode::Char(' ') | KeyCode::Left | KeyCode::Right => {
+                    if let Some(job_id) = current.and_then(row_job) {
+                        follow_newest = false;
+                        let open = match key.code {
+                            KeyCode::Left => false,
+                            KeyCode::Right => true,
+                            _ => !expanded.contains(job_id),
+                        };
+                        if open {
+                            expanded.insert(job_id.to_string());
+                        } else {
+                            expanded.remove(job_id);
+                            // Keep the selection on the job rather than a hidden chunk.
+                            if let Some(pos) = visible.iter().position(|scope| {
+                                matches!(scope, ProgressScope::Job { id, .. } if id == job_id)
+                            }) {
+                                selected = pos;
+                            }
+                        }
+                    }
+                }
+                KeyCode::Char('p') => {
+                    control.toggle_pause();
+                }
+                KeyCode::Char('s') => {
+                    if let Some(scope @ ProgressScope::ChunkDetail { job_id, index, .. }) = current
+                    {
+                        if let Some(state) = rows.get_mut(scope).filter(|state| !state.finished) {
+                            control.skip(job_id, *index);
+                            state.status = "skipping".into();
+                        }
+                    }
+                }
+                _ => {}
             }
         }
     }
 
+    LOG.set_active(false);
     terminal::disable_raw_mode()?;
     let (_, term_rows) = terminal::size().unwrap_or((80, 24));
     let current_row = cursor::position().map(|(_, r)| r).unwrap_or(base_row);
@@ -241,6 +557,113 @@ pub async fn run_tui(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
+fn render_row(
+    out: &mut Stdout,
+    y: u16,
+    cols: usize,
+    prefix: &str,
+    scope: &ProgressScope,
+    state: &RowState,
+    frame: &str,
+    selected: bool,
+) -> anyhow::Result<()> {
+    let percent = if state.total > 0 {
+        (state.cur as f64 / state.total as f64).min(1.0)
+    } else {
+        0.0
+    };
+    let done = percent >= 1.0 || state.finished;
+    let percent_label = format!("{:>3}%", (percent * 100.0).round() as u64);
+
+    let count_label = if matches!(
+        scope,
+        ProgressScope::Download { .. } | ProgressScope::Encode { .. }
+    ) {
+        // Byte counts or media milliseconds, shown in the status instead.
+        String::new()
+    } else if state.total > 0 {
+        format!("{:>5}/{:<5}", state.cur.min(state.total), state.total)
+    } else {
+        "  -/- ".to_string()
+    };
+
+    let eta_label = match state.rate {
+        Some(rate) if !done => format!(
+            " {:.1} {}/min eta {}",
+            rate.per_minute,
+            rate.unit,
+            format_eta(rate.eta_seconds)
+        ),
+        _ => String::new(),
+    };
+
+    let label_text = if !matches!(scope, ProgressScope::Run) {
+        format!("{} · {}", scope, state.stage.label())
+    } else {
+        scope.to_string()
+    };
+
+    let spin = if done { " " } else { frame };
+
+    let min_bar_width = 10;
+    let base_len = prefix.len()
+        + 2 /*spin+space*/
+        + label_text.len()
+        + 2 /*leading space+bracket*/
+        + 2 /*trailing bracket+space*/
+        + percent_label.len()
+        + 1 /*space*/
+        + count_label.len()
+        + eta_label.len()
+        + 1; /*space before status*/
+
+    let available = cols.saturating_sub(base_len);
+
+    let mut status_text = state.status.clone();
+
+    if available <= min_bar_width {
+        status_text.clear();
+    } else {
+        let max_status_len = available - min_bar_width;
+
+        if status_text.len() > max_status_len {
+            status_text = truncate_status(&status_text, max_status_len);
+        }
+    }
+
+    let status_len = status_text.len();
+    let bar_width = available.saturating_sub(status_len).max(1);
+    let bar = progress_bar(percent, bar_width);
+    let styled_bar = if done {
+        bar.with(Color::Green)
+    } else {
+        bar.with(Color::Yellow)
+    };
+    let status_style = if done {
+        status_text.with(Color::Green)
+    } else {
+        status_text.with(Color::White)
+    };
+    let label_color = if selected { Color::Cyan } else { Color::White };
+    queue!(
+        out,
+        cursor::MoveTo(0, y),
+        Clear(ClearType::CurrentLine),
+        PrintStyledContent(format!("{prefix}{spin} {label_text} ").with(label_color)),
+        PrintStyledContent(" [".with(Color::DarkGrey)),
+        PrintStyledContent(styled_bar),
+        PrintStyledContent("] ".with(Color::DarkGrey)),
+        PrintStyledContent(percent_label.with(Color::Cyan)),
+        PrintStyledContent(" ".with(Color::DarkGrey)),
+        PrintStyledContent(count_label.with(Color::Magenta)),
+        PrintStyledContent(eta_label.with(Color::Blue)),
+        PrintStyledContent(" ".with(Color::DarkGrey)),
+        PrintStyledContent(status_style)
+    )?;
+    Ok(())
+}
+
 fn progress_bar(progress: f64, width: usize) -> String {
     let filled = (progress * width as f64).round() as usize;
     let mut bar = String::with_capacity(width);
@@ -1,55 +1,58 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
 use std::{fs, io};
 
+use crate::core::ConflictPolicy;
+
 pub fn ensure_dir(path: &Path) -> io::Result<()> {
     fs::create_dir_all(path)
 }
 
-pub fn resolve_path_with_prompt(path: &Path, is_dir: bool) -> Result<Option<PathBuf>> {
+/// Decide where to write `path` when it may already exist.
+///
+/// Returns `None` when the output should not be written (skipped or stopped at the
+/// prompt). Prompting needs a terminal; without one the run fails instead of hanging.
+pub fn resolve_output_path(
+    path: &Path,
+    is_dir: bool,
+    policy: ConflictPolicy,
+) -> Result<Option<PathBuf>> {
     if !path.exists() {
         return Ok(Some(path.to_path_buf()));
     }
 
+    match policy {
+        ConflictPolicy::Overwrite => Ok(Some(path.to_path_buf())),
+        ConflictPolicy::Rename => Ok(Some(free_name(path, is_dir))),
+        ConflictPolicy::Skip => Ok(None),
+        ConflictPolicy::Fail => bail!("{} already exists", path.display()),
+        ConflictPolicy::Prompt if !io::stdin().is_terminal() => bail!(
+            "{} already exists; pass --on-conflict overwrite|rename|skip to decide without a prompt",
+            path.display()
+        ),
+        ConflictPolicy::Prompt => prompt_for_path(path, is_dir),
+    }
+}
+
+fn prompt_for_path(path: &Path, is_dir: bool) -> Result<Option<PathBuf>> {
     println!(
         "{} already exists. [o]verwrite, [n]ew name, [s]top? ",
         path.display()
     );
     loop {
         let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(None);
+        }
         let choice = input.trim().to_lowercase();
         match choice.as_str() {
             "o" | "overwrite" => return Ok(Some(path.to_path_buf())),
             "s" | "q" | "stop" | "cancel" => return Ok(None),
             "n" | "new" => {
-                let mut idx = 1;
-                loop {
-                    let candidate = if is_dir {
-                        let name = path
-                            .file_name()
-                            .map(|s| s.to_string_lossy().to_string())
-                            .unwrap_or_else(|| "output".into());
-                        let parent = path.parent().unwrap_or_else(|| Path::new("."));
-                        parent.join(format!("{name}-{idx}"))
-                    } else {
-                        let stem = path
-                            .file_stem()
-                            .map(|s| s.to_string_lossy().to_string())
-                            .unwrap_or_else(|| "output".into());
-                        let ext = path
-                            .extension()
-                            .map(|s| format!(".{}", s.to_string_lossy()))
-                            .unwrap_or_default();
-                        let parent = path.parent().unwrap_or_else(|| Path::new("."));
-                        parent.join(format!("{stem}-{idx}{ext}"))
-                    };
-                    if !candidate.exists() {
-                        println!("using {}", candidate.display());
-                        return Ok(Some(candidate));
-                    }
-                    idx += 1;
-                }
+                let candidate = free_name(path, is_dir);
+                println!("using {}", candidate.display());
+                return Ok(Some(candidate));
             }
             _ => {
                 println!("Choose [o]verwrite, [n]ew name, or [s]top: ");
@@ -58,6 +61,35 @@ pub fn resolve_path_with_prompt(path: &Path, is_dir: bool) -> Result<Option<Path
     }
 }
 
+/// The first `name-N` (or `stem-N.ext`) beside `path` that does not exist yet.
+fn free_name(path: &Path, is_dir: bool) -> PathBuf {
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut idx = 1;
+    loop {
+        let candidate = if is_dir {
+            let name = path
+                .file_name()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "output".into());
+            parent.join(format!("{name}-{idx}"))
+        } else {
+            let stem = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "output".into());
+            let ext = path
+                .extension()
+                .map(|s| format!(".{}", s.to_string_lossy()))
+                .unwrap_or_default();
+            parent.join(format!("{stem}-{idx}{ext}"))
+        };
+        if !candidate.exists() {
+            return candidate;
+        }
+        idx += 1;
+    }
+}
+
 pub fn slugify<S: AsRef<str>>(input: S) -> String {
     input
         .as_ref()
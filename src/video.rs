@@ -4,16 +4,29 @@ use rayon::ThreadPoolBuilder;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Mutex, OnceLock};
 
+use crate::tools::{self, Tool};
 use crate::utils::ensure_dir;
 
 pub const DEFAULT_MAX_CHUNK_SECONDS: f64 = 7_200.0;
 pub const DEFAULT_MAX_CHUNK_BYTES: u64 = 500 * 1024 * 1024;
 pub const DEFAULT_TOKENS_PER_SECOND: f64 = 300.0;
+/// Gemini bills audio at a flat 32 tokens per second regardless of media resolution.
+pub const DEFAULT_AUDIO_TOKENS_PER_SECOND: f64 = 32.0;
+/// How far before a fixed cut a silence or scene change may pull it.
+pub const DEFAULT_BOUNDARY_TOLERANCE_SECONDS: f64 = 60.0;
+/// Interval between frames compared when looking for slide changes.
+pub const SLIDE_SAMPLE_SECONDS: f64 = 2.0;
+/// dHash bits that must differ before a frame counts as a new slide.
+const SLIDE_HASH_THRESHOLD: u32 = 10;
 
 static ENCODE_CACHE: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
 
@@ -58,14 +71,346 @@ impl VideoEncoderPreference {
     }
 }
 
+/// Where long recordings are split into chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkBoundaryMode {
+    /// Cut at the size/duration limit.
+    #[default]
+    Fixed,
+    /// Move each cut back to the nearest pause in the audio.
+    Silence,
+    /// Move each cut back to the nearest scene change, e.g. a slide transition.
+    Scene,
+}
+
+impl ChunkBoundaryMode {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "fixed" | "" => Ok(Self::Fixed),
+            "silence" => Ok(Self::Silence),
+            "scene" => Ok(Self::Scene),
+            other => bail!("Unknown chunk boundary mode '{}'", other),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Fixed => "fixed",
+            Self::Silence => "silence",
+            Self::Scene => "scene",
+        }
+    }
+}
+
+/// What a video is sent to the model as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VideoMode {
+    /// The normalized video, chunked as needed.
+    #[default]
+    Full,
+    /// Only the distinct slide frames, as images.
+    Slides,
+    /// The video with each chunk's slide frames attached as images.
+    Hybrid,
+    /// Only the soundtrack, billed at audio rates.
+    Audio,
+}
+
+impl VideoMode {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "full" | "" => Ok(Self::Full),
+            "slides" => Ok(Self::Slides),
+            "hybrid" => Ok(Self::Hybrid),
+            "audio" => Ok(Self::Audio),
+            other => bail!("Unknown video mode '{}'", other),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::Slides => "slides",
+            Self::Hybrid => "hybrid",
+            Self::Audio => "audio",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SlideFrame {
+    pub index: usize,
+    pub seconds: f64,
+    pub path: PathBuf,
+}
+
+/// How chunks are cut out of the normalized recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkExtraction {
+    /// Stream copy from the keyframe at or before each cut; fast, but a chunk
+    /// can start up to a GOP early.
+    #[default]
+    Copy,
+    /// Seek to the exact cut and re-encode the chunk.
+    Accurate,
+}
+
+impl ChunkExtraction {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "copy" | "fast" | "" => Ok(Self::Copy),
+            "accurate" => Ok(Self::Accurate),
+            other => bail!("Unknown chunk extraction mode '{}'", other),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Copy => "copy",
+            Self::Accurate => "accurate",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkBoundaries {
+    pub mode: ChunkBoundaryMode,
+    pub tolerance_seconds: f64,
+    pub extraction: ChunkExtraction,
+}
+
+impl Default for ChunkBoundaries {
+    fn default() -> Self {
+        Self {
+            mode: ChunkBoundaryMode::Fixed,
+            tolerance_seconds: DEFAULT_BOUNDARY_TOLERANCE_SECONDS,
+            extraction: ChunkExtraction::Copy,
+        }
+    }
+}
+
+/// Cutting long silences out of a recording before it is chunked
+/// (`video.remove_silence`), so slow lectures cost fewer duration-billed tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SilenceRemoval {
+    pub enabled: bool,
+    /// Silences at least this long are cut.
+    pub min_seconds: f64,
+    /// Audio quieter than this counts as silence.
+    pub threshold_db: f64,
+    /// Silence left on each side of a cut so speech is not clipped.
+    pub padding_seconds: f64,
+}
+
+impl Default for SilenceRemoval {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_seconds: 2.0,
+            threshold_db: -35.0,
+            padding_seconds: 0.25,
+        }
+    }
+}
+
+/// A stretch of a condensed recording and where it starts in the original.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct KeptSpan {
+    /// Start in the condensed recording, in seconds.
+    pub start: f64,
+    /// Start in the original recording, in seconds.
+    pub source_start: f64,
+    pub duration: f64,
+}
+
+/// How times in a recording with its silences removed map back onto the
+/// original, kept in the chunk manifest and chunk descriptors.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TimeMap {
+    pub spans: Vec<KeptSpan>,
+}
+
+impl TimeMap {
+    fn from_kept(kept: &[(f64, f64)]) -> Self {
+        let mut start = 0.0;
+        let spans = kept
+            .iter()
+            .map(|&(from, to)| {
+                let span = KeptSpan {
+                    start,
+                    source_start: from,
+                    duration: to - from,
+                };
+                start += to - from;
+                span
+            })
+            .collect();
+        Self { spans }
+    }
+
+    /// The original time of `seconds` in the condensed recording.
+    pub fn to_source(&self, seconds: f64) -> f64 {
+        let Some(span) = self
+            .spans
+            .iter()
+            .rev()
+            .find(|span| span.start <= seconds)
+            .or(self.spans.first())
+        else {
+            return seconds;
+        };
+        span.source_start + (seconds - span.start).clamp(0.0, span.duration)
+    }
+
+    /// The spans overlapping `start..end` of the condensed recording.
+    pub fn slice(&self, start: f64, end: f64) -> Self {
+        Self {
+            spans: self
+                .spans
+                .iter()
+                .filter(|span| span.start < end && span.start + span.duration > start)
+                .copied()
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct EncoderSpec {
     pub preference: VideoEncoderPreference,
     pub codec: &'static str,
     pub args: &'static [&'static str],
+    /// Constant-quality option `EncodeSettings::crf` maps to, when the encoder has one.
+    pub quality_flag: Option<&'static str>,
     pub accelerated: bool,
 }
 
+/// Quality and speed knobs for re-encoding videos (`video.crf`, `video.max_height`, ...).
+/// Unset fields keep each encoder's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EncodeSettings {
+    /// Constant-quality level; lower is better (x264 defaults to 23).
+    pub crf: Option<u32>,
+    /// Target video bitrate such as `1500k`; takes precedence over `crf`.
+    pub video_bitrate: Option<String>,
+    /// AAC bitrate, `192k` when unset.
+    pub audio_bitrate: Option<String>,
+    /// Downscale taller videos to this height.
+    pub max_height: Option<u32>,
+    /// Drop frames above this rate.
+    pub max_fps: Option<f64>,
+    /// `-hwaccel` decode method, such as `auto`, `cuda`, or `videotoolbox`.
+    pub hwaccel: Option<String>,
+    /// Remux sources that are already H.264/AAC within `max_height` and `max_fps`
+    /// with `-c copy` instead of re-encoding them.
+    pub skip_when_compatible: bool,
+}
+
+impl EncodeSettings {
+    /// Whether the streams in ffprobe's `-show_streams` JSON can be copied as-is:
+    /// 8-bit 4:2:0 H.264 video, AAC or no audio, and no taller or faster than the caps.
+    fn is_compatible(&self, probe: &Value) -> bool {
+        let streams = probe
+            .get("streams")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let field =
+            |stream: &Value, key: &str| stream.get(key).and_then(Value::as_str).map(str::to_string);
+        let of_type = |kind: &str| -> Vec<&Value> {
+            streams
+                .iter()
+                .filter(|stream| field(stream, "codec_type").as_deref() == Some(kind))
+                .collect()
+        };
+        let Some(&video) = of_type("video").first() else {
+            return false;
+        };
+        let video_ok = field(video, "codec_name").as_deref() == Some("h264")
+            && field(video, "pix_fmt").as_deref() == Some("yuv420p");
+        let audio_ok = of_type("audio")
+            .iter()
+            .all(|stream| field(stream, "codec_name").as_deref() == Some("aac"));
+        let height_ok = match self.max_height {
+            Some(max) => {
+                video
+                    .get("height")
+                    .and_then(Value::as_u64)
+                    .unwrap_or(u64::MAX)
+                    <= max as u64
+            }
+            None => true,
+        };
+        let fps_ok = match self.max_fps {
+            Some(max) => field(video, "avg_frame_rate")
+                .as_deref()
+                .and_then(parse_rate)
+                .is_some_and(|fps| fps <= max + 0.01),
+            None => true,
+        };
+        video_ok && audio_ok && height_ok && fps_ok
+    }
+
+    fn audio_bitrate(&self) -> &str {
+        self.audio_bitrate.as_deref().unwrap_or("192k")
+    }
+
+    /// Suffix for normalized file names, so changing a knob re-encodes instead of
+    /// reusing a file made with other settings.
+    fn file_tag(&self) -> String {
+        if *self == Self::default() {
+            return String::new();
+        }
+        use sha2::{Digest, Sha256};
+        let digest = Sha256::digest(serde_json::to_vec(self).unwrap_or_default());
+        format!("-{}", &hex::encode(digest)[..8])
+    }
+
+    /// Scale and frame-rate filters for a source with `metadata`.
+    fn filters(&self, metadata: Option<&VideoMetadata>) -> Vec<String> {
+        let mut filters = Vec::new();
+        if let Some(max_height) = self.max_height {
+            if metadata.and_then(|meta| meta.height).unwrap_or(u32::MAX) > max_height {
+                filters.push(format!("scale=-2:{max_height}"));
+            }
+        }
+        if let Some(max_fps) = self.max_fps {
+            if metadata.and_then(|meta| meta.fps).unwrap_or(0.0) > max_fps {
+                filters.push(format!("fps={max_fps}"));
+            }
+        }
+        filters
+    }
+
+    /// Video encoder arguments for `spec` with `filters` applied ahead of any the
+    /// encoder needs itself (VAAPI uploads frames in its own `-vf`).
+    fn video_args(&self, spec: &EncoderSpec, filters: &[String]) -> Vec<String> {
+        let mut args: Vec<String> = spec.args.iter().map(|arg| arg.to_string()).collect();
+        if !filters.is_empty() {
+            let chain = filters.join(",");
+            match args.iter().position(|arg| arg == "-vf") {
+                Some(index) => args[index + 1] = format!("{chain},{}", args[index + 1]),
+                None => {
+                    args.splice(0..0, ["-vf".to_string(), chain]);
+                }
+            }
+        }
+        if let Some(bitrate) = &self.video_bitrate {
+            args.extend(["-b:v".to_string(), bitrate.clone()]);
+        } else if let (Some(crf), Some(flag)) = (self.crf, spec.quality_flag) {
+            args.extend([flag.to_string(), crf.to_string()]);
+        }
+        args
+    }
+
+    fn decode_args(&self) -> Vec<String> {
+        self.hwaccel
+            .iter()
+            .flat_map(|method| ["-hwaccel".to_string(), method.clone()])
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct NormalizationResult {
     pub path: PathBuf,
@@ -77,6 +422,8 @@ pub struct VideoChunk {
     pub start_seconds: f64,
     pub end_seconds: f64,
     pub path: PathBuf,
+    /// Duration ffprobe reports for the extracted file.
+    pub actual_seconds: Option<f64>,
 }
 
 #[derive(Debug, Clone)]
@@ -100,36 +447,42 @@ static ENCODER_SPECS: &[EncoderSpec] = &[
             "-bf",
             "2",
         ],
+        quality_flag: Some("-crf"),
         accelerated: false,
     },
     EncoderSpec {
         preference: VideoEncoderPreference::Nvenc,
         codec: "h264_nvenc",
         args: &["-c:v", "h264_nvenc", "-preset", "p4", "-tune", "hq"],
+        quality_flag: Some("-cq"),
         accelerated: true,
     },
     EncoderSpec {
         preference: VideoEncoderPreference::Videotoolbox,
         codec: "h264_videotoolbox",
         args: &["-c:v", "h264_videotoolbox"],
+        quality_flag: None,
         accelerated: true,
     },
     EncoderSpec {
         preference: VideoEncoderPreference::Qsv,
         codec: "h264_qsv",
         args: &["-c:v", "h264_qsv"],
+        quality_flag: Some("-global_quality"),
         accelerated: true,
     },
     EncoderSpec {
         preference: VideoEncoderPreference::Vaapi,
         codec: "h264_vaapi",
         args: &["-vf", "format=nv12,hwupload", "-c:v", "h264_vaapi"],
+        quality_flag: Some("-qp"),
         accelerated: true,
     },
     EncoderSpec {
         preference: VideoEncoderPreference::Amf,
         codec: "h264_amf",
         args: &["-c:v", "h264_amf"],
+        quality_flag: None,
         accelerated: true,
     },
 ];
@@ -215,7 +568,7 @@ pub fn ffmpeg_encoder_names() -> HashSet<String> {
         }
     }
 
-    let output = Command::new("ffmpeg")
+    let output = tools::command(Tool::Ffmpeg)
         .args(["-hide_banner", "-encoders"])
         .output();
     let mut names = HashSet::new();
@@ -237,12 +590,15 @@ pub fn normalize_video(
     path: &Path,
     output_dir: &Path,
     encoder_chain: &[&EncoderSpec],
+    settings: &EncodeSettings,
+    on_progress: OnEncodeProgress,
 ) -> Result<NormalizationResult> {
     ensure_dir(output_dir)?;
     let source = PathBuf::from(path);
     let normalized = output_dir.join(format!(
-        "{}-normalized.mp4",
-        source.file_stem().unwrap_or_default().to_string_lossy()
+        "{}-normalized{}.mp4",
+        source.file_stem().unwrap_or_default().to_string_lossy(),
+        settings.file_tag()
     ));
 
     if normalized.exists() && normalized.metadata()?.modified()? >= path.metadata()?.modified()? {
@@ -257,11 +613,23 @@ pub fn normalize_video(
         encoder_chain.to_vec()
     };
 
+    // The length drives progress; height and frame rate drive the caps.
+    let metadata = probe_video(path).ok();
+    let total_seconds = metadata.as_ref().map_or(0.0, |meta| meta.duration_seconds);
+    if settings.skip_when_compatible
+        && remux_if_compatible(path, &normalized, settings, total_seconds, on_progress)?
+    {
+        return Ok(NormalizationResult { path: normalized });
+    }
+    let filters = settings.filters(metadata.as_ref());
+
     let mut last_err: Option<anyhow::Error> = None;
     for spec in chain {
-        let mut cmd = Command::new("ffmpeg");
-        cmd.args(["-y", "-i", path.to_str().unwrap()]);
-        cmd.args(spec.args);
+        let mut cmd = ffmpeg_with_progress();
+        cmd.arg("-y");
+        cmd.args(settings.decode_args());
+        cmd.args(["-i", path.to_str().unwrap()]);
+        cmd.args(settings.video_args(spec, &filters));
         cmd.args([
             "-pix_fmt",
             "yuv420p",
@@ -270,10 +638,10 @@ pub fn normalize_video(
             "-c:a",
             "aac",
             "-b:a",
-            "192k",
+            settings.audio_bitrate(),
         ]);
         cmd.arg(normalized.to_str().unwrap());
-        match cmd.output() {
+        match output_with_progress(&mut cmd, total_seconds, on_progress) {
             Ok(output) if output.status.success() => {
                 return Ok(NormalizationResult { path: normalized });
             }
@@ -289,8 +657,183 @@ pub fn normalize_video(
     Err(last_err.unwrap_or_else(|| anyhow!("ffmpeg failed for {}", path.display())))
 }
 
+/// How far one ffmpeg pass has got, from its `-progress` output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncodeProgress {
+    pub done_seconds: f64,
+    /// Length of the input, or 0 when it could not be probed.
+    pub total_seconds: f64,
+    pub fps: Option<f64>,
+}
+
+/// Callback for [`EncodeProgress`] reports; shared by parallel chunk cuts.
+pub type OnEncodeProgress<'a> = &'a (dyn Fn(EncodeProgress) + Sync);
+
+/// An ffmpeg command that writes `-progress` reports to stdout for
+/// [`output_with_progress`]. Global options have to come before the inputs.
+fn ffmpeg_with_progress() -> Command {
+    let mut cmd = tools::command(Tool::Ffmpeg);
+    cmd.args(["-hide_banner", "-nostats", "-progress", "pipe:1"]);
+    cmd
+}
+
+/// Run `cmd`, passing each progress report to `on_progress`, and return its
+/// exit status and stderr like [`Command::output`].
+fn output_with_progress(
+    cmd: &mut Command,
+    total_seconds: f64,
+    on_progress: OnEncodeProgress,
+) -> std::io::Result<std::process::Output> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Drain stderr on its own thread so a chatty ffmpeg never blocks on a full pipe.
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let stderr_reader = std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let _ = std::io::Read::read_to_end(&mut stderr, &mut buffer);
+        buffer
+    });
+    if let Some(stdout) = child.stdout.take() {
+        read_progress(BufReader::new(stdout), total_seconds, on_progress);
+    }
+    let status = child.wait()?;
+    Ok(std::process::Output {
+        status,
+        stdout: Vec::new(),
+        stderr: stderr_reader.join().unwrap_or_default(),
+    })
+}
+
+/// Parse ffmpeg's `key=value` progress blocks, each ended by a `progress=` line.
+fn read_progress(reader: impl BufRead, total_seconds: f64, on_progress: OnEncodeProgress) {
+    let mut done_seconds = 0.0;
+    let mut fps = None;
+    for line in reader.lines().map_while(Result::ok) {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        match key {
+            // Microseconds despite the name of the older `out_time_ms` key.
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(micros) = value.parse::<i64>() {
+                    done_seconds = micros.max(0) as f64 / 1_000_000.0;
+                }
+            }
+            "fps" => fps = value.parse::<f64>().ok().filter(|fps| *fps > 0.0),
+            "progress" => on_progress(EncodeProgress {
+                done_seconds: match total_seconds > 0.0 {
+                    true => done_seconds.min(total_seconds),
+                    false => done_seconds,
+                },
+                total_seconds,
+                fps,
+            }),
+            _ => {}
+        }
+    }
+}
+
+/// Copy `path` into `dest` without re-encoding when its streams already are what
+/// [`normalize_video`] would produce. Returns whether it did.
+fn remux_if_compatible(
+    path: &Path,
+    dest: &Path,
+    settings: &EncodeSettings,
+    total_seconds: f64,
+    on_progress: OnEncodeProgress,
+) -> Result<bool> {
+    let output = tools::command(Tool::Ffprobe)
+        .args(["-v", "error", "-print_format", "json", "-show_streams"])
+        .arg(path)
+        .output()
+        .context("ffprobe invocation failed")?;
+    if !output.status.success() {
+        return Ok(false);
+    }
+    let probe: Value = serde_json::from_slice(&output.stdout)?;
+    if !settings.is_compatible(&probe) {
+        return Ok(false);
+    }
+    let output = output_with_progress(
+        ffmpeg_with_progress()
+            .args(["-y", "-i"])
+            .arg(path)
+            .args([
+                "-map",
+                "0:v:0",
+                "-map",
+                "0:a:0?",
+                "-c",
+                "copy",
+                "-movflags",
+                "+faststart",
+            ])
+            .arg(dest),
+        total_seconds,
+        on_progress,
+    )
+    .context("ffmpeg invocation failed")?;
+    // A failed remux falls back to re-encoding; drop the partial file so it is
+    // not mistaken for a finished one next run.
+    if !output.status.success() {
+        let _ = fs::remove_file(dest);
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Re-encode an audio (or audio track of a video) file to mono 16 kHz AAC.
+pub fn normalize_audio(
+    path: &Path,
+    output_dir: &Path,
+    on_progress: OnEncodeProgress,
+) -> Result<NormalizationResult> {
+    ensure_dir(output_dir)?;
+    let normalized = output_dir.join(format!(
+        "{}-normalized.m4a",
+        path.file_stem().unwrap_or_default().to_string_lossy()
+    ));
+
+    if normalized.exists() && normalized.metadata()?.modified()? >= path.metadata()?.modified()? {
+        probe_video(&normalized)?;
+        return Ok(NormalizationResult { path: normalized });
+    }
+
+    let total_seconds = probe_video(path).map_or(0.0, |meta| meta.duration_seconds);
+    let output = output_with_progress(
+        ffmpeg_with_progress().args([
+            "-y",
+            "-i",
+            path.to_str().unwrap(),
+            "-vn",
+            "-ac",
+            "1",
+            "-ar",
+            "16000",
+            "-c:a",
+            "aac",
+            "-b:a",
+            "64k",
+            "-movflags",
+            "+faststart",
+            normalized.to_str().unwrap(),
+        ]),
+        total_seconds,
+        on_progress,
+    )
+    .context("ffmpeg invocation failed")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("ffmpeg failed (aac) {}", stderr);
+    }
+    Ok(NormalizationResult { path: normalized })
+}
+
 pub fn probe_video(path: &Path) -> Result<VideoMetadata> {
-    let output = Command::new("ffprobe")
+    let output = tools::command(Tool::Ffprobe)
         .args([
             "-v",
             "error",
@@ -387,6 +930,57 @@ fn parse_rate(rate: &str) -> Option<f64> {
     rate.parse().ok()
 }
 
+/// Input tokens per second of media as counted by `countTokens`, kept in
+/// `<cache>/recapit/token-rates.json` so later runs plan chunks from measured rates
+/// instead of `video.tokens_per_second`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TokenRates {
+    rates: HashMap<String, f64>,
+}
+
+static TOKEN_RATES_LOCK: Mutex<()> = Mutex::new(());
+
+fn token_rates_path() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("recapit")
+        .join("token-rates.json")
+}
+
+impl TokenRates {
+    pub fn load() -> Self {
+        fs::read_to_string(token_rates_path())
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    /// The measured rate for `media` (`video` or `audio`) at a media resolution.
+    pub fn get(&self, media: &str, resolution: &str) -> Option<f64> {
+        self.rates.get(&format!("{media}/{resolution}")).copied()
+    }
+
+    /// Fold a new measurement into the stored rate, averaging it with earlier ones.
+    pub fn record(media: &str, resolution: &str, rate: f64) -> Result<()> {
+        if !rate.is_finite() || rate <= 0.0 {
+            return Ok(());
+        }
+        let _guard = TOKEN_RATES_LOCK.lock().unwrap();
+        let mut rates = Self::load();
+        rates
+            .rates
+            .entry(format!("{media}/{resolution}"))
+            .and_modify(|stored| *stored = (*stored + rate) / 2.0)
+            .or_insert(rate);
+        let path = token_rates_path();
+        ensure_dir(path.parent().unwrap())?;
+        fs::write(&path, serde_json::to_string_pretty(&rates)?)
+            .with_context(|| format!("writing {}", path.display()))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn plan_video_chunks(
     metadata: &VideoMetadata,
     normalized_path: &Path,
@@ -396,14 +990,38 @@ pub fn plan_video_chunks(
     tokens_per_second: f64,
     chunk_dir: &Path,
     max_workers: usize,
+    boundaries: ChunkBoundaries,
+    on_progress: OnEncodeProgress,
 ) -> Result<VideoChunkPlan> {
-    let bounds = compute_chunk_boundaries(
+    let mut bounds = compute_chunk_boundaries(
         metadata,
         max_seconds,
         max_bytes,
         token_limit,
         tokens_per_second,
+        &[],
+        0.0,
     );
+    // Only scan for breaks when the recording actually needs splitting.
+    if bounds.len() > 1 && boundaries.mode != ChunkBoundaryMode::Fixed {
+        match detect_breaks(normalized_path, boundaries.mode) {
+            Ok(breaks) => {
+                bounds = compute_chunk_boundaries(
+                    metadata,
+                    max_seconds,
+                    max_bytes,
+                    token_limit,
+                    tokens_per_second,
+                    &breaks,
+                    boundaries.tolerance_seconds,
+                );
+            }
+            Err(err) => tracing::warn!(
+                "{} detection failed; using fixed chunk boundaries: {err:#}",
+                boundaries.mode.as_str()
+            ),
+        }
+    }
     if bounds.len() == 1 {
         return Ok(VideoChunkPlan {
             metadata: metadata.clone(),
@@ -413,6 +1031,7 @@ pub fn plan_video_chunks(
                 start_seconds: bounds[0].0,
                 end_seconds: bounds[0].1,
                 path: normalized_path.to_path_buf(),
+                actual_seconds: Some(metadata.duration_seconds),
             }],
         });
     }
@@ -424,19 +1043,43 @@ pub fn plan_video_chunks(
         .unwrap_or_default()
         .to_string_lossy()
         .to_string();
+    let extension = normalized_path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string())
+        .unwrap_or_else(|| "mp4".into());
 
+    // Chunks finish out of order in parallel, so report the total cut so far.
+    let cut_millis = AtomicU64::new(0);
+    let total_seconds = metadata.duration_seconds;
+    let report_cut = |seconds: f64| {
+        let millis = (seconds * 1000.0) as u64;
+        let done = (cut_millis.fetch_add(millis, Ordering::Relaxed) + millis) as f64 / 1000.0;
+        on_progress(EncodeProgress {
+            done_seconds: done.min(total_seconds),
+            total_seconds,
+            fps: None,
+        });
+    };
     let chunks: Vec<VideoChunk> = if worker_count <= 1 {
         bounds
             .iter()
             .enumerate()
             .map(|(idx, (start, end))| {
-                let chunk_path = chunk_dir.join(format!("{stem}-chunk{idx:02}.mp4"));
-                extract_segment(normalized_path, &chunk_path, *start, *end)?;
+                let chunk_path = chunk_dir.join(format!("{stem}-chunk{idx:02}.{extension}"));
+                let actual_seconds = extract_segment(
+                    normalized_path,
+                    &chunk_path,
+                    *start,
+                    *end,
+                    boundaries.extraction,
+                )?;
+                report_cut(*end - *start);
                 Ok(VideoChunk {
                     index: idx,
                     start_seconds: *start,
                     end_seconds: *end,
                     path: chunk_path,
+                    actual_seconds,
                 })
             })
             .collect::<Result<Vec<_>>>()?
@@ -447,13 +1090,21 @@ pub fn plan_video_chunks(
                 .par_iter()
                 .enumerate()
                 .map(|(idx, (start, end))| {
-                    let chunk_path = chunk_dir.join(format!("{stem}-chunk{idx:02}.mp4"));
-                    extract_segment(normalized_path, &chunk_path, *start, *end)?;
+                    let chunk_path = chunk_dir.join(format!("{stem}-chunk{idx:02}.{extension}"));
+                    let actual_seconds = extract_segment(
+                        normalized_path,
+                        &chunk_path,
+                        *start,
+                        *end,
+                        boundaries.extraction,
+                    )?;
+                    report_cut(*end - *start);
                     Ok(VideoChunk {
                         index: idx,
                         start_seconds: *start,
                         end_seconds: *end,
                         path: chunk_path,
+                        actual_seconds,
                     })
                 })
                 .collect::<Result<Vec<_>>>()
@@ -467,12 +1118,17 @@ pub fn plan_video_chunks(
     })
 }
 
+/// Fixed-length cuts, each pulled back to the latest of `breaks` within `tolerance`
+/// seconds before it (but never below half a chunk). Cuts only move earlier, so no
+/// chunk exceeds the limits.
 fn compute_chunk_boundaries(
     metadata: &VideoMetadata,
     max_seconds: f64,
     max_bytes: u64,
     token_limit: Option<u32>,
     tokens_per_second: f64,
+    breaks: &[f64],
+    tolerance: f64,
 ) -> Vec<(f64, f64)> {
     let duration = metadata.duration_seconds.max(0.0);
     if duration <= f64::EPSILON {
@@ -502,7 +1158,18 @@ fn compute_chunk_boundaries(
     let mut start = 0.0;
     let mut bounds = Vec::new();
     while start < duration {
-        let end = (start + effective).min(duration);
+        let mut end = (start + effective).min(duration);
+        if end < duration {
+            let earliest = (end - tolerance).max(start + effective / 2.0);
+            if let Some(snapped) = breaks
+                .iter()
+                .copied()
+                .filter(|point| *point >= earliest && *point <= end)
+                .reduce(f64::max)
+            {
+                end = snapped;
+            }
+        }
         bounds.push((start, end));
         start = end;
     }
@@ -512,32 +1179,455 @@ fn compute_chunk_boundaries(
     bounds
 }
 
-fn extract_segment(source: &Path, dest: &Path, start: f64, end: f64) -> Result<()> {
-    if dest.exists()
+/// Silence midpoints or scene-change timestamps, in seconds, found by an ffmpeg pass.
+fn detect_breaks(path: &Path, mode: ChunkBoundaryMode) -> Result<Vec<f64>> {
+    let filter_args: &[&str] = match mode {
+        ChunkBoundaryMode::Fixed => return Ok(Vec::new()),
+        ChunkBoundaryMode::Silence => &["-vn", "-af", "silencedetect=noise=-35dB:d=0.5"],
+        ChunkBoundaryMode::Scene => &["-an", "-vf", "select='gt(scene,0.4)',showinfo"],
+    };
+    let output = tools::command(Tool::Ffmpeg)
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(path)
+        .args(filter_args)
+        .args(["-f", "null", "-"])
+        .output()
+        .context("ffmpeg invocation failed")?;
+    if !output.status.success() {
+        bail!(
+            "ffmpeg failed ({}) {}",
+            mode.as_str(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(parse_breaks(&String::from_utf8_lossy(&output.stderr), mode))
+}
+
+fn parse_breaks(log: &str, mode: ChunkBoundaryMode) -> Vec<f64> {
+    let number_after = |line: &str, key: &str| -> Option<f64> {
+        let rest = &line[line.find(key)? + key.len()..];
+        rest.trim_start()
+            .split(|c: char| c.is_whitespace() || c == '|')
+            .next()?
+            .parse()
+            .ok()
+    };
+    let mut breaks = Vec::new();
+    let mut silence_start = None;
+    for line in log.lines() {
+        match mode {
+            ChunkBoundaryMode::Silence => {
+                if let Some(start) = number_after(line, "silence_start:") {
+                    silence_start = Some(start);
+                } else if let Some(end) = number_after(line, "silence_end:") {
+                    let start = silence_start.take().unwrap_or(end);
+                    breaks.push((start.max(0.0) + end) / 2.0);
+                }
+            }
+            ChunkBoundaryMode::Scene => {
+                if line.contains("Parsed_showinfo") {
+                    breaks.extend(number_after(line, "pts_time:"));
+                }
+            }
+            ChunkBoundaryMode::Fixed => {}
+        }
+    }
+    breaks
+}
+
+/// Cut the silences `settings` describes out of `path`, writing
+/// `<stem>-condensed.<ext>` and its [`TimeMap`] into `output_dir`.
+///
+/// Returns `None` when nothing is long enough to cut. Audio-only inputs are
+/// re-encoded like [`normalize_audio`]; videos with the first encoder in
+/// `encoder_chain` that works.
+pub fn remove_silence(
+    path: &Path,
+    output_dir: &Path,
+    settings: &SilenceRemoval,
+    audio_only: bool,
+    encoder_chain: &[&EncoderSpec],
+    encode: &EncodeSettings,
+    on_progress: OnEncodeProgress,
+) -> Result<Option<(NormalizationResult, TimeMap)>> {
+    ensure_dir(output_dir)?;
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = if audio_only { "m4a" } else { "mp4" };
+    let condensed = output_dir.join(format!("{stem}-condensed.{ext}"));
+    let map_path = output_dir.join(format!("{stem}-condensed.json"));
+    let source_modified = path.metadata()?.modified()?;
+    let fresh = |file: &Path| {
+        file.metadata()
+            .and_then(|meta| meta.modified())
+            .is_ok_and(|modified| modified >= source_modified)
+    };
+    if fresh(&map_path) {
+        if let Ok(map) = serde_json::from_str::<Option<TimeMap>>(&fs::read_to_string(&map_path)?) {
+            match map {
+                None => return Ok(None),
+                Some(map) if fresh(&condensed) => {
+                    return Ok(Some((NormalizationResult { path: condensed }, map)));
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    let duration = probe_video(path)?.duration_seconds;
+    let output = output_with_progress(
+        ffmpeg_with_progress().arg("-i").arg(path).args([
+            "-vn",
+            "-af",
+            &format!(
+                "silencedetect=noise={}dB:d={}",
+                settings.threshold_db, settings.min_seconds
+            ),
+            "-f",
+            "null",
+            "-",
+        ]),
+        duration,
+        on_progress,
+    )
+    .context("ffmpeg invocation failed")?;
+    if !output.status.success() {
+        bail!(
+            "ffmpeg failed (silencedetect) {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    let silences = parse_silences(&String::from_utf8_lossy(&output.stderr), duration);
+    let kept = kept_spans(&silences, duration, settings);
+    let removed = duration - kept.iter().map(|(from, to)| to - from).sum::<f64>();
+    // An all-silent recording is left alone rather than cut to nothing.
+    if kept.is_empty() || removed < 0.5 {
+        fs::write(&map_path, "null")?;
+        return Ok(None);
+    }
+
+    let select = kept
+        .iter()
+        .map(|(from, to)| format!("between(t,{from:.3},{to:.3})"))
+        .collect::<Vec<_>>()
+        .join("+");
+    let audio_filter = format!("aselect='{select}',asetpts=N/SR/TB");
+    let attempts: Vec<Vec<String>> = if audio_only {
+        vec![[
+            "-vn",
+            "-af",
+            &audio_filter,
+            "-ac",
+            "1",
+            "-ar",
+            "16000",
+            "-c:a",
+            "aac",
+            "-b:a",
+            "64k",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect()]
+    } else {
+        let video_filter = format!("select='{select}',setpts=N/FRAME_RATE/TB");
+        let chain = if encoder_chain.is_empty() {
+            vec![encoder_spec(VideoEncoderPreference::Cpu)
+                .ok_or_else(|| anyhow!("No CPU encoder spec available"))?]
+        } else {
+            encoder_chain.to_vec()
+        };
+        chain
+            .iter()
+            .map(|spec| {
+                let mut args = encode.video_args(spec, std::slice::from_ref(&video_filter));
+                args.extend(
+                    [
+                        "-af",
+                        &audio_filter,
+                        "-pix_fmt",
+                        "yuv420p",
+                        "-c:a",
+                        "aac",
+                        "-b:a",
+                        encode.audio_bitrate(),
+                    ]
+                    .iter()
+                    .map(|arg| arg.to_string()),
+                );
+                args
+            })
+            .collect()
+    };
+
+    let mut last_err = None;
+    for args in attempts {
+        // Progress counts output time, which ends at the condensed length.
+        let output = output_with_progress(
+            ffmpeg_with_progress()
+                .args(["-y", "-i"])
+                .arg(path)
+                .args(&args)
+                .args(["-movflags", "+faststart"])
+                .arg(&condensed),
+            kept.iter().map(|(start, end)| end - start).sum(),
+            on_progress,
+        )
+        .context("ffmpeg invocation failed")?;
+        if output.status.success() {
+            let map = TimeMap::from_kept(&kept);
+            fs::write(&map_path, serde_json::to_string(&Some(&map))?)?;
+            return Ok(Some((NormalizationResult { path: condensed }, map)));
+        }
+        last_err = Some(anyhow!(
+            "ffmpeg failed (silence removal) {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Err(last_err.unwrap_or_else(|| anyhow!("ffmpeg failed for {}", path.display())))
+}
+
+/// `(start, end)` of each silence in an ffmpeg `silencedetect` log; a silence
+/// still open at the end of the log runs to `duration`.
+fn parse_silences(log: &str, duration: f64) -> Vec<(f64, f64)> {
+    let number_after = |line: &str, key: &str| -> Option<f64> {
+        let rest = &line[line.find(key)? + key.len()..];
+        rest.trim_start()
+            .split(|c: char| c.is_whitespace() || c == '|')
+            .next()?
+            .parse()
+            .ok()
+    };
+    let mut silences = Vec::new();
+    let mut open = None;
+    for line in log.lines() {
+        if let Some(start) = number_after(line, "silence_start:") {
+            open = Some(start.max(0.0));
+        } else if let Some(end) = number_after(line, "silence_end:") {
+            silences.push((open.take().unwrap_or(end), end));
+        }
+    }
+    if let Some(start) = open {
+        silences.push((start, duration));
+    }
+    silences
+}
+
+/// The stretches of a `duration`-second recording left after cutting each
+/// silence of at least `min_seconds`, minus `padding_seconds` on either side.
+/// An empty result means the whole recording is silent.
+fn kept_spans(
+    silences: &[(f64, f64)],
+    duration: f64,
+    settings: &SilenceRemoval,
+) -> Vec<(f64, f64)> {
+    let mut kept = Vec::new();
+    let mut from = 0.0;
+    for &(start, end) in silences {
+        if end - start < settings.min_seconds {
+            continue;
+        }
+        // Nothing is spoken before the start or after the end, so no padding there.
+        let cut_start = if start <= 0.0 {
+            0.0
+        } else {
+            start + settings.padding_seconds
+        }
+        .max(from);
+        let cut_end = if end >= duration {
+            duration
+        } else {
+            end - settings.padding_seconds
+        };
+        if cut_end <= cut_start {
+            continue;
+        }
+        if cut_start > from {
+            kept.push((from, cut_start));
+        }
+        from = cut_end;
+    }
+    if duration > from {
+        kept.push((from, duration));
+    }
+    kept
+}
+
+/// Cut `start..end` of `source` into `dest` and return the duration ffprobe
+/// reports for it, warning when that strays from the planned length.
+fn extract_segment(
+    source: &Path,
+    dest: &Path,
+    start: f64,
+    end: f64,
+    extraction: ChunkExtraction,
+) -> Result<Option<f64>> {
+    let fresh = dest.exists()
         && dest.metadata()?.modified()? >= source.metadata()?.modified()?
-        && dest.metadata()?.len() > 0
-    {
-        return Ok(());
+        && dest.metadata()?.len() > 0;
+    if !fresh {
+        ensure_dir(dest.parent().unwrap())?;
+        let status = tools::command(Tool::Ffmpeg)
+            .args(segment_args(source, dest, start, end, extraction))
+            .status()?;
+        if !status.success() {
+            bail!("ffmpeg failed while extracting segment");
+        }
+    }
+    let actual = probe_video(dest).ok().map(|meta| meta.duration_seconds);
+    if let Some(actual) = actual {
+        let planned = end - start;
+        if (actual - planned).abs() > (planned * 0.02).max(1.0) {
+            tracing::warn!(
+                "chunk {} is {actual:.1}s long, planned {planned:.1}s; set video.chunk_extraction: accurate for exact cuts",
+                dest.display()
+            );
+        }
     }
-    ensure_dir(dest.parent().unwrap())?;
-    let status = Command::new("ffmpeg")
+    Ok(actual)
+}
+
+/// ffmpeg arguments for [`extract_segment`]. Seeking before `-i` starts from
+/// the nearest keyframe; with `Copy` that keyframe is where the chunk begins,
+/// while `Accurate` decodes from it and drops frames up to `start`. Audio-only
+/// sources are always copied, since AAC frames are a few milliseconds long.
+fn segment_args(
+    source: &Path,
+    dest: &Path,
+    start: f64,
+    end: f64,
+    extraction: ChunkExtraction,
+) -> Vec<String> {
+    let mut args: Vec<String> = vec![
+        "-y".into(),
+        "-ss".into(),
+        format!("{start:.3}"),
+        "-i".into(),
+        source.to_string_lossy().into_owned(),
+        "-t".into(),
+        format!("{:.3}", (end - start).max(0.0)),
+    ];
+    let audio_only = dest
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("m4a"));
+    match extraction {
+        ChunkExtraction::Accurate if !audio_only => args.extend(
+            [
+                "-c:v",
+                "libx264",
+                "-preset",
+                "veryfast",
+                "-crf",
+                "18",
+                "-pix_fmt",
+                "yuv420p",
+                "-c:a",
+                "aac",
+                "-b:a",
+                "192k",
+                "-movflags",
+                "+faststart",
+            ]
+            .map(String::from),
+        ),
+        _ => args.extend(["-c", "copy", "-avoid_negative_ts", "make_zero"].map(String::from)),
+    }
+    args.push(dest.to_string_lossy().into_owned());
+    args
+}
+
+/// Sample the video every `SLIDE_SAMPLE_SECONDS`, keep frames that differ from the
+/// previous slide and hold for at least two samples, and write each as a PNG.
+pub fn extract_slides(video: &Path, output_dir: &Path) -> Result<Vec<SlideFrame>> {
+    ensure_dir(output_dir)?;
+    // 9x8 grayscale thumbnails are exactly what a 64-bit difference hash needs.
+    let output = tools::command(Tool::Ffmpeg)
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(video)
         .args([
-            "-y",
-            "-i",
-            source.to_str().unwrap(),
-            "-ss",
-            &format!("{start:.3}"),
-            "-to",
-            &format!("{end:.3}"),
-            "-c",
-            "copy",
-            dest.to_str().unwrap(),
+            "-an",
+            "-vf",
+            &format!("fps=1/{SLIDE_SAMPLE_SECONDS},scale=9:8,format=gray"),
+            "-f",
+            "rawvideo",
+            "-",
         ])
-        .status()?;
-    if !status.success() {
-        bail!("ffmpeg failed while extracting segment");
+        .output()
+        .context("ffmpeg invocation failed")?;
+    if !output.status.success() {
+        bail!(
+            "ffmpeg failed (slide sampling) {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    let hashes: Vec<u64> = output.stdout.chunks_exact(72).map(dhash).collect();
+
+    let source_modified = video.metadata()?.modified()?;
+    let mut slides = Vec::new();
+    for (index, frame) in distinct_frames(&hashes, SLIDE_HASH_THRESHOLD)
+        .into_iter()
+        .enumerate()
+    {
+        let seconds = frame as f64 * SLIDE_SAMPLE_SECONDS;
+        let path = output_dir.join(format!("slide{index:03}.png"));
+        let fresh = path
+            .metadata()
+            .and_then(|meta| meta.modified())
+            .is_ok_and(|modified| modified >= source_modified);
+        if !fresh {
+            let status = tools::command(Tool::Ffmpeg)
+                .args([
+                    "-y",
+                    "-loglevel",
+                    "error",
+                    "-ss",
+                    &format!("{seconds:.3}"),
+                    "-i",
+                ])
+                .arg(video)
+                .args(["-frames:v", "1"])
+                .arg(&path)
+                .status()
+                .context("ffmpeg invocation failed")?;
+            if !status.success() {
+                bail!("ffmpeg failed while extracting slide at {seconds:.1}s");
+            }
+        }
+        slides.push(SlideFrame {
+            index,
+            seconds,
+            path,
+        });
     }
-    Ok(())
+    Ok(slides)
+}
+
+/// Difference hash of a 9x8 grayscale frame: one bit per horizontally adjacent pair.
+fn dhash(pixels: &[u8]) -> u64 {
+    let mut hash = 0u64;
+    for row in pixels.chunks_exact(9) {
+        for pair in row.windows(2) {
+            hash = (hash << 1) | u64::from(pair[0] > pair[1]);
+        }
+    }
+    hash
+}
+
+/// Indexes of frames that start a new slide: far from the last kept slide, and
+/// still close to the next sample so transitions and motion are skipped.
+fn distinct_frames(hashes: &[u64], threshold: u32) -> Vec<usize> {
+    let mut kept: Vec<usize> = Vec::new();
+    for (idx, hash) in hashes.iter().enumerate() {
+        let new = kept
+            .last()
+            .is_none_or(|last| (hashes[*last] ^ hash).count_ones() > threshold);
+        let stable = hashes
+            .get(idx + 1)
+            .is_none_or(|next| (next ^ hash).count_ones() <= threshold);
+        if new && stable {
+            kept.push(idx);
+        }
+    }
+    kept
 }
 
 pub fn sha256sum(path: &Path) -> Result<String> {
@@ -555,3 +1645,191 @@ pub fn seconds_to_iso(value: f64) -> String {
     let seconds = total_seconds % 60;
     format!("PT{}H{}M{}S", hours, minutes, seconds)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_settings_build_encoder_arguments() {
+        let settings = EncodeSettings {
+            crf: Some(28),
+            max_height: Some(720),
+            max_fps: Some(15.0),
+            ..EncodeSettings::default()
+        };
+        let meta = VideoMetadata {
+            path: PathBuf::from("lecture.mp4"),
+            duration_seconds: 60.0,
+            size_bytes: 0,
+            fps: Some(30.0),
+            width: Some(3840),
+            height: Some(2160),
+            video_codec: None,
+            audio_codec: None,
+            audio_sample_rate: None,
+        };
+        let filters = settings.filters(Some(&meta));
+        assert_eq!(filters, ["scale=-2:720", "fps=15"]);
+
+        let x264 = encoder_spec(VideoEncoderPreference::Cpu).unwrap();
+        let args = settings.video_args(x264, &filters);
+        assert_eq!(args[..2], ["-vf", "scale=-2:720,fps=15"]);
+        assert_eq!(args[args.len() - 2..], ["-crf", "28"]);
+
+        let vaapi = encoder_spec(VideoEncoderPreference::Vaapi).unwrap();
+        let args = settings.video_args(vaapi, &filters);
+        assert_eq!(
+            args[..2],
+            ["-vf", "scale=-2:720,fps=15,format=nv12,hwupload"]
+        );
+
+        let small = VideoMetadata {
+            height: Some(480),
+            fps: Some(10.0),
+            ..meta
+        };
+        assert!(settings.filters(Some(&small)).is_empty());
+        assert_eq!(EncodeSettings::default().file_tag(), "");
+        assert_ne!(settings.file_tag(), "");
+    }
+
+    #[test]
+    fn ffmpeg_progress_blocks_are_reported() {
+        let output = "frame=120\nfps=59.94\nout_time_us=4000000\nout_time=00:00:04.000000\nspeed=2.0x\nprogress=continue\n\
+                      frame=300\nfps=0.00\nout_time_us=N/A\nprogress=continue\n\
+                      out_time_us=12500000\nprogress=end\n";
+        let reports = Mutex::new(Vec::new());
+        read_progress(std::io::Cursor::new(output), 10.0, &|progress| {
+            reports.lock().unwrap().push(progress)
+        });
+        let reports = reports.into_inner().unwrap();
+        assert_eq!(reports.len(), 3);
+        assert_eq!(reports[0].done_seconds, 4.0);
+        assert_eq!(reports[0].fps, Some(59.94));
+        // N/A keeps the last time; a zero rate is no rate.
+        assert_eq!(reports[1].done_seconds, 4.0);
+        assert_eq!(reports[1].fps, None);
+        assert_eq!(reports[2].done_seconds, 10.0);
+    }
+
+    #[test]
+    fn segments_seek_before_input() {
+        let args = segment_args(
+            Path::new("lecture.mp4"),
+            Path::new("chunk00.mp4"),
+            600.0,
+            1200.5,
+            ChunkExtraction::Copy,
+        );
+        assert_eq!(args[1..5], ["-ss", "600.000", "-i", "lecture.mp4"]);
+        assert_eq!(args[5..7], ["-t", "600.500"]);
+        assert!(args.contains(&"copy".to_string()));
+
+        let args = segment_args(
+            Path::new("lecture.mp4"),
+            Path::new("chunk00.mp4"),
+            600.0,
+            1200.5,
+            ChunkExtraction::Accurate,
+        );
+        assert!(args.contains(&"libx264".to_string()));
+        let args = segment_args(
+            Path::new("lecture.m4a"),
+            Path::new("chunk00.m4a"),
+            600.0,
+            1200.5,
+            ChunkExtraction::Accurate,
+        );
+        assert!(!args.contains(&"libx264".to_string()));
+    }
+
+    #[test]
+    fn only_h264_aac_within_caps_is_copied() {
+        let probe = |codec: &str, pix_fmt: &str, audio: &str| {
+            serde_json::json!({"streams": [
+                {"codec_type": "video", "codec_name": codec, "pix_fmt": pix_fmt, "height": 1080, "avg_frame_rate": "30000/1001"},
+                {"codec_type": "audio", "codec_name": audio},
+            ]})
+        };
+        let settings = EncodeSettings {
+            skip_when_compatible: true,
+            ..EncodeSettings::default()
+        };
+        assert!(settings.is_compatible(&probe("h264", "yuv420p", "aac")));
+        assert!(!settings.is_compatible(&probe("hevc", "yuv420p", "aac")));
+        assert!(!settings.is_compatible(&probe("h264", "yuv420p10le", "aac")));
+        assert!(!settings.is_compatible(&probe("h264", "yuv420p", "opus")));
+        assert!(!settings.is_compatible(&serde_json::json!({"streams": []})));
+
+        let capped = EncodeSettings {
+            max_height: Some(720),
+            ..settings.clone()
+        };
+        assert!(!capped.is_compatible(&probe("h264", "yuv420p", "aac")));
+        let capped = EncodeSettings {
+            max_fps: Some(30.0),
+            ..settings
+        };
+        assert!(capped.is_compatible(&probe("h264", "yuv420p", "aac")));
+    }
+
+    #[test]
+    fn silence_cuts_map_back_to_source_times() {
+        let log = "[silencedetect @ 0x1] silence_start: 10\n\
+                   [silencedetect @ 0x1] silence_end: 20 | silence_duration: 10\n\
+                   [silencedetect @ 0x1] silence_start: 30\n\
+                   [silencedetect @ 0x1] silence_end: 31 | silence_duration: 1\n\
+                   [silencedetect @ 0x1] silence_start: 55\n";
+        let silences = parse_silences(log, 60.0);
+        assert_eq!(silences, vec![(10.0, 20.0), (30.0, 31.0), (55.0, 60.0)]);
+        let settings = SilenceRemoval {
+            enabled: true,
+            min_seconds: 2.0,
+            threshold_db: -35.0,
+            padding_seconds: 0.5,
+        };
+        let kept = kept_spans(&silences, 60.0, &settings);
+        assert_eq!(kept, vec![(0.0, 10.5), (19.5, 55.5)]);
+
+        let map = TimeMap::from_kept(&kept);
+        assert_eq!(map.to_source(5.0), 5.0);
+        assert_eq!(map.to_source(10.5), 19.5);
+        assert_eq!(map.to_source(20.5), 29.5);
+        assert_eq!(map.slice(0.0, 10.0).spans.len(), 1);
+        assert_eq!(map.slice(10.0, 20.0).spans.len(), 2);
+    }
+
+    #[test]
+    fn cuts_snap_back_to_breaks_within_tolerance() {
+        let metadata = VideoMetadata {
+            path: PathBuf::from("lecture.mp4"),
+            duration_seconds: 250.0,
+            size_bytes: 0,
+            fps: None,
+            width: None,
+            height: None,
+            video_codec: None,
+            audio_codec: None,
+            audio_sample_rate: None,
+        };
+        let log = "[silencedetect @ 0x1] silence_start: 90.5\n\
+                   [silencedetect @ 0x1] silence_end: 91.5 | silence_duration: 1\n\
+                   [silencedetect @ 0x1] silence_start: 150\n\
+                   [silencedetect @ 0x1] silence_end: 152 | silence_duration: 2\n";
+        let breaks = parse_breaks(log, ChunkBoundaryMode::Silence);
+        assert_eq!(breaks, vec![91.0, 151.0]);
+
+        let bounds = compute_chunk_boundaries(&metadata, 100.0, 0, None, 0.0, &breaks, 20.0);
+        assert_eq!(bounds, vec![(0.0, 91.0), (91.0, 191.0), (191.0, 250.0)]);
+    }
+
+    #[test]
+    fn slide_frames_skip_repeats_and_transitions() {
+        let slide_a = 0u64;
+        let slide_b = u64::MAX;
+        let blend = 0xFFFF_FFFF;
+        let hashes = [slide_a, slide_a, blend, slide_b, slide_b, slide_a];
+        assert_eq!(distinct_frames(&hashes, 10), vec![0, 3, 5]);
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde_json::json;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use walkdir::WalkDir;
+
+use crate::cli::{Cli, ProgressMode};
+use crate::config::AppConfig;
+use crate::ingest::is_supported_file;
+use crate::transcripts::TranscriptIndex;
+
+/// The watch log is rotated to `<name>.1` once it grows past this size.
+const LOG_ROTATE_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Working directories a run writes under its output folder.
+const WORK_DIRS: &[&str] = &["pdf-chunks", "manifests"];
+
+pub struct WatchOptions {
+    pub recursive: bool,
+    pub existing: bool,
+    pub settle: Duration,
+    pub log: Option<PathBuf>,
+}
+
+/// Transcribe supported files as they appear under `dir` until Ctrl+C.
+///
+/// Files are picked up once their size has stopped changing for `settle`, so copies and
+/// downloads in progress are not transcribed half-written. Sources with a transcript on
+/// record are skipped.
+pub async fn run_watch(cli: Cli, dir: &Path, options: WatchOptions) -> Result<()> {
+    let dir = dir
+        .canonicalize()
+        .with_context(|| format!("watching {}", dir.display()))?;
+    let log_path = options
+        .log
+        .clone()
+        .unwrap_or_else(|| dir.join(".recapit-watch.ndjson"));
+    let own = OwnFiles::new(&cli, &dir, &log_path);
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<PathBuf>();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                for path in event.paths {
+                    let _ = tx.send(path);
+                }
+            }
+        }
+    })?;
+    let mode = if options.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher.watch(&dir, mode)?;
+
+    let mut pending: HashMap<PathBuf, (u64, Instant)> = HashMap::new();
+    if options.existing {
+        let depth = if options.recursive { usize::MAX } else { 1 };
+        for entry in WalkDir::new(&dir)
+            .max_depth(depth)
+            .into_iter()
+            .filter_entry(|entry| !own.contains(entry.path()))
+            .filter_map(|entry| entry.ok())
+        {
+            track(&mut pending, entry.path());
+        }
+    }
+    if !cli.quiet {
+        println!(
+            "watching {} (log {}); Ctrl+C to stop",
+            dir.display(),
+            log_path.display()
+        );
+    }
+
+    let mut tick = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        tokio::select! {
+            Some(path) = rx.recv() => {
+                if !own.contains(&path) {
+                    track(&mut pending, &path);
+                }
+            }
+            _ = tick.tick() => {
+                for path in settled(&mut pending, options.settle) {
+                    process(&cli, &path, &log_path).await?;
+                }
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    Ok(())
+}
+
+/// What Recapit itself writes inside a watched folder, so a recursive watch does not
+/// transcribe its own page images, exports, and logs.
+struct OwnFiles {
+    /// The output folder, when it is somewhere other than the watched folder itself.
+    output_dir: Option<PathBuf>,
+    logs: Vec<PathBuf>,
+}
+
+impl OwnFiles {
+    fn new(cli: &Cli, dir: &Path, log_path: &Path) -> Self {
+        let cfg = AppConfig::load_with_profile(cli.config.as_deref(), cli.profile.as_deref()).ok();
+        let output_dir = cli
+            .output_dir
+            .clone()
+            .or_else(|| cfg.as_ref().and_then(|cfg| cfg.output_dir.clone()))
+            .map(|path| absolute(&path))
+            .filter(|path| path != dir);
+        let log_file = cli
+            .log_file
+            .clone()
+            .or_else(|| cfg.and_then(|cfg| cfg.log_file));
+        Self {
+            output_dir,
+            logs: [Some(log_path.to_path_buf()), log_file]
+                .into_iter()
+                .flatten()
+                .map(|path| absolute(&path))
+                .collect(),
+        }
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        let path = absolute(path);
+        if self.logs.contains(&path) {
+            return true;
+        }
+        if self
+            .output_dir
+            .as_ref()
+            .is_some_and(|output| path.starts_with(output))
+        {
+            return true;
+        }
+        path.components().any(|component| {
+            let name = component.as_os_str().to_string_lossy();
+            WORK_DIRS.contains(&name.as_ref())
+                || name.starts_with(".recapit-")
+                || name.ends_with("-transcribed")
+        })
+    }
+}
+
+/// `path` made absolute and, where it exists, with links resolved, so it compares
+/// against the paths the watcher reports.
+fn absolute(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    })
+}
+
+fn track(pending: &mut HashMap<PathBuf, (u64, Instant)>, path: &Path) {
+    let hidden = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+    if hidden || !path.is_file() || !is_supported_file(path) {
+        return;
+    }
+    let size = path.metadata().map(|meta| meta.len()).unwrap_or(0);
+    pending.insert(path.to_path_buf(), (size, Instant::now()));
+}
+
+/// Paths whose size has not changed for `settle`, removed from `pending`.
+fn settled(pending: &mut HashMap<PathBuf, (u64, Instant)>, settle: Duration) -> Vec<PathBuf> {
+    let mut ready = Vec::new();
+    pending.retain(|path, (size, since)| {
+        let Ok(meta) = path.metadata() else {
+            return false;
+        };
+        if meta.len() != *size {
+            *size = meta.len();
+            *since = Instant::now();
+            return true;
+        }
+        if since.elapsed() >= settle {
+            ready.push(path.clone());
+            return false;
+        }
+        true
+    });
+    ready.sort();
+    ready
+}
+
+async fn process(cli: &Cli, path: &Path, log_path: &Path) -> Result<()> {
+    let source = path.to_string_lossy().to_string();
+    if let Some(entry) = TranscriptIndex::load().lookup(&source) {
+        return log_result(
+            cli,
+            log_path,
+            json!({
+                "source": source,
+                "status": "skipped",
+                "output": entry.path,
+            }),
+        );
+    }
+
+    let mut job_cli = cli.clone();
+    job_cli.cmd = None;
+    job_cli.source = vec![source.clone()];
+    job_cli.quiet = true;
+    job_cli.progress = Some(ProgressMode::None);
+    let started = Instant::now();
+    let result = Box::pin(crate::app::run_primary(job_cli)).await;
+    let output = TranscriptIndex::load()
+        .lookup(&source)
+        .map(|entry| entry.path.clone());
+    let record = match result {
+        Ok(()) => json!({
+            "source": source,
+            "status": if output.is_some() { "ok" } else { "empty" },
+            "output": output,
+            "elapsed_seconds": started.elapsed().as_secs_f64(),
+        }),
+        Err(err) => json!({
+            "source": source,
+            "status": "error",
+            "error": format!("{err:#}"),
+            "elapsed_seconds": started.elapsed().as_secs_f64(),
+        }),
+    };
+    log_result(cli, log_path, record)
+}
+
+fn log_result(cli: &Cli, log_path: &Path, mut record: serde_json::Value) -> Result<()> {
+    record["timestamp"] = json!(OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_default());
+    if !cli.quiet {
+        println!(
+            "{} {}",
+            record["status"].as_str().unwrap_or_default(),
+            record["source"].as_str().unwrap_or_default()
+        );
+    }
+    if log_path
+        .metadata()
+        .is_ok_and(|meta| meta.len() > LOG_ROTATE_BYTES)
+    {
+        fs::rename(log_path, log_path.with_extension("ndjson.1"))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)
+        .with_context(|| format!("opening {}", log_path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(&record)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn own_outputs_and_logs_are_not_sources() {
+        let own = OwnFiles {
+            output_dir: Some(PathBuf::from("/watched/out")),
+            logs: vec![PathBuf::from("/watched/.recapit-watch.ndjson")],
+        };
+        assert!(own.contains(Path::new("/watched/out/lecture.pdf")));
+        assert!(own.contains(Path::new("/watched/talk/pdf-chunks/talk/page-1.png")));
+        assert!(own.contains(Path::new("/watched/.recapit-talk/slide.png")));
+        assert!(own.contains(Path::new("/watched/talk-transcribed/talk.pdf")));
+        assert!(own.contains(Path::new("/watched/.recapit-watch.ndjson")));
+        assert!(!own.contains(Path::new("/watched/week2/lecture.pdf")));
+    }
+}
@@ -0,0 +1,120 @@
+//! End-to-end runs of the engine over the mock stages in `recapit::testing`,
+//! compared against the files in `tests/golden`. Set `UPDATE_GOLDEN=1` to rewrite
+//! them after an intended change to prompts, writers, or exporters.
+
+use std::fs;
+use std::path::Path;
+
+use recapit::testing::{self, MockIngestor, MockNormalizer, MockProvider};
+use serde_json::json;
+
+fn assert_golden(actual: &Path, golden: &str) {
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(golden);
+    let actual = fs::read_to_string(actual).unwrap();
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&golden, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&golden)
+        .unwrap_or_else(|err| panic!("{}: {err}; run with UPDATE_GOLDEN=1", golden.display()));
+    assert_eq!(actual, expected, "{} changed", golden.display());
+}
+
+#[tokio::test]
+async fn document_is_transcribed_in_one_request() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = testing::config(dir.path()).unwrap();
+    let provider = MockProvider::scripted();
+    let ingestor = MockIngestor::new(vec![testing::asset("notes.pdf", "pdf", json!({}))]);
+    let (mut engine, _progress) = testing::engine(
+        ingestor,
+        MockNormalizer::passthrough(),
+        provider.clone(),
+        &config,
+    )
+    .unwrap();
+
+    let output = engine
+        .run(&testing::job("notes.pdf", dir.path()))
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(output, dir.path().join("notes-transcribed.md"));
+    let calls = provider.calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].modality, "pdf");
+    assert_golden(&output, "notes-transcribed.md");
+}
+
+#[tokio::test]
+async fn chunked_video_subtitles_follow_the_source_timeline() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = testing::config(dir.path()).unwrap();
+    let provider = MockProvider::scripted();
+    let ingestor = MockIngestor::new(vec![testing::asset(
+        "lecture.mp4",
+        "video",
+        json!({"duration_seconds": 75.0}),
+    )]);
+    let (mut engine, _progress) = testing::engine(
+        ingestor,
+        MockNormalizer::chunked(30.0),
+        provider.clone(),
+        &config,
+    )
+    .unwrap();
+    let mut job = testing::job("lecture.mp4", dir.path());
+    job.export = vec!["srt".into()];
+
+    let output = engine.run(&job).await.unwrap().unwrap();
+
+    let folder = dir.path().join("lecture-transcribed");
+    assert_eq!(output, folder.join("lecture-transcribed.md"));
+    let calls = provider.calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].modality, "video");
+    assert_eq!(calls[0].assets.len(), 3);
+    assert_golden(&output, "lecture-transcribed.md");
+    assert_golden(
+        &folder.join("lecture-transcribed.srt"),
+        "lecture-transcribed.srt",
+    );
+}
+
+#[tokio::test]
+async fn extract_only_stages_chunks_without_calling_the_provider() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = testing::config(dir.path()).unwrap();
+    let provider = MockProvider::scripted();
+    let ingestor = MockIngestor::new(vec![testing::asset(
+        "lecture.mp4",
+        "video",
+        json!({"duration_seconds": 75.0}),
+    )]);
+    let (engine, _progress) = testing::engine(
+        ingestor,
+        MockNormalizer::chunked(30.0),
+        provider.clone(),
+        &config,
+    )
+    .unwrap();
+    let mut engine = engine.with_extract_only(true);
+
+    let output = engine
+        .run(&testing::job("lecture.mp4", dir.path()))
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(output, dir.path().join("lecture-staged.json"));
+    assert!(provider.calls().is_empty());
+    assert!(!dir.path().join("lecture-transcribed.md").exists());
+    let staged: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+    assert_eq!(staged["modality"], "video");
+    assert_eq!(staged["normalized"].as_array().unwrap().len(), 3);
+    assert_eq!(staged["chunks"][2]["chunk_end_seconds"], 75.0);
+}